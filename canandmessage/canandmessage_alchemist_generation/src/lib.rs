@@ -99,12 +99,29 @@ fn generate_main_struct(dev: &Device) -> String {
                 DType::None => continue,
                 DType::Pad { width } => continue,
                 _ => {
+                    // muxed signals hold their last value while their multiplexor selects another
+                    let present = match &signal.muxed_by {
+                        Some(muxed_by) => format!(
+                            " // when {} is {}",
+                            muxed_by.selector,
+                            message
+                                .1
+                                .mux_groups()
+                                .iter()
+                                .filter(|group| group.signals.iter().any(|s| s.name == signal.name))
+                                .map(|group| group.name)
+                                .collect::<Vec<_>>()
+                                .join(" or ")
+                        ),
+                        None => String::new(),
+                    };
                     main_struct_name += &format!(
-                        "{}_{}: {} = {}\n",
+                        "{}_{}: {} = {}{}\n",
                         screaming_snake_to_camel(message.0),
                         signal.name,
                         type_from_dtype(&signal.dtype),
-                        make_default(&signal.dtype)
+                        make_default(&signal.dtype),
+                        present
                     );
                 }
             }
@@ -292,6 +309,7 @@ fn type_from_dtype(dtype: &DType) -> String {
     match dtype {
         DType::None => "".to_owned(),
         DType::UInt { meta } => "number".to_owned(),
+        DType::Reserved { width } => "number".to_owned(),
         DType::SInt { meta } => "number".to_owned(),
         DType::Buf { meta } => "number[]".to_owned(),
         DType::Float { meta } => "number".to_owned(),
@@ -306,6 +324,7 @@ fn type_from_dtype(dtype: &DType) -> String {
         DType::Struct { meta } => {
             format!("{}", screaming_snake_to_camel(&meta.name))
         }
+        DType::Array { meta } => format!("{}[]", type_from_dtype(&meta.dtype)),
     }
 }
 
@@ -315,6 +334,7 @@ fn make_default(dtype: &DType) -> String {
         DType::UInt { meta } => {
             format!("{};", meta.default_value)
         }
+        DType::Reserved { width } => "0;".to_owned(),
         DType::SInt { meta } => {
             format!("{};", meta.default_value)
         }
@@ -361,6 +381,13 @@ fn make_default(dtype: &DType) -> String {
         DType::Struct { meta } => {
             format!("new {}();", screaming_snake_to_camel(&meta.name))
         }
+        DType::Array { meta } => {
+            format!(
+                "Array.from({{ length: {} }}, () => {});",
+                meta.len,
+                make_default(&meta.dtype).trim_end_matches(';')
+            )
+        }
     }
 }
 
@@ -370,6 +397,7 @@ fn make_default_comparison(name: String, dtype: &DType) -> String {
         DType::UInt { meta } => {
             format!("{} === {}", name, meta.default_value)
         }
+        DType::Reserved { width } => format!("{} === 0", name),
         DType::SInt { meta } => {
             format!("{} === {}", name, meta.default_value)
         }
@@ -437,6 +465,13 @@ fn make_default_comparison(name: String, dtype: &DType) -> String {
             }
             outstr
         }
+        DType::Array { meta } => {
+            format!(
+                "{}.every((v) => {})",
+                name,
+                make_default_comparison("v".to_owned(), &meta.dtype)
+            )
+        }
     }
 }
 
@@ -3,6 +3,7 @@ use canandmessage_parser::{DType, Device, Setting, Signal, Source, StructMeta};
 use proc_macro2::{Literal, TokenStream};
 use quote::{format_ident, quote, ToTokens};
 
+use crate::message_generation;
 use crate::setting_generation::gen_default_settings_value;
 use crate::utils::{
     self, f_with_size, flatten_token_vec, gen_type_for_dtype, i_with_size, min_width, u8_buf,
@@ -94,8 +95,7 @@ pub fn gen_alchemist(device: &Device) -> proc_macro2::TokenStream {
          */
         let mut process_signals: Vec<proc_macro2::TokenStream> = message
             .1
-            .signals
-            .iter()
+            .plain_signals()
             .filter_map(|sig| {
                 let name = format_ident!("{}", sig.name);
                 match sig.dtype {
@@ -114,8 +114,7 @@ pub fn gen_alchemist(device: &Device) -> proc_macro2::TokenStream {
          */
         let mut process_signal_assign: Vec<proc_macro2::TokenStream> = message
             .1
-            .signals
-            .iter()
+            .plain_signals()
             .filter_map(|sig| match sig.dtype {
                 DType::Pad { width } => None,
                 _ => {
@@ -125,12 +124,28 @@ pub fn gen_alchemist(device: &Device) -> proc_macro2::TokenStream {
                         utils::screaming_snake_to_camel(message.0),
                         sig.name
                     );
-                    Some(quote!(
-                        self.#self_name = #name
-                    ))
+                    Some(match sig.mux {
+                        true => quote!(self.#self_name = #name.value()),
+                        false => quote!(self.#self_name = #name),
+                    })
                 }
             })
             .collect();
+        // muxed signals keep their last value while the multiplexor selects something else
+        process_signal_assign.extend(message_generation::gen_mux_destructure(
+            device,
+            message.0,
+            message.1,
+            |sig| {
+                let name = format_ident!("{}", sig.name);
+                let self_name = format_ident!(
+                    "{}_{}",
+                    utils::screaming_snake_to_camel(message.0),
+                    sig.name
+                );
+                quote!(self.#self_name = #name)
+            },
+        ));
 
         let msg_name = format_ident!("{}", utils::screaming_snake_to_camel(message.0));
 
@@ -451,6 +466,7 @@ pub fn gen_default_value(dev: &Device, dtype: &DType) -> Option<TokenStream> {
             Some(quote!(#dtype_name::from_bitfield(#val)))
         }
         DType::Pad { width } => None,
+        DType::Reserved { width } => Some(utils::uint_literal(0, *width)),
         DType::Bool { default_value } => Some(quote!(#default_value)),
         DType::Enum { meta } => {
             let dtype_name =
@@ -483,6 +499,11 @@ pub fn gen_default_value(dev: &Device, dtype: &DType) -> Option<TokenStream> {
                 }
             })
         }
+        DType::Array { meta } => {
+            let value = gen_default_value(dev, &meta.dtype)?;
+            let len = Literal::usize_unsuffixed(meta.len);
+            Some(quote!([#value; #len]))
+        }
     }
 }
 
@@ -6,10 +6,10 @@ use crate::bitset_generation::{gen_bitsets, gen_faults};
 use crate::enum_generation::gen_enums;
 use crate::message_generation::{
     gen_inbound_message_impl, gen_message_enum, gen_message_filters, gen_message_index_enum,
-    gen_outbound_message_impl,
+    gen_outbound_message_impl, gen_signal_scaling,
 };
 use crate::setting_generation::{
-    gen_default_settings_vec, gen_setting_enum,
+    gen_composite_settings, gen_default_settings_vec, gen_frame_periods, gen_setting_enum,
     gen_setting_enum_pack, gen_setting_enum_unpack,
 };
 use crate::simulation_generation::gen_simulation;
@@ -28,12 +28,15 @@ pub fn gen_device(
     let msg_enum = gen_message_enum(device);
     let msg_index = gen_message_index_enum(device);
     let msg_filters = gen_message_filters(device);
+    let signal_scaling = gen_signal_scaling(device);
     let unpack = gen_inbound_message_impl(device, tgt_source);
     let repack = gen_outbound_message_impl(device, tgt_source.flip());
     let setting_enum = gen_setting_enum(device);
     let setting_enum_unpack = gen_setting_enum_unpack(device);
     let setting_enum_pack = gen_setting_enum_pack(device);
     let setting_default = gen_default_settings_vec(device);
+    let composite_settings = gen_composite_settings(device);
+    let frame_periods = gen_frame_periods(device);
     let faults = gen_faults(device);
 
     gen_device_info(device, mod_vec);
@@ -52,6 +55,7 @@ pub fn gen_device(
         #msg_enum
         #msg_index
         #msg_filters
+        #signal_scaling
 
         #unpack
         #repack
@@ -60,6 +64,8 @@ pub fn gen_device(
         #setting_enum_unpack
         #setting_enum_pack
         #setting_default
+        #composite_settings
+        #frame_periods
     }))
 
     // gen_messages(device, mod_vec);
@@ -93,6 +99,12 @@ pub fn gen_device_info(device: &Device, mod_vec: &mut Vec<syn::Item>) {
             fn setting_info<'a>() -> &'a [SettingInfo<Self::Setting>] {
                 &crate::#dev_lname::SETTING_INFO
             }
+            fn composite_settings<'a>() -> &'a [crate::generic::CompositeSetting] {
+                &crate::#dev_lname::COMPOSITE_SETTINGS
+            }
+            fn frame_periods<'a>() -> &'a [crate::generic::FramePeriodSetting] {
+                &crate::#dev_lname::FRAME_PERIODS
+            }
         }
     );
     mod_vec.push(syn::Item::Verbatim(dev_info));
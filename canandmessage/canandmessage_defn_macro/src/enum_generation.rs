@@ -18,19 +18,40 @@ pub fn gen_enum(spec: &EnumMeta, dev: &Device) -> proc_macro2::TokenStream {
         );
     }
 
-    let entries: Vec<proc_macro2::TokenStream> = spec
+    if spec.catch_all && spec.values.values().any(|ent| ent.name == "UNKNOWN") {
+        panic!(
+            "enum {} has catch_all set, so it can't have an UNKNOWN entry",
+            spec.name
+        );
+    }
+
+    // enums with a catch-all variant carry data, so they can't have explicit discriminants
+    let mut entries: Vec<proc_macro2::TokenStream> = spec
         .values
         .iter()
         .map(|(idx, ent)| {
             let name = screaming_snake_to_ident(&ent.name);
             let val = Literal::u64_unsuffixed(ent.index);
             let docstr = Literal::string(ent.comment.as_str());
-            quote! (
-                #[doc = #docstr]
-                #name = #val
-            )
+            if spec.catch_all {
+                quote! (
+                    #[doc = #docstr]
+                    #name
+                )
+            } else {
+                quote! (
+                    #[doc = #docstr]
+                    #name = #val
+                )
+            }
         })
         .collect();
+    if spec.catch_all {
+        entries.push(quote! (
+            /// A value with no entry in this version of the spec, e.g. from newer firmware
+            Unknown(#repr_type)
+        ));
+    }
 
     let assoc: Vec<proc_macro2::TokenStream> = spec
         .values
@@ -42,6 +63,27 @@ pub fn gen_enum(spec: &EnumMeta, dev: &Device) -> proc_macro2::TokenStream {
         })
         .collect();
 
+    let (assoc_fallback, to_repr) = if spec.catch_all {
+        let arms: Vec<proc_macro2::TokenStream> = spec
+            .values
+            .iter()
+            .map(|(idx, ent)| {
+                let ename = screaming_snake_to_ident(&ent.name);
+                let index = Literal::u64_unsuffixed(*idx);
+                quote!(#name::#ename => #index,)
+            })
+            .collect();
+        (
+            quote!(_ => Ok(#name::Unknown(v))),
+            quote!(match v {
+                #(#arms)*
+                #name::Unknown(v) => v,
+            }),
+        )
+    } else {
+        (quote!(_ => Err(())), quote!(v as #repr_type))
+    };
+
     let default_block = if spec.default_value.len() > 0 {
         let default_value = screaming_snake_to_ident(&spec.default_value);
         quote! {
@@ -84,14 +126,14 @@ pub fn gen_enum(spec: &EnumMeta, dev: &Device) -> proc_macro2::TokenStream {
             fn try_from(v: #repr_type) -> Result<Self, Self::Error> {
                 match v {
                     #(#assoc)*
-                    _ => Err(())
+                    #assoc_fallback
                 }
             }
         }
 
         impl From<#name> for #repr_type {
             fn from(v: #name) -> #repr_type {
-                v as #repr_type
+                #to_repr
             }
         }
         #default_block
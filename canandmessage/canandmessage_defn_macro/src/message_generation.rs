@@ -13,10 +13,11 @@ pub fn gen_message_enum(device: &Device) -> TokenStream {
             let msg_name = utils::screaming_snake_to_ident(name);
             let doc_str = Literal::string(msg.comment.as_str());
             let signals: Vec<TokenStream> = msg
-                .signals
-                .iter()
+                .plain_signals()
                 .filter_map(|sig| {
                     let sig_dtype = match utils::gen_type_for_dtype(device, &sig.dtype) {
+                        // the muxed signals ride along with the value that selects them
+                        Some(_) if sig.mux => mux_type(device, name),
                         Some(v) => {
                             if sig.optional {
                                 quote! { Option<#v> }
@@ -46,6 +47,11 @@ pub fn gen_message_enum(device: &Device) -> TokenStream {
             }
         })
         .collect();
+    let mux_enums: Vec<TokenStream> = device
+        .messages
+        .iter()
+        .filter_map(|(name, msg)| gen_mux_enum(device, name, msg))
+        .collect();
     quote! {
         #[cfg_attr(feature="device",derive(defmt::Format))]
         #[repr(u8)]
@@ -53,9 +59,161 @@ pub fn gen_message_enum(device: &Device) -> TokenStream {
         pub enum Message {
             #(#entries),*
         }
+
+        #(#mux_enums)*
     }
 }
 
+/// Path of the enum a muxed message carries its multiplexor and muxed signals in.
+pub fn mux_type(device: &Device, msg_name: &String) -> TokenStream {
+    let lname = utils::lname(device);
+    let ident = format_ident!("{}Mux", utils::screaming_snake_to_ident(msg_name));
+    quote!(crate::#lname::#ident)
+}
+
+/// One variant per multiplexor value with muxed signals, holding them, and `Other` for the rest.
+fn gen_mux_enum(device: &Device, name: &String, msg: &Message) -> Option<TokenStream> {
+    let mux = msg.mux()?;
+    let mux_dtype = utils::gen_type_for_dtype(device, &mux.dtype)?;
+    let ident = format_ident!("{}Mux", utils::screaming_snake_to_ident(name));
+    let doc = Literal::string(&format!(
+        "`{}` of [`Message::{}`], with the signals present for its value.",
+        mux.name,
+        utils::screaming_snake_to_ident(name)
+    ));
+    let DType::Enum { meta } = &mux.dtype else {
+        unreachable!("muxes are enums");
+    };
+    let variants: Vec<TokenStream> = msg
+        .mux_groups()
+        .iter()
+        .map(|group| {
+            let variant = utils::screaming_snake_to_ident(&group.name.to_string());
+            let variant_doc = Literal::string(meta.values[&group.value].comment.as_str());
+            let fields: Vec<TokenStream> = group
+                .signals
+                .iter()
+                .filter_map(|sig| {
+                    let sig_dtype = utils::gen_type_for_dtype(device, &sig.dtype)?;
+                    let sig_doc = Literal::string(sig.comment.as_str());
+                    let sig_name = format_ident!("{}", sig.name);
+                    Some(quote! {
+                        #[doc=#sig_doc]
+                        #sig_name: #sig_dtype
+                    })
+                })
+                .collect();
+            quote! {
+                #[doc=#variant_doc]
+                #variant { #(#fields),* }
+            }
+        })
+        .collect();
+    let value_arms: Vec<TokenStream> = msg
+        .mux_groups()
+        .iter()
+        .map(|group| {
+            let variant = utils::screaming_snake_to_ident(&group.name.to_string());
+            quote!(#ident::#variant { .. } => #mux_dtype::#variant,)
+        })
+        .collect();
+    Some(quote! {
+        #[doc=#doc]
+        #[cfg_attr(feature="device",derive(defmt::Format))]
+        #[derive(Debug, PartialEq, Clone, Copy)]
+        pub enum #ident {
+            #(#variants,)*
+            /// Any other value, which has no signals of its own
+            Other(#mux_dtype),
+        }
+
+        impl #ident {
+            /// The value of the multiplexor.
+            pub fn value(&self) -> #mux_dtype {
+                match self {
+                    #(#value_arms)*
+                    #ident::Other(v) => *v,
+                }
+            }
+        }
+    })
+}
+
+/// Matches the message's mux enum, binding the muxed signals by name for `assign` to use.
+pub fn gen_mux_destructure(
+    device: &Device,
+    name: &String,
+    msg: &Message,
+    assign: impl Fn(&Signal) -> TokenStream,
+) -> Option<TokenStream> {
+    let mux = msg.mux()?;
+    let mux_type = mux_type(device, name);
+    let mux_name = format_ident!("{}", mux.name);
+    let arms: Vec<TokenStream> = msg
+        .mux_groups()
+        .iter()
+        .map(|group| {
+            let variant = utils::screaming_snake_to_ident(&group.name.to_string());
+            let sigs: Vec<&Signal> = group
+                .signals
+                .iter()
+                .copied()
+                .filter(|sig| !sig.dtype.is_pad())
+                .collect();
+            let sig_names = sigs.iter().map(|sig| format_ident!("{}", sig.name));
+            let assigns = sigs.iter().map(|sig| assign(sig));
+            quote! {
+                #mux_type::#variant { #(#sig_names),* } => {
+                    #(#assigns;)*
+                }
+            }
+        })
+        .collect();
+    Some(quote! {
+        match #mux_name {
+            #(#arms)*
+            #mux_type::Other(_) => (),
+        }
+    })
+}
+
+/// Builds the message's mux enum from the multiplexor and signal values `field` gives.
+pub fn gen_mux_construct(
+    device: &Device,
+    name: &String,
+    msg: &Message,
+    field: impl Fn(&Signal) -> TokenStream,
+) -> Option<TokenStream> {
+    let mux = msg.mux()?;
+    let mux_dtype = utils::gen_type_for_dtype(device, &mux.dtype)?;
+    let mux_type = mux_type(device, name);
+    let mux_value = field(mux);
+    let arms: Vec<TokenStream> = msg
+        .mux_groups()
+        .iter()
+        .map(|group| {
+            let variant = utils::screaming_snake_to_ident(&group.name.to_string());
+            let fields = group
+                .signals
+                .iter()
+                .filter(|sig| !sig.dtype.is_pad())
+                .map(|sig| {
+                    let sig_name = format_ident!("{}", sig.name);
+                    let value = field(sig);
+                    quote!(#sig_name: #value)
+                });
+            quote!(#mux_dtype::#variant => #mux_type::#variant { #(#fields),* },)
+        })
+        .collect();
+    Some(quote! {
+        match #mux_value {
+            #(#arms)*
+            #[allow(unreachable_patterns)]
+            other => #mux_type::Other(other),
+        }
+    })
+}
+
 fn gen_sig_bit_load(sig: &Signal, dtype: TokenStream, idx: &mut usize) -> TokenStream {
     let width = sig.dtype.bit_length();
     let (start, end) = (*idx, *idx + width);
@@ -68,14 +226,21 @@ fn gen_sig_bit_load(sig: &Signal, dtype: TokenStream, idx: &mut usize) -> TokenS
         _ => utils::u_with_size(width),
     };
     let slice_expr = quote!(data[#start_byte..#end_byte].try_into().unwrap());
-    let integral_expr = quote!(bits.get_unchecked(#start..#end).load_le::<#backing_int>());
+    // big-endian signals are whole bytes, so only the byte slice reads need to swap
+    let from_bytes = match sig.big_endian {
+        true => format_ident!("from_be_bytes"),
+        false => format_ident!("from_le_bytes"),
+    };
+    let integral_expr = quote!(bits_get!(bits, #start..#end).load_le::<#backing_int>());
 
     let (from_slice, from_bits) = match &sig.dtype {
-        DType::UInt { .. } => (quote!(#dtype::from_le_bytes(#slice_expr)), integral_expr),
-        DType::SInt { .. } => (quote!(#dtype::from_le_bytes(#slice_expr)), integral_expr),
+        DType::UInt { .. } | DType::Reserved { .. } => {
+            (quote!(#dtype::#from_bytes(#slice_expr)), integral_expr)
+        }
+        DType::SInt { .. } => (quote!(#dtype::#from_bytes(#slice_expr)), integral_expr),
         DType::Float { meta } => match meta.width {
             32 | 64 => (
-                quote!(#dtype::from_le_bytes(#slice_expr)),
+                quote!(#dtype::#from_bytes(#slice_expr)),
                 quote!(#dtype::from_bits(#integral_expr)),
             ),
             24 => (
@@ -86,26 +251,29 @@ fn gen_sig_bit_load(sig: &Signal, dtype: TokenStream, idx: &mut usize) -> TokenS
         },
         DType::Buf { .. } => (slice_expr, quote!(#integral_expr.to_le_bytes())),
         DType::Enum { .. } => (
-            quote!(#dtype::try_from(#backing_int::from_le_bytes(#slice_expr))?),
+            quote!(#dtype::try_from(#backing_int::#from_bytes(#slice_expr))?),
             quote!(#dtype::try_from(#integral_expr)?),
         ),
         DType::Bitset { .. } => {
             let arb_ubits = format_ident!("u{}", width);
             (
-                quote!(#dtype::from_bitfield(#backing_int::from_le_bytes(#slice_expr))),
+                quote!(#dtype::from_bitfield(#backing_int::#from_bytes(#slice_expr))),
                 quote!(#dtype::from_bitfield(#arb_ubits::from(#integral_expr))),
             )
         }
         _ => unreachable!(),
     };
 
-    if utils::byte_aligned(width) && utils::byte_aligned(start) {
+    // reserved regions can be any number of whole bytes, which a native integer's bytes won't fit,
+    // so they're always loaded as bits
+    let sliceable = !matches!(sig.dtype, DType::Reserved { .. });
+    if sliceable && utils::byte_aligned(width) && utils::byte_aligned(start) {
         // if both the width is aligned and the starting point is byte aligned, we just do a byte copy (happy path).
         // this _ideally_ compiles to some memcpy intrinsic
         quote!(unsafe{#from_slice})
     } else {
         // otherwise we convert to some sort of integral type and load it that way
-        quote!(unsafe{#from_bits})
+        from_bits
     }
 }
 
@@ -207,7 +375,8 @@ pub fn gen_signal_unpacker(
         | DType::Buf { .. }
         | DType::Float { .. }
         | DType::Bitset { .. }
-        | DType::Enum { .. } => Some(gen_assignment(
+        | DType::Enum { .. }
+        | DType::Reserved { .. } => Some(gen_assignment(
             name,
             sig,
             gen_sig_bit_load(sig, dtype.unwrap(), idx),
@@ -227,7 +396,7 @@ pub fn gen_signal_unpacker(
             Some(gen_assignment(
                 name,
                 sig,
-                quote!(unsafe{*bits.get_unchecked(#pos)}),
+                quote!(*bits_get!(bits, #pos)),
                 *idx,
                 check_bounds,
             ))
@@ -268,16 +437,91 @@ pub fn gen_signal_unpacker(
                 }, // the fields of the struct that get appended on
             ))
         }
+        DType::Array { meta } => {
+            // each element is unpacked like its own signal, then gathered into an array
+            let mut declrs: Vec<TokenStream> = Vec::new();
+            let elems: Vec<TokenStream> = (0..meta.len)
+                .filter_map(|i| {
+                    gen_signal_unpacker(
+                        device,
+                        &sig.array_element(i),
+                        prefix.clone(),
+                        idx,
+                        check_bounds,
+                    )
+                    .map(|(declr, _, elem_fill)| {
+                        declrs.push(declr);
+                        elem_fill
+                    })
+                })
+                .collect();
+            let expr_name = format_ident!("{}", sig.name.to_owned());
+            Some((
+                quote! {
+                    #(#declrs)*
+                },
+                expr_name,
+                quote! {
+                    [#(#elems),*]
+                },
+            ))
+        }
     }
 }
 
+/// Rebinds the decoded multiplexor as its mux enum, decoding the signals its value selects.
+fn gen_mux_unpacker(device: &Device, name: &String, msg: &Message) -> Option<TokenStream> {
+    let mux = msg.mux()?;
+    let mux_dtype = utils::gen_type_for_dtype(device, &mux.dtype)?;
+    let mux_type = mux_type(device, name);
+    let mux_var = format_ident!("sig_{}", mux.name);
+    let arms: Vec<TokenStream> = msg
+        .mux_groups()
+        .iter()
+        .map(|group| {
+            let variant = utils::screaming_snake_to_ident(&group.name.to_string());
+            let group_bytes = (group.end + 7) / 8;
+            let mut idx = msg.mux_offset();
+            let mut declrs: Vec<TokenStream> = Vec::new();
+            let fields: Vec<TokenStream> = group
+                .signals
+                .iter()
+                .filter_map(|sig| {
+                    gen_signal_unpacker(device, sig, "sig".to_string(), &mut idx, false).map(
+                        |(sig_declrs, sig_expr_name, sig_struct_fill)| {
+                            declrs.push(sig_declrs);
+                            quote!(#sig_expr_name: #sig_struct_fill)
+                        },
+                    )
+                })
+                .collect();
+            quote! {
+                #mux_dtype::#variant => {
+                    if dlc < #group_bytes {
+                        return Err(());
+                    }
+                    #(#declrs)*
+                    #mux_type::#variant { #(#fields),* }
+                }
+            }
+        })
+        .collect();
+    Some(quote! {
+        let #mux_var = match #mux_var {
+            #(#arms)*
+            #[allow(unreachable_patterns)]
+            other => #mux_type::Other(other),
+        };
+    })
+}
+
 pub fn gen_inbound_message_impl(device: &Device, target_source: Source) -> TokenStream {
     let arms: Vec<TokenStream> = device
         .messages
         .iter()
         .map(|(name, msg)| {
             let msg_can_id_first = utils::gen_can_id(device, msg.id);
-            let msg_can_id_last = msg_can_id_first + 63;
+            let msg_can_id_last = msg_can_id_first | frc_can_id::DEVICE_NUMBER_MASK;
             let msg_min_dlc = msg.min_length as usize;
             let msg_max_dlc = msg.max_length as usize;
             let msg_name = utils::screaming_snake_to_ident(name);
@@ -290,8 +534,7 @@ pub fn gen_inbound_message_impl(device: &Device, target_source: Source) -> Token
             let mut idx = 0usize;
             let mut declrs: Vec<TokenStream> = Vec::new();
             let fields: Vec<TokenStream> = msg
-                .signals
-                .iter()
+                .plain_signals()
                 .filter_map(|sig| {
                     gen_signal_unpacker(device, sig, "sig".to_string(), &mut idx, false).map(
                         |(sig_declrs, sig_expr_name, sig_struct_fill)| {
@@ -301,6 +544,9 @@ pub fn gen_inbound_message_impl(device: &Device, target_source: Source) -> Token
                     )
                 })
                 .collect();
+            if let Some(mux) = gen_mux_unpacker(device, name, msg) {
+                declrs.push(mux);
+            }
 
             // the match arm for a message.
             quote! {
@@ -365,14 +611,22 @@ fn gen_sig_bit_store(device: &Device, sig: &Signal, idx: &mut usize) -> TokenStr
         _ => utils::u_with_size(width),
     };
 
+    // big-endian signals are whole bytes, so only the byte slice writes need to swap
+    let to_bytes = match sig.big_endian {
+        true => format_ident!("to_be_bytes"),
+        false => format_ident!("to_le_bytes"),
+    };
+
     // each type this function handles can either be addressed as a slice or as an integral type (usually unsigned.)
     // which one is used depends on if the signal (and value) is byte-aligned or not.
-    let (to_slice, to_integral) = match sig.dtype {
-        DType::UInt { meta: _ } => (quote!(&_value.to_le_bytes()), quote!(_value)),
-        DType::SInt { meta: _ } => (quote!(&_value.to_le_bytes()), quote!(_value)),
+    let (to_slice, to_integral) = match &sig.dtype {
+        DType::UInt { meta: _ } | DType::Reserved { .. } => {
+            (quote!(&_value.#to_bytes()), quote!(_value))
+        }
+        DType::SInt { meta: _ } => (quote!(&_value.#to_bytes()), quote!(_value)),
         DType::Float { meta } => match meta.width {
             32 | 64 => (
-                quote!(&_value.to_bits().to_le_bytes()),
+                quote!(&_value.to_bits().#to_bytes()),
                 quote!(&_value.to_bits()),
             ),
             24 => (
@@ -386,13 +640,13 @@ fn gen_sig_bit_store(device: &Device, sig: &Signal, idx: &mut usize) -> TokenStr
             quote!(#backing_integral::from_le_bytes(_value)),
         ),
         DType::Enum { meta: _ } => (
-            quote!(&(_value as #backing_integral).to_le_bytes()),
-            quote!(_value as #backing_integral),
+            quote!(&#backing_integral::from(_value).#to_bytes()),
+            quote!(#backing_integral::from(_value)),
         ),
         DType::Bitset { meta: _ } => {
             let arb_ubits = format_ident!("u{}", width);
             (
-                quote!(&_value.value().to_le_bytes()),
+                quote!(&_value.value().#to_bytes()),
                 quote!(_value.value().value()),
             )
         }
@@ -402,7 +656,9 @@ fn gen_sig_bit_store(device: &Device, sig: &Signal, idx: &mut usize) -> TokenStr
     // increment the idx ctr here.
     *idx += width;
 
-    if utils::byte_aligned(width) && (start % 8) == 0 {
+    // see gen_sig_bit_load
+    let sliceable = !matches!(sig.dtype, DType::Reserved { .. });
+    if sliceable && utils::byte_aligned(width) && (start % 8) == 0 {
         // if both the width is aligned and the starting point is byte aligned, we just do a byte copy (happy path).
         // this uses the to_slice expression
         let start_byte = start / 8;
@@ -410,7 +666,7 @@ fn gen_sig_bit_store(device: &Device, sig: &Signal, idx: &mut usize) -> TokenStr
         quote! { unsafe { msg_buf.as_raw_mut_slice()[#start_byte..#end_byte].copy_from_slice(#to_slice); } }
     } else {
         // otherwise we convert to some sort of integral type and store it that way
-        quote! { unsafe { msg_buf.get_unchecked_mut(#start..#end).store_le::<#backing_integral>(#to_integral); } }
+        quote! { bits_get_mut!(msg_buf, #start..#end).store_le::<#backing_integral>(#to_integral); }
     }
 }
 
@@ -426,13 +682,24 @@ pub fn gen_signal_packer(
         Some(prefix_tok) => quote! {#prefix_tok.#name},
         None => quote! {#name},
     };
+    gen_value_packer(device, sig, qual_name, idx)
+}
+
+/// Packs the value at `qual_name` as `sig`.
+fn gen_value_packer(
+    device: &Device,
+    sig: &Signal,
+    qual_name: TokenStream,
+    idx: &mut usize,
+) -> TokenStream {
     let serialize_op = match &sig.dtype {
         DType::UInt { .. }
         | DType::SInt { .. }
         | DType::Buf { .. }
         | DType::Float { .. }
         | DType::Bitset { .. }
-        | DType::Enum { .. } => gen_sig_bit_store(device, sig, idx),
+        | DType::Enum { .. }
+        | DType::Reserved { .. } => gen_sig_bit_store(device, sig, idx),
         DType::None => quote!(),
         DType::Pad { width } => {
             *idx += width;
@@ -441,7 +708,7 @@ pub fn gen_signal_packer(
         DType::Bool { default_value } => {
             let start = *idx;
             *idx += 1;
-            quote! {unsafe { msg_buf.set_unchecked(#start, _value)}}
+            quote! {bits_set!(msg_buf, #start, _value);}
         }
         DType::Struct { meta } => utils::flatten_token_vec(
             meta.signals
@@ -449,6 +716,20 @@ pub fn gen_signal_packer(
                 .map(|sig| gen_signal_packer(device, sig, Some(qual_name.clone()), idx))
                 .collect(),
         ),
+        DType::Array { meta } => utils::flatten_token_vec(
+            (0..meta.len)
+                .map(|i| {
+                    let elem_idx = Literal::usize_unsuffixed(i);
+                    let elem_op = gen_value_packer(
+                        device,
+                        &sig.array_element(i),
+                        quote!(#qual_name[#elem_idx]),
+                        idx,
+                    );
+                    quote! { { #elem_op } }
+                })
+                .collect(),
+        ),
     };
 
     if sig.optional {
@@ -470,6 +751,45 @@ pub fn gen_signal_packer(
     }
 }
 
+/// Packs the signals the multiplexor's value selects, lengthening the frame to fit them.
+fn gen_mux_packer(device: &Device, name: &String, msg: &Message) -> Option<TokenStream> {
+    let mux = msg.mux()?;
+    let mux_type = mux_type(device, name);
+    let mux_name = format_ident!("{}", mux.name);
+    let arms: Vec<TokenStream> = msg
+        .mux_groups()
+        .iter()
+        .map(|group| {
+            let variant = utils::screaming_snake_to_ident(&group.name.to_string());
+            let group_bytes = (group.end + 7) / 8;
+            let mut idx = msg.mux_offset();
+            let sig_names: Vec<TokenStream> = group
+                .signals
+                .iter()
+                .filter(|sig| !sig.dtype.is_pad())
+                .map(|sig| format_ident!("{}", sig.name).into_token_stream())
+                .collect();
+            let packers: Vec<TokenStream> = group
+                .signals
+                .iter()
+                .map(|sig| gen_signal_packer(device, sig, None, &mut idx))
+                .collect();
+            quote! {
+                #mux_type::#variant { #(#sig_names),* } => {
+                    #(#packers)*
+                    msg_dlc = msg_dlc.max(#group_bytes);
+                }
+            }
+        })
+        .collect();
+    Some(quote! {
+        match #mux_name {
+            #(#arms)*
+            #mux_type::Other(_) => (),
+        }
+    })
+}
+
 pub fn gen_outbound_message_impl(device: &Device, target_source: Source) -> TokenStream {
     let device_lname = format_ident!("{}", device.name.to_lowercase());
     let arms: Vec<TokenStream> = device
@@ -477,7 +797,7 @@ pub fn gen_outbound_message_impl(device: &Device, target_source: Source) -> Toke
         .iter()
         .map(|(name, msg)| {
             let msg_name = utils::screaming_snake_to_ident(name);
-            let sig_names: Vec<TokenStream> = msg.signals.iter().filter_map(|sig| {
+            let sig_names: Vec<TokenStream> = msg.plain_signals().filter_map(|sig| {
                 if sig.dtype.is_pad() {
                     None
                 } else {
@@ -492,9 +812,15 @@ pub fn gen_outbound_message_impl(device: &Device, target_source: Source) -> Toke
             let msg_dlc = msg.min_length as usize;
             let mut idx = 0;
             
-            let packers : Vec<TokenStream> = msg.signals.iter().map(|sig| {
-                gen_signal_packer(device, sig, None, &mut idx)
+            let mut packers : Vec<TokenStream> = msg.plain_signals().map(|sig| {
+                if sig.mux {
+                    let mux_name = format_ident!("{}", sig.name);
+                    gen_value_packer(device, sig, quote!(#mux_name.value()), &mut idx)
+                } else {
+                    gen_signal_packer(device, sig, None, &mut idx)
+                }
             }).collect();
+            packers.extend(gen_mux_packer(device, name, msg));
 
             quote! {
                 Message::#msg_name { #(#sig_names),* } => {
@@ -527,6 +853,12 @@ pub fn gen_outbound_message_impl(device: &Device, target_source: Source) -> Toke
 }
 
 pub fn gen_message_index_enum(device: &Device) -> TokenStream {
+    let dev_type = if device.dev_type == 31 {
+        quote!(None)
+    } else {
+        let dev_type = device.dev_type;
+        quote!(Some(#dev_type))
+    };
     let ents: Vec<TokenStream> = device
         .messages
         .iter()
@@ -570,6 +902,10 @@ pub fn gen_message_index_enum(device: &Device) -> TokenStream {
                 v as u8
             }
         }
+
+        impl crate::traits::MessageIndexId for MessageIndex {
+            const DEV_TYPE: Option<u8> = #dev_type;
+        }
     }
 }
 
@@ -580,28 +916,230 @@ pub fn gen_message_filters(device: &Device) -> TokenStream {
         .map(|(name, msg)| {
             let msg_name = utils::screaming_snake_to_ident(name);
             let filter_numer: u32 = utils::gen_can_id(device, msg.id);
-            quote! { #msg_name => #filter_numer, }
+            quote! { MessageIndex::#msg_name => #filter_numer, }
+        })
+        .collect();
+
+    // The ids above were built with the frc-can-id the macro links against; check them against the
+    // one the generated code links against, so the two can't drift apart silently.
+    let dev_type_code = utils::gen_device_type_code(device);
+    let filter_asserts: Vec<TokenStream> = device
+        .messages
+        .values()
+        .map(|msg| {
+            let filter_numer: u32 = utils::gen_can_id(device, msg.id);
+            let msg_id = msg.id as u16;
+            quote! {
+                assert!(#filter_numer == frc_can_id::build_frc_can_id(#dev_type_code, crate::REDUX_VENDOR_ID, #msg_id, 0));
+            }
+        })
+        .collect();
+
+    let message_ids: Vec<TokenStream> = device
+        .messages
+        .iter()
+        .map(|(name, msg)| {
+            let msg_name = utils::screaming_snake_to_ident(name);
+            let can_id: u32 = utils::gen_can_id(device, msg.id);
+            quote! { (MessageIndex::#msg_name, #can_id), }
         })
         .collect();
 
     let device_expect = utils::gen_can_id(device, 0);
     quote! {
+        const _: () = {
+            assert!(#device_expect == frc_can_id::build_frc_can_id(#dev_type_code, crate::REDUX_VENDOR_ID, 0, 0));
+            assert!(#device_expect & !frc_can_id::device_filter_mask() == 0);
+            #(#filter_asserts)*
+        };
+
         pub fn can_filter_for(device_id: u8) -> crate::generic::CanMaskFilter {
             crate::generic::CanMaskFilter {
                 expect: #device_expect | device_id as u32,
-                mask: 0x1FFF003F
+                mask: frc_can_id::device_filter_mask()
             }
         }
 
+        /// Every message with its CAN id for device number 0, for tables that live in flash.
+        pub const MESSAGE_IDS: &[(MessageIndex, u32)] = &[
+            #(#message_ids)*
+        ];
+
         impl MessageIndex {
-            pub fn filter_for(&self, device_id : u8) -> crate::generic::CanMaskFilter {
+            /// Gets the CAN id of this message for device number `device_id`.
+            pub const fn can_id(&self, device_id: u8) -> u32 {
+                device_id as u32 | match self {
+                    #(#filter_expects)*
+                }
+            }
+
+            pub const fn filter_for(&self, device_id : u8) -> crate::generic::CanMaskFilter {
                 crate::generic::CanMaskFilter {
-                    expect: device_id as u32 | match self {
-                        #(#filter_expects)*
-                    },
-                    mask: 0x1FFFFFFF
+                    expect: self.can_id(device_id),
+                    mask: frc_can_id::message_filter_mask()
+                }
+            }
+
+            /// One exact filter per message, e.g. for a controller's filter banks.
+            pub const fn filters_for<const N: usize>(
+                indexes: [MessageIndex; N],
+                device_id: u8,
+            ) -> [crate::generic::CanMaskFilter; N] {
+                let mut filters = [crate::generic::CanMaskFilter { expect: 0, mask: 0 }; N];
+                let mut i = 0;
+                while i < N {
+                    filters[i] = indexes[i].filter_for(device_id);
+                    i += 1;
+                }
+                filters
+            }
+
+            /// A single filter passing all of `indexes`, which may also pass some other messages
+            /// of the device; see [`crate::generic::CanMaskFilter::cover`].
+            ///
+            /// Panics, or fails to compile in a const, if `indexes` is empty.
+            pub const fn covering_filter(
+                indexes: &[MessageIndex],
+                device_id: u8,
+            ) -> crate::generic::CanMaskFilter {
+                assert!(!indexes.is_empty(), "no messages to filter for");
+                let mut filter = indexes[0].filter_for(device_id);
+                let mut i = 1;
+                while i < indexes.len() {
+                    filter = filter.cover(indexes[i].can_id(device_id));
+                    i += 1;
+                }
+                filter
+            }
+        }
+    }
+}
+
+/// Conversions between the raw integers of scaled message signals (those whose type has a
+/// `factor` or `offset`) and the values they stand for, so consumers don't each redo the scaling.
+pub fn gen_signal_scaling(device: &Device) -> TokenStream {
+    let mut helpers: Vec<TokenStream> = Vec::new();
+    let mut views: Vec<TokenStream> = Vec::new();
+    for (msg_name, msg) in device.messages.iter() {
+        let mut view_fields: Vec<TokenStream> = Vec::new();
+        let mut view_values: Vec<TokenStream> = Vec::new();
+        let mut view_bindings: Vec<Ident> = Vec::new();
+        for sig in msg.signals.iter().filter(|sig| !sig.optional) {
+            let (raw_type, factor_num, factor_den, offset, min, max, unit) = match &sig.dtype {
+                DType::UInt { meta } => (
+                    utils::u_with_size(meta.width),
+                    meta.factor_num,
+                    meta.factor_den,
+                    meta.offset,
+                    meta.min.unwrap_or(0) as f64,
+                    meta.max
+                        .unwrap_or(canandmessage_parser::utils::default_uint_max(meta.width))
+                        as f64,
+                    meta.unit.as_deref(),
+                ),
+                DType::SInt { meta } => (
+                    utils::i_with_size(meta.width),
+                    meta.factor_num,
+                    meta.factor_den,
+                    meta.offset,
+                    meta.min
+                        .unwrap_or(canandmessage_parser::utils::default_sint_min(meta.width))
+                        as f64,
+                    meta.max
+                        .unwrap_or(canandmessage_parser::utils::default_sint_max(meta.width))
+                        as f64,
+                    meta.unit.as_deref(),
+                ),
+                _ => continue,
+            };
+            if factor_num == factor_den && offset == 0.0 {
+                continue;
+            }
+            let in_unit = unit.map(|unit| format!(" in {unit}")).unwrap_or_default();
+            let fn_name = format_ident!("{}_{}", msg_name.to_lowercase(), sig.name);
+            let raw_fn_name = format_ident!("{}_{}_raw", msg_name.to_lowercase(), sig.name);
+            let doc = Literal::string(&format!(
+                "`{msg_name}.{}`{in_unit} from its raw value: {}",
+                sig.name, sig.comment
+            ));
+            let raw_doc = Literal::string(&format!(
+                "The raw value of `{msg_name}.{}` closest to `value`{in_unit}, clamped to its range.",
+                sig.name
+            ));
+            let scale = Literal::f64_unsuffixed(factor_num as f64 / factor_den as f64);
+            let offset = Literal::f64_unsuffixed(offset);
+            let (min, max) = (Literal::f64_unsuffixed(min), Literal::f64_unsuffixed(max));
+            helpers.push(quote! {
+                #[doc=#doc]
+                #[inline]
+                pub fn #fn_name(raw: #raw_type) -> f64 {
+                    raw as f64 * #scale + #offset
+                }
+
+                #[doc=#raw_doc]
+                #[inline]
+                pub fn #raw_fn_name(value: f64) -> #raw_type {
+                    let raw = ((value - #offset) / #scale).clamp(#min, #max);
+                    // rounds half away from zero; `as` saturates, and takes NaN to 0
+                    (if raw < 0.0 { raw - 0.5 } else { raw + 0.5 }) as #raw_type
+                }
+            });
+
+            // the variant holds the mux and muxed signals as a `{Msg}Mux`, not as raw values
+            if sig.mux || sig.muxed_by.is_some() {
+                continue;
+            }
+            let field = format_ident!("{}", sig.name);
+            let field_doc = Literal::string(&match unit {
+                Some(unit) => format!("{} ({unit})", sig.comment),
+                None => sig.comment.clone(),
+            });
+            view_fields.push(quote! {
+                #[doc=#field_doc]
+                pub #field: f64
+            });
+            view_values.push(quote! { #field: #fn_name(*#field) });
+            view_bindings.push(field);
+        }
+        if view_fields.is_empty() {
+            continue;
+        }
+
+        let msg_ident = utils::screaming_snake_to_ident(msg_name);
+        let view_doc = Literal::string(&format!(
+            "The scaled signals of [`super::Message::{msg_ident}`], in their units."
+        ));
+        views.push(quote! {
+            #[doc=#view_doc]
+            #[derive(Debug, Clone, Copy, PartialEq)]
+            pub struct #msg_ident {
+                #(#view_fields),*
+            }
+
+            impl #msg_ident {
+                /// Scales the signals of `msg`, or `None` if it's a different message.
+                pub fn from_message(msg: &super::Message) -> Option<Self> {
+                    match msg {
+                        super::Message::#msg_ident { #(#view_bindings,)* .. } => Some(Self {
+                            #(#view_values),*
+                        }),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    }
                 }
             }
+        });
+    }
+    if helpers.is_empty() {
+        return quote!();
+    }
+    quote! {
+        /// Scaling of message signals between raw integers and their units; see the signals' types.
+        /// Messages with scaled signals also get a struct here holding them all scaled.
+        pub mod scaled {
+            #(#helpers)*
+
+            #(#views)*
         }
     }
 }
@@ -1,5 +1,5 @@
 use canandmessage_parser::toml_defs::TypeSpec;
-use canandmessage_parser::{DType, Device, Setting, Signal, StructMeta};
+use canandmessage_parser::{CompositeEncoding, DType, Device, Setting, Signal, StructMeta};
 use darling::FromMeta;
 use proc_macro2::{Literal, TokenStream};
 use quote::{format_ident, quote, ToTokens};
@@ -137,6 +137,7 @@ pub fn gen_default_settings_value(dev: &Device, dtype: &DType) -> TokenStream {
             quote!(#dtype_name::from_bitfield(#val))
         }
         DType::Pad { width } => quote!(),
+        DType::Reserved { width } => utils::uint_literal(0, *width),
         DType::Bool { default_value } => quote!(#default_value),
         DType::Enum { meta } => {
             let dtype_name =
@@ -166,6 +167,11 @@ pub fn gen_default_settings_value(dev: &Device, dtype: &DType) -> TokenStream {
                 }
             }
         }
+        DType::Array { meta } => {
+            let value = gen_default_settings_value(dev, &meta.dtype);
+            let len = Literal::usize_unsuffixed(meta.len);
+            quote!([#value; #len])
+        }
     }
 }
 
@@ -212,3 +218,62 @@ pub fn gen_default_settings_vec(device: &Device) -> TokenStream {
         pub static SETTING_INFO: [SettingInfo<crate::#lname::Setting>; #vlen] = [#(#stgs),*];
     }
 }
+
+pub fn gen_composite_settings(device: &Device) -> TokenStream {
+    let composites: Vec<TokenStream> = device
+        .composite_settings
+        .values()
+        .map(|composite| {
+            let name = &composite.name;
+            let parts = &composite.parts;
+            let encoding = match composite.encoding {
+                CompositeEncoding::Bytes => quote!(crate::generic::CompositeEncoding::Bytes),
+                CompositeEncoding::Utf8 => quote!(crate::generic::CompositeEncoding::Utf8),
+            };
+            quote! {
+                crate::generic::CompositeSetting {
+                    name: #name,
+                    parts: &[#(#parts),*],
+                    encoding: #encoding,
+                }
+            }
+        })
+        .collect();
+    let vlen = Literal::usize_unsuffixed(composites.len());
+
+    quote! {
+        #[doc="Settings split across several setting indexes, such as the device name."]
+        pub static COMPOSITE_SETTINGS: [crate::generic::CompositeSetting; #vlen] = [#(#composites),*];
+    }
+}
+
+pub fn gen_frame_periods(device: &Device) -> TokenStream {
+    let periods: Vec<TokenStream> = device
+        .messages
+        .iter()
+        .filter_map(|(name, msg)| {
+            let period = msg.frame_period.as_ref()?;
+            let message_index = msg.id;
+            let setting = &period.setting;
+            let setting_index = period.setting_id;
+            let min = u16::try_from(period.min).expect("frame period min must fit in 16 bits");
+            let max = u16::try_from(period.max).expect("frame period max must fit in 16 bits");
+            Some(quote! {
+                crate::generic::FramePeriodSetting {
+                    message: #name,
+                    message_index: #message_index,
+                    setting: #setting,
+                    setting_index: #setting_index,
+                    min_ms: #min,
+                    max_ms: #max,
+                }
+            })
+        })
+        .collect();
+    let vlen = Literal::usize_unsuffixed(periods.len());
+
+    quote! {
+        #[doc="Settings controlling how often each periodic frame is sent."]
+        pub static FRAME_PERIODS: [crate::generic::FramePeriodSetting; #vlen] = [#(#periods),*];
+    }
+}
@@ -3,6 +3,7 @@ use canandmessage_parser::{DType, Device, Setting, Signal, Source, StructMeta};
 use proc_macro2::{Literal, TokenStream};
 use quote::{format_ident, quote, ToTokens};
 
+use crate::message_generation;
 use crate::setting_generation::gen_default_settings_value;
 use crate::utils::{
     self, f_with_size, flatten_token_vec, gen_type_for_dtype, i_with_size, min_width, u8_buf,
@@ -23,6 +24,9 @@ pub fn gen_simulation(device: &Device) -> proc_macro2::TokenStream {
     let mut type_contents: Vec<proc_macro2::TokenStream> = Vec::new();
     let mut defaults_contents: Vec<proc_macro2::TokenStream> = Vec::new();
     let mut periodic_contents: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut set_signal_arms: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut signal_entries: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut rate_arms: Vec<proc_macro2::TokenStream> = Vec::new();
 
     for message in device.messages.iter() {
         if message.1.source == Source::Device {
@@ -73,6 +77,32 @@ pub fn gen_simulation(device: &Device) -> proc_macro2::TokenStream {
                 pub #msg_rate: std::time::Duration
             ));
 
+            // name-based access, for driving simulated devices from outside Rust
+            let msg_str = message.0.to_lowercase();
+            rate_arms.push(quote!(
+                #msg_str => self.#msg_rate = rate
+            ));
+            for sig in message.1.signals.iter() {
+                if matches!(sig.dtype, DType::Pad { .. } | DType::None) {
+                    continue;
+                }
+                let field = format_ident!(
+                    "{}_{}",
+                    utils::screaming_snake_to_camel(message.0),
+                    sig.name
+                );
+                let sig_str = format!("{msg_str}.{}", sig.name);
+                set_signal_arms.push(quote!(
+                    #sig_str => {
+                        self.#field = serde_json::from_value(value)
+                            .map_err(crate::simulation::SimSignalError::InvalidValue)?;
+                    }
+                ));
+                signal_entries.push(quote!(
+                    (#sig_str, serde_json::to_value(self.#field).unwrap_or_default())
+                ));
+            }
+
             let msg_instant = format_ident!("last_{}", utils::screaming_snake_to_camel(message.0));
             type_contents.push(quote!(
                 #msg_instant: std::time::Instant
@@ -107,21 +137,32 @@ pub fn gen_simulation(device: &Device) -> proc_macro2::TokenStream {
 
             defaults_contents.append(&mut defaults_lcl);
 
+            let global_field = |sig: &Signal| {
+                let global_name = format_ident!(
+                    "{}_{}",
+                    utils::screaming_snake_to_camel(message.0),
+                    sig.name
+                );
+                quote!(self.#global_name)
+            };
             let mut process_signals: Vec<proc_macro2::TokenStream> = message
                 .1
-                .signals
-                .iter()
+                .plain_signals()
                 .filter_map(|sig| {
                     let sig_name = format_ident!("{}", sig.name);
-                    let global_name = format_ident!(
-                        "{}_{}",
-                        utils::screaming_snake_to_camel(message.0),
-                        sig.name
-                    );
+                    let global_name = match sig.mux {
+                        true => message_generation::gen_mux_construct(
+                            device,
+                            message.0,
+                            message.1,
+                            global_field,
+                        )?,
+                        false => global_field(sig),
+                    };
                     match sig.dtype {
                         DType::Pad { width } => None,
                         _ => Some(quote!(
-                            #sig_name: self.#global_name
+                            #sig_name: #global_name
                         )),
                     }
                 })
@@ -323,6 +364,53 @@ pub fn gen_simulation(device: &Device) -> proc_macro2::TokenStream {
 
                 return message_buf;
             }
+
+            /// Sets a simulated signal from JSON, by its `message_name.signal_name` name.
+            pub fn set_signal(&mut self, name: &str, value: serde_json::Value) -> Result<(), crate::simulation::SimSignalError> {
+                match name {
+                    #(#set_signal_arms)*
+                    _ => return Err(crate::simulation::SimSignalError::UnknownSignal),
+                }
+                Ok(())
+            }
+
+            /// Current values of every simulated signal, keyed like [`Self::set_signal`].
+            pub fn signals(&self) -> Vec<(&'static str, serde_json::Value)> {
+                vec![#(#signal_entries),*]
+            }
+
+            /// Sets how often a message is sent. A zero rate stops sending it.
+            pub fn set_rate(&mut self, message: &str, rate: std::time::Duration) -> Result<(), crate::simulation::SimSignalError> {
+                match message {
+                    #(#rate_arms),*,
+                    _ => return Err(crate::simulation::SimSignalError::UnknownMessage),
+                }
+                Ok(())
+            }
+
+            /// Applies a message sent to the device by the host.
+            pub fn handle_message(&mut self, msg: #lowercase_name::Message) {
+                use crate::traits::CanandDeviceSetting;
+                match msg {
+                    #lowercase_name::Message::SetSetting { address, value, .. } => {
+                        if let Ok(setting) = #lowercase_name::Setting::from_address_data(address, &value) {
+                            self.settings.process(address, setting);
+                        }
+                    }
+                    #lowercase_name::Message::SettingCommand { control_flag, setting_index } => {
+                        match (control_flag, setting_index) {
+                            (#lowercase_name::types::SettingCommand::FetchSettings, _) => {
+                                self.settings.add_all_to_report_queue();
+                            }
+                            (#lowercase_name::types::SettingCommand::FetchSettingValue, Some(index)) => {
+                                self.settings.report_setting(index);
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
         }
 
         #[cfg(feature="simulation")]
@@ -378,6 +466,7 @@ pub fn gen_default_value(dev: &Device, dtype: &DType) -> Option<TokenStream> {
             Some(quote!(#dtype_name::from_bitfield(#val)))
         }
         DType::Pad { width } => None,
+        DType::Reserved { width } => Some(utils::uint_literal(0, *width)),
         DType::Bool { default_value } => Some(quote!(#default_value)),
         DType::Enum { meta } => {
             let dtype_name =
@@ -410,6 +499,11 @@ pub fn gen_default_value(dev: &Device, dtype: &DType) -> Option<TokenStream> {
                 }
             })
         }
+        DType::Array { meta } => {
+            let value = gen_default_value(dev, &meta.dtype)?;
+            let len = Literal::usize_unsuffixed(meta.len);
+            Some(quote!([#value; #len]))
+        }
     }
 }
 
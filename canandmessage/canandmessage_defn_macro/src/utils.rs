@@ -40,14 +40,21 @@ pub fn u8_buf(size: usize) -> proc_macro2::TokenStream {
 }
 
 pub fn gen_can_id(device: &Device, msg_id: u8) -> u32 {
-    let mut out = 0;
-    if device.dev_type != 31 {
-        out = (device.dev_type as u32) << 24;
-    };
+    frc_can_id::build_frc_can_id(
+        gen_device_type_code(device),
+        frc_can_id::REDUX_VENDOR_ID,
+        msg_id as u16,
+        0,
+    )
+}
 
-    out |= (0xE) << 16;
-    //out |= (device.dev_class as u32) << 10;
-    out | (msg_id as u32) << 6
+/// Device type code in generated ids. Type 31 is shared by every device, and is sent as 0.
+pub fn gen_device_type_code(device: &Device) -> u8 {
+    if device.dev_type != 31 {
+        device.dev_type
+    } else {
+        0
+    }
 }
 
 pub fn min_width(bits: usize) -> usize {
@@ -82,9 +89,15 @@ pub fn gen_type_for_dtype(dev: &Device, dtype: &DType) -> Option<proc_macro2::To
         DType::Float { meta } => Some(f_with_size(meta.width)),
         DType::Bitset { meta } => Some(fully_qualified_type_name(&dev.name, &meta.name)),
         DType::Pad { width } => None,
+        DType::Reserved { width } => Some(u_with_size(*width)),
         DType::Bool { default_value } => Some(quote!(bool)),
         DType::Enum { meta } => Some(fully_qualified_type_name(&dev.name, &meta.name)),
         DType::Struct { meta } => Some(fully_qualified_type_name(&dev.name, &meta.name)),
+        DType::Array { meta } => {
+            let elem = gen_type_for_dtype(dev, &meta.dtype)?;
+            let len = Literal::usize_unsuffixed(meta.len);
+            Some(quote!([#elem; #len]))
+        }
     }
 }
 
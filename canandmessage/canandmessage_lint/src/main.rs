@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+use canandmessage_parser::lint::{check_compat, lint_spec, Diagnostic};
+use clap::{arg, Command};
+use serde_json::json;
+
+fn main() {
+    let m = Command::new("canandmessage-lint")
+        .version("0.1.0")
+        .about("checks canandmessage TOML specs, exiting nonzero if any have errors")
+        .arg(
+            arg!(--"format" <FORMAT> "output format")
+                .value_parser(["gcc", "json"])
+                .default_value("gcc"),
+        )
+        .arg(
+            arg!(--"baseline" <DIR> "folder of the previous revision's specs; each spec must stay compatible with the one of the same name in it"),
+        )
+        .arg(arg!([spec] ... "spec files or folders of them, defaults to ./messages"))
+        .get_matches();
+
+    let specs = m
+        .get_many::<String>("spec")
+        .map_or(vec!["messages".to_string()], |specs| {
+            specs.cloned().collect()
+        });
+    let files = match spec_files(&specs) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("canandmessage-lint: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    // model conversion failures are reported as diagnostics, not as panic spew
+    std::panic::set_hook(Box::new(|_| {}));
+    let baseline = m.get_one::<String>("baseline").map(PathBuf::from);
+    let diagnostics: Vec<Diagnostic> = files
+        .iter()
+        .flat_map(|file| {
+            let mut diagnostics = lint_spec(file);
+            let old = baseline
+                .as_ref()
+                .zip(file.file_name())
+                .map(|(dir, name)| dir.join(name))
+                .filter(|old| old.is_file());
+            if let Some(old) = old {
+                diagnostics.extend(check_compat(&old, file));
+            }
+            diagnostics
+        })
+        .collect();
+
+    match m.get_one::<String>("format").map(String::as_str) {
+        Some("json") => {
+            let out: Vec<_> = diagnostics
+                .iter()
+                .map(|d| {
+                    json!({
+                        "file": d.file.display().to_string(),
+                        "line": d.line,
+                        "column": d.column,
+                        "severity": "error",
+                        "message": d.message,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::Value::Array(out));
+        }
+        _ => diagnostics.iter().for_each(|d| println!("{d}")),
+    }
+
+    if !diagnostics.is_empty() {
+        eprintln!(
+            "canandmessage-lint: {} error(s) in {} spec(s)",
+            diagnostics.len(),
+            files.len()
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Expands folders into the `.toml` files in them.
+fn spec_files(specs: &[String]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for spec in specs {
+        let path = Path::new(spec);
+        if !path.is_dir() {
+            files.push(path.to_path_buf());
+            continue;
+        }
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let path_buf = entry?.path();
+            if path_buf.extension().and_then(|e| e.to_str()) == Some("toml") {
+                found.push(path_buf);
+            }
+        }
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
@@ -0,0 +1,286 @@
+use std::{path::Path, process::Command};
+
+fn lint(args: &[&str]) -> (Option<i32>, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_canandmessage-lint"))
+        .args(args)
+        .output()
+        .unwrap();
+    (out.status.code(), String::from_utf8(out.stdout).unwrap())
+}
+
+fn messages() -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../messages")
+}
+
+#[test]
+fn test_shipped_specs_are_clean() {
+    let (code, out) = lint(&[messages().to_str().unwrap()]);
+    assert_eq!((code, out.as_str()), (Some(0), ""));
+}
+
+#[test]
+fn test_broken_spec() {
+    let dir = std::env::temp_dir().join(format!("canandmessage-lint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        messages().join("cananddevice.toml"),
+        dir.join("cananddevice.toml"),
+    )
+    .unwrap();
+    // YAW_OUTPUT takes the id of ANGULAR_POSITION_OUTPUT and loses its frame period setting
+    let gyro = std::fs::read_to_string(messages().join("canandgyro.toml"))
+        .unwrap()
+        .replacen("id = 31", "id = 30", 1)
+        .replacen("\"YAW_FRAME_PERIOD\"", "\"NO_SUCH_SETTING\"", 1);
+    let spec = dir.join("canandgyro.toml");
+    std::fs::write(&spec, gyro).unwrap();
+
+    let (code, out) = lint(&["--format", "gcc", spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 2, "{out}");
+    assert!(lines[0].ends_with(
+        "canandgyro.toml:24:1: error: messages ANGULAR_POSITION_OUTPUT, YAW_OUTPUT share id 30"
+    ));
+    assert!(lines[1].contains("no frame period setting named NO_SUCH_SETTING"));
+
+    let (code, out) = lint(&["--format", "json", spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    let diagnostics: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(diagnostics.as_array().unwrap().len(), 2);
+    assert_eq!(diagnostics[0]["line"], 24);
+    assert_eq!(diagnostics[0]["severity"], "error");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_catch_all_unknown_entry() {
+    let dir = std::env::temp_dir().join(format!("canandmessage-lint-ca-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        messages().join("cananddevice.toml"),
+        dir.join("cananddevice.toml"),
+    )
+    .unwrap();
+    let color = std::fs::read_to_string(messages().join("canandcolor.toml"))
+        .unwrap()
+        .replacen(
+            "[enums.EXTRA_FRAME_MODE.values]\n",
+            "[enums.EXTRA_FRAME_MODE.values]\nUNKNOWN = { id = 3, comment = \"Unknown\" }\n",
+            1,
+        );
+    let spec = dir.join("canandcolor.toml");
+    std::fs::write(&spec, color).unwrap();
+
+    let (code, out) = lint(&["--format", "gcc", spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(
+        out.contains("enum EXTRA_FRAME_MODE: catch_all adds an Unknown variant"),
+        "{out}"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_baseline_compat() {
+    let dir = std::env::temp_dir().join(format!("canandmessage-lint-bl-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        messages().join("cananddevice.toml"),
+        dir.join("cananddevice.toml"),
+    )
+    .unwrap();
+    let gyro = std::fs::read_to_string(messages().join("canandgyro.toml")).unwrap();
+    let spec = dir.join("canandgyro.toml");
+    let baseline = messages();
+    let baseline = baseline.to_str().unwrap();
+
+    // a new signal in the reserved bits of STATUS is fine
+    let status_reserved =
+        "{ name = \"reserved\",      dtype = \"reserved:32\", comment = \"Reserved bits\"}";
+    std::fs::write(
+        &spec,
+        gyro.replacen(
+            status_reserved,
+            "{ name = \"uptime\", dtype = \"uint:16\", comment = \"Uptime\"},\n    \
+             { name = \"reserved\", dtype = \"reserved:16\", comment = \"Reserved bits\"}",
+            1,
+        ),
+    )
+    .unwrap();
+    let (code, out) = lint(&["--baseline", baseline, spec.to_str().unwrap()]);
+    assert_eq!((code, out.as_str()), (Some(0), ""));
+
+    // padding isn't reserved, and old signals stay put
+    std::fs::write(
+        &spec,
+        gyro.replacen(
+            status_reserved,
+            "{ name = \"reserved\", dtype = \"pad:32\", comment = \"\"}",
+            1,
+        )
+        .replacen(
+            "{ name = \"temperature\",   dtype = \"temperature\",",
+            "{ name = \"uptime\", dtype = \"uint:16\", comment = \"Uptime\"},\n    \
+                 { name = \"temperature\",   dtype = \"temperature\",",
+            1,
+        ),
+    )
+    .unwrap();
+    let (code, out) = lint(&["--baseline", baseline, spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(
+        out.contains("message STATUS: signal temperature no longer has type sint:16 at bit 16"),
+        "{out}"
+    );
+    assert!(
+        out.contains("message STATUS: new signal uptime takes bits 16..32, which weren't reserved"),
+        "{out}"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_muxed_message() {
+    let dir = std::env::temp_dir().join(format!("canandmessage-lint-mux-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        messages().join("cananddevice.toml"),
+        dir.join("cananddevice.toml"),
+    )
+    .unwrap();
+    let paged = r#"name = "Paged"
+base = ["CanandDevice"]
+arch = "esp32c3"
+dev_type = 9
+dev_class = 0
+
+[vendordep]
+java_package = "com.reduxrobotics.misc.paged"
+cpp_namespace = "redux::misc::paged"
+
+[msg.PAGED_OUTPUT]
+id = 20
+min_length = 1
+max_length = 4
+source = "device"
+comment = "Paged output"
+signals = [
+    { name = "page", dtype = "enum:PAGE", mux = true, comment = "Which page follows" },
+    { name = "speed", dtype = "sint:16", muxed_by = "page", muxed_match = ["SPEED", "BOTH"], comment = "Speed" },
+    { name = "lo", dtype = "uint:12", muxed_by = "page", muxed_match = ["LIMITS"], comment = "Low limit" },
+    { name = "hi", dtype = "uint:12", muxed_by = "page", muxed_match = ["LIMITS"], comment = "High limit" },
+]
+
+[enums.PAGE]
+btype = "uint"
+bits = 8
+default_value = "SPEED"
+comment = "Pages"
+[enums.PAGE.values]
+SPEED = { id = 0, comment = "Speed page" }
+LIMITS = { id = 1, comment = "Limits page" }
+BOTH = { id = 3, comment = "Both pages" }
+"#;
+    let spec = dir.join("paged.toml");
+
+    // each page fits the frame on its own
+    std::fs::write(&spec, paged).unwrap();
+    let (code, out) = lint(&[spec.to_str().unwrap()]);
+    assert_eq!((code, out.as_str()), (Some(0), ""));
+
+    std::fs::write(
+        &spec,
+        paged.replacen("\"uint:12\", muxed_by", "\"uint:16\", muxed_by", 1),
+    )
+    .unwrap();
+    let (code, out) = lint(&[spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(
+        out.contains("message PAGED_OUTPUT: signals take 36 bits, but the frame holds at most 32"),
+        "{out}"
+    );
+
+    std::fs::write(&spec, paged.replacen("[\"LIMITS\"]", "[\"LIMIT\"]", 1)).unwrap();
+    let (code, out) = lint(&[spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(
+        out.contains(
+            "message PAGED_OUTPUT: signal lo matches LIMIT, which isn't an entry of enum PAGE"
+        ),
+        "{out}"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_fd_big_endian_message() {
+    let dir = std::env::temp_dir().join(format!("canandmessage-lint-fd-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        messages().join("cananddevice.toml"),
+        dir.join("cananddevice.toml"),
+    )
+    .unwrap();
+    let wide = r#"name = "Wide"
+base = ["CanandDevice"]
+arch = "esp32c3"
+dev_type = 9
+dev_class = 0
+
+[vendordep]
+java_package = "com.reduxrobotics.misc.wide"
+cpp_namespace = "redux::misc::wide"
+
+[msg.WIDE_OUTPUT]
+id = 20
+length = 16
+source = "device"
+vendordep = false
+is_public = false
+comment = "Wide output"
+signals = [
+    { name = "count", dtype = "uint:32", byte_order = "big", comment = "Count" },
+    { name = "flags", dtype = "uint:4", comment = "Flags" },
+    { name = "pad", dtype = "pad:4", comment = "" },
+    { name = "temp", dtype = "sint:16", byte_order = "big", comment = "Temperature" },
+    { name = "energy", dtype = "float:64", byte_order = "big", comment = "Energy" },
+]
+"#;
+    let spec = dir.join("wide.toml");
+
+    std::fs::write(&spec, wide).unwrap();
+    let (code, out) = lint(&[spec.to_str().unwrap()]);
+    assert_eq!((code, out.as_str()), (Some(0), ""));
+
+    std::fs::write(&spec, wide.replacen("length = 16", "length = 10", 1)).unwrap();
+    let (code, out) = lint(&[spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(
+        out.contains("message WIDE_OUTPUT: 10 bytes isn't a CAN FD frame length"),
+        "{out}"
+    );
+
+    std::fs::write(&spec, wide.replacen("length = 16", "length = 72", 1)).unwrap();
+    let (code, out) = lint(&[spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(
+        out.contains("message WIDE_OUTPUT: 72 bytes is longer than a CAN FD frame"),
+        "{out}"
+    );
+
+    // temp would start half a byte in
+    std::fs::write(&spec, wide.replacen("\"pad:4\"", "\"pad:8\"", 1)).unwrap();
+    let (code, out) = lint(&[spec.to_str().unwrap()]);
+    assert_eq!(code, Some(1));
+    assert!(
+        out.contains("message WIDE_OUTPUT: big-endian signal temp takes bits 44..60"),
+        "{out}"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
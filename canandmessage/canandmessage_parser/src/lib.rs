@@ -3,11 +3,12 @@ use std::{collections::BTreeMap, path::Path};
 use std::{error, fs};
 use toml_defs::{DeviceSpec, EnumEntrySpec, EnumSpec};
 
+pub mod lint;
 pub mod model_impl;
 pub mod toml_defs;
 pub mod utils;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct UIntMeta {
     pub width: usize,
     pub min: Option<u64>,
@@ -15,10 +16,13 @@ pub struct UIntMeta {
     pub default_value: u64,
     pub factor_num: i64,
     pub factor_den: i64,
-    // not implemented: offset
+    /// Added after scaling: value = raw * factor_num / factor_den + offset
+    pub offset: f64,
+    /// Unit of the scaled value, e.g. `"deg/s"`
+    pub unit: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct SIntMeta {
     pub width: usize,
     pub min: Option<i64>,
@@ -26,10 +30,13 @@ pub struct SIntMeta {
     pub default_value: i64,
     pub factor_num: i64,
     pub factor_den: i64,
-    // not implemented: offset
+    /// Added after scaling: value = raw * factor_num / factor_den + offset
+    pub offset: f64,
+    /// Unit of the scaled value, e.g. `"deg/s"`
+    pub unit: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct FloatMeta {
     pub width: usize,
     pub min: Option<f64>,
@@ -38,7 +45,10 @@ pub struct FloatMeta {
     pub allow_nan_inf: bool,
     pub factor_num: i64,
     pub factor_den: i64,
-    // not implemented: offset
+    /// Added after scaling: value = raw * factor_num / factor_den + offset
+    pub offset: f64,
+    /// Unit of the scaled value, e.g. `"deg/s"`
+    pub unit: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -63,6 +73,8 @@ pub struct EnumMeta {
     pub width: usize,
     pub default_value: String, // default "index"
     pub is_public: bool,
+    /// Whether values without an entry decode to an `Unknown` variant
+    pub catch_all: bool,
     pub values: BTreeMap<u64, EnumEntry>,
 }
 
@@ -88,6 +100,14 @@ pub struct BitsetMeta {
     pub flags: Vec<BitsetFlag>,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArrayMeta {
+    /// Element type
+    pub dtype: Box<DType>,
+    /// Number of elements
+    pub len: usize,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum DType {
     None,
@@ -97,9 +117,15 @@ pub enum DType {
     Float { meta: FloatMeta },
     Bitset { meta: BitsetMeta },
     Pad { width: usize },
+    /// Space set aside for signals added in later minor revisions, e.g. `reserved:32`. Unlike
+    /// padding, its bits are decoded and re-encoded as they were, so a message passed through a
+    /// host that doesn't know the newer signals yet keeps them.
+    Reserved { width: usize },
     Bool { default_value: bool },
     Enum { meta: EnumMeta },
     Struct { meta: StructMeta },
+    /// Fixed-length array, e.g. `uint:12[4]`. Elements are packed back to back, index 0 first.
+    Array { meta: ArrayMeta },
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -108,7 +134,33 @@ pub struct Signal {
     pub comment: String,
     pub dtype: DType,
     pub optional: bool,
-    // NOT implemented: mux, muxed_by, muxed_match
+    /// Selects which of the message's muxed signals are present; always an enum
+    pub mux: bool,
+    /// Set if the signal is only present for some values of the message's multiplexor
+    pub muxed_by: Option<MuxedBy>,
+    /// Sent most significant byte first; such signals are 8, 16, 32 or 64 bits starting on a byte
+    pub big_endian: bool,
+}
+
+/// Which values of a message's multiplexor a muxed signal is present for.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MuxedBy {
+    /// Name of the multiplexor signal
+    pub selector: String,
+    /// Multiplexor values, ascending
+    pub values: Vec<u64>,
+}
+
+/// The muxed signals present for one value of a message's multiplexor.
+#[derive(Debug, Clone)]
+pub struct MuxGroup<'a> {
+    pub value: u64,
+    /// Name of the multiplexor's enum entry for `value`
+    pub name: &'a str,
+    /// In frame order; the first starts right after the message's unmuxed signals
+    pub signals: Vec<&'a Signal>,
+    /// Bit just past the last of `signals`
+    pub end: usize,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -128,6 +180,18 @@ pub struct Message {
     pub is_public: bool,
     pub signals: Vec<Signal>,
     pub origin_lname: String,
+    pub frame_period: Option<FramePeriod>,
+}
+
+/// The setting that controls how often a periodic frame is sent.
+#[derive(Debug, Clone)]
+pub struct FramePeriod {
+    pub setting: String,
+    pub setting_id: u8,
+    /// Shortest allowed period in milliseconds; 0 allows the frame to be turned off
+    pub min: u64,
+    /// Longest allowed period in milliseconds
+    pub max: u64,
 }
 
 #[derive(Debug)]
@@ -144,6 +208,25 @@ pub struct Setting {
     pub special_flags: Vec<String>,
     pub origin_lname: String,
 }
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CompositeEncoding {
+    /// Raw bytes
+    Bytes,
+    /// UTF-8 text, padded with trailing zeros
+    Utf8,
+}
+
+/// A value too long for one setting, split across several `buf:48` settings.
+#[derive(Debug)]
+pub struct CompositeSetting {
+    pub name: String,
+    pub comment: String,
+    /// Setting ids of the parts, lowest bytes first
+    pub parts: Vec<u8>,
+    pub encoding: CompositeEncoding,
+}
+
 #[derive(Debug)]
 pub struct Device {
     pub name: String,
@@ -152,6 +235,7 @@ pub struct Device {
     pub dev_class: u8,
     pub messages: BTreeMap<String, Message>,
     pub settings: BTreeMap<String, Setting>,
+    pub composite_settings: BTreeMap<String, CompositeSetting>,
     pub enums: BTreeMap<String, EnumMeta>,
     pub structs: BTreeMap<String, StructMeta>,
     pub bitsets: BTreeMap<String, BitsetMeta>,
@@ -164,6 +248,7 @@ fn regen_settings_enum(spec: &DeviceSpec) -> EnumSpec {
         btype: "uint".to_string().to_owned(),
         bits: 8,
         is_public: true,
+        catch_all: false,
         default_value: "".to_string().to_owned(),
         values: spec
             .settings
@@ -187,6 +272,7 @@ fn regen_setting_commands_enum(spec: &DeviceSpec) -> EnumSpec {
         btype: "uint".to_string().to_owned(),
         bits: 8,
         is_public: true,
+        catch_all: false,
         default_value: "".to_string().to_owned(),
         values: spec
             .setting_commands
@@ -267,6 +353,11 @@ pub fn parse_spec(spec_path: &Path) -> Result<DeviceSpec, Box<dyn error::Error>>
                         .setting_commands
                         .insert(stg_cmd.0.to_owned(), stg_cmd.1.to_owned());
                 }
+                for composite in upper_dev.composite_settings.iter() {
+                    base_spec
+                        .composite_settings
+                        .insert(composite.0.to_owned(), composite.1.to_owned());
+                }
 
                 // update the setting and setting_command enums
 
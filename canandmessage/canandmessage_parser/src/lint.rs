@@ -0,0 +1,414 @@
+//! Spec checks that report problems instead of panicking.
+//!
+//! Building a [`Device`] panics on the first thing wrong with a spec, which is fine inside the
+//! proc macro but no use for gating a merge. [`lint_spec`] runs the same conversion with the panic
+//! caught, plus a few checks the model never makes (clashing ids, signals that overflow their
+//! frame), and points each problem at the line of the spec it's about. [`check_compat`] compares a
+//! spec against an earlier revision of itself, so a minor revision can't break decoders already out
+//! in the field.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use crate::{toml_defs::DeviceSpec, DType, Device};
+
+/// Longest payload of a classic CAN frame, which messages are unless they say otherwise
+const CLASSIC_FRAME_BYTES: u8 = 8;
+/// Payload lengths a CAN FD frame can have past the classic 8 bytes
+const FD_FRAME_BYTES: [u8; 7] = [12, 16, 20, 24, 32, 48, 64];
+/// Message ids get 5 bits of the FRC CAN id
+const MAX_MESSAGE_ID: u8 = 31;
+/// Settings are carried in the 6 value bytes of a setting frame
+const MAX_SETTING_BITS: usize = 48;
+
+/// A problem with a spec file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column, in characters
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    /// Formats like a gcc diagnostic, which most CI log parsers and editors understand.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: error: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.message
+        )
+    }
+}
+
+/// Checks the spec at `path`, merged with its bases.
+///
+/// Only problems with things defined in `path` itself are reported; a broken base is reported
+/// when the base is linted. Model conversion panics are caught, but the panic hook still runs, so
+/// callers that want clean output should install a quiet hook first.
+pub fn lint_spec(path: &Path) -> Vec<Diagnostic> {
+    let mut lint = Lint {
+        file: path.to_path_buf(),
+        text: String::new(),
+        diagnostics: Vec::new(),
+    };
+    match fs::read_to_string(path) {
+        Ok(text) => lint.text = text,
+        Err(e) => {
+            lint.report(None, format!("can't read spec: {e}"));
+            return lint.diagnostics;
+        }
+    }
+
+    let own: DeviceSpec = match toml::from_str(&lint.text) {
+        Ok(spec) => spec,
+        Err(e) => {
+            let at = e.span().map(|span| line_col(&lint.text, span.start));
+            lint.report(at, e.message().to_string());
+            return lint.diagnostics;
+        }
+    };
+    let spec = match crate::parse_spec(path) {
+        Ok(spec) => spec,
+        Err(e) => {
+            let at = lint.find("", "base");
+            lint.report(at, format!("bases {:?} don't load: {e}", own.base));
+            return lint.diagnostics;
+        }
+    };
+
+    lint.check_ids(&spec);
+    lint.check_lengths(&spec);
+    match panic::catch_unwind(AssertUnwindSafe(|| Device::from(spec.clone()))) {
+        Ok(dev) => lint.check_widths(&dev),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "spec doesn't convert to a device model".to_string());
+            // panic messages name the offending item; point at the first name we can find
+            let at = message
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .filter(|word| !word.is_empty())
+                .find_map(|word| lint.find_anywhere(word));
+            lint.report(at, message);
+        }
+    }
+    lint.diagnostics
+}
+
+/// Checks that the spec at `path` is a compatible revision of the one at `baseline`.
+///
+/// Every message of the baseline has to still be there, with every signal it had at the same bits
+/// and of the same type. New signals may only take bits the baseline set aside as `reserved`, which
+/// decoders built from it pass through untouched; padding is not fair game, as those decoders throw
+/// it away. Specs that don't lint cleanly are skipped, since [`lint_spec`] already reports them.
+pub fn check_compat(baseline: &Path, path: &Path) -> Vec<Diagnostic> {
+    let mut lint = Lint {
+        file: path.to_path_buf(),
+        text: fs::read_to_string(path).unwrap_or_default(),
+        diagnostics: Vec::new(),
+    };
+    let load = |path: &Path| {
+        let spec = crate::parse_spec(path).ok()?;
+        panic::catch_unwind(AssertUnwindSafe(|| Device::from(spec))).ok()
+    };
+    let (Some(old), Some(new)) = (load(baseline), load(path)) else {
+        return lint.diagnostics;
+    };
+    // messages of the bases are checked when the bases are
+    let own: Option<DeviceSpec> = fs::read_to_string(baseline)
+        .ok()
+        .and_then(|text| toml::from_str(&text).ok());
+
+    for (name, old_msg) in old.messages.iter() {
+        let Some(new_msg) = new.messages.get(name) else {
+            if own.as_ref().is_some_and(|own| own.msg.contains_key(name)) {
+                let at = lint.find("", "msg");
+                lint.report(at, format!("message {name} was removed"));
+            }
+            continue;
+        };
+        if new_msg.id != old_msg.id {
+            lint.report_item(
+                "msg",
+                name,
+                format!(
+                    "message {name}: id changed from {} to {}",
+                    old_msg.id, new_msg.id
+                ),
+            );
+        }
+        let old_layout = old_msg.layout();
+        let new_layout = new_msg.layout();
+        for (offset, sig) in old_layout.iter() {
+            if matches!(sig.dtype, DType::Pad { .. } | DType::Reserved { .. }) {
+                continue;
+            }
+            let kept = new_layout.iter().any(|(new_offset, new_sig)| {
+                new_offset == offset
+                    && new_sig.name == sig.name
+                    && new_sig.dtype.canonical_name() == sig.dtype.canonical_name()
+            });
+            if !kept {
+                lint.report_item(
+                    "msg",
+                    name,
+                    format!(
+                        "message {name}: signal {} no longer has type {} at bit {offset}",
+                        sig.name,
+                        sig.dtype.canonical_name()
+                    ),
+                );
+            }
+        }
+        let reserved: Vec<(usize, usize)> = old_layout
+            .iter()
+            .filter(|(_, sig)| matches!(sig.dtype, DType::Reserved { .. }))
+            .map(|(offset, sig)| (*offset, offset + sig.dtype.bit_length()))
+            .collect();
+        for (offset, sig) in new_layout.iter() {
+            if matches!(sig.dtype, DType::Pad { .. } | DType::Reserved { .. })
+                || old_layout
+                    .iter()
+                    .any(|(_, old_sig)| old_sig.name == sig.name)
+            {
+                continue;
+            }
+            let end = offset + sig.dtype.bit_length();
+            if !reserved
+                .iter()
+                .any(|(start, stop)| start <= offset && end <= *stop)
+            {
+                lint.report_item(
+                    "msg",
+                    name,
+                    format!(
+                        "message {name}: new signal {} takes bits {offset}..{end}, which weren't reserved",
+                        sig.name
+                    ),
+                );
+            }
+        }
+    }
+    lint.diagnostics
+}
+
+struct Lint {
+    file: PathBuf,
+    text: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lint {
+    fn report(&mut self, at: Option<(usize, usize)>, message: String) {
+        let (line, column) = at.unwrap_or((1, 1));
+        self.diagnostics.push(Diagnostic {
+            file: self.file.clone(),
+            line,
+            column,
+            // toml errors run over several lines; diagnostics are one line each
+            message: message.trim().lines().collect::<Vec<_>>().join(": "),
+        });
+    }
+
+    /// Reports `message` against `name` in `[table]`, if this file defines it.
+    fn report_item(&mut self, table: &str, name: &str, message: String) {
+        if let Some(at) = self.find(table, name) {
+            self.report(Some(at), message);
+        }
+    }
+
+    /// Where `name` is defined in `[table]`, either as its own `[table.name]` header or as a
+    /// `name = ...` key under `[table]`. The top level is `table == ""`.
+    fn find(&self, table: &str, name: &str) -> Option<(usize, usize)> {
+        let header = if table.is_empty() {
+            format!("[{name}]")
+        } else {
+            format!("[{table}.{name}]")
+        };
+        let mut current = String::new();
+        for (idx, line) in self.text.lines().enumerate() {
+            let trimmed = line.trim_start();
+            let column = line.chars().count() - trimmed.chars().count() + 1;
+            if trimmed.starts_with('[') {
+                if trimmed.trim_end() == header {
+                    return Some((idx + 1, column));
+                }
+                current = trimmed
+                    .trim_end()
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string();
+            } else if current == table
+                && trimmed
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.trim_start().starts_with('='))
+            {
+                return Some((idx + 1, column));
+            }
+        }
+        None
+    }
+
+    /// Where `name` is defined in any table of this file.
+    fn find_anywhere(&self, name: &str) -> Option<(usize, usize)> {
+        let tables = self
+            .text
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with('['))
+            .map(|line| line.trim_matches(|c| c == '[' || c == ']').to_string())
+            .collect::<Vec<_>>();
+        std::iter::once(String::new())
+            .chain(tables)
+            .find_map(|table| self.find(&table, name))
+    }
+
+    /// Messages, settings, setting commands, and enum entries each need ids of their own.
+    fn check_ids(&mut self, spec: &DeviceSpec) {
+        for (name, msg) in spec.msg.iter() {
+            if msg.id > MAX_MESSAGE_ID {
+                self.report_item(
+                    "msg",
+                    name,
+                    format!(
+                        "message {name}: id {} doesn't fit in 5 bits (max {MAX_MESSAGE_ID})",
+                        msg.id
+                    ),
+                );
+            }
+        }
+        self.check_clashes(
+            "msg",
+            "message",
+            spec.msg.iter().map(|(name, msg)| (name, msg.id as u32)),
+        );
+        self.check_clashes(
+            "settings",
+            "setting",
+            spec.settings
+                .iter()
+                .map(|(name, stg)| (name, stg.id as u32)),
+        );
+        self.check_clashes(
+            "setting_commands",
+            "setting command",
+            spec.setting_commands
+                .iter()
+                .map(|(name, cmd)| (name, cmd.id as u32)),
+        );
+        for (enum_name, enum_) in spec.enums.iter() {
+            self.check_clashes(
+                &format!("enums.{enum_name}.values"),
+                &format!("{enum_name} entry"),
+                enum_.values.iter().map(|(name, ent)| (name, ent.id)),
+            );
+            if enum_.catch_all && enum_.values.contains_key("UNKNOWN") {
+                self.report_item(
+                    &format!("enums.{enum_name}.values"),
+                    "UNKNOWN",
+                    format!("enum {enum_name}: catch_all adds an Unknown variant, so UNKNOWN can't be an entry"),
+                );
+            }
+        }
+    }
+
+    fn check_clashes<'a>(
+        &mut self,
+        table: &str,
+        kind: &str,
+        items: impl Iterator<Item = (&'a String, u32)>,
+    ) {
+        let mut by_id: BTreeMap<u32, Vec<&String>> = BTreeMap::new();
+        for (name, id) in items {
+            by_id.entry(id).or_default().push(name);
+        }
+        for (id, names) in by_id.into_iter().filter(|(_, names)| names.len() > 1) {
+            let Some(at) = names.iter().find_map(|name| self.find(table, name)) else {
+                continue;
+            };
+            let names = names
+                .iter()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.report(Some(at), format!("{kind}s {names} share id {id}"));
+        }
+    }
+
+    fn check_lengths(&mut self, spec: &DeviceSpec) {
+        for (name, msg) in spec.msg.iter() {
+            let max = msg.length.or(msg.max_length).unwrap_or(CLASSIC_FRAME_BYTES);
+            let min = msg.length.or(msg.min_length).unwrap_or(0);
+            if max > CLASSIC_FRAME_BYTES && !FD_FRAME_BYTES.contains(&max) {
+                let why = match max > FD_FRAME_BYTES[FD_FRAME_BYTES.len() - 1] {
+                    true => format!("{max} bytes is longer than a CAN FD frame"),
+                    false => format!(
+                        "{max} bytes isn't a CAN FD frame length, which past 8 are {FD_FRAME_BYTES:?}"
+                    ),
+                };
+                self.report_item("msg", name, format!("message {name}: {why}"));
+            }
+            if min > max {
+                self.report_item(
+                    "msg",
+                    name,
+                    format!("message {name}: min_length {min} is more than max_length {max}"),
+                );
+            }
+        }
+    }
+
+    /// Signals have to fit in their frame, and settings in a setting frame. A muxed message only
+    /// has to fit the signals of one multiplexor value at a time.
+    fn check_widths(&mut self, dev: &Device) {
+        for (name, msg) in dev.messages.iter() {
+            let bits = msg
+                .mux_groups()
+                .iter()
+                .map(|group| group.end)
+                .fold(msg.mux_offset(), usize::max);
+            if bits > msg.max_length as usize * 8 {
+                self.report_item(
+                    "msg",
+                    name,
+                    format!(
+                        "message {name}: signals take {bits} bits, but the frame holds at most {}",
+                        msg.max_length as usize * 8
+                    ),
+                );
+            }
+        }
+        for (name, stg) in dev.settings.iter() {
+            let bits = stg.dtype.bit_length();
+            if bits > MAX_SETTING_BITS {
+                self.report_item(
+                    "settings",
+                    name,
+                    format!("setting {name}: {bits} bits is more than the {MAX_SETTING_BITS} a setting holds"),
+                );
+            }
+        }
+    }
+}
+
+/// 1-based line and column of a byte offset into `text`.
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
@@ -1,10 +1,14 @@
 use crate::toml_defs::{self, DeviceSpec, TypeSpec};
 use crate::utils::{
     decode_bounds_f64, default_sint_max, default_sint_min, default_uint_max, opt_value_to_opt_bool,
-    opt_value_to_opt_f64, opt_value_to_opt_i64, opt_value_to_opt_u64, read_suffix,
-    read_suffix_as_usize,
+    opt_value_to_opt_f64, opt_value_to_opt_i64, opt_value_to_opt_u64, read_array_suffix,
+    read_suffix, read_suffix_as_usize,
 };
-use crate::{BitsetMeta, DType, Device, EnumMeta, Message, Setting, Signal, Source, StructMeta};
+use crate::{
+    BitsetMeta, CompositeEncoding, CompositeSetting, DType, Device, EnumMeta, FramePeriod, Message,
+    MuxGroup, MuxedBy, Setting, Signal, Source, StructMeta,
+};
+use std::collections::BTreeMap;
 
 //pub mod model;
 
@@ -18,6 +22,7 @@ impl DType {
             DType::Float { meta } => meta.width,
             DType::Bitset { meta } => meta.width,
             DType::Pad { width } => *width,
+            DType::Reserved { width } => *width,
             DType::Bool { default_value: _ } => 1,
             DType::Enum { meta } => meta.width,
             DType::Struct { meta } => meta
@@ -26,6 +31,7 @@ impl DType {
                 .into_iter()
                 .map(|x| x.dtype.bit_length())
                 .sum(),
+            DType::Array { meta } => meta.dtype.bit_length() * meta.len,
         }
     }
 
@@ -46,9 +52,11 @@ impl DType {
             DType::Float { meta } => format!("float:{}", meta.width),
             DType::Bitset { meta } => format!("uint:{}", meta.width),
             DType::Pad { width } => format!("pad:{}", width),
+            DType::Reserved { width } => format!("reserved:{}", width),
             DType::Bool { .. } => format!("bool"),
             DType::Enum { meta } => format!("enum:{}", meta.name),
             DType::Struct { meta } => format!("struct:{}", meta.name),
+            DType::Array { meta } => format!("{}[{}]", meta.dtype.canonical_name(), meta.len),
         }
     }
 
@@ -89,6 +97,8 @@ impl DType {
                         default_value: opt_value_to_opt_u64(default_value).unwrap_or(0),
                         factor_num: type_def.factor[0],
                         factor_den: type_def.factor[1],
+                        offset: opt_value_to_opt_f64(&type_def.offset).unwrap_or(0.0),
+                        unit: type_def.unit.clone(),
                     },
                 }
             }
@@ -116,6 +126,8 @@ impl DType {
                         default_value: opt_value_to_opt_i64(default_value).unwrap_or(0i64),
                         factor_num: type_def.factor[0],
                         factor_den: type_def.factor[1],
+                        offset: opt_value_to_opt_f64(&type_def.offset).unwrap_or(0.0),
+                        unit: type_def.unit.clone(),
                     },
                 }
             }
@@ -138,6 +150,8 @@ impl DType {
                         allow_nan_inf: type_def.allow_nan_inf,
                         factor_num: type_def.factor[0],
                         factor_den: type_def.factor[1],
+                        offset: opt_value_to_opt_f64(&type_def.offset).unwrap_or(0.0),
+                        unit: type_def.unit.clone(),
                     },
                 }
             }
@@ -147,6 +161,9 @@ impl DType {
             "pad" => DType::Pad {
                 width: type_def.bits as usize,
             },
+            "reserved" => DType::Reserved {
+                width: type_def.bits as usize,
+            },
             "bool" => DType::Bool {
                 default_value: opt_value_to_opt_u64(default_value).unwrap_or(0) > 0,
             },
@@ -170,7 +187,21 @@ impl DType {
         default_value: &Option<toml::Value>,
     ) -> Self {
         // this function allows "inline" typedefs, unlike from_type
-        if dtype_name == "none" {
+        if let Some((elem_name, len)) = read_array_suffix(dtype_name) {
+            let dtype = DType::from_sig(dev, &elem_name, default_value);
+            if matches!(
+                dtype,
+                DType::None | DType::Pad { .. } | DType::Reserved { .. } | DType::Array { .. }
+            ) {
+                panic!("{dtype_name}: arrays of none, pad, reserved or arrays are not supported");
+            }
+            DType::Array {
+                meta: crate::ArrayMeta {
+                    dtype: Box::new(dtype),
+                    len,
+                },
+            }
+        } else if dtype_name == "none" {
             DType::None
         } else if dtype_name.starts_with("buf:") {
             DType::Buf {
@@ -191,6 +222,8 @@ impl DType {
                     default_value: opt_value_to_opt_u64(default_value).unwrap_or(0u64),
                     factor_num: 1,
                     factor_den: 1,
+                    offset: 0.0,
+                    unit: None,
                 },
             }
         } else if dtype_name.starts_with("sint") {
@@ -203,6 +236,8 @@ impl DType {
                     default_value: opt_value_to_opt_i64(default_value).unwrap_or(0i64),
                     factor_num: 1,
                     factor_den: 1,
+                    offset: 0.0,
+                    unit: None,
                 },
             }
         } else if dtype_name.starts_with("float:") {
@@ -215,12 +250,18 @@ impl DType {
                     allow_nan_inf: true,
                     factor_num: 1,
                     factor_den: 1,
+                    offset: 0.0,
+                    unit: None,
                 },
             }
         } else if dtype_name.starts_with("pad:") {
             DType::Pad {
                 width: read_suffix_as_usize(dtype_name),
             }
+        } else if dtype_name.starts_with("reserved:") {
+            DType::Reserved {
+                width: read_suffix_as_usize(dtype_name),
+            }
         } else if dtype_name == "bool" {
             DType::Bool {
                 default_value: opt_value_to_opt_bool(default_value).unwrap_or(false),
@@ -264,14 +305,44 @@ impl DType {
     }
 }
 
-// TODO: add mux support. i can't be assed to do this
 impl Signal {
     fn from(sgnl: &toml_defs::MessageSignalSpec, dev: &toml_defs::DeviceSpec) -> Self {
+        let dtype = DType::from_sig(dev, &sgnl.dtype, &sgnl.default_value);
+        if sgnl.optional && matches!(dtype, DType::Array { .. }) {
+            panic!("signal {}: optional arrays are not supported", sgnl.name);
+        }
+        // muxed_by is resolved against the multiplexor by the message
         Self {
             name: sgnl.name.to_owned(),
             comment: sgnl.comment.to_owned(),
-            dtype: DType::from_sig(dev, &sgnl.dtype, &sgnl.default_value),
+            dtype,
             optional: sgnl.optional,
+            mux: sgnl.mux,
+            muxed_by: None,
+            big_endian: match sgnl.byte_order.as_deref() {
+                None | Some("little") => false,
+                Some("big") => true,
+                Some(other) => panic!(
+                    "signal {}: byte_order is \"little\" or \"big\", not {other:?}",
+                    sgnl.name
+                ),
+            },
+        }
+    }
+
+    /// The signal for element `idx` of an array signal, named `{name}_{idx}`.
+    pub fn array_element(&self, idx: usize) -> Self {
+        let DType::Array { meta } = &self.dtype else {
+            panic!("signal {} is not an array", self.name);
+        };
+        Self {
+            name: format!("{}_{}", self.name, idx),
+            comment: format!("{} [{}]", self.comment, idx),
+            dtype: (*meta.dtype).clone(),
+            optional: false,
+            mux: false,
+            muxed_by: self.muxed_by.clone(),
+            big_endian: false,
         }
     }
     pub fn from_stg(name: &String, stg: &Setting) -> Self {
@@ -280,6 +351,9 @@ impl Signal {
             comment: stg.comment.to_owned(),
             dtype: stg.dtype.clone(),
             optional: false,
+            mux: false,
+            muxed_by: None,
+            big_endian: false,
         }
     }
 }
@@ -290,6 +364,9 @@ impl From<&Setting> for Signal {
             comment: "setting value".to_string(),
             dtype: value.dtype.clone(),
             optional: false,
+            mux: false,
+            muxed_by: None,
+            big_endian: false,
         }
     }
 }
@@ -317,21 +394,270 @@ impl From<&String> for Source {
 }
 
 impl Message {
-    fn from(dm: &toml_defs::DeviceMessageSpec, dev: &toml_defs::DeviceSpec) -> Self {
+    fn from(name: &String, dm: &toml_defs::DeviceMessageSpec, dev: &toml_defs::DeviceSpec) -> Self {
         let (min_length, max_length) = match dm.length {
             Some(len) => (len, len),
             None => (dm.min_length.unwrap_or(0u8), dm.max_length.unwrap_or(8u8)),
         };
 
-        Message {
+        let mut signals: Vec<Signal> = dm.signals.iter().map(|v| Signal::from(v, dev)).collect();
+        resolve_mux(name, &dm.signals, &mut signals);
+
+        let msg = Message {
             id: dm.id,
             min_length: min_length,
             max_length: max_length,
             comment: dm.comment.to_owned(),
             is_public: dm.is_public,
-            signals: dm.signals.iter().map(|v| Signal::from(v, dev)).collect(),
+            signals,
             source: (&dm.source).into(),
             origin_lname: dev.name.to_lowercase(),
+            frame_period: dm
+                .frame_period_setting
+                .as_ref()
+                .map(|setting| FramePeriod::from(name, setting, dev)),
+        };
+        check_byte_order(name, &msg);
+        msg
+    }
+
+    /// Whether the message packs into one little-endian 64-bit integer, as the vendordeps pack
+    /// messages.
+    pub fn fits_u64(&self) -> bool {
+        self.max_length <= 8 && !self.signals.iter().any(|sig| sig.big_endian)
+    }
+
+    /// The signal selecting which muxed signals are present, if the message has one.
+    pub fn mux(&self) -> Option<&Signal> {
+        self.signals.iter().find(|sig| sig.mux)
+    }
+
+    /// Signals present whatever the multiplexor says, the multiplexor included.
+    pub fn plain_signals(&self) -> impl Iterator<Item = &Signal> {
+        self.signals.iter().filter(|sig| sig.muxed_by.is_none())
+    }
+
+    /// Bit the muxed signals start at, right after the plain ones.
+    pub fn mux_offset(&self) -> usize {
+        self.plain_signals().map(|sig| sig.dtype.bit_length()).sum()
+    }
+
+    /// The muxed signals for each multiplexor value that has any, ascending by value.
+    pub fn mux_groups(&self) -> Vec<MuxGroup<'_>> {
+        let Some(DType::Enum { meta }) = self.mux().map(|sig| &sig.dtype) else {
+            return Vec::new();
+        };
+        let mut groups: BTreeMap<u64, Vec<&Signal>> = BTreeMap::new();
+        for sig in self.signals.iter() {
+            for value in sig.muxed_by.iter().flat_map(|by| by.values.iter()) {
+                groups.entry(*value).or_default().push(sig);
+            }
+        }
+        let offset = self.mux_offset();
+        groups
+            .into_iter()
+            .map(|(value, signals)| {
+                let width: usize = signals.iter().map(|sig| sig.dtype.bit_length()).sum();
+                MuxGroup {
+                    value,
+                    name: &meta.values[&value].name,
+                    end: offset + width,
+                    signals,
+                }
+            })
+            .collect()
+    }
+
+    /// Every signal with the bit it starts at. Muxed signals for different multiplexor values
+    /// overlap.
+    pub fn layout(&self) -> Vec<(usize, &Signal)> {
+        let mut offset = 0;
+        let mut layout: Vec<(usize, &Signal)> = self
+            .plain_signals()
+            .map(|sig| {
+                let start = offset;
+                offset += sig.dtype.bit_length();
+                (start, sig)
+            })
+            .collect();
+        for group in self.mux_groups() {
+            let mut offset = offset;
+            for sig in group.signals {
+                if !layout.iter().any(|(_, placed)| placed.name == sig.name) {
+                    layout.push((offset, sig));
+                }
+                offset += sig.dtype.bit_length();
+            }
+        }
+        layout
+    }
+}
+
+/// Fills in `muxed_by` of the message's signals from their specs, checking that the muxing is
+/// something the generators can lay out: one enum multiplexor, with the muxed signals after every
+/// other signal, each starting at the same bit whichever value it's present for.
+/// Big-endian signals are byte-swapped as a whole integer, so they have to be 8, 16, 32 or 64 bits
+/// starting on a byte.
+fn check_byte_order(msg_name: &String, msg: &Message) {
+    for (start, sig) in msg.layout().into_iter().filter(|(_, sig)| sig.big_endian) {
+        if !matches!(
+            sig.dtype,
+            DType::UInt { .. } | DType::SInt { .. } | DType::Float { .. } | DType::Enum { .. }
+        ) {
+            panic!(
+                "message {msg_name}: signal {} can't be big-endian; only ints, floats and enums can",
+                sig.name
+            );
+        }
+        let width = sig.dtype.bit_length();
+        if start % 8 != 0 || !matches!(width, 8 | 16 | 32 | 64) {
+            panic!(
+                "message {msg_name}: big-endian signal {} takes bits {start}..{}, but has to be 8, 16, 32 or 64 bits starting on a byte",
+                sig.name,
+                start + width
+            );
+        }
+    }
+}
+
+fn resolve_mux(msg: &String, specs: &[toml_defs::MessageSignalSpec], signals: &mut [Signal]) {
+    let muxes: Vec<&Signal> = signals.iter().filter(|sig| sig.mux).collect();
+    let mux = match muxes.as_slice() {
+        [] => None,
+        [mux] => Some(*mux),
+        _ => panic!("message {msg}: only one signal can be a mux"),
+    };
+    let mux_meta = match mux {
+        Some(Signal {
+            dtype: DType::Enum { meta },
+            optional: false,
+            ..
+        }) => Some((mux.unwrap().name.clone(), meta.clone())),
+        Some(mux) => panic!(
+            "message {msg}: mux {} has to be a non-optional enum",
+            mux.name
+        ),
+        None => None,
+    };
+
+    let mut seen_muxed = false;
+    for (sig, spec) in signals.iter_mut().zip(specs) {
+        let muxed_by = spec.muxed_by.as_deref().unwrap_or_default();
+        if muxed_by.is_empty() {
+            if seen_muxed {
+                panic!(
+                    "message {msg}: signal {} comes after muxed signals, which have to be last",
+                    sig.name
+                );
+            }
+            continue;
+        }
+        seen_muxed = true;
+        let Some((mux_name, meta)) = mux_meta.as_ref().filter(|(name, _)| name == muxed_by) else {
+            panic!(
+                "message {msg}: signal {} is muxed by {muxed_by}, which isn't a mux of the message",
+                sig.name
+            );
+        };
+        if sig.mux || sig.optional {
+            panic!(
+                "message {msg}: muxed signal {} can't be optional or a mux",
+                sig.name
+            );
+        }
+        let entry = |v: &toml::Value| -> u64 {
+            let found = match v {
+                toml::Value::String(name) => meta
+                    .values
+                    .values()
+                    .find(|ent| &ent.name == name)
+                    .map(|ent| ent.index),
+                toml::Value::Integer(idx) => {
+                    Some(*idx as u64).filter(|idx| meta.values.contains_key(idx))
+                }
+                _ => None,
+            };
+            found.unwrap_or_else(|| {
+                let v = match v {
+                    toml::Value::String(name) => name.to_owned(),
+                    toml::Value::Integer(idx) => idx.to_string(),
+                    other => format!("{other:?}"),
+                };
+                panic!(
+                    "message {msg}: signal {} matches {v}, which isn't an entry of enum {}",
+                    sig.name, meta.name
+                )
+            })
+        };
+        let mut values: Vec<u64> = match &spec.muxed_match {
+            Some(toml::Value::Array(entries)) => entries.iter().map(entry).collect(),
+            Some(toml::Value::Table(range)) => match (range.get("min"), range.get("max")) {
+                (Some(min), Some(max)) => (entry(min)..=entry(max))
+                    .filter(|idx| meta.values.contains_key(idx))
+                    .collect(),
+                _ => panic!(
+                    "message {msg}: signal {}: a muxed_match range needs a min and a max",
+                    sig.name
+                ),
+            },
+            _ => Vec::new(),
+        };
+        values.sort();
+        values.dedup();
+        if values.is_empty() {
+            panic!(
+                "message {msg}: muxed signal {} doesn't match any value of {mux_name}",
+                sig.name
+            );
+        }
+        sig.muxed_by = Some(MuxedBy {
+            selector: mux_name.clone(),
+            values,
+        });
+    }
+
+    // a signal present for several values has to sit at the same bit for each of them
+    let mut ends: BTreeMap<u64, usize> = BTreeMap::new();
+    for sig in signals.iter() {
+        let Some(muxed_by) = &sig.muxed_by else {
+            continue;
+        };
+        let starts: Vec<usize> = muxed_by
+            .values
+            .iter()
+            .map(|value| ends.get(value).copied().unwrap_or(0))
+            .collect();
+        if starts.iter().any(|start| *start != starts[0]) {
+            panic!(
+                "message {msg}: muxed signal {} would start at different bits for different values of {}",
+                sig.name, muxed_by.selector
+            );
+        }
+        for value in muxed_by.values.iter() {
+            ends.insert(*value, starts[0] + sig.dtype.bit_length());
+        }
+    }
+    if let Some((mux_name, _)) = &mux_meta {
+        if ends.is_empty() {
+            panic!("message {msg}: mux {mux_name} doesn't mux any signals");
+        }
+    }
+}
+
+impl FramePeriod {
+    fn from(msg: &String, setting_name: &String, dev: &toml_defs::DeviceSpec) -> Self {
+        let Some(setting) = dev.settings.get(setting_name) else {
+            panic!("message {msg}: no frame period setting named {setting_name}");
+        };
+        let DType::UInt { meta } = DType::from_sig(dev, &setting.dtype, &setting.default_value)
+        else {
+            panic!("message {msg}: frame period setting {setting_name} must be a uint");
+        };
+        FramePeriod {
+            setting: setting_name.to_owned(),
+            setting_id: setting.id,
+            min: meta.min.unwrap_or(0),
+            max: meta.max.unwrap_or(default_uint_max(meta.width)),
         }
     }
 }
@@ -360,6 +686,42 @@ impl Setting {
     }
 }
 
+impl CompositeSetting {
+    fn from(
+        name: &String,
+        spec: &toml_defs::CompositeSettingSpec,
+        dev: &toml_defs::DeviceSpec,
+    ) -> Self {
+        let parts = spec
+            .parts
+            .iter()
+            .map(|part| {
+                let Some(setting) = dev.settings.get(part) else {
+                    panic!("composite setting {name}: no setting named {part}");
+                };
+                if setting.dtype != "buf:48" {
+                    panic!("composite setting {name}: part {part} must be buf:48");
+                }
+                setting.id
+            })
+            .collect::<Vec<u8>>();
+        if parts.is_empty() {
+            panic!("composite setting {name} has no parts");
+        }
+        let encoding = match spec.encoding.as_str() {
+            "bytes" => CompositeEncoding::Bytes,
+            "utf8" => CompositeEncoding::Utf8,
+            other => panic!("composite setting {name}: unknown encoding {other}"),
+        };
+        CompositeSetting {
+            name: name.to_owned(),
+            comment: spec.comment.to_owned(),
+            parts,
+            encoding,
+        }
+    }
+}
+
 impl EnumMeta {
     fn from(name: &String, entry: &toml_defs::EnumSpec, default_value: Option<String>) -> Self {
         let default_value = default_value.unwrap_or(entry.default_value.clone());
@@ -378,6 +740,7 @@ impl EnumMeta {
                 },
             },
             is_public: entry.is_public,
+            catch_all: entry.catch_all,
             values: entry
                 .values
                 .iter()
@@ -440,11 +803,25 @@ impl StructMeta {
             signals: ent
                 .signals
                 .iter()
-                .map(|sig| Signal {
-                    name: sig.name.to_owned(),
-                    comment: sig.comment.to_owned(),
-                    dtype: DType::from_sig(dev, &sig.dtype, &sig.default_value),
-                    optional: sig.optional,
+                .map(|sig| {
+                    if sig.mux || sig.muxed_by.as_ref().is_some_and(|by| !by.is_empty()) {
+                        panic!("struct {name}: signal {}: only messages can mux", sig.name);
+                    }
+                    if sig.byte_order.is_some() {
+                        panic!(
+                            "struct {name}: signal {}: only message signals have a byte_order",
+                            sig.name
+                        );
+                    }
+                    Signal {
+                        name: sig.name.to_owned(),
+                        comment: sig.comment.to_owned(),
+                        dtype: DType::from_sig(dev, &sig.dtype, &sig.default_value),
+                        optional: sig.optional,
+                        mux: false,
+                        muxed_by: None,
+                        big_endian: false,
+                    }
                 })
                 .collect(),
         }
@@ -471,7 +848,7 @@ impl From<toml_defs::DeviceSpec> for Device {
             messages: dev_spec_local
                 .msg
                 .iter()
-                .map(|msg| (msg.0.to_owned(), Message::from(msg.1, &dev_spec_local)))
+                .map(|msg| (msg.0.to_owned(), Message::from(msg.0, msg.1, &dev_spec_local)))
                 .collect(),
             settings: dev_spec_local
                 .settings
@@ -483,6 +860,16 @@ impl From<toml_defs::DeviceSpec> for Device {
                     )
                 })
                 .collect(),
+            composite_settings: dev_spec_local
+                .composite_settings
+                .iter()
+                .map(|(name, ent)| {
+                    (
+                        name.to_owned(),
+                        CompositeSetting::from(name, ent, &dev_spec_local),
+                    )
+                })
+                .collect(),
             enums: dev_spec_local
                 .enums
                 .iter()
@@ -30,6 +30,8 @@ pub struct DeviceSpec {
     pub enums: BTreeMap<String, EnumSpec>,
     #[serde(default = "BTreeMap::new")]
     pub setting_commands: BTreeMap<String, SettingCommandSpec>,
+    #[serde(default = "BTreeMap::new")]
+    pub composite_settings: BTreeMap<String, CompositeSettingSpec>,
 
     pub vendordep: Option<VendordepSpec>,
 }
@@ -53,6 +55,8 @@ pub struct DeviceMessageSpec {
     pub vendordep: bool,
     pub comment: String,
     pub signals: Vec<MessageSignalSpec>,
+    /// Setting controlling how often the device sends this frame
+    pub frame_period_setting: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -67,7 +71,10 @@ pub struct MessageSignalSpec {
     #[serde(default = "bool::default")]
     pub mux: bool,
     pub muxed_by: Option<String>,
-    pub muxed_match: Option<Value>, // TODO: this isn't correct
+    /// Entries of the multiplexor enum this signal is present for, or `{ min = .., max = .. }`
+    pub muxed_match: Option<Value>,
+    /// `"little"` (the default) or `"big"`, for byte-aligned message signals of 8 to 64 bits
+    pub byte_order: Option<String>,
 
     #[serde(default = "default_true")]
     pub alchemist: bool,
@@ -96,6 +103,19 @@ pub struct DeviceSettingSpec {
     pub special_flags: Vec<String>,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct CompositeSettingSpec {
+    pub comment: String,
+    /// Names of the settings holding the value, lowest bytes first
+    pub parts: Vec<String>,
+    #[serde(default = "default_bytes")]
+    pub encoding: String,
+}
+
+fn default_bytes() -> String {
+    String::from("bytes")
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct TypeSpec {
     pub btype: String,
@@ -114,6 +134,7 @@ pub struct TypeSpec {
     #[serde(default = "default_scale")]
     pub factor: [i64; 2],
     pub offset: Option<Value>,
+    pub unit: Option<String>,
     #[serde(default = "Vec::default")]
     pub signals: Vec<MessageSignalSpec>,
     #[serde(default = "Vec::default")]
@@ -141,6 +162,9 @@ pub struct EnumSpec {
     pub bits: u8,
     #[serde(default = "default_true")]
     pub is_public: bool,
+    /// Decode values without an entry to an `Unknown` variant instead of failing
+    #[serde(default)]
+    pub catch_all: bool,
     #[serde(skip, default = "String::default")]
     pub origin_lname: String,
     pub default_value: String,
@@ -33,6 +33,21 @@ pub fn read_suffix_as_usize(s: &String) -> usize {
         .expect("bit length specified but not an usize")
 }
 
+/// Splits an array dtype like `uint:12[4]` into its element dtype and length.
+///
+/// Returns `None` if the dtype isn't an array.
+pub fn read_array_suffix(s: &str) -> Option<(String, usize)> {
+    let inner = s.strip_suffix(']')?;
+    let (elem, len) = inner.rsplit_once('[')?;
+    let len = len
+        .parse::<usize>()
+        .expect("array length specified but not an usize");
+    if len == 0 {
+        panic!("zero-length array in \"{s}\"");
+    }
+    Some((elem.to_string(), len))
+}
+
 pub fn default_uint_max(width: usize) -> u64 {
     if width < 64 {
         (1u64 << width) - 1
@@ -18,6 +18,9 @@ __all__ = [
     'BitsetMeta',
     'PadMeta',
     'BoolMeta',
+    'ArrayMeta',
+    'MuxedBy',
+    'MuxGroup',
     'Signal',
     'Source',
     'Message',
@@ -105,11 +108,32 @@ class BitsetMeta:
 @dataclasses.dataclass
 class PadMeta:
     width: int
+    # reserved for later minor revisions, which hosts that decode it carry over when re-encoding
+    reserved: bool = False
 
 @dataclasses.dataclass
 class BoolMeta:
     default_value: bool
 
+@dataclasses.dataclass
+class ArrayMeta:
+    dtype: 'DType'
+    len: int
+
+@dataclasses.dataclass
+class MuxedBy:
+    """The multiplexor a signal is muxed by, and the values (ascending) it's present for."""
+    selector: str
+    values: List[int]
+
+@dataclasses.dataclass
+class MuxGroup:
+    """The muxed signals present for one multiplexor value, ending at bit `end`."""
+    value: int
+    name: str
+    signals: List['Signal']
+    end: int
+
 #DType = Union[None, UIntMeta, SIntMeta, BufMeta, FloatMeta, BitsetMeta, PadMeta, BoolMeta, EnumMeta, StructMeta]
 
 @dataclasses.dataclass
@@ -118,6 +142,11 @@ class Signal:
     comment: str
     dtype: 'DType'
     optional: bool
+    # selects which of the message's muxed signals are present; always an enum
+    mux: bool = False
+    muxed_by: Optional[MuxedBy] = None
+    # sent most significant byte first; such signals are 8, 16, 32 or 64 bits starting on a byte
+    big_endian: bool = False
 
     @classmethod
     def from_msg(cls, name: str, msg: 'Message') -> Self:
@@ -141,6 +170,19 @@ class Signal:
         )
         pass
 
+    def array_element(self, idx: int) -> Self:
+        """The signal for element `idx` of an array signal, named `{name}_{idx}`."""
+        meta = self.dtype.meta
+        if not isinstance(meta, ArrayMeta):
+            raise ValueError(f"signal {self.name} is not an array")
+        return Signal(
+            name = f"{self.name}_{idx}",
+            comment = f"{self.comment} [{idx}]",
+            dtype = meta.dtype,
+            optional = False,
+            muxed_by = self.muxed_by,
+        )
+
 class Source(enum.StrEnum):
     Device = "Device"
     Host = "Host"
@@ -164,6 +206,55 @@ class Message:
     is_public: bool
     signals: List[Signal]
 
+    def fits_u64(self) -> bool:
+        """Whether the message packs into one little-endian 64-bit integer, as the vendordeps pack messages."""
+        return self.max_length <= 8 and not any(sig.big_endian for sig in self.signals)
+
+    def mux(self) -> Optional[Signal]:
+        """The signal selecting which muxed signals are present, if the message has one."""
+        return next((sig for sig in self.signals if sig.mux), None)
+
+    def plain_signals(self) -> List[Signal]:
+        """Signals present whatever the multiplexor says, the multiplexor included."""
+        return [sig for sig in self.signals if sig.muxed_by is None]
+
+    def mux_offset(self) -> int:
+        """Bit the muxed signals start at, right after the plain ones."""
+        return sum(sig.dtype.bit_length() for sig in self.plain_signals())
+
+    def mux_groups(self) -> List[MuxGroup]:
+        """The muxed signals for each multiplexor value that has any, ascending by value."""
+        mux = self.mux()
+        if mux is None:
+            return []
+        groups: Dict[int, List[Signal]] = {}
+        for sig in self.signals:
+            for value in (sig.muxed_by.values if sig.muxed_by else []):
+                groups.setdefault(value, []).append(sig)
+        offset = self.mux_offset()
+        return [
+            MuxGroup(
+                value = value,
+                name = mux.dtype.meta.values[value].name,
+                signals = signals,
+                end = offset + sum(sig.dtype.bit_length() for sig in signals))
+            for value, signals in sorted(groups.items())]
+
+    def layout(self) -> List[Tuple[int, Signal]]:
+        """Signals with the bit each starts at: plain signals, then each muxed signal once."""
+        layout = []
+        offset = 0
+        for sig in self.plain_signals():
+            layout.append((offset, sig))
+            offset += sig.dtype.bit_length()
+        for group in self.mux_groups():
+            start = offset
+            for sig in group.signals:
+                if not any(placed.name == sig.name for _, placed in layout):
+                    layout.append((start, sig))
+                start += sig.dtype.bit_length()
+        return layout
+
 @dataclasses.dataclass
 class Setting:
     name: str
@@ -192,7 +283,7 @@ class Device:
     bitsets: Dict[str, BitsetMeta]
     spec: DeviceSpec
 
-DTypeOnion = Union[None, UIntMeta, SIntMeta, BufMeta, FloatMeta, BitsetMeta, PadMeta, BoolMeta, EnumMeta, StructMeta]
+DTypeOnion = Union[None, UIntMeta, SIntMeta, BufMeta, FloatMeta, BitsetMeta, PadMeta, BoolMeta, EnumMeta, StructMeta, ArrayMeta]
 class DType:
     def __init__(self, meta: DTypeOnion):
         self.meta = meta
@@ -205,6 +296,8 @@ class DType:
                 return 1
             case StructMeta():
                 return sum(sig.dtype.bit_length() for sig in self.meta.signals)
+            case ArrayMeta():
+                return self.meta.dtype.bit_length() * self.meta.len
             case other:
                 return self.meta.width
     
@@ -221,6 +314,8 @@ class DType:
                 return f"float:{self.meta.width}"
             case BoolMeta():
                 return "bool"
+            case PadMeta(reserved=True):
+                return f"reserved:{self.meta.width}"
             case PadMeta():
                 return f"pad:{self.meta.width}"
             case StructMeta():
@@ -231,6 +326,8 @@ class DType:
                 return f"buf:{self.meta.width}"
             case EnumMeta():
                 return f"enum:{self.meta.name}"
+            case ArrayMeta():
+                return f"{self.meta.dtype.canonical_name()}[{self.meta.len}]"
             case aaa:
                 raise ValueError(f"DType::None encountered: {aaa}")
 
@@ -271,6 +368,12 @@ class DType:
                     return meta.default_value_idx
                 else:
                     return 0
+            case ArrayMeta():
+                ivalue = 0
+                elem_width = meta.dtype.bit_length()
+                for i in range(meta.len):
+                    ivalue |= meta.dtype.default_value_as_bits() << (i * elem_width)
+                return ivalue
             case _:
                 return None
 
@@ -16,7 +16,7 @@ def impl_DType_from_type(type_name: str, type_def: toml_defs.TypeSpec, default_v
                 default_value = unwrap_or(default_value, 0),
                 factor_num = type_def.factor[0],
                 factor_den = type_def.factor[1],
-                offset = 0))
+                offset = unwrap_or(none_map(type_def.offset, float), 0)))
         case "sint":
             return DType(SIntMeta(
                 width = width,
@@ -25,7 +25,7 @@ def impl_DType_from_type(type_name: str, type_def: toml_defs.TypeSpec, default_v
                 default_value = unwrap_or(default_value, 0),
                 factor_num = type_def.factor[0],
                 factor_den = type_def.factor[1],
-                offset = 0))
+                offset = unwrap_or(none_map(type_def.offset, float), 0)))
         case "buf":
             return DType(BufMeta(
                 width = width,
@@ -39,11 +39,13 @@ def impl_DType_from_type(type_name: str, type_def: toml_defs.TypeSpec, default_v
                 allow_nan_inf = type_def.allow_nan_inf,
                 factor_num = type_def.factor[0],
                 factor_den = type_def.factor[1],
-                offset = 0))
+                offset = unwrap_or(none_map(type_def.offset, float), 0)))
         case "bitset":
             return DType(impl_BitsetMeta_from(type_name, type_def))
         case "pad":
             return DType(PadMeta(width))
+        case "reserved":
+            return DType(PadMeta(width, reserved=True))
         case "bool":
             return DType(BoolMeta(default_value=default_value))
         case "struct":
@@ -53,6 +55,12 @@ def impl_DType_from_type(type_name: str, type_def: toml_defs.TypeSpec, default_v
             pass
 
 def impl_DType_from_sig(dev: toml_defs.DeviceSpec, dtype_name: str, default_value: Any) -> DType:
+    if (array := read_array_suffix(dtype_name)) is not None:
+        elem_name, length = array
+        elem = impl_DType_from_sig(dev, elem_name, default_value)
+        if elem.is_pad() or isinstance(elem.meta, ArrayMeta):
+            panic(ValueError(f"{dtype_name}: arrays of none, pad or arrays are not supported"))
+        return DType(ArrayMeta(dtype = elem, len = length))
     nsplit = dtype_name.split(":")
     match nsplit[0]:
         case "none":
@@ -100,6 +108,9 @@ def impl_DType_from_sig(dev: toml_defs.DeviceSpec, dtype_name: str, default_valu
         case "pad":
             width = int(nsplit[1])
             return DType(PadMeta(width))
+        case "reserved":
+            width = int(nsplit[1])
+            return DType(PadMeta(width, reserved=True))
         case "bool":
             return DType(BoolMeta(bool(default_value)))
         case "setting_data":
@@ -110,13 +121,28 @@ def impl_DType_from_sig(dev: toml_defs.DeviceSpec, dtype_name: str, default_valu
             return impl_DType_from_type(dtype_name, dev.types[dtype_name], default_value, dev)
 
 def impl_Signal_from(sgnl: toml_defs.MessageSignalSpec, dev: toml_defs.DeviceSpec) -> Signal:
+    dtype = impl_DType_from_sig(dev, sgnl.dtype, sgnl.default_value)
+    if sgnl.optional and isinstance(dtype.meta, ArrayMeta):
+        panic(ValueError(f"signal {sgnl.name}: optional arrays are not supported"))
     return Signal(
         name = sgnl.name,
         comment = sgnl.comment,
-        dtype = impl_DType_from_sig(dev, sgnl.dtype, sgnl.default_value),
-        optional = sgnl.optional
+        dtype = dtype,
+        optional = sgnl.optional,
+        # muxed_by is resolved by the message, which knows its mux
+        mux = sgnl.mux,
+        big_endian = impl_Signal_big_endian(sgnl),
     )
 
+def impl_Signal_big_endian(sgnl: toml_defs.MessageSignalSpec) -> bool:
+    match sgnl.byte_order:
+        case None | "little":
+            return False
+        case "big":
+            return True
+        case other:
+            panic(ValueError(f"signal {sgnl.name}: byte_order is \"little\" or \"big\", not {other!r}"))
+
 def impl_Signal_from_Setting(value: Setting) -> Signal:
     return Signal(
         name = "value", 
@@ -127,21 +153,95 @@ def impl_Signal_from_Setting(value: Setting) -> Signal:
 
 # impl Source.flip and Source.from is on Source
 
-def impl_Message_from(dm: toml_defs.DeviceMessageSpec, dev: toml_defs.DeviceSpec) -> Message:
+def impl_Message_from(name: str, dm: toml_defs.DeviceMessageSpec, dev: toml_defs.DeviceSpec) -> Message:
     if dm.length is not None:
         min_length, max_length = (dm.length, dm.length)
     else:
         min_length, max_length = (unwrap_or(dm.min_length, 0), unwrap_or(dm.max_length, 8))
     
-    return Message(
+    signals = [impl_Signal_from(v, dev) for v in dm.signals]
+    impl_Message_resolve_mux(name, dm.signals, signals)
+    msg = Message(
         id = dm.id, 
         min_length = min_length,
         max_length = max_length,
         comment = dm.comment,
         is_public = dm.is_public,
-        signals = [impl_Signal_from(v, dev) for v in dm.signals],
+        signals = signals,
         source = Source.from_str(dm.source)
     )
+    impl_Message_check_byte_order(name, msg)
+    return msg
+
+def impl_Message_check_byte_order(msg_name: str, msg: Message):
+    """Big-endian signals are byte-swapped as a whole integer, so they have to be 8, 16, 32 or 64 bits starting on a byte."""
+    for start, sig in msg.layout():
+        if not sig.big_endian:
+            continue
+        if not isinstance(sig.dtype.meta, (UIntMeta, SIntMeta, FloatMeta, EnumMeta)):
+            panic(ValueError(f"message {msg_name}: signal {sig.name} can't be big-endian; only ints, floats and enums can"))
+        width = sig.dtype.bit_length()
+        if start % 8 != 0 or width not in (8, 16, 32, 64):
+            panic(ValueError(f"message {msg_name}: big-endian signal {sig.name} takes bits {start}..{start + width}, but has to be 8, 16, 32 or 64 bits starting on a byte"))
+
+def impl_Message_resolve_mux(msg: str, specs: List[toml_defs.MessageSignalSpec], signals: List[Signal]):
+    """Fills in muxed_by from each signal's muxed_by/muxed_match, checking the mux layout rules."""
+    muxes = [sig for sig in signals if sig.mux]
+    if len(muxes) > 1:
+        panic(ValueError(f"message {msg}: only one signal can be a mux"))
+    mux = muxes[0] if muxes else None
+    if mux is not None and (not isinstance(mux.dtype.meta, EnumMeta) or mux.optional):
+        panic(ValueError(f"message {msg}: mux {mux.name} has to be a non-optional enum"))
+
+    seen_muxed = False
+    for sig, spec in zip(signals, specs):
+        if not spec.muxed_by:
+            if seen_muxed:
+                panic(ValueError(f"message {msg}: signal {sig.name} comes after muxed signals, which have to be last"))
+            continue
+        seen_muxed = True
+        if mux is None or mux.name != spec.muxed_by:
+            panic(ValueError(f"message {msg}: signal {sig.name} is muxed by {spec.muxed_by}, which isn't a mux of the message"))
+        if sig.mux or sig.optional:
+            panic(ValueError(f"message {msg}: muxed signal {sig.name} can't be optional or a mux"))
+        meta: EnumMeta = mux.dtype.meta
+
+        def entry(v) -> int:
+            found = None
+            if isinstance(v, str):
+                found = next((ent.index for ent in meta.values.values() if ent.name == v), None)
+            elif isinstance(v, int) and v in meta.values:
+                found = v
+            if found is None:
+                panic(ValueError(f"message {msg}: signal {sig.name} matches {v}, which isn't an entry of enum {meta.name}"))
+            return found
+
+        match spec.muxed_match:
+            case list(entries):
+                values = [entry(v) for v in entries]
+            case {"min": lo, "max": hi}:
+                values = [idx for idx in range(entry(lo), entry(hi) + 1) if idx in meta.values]
+            case dict():
+                panic(ValueError(f"message {msg}: signal {sig.name}: a muxed_match range needs a min and a max"))
+            case _:
+                values = []
+        values = sorted(set(values))
+        if not values:
+            panic(ValueError(f"message {msg}: muxed signal {sig.name} doesn't match any value of {mux.name}"))
+        sig.muxed_by = MuxedBy(selector = mux.name, values = values)
+
+    # a signal present for several values has to sit at the same bit for each of them
+    ends: Dict[int, int] = {}
+    for sig in signals:
+        if sig.muxed_by is None:
+            continue
+        starts = [ends.get(value, 0) for value in sig.muxed_by.values]
+        if any(start != starts[0] for start in starts):
+            panic(ValueError(f"message {msg}: muxed signal {sig.name} would start at different bits for different values of {sig.muxed_by.selector}"))
+        for value in sig.muxed_by.values:
+            ends[value] = starts[0] + sig.dtype.bit_length()
+    if mux is not None and not ends:
+        panic(ValueError(f"message {msg}: mux {mux.name} doesn't mux any signals"))
 
 
 def impl_Setting_from(name: str, value: toml_defs.DeviceSettingSpec, dev: toml_defs.DeviceSpec) -> Setting:
@@ -216,7 +316,7 @@ def impl_Device_from(dev_spec: toml_defs.DeviceSpec) -> Device:
         arch = dev_spec.arch,
         dev_type = dev_spec.dev_type,
         dev_class = dev_spec.dev_class,
-        messages = { name: impl_Message_from(msg, dev_spec) for name, msg in dev_spec.msg.items() },
+        messages = { name: impl_Message_from(name, msg, dev_spec) for name, msg in dev_spec.msg.items() },
         settings = { name: impl_Setting_from(name, stg, dev_spec) for name, stg in dev_spec.settings.items() },
         enums = { name: impl_EnumMeta_from(name, ent, None) for name, ent in dev_spec.enums.items() },
         bitsets = { name: impl_BitsetMeta_from(name, ent) for name, ent in dev_spec.types.items() if ent.btype == "bitset"},
@@ -66,6 +66,8 @@ class MessageSignalSpec(Serde):
     mux: bool = default_false
     muxed_by: typing.Optional[str]
     muxed_match: Anything
+    # "little" (the default) or "big", for byte-aligned message signals of 8 to 64 bits
+    byte_order: typing.Optional[str]
 
 #[derive(Deserialize, Debug, Clone)]
 class DeviceSettingSpec(Serde):
@@ -31,6 +31,16 @@ def read_suffix_as_usize(s: str) -> int:
         panic("hey dumbass you forgot the bit length in \"{s}\"")
     return int(parts[1])
 
+def read_array_suffix(s: str) -> typing.Tuple[str, int] | None:
+    """Splits an array dtype like `uint:12[4]` into its element dtype and length."""
+    if not s.endswith("]") or "[" not in s:
+        return None
+    elem, length = s[:-1].rsplit("[", 1)
+    length = int(length)
+    if length == 0:
+        panic(ValueError(f"zero-length array in \"{s}\""))
+    return elem, length
+
 def default_uint_max(width: int) -> int:
     return (1 << width) - 1
 
@@ -322,6 +322,8 @@ def gen_msg(dev: Device) -> str:
     struct_defs = []
     for name, msg in dev.messages.items():
         if msg.is_public:
+            if not msg.fits_u64():
+                utils.panic(ValueError(f"message {name}: the C++ vendordep packs messages into a uint64_t, so it can't have big-endian signals or more than 8 bytes"))
             indexes.append(f"    /** {msg.comment} */")
             indexes.append(f"    {utils.screaming_snake_to_kamel(name)} = 0x{msg.id:x},\n")
             struct_defs.append(f"/** {msg.comment} struct */")
@@ -130,6 +130,25 @@ def gen_sig_extract(sig: Signal, prefix="", offset=0, apply_prefix=True) -> typi
             for subsig in meta.signals:
                 v, offset = gen_sig_extract(subsig, prefix, offset)
                 extract_value.extend(v)
+        case ArrayMeta():
+            # one extractor per element, plus an indexed one that dispatches to them
+            extract_value = []
+            for i in range(meta.len):
+                v, offset = gen_sig_extract(sig.array_element(i), prefix, offset, apply_prefix)
+                extract_value.extend(v)
+            if not isinstance(meta.dtype.meta, StructMeta):
+                applied_prefix = prefix if apply_prefix else ''
+                cases = NL.join(f"case {i}: return extract{applied_prefix}{name}{i}(field);" for i in range(meta.len))
+                doc = doc_comment(f"Extracts element index of {sig.comment} from {prefix.strip('_')}.\n\n"
+                                f"@param field data bitfield\n"
+                                f"@param index array index, from 0 to {meta.len - 1}\n"
+                                f"@return element index of {sig.name} as a {meta.dtype.canonical_name()}")
+                body = (f"switch (index) {{\n{textwrap.indent(cases, IDENT)}\n"
+                        f"{IDENT}default: throw new IndexOutOfBoundsException(\"{sig.name} index \" + index);\n}}")
+                extract_value.append(f"""{doc}
+public static {get_type_for_dtype(meta.dtype)} extract{applied_prefix}{name}(long field, int index) {{
+{textwrap.indent(body, IDENT)}
+}}""")
         case _:
             is_pad_or_none = True
     if is_pad_or_none:
@@ -137,7 +156,7 @@ def gen_sig_extract(sig: Signal, prefix="", offset=0, apply_prefix=True) -> typi
     if isinstance(extract_value, str):
         doc = doc_comment(f"Extracts {sig.comment} from {prefix.strip('_')}.\n\n"
                         f"@param field data bitfield\n"
-                        f"@return {sig.name} as a {sig.dtype.canonical_name()}")
+                        f"@return {sig.name} as a {sig.dtype.canonical_name()}{scaling_note(sig.dtype)}")
         return [f"""{doc}
 public static {get_type_for_dtype(sig.dtype)} extract{prefix if apply_prefix else ''}{name}(long field) {{
 {textwrap.indent(extract_value, IDENT)}
@@ -145,6 +164,16 @@ public static {get_type_for_dtype(sig.dtype)} extract{prefix if apply_prefix els
     else:
         return extract_value, offset
 
+def scaling_note(dtype: DType) -> str:
+    """How the raw value of a scaled signal converts to the value it stands for, if it's scaled."""
+    meta = dtype.meta
+    if not isinstance(meta, (UIntMeta, SIntMeta, FloatMeta)):
+        return ""
+    if meta.factor_num == meta.factor_den and not meta.offset:
+        return ""
+    offset = f" + {meta.offset}" if meta.offset else ""
+    return f", value = raw * {meta.factor_num}/{meta.factor_den}{offset}"
+
 def gen_check(expr: str, err_msg: str):
     return f"if ({expr}) {{ throw new IllegalArgumentException({err_msg}); }}"
 
@@ -211,6 +240,11 @@ def gen_sig_checks(sig: Signal) -> typing.List[str]:
                     optional = subsig.optional
                 )))
             return checks
+        case ArrayMeta():
+            checks = []
+            for i in range(meta.len):
+                checks.extend(gen_sig_checks(sig.array_element(i)))
+            return checks
         case _:
             return []
         
@@ -233,23 +267,37 @@ def _render_sig(sig: Signal, offset: int) -> typing.Tuple[typing.List[str], typi
             arg.extend(a)
             pack_expr.extend(k)
         return param, arg, pack_expr, offset
+    if isinstance(sig.dtype.meta, ArrayMeta):
+        # Java has no fixed-size arrays, so each element is its own parameter
+        param, arg, pack_expr = [], [], []
+        for i in range(sig.dtype.meta.len):
+            p, a, k, offset = _render_sig(sig.array_element(i), offset)
+            param.extend(p)
+            arg.extend(a)
+            pack_expr.extend(k)
+        return param, arg, pack_expr, offset
 
     jtype = get_type_for_dtype(sig.dtype)
     sig_name = utils.snake_to_stilted_camel(sig.name)
     try:
-        param = f"@param {sig_name} {sig.comment} ({sig.dtype.canonical_name()})"
+        param = f"@param {sig_name} {sig.comment} ({sig.dtype.canonical_name()}{scaling_note(sig.dtype)})"
     except Exception:
         raise ValueError(str(sig))
     arg = f"{jtype} {sig_name}"
     pack_expr = jtype_to_long(sig_name, jtype, offset, sig.dtype.bit_length())
     return ([param], [arg], [pack_expr], offset + sig.dtype.bit_length())
 
-def gen_sigs_pack(name: str, signals: typing.List[Signal], compound_type: str, check_bounds=False) -> str:
+def gen_sigs_pack(name: str, signals: typing.List[Signal], compound_type: str, check_bounds=False, mux_value: typing.Optional[int] = None) -> str:
     params = []
     args = []
     pack_exprs = []
     offset = 0
     for sig in signals:
+        if sig.mux and mux_value is not None:
+            # the constructor is for one value of the multiplexor, so it isn't a parameter
+            pack_exprs.append(rshift_to_long(f"{mux_value}L", offset))
+            offset += sig.dtype.bit_length()
+            continue
         param, arg, pack_expr, offset = _render_sig(sig, offset)
         params.extend(param)
         args.extend(arg)
@@ -284,21 +332,25 @@ def gen_msg(dev: Device) -> str:
     for name, msg in utils.rsort_by_ent_id(dev.messages):
         if not msg.is_public:
             continue
+        if not msg.fits_u64():
+            utils.panic(ValueError(f"message {name}: the Java vendordep packs messages into a long, so it can't have big-endian signals or more than 8 bytes"))
         members.append(
             f"{doc_comment(msg.comment)}\npublic static final int {msg_pad(utils.screaming_snake_to_kamel(name))} = 0x{msg.id:x};")
     
     for name, msg in utils.rsort_by_ent_id(dev.messages):
         if not msg.is_public:
             continue
-        offset = 0
-        for sig in msg.signals:
-            v, offset = gen_sig_extract(sig, prefix=utils.screaming_snake_to_camel(name) + "_", offset=offset)
+        # muxed signals for different multiplexor values overlap, so each starts where it's laid out
+        for offset, sig in msg.layout():
+            v, _ = gen_sig_extract(sig, prefix=utils.screaming_snake_to_camel(name) + "_", offset=offset)
             members.extend(v)
     
     for name, msg in utils.rsort_by_ent_id(dev.messages):
         if not msg.is_public:
             continue
-        members.append(gen_sigs_pack(name, msg.signals, "message"))
+        members.append(gen_sigs_pack(name, msg.plain_signals(), "message"))
+        for group in msg.mux_groups():
+            members.append(gen_sigs_pack(f"{name}_{group.name}", msg.plain_signals() + group.signals, "message", mux_value=group.value))
     
     for name, msg in utils.rsort_by_ent_id(dev.messages):
         if not msg.is_public:
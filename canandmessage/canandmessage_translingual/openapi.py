@@ -52,6 +52,14 @@ def format_dtype_for_openapi(dtype: str, dev: toml_defs.DeviceSpec) -> Dict[str,
             "maximum": 0,
             "description": f"Padding field ({dtype[4:]} bits)"
         }
+    elif dtype.startswith("reserved:"):
+        bits = int(dtype[9:])
+        return {
+            "type": "integer",
+            "minimum": 0,
+            "maximum": 2**bits - 1,
+            "description": f"Reserved for future signals ({bits} bits), kept as received"
+        }
     elif dtype == "bool" or dtype == "bit":
         return {
             "type": "boolean",
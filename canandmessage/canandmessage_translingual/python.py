@@ -199,6 +199,8 @@ def gen_msg(dev: Device) -> str:
     variants = [msg_header]
     names = []
     for name, msg in dev.messages.items():
+        if any(sig.big_endian for sig in msg.signals):
+            utils.panic(ValueError(f"message {name}: pycanandmessage packs messages little-endian, so it can't have big-endian signals"))
         entries = gen_composite_signal(msg.signals, prefix="device_types.")
         camel_name = utils.screaming_snake_to_camel(name)
         names.append(camel_name)
@@ -189,7 +189,8 @@ def render_default_value(dev: toml_defs.DeviceSpec, dtype: str, default_value, d
             return f":ref:`Enum default<{dev.name.lower()}_enum_{dtype[5:].lower()}>`"
         elif (dtype.startswith("sint:") or 
             dtype.startswith("uint:") or
-            dtype.startswith("pad:")):
+            dtype.startswith("pad:") or
+            dtype.startswith("reserved:")):
             return "``0``"
         elif dtype.startswith("bool"):
             return "``false``"
@@ -248,6 +249,8 @@ def format_dtype(dtype: str) -> str:
         return f"``uint8_t[{n_bytes}]``"
     if dtype.startswith("pad:"):
         return f"``pad{dtype[4:]}_t``"
+    if dtype.startswith("reserved:"):
+        return f"``reserved{dtype[9:]}_t``"
     if dtype == "bool" or dtype == "bit":
         return "``bool``"
     if dtype.startswith("float:"):
@@ -332,7 +335,7 @@ def render_type(name: str, type_spec: toml_defs.TypeSpec, dev: toml_defs.DeviceS
             return table(["Property", "Value"], tbl)
         case PadMeta():
             tbl.extend([
-                ["Base type", "pad"],
+                ["Base type", "reserved" if meta.reserved else "pad"],
                 ["Bit width", type_spec.bits],
             ])
         case BufMeta():
@@ -217,7 +217,7 @@ fn gen_sig_extract(
     let field = "field".to_string();
     let extract = match &sig.dtype {
         DType::None => return (Vec::new(), offset),
-        DType::Pad { .. } => return (Vec::new(), new_off),
+        DType::Pad { .. } | DType::Reserved { .. } => return (Vec::new(), new_off),
         DType::UInt { .. } | DType::Enum { .. } | DType::Buf { .. } => {
             let width = sig.dtype.bit_length();
             format!("return {}", extract_lbits(&field, width, offset, false))
@@ -266,6 +266,50 @@ fn gen_sig_extract(
                 .collect::<Vec<String>>();
             return (extract_value, new_offset);
         }
+        DType::Array { meta } => {
+            // one extractor per element, plus an indexed one that dispatches to them
+            let mut new_offset = offset;
+            let mut extract_value = (0..meta.len)
+                .flat_map(|i| {
+                    let (v, new_off) =
+                        gen_sig_extract(&sig.array_element(i), prefix, new_offset, apply_prefix);
+                    new_offset = new_off;
+                    v
+                })
+                .collect::<Vec<String>>();
+            if !matches!(*meta.dtype, DType::Struct { .. }) {
+                let applied_prefix = if apply_prefix { prefix.as_str() } else { "" };
+                let cases = (0..meta.len)
+                    .map(|i| format!("case {i}: return extract{applied_prefix}{name}{i}(field);"))
+                    .collect::<Vec<String>>()
+                    .join(NL);
+                extract_value.push(format!(
+                    "Extracts element {{@code index}} of {sig_comment} from {sig_prefix}.
+
+        @param field data bitfield
+        @param index array index, from 0 to {last}
+        @return element {{@code index}} of {sig_name} as a {canon_name}
+        public static {return_type} extract{applied_prefix}{name}(long field, int index) {{
+        {body}
+        }}",
+                    sig_comment = sig.comment,
+                    sig_prefix = prefix.trim_matches('_'),
+                    last = meta.len - 1,
+                    sig_name = sig.name,
+                    canon_name = sig.dtype.canonical_name(),
+                    return_type = get_type_for_dtype(&meta.dtype),
+                    body = putils::indent(
+                        &format!(
+                            "switch (index) {{\n{}\n{INDENT}default: throw new IndexOutOfBoundsException(\"{sig_name} index \" + index);\n}}",
+                            putils::indent(&cases, INDENT),
+                            sig_name = sig.name,
+                        ),
+                        INDENT
+                    )
+                ));
+            }
+            return (extract_value, new_offset);
+        }
     };
     (
         vec![format!(
@@ -371,7 +415,7 @@ fn gen_sig_checks(sig: &Signal) -> Vec<String> {
             }
             checks
         }
-        DType::Pad { .. } => Vec::new(),
+        DType::Pad { .. } | DType::Reserved { .. } => Vec::new(),
         DType::Bool { .. } => Vec::new(),
         DType::None => Vec::new(),
         DType::Enum { .. } => Vec::new(),
@@ -381,19 +425,21 @@ fn gen_sig_checks(sig: &Signal) -> Vec<String> {
             .map(|subsig| {
                 gen_sig_checks(&Signal {
                     name: format!("{}_{}", sig.name, subsig.name),
-                    comment: subsig.comment.clone(),
-                    dtype: subsig.dtype.clone(),
-                    optional: subsig.optional,
+                    ..subsig.clone()
                 })
             })
             .flatten()
             .collect(),
+        DType::Array { meta } => (0..meta.len)
+            .flat_map(|i| gen_sig_checks(&sig.array_element(i)))
+            .collect(),
     }
 }
 
 fn render_sig(sig: &Signal, offset: usize) -> (Vec<String>, Vec<String>, Vec<String>, usize) {
     match &sig.dtype {
-        DType::Pad { width } => {
+        // the vendordep only sends messages it builds itself, so there's nothing to carry over
+        DType::Pad { width } | DType::Reserved { width } => {
             return (Vec::new(), Vec::new(), Vec::new(), offset + *width);
         }
         DType::Struct { meta } => {
@@ -403,9 +449,7 @@ fn render_sig(sig: &Signal, offset: usize) -> (Vec<String>, Vec<String>, Vec<Str
                 let (mut p, mut a, mut k, o) = render_sig(
                     &Signal {
                         name: format!("{}_{}", sig.name, subsig.name),
-                        comment: subsig.comment.clone(),
-                        dtype: subsig.dtype.clone(),
-                        optional: subsig.optional,
+                        ..subsig.clone()
                     },
                     new_offset,
                 );
@@ -418,6 +462,19 @@ fn render_sig(sig: &Signal, offset: usize) -> (Vec<String>, Vec<String>, Vec<Str
                 return (param, arg, pack_expr, new_offset);
             }
         }
+        DType::Array { meta } => {
+            // Java has no fixed-size arrays, so each element is its own parameter
+            let (mut param, mut arg, mut pack_expr) = (Vec::new(), Vec::new(), Vec::new());
+            let mut new_offset = offset;
+            for i in 0..meta.len {
+                let (mut p, mut a, mut k, o) = render_sig(&sig.array_element(i), new_offset);
+                param.append(&mut p);
+                arg.append(&mut a);
+                pack_expr.append(&mut k);
+                new_offset = o;
+            }
+            return (param, arg, pack_expr, new_offset);
+        }
         _ => (),
     };
 
@@ -509,6 +566,9 @@ fn gen_msg(dev: &Device) -> String {
         if !msg.is_public {
             continue;
         }
+        if !msg.fits_u64() {
+            panic!("message {name}: the Java vendordep packs messages into a long, so it can't have big-endian signals or more than 8 bytes");
+        }
         let kamel_name = screaming_snake_to_kamel(name);
         let camel_name = screaming_snake_to_camel(name);
 
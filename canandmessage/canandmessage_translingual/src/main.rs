@@ -5,6 +5,7 @@ use std::{env, fmt::Display, path::Path};
 use canandmessage_parser::{utils, DType, Device, Message, Signal, Source};
 extern crate canandmessage_parser;
 pub mod java;
+pub mod python;
 
 static TEMPLATE: &str = "VERSION \"\"
 
@@ -142,7 +143,7 @@ impl DBCBuilder {
                 meta.width,
                 false,
                 Some((meta.factor_num as f64) / (meta.factor_den as f64)),
-                None,
+                Some(meta.offset),
                 meta.min.unwrap_or(0).into(),
                 meta.max
                     .unwrap_or(utils::default_uint_max(meta.width))
@@ -155,7 +156,7 @@ impl DBCBuilder {
                 meta.width,
                 true,
                 Some((meta.factor_num as f64) / (meta.factor_den as f64)),
-                None,
+                Some(meta.offset),
                 meta.min
                     .unwrap_or(utils::default_sint_min(meta.width))
                     .into(),
@@ -183,7 +184,7 @@ impl DBCBuilder {
                     meta.width,
                     false,
                     Some((meta.factor_num as f64) / (meta.factor_den as f64)),
-                    None,
+                    Some(meta.offset),
                     0.0.into(),
                     0.0.into(),
                     &dest,
@@ -200,7 +201,7 @@ impl DBCBuilder {
                 utils::default_uint_max(meta.width).into(),
                 &dest,
             ),
-            DType::Pad { width } => self.render_sg(
+            DType::Pad { width } | DType::Reserved { width } => self.render_sg(
                 pos,
                 &name,
                 *width,
@@ -247,6 +248,18 @@ impl DBCBuilder {
                     )
                 });
             }
+            DType::Array { meta } => {
+                for i in 0..meta.len {
+                    self.render_signal(
+                        pos,
+                        dev,
+                        &sig.array_element(i),
+                        sig_prefix.clone(),
+                        dest,
+                        full_id,
+                    );
+                }
+            }
         };
     }
 
@@ -297,10 +310,18 @@ impl ToString for DBCBuilder {
 }
 
 fn main() {
-    let argv: Vec<String> = env::args().collect();
+    let mut argv: Vec<String> = env::args().collect();
+    // `--python` prints a Python module of the device's constants instead of a DBC
+    let python = match argv.iter().position(|arg| arg == "--python") {
+        Some(idx) => {
+            argv.remove(idx);
+            true
+        }
+        None => false,
+    };
     let toml_name = argv
         .get(1)
-        .expect(format!("usage: {} toml_file [device_id]", argv[0]).as_str());
+        .unwrap_or_else(|| panic!("usage: {} [--python] toml_file [device_id]", argv[0]));
     let dev_id = argv
         .get(2)
         .unwrap_or(&"0".to_string())
@@ -309,6 +330,10 @@ fn main() {
 
     let devspec = canandmessage_parser::parse_spec(Path::new(toml_name)).unwrap();
     let dev: Device = devspec.clone().into();
+    if python {
+        print!("{}", python::gen_module(&dev));
+        return;
+    }
     let mut dbc = DBCBuilder::new();
     dbc.render_device(&dev, dev_id);
 
@@ -0,0 +1,619 @@
+use canandmessage_parser::utils as putils;
+use canandmessage_parser::DType;
+use canandmessage_parser::Device;
+use canandmessage_parser::Signal;
+
+const COPYRIGHT_NOTICE: &str = "# Copyright (c) Redux Robotics and other contributors.
+# This is open source and can be modified and shared under the 3-clause BSD license.
+";
+
+const INDENT: &str = "    ";
+
+/// Module-private helpers the generated extract/construct functions call into.
+const HELPERS: &str = r#"def _sign_extend(value: int, width: int) -> int:
+    """Interprets the low width bits of value as two's complement."""
+    return value - (1 << width) if value & (1 << (width - 1)) else value
+
+
+def _swap_bytes(value: int, width: int) -> int:
+    """Reverses the byte order of a width-bit value."""
+    return int.from_bytes(value.to_bytes(width // 8, "little"), "big")
+
+
+def _float_from_bits(bits: int, width: int) -> float:
+    """Decodes a float. 24-bit floats are single precision missing the low mantissa byte."""
+    if width == 64:
+        return struct.unpack("<d", bits.to_bytes(8, "little"))[0]
+    return struct.unpack("<f", (bits << (32 - width)).to_bytes(4, "little"))[0]
+
+
+def _float_to_bits(value: float, width: int) -> int:
+    """Encodes a float, the inverse of _float_from_bits."""
+    if width == 64:
+        return int.from_bytes(struct.pack("<d", value), "little")
+    return int.from_bytes(struct.pack("<f", value), "little") >> (32 - width)"#;
+
+/// Names that can't be Python parameters; signals named after one get a trailing underscore.
+const KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda",
+    "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+];
+
+/// Generates a docstring.
+fn docstring(s: &str) -> String {
+    let body = s.trim_end().replace('\\', "\\\\").replace('"', "\\\"");
+    if body.contains('\n') {
+        format!("\"\"\"{body}\n\"\"\"")
+    } else {
+        format!("\"\"\"{body}\"\"\"")
+    }
+}
+
+/// Generates a Python identifier for a signal name.
+fn py_ident(s: &str) -> String {
+    let ident = s.to_lowercase();
+    if KEYWORDS.contains(&ident.as_str()) {
+        format!("{ident}_")
+    } else {
+        ident
+    }
+}
+
+fn screaming_snake_to_camel(s: &str) -> String {
+    s.split('_')
+        .map(|part| putils::capitalize(&part.to_lowercase()))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+fn mask(width: usize) -> String {
+    format!("0x{:x}", (1u128 << width) - 1)
+}
+
+fn shift_left(expr: String, offset: usize) -> String {
+    if offset == 0 {
+        expr
+    } else {
+        format!("({expr}) << {offset}")
+    }
+}
+
+/// Gets the Python type hint for the dtype.
+fn get_type_for_dtype(dtype: &DType) -> &'static str {
+    match dtype {
+        DType::Bool { .. } => "bool",
+        DType::Float { .. } => "float",
+        DType::Buf { .. } => "bytes",
+        _ => "int",
+    }
+}
+
+/// Generates the expression pulling a signal out of the bitfield `field`.
+fn extract_expr(sig: &Signal, offset: usize) -> String {
+    let width = sig.dtype.bit_length();
+    let raw = if offset == 0 {
+        format!("field & {}", mask(width))
+    } else {
+        format!("(field >> {offset}) & {}", mask(width))
+    };
+    let raw = if sig.big_endian {
+        format!("_swap_bytes({raw}, {width})")
+    } else {
+        raw
+    };
+    match &sig.dtype {
+        DType::SInt { .. } => format!("_sign_extend({raw}, {width})"),
+        DType::Float { .. } => format!("_float_from_bits({raw}, {width})"),
+        DType::Bool { .. } => format!("bool({raw})"),
+        DType::Buf { .. } => format!("({raw}).to_bytes({}, \"little\")", width.div_ceil(8)),
+        _ => raw,
+    }
+}
+
+/// Generates the expression placing the parameter `name` into a bitfield at `offset`.
+fn pack_expr(sig: &Signal, name: &str, offset: usize) -> String {
+    let width = sig.dtype.bit_length();
+    let raw = match &sig.dtype {
+        DType::Float { .. } => format!("_float_to_bits({name}, {width})"),
+        DType::Bool { .. } => format!("int(bool({name}))"),
+        DType::Buf { .. } => format!("int.from_bytes({name}, \"little\") & {}", mask(width)),
+        _ => format!("{name} & {}", mask(width)),
+    };
+    let raw = if sig.big_endian {
+        format!("_swap_bytes({raw}, {width})")
+    } else {
+        raw
+    };
+    shift_left(raw, offset)
+}
+
+fn gen_fn(decl: String, doc: String, body: &[String]) -> String {
+    let body = putils::indent(&body.join("\n"), INDENT);
+    format!(
+        "@staticmethod\n{decl}:\n{}\n{body}",
+        putils::indent(&docstring(&doc), INDENT)
+    )
+}
+
+fn gen_sig_extract(sig: &Signal, prefix: &str, offset: usize) -> (Vec<String>, usize) {
+    let name = format!("{prefix}{}", sig.name.to_lowercase());
+    let new_off = offset + sig.dtype.bit_length();
+    let from = if prefix.is_empty() {
+        sig.name.clone()
+    } else {
+        prefix.trim_matches('_').to_uppercase()
+    };
+    match &sig.dtype {
+        DType::None => return (Vec::new(), offset),
+        DType::Pad { .. } | DType::Reserved { .. } => return (Vec::new(), new_off),
+        DType::Struct { meta } => {
+            let prefix = format!("{name}_");
+            let mut new_offset = offset;
+            let extract_value = meta
+                .signals
+                .iter()
+                .flat_map(|subsig| {
+                    let (v, new_off) = gen_sig_extract(subsig, &prefix, new_offset);
+                    new_offset = new_off;
+                    v
+                })
+                .collect::<Vec<String>>();
+            return (extract_value, new_offset);
+        }
+        DType::Array { meta } => {
+            // one extractor per element, plus an indexed one
+            let mut new_offset = offset;
+            let mut extract_value = (0..meta.len)
+                .flat_map(|i| {
+                    let (v, new_off) = gen_sig_extract(&sig.array_element(i), prefix, new_offset);
+                    new_offset = new_off;
+                    v
+                })
+                .collect::<Vec<String>>();
+            if !matches!(*meta.dtype, DType::Struct { .. }) {
+                let elem_width = meta.dtype.bit_length();
+                let elem = sig.array_element(0);
+                let elem_expr = extract_expr(&elem, 0).replacen(
+                    "field",
+                    &format!("(field >> ({offset} + index * {elem_width}))"),
+                    1,
+                );
+                extract_value.push(gen_fn(
+                    format!(
+                        "def extract_{name}(field: int, index: int) -> {}",
+                        get_type_for_dtype(&meta.dtype)
+                    ),
+                    format!(
+                        "Extracts element index of {comment} from {from}.
+
+:param field: data bitfield
+:param index: array index, from 0 to {last}
+:return: element index of {sig_name} as a {canon_name}",
+                        comment = sig.comment,
+                        last = meta.len - 1,
+                        sig_name = sig.name,
+                        canon_name = meta.dtype.canonical_name(),
+                    ),
+                    &[
+                        format!("if not 0 <= index < {}:", meta.len),
+                        format!(
+                            "{INDENT}raise IndexError(f\"{} index {{index}}\")",
+                            sig.name
+                        ),
+                        format!("return {elem_expr}"),
+                    ],
+                ));
+            }
+            return (extract_value, new_offset);
+        }
+        _ => (),
+    };
+    (
+        vec![gen_fn(
+            format!(
+                "def extract_{name}(field: int) -> {}",
+                get_type_for_dtype(&sig.dtype)
+            ),
+            format!(
+                "Extracts {comment} from {from}.
+
+:param field: data bitfield
+:return: {sig_name} as a {canon_name}",
+                comment = sig.comment,
+                sig_name = sig.name,
+                canon_name = sig.dtype.canonical_name(),
+            ),
+            &[format!("return {}", extract_expr(sig, offset))],
+        )],
+        new_off,
+    )
+}
+
+fn gen_check(expr: String, err_msg: String) -> Vec<String> {
+    vec![
+        format!("if {expr}:"),
+        format!("{INDENT}raise ValueError(f\"{err_msg}\")"),
+    ]
+}
+
+fn gen_sig_checks(sig: &Signal) -> Vec<String> {
+    let sig_name = py_ident(&sig.name);
+    match &sig.dtype {
+        DType::UInt { meta } => {
+            let (min, max) = (
+                meta.min.unwrap_or(0),
+                meta.max.unwrap_or(putils::default_uint_max(meta.width)),
+            );
+            gen_check(
+                format!("not {min} <= {sig_name} <= {max}"),
+                format!("{sig_name} must be between [{min}..={max}] inclusive, instead got {{{sig_name}}}"),
+            )
+        }
+        DType::SInt { meta } => {
+            let min = meta.min.unwrap_or(putils::default_sint_min(meta.width));
+            let max = meta.max.unwrap_or(putils::default_sint_max(meta.width));
+            gen_check(
+                format!("not {min} <= {sig_name} <= {max}"),
+                format!("{sig_name} must be between [{min}..={max}] inclusive, instead got {{{sig_name}}}"),
+            )
+        }
+        DType::Bitset { meta } => {
+            let umax = putils::default_uint_max(meta.width);
+            gen_check(
+                format!("not 0 <= {sig_name} <= {umax}"),
+                format!(
+                    "{sig_name} must be between [0..={umax}] inclusive, instead got {{{sig_name}}}"
+                ),
+            )
+        }
+        DType::Buf { meta } => {
+            let len = meta.width.div_ceil(8);
+            gen_check(
+                format!("len({sig_name}) != {len}"),
+                format!("{sig_name} must be {len} bytes long, instead got {{len({sig_name})}}"),
+            )
+        }
+        DType::Float { meta } => {
+            let mut checks: Vec<String> = Vec::new();
+            if let Some(min) = meta.min.filter(|min| min.is_finite()) {
+                checks.extend(gen_check(
+                    format!("{sig_name} < {min:?}"),
+                    format!("{sig_name} value {{{sig_name}}} violates bound {sig_name} >= {min:?}"),
+                ));
+            }
+            if let Some(max) = meta.max.filter(|max| max.is_finite()) {
+                checks.extend(gen_check(
+                    format!("{sig_name} > {max:?}"),
+                    format!("{sig_name} value {{{sig_name}}} violates bound {sig_name} <= {max:?}"),
+                ));
+            }
+            if !meta.allow_nan_inf {
+                checks.extend(gen_check(
+                    format!("not math.isfinite({sig_name})"),
+                    format!("{sig_name} cannot be infinite or NaN!"),
+                ));
+            }
+            checks
+        }
+        DType::Pad { .. } | DType::Reserved { .. } => Vec::new(),
+        DType::Bool { .. } => Vec::new(),
+        DType::None => Vec::new(),
+        DType::Enum { .. } => Vec::new(),
+        DType::Struct { meta } => meta
+            .signals
+            .iter()
+            .flat_map(|subsig| {
+                gen_sig_checks(&Signal {
+                    name: format!("{}_{}", sig.name, subsig.name),
+                    ..subsig.clone()
+                })
+            })
+            .collect(),
+        DType::Array { meta } => (0..meta.len)
+            .flat_map(|i| gen_sig_checks(&sig.array_element(i)))
+            .collect(),
+    }
+}
+
+fn render_sig(sig: &Signal, offset: usize) -> (Vec<String>, Vec<String>, Vec<String>, usize) {
+    match &sig.dtype {
+        DType::None => return (Vec::new(), Vec::new(), Vec::new(), offset),
+        DType::Pad { width } | DType::Reserved { width } => {
+            return (Vec::new(), Vec::new(), Vec::new(), offset + *width);
+        }
+        DType::Struct { meta } => {
+            let (mut param, mut arg, mut pack) = (Vec::new(), Vec::new(), Vec::new());
+            let mut new_offset = offset;
+            for subsig in &meta.signals {
+                let (p, a, k, o) = render_sig(
+                    &Signal {
+                        name: format!("{}_{}", sig.name, subsig.name),
+                        ..subsig.clone()
+                    },
+                    new_offset,
+                );
+                param.extend(p);
+                arg.extend(a);
+                pack.extend(k);
+                new_offset = o;
+            }
+            return (param, arg, pack, new_offset);
+        }
+        DType::Array { meta } => {
+            // each element is its own parameter, like the Java details
+            let (mut param, mut arg, mut pack) = (Vec::new(), Vec::new(), Vec::new());
+            let mut new_offset = offset;
+            for i in 0..meta.len {
+                let (p, a, k, o) = render_sig(&sig.array_element(i), new_offset);
+                param.extend(p);
+                arg.extend(a);
+                pack.extend(k);
+                new_offset = o;
+            }
+            return (param, arg, pack, new_offset);
+        }
+        _ => (),
+    };
+
+    let sig_name = py_ident(&sig.name);
+    let param = format!(
+        ":param {sig_name}: {sig_comment} ({sig_dname})",
+        sig_comment = sig.comment,
+        sig_dname = sig.dtype.canonical_name()
+    );
+    let arg = format!("{sig_name}: {}", get_type_for_dtype(&sig.dtype));
+    let pack = pack_expr(sig, &sig_name, offset);
+    (
+        vec![param],
+        vec![arg],
+        vec![pack],
+        offset + sig.dtype.bit_length(),
+    )
+}
+
+fn gen_sigs_pack(
+    name: &str,
+    signals: &[&Signal],
+    compound_type: &str,
+    check_bounds: bool,
+    mux_value: Option<u64>,
+) -> String {
+    let (mut params, mut args, mut pack_exprs, mut offset) =
+        (Vec::new(), Vec::new(), Vec::new(), 0usize);
+    for sig in signals {
+        if let (true, Some(value)) = (sig.mux, mux_value) {
+            // the constructor is for one value of the multiplexor, so it isn't a parameter
+            pack_exprs.push(shift_left(format!("0x{value:x}"), offset));
+            offset += sig.dtype.bit_length();
+            continue;
+        }
+        let (p, a, k, o) = render_sig(sig, offset);
+        params.extend(p);
+        args.extend(a);
+        pack_exprs.extend(k);
+        offset = o;
+    }
+
+    let mut body = if check_bounds {
+        signals
+            .iter()
+            .flat_map(|sig| gen_sig_checks(sig))
+            .collect::<Vec<String>>()
+    } else {
+        Vec::new()
+    };
+    match pack_exprs.len() {
+        0 => body.push("return 0".to_string()),
+        1 => body.push(format!("return {}", pack_exprs[0])),
+        _ => {
+            body.push("return (".to_string());
+            for (i, expr) in pack_exprs.iter().enumerate() {
+                let sep = if i == 0 { "" } else { "| " };
+                body.push(format!("{INDENT}{sep}({expr})"));
+            }
+            body.push(")".to_string());
+        }
+    }
+
+    let params = if params.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", params.join("\n"))
+    };
+    gen_fn(
+        format!(
+            "def construct_{lname}({args}) -> int",
+            lname = name.to_lowercase(),
+            args = args.join(", ")
+        ),
+        format!("Constructs a {name} {compound_type}.\n\n{params}:return: {compound_type} data as an int"),
+        &body,
+    )
+}
+
+fn gen_cls(name: &str, bases: &str, doc: &str, members: &[String]) -> String {
+    let mut body = vec![docstring(doc)];
+    body.extend(members.iter().cloned());
+    format!(
+        "class {name}{bases}:\n{}",
+        putils::indent(&body.join("\n\n"), INDENT)
+    )
+    .split('\n')
+    .map(str::trim_end)
+    .collect::<Vec<&str>>()
+    .join("\n")
+}
+
+fn gen_msg(dev: &Device) -> String {
+    let mut members: Vec<String> = Vec::new();
+    let mut sig_extract_members: Vec<String> = Vec::new();
+    let mut sig_pack_members: Vec<String> = Vec::new();
+    let mut dlc_members: Vec<String> = Vec::new();
+
+    let mut msg_vec = dev
+        .messages
+        .iter()
+        .collect::<Vec<(&String, &canandmessage_parser::Message)>>();
+    msg_vec.sort_by_key(|(_, msg)| u8::MAX - msg.id);
+    for (name, msg) in msg_vec {
+        if !msg.is_public {
+            continue;
+        }
+        members.push(format!(
+            "{name} = 0x{:x}\n{}",
+            msg.id,
+            docstring(&msg.comment)
+        ));
+
+        // muxed signals for different multiplexor values overlap, so each starts where it's laid out
+        let prefix = format!("{}_", name.to_lowercase());
+        for (offset, sig) in msg.layout() {
+            sig_extract_members.extend(gen_sig_extract(sig, &prefix, offset).0);
+        }
+
+        let plain_signals = msg.plain_signals().collect::<Vec<&Signal>>();
+        sig_pack_members.push(gen_sigs_pack(name, &plain_signals, "message", false, None));
+        for group in msg.mux_groups() {
+            let signals = plain_signals
+                .iter()
+                .chain(group.signals.iter())
+                .copied()
+                .collect::<Vec<&Signal>>();
+            sig_pack_members.push(gen_sigs_pack(
+                &format!("{name}_{}", group.name),
+                &signals,
+                "message",
+                false,
+                Some(group.value),
+            ));
+        }
+
+        if msg.min_length == msg.max_length {
+            dlc_members.push(format!(
+                "DLC_{name} = {}\n\"\"\"{name} message length\"\"\"",
+                msg.min_length
+            ));
+        } else {
+            dlc_members.push(format!(
+                "DLC_MIN_{name} = {}\n\"\"\"{name} message min length\"\"\"",
+                msg.min_length
+            ));
+            dlc_members.push(format!(
+                "DLC_MAX_{name} = {}\n\"\"\"{name} message max length\"\"\"",
+                msg.max_length
+            ));
+        }
+    }
+
+    members.append(&mut sig_extract_members);
+    members.append(&mut sig_pack_members);
+    members.append(&mut dlc_members);
+    gen_cls("Msg", "", "Messages.", &members)
+}
+
+fn gen_stg(dev: &Device) -> String {
+    let mut stg_vec = dev
+        .settings
+        .iter()
+        .collect::<Vec<(&String, &canandmessage_parser::Setting)>>();
+    stg_vec.sort_by_key(|(_, stg)| u8::MAX - stg.id);
+
+    let mut members: Vec<String> = Vec::new();
+    let mut sig_extract_members = Vec::new();
+    let mut sig_pack_members = Vec::new();
+
+    for (name, stg) in stg_vec {
+        members.push(format!(
+            "{name} = 0x{:x}\n{}",
+            stg.id,
+            docstring(&stg.comment)
+        ));
+        let sig = Signal::from_stg(name, stg);
+        sig_extract_members.extend(gen_sig_extract(&sig, "", 0).0);
+
+        match &stg.dtype {
+            DType::Struct { meta } => {
+                let signals = meta.signals.iter().collect::<Vec<&Signal>>();
+                sig_pack_members.push(gen_sigs_pack(name, &signals, "setting", true, None));
+            }
+            _ => {
+                sig_pack_members.push(gen_sigs_pack(name, &[&sig], "setting", true, None));
+            }
+        }
+    }
+    members.append(&mut sig_extract_members);
+    members.append(&mut sig_pack_members);
+
+    gen_cls("Stg", "", "Settings.", &members)
+}
+
+fn gen_enumers(dev: &Device) -> Vec<String> {
+    dev.enums
+        .iter()
+        .filter(|(name, _)| name.as_str() != "SETTING")
+        .map(|(name, meta)| {
+            let enumer_members = meta
+                .values
+                .iter()
+                .map(|(id, ent)| format!("{} = 0x{id:x}\n{}", ent.name, docstring(&ent.comment)))
+                .collect::<Vec<String>>();
+            gen_cls(
+                &screaming_snake_to_camel(name),
+                "(enum.IntEnum)",
+                &format!("enum {dev_name}::{name}", dev_name = dev.name),
+                &enumer_members,
+            )
+        })
+        .collect()
+}
+
+fn gen_bitsets(dev: &Device) -> Vec<String> {
+    dev.bitsets
+        .iter()
+        .map(|(name, meta)| {
+            let flag_members = meta
+                .flags
+                .iter()
+                .map(|ent| {
+                    format!(
+                        "{} = 0x{:x}\n{}",
+                        ent.name,
+                        1u64 << ent.bit_idx,
+                        docstring(&ent.comment)
+                    )
+                })
+                .collect::<Vec<String>>();
+            gen_cls(
+                &screaming_snake_to_camel(name),
+                "(enum.IntFlag)",
+                &format!("bitset {dev_name}::{name}", dev_name = dev.name),
+                &flag_members,
+            )
+        })
+        .collect()
+}
+
+/// Generates a pure-Python module with the device's message and setting indexes, functions to
+/// extract signals from and construct message and setting data, and its enums and bitsets.
+///
+/// Message and setting data is handled as an int of the little-endian frame bytes, e.g.
+/// `int.from_bytes(frame.data, "little")`.
+pub fn gen_module(dev: &Device) -> String {
+    let mut sections = vec![
+        format!(
+            "{COPYRIGHT_NOTICE}{}",
+            docstring(&format!(
+                "{} device constants.\n\nThis file is autogenerated by canandmessage, do not hand-edit!\n",
+                dev.name
+            ))
+        ),
+        "import enum\nimport math\nimport struct".to_string(),
+        HELPERS.to_string(),
+    ];
+    sections.extend(gen_enumers(dev));
+    sections.extend(gen_bitsets(dev));
+    sections.push(gen_msg(dev));
+    sections.push(gen_stg(dev));
+    format!("{}\n", sections.join("\n\n\n"))
+}
@@ -0,0 +1,1042 @@
+# Copyright (c) Redux Robotics and other contributors.
+# This is open source and can be modified and shared under the 3-clause BSD license.
+"""Canandmag device constants.
+
+This file is autogenerated by canandmessage, do not hand-edit!
+"""
+
+
+import enum
+import math
+import struct
+
+
+def _sign_extend(value: int, width: int) -> int:
+    """Interprets the low width bits of value as two's complement."""
+    return value - (1 << width) if value & (1 << (width - 1)) else value
+
+
+def _swap_bytes(value: int, width: int) -> int:
+    """Reverses the byte order of a width-bit value."""
+    return int.from_bytes(value.to_bytes(width // 8, "little"), "big")
+
+
+def _float_from_bits(bits: int, width: int) -> float:
+    """Decodes a float. 24-bit floats are single precision missing the low mantissa byte."""
+    if width == 64:
+        return struct.unpack("<d", bits.to_bytes(8, "little"))[0]
+    return struct.unpack("<f", (bits << (32 - width)).to_bytes(4, "little"))[0]
+
+
+def _float_to_bits(value: float, width: int) -> int:
+    """Encodes a float, the inverse of _float_from_bits."""
+    if width == 64:
+        return int.from_bytes(struct.pack("<d", value), "little")
+    return int.from_bytes(struct.pack("<f", value), "little") >> (32 - width)
+
+
+class AtomicBondBusRate(enum.IntEnum):
+    """enum Canandmag::ATOMIC_BOND_BUS_RATE"""
+
+    RATE_1M_2B = 0x0
+    """1 megabit/s CAN 2.0B"""
+
+    RATE_RESERVED_0 = 0x1
+    """1 megabit/s CAN-FD"""
+
+    RATE_RESERVED_1 = 0x2
+    """5 megabit/s CAN-FD"""
+
+    RATE_RESERVED_2 = 0x3
+    """8 megabit/s CAN-FD"""
+
+
+class SettingCommand(enum.IntEnum):
+    """enum Canandmag::SETTING_COMMAND"""
+
+    FETCH_SETTINGS = 0x0
+    """Fetch all settings from device via a series of :ref:`report setting<msg_report_setting>` messages of all indexes"""
+
+    RESET_FACTORY_DEFAULT = 0x1
+    """Reset all resettanble settings to factory default, and broadcast all setting values via
+    :ref:`report setting<msg_report_setting>` messages.
+    """
+
+    FETCH_SETTING_VALUE = 0x2
+    """Requests to fetch a single setting from device, with its value reported via the
+    :ref:`report setting<msg_report_setting>` message.
+
+    This requires the use of the second byte to specify the setting index to fetch.
+    """
+
+    RESET_FACTORY_DEFAULT_KEEP_ZERO = 0xff
+    """Reset to factory defaults, but keep encoder zero offset"""
+
+
+class AtomicAnnouncementFlags(enum.IntFlag):
+    """bitset Canandmag::atomic_announcement_flags"""
+
+    negotiation = 0x1
+    """Device should enter negotiation phase"""
+
+    init = 0x2
+    """Device should initialize bus with new rate"""
+
+    confirm = 0x4
+    """Device should confirm new bus rate"""
+
+    begin_tx = 0x8
+    """Device should begin transmission"""
+
+    bus_interrupt = 0x10
+    """Device should cease all transmission"""
+
+
+class Faults(enum.IntFlag):
+    """bitset Canandmag::faults"""
+
+    power_cycle = 0x1
+    """The power cycle fault flag, which is set to true when the encoder first boots.
+    Clearing sticky faults and then checking this flag can be used to determine if the encoder rebooted.
+    """
+
+    can_id_conflict = 0x2
+    """The CAN ID conflict flag, which is set to true if there is a CAN id conflict.
+    In practice, you should physically inspect the encoder to ensure it's not flashing blue.
+    """
+
+    can_general_error = 0x4
+    """The CAN general error flag, which will raise if the device encounters a CAN fault during operation.
+    If communication with the device still functions, this will not register as an active fault for long if at all.
+    This may raise due to wiring issues, such as an intermittently shorted CAN bus.
+    """
+
+    out_of_temperature_range = 0x8
+    """The temperature range flag, which will raise if the encoder is not between 0-70 degrees Celsius.
+    This may be of concern if the encoder is near very active motors.
+    """
+
+    hardware_fault = 0x10
+    """The hardware fault flag, which will raise if a hardware issue is detected.
+    Generally will raise if the device's controller cannot read the physical sensor itself.
+    """
+
+    magnet_out_of_range = 0x20
+    """The magnet out of range flag, which will raise if the measured shaft's magnet is not detected.
+    This will match the encoder's LED shining red in normal operation.
+    """
+
+    under_volt = 0x40
+    """The undervolt flag, which will raise if the encoder is experiencing brownout conditions."""
+
+
+class SettingReportFlags(enum.IntFlag):
+    """bitset Canandmag::setting_report_flags"""
+
+    set_success = 0x1
+    """Whether the setting set/fetch was successful"""
+
+    commit_success = 0x2
+    """Whether the setting synch commit was successful"""
+
+
+class Msg:
+    """Messages."""
+
+    POSITION_OUTPUT = 0x1f
+    """Position frame"""
+
+    VELOCITY_OUTPUT = 0x1e
+    """Velocity frame"""
+
+    RAW_POSITION_OUTPUT = 0x1d
+    """Raw position frame"""
+
+    ENUMERATE = 0xb
+    """Device enumerate response"""
+
+    PARTY_MODE = 0x7
+    """Party mode"""
+
+    STATUS = 0x6
+    """Status frame"""
+
+    CLEAR_STICKY_FAULTS = 0x5
+    """Clear device sticky faults"""
+
+    REPORT_SETTING = 0x4
+    """setting value report from device"""
+
+    SET_SETTING = 0x3
+    """update setting on device"""
+
+    SETTING_COMMAND = 0x2
+    """setting control command"""
+
+    @staticmethod
+    def extract_position_output_relative_position(field: int) -> int:
+        """Extracts 32-bit signed relative position in 1/16384-ths of a rotation. This value does not persist on reboots. from POSITION_OUTPUT.
+
+        :param field: data bitfield
+        :return: relative_position as a sint:32
+        """
+        return _sign_extend(field & 0xffffffff, 32)
+
+    @staticmethod
+    def extract_position_output_magnet_status(field: int) -> int:
+        """Extracts 2-bit magnet status. If both bits are zero, the magnet is in range. from POSITION_OUTPUT.
+
+        :param field: data bitfield
+        :return: magnet_status as a uint:2
+        """
+        return (field >> 32) & 0x3
+
+    @staticmethod
+    def extract_position_output_absolute_position(field: int) -> int:
+        """Extracts 14-bit unsigned absolute position in 1/16384-ths of a rotation. The zero offset of the absolute encoder will preserve through reboots. from POSITION_OUTPUT.
+
+        :param field: data bitfield
+        :return: absolute_position as a uint:14
+        """
+        return (field >> 34) & 0x3fff
+
+    @staticmethod
+    def extract_velocity_output_velocity(field: int) -> int:
+        """Extracts Velocity as a 22-bit signed integer. One velocity tick corresponds to 1/1024th of a rotation per second. from VELOCITY_OUTPUT.
+
+        :param field: data bitfield
+        :return: velocity as a sint:22
+        """
+        return _sign_extend(field & 0x3fffff, 22)
+
+    @staticmethod
+    def extract_velocity_output_magnet_status(field: int) -> int:
+        """Extracts 2-bit magnet status. If both bits are zero, the magnet is in range. from VELOCITY_OUTPUT.
+
+        :param field: data bitfield
+        :return: magnet_status as a uint:2
+        """
+        return (field >> 22) & 0x3
+
+    @staticmethod
+    def extract_raw_position_output_raw_position(field: int) -> int:
+        """Extracts 14-bit raw absolute position in 1/16384-ths of a rotation. from RAW_POSITION_OUTPUT.
+
+        :param field: data bitfield
+        :return: raw_position as a uint:14
+        """
+        return field & 0x3fff
+
+    @staticmethod
+    def extract_raw_position_output_magnet_status(field: int) -> int:
+        """Extracts 2-bit magnet status. If both bits are zero, the magnet is in range. from RAW_POSITION_OUTPUT.
+
+        :param field: data bitfield
+        :return: magnet_status as a uint:2
+        """
+        return (field >> 14) & 0x3
+
+    @staticmethod
+    def extract_raw_position_output_timestamp(field: int) -> int:
+        """Extracts 32-bit sensor reading timestamp in microseconds since device boot. from RAW_POSITION_OUTPUT.
+
+        :param field: data bitfield
+        :return: timestamp as a uint:32
+        """
+        return (field >> 16) & 0xffffffff
+
+    @staticmethod
+    def extract_enumerate_serial(field: int) -> bytes:
+        """Extracts Device-unique serial number from ENUMERATE.
+
+        :param field: data bitfield
+        :return: serial as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_enumerate_is_bootloader(field: int) -> bool:
+        """Extracts Device is in bootloader. from ENUMERATE.
+
+        :param field: data bitfield
+        :return: is_bootloader as a bool
+        """
+        return bool((field >> 48) & 0x1)
+
+    @staticmethod
+    def extract_party_mode_party_level(field: int) -> int:
+        """Extracts Party level. 0 disables the strobe, whereas 1 enables it. from PARTY_MODE.
+
+        :param field: data bitfield
+        :return: party_level as a uint:8
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_status_faults(field: int) -> int:
+        """Extracts 8-bit active faults bitfield from STATUS.
+
+        :param field: data bitfield
+        :return: faults as a uint:8
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_status_sticky_faults(field: int) -> int:
+        """Extracts 8-bit sticky faults bitfield from STATUS.
+
+        :param field: data bitfield
+        :return: sticky_faults as a uint:8
+        """
+        return (field >> 8) & 0xff
+
+    @staticmethod
+    def extract_status_temperature(field: int) -> int:
+        """Extracts 8-bit signed temperature byte in Celsius from STATUS.
+
+        :param field: data bitfield
+        :return: temperature as a sint:8
+        """
+        return _sign_extend((field >> 16) & 0xff, 8)
+
+    @staticmethod
+    def extract_report_setting_address(field: int) -> int:
+        """Extracts Setting index to write to from REPORT_SETTING.
+
+        :param field: data bitfield
+        :return: address as a enum:SETTING
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_report_setting_value(field: int) -> bytes:
+        """Extracts 6-byte setting value from REPORT_SETTING.
+
+        :param field: data bitfield
+        :return: value as a buf:48
+        """
+        return ((field >> 8) & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_report_setting_flags(field: int) -> int:
+        """Extracts Setting receive status from REPORT_SETTING.
+
+        :param field: data bitfield
+        :return: flags as a uint:8
+        """
+        return (field >> 56) & 0xff
+
+    @staticmethod
+    def extract_set_setting_address(field: int) -> int:
+        """Extracts Setting index to write to from SET_SETTING.
+
+        :param field: data bitfield
+        :return: address as a enum:SETTING
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_set_setting_value(field: int) -> bytes:
+        """Extracts 6-byte setting value from SET_SETTING.
+
+        :param field: data bitfield
+        :return: value as a buf:48
+        """
+        return ((field >> 8) & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_set_setting_flags_ephemeral(field: int) -> bool:
+        """Extracts Whether the setting should be set ephemeral from SET_SETTING_FLAGS.
+
+        :param field: data bitfield
+        :return: ephemeral as a bool
+        """
+        return bool((field >> 56) & 0x1)
+
+    @staticmethod
+    def extract_set_setting_flags_synch_hold(field: int) -> bool:
+        """Extracts Whether the setting should be held until the next synch barrier from SET_SETTING_FLAGS.
+
+        :param field: data bitfield
+        :return: synch_hold as a bool
+        """
+        return bool((field >> 57) & 0x1)
+
+    @staticmethod
+    def extract_set_setting_flags_synch_msg_count(field: int) -> int:
+        """Extracts Synch message count from SET_SETTING_FLAGS.
+
+        :param field: data bitfield
+        :return: synch_msg_count as a uint:4
+        """
+        return (field >> 60) & 0xf
+
+    @staticmethod
+    def extract_setting_command_control_flag(field: int) -> int:
+        """Extracts Setting command index from SETTING_COMMAND.
+
+        :param field: data bitfield
+        :return: control_flag as a enum:SETTING_COMMAND
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_setting_command_setting_index(field: int) -> int:
+        """Extracts setting index to fetch from SETTING_COMMAND.
+
+        :param field: data bitfield
+        :return: setting_index as a enum:SETTING
+        """
+        return (field >> 8) & 0xff
+
+    @staticmethod
+    def construct_position_output(relative_position: int, magnet_status: int, absolute_position: int) -> int:
+        """Constructs a POSITION_OUTPUT message.
+
+        :param relative_position: 32-bit signed relative position in 1/16384-ths of a rotation. This value does not persist on reboots. (sint:32)
+        :param magnet_status: 2-bit magnet status. If both bits are zero, the magnet is in range. (uint:2)
+        :param absolute_position: 14-bit unsigned absolute position in 1/16384-ths of a rotation. The zero offset of the absolute encoder will preserve through reboots. (uint:14)
+        :return: message data as an int
+        """
+        return (
+            (relative_position & 0xffffffff)
+            | ((magnet_status & 0x3) << 32)
+            | ((absolute_position & 0x3fff) << 34)
+        )
+
+    @staticmethod
+    def construct_velocity_output(velocity: int, magnet_status: int) -> int:
+        """Constructs a VELOCITY_OUTPUT message.
+
+        :param velocity: Velocity as a 22-bit signed integer. One velocity tick corresponds to 1/1024th of a rotation per second. (sint:22)
+        :param magnet_status: 2-bit magnet status. If both bits are zero, the magnet is in range. (uint:2)
+        :return: message data as an int
+        """
+        return (
+            (velocity & 0x3fffff)
+            | ((magnet_status & 0x3) << 22)
+        )
+
+    @staticmethod
+    def construct_raw_position_output(raw_position: int, magnet_status: int, timestamp: int) -> int:
+        """Constructs a RAW_POSITION_OUTPUT message.
+
+        :param raw_position: 14-bit raw absolute position in 1/16384-ths of a rotation. (uint:14)
+        :param magnet_status: 2-bit magnet status. If both bits are zero, the magnet is in range. (uint:2)
+        :param timestamp: 32-bit sensor reading timestamp in microseconds since device boot. (uint:32)
+        :return: message data as an int
+        """
+        return (
+            (raw_position & 0x3fff)
+            | ((magnet_status & 0x3) << 14)
+            | ((timestamp & 0xffffffff) << 16)
+        )
+
+    @staticmethod
+    def construct_enumerate(serial: bytes, is_bootloader: bool) -> int:
+        """Constructs a ENUMERATE message.
+
+        :param serial: Device-unique serial number (buf:48)
+        :param is_bootloader: Device is in bootloader. (bool)
+        :return: message data as an int
+        """
+        return (
+            (int.from_bytes(serial, "little") & 0xffffffffffff)
+            | ((int(bool(is_bootloader))) << 48)
+        )
+
+    @staticmethod
+    def construct_party_mode(party_level: int) -> int:
+        """Constructs a PARTY_MODE message.
+
+        :param party_level: Party level. 0 disables the strobe, whereas 1 enables it. (uint:8)
+        :return: message data as an int
+        """
+        return party_level & 0xff
+
+    @staticmethod
+    def construct_status(faults: int, sticky_faults: int, temperature: int) -> int:
+        """Constructs a STATUS message.
+
+        :param faults: 8-bit active faults bitfield (uint:8)
+        :param sticky_faults: 8-bit sticky faults bitfield (uint:8)
+        :param temperature: 8-bit signed temperature byte in Celsius (sint:8)
+        :return: message data as an int
+        """
+        return (
+            (faults & 0xff)
+            | ((sticky_faults & 0xff) << 8)
+            | ((temperature & 0xff) << 16)
+        )
+
+    @staticmethod
+    def construct_clear_sticky_faults() -> int:
+        """Constructs a CLEAR_STICKY_FAULTS message.
+
+        :return: message data as an int
+        """
+        return 0
+
+    @staticmethod
+    def construct_report_setting(address: int, value: bytes, flags: int) -> int:
+        """Constructs a REPORT_SETTING message.
+
+        :param address: Setting index to write to (enum:SETTING)
+        :param value: 6-byte setting value (buf:48)
+        :param flags: Setting receive status (uint:8)
+        :return: message data as an int
+        """
+        return (
+            (address & 0xff)
+            | ((int.from_bytes(value, "little") & 0xffffffffffff) << 8)
+            | ((flags & 0xff) << 56)
+        )
+
+    @staticmethod
+    def construct_set_setting(address: int, value: bytes, flags_ephemeral: bool, flags_synch_hold: bool, flags_synch_msg_count: int) -> int:
+        """Constructs a SET_SETTING message.
+
+        :param address: Setting index to write to (enum:SETTING)
+        :param value: 6-byte setting value (buf:48)
+        :param flags_ephemeral: Whether the setting should be set ephemeral (bool)
+        :param flags_synch_hold: Whether the setting should be held until the next synch barrier (bool)
+        :param flags_synch_msg_count: Synch message count (uint:4)
+        :return: message data as an int
+        """
+        return (
+            (address & 0xff)
+            | ((int.from_bytes(value, "little") & 0xffffffffffff) << 8)
+            | ((int(bool(flags_ephemeral))) << 56)
+            | ((int(bool(flags_synch_hold))) << 57)
+            | ((flags_synch_msg_count & 0xf) << 60)
+        )
+
+    @staticmethod
+    def construct_setting_command(control_flag: int, setting_index: int) -> int:
+        """Constructs a SETTING_COMMAND message.
+
+        :param control_flag: Setting command index (enum:SETTING_COMMAND)
+        :param setting_index: setting index to fetch (enum:SETTING)
+        :return: message data as an int
+        """
+        return (
+            (control_flag & 0xff)
+            | ((setting_index & 0xff) << 8)
+        )
+
+    DLC_POSITION_OUTPUT = 6
+    """POSITION_OUTPUT message length"""
+
+    DLC_VELOCITY_OUTPUT = 3
+    """VELOCITY_OUTPUT message length"""
+
+    DLC_RAW_POSITION_OUTPUT = 6
+    """RAW_POSITION_OUTPUT message length"""
+
+    DLC_ENUMERATE = 8
+    """ENUMERATE message length"""
+
+    DLC_MIN_PARTY_MODE = 1
+    """PARTY_MODE message min length"""
+
+    DLC_MAX_PARTY_MODE = 8
+    """PARTY_MODE message max length"""
+
+    DLC_STATUS = 8
+    """STATUS message length"""
+
+    DLC_MIN_CLEAR_STICKY_FAULTS = 0
+    """CLEAR_STICKY_FAULTS message min length"""
+
+    DLC_MAX_CLEAR_STICKY_FAULTS = 8
+    """CLEAR_STICKY_FAULTS message max length"""
+
+    DLC_REPORT_SETTING = 8
+    """REPORT_SETTING message length"""
+
+    DLC_SET_SETTING = 8
+    """SET_SETTING message length"""
+
+    DLC_MIN_SETTING_COMMAND = 1
+    """SETTING_COMMAND message min length"""
+
+    DLC_MAX_SETTING_COMMAND = 8
+    """SETTING_COMMAND message max length"""
+
+
+class Stg:
+    """Settings."""
+
+    ZERO_OFFSET = 0xff
+    """Encoder zero offset"""
+
+    VELOCITY_WINDOW = 0xfe
+    """Velocity window width (value*250us)"""
+
+    POSITION_FRAME_PERIOD = 0xfd
+    """Position frame period (ms)"""
+
+    VELOCITY_FRAME_PERIOD = 0xfc
+    """Velocity frame period (ms)"""
+
+    RAW_POSITION_FRAME_PERIOD = 0xfb
+    """Raw position frame period (ms)"""
+
+    INVERT_DIRECTION = 0xfa
+    """Invert direction (0=ccw, 1=cw)"""
+
+    RELATIVE_POSITION = 0xf9
+    """Set relative position value"""
+
+    DISABLE_ZERO_BUTTON = 0xf8
+    """Disable the zero button"""
+
+    SCRATCH_1 = 0xa
+    """User-writable scratch bytes 2"""
+
+    SCRATCH_0 = 0x9
+    """User-writable scratch bytes 1"""
+
+    DEVICE_TYPE = 0x8
+    """Device-specific type identifier"""
+
+    CHICKEN_BITS = 0x7
+    """Device-specific chicken bits"""
+
+    FIRMWARE_VERSION = 0x6
+    """Firmware version"""
+
+    SERIAL_NUMBER = 0x5
+    """Serial number"""
+
+    STATUS_FRAME_PERIOD = 0x4
+    """Status frame period (ms)"""
+
+    NAME_2 = 0x3
+    """device_name[12:17]"""
+
+    NAME_1 = 0x2
+    """device_name[6:11]"""
+
+    NAME_0 = 0x1
+    """device_name[0:5]"""
+
+    CAN_ID = 0x0
+    """CAN Device ID"""
+
+    @staticmethod
+    def extract_zero_offset_offset_or_position(field: int) -> int:
+        """Extracts Zero offset or position from ZERO_OFFSET.
+
+        :param field: data bitfield
+        :return: offset_or_position as a uint:14
+        """
+        return field & 0x3fff
+
+    @staticmethod
+    def extract_zero_offset_position_bit(field: int) -> bool:
+        """Extracts True to set position instead of a zero offset. from ZERO_OFFSET.
+
+        :param field: data bitfield
+        :return: position_bit as a bool
+        """
+        return bool((field >> 16) & 0x1)
+
+    @staticmethod
+    def extract_velocity_window(field: int) -> int:
+        """Extracts Velocity window width (value*250us) from VELOCITY_WINDOW.
+
+        :param field: data bitfield
+        :return: VELOCITY_WINDOW as a uint:8
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_position_frame_period(field: int) -> int:
+        """Extracts Position frame period (ms) from POSITION_FRAME_PERIOD.
+
+        :param field: data bitfield
+        :return: POSITION_FRAME_PERIOD as a uint:16
+        """
+        return field & 0xffff
+
+    @staticmethod
+    def extract_velocity_frame_period(field: int) -> int:
+        """Extracts Velocity frame period (ms) from VELOCITY_FRAME_PERIOD.
+
+        :param field: data bitfield
+        :return: VELOCITY_FRAME_PERIOD as a uint:16
+        """
+        return field & 0xffff
+
+    @staticmethod
+    def extract_raw_position_frame_period(field: int) -> int:
+        """Extracts Raw position frame period (ms) from RAW_POSITION_FRAME_PERIOD.
+
+        :param field: data bitfield
+        :return: RAW_POSITION_FRAME_PERIOD as a uint:16
+        """
+        return field & 0xffff
+
+    @staticmethod
+    def extract_invert_direction(field: int) -> bool:
+        """Extracts Invert direction (0=ccw, 1=cw) from INVERT_DIRECTION.
+
+        :param field: data bitfield
+        :return: INVERT_DIRECTION as a bool
+        """
+        return bool(field & 0x1)
+
+    @staticmethod
+    def extract_relative_position(field: int) -> int:
+        """Extracts Set relative position value from RELATIVE_POSITION.
+
+        :param field: data bitfield
+        :return: RELATIVE_POSITION as a sint:32
+        """
+        return _sign_extend(field & 0xffffffff, 32)
+
+    @staticmethod
+    def extract_disable_zero_button(field: int) -> bool:
+        """Extracts Disable the zero button from DISABLE_ZERO_BUTTON.
+
+        :param field: data bitfield
+        :return: DISABLE_ZERO_BUTTON as a bool
+        """
+        return bool(field & 0x1)
+
+    @staticmethod
+    def extract_scratch_1(field: int) -> bytes:
+        """Extracts User-writable scratch bytes 2 from SCRATCH_1.
+
+        :param field: data bitfield
+        :return: SCRATCH_1 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_scratch_0(field: int) -> bytes:
+        """Extracts User-writable scratch bytes 1 from SCRATCH_0.
+
+        :param field: data bitfield
+        :return: SCRATCH_0 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_device_type(field: int) -> int:
+        """Extracts Device-specific type identifier from DEVICE_TYPE.
+
+        :param field: data bitfield
+        :return: DEVICE_TYPE as a uint:16
+        """
+        return field & 0xffff
+
+    @staticmethod
+    def extract_chicken_bits(field: int) -> bytes:
+        """Extracts Device-specific chicken bits from CHICKEN_BITS.
+
+        :param field: data bitfield
+        :return: CHICKEN_BITS as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_firmware_version_firmware_patch(field: int) -> int:
+        """Extracts Firmware version patch number from FIRMWARE_VERSION.
+
+        :param field: data bitfield
+        :return: firmware_patch as a uint:8
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_firmware_version_firmware_minor(field: int) -> int:
+        """Extracts Firmware version minor number from FIRMWARE_VERSION.
+
+        :param field: data bitfield
+        :return: firmware_minor as a uint:8
+        """
+        return (field >> 8) & 0xff
+
+    @staticmethod
+    def extract_firmware_version_firmware_year(field: int) -> int:
+        """Extracts Firmware version year from FIRMWARE_VERSION.
+
+        :param field: data bitfield
+        :return: firmware_year as a uint:16
+        """
+        return (field >> 16) & 0xffff
+
+    @staticmethod
+    def extract_serial_number(field: int) -> bytes:
+        """Extracts Serial number from SERIAL_NUMBER.
+
+        :param field: data bitfield
+        :return: SERIAL_NUMBER as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_status_frame_period(field: int) -> int:
+        """Extracts Status frame period (ms) from STATUS_FRAME_PERIOD.
+
+        :param field: data bitfield
+        :return: STATUS_FRAME_PERIOD as a uint:16
+        """
+        return field & 0xffff
+
+    @staticmethod
+    def extract_name_2(field: int) -> bytes:
+        """Extracts device_name[12:17] from NAME_2.
+
+        :param field: data bitfield
+        :return: NAME_2 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_name_1(field: int) -> bytes:
+        """Extracts device_name[6:11] from NAME_1.
+
+        :param field: data bitfield
+        :return: NAME_1 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_name_0(field: int) -> bytes:
+        """Extracts device_name[0:5] from NAME_0.
+
+        :param field: data bitfield
+        :return: NAME_0 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_can_id(field: int) -> int:
+        """Extracts CAN Device ID from CAN_ID.
+
+        :param field: data bitfield
+        :return: CAN_ID as a uint:8
+        """
+        return field & 0xff
+
+    @staticmethod
+    def construct_zero_offset(offset_or_position: int, position_bit: bool) -> int:
+        """Constructs a ZERO_OFFSET setting.
+
+        :param offset_or_position: Zero offset or position (uint:14)
+        :param position_bit: True to set position instead of a zero offset. (bool)
+        :return: setting data as an int
+        """
+        if not 0 <= offset_or_position <= 16383:
+            raise ValueError(f"offset_or_position must be between [0..=16383] inclusive, instead got {offset_or_position}")
+        return (
+            (offset_or_position & 0x3fff)
+            | ((int(bool(position_bit))) << 16)
+        )
+
+    @staticmethod
+    def construct_velocity_window(velocity_window: int) -> int:
+        """Constructs a VELOCITY_WINDOW setting.
+
+        :param velocity_window: Velocity window width (value*250us) (uint:8)
+        :return: setting data as an int
+        """
+        if not 1 <= velocity_window <= 255:
+            raise ValueError(f"velocity_window must be between [1..=255] inclusive, instead got {velocity_window}")
+        return velocity_window & 0xff
+
+    @staticmethod
+    def construct_position_frame_period(position_frame_period: int) -> int:
+        """Constructs a POSITION_FRAME_PERIOD setting.
+
+        :param position_frame_period: Position frame period (ms) (uint:16)
+        :return: setting data as an int
+        """
+        if not 0 <= position_frame_period <= 65535:
+            raise ValueError(f"position_frame_period must be between [0..=65535] inclusive, instead got {position_frame_period}")
+        return position_frame_period & 0xffff
+
+    @staticmethod
+    def construct_velocity_frame_period(velocity_frame_period: int) -> int:
+        """Constructs a VELOCITY_FRAME_PERIOD setting.
+
+        :param velocity_frame_period: Velocity frame period (ms) (uint:16)
+        :return: setting data as an int
+        """
+        if not 0 <= velocity_frame_period <= 65535:
+            raise ValueError(f"velocity_frame_period must be between [0..=65535] inclusive, instead got {velocity_frame_period}")
+        return velocity_frame_period & 0xffff
+
+    @staticmethod
+    def construct_raw_position_frame_period(raw_position_frame_period: int) -> int:
+        """Constructs a RAW_POSITION_FRAME_PERIOD setting.
+
+        :param raw_position_frame_period: Raw position frame period (ms) (uint:16)
+        :return: setting data as an int
+        """
+        if not 0 <= raw_position_frame_period <= 65535:
+            raise ValueError(f"raw_position_frame_period must be between [0..=65535] inclusive, instead got {raw_position_frame_period}")
+        return raw_position_frame_period & 0xffff
+
+    @staticmethod
+    def construct_invert_direction(invert_direction: bool) -> int:
+        """Constructs a INVERT_DIRECTION setting.
+
+        :param invert_direction: Invert direction (0=ccw, 1=cw) (bool)
+        :return: setting data as an int
+        """
+        return int(bool(invert_direction))
+
+    @staticmethod
+    def construct_relative_position(relative_position: int) -> int:
+        """Constructs a RELATIVE_POSITION setting.
+
+        :param relative_position: Set relative position value (sint:32)
+        :return: setting data as an int
+        """
+        if not -2147483648 <= relative_position <= 2147483647:
+            raise ValueError(f"relative_position must be between [-2147483648..=2147483647] inclusive, instead got {relative_position}")
+        return relative_position & 0xffffffff
+
+    @staticmethod
+    def construct_disable_zero_button(disable_zero_button: bool) -> int:
+        """Constructs a DISABLE_ZERO_BUTTON setting.
+
+        :param disable_zero_button: Disable the zero button (bool)
+        :return: setting data as an int
+        """
+        return int(bool(disable_zero_button))
+
+    @staticmethod
+    def construct_scratch_1(scratch_1: bytes) -> int:
+        """Constructs a SCRATCH_1 setting.
+
+        :param scratch_1: User-writable scratch bytes 2 (buf:48)
+        :return: setting data as an int
+        """
+        if len(scratch_1) != 6:
+            raise ValueError(f"scratch_1 must be 6 bytes long, instead got {len(scratch_1)}")
+        return int.from_bytes(scratch_1, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_scratch_0(scratch_0: bytes) -> int:
+        """Constructs a SCRATCH_0 setting.
+
+        :param scratch_0: User-writable scratch bytes 1 (buf:48)
+        :return: setting data as an int
+        """
+        if len(scratch_0) != 6:
+            raise ValueError(f"scratch_0 must be 6 bytes long, instead got {len(scratch_0)}")
+        return int.from_bytes(scratch_0, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_device_type(device_type: int) -> int:
+        """Constructs a DEVICE_TYPE setting.
+
+        :param device_type: Device-specific type identifier (uint:16)
+        :return: setting data as an int
+        """
+        if not 0 <= device_type <= 65535:
+            raise ValueError(f"device_type must be between [0..=65535] inclusive, instead got {device_type}")
+        return device_type & 0xffff
+
+    @staticmethod
+    def construct_chicken_bits(chicken_bits: bytes) -> int:
+        """Constructs a CHICKEN_BITS setting.
+
+        :param chicken_bits: Device-specific chicken bits (buf:48)
+        :return: setting data as an int
+        """
+        if len(chicken_bits) != 6:
+            raise ValueError(f"chicken_bits must be 6 bytes long, instead got {len(chicken_bits)}")
+        return int.from_bytes(chicken_bits, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_firmware_version(firmware_patch: int, firmware_minor: int, firmware_year: int) -> int:
+        """Constructs a FIRMWARE_VERSION setting.
+
+        :param firmware_patch: Firmware version patch number (uint:8)
+        :param firmware_minor: Firmware version minor number (uint:8)
+        :param firmware_year: Firmware version year (uint:16)
+        :return: setting data as an int
+        """
+        if not 0 <= firmware_patch <= 255:
+            raise ValueError(f"firmware_patch must be between [0..=255] inclusive, instead got {firmware_patch}")
+        if not 0 <= firmware_minor <= 255:
+            raise ValueError(f"firmware_minor must be between [0..=255] inclusive, instead got {firmware_minor}")
+        if not 0 <= firmware_year <= 65535:
+            raise ValueError(f"firmware_year must be between [0..=65535] inclusive, instead got {firmware_year}")
+        return (
+            (firmware_patch & 0xff)
+            | ((firmware_minor & 0xff) << 8)
+            | ((firmware_year & 0xffff) << 16)
+        )
+
+    @staticmethod
+    def construct_serial_number(serial_number: bytes) -> int:
+        """Constructs a SERIAL_NUMBER setting.
+
+        :param serial_number: Serial number (buf:48)
+        :return: setting data as an int
+        """
+        if len(serial_number) != 6:
+            raise ValueError(f"serial_number must be 6 bytes long, instead got {len(serial_number)}")
+        return int.from_bytes(serial_number, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_status_frame_period(status_frame_period: int) -> int:
+        """Constructs a STATUS_FRAME_PERIOD setting.
+
+        :param status_frame_period: Status frame period (ms) (uint:16)
+        :return: setting data as an int
+        """
+        if not 0 <= status_frame_period <= 65535:
+            raise ValueError(f"status_frame_period must be between [0..=65535] inclusive, instead got {status_frame_period}")
+        return status_frame_period & 0xffff
+
+    @staticmethod
+    def construct_name_2(name_2: bytes) -> int:
+        """Constructs a NAME_2 setting.
+
+        :param name_2: device_name[12:17] (buf:48)
+        :return: setting data as an int
+        """
+        if len(name_2) != 6:
+            raise ValueError(f"name_2 must be 6 bytes long, instead got {len(name_2)}")
+        return int.from_bytes(name_2, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_name_1(name_1: bytes) -> int:
+        """Constructs a NAME_1 setting.
+
+        :param name_1: device_name[6:11] (buf:48)
+        :return: setting data as an int
+        """
+        if len(name_1) != 6:
+            raise ValueError(f"name_1 must be 6 bytes long, instead got {len(name_1)}")
+        return int.from_bytes(name_1, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_name_0(name_0: bytes) -> int:
+        """Constructs a NAME_0 setting.
+
+        :param name_0: device_name[0:5] (buf:48)
+        :return: setting data as an int
+        """
+        if len(name_0) != 6:
+            raise ValueError(f"name_0 must be 6 bytes long, instead got {len(name_0)}")
+        return int.from_bytes(name_0, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_can_id(can_id: int) -> int:
+        """Constructs a CAN_ID setting.
+
+        :param can_id: CAN Device ID (uint:8)
+        :return: setting data as an int
+        """
+        if not 0 <= can_id <= 63:
+            raise ValueError(f"can_id must be between [0..=63] inclusive, instead got {can_id}")
+        return can_id & 0xff
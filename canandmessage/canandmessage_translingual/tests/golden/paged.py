@@ -0,0 +1,856 @@
+# Copyright (c) Redux Robotics and other contributors.
+# This is open source and can be modified and shared under the 3-clause BSD license.
+"""Paged device constants.
+
+This file is autogenerated by canandmessage, do not hand-edit!
+"""
+
+
+import enum
+import math
+import struct
+
+
+def _sign_extend(value: int, width: int) -> int:
+    """Interprets the low width bits of value as two's complement."""
+    return value - (1 << width) if value & (1 << (width - 1)) else value
+
+
+def _swap_bytes(value: int, width: int) -> int:
+    """Reverses the byte order of a width-bit value."""
+    return int.from_bytes(value.to_bytes(width // 8, "little"), "big")
+
+
+def _float_from_bits(bits: int, width: int) -> float:
+    """Decodes a float. 24-bit floats are single precision missing the low mantissa byte."""
+    if width == 64:
+        return struct.unpack("<d", bits.to_bytes(8, "little"))[0]
+    return struct.unpack("<f", (bits << (32 - width)).to_bytes(4, "little"))[0]
+
+
+def _float_to_bits(value: float, width: int) -> int:
+    """Encodes a float, the inverse of _float_from_bits."""
+    if width == 64:
+        return int.from_bytes(struct.pack("<d", value), "little")
+    return int.from_bytes(struct.pack("<f", value), "little") >> (32 - width)
+
+
+class AtomicBondBusRate(enum.IntEnum):
+    """enum Paged::ATOMIC_BOND_BUS_RATE"""
+
+    RATE_1M_2B = 0x0
+    """1 megabit/s CAN 2.0B"""
+
+    RATE_RESERVED_0 = 0x1
+    """1 megabit/s CAN-FD"""
+
+    RATE_RESERVED_1 = 0x2
+    """5 megabit/s CAN-FD"""
+
+    RATE_RESERVED_2 = 0x3
+    """8 megabit/s CAN-FD"""
+
+
+class Page(enum.IntEnum):
+    """enum Paged::PAGE"""
+
+    SPEED = 0x0
+    """Speed page"""
+
+    LIMITS = 0x1
+    """Limits page"""
+
+
+class SettingCommand(enum.IntEnum):
+    """enum Paged::SETTING_COMMAND"""
+
+    FETCH_SETTINGS = 0x0
+    """Fetch all settings from device via a series of :ref:`report setting<msg_report_setting>` messages of all indexes"""
+
+    RESET_FACTORY_DEFAULT = 0x1
+    """Reset all resettanble settings to factory default, and broadcast all setting values via
+    :ref:`report setting<msg_report_setting>` messages.
+    """
+
+    FETCH_SETTING_VALUE = 0x2
+    """Requests to fetch a single setting from device, with its value reported via the
+    :ref:`report setting<msg_report_setting>` message.
+
+    This requires the use of the second byte to specify the setting index to fetch.
+    """
+
+
+class AtomicAnnouncementFlags(enum.IntFlag):
+    """bitset Paged::atomic_announcement_flags"""
+
+    negotiation = 0x1
+    """Device should enter negotiation phase"""
+
+    init = 0x2
+    """Device should initialize bus with new rate"""
+
+    confirm = 0x4
+    """Device should confirm new bus rate"""
+
+    begin_tx = 0x8
+    """Device should begin transmission"""
+
+    bus_interrupt = 0x10
+    """Device should cease all transmission"""
+
+
+class SettingReportFlags(enum.IntFlag):
+    """bitset Paged::setting_report_flags"""
+
+    set_success = 0x1
+    """Whether the setting set/fetch was successful"""
+
+    commit_success = 0x2
+    """Whether the setting synch commit was successful"""
+
+
+class Msg:
+    """Messages."""
+
+    WIDE_OUTPUT = 0x15
+    """Wide output"""
+
+    PAGED_OUTPUT = 0x14
+    """Paged output"""
+
+    ENUMERATE = 0xb
+    """Device enumerate response"""
+
+    PARTY_MODE = 0x7
+    """Party mode"""
+
+    STATUS = 0x6
+    """Status frame"""
+
+    CLEAR_STICKY_FAULTS = 0x5
+    """Clear device sticky faults"""
+
+    REPORT_SETTING = 0x4
+    """setting value report from device"""
+
+    SET_SETTING = 0x3
+    """update setting on device"""
+
+    SETTING_COMMAND = 0x2
+    """setting control command"""
+
+    @staticmethod
+    def extract_wide_output_count(field: int) -> int:
+        """Extracts Count from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :return: count as a uint:32
+        """
+        return _swap_bytes(field & 0xffffffff, 32)
+
+    @staticmethod
+    def extract_wide_output_flags(field: int) -> int:
+        """Extracts Flags from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :return: flags as a uint:4
+        """
+        return (field >> 32) & 0xf
+
+    @staticmethod
+    def extract_wide_output_temp(field: int) -> int:
+        """Extracts Temperature from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :return: temp as a sint:16
+        """
+        return _sign_extend(_swap_bytes((field >> 40) & 0xffff, 16), 16)
+
+    @staticmethod
+    def extract_wide_output_energy(field: int) -> float:
+        """Extracts Energy from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :return: energy as a float:32
+        """
+        return _float_from_bits(_swap_bytes((field >> 56) & 0xffffffff, 32), 32)
+
+    @staticmethod
+    def extract_wide_output_from_0(field: int) -> int:
+        """Extracts Samples [0] from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :return: from_0 as a uint:12
+        """
+        return (field >> 88) & 0xfff
+
+    @staticmethod
+    def extract_wide_output_from_1(field: int) -> int:
+        """Extracts Samples [1] from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :return: from_1 as a uint:12
+        """
+        return (field >> 100) & 0xfff
+
+    @staticmethod
+    def extract_wide_output_from_2(field: int) -> int:
+        """Extracts Samples [2] from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :return: from_2 as a uint:12
+        """
+        return (field >> 112) & 0xfff
+
+    @staticmethod
+    def extract_wide_output_from(field: int, index: int) -> int:
+        """Extracts element index of Samples from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :param index: array index, from 0 to 2
+        :return: element index of from as a uint:12
+        """
+        if not 0 <= index < 3:
+            raise IndexError(f"from index {index}")
+        return (field >> (88 + index * 12)) & 0xfff
+
+    @staticmethod
+    def extract_wide_output_yaw(field: int) -> float:
+        """Extracts Yaw from WIDE_OUTPUT.
+
+        :param field: data bitfield
+        :return: yaw as a float:24
+        """
+        return _float_from_bits((field >> 124) & 0xffffff, 24)
+
+    @staticmethod
+    def extract_paged_output_page(field: int) -> int:
+        """Extracts Which page follows from PAGED_OUTPUT.
+
+        :param field: data bitfield
+        :return: page as a enum:PAGE
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_paged_output_speed(field: int) -> int:
+        """Extracts Speed from PAGED_OUTPUT.
+
+        :param field: data bitfield
+        :return: speed as a sint:16
+        """
+        return _sign_extend((field >> 8) & 0xffff, 16)
+
+    @staticmethod
+    def extract_paged_output_lo(field: int) -> int:
+        """Extracts Low limit from PAGED_OUTPUT.
+
+        :param field: data bitfield
+        :return: lo as a uint:12
+        """
+        return (field >> 8) & 0xfff
+
+    @staticmethod
+    def extract_paged_output_hi(field: int) -> int:
+        """Extracts High limit from PAGED_OUTPUT.
+
+        :param field: data bitfield
+        :return: hi as a uint:12
+        """
+        return (field >> 20) & 0xfff
+
+    @staticmethod
+    def extract_enumerate_serial(field: int) -> bytes:
+        """Extracts Device-unique serial number from ENUMERATE.
+
+        :param field: data bitfield
+        :return: serial as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_enumerate_is_bootloader(field: int) -> bool:
+        """Extracts Device is in bootloader. from ENUMERATE.
+
+        :param field: data bitfield
+        :return: is_bootloader as a bool
+        """
+        return bool((field >> 48) & 0x1)
+
+    @staticmethod
+    def extract_party_mode_party_level(field: int) -> int:
+        """Extracts Party level. 0 disables the strobe, whereas 1 enables it. from PARTY_MODE.
+
+        :param field: data bitfield
+        :return: party_level as a uint:8
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_status_dev_specific(field: int) -> bytes:
+        """Extracts Device-specific status data. See device pages for more information. from STATUS.
+
+        :param field: data bitfield
+        :return: dev_specific as a buf:64
+        """
+        return (field & 0xffffffffffffffff).to_bytes(8, "little")
+
+    @staticmethod
+    def extract_report_setting_address(field: int) -> int:
+        """Extracts Setting index to write to from REPORT_SETTING.
+
+        :param field: data bitfield
+        :return: address as a enum:SETTING
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_report_setting_value(field: int) -> bytes:
+        """Extracts 6-byte setting value from REPORT_SETTING.
+
+        :param field: data bitfield
+        :return: value as a buf:48
+        """
+        return ((field >> 8) & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_report_setting_flags(field: int) -> int:
+        """Extracts Setting receive status from REPORT_SETTING.
+
+        :param field: data bitfield
+        :return: flags as a uint:8
+        """
+        return (field >> 56) & 0xff
+
+    @staticmethod
+    def extract_set_setting_address(field: int) -> int:
+        """Extracts Setting index to write to from SET_SETTING.
+
+        :param field: data bitfield
+        :return: address as a enum:SETTING
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_set_setting_value(field: int) -> bytes:
+        """Extracts 6-byte setting value from SET_SETTING.
+
+        :param field: data bitfield
+        :return: value as a buf:48
+        """
+        return ((field >> 8) & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_set_setting_flags_ephemeral(field: int) -> bool:
+        """Extracts Whether the setting should be set ephemeral from SET_SETTING_FLAGS.
+
+        :param field: data bitfield
+        :return: ephemeral as a bool
+        """
+        return bool((field >> 56) & 0x1)
+
+    @staticmethod
+    def extract_set_setting_flags_synch_hold(field: int) -> bool:
+        """Extracts Whether the setting should be held until the next synch barrier from SET_SETTING_FLAGS.
+
+        :param field: data bitfield
+        :return: synch_hold as a bool
+        """
+        return bool((field >> 57) & 0x1)
+
+    @staticmethod
+    def extract_set_setting_flags_synch_msg_count(field: int) -> int:
+        """Extracts Synch message count from SET_SETTING_FLAGS.
+
+        :param field: data bitfield
+        :return: synch_msg_count as a uint:4
+        """
+        return (field >> 60) & 0xf
+
+    @staticmethod
+    def extract_setting_command_control_flag(field: int) -> int:
+        """Extracts Setting command index from SETTING_COMMAND.
+
+        :param field: data bitfield
+        :return: control_flag as a enum:SETTING_COMMAND
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_setting_command_setting_index(field: int) -> int:
+        """Extracts setting index to fetch from SETTING_COMMAND.
+
+        :param field: data bitfield
+        :return: setting_index as a enum:SETTING
+        """
+        return (field >> 8) & 0xff
+
+    @staticmethod
+    def construct_wide_output(count: int, flags: int, temp: int, energy: float, from_0: int, from_1: int, from_2: int, yaw: float) -> int:
+        """Constructs a WIDE_OUTPUT message.
+
+        :param count: Count (uint:32)
+        :param flags: Flags (uint:4)
+        :param temp: Temperature (sint:16)
+        :param energy: Energy (float:32)
+        :param from_0: Samples [0] (uint:12)
+        :param from_1: Samples [1] (uint:12)
+        :param from_2: Samples [2] (uint:12)
+        :param yaw: Yaw (float:24)
+        :return: message data as an int
+        """
+        return (
+            (_swap_bytes(count & 0xffffffff, 32))
+            | ((flags & 0xf) << 32)
+            | ((_swap_bytes(temp & 0xffff, 16)) << 40)
+            | ((_swap_bytes(_float_to_bits(energy, 32), 32)) << 56)
+            | ((from_0 & 0xfff) << 88)
+            | ((from_1 & 0xfff) << 100)
+            | ((from_2 & 0xfff) << 112)
+            | ((_float_to_bits(yaw, 24)) << 124)
+        )
+
+    @staticmethod
+    def construct_paged_output(page: int) -> int:
+        """Constructs a PAGED_OUTPUT message.
+
+        :param page: Which page follows (enum:PAGE)
+        :return: message data as an int
+        """
+        return page & 0xff
+
+    @staticmethod
+    def construct_paged_output_speed(speed: int) -> int:
+        """Constructs a PAGED_OUTPUT_SPEED message.
+
+        :param speed: Speed (sint:16)
+        :return: message data as an int
+        """
+        return (
+            (0x0)
+            | ((speed & 0xffff) << 8)
+        )
+
+    @staticmethod
+    def construct_paged_output_limits(lo: int, hi: int) -> int:
+        """Constructs a PAGED_OUTPUT_LIMITS message.
+
+        :param lo: Low limit (uint:12)
+        :param hi: High limit (uint:12)
+        :return: message data as an int
+        """
+        return (
+            (0x1)
+            | ((lo & 0xfff) << 8)
+            | ((hi & 0xfff) << 20)
+        )
+
+    @staticmethod
+    def construct_enumerate(serial: bytes, is_bootloader: bool) -> int:
+        """Constructs a ENUMERATE message.
+
+        :param serial: Device-unique serial number (buf:48)
+        :param is_bootloader: Device is in bootloader. (bool)
+        :return: message data as an int
+        """
+        return (
+            (int.from_bytes(serial, "little") & 0xffffffffffff)
+            | ((int(bool(is_bootloader))) << 48)
+        )
+
+    @staticmethod
+    def construct_party_mode(party_level: int) -> int:
+        """Constructs a PARTY_MODE message.
+
+        :param party_level: Party level. 0 disables the strobe, whereas 1 enables it. (uint:8)
+        :return: message data as an int
+        """
+        return party_level & 0xff
+
+    @staticmethod
+    def construct_status(dev_specific: bytes) -> int:
+        """Constructs a STATUS message.
+
+        :param dev_specific: Device-specific status data. See device pages for more information. (buf:64)
+        :return: message data as an int
+        """
+        return int.from_bytes(dev_specific, "little") & 0xffffffffffffffff
+
+    @staticmethod
+    def construct_clear_sticky_faults() -> int:
+        """Constructs a CLEAR_STICKY_FAULTS message.
+
+        :return: message data as an int
+        """
+        return 0
+
+    @staticmethod
+    def construct_report_setting(address: int, value: bytes, flags: int) -> int:
+        """Constructs a REPORT_SETTING message.
+
+        :param address: Setting index to write to (enum:SETTING)
+        :param value: 6-byte setting value (buf:48)
+        :param flags: Setting receive status (uint:8)
+        :return: message data as an int
+        """
+        return (
+            (address & 0xff)
+            | ((int.from_bytes(value, "little") & 0xffffffffffff) << 8)
+            | ((flags & 0xff) << 56)
+        )
+
+    @staticmethod
+    def construct_set_setting(address: int, value: bytes, flags_ephemeral: bool, flags_synch_hold: bool, flags_synch_msg_count: int) -> int:
+        """Constructs a SET_SETTING message.
+
+        :param address: Setting index to write to (enum:SETTING)
+        :param value: 6-byte setting value (buf:48)
+        :param flags_ephemeral: Whether the setting should be set ephemeral (bool)
+        :param flags_synch_hold: Whether the setting should be held until the next synch barrier (bool)
+        :param flags_synch_msg_count: Synch message count (uint:4)
+        :return: message data as an int
+        """
+        return (
+            (address & 0xff)
+            | ((int.from_bytes(value, "little") & 0xffffffffffff) << 8)
+            | ((int(bool(flags_ephemeral))) << 56)
+            | ((int(bool(flags_synch_hold))) << 57)
+            | ((flags_synch_msg_count & 0xf) << 60)
+        )
+
+    @staticmethod
+    def construct_setting_command(control_flag: int, setting_index: int) -> int:
+        """Constructs a SETTING_COMMAND message.
+
+        :param control_flag: Setting command index (enum:SETTING_COMMAND)
+        :param setting_index: setting index to fetch (enum:SETTING)
+        :return: message data as an int
+        """
+        return (
+            (control_flag & 0xff)
+            | ((setting_index & 0xff) << 8)
+        )
+
+    DLC_WIDE_OUTPUT = 20
+    """WIDE_OUTPUT message length"""
+
+    DLC_MIN_PAGED_OUTPUT = 1
+    """PAGED_OUTPUT message min length"""
+
+    DLC_MAX_PAGED_OUTPUT = 4
+    """PAGED_OUTPUT message max length"""
+
+    DLC_ENUMERATE = 8
+    """ENUMERATE message length"""
+
+    DLC_MIN_PARTY_MODE = 1
+    """PARTY_MODE message min length"""
+
+    DLC_MAX_PARTY_MODE = 8
+    """PARTY_MODE message max length"""
+
+    DLC_STATUS = 8
+    """STATUS message length"""
+
+    DLC_MIN_CLEAR_STICKY_FAULTS = 0
+    """CLEAR_STICKY_FAULTS message min length"""
+
+    DLC_MAX_CLEAR_STICKY_FAULTS = 8
+    """CLEAR_STICKY_FAULTS message max length"""
+
+    DLC_REPORT_SETTING = 8
+    """REPORT_SETTING message length"""
+
+    DLC_SET_SETTING = 8
+    """SET_SETTING message length"""
+
+    DLC_MIN_SETTING_COMMAND = 1
+    """SETTING_COMMAND message min length"""
+
+    DLC_MAX_SETTING_COMMAND = 8
+    """SETTING_COMMAND message max length"""
+
+
+class Stg:
+    """Settings."""
+
+    SCRATCH_1 = 0xa
+    """User-writable scratch bytes 2"""
+
+    SCRATCH_0 = 0x9
+    """User-writable scratch bytes 1"""
+
+    DEVICE_TYPE = 0x8
+    """Device-specific type identifier"""
+
+    CHICKEN_BITS = 0x7
+    """Device-specific chicken bits"""
+
+    FIRMWARE_VERSION = 0x6
+    """Firmware version"""
+
+    SERIAL_NUMBER = 0x5
+    """Serial number"""
+
+    STATUS_FRAME_PERIOD = 0x4
+    """Status frame period (ms)"""
+
+    NAME_2 = 0x3
+    """device_name[12:17]"""
+
+    NAME_1 = 0x2
+    """device_name[6:11]"""
+
+    NAME_0 = 0x1
+    """device_name[0:5]"""
+
+    CAN_ID = 0x0
+    """CAN Device ID"""
+
+    @staticmethod
+    def extract_scratch_1(field: int) -> bytes:
+        """Extracts User-writable scratch bytes 2 from SCRATCH_1.
+
+        :param field: data bitfield
+        :return: SCRATCH_1 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_scratch_0(field: int) -> bytes:
+        """Extracts User-writable scratch bytes 1 from SCRATCH_0.
+
+        :param field: data bitfield
+        :return: SCRATCH_0 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_device_type(field: int) -> int:
+        """Extracts Device-specific type identifier from DEVICE_TYPE.
+
+        :param field: data bitfield
+        :return: DEVICE_TYPE as a uint:16
+        """
+        return field & 0xffff
+
+    @staticmethod
+    def extract_chicken_bits(field: int) -> bytes:
+        """Extracts Device-specific chicken bits from CHICKEN_BITS.
+
+        :param field: data bitfield
+        :return: CHICKEN_BITS as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_firmware_version_firmware_patch(field: int) -> int:
+        """Extracts Firmware version patch number from FIRMWARE_VERSION.
+
+        :param field: data bitfield
+        :return: firmware_patch as a uint:8
+        """
+        return field & 0xff
+
+    @staticmethod
+    def extract_firmware_version_firmware_minor(field: int) -> int:
+        """Extracts Firmware version minor number from FIRMWARE_VERSION.
+
+        :param field: data bitfield
+        :return: firmware_minor as a uint:8
+        """
+        return (field >> 8) & 0xff
+
+    @staticmethod
+    def extract_firmware_version_firmware_year(field: int) -> int:
+        """Extracts Firmware version year from FIRMWARE_VERSION.
+
+        :param field: data bitfield
+        :return: firmware_year as a uint:16
+        """
+        return (field >> 16) & 0xffff
+
+    @staticmethod
+    def extract_serial_number(field: int) -> bytes:
+        """Extracts Serial number from SERIAL_NUMBER.
+
+        :param field: data bitfield
+        :return: SERIAL_NUMBER as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_status_frame_period(field: int) -> int:
+        """Extracts Status frame period (ms) from STATUS_FRAME_PERIOD.
+
+        :param field: data bitfield
+        :return: STATUS_FRAME_PERIOD as a uint:16
+        """
+        return field & 0xffff
+
+    @staticmethod
+    def extract_name_2(field: int) -> bytes:
+        """Extracts device_name[12:17] from NAME_2.
+
+        :param field: data bitfield
+        :return: NAME_2 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_name_1(field: int) -> bytes:
+        """Extracts device_name[6:11] from NAME_1.
+
+        :param field: data bitfield
+        :return: NAME_1 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_name_0(field: int) -> bytes:
+        """Extracts device_name[0:5] from NAME_0.
+
+        :param field: data bitfield
+        :return: NAME_0 as a buf:48
+        """
+        return (field & 0xffffffffffff).to_bytes(6, "little")
+
+    @staticmethod
+    def extract_can_id(field: int) -> int:
+        """Extracts CAN Device ID from CAN_ID.
+
+        :param field: data bitfield
+        :return: CAN_ID as a uint:8
+        """
+        return field & 0xff
+
+    @staticmethod
+    def construct_scratch_1(scratch_1: bytes) -> int:
+        """Constructs a SCRATCH_1 setting.
+
+        :param scratch_1: User-writable scratch bytes 2 (buf:48)
+        :return: setting data as an int
+        """
+        if len(scratch_1) != 6:
+            raise ValueError(f"scratch_1 must be 6 bytes long, instead got {len(scratch_1)}")
+        return int.from_bytes(scratch_1, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_scratch_0(scratch_0: bytes) -> int:
+        """Constructs a SCRATCH_0 setting.
+
+        :param scratch_0: User-writable scratch bytes 1 (buf:48)
+        :return: setting data as an int
+        """
+        if len(scratch_0) != 6:
+            raise ValueError(f"scratch_0 must be 6 bytes long, instead got {len(scratch_0)}")
+        return int.from_bytes(scratch_0, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_device_type(device_type: int) -> int:
+        """Constructs a DEVICE_TYPE setting.
+
+        :param device_type: Device-specific type identifier (uint:16)
+        :return: setting data as an int
+        """
+        if not 0 <= device_type <= 65535:
+            raise ValueError(f"device_type must be between [0..=65535] inclusive, instead got {device_type}")
+        return device_type & 0xffff
+
+    @staticmethod
+    def construct_chicken_bits(chicken_bits: bytes) -> int:
+        """Constructs a CHICKEN_BITS setting.
+
+        :param chicken_bits: Device-specific chicken bits (buf:48)
+        :return: setting data as an int
+        """
+        if len(chicken_bits) != 6:
+            raise ValueError(f"chicken_bits must be 6 bytes long, instead got {len(chicken_bits)}")
+        return int.from_bytes(chicken_bits, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_firmware_version(firmware_patch: int, firmware_minor: int, firmware_year: int) -> int:
+        """Constructs a FIRMWARE_VERSION setting.
+
+        :param firmware_patch: Firmware version patch number (uint:8)
+        :param firmware_minor: Firmware version minor number (uint:8)
+        :param firmware_year: Firmware version year (uint:16)
+        :return: setting data as an int
+        """
+        if not 0 <= firmware_patch <= 255:
+            raise ValueError(f"firmware_patch must be between [0..=255] inclusive, instead got {firmware_patch}")
+        if not 0 <= firmware_minor <= 255:
+            raise ValueError(f"firmware_minor must be between [0..=255] inclusive, instead got {firmware_minor}")
+        if not 0 <= firmware_year <= 65535:
+            raise ValueError(f"firmware_year must be between [0..=65535] inclusive, instead got {firmware_year}")
+        return (
+            (firmware_patch & 0xff)
+            | ((firmware_minor & 0xff) << 8)
+            | ((firmware_year & 0xffff) << 16)
+        )
+
+    @staticmethod
+    def construct_serial_number(serial_number: bytes) -> int:
+        """Constructs a SERIAL_NUMBER setting.
+
+        :param serial_number: Serial number (buf:48)
+        :return: setting data as an int
+        """
+        if len(serial_number) != 6:
+            raise ValueError(f"serial_number must be 6 bytes long, instead got {len(serial_number)}")
+        return int.from_bytes(serial_number, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_status_frame_period(status_frame_period: int) -> int:
+        """Constructs a STATUS_FRAME_PERIOD setting.
+
+        :param status_frame_period: Status frame period (ms) (uint:16)
+        :return: setting data as an int
+        """
+        if not 1 <= status_frame_period <= 16383:
+            raise ValueError(f"status_frame_period must be between [1..=16383] inclusive, instead got {status_frame_period}")
+        return status_frame_period & 0xffff
+
+    @staticmethod
+    def construct_name_2(name_2: bytes) -> int:
+        """Constructs a NAME_2 setting.
+
+        :param name_2: device_name[12:17] (buf:48)
+        :return: setting data as an int
+        """
+        if len(name_2) != 6:
+            raise ValueError(f"name_2 must be 6 bytes long, instead got {len(name_2)}")
+        return int.from_bytes(name_2, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_name_1(name_1: bytes) -> int:
+        """Constructs a NAME_1 setting.
+
+        :param name_1: device_name[6:11] (buf:48)
+        :return: setting data as an int
+        """
+        if len(name_1) != 6:
+            raise ValueError(f"name_1 must be 6 bytes long, instead got {len(name_1)}")
+        return int.from_bytes(name_1, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_name_0(name_0: bytes) -> int:
+        """Constructs a NAME_0 setting.
+
+        :param name_0: device_name[0:5] (buf:48)
+        :return: setting data as an int
+        """
+        if len(name_0) != 6:
+            raise ValueError(f"name_0 must be 6 bytes long, instead got {len(name_0)}")
+        return int.from_bytes(name_0, "little") & 0xffffffffffff
+
+    @staticmethod
+    def construct_can_id(can_id: int) -> int:
+        """Constructs a CAN_ID setting.
+
+        :param can_id: CAN Device ID (uint:8)
+        :return: setting data as an int
+        """
+        if not 0 <= can_id <= 63:
+            raise ValueError(f"can_id must be between [0..=63] inclusive, instead got {can_id}")
+        return can_id & 0xff
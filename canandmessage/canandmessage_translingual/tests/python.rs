@@ -0,0 +1,65 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn gen_python(spec: &Path) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_canandmessage_translingual"))
+        .args(["--python", spec.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout).unwrap()
+}
+
+fn messages() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../messages")
+}
+
+fn golden() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Compares against `tests/golden/{name}.py`. After an intended change, regenerate it from this
+/// crate's directory with `cargo run -- --python <spec> > tests/golden/{name}.py`.
+fn assert_golden(name: &str, generated: &str) {
+    let expected = std::fs::read_to_string(golden().join(format!("{name}.py"))).unwrap();
+    if generated != expected {
+        let line = generated
+            .lines()
+            .zip(expected.lines())
+            .position(|(g, e)| g != e)
+            .unwrap_or_else(|| generated.lines().count().min(expected.lines().count()));
+        panic!(
+            "generated {name}.py differs from tests/golden/{name}.py at line {}:\n  generated: {:?}\n  golden:    {:?}",
+            line + 1,
+            generated.lines().nth(line),
+            expected.lines().nth(line),
+        );
+    }
+}
+
+#[test]
+fn test_canandmag_module() {
+    assert_golden("canandmag", &gen_python(&messages().join("canandmag.toml")));
+}
+
+#[test]
+fn test_muxed_fd_module() {
+    // muxed, big-endian, array and CAN FD signals, which the shipped specs don't all have
+    let dir = std::env::temp_dir().join(format!("canandmessage-python-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(
+        messages().join("cananddevice.toml"),
+        dir.join("cananddevice.toml"),
+    )
+    .unwrap();
+    std::fs::copy(golden().join("paged.toml"), dir.join("paged.toml")).unwrap();
+    let generated = gen_python(&dir.join("paged.toml"));
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_golden("paged", &generated);
+}
@@ -0,0 +1,389 @@
+//! Capture file readers.
+//!
+//! Supports `candump` text output (both `-l` log files and the default screen format),
+//! classic pcap, and pcapng captures using the SocketCAN link type.
+
+use std::{fmt::Display, io};
+
+/// `LINKTYPE_CAN_SOCKETCAN`
+const LINKTYPE_CAN_SOCKETCAN: u32 = 227;
+
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+const CAN_EFF_MASK: u32 = 0x1fff_ffff;
+
+/// A single frame pulled out of a capture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedFrame {
+    /// Capture timestamp in seconds, if the format records one
+    pub timestamp: Option<f64>,
+    /// Interface name (candump) or interface index (pcapng), if known
+    pub interface: Option<String>,
+    /// Arbitration id without any flag bits
+    pub id: u32,
+    pub extended: bool,
+    pub rtr: bool,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Io(io::Error),
+    /// The capture is not something we know how to read
+    UnknownFormat,
+    /// pcap/pcapng interface that isn't SocketCAN
+    UnsupportedLinkType(u32),
+    /// Malformed line or block
+    Malformed(String),
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::Io(e) => write!(f, "io error: {e}"),
+            CaptureError::UnknownFormat => write!(f, "unrecognized capture format"),
+            CaptureError::UnsupportedLinkType(lt) => {
+                write!(
+                    f,
+                    "unsupported link type {lt} (only SocketCAN captures are supported)"
+                )
+            }
+            CaptureError::Malformed(s) => write!(f, "malformed capture: {s}"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<io::Error> for CaptureError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Reads every frame out of a capture, sniffing the format from its contents.
+pub fn read_capture(buf: &[u8]) -> Result<Vec<CapturedFrame>, CaptureError> {
+    match buf
+        .get(..4)
+        .map(|m| u32::from_le_bytes(m.try_into().unwrap()))
+    {
+        Some(0x0a0d0d0a) => read_pcapng(buf),
+        Some(0xa1b2c3d4 | 0xd4c3b2a1 | 0xa1b23c4d | 0x4d3cb2a1) => read_pcap(buf),
+        _ => {
+            let text = std::str::from_utf8(buf).map_err(|_| CaptureError::UnknownFormat)?;
+            read_candump(text)
+        }
+    }
+}
+
+/// Parses `candump` output.
+///
+/// Accepts both the `-l` log format:
+/// ```text
+/// (1436509052.249713) can0 0E0A1234#0102030405060708
+/// ```
+/// and the default screen format, with or without a `-t` timestamp:
+/// ```text
+///   can0  0E0A1234   [8]  01 02 03 04 05 06 07 08
+/// ```
+pub fn read_candump(text: &str) -> Result<Vec<CapturedFrame>, CaptureError> {
+    let mut frames = Vec::new();
+    for (lineno, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let malformed = || CaptureError::Malformed(format!("line {}: {line}", lineno + 1));
+        let mut tokens = line.split_whitespace().peekable();
+
+        let timestamp = match tokens.peek() {
+            Some(tok) if tok.starts_with('(') => {
+                let tok = tokens.next().ok_or_else(malformed)?;
+                Some(
+                    tok.trim_start_matches('(')
+                        .trim_end_matches(')')
+                        .parse::<f64>()
+                        .map_err(|_| malformed())?,
+                )
+            }
+            _ => None,
+        };
+        let interface = tokens.next().ok_or_else(malformed)?.to_string();
+        let frame = tokens.next().ok_or_else(malformed)?;
+
+        let frame = if let Some((id, data)) = frame.split_once('#') {
+            // log format. FD frames use "##" followed by a flags nibble.
+            let data = match data.strip_prefix('#') {
+                Some(fd) => fd.get(1..).ok_or_else(malformed)?,
+                None => data,
+            };
+            let rtr = data.starts_with('R');
+            let data = if rtr { "" } else { data };
+            // checked up front so the byte-offset slicing below can't split a char
+            if data.len() & 1 != 0 || !data.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(malformed());
+            }
+            let data = (0..data.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&data[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| malformed())?;
+            CapturedFrame {
+                timestamp,
+                interface: Some(interface),
+                id: u32::from_str_radix(id, 16).map_err(|_| malformed())?,
+                extended: id.len() > 3,
+                rtr,
+                data,
+            }
+        } else {
+            // screen format
+            let len = tokens.next().ok_or_else(malformed)?;
+            let rtr = tokens.peek().is_some_and(|t| *t == "remote");
+            let len = len
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<usize>()
+                .map_err(|_| malformed())?;
+            let data = if rtr {
+                Vec::new()
+            } else {
+                tokens
+                    .take(len)
+                    .map(|b| u8::from_str_radix(b, 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map_err(|_| malformed())?
+            };
+            CapturedFrame {
+                timestamp,
+                interface: Some(interface),
+                id: u32::from_str_radix(frame, 16).map_err(|_| malformed())?,
+                extended: frame.len() > 3,
+                rtr,
+                data,
+            }
+        };
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+/// Decodes a `LINKTYPE_CAN_SOCKETCAN` packet body.
+fn socketcan_frame(
+    body: &[u8],
+    timestamp: Option<f64>,
+    interface: Option<String>,
+) -> Result<Option<CapturedFrame>, CaptureError> {
+    let Some(header) = body.get(..8) else {
+        return Err(CaptureError::Malformed(
+            "short SocketCAN packet".to_string(),
+        ));
+    };
+    // the can_id field is always big-endian in captures
+    let can_id = u32::from_be_bytes(header[..4].try_into().unwrap());
+    if can_id & CAN_ERR_FLAG != 0 {
+        return Ok(None);
+    }
+    let len = header[4] as usize;
+    let data = body
+        .get(8..8 + len)
+        .ok_or_else(|| CaptureError::Malformed("truncated SocketCAN payload".to_string()))?;
+    let extended = can_id & CAN_EFF_FLAG != 0;
+    Ok(Some(CapturedFrame {
+        timestamp,
+        interface,
+        id: can_id & CAN_EFF_MASK,
+        extended,
+        rtr: can_id & CAN_RTR_FLAG != 0,
+        data: data.to_vec(),
+    }))
+}
+
+/// Little cursor that reads integers in a capture-defined byte order.
+struct Reader<'a> {
+    buf: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn u16(&self, off: usize) -> Result<u16, CaptureError> {
+        let b: [u8; 2] = self
+            .buf
+            .get(off..off + 2)
+            .ok_or_else(|| CaptureError::Malformed("unexpected end of capture".to_string()))?
+            .try_into()
+            .unwrap();
+        Ok(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32(&self, off: usize) -> Result<u32, CaptureError> {
+        let b: [u8; 4] = self
+            .buf
+            .get(off..off + 4)
+            .ok_or_else(|| CaptureError::Malformed("unexpected end of capture".to_string()))?
+            .try_into()
+            .unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    fn slice(&self, off: usize, len: usize) -> Result<&'a [u8], CaptureError> {
+        self.buf
+            .get(off..off + len)
+            .ok_or_else(|| CaptureError::Malformed("unexpected end of capture".to_string()))
+    }
+}
+
+/// Reads a classic libpcap capture.
+pub fn read_pcap(buf: &[u8]) -> Result<Vec<CapturedFrame>, CaptureError> {
+    let magic = buf
+        .get(..4)
+        .map(|m| u32::from_le_bytes(m.try_into().unwrap()))
+        .ok_or(CaptureError::UnknownFormat)?;
+    let (big_endian, nanos) = match magic {
+        0xa1b2c3d4 => (false, false),
+        0xd4c3b2a1 => (true, false),
+        0xa1b23c4d => (false, true),
+        0x4d3cb2a1 => (true, true),
+        _ => return Err(CaptureError::UnknownFormat),
+    };
+    let rd = Reader { buf, big_endian };
+    let link_type = rd.u32(20)? & 0x0fff_ffff;
+    if link_type != LINKTYPE_CAN_SOCKETCAN {
+        return Err(CaptureError::UnsupportedLinkType(link_type));
+    }
+    let frac_scale = if nanos { 1e-9 } else { 1e-6 };
+
+    let mut frames = Vec::new();
+    let mut off = 24;
+    while off < buf.len() {
+        let ts_sec = rd.u32(off)?;
+        let ts_frac = rd.u32(off + 4)?;
+        let incl_len = rd.u32(off + 8)? as usize;
+        let body = rd.slice(off + 16, incl_len)?;
+        let ts = ts_sec as f64 + ts_frac as f64 * frac_scale;
+        if let Some(frame) = socketcan_frame(body, Some(ts), None)? {
+            frames.push(frame);
+        }
+        off += 16 + incl_len;
+    }
+    Ok(frames)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PcapngInterface {
+    link_type: u32,
+    /// seconds per timestamp tick
+    ts_resolution: f64,
+}
+
+/// Reads a pcapng capture. Non-SocketCAN interfaces in the same file are skipped.
+pub fn read_pcapng(buf: &[u8]) -> Result<Vec<CapturedFrame>, CaptureError> {
+    const SHB: u32 = 0x0a0d0d0a;
+    const IDB: u32 = 0x0000_0001;
+    const SPB: u32 = 0x0000_0003;
+    const EPB: u32 = 0x0000_0006;
+    const OPT_IF_TSRESOL: u16 = 9;
+
+    let mut frames = Vec::new();
+    let mut interfaces: Vec<PcapngInterface> = Vec::new();
+    let mut rd = Reader {
+        buf,
+        big_endian: false,
+    };
+    let mut off = 0usize;
+    while off < buf.len() {
+        let block_type = rd.u32(off)?;
+        if block_type == SHB {
+            // each section header resets the byte order and the interface list
+            rd.big_endian = match rd.slice(off + 8, 4)? {
+                [0x4d, 0x3c, 0x2b, 0x1a] => false,
+                [0x1a, 0x2b, 0x3c, 0x4d] => true,
+                _ => return Err(CaptureError::Malformed("bad byte-order magic".to_string())),
+            };
+            interfaces.clear();
+        }
+        let block_len = rd.u32(off + 4)? as usize;
+        if block_len < 12 {
+            return Err(CaptureError::Malformed(format!("block length {block_len}")));
+        }
+
+        match block_type {
+            IDB => {
+                let link_type = rd.u16(off + 8)? as u32;
+                let mut ts_resolution = 1e-6;
+                // walk the options for if_tsresol
+                let mut opt = off + 16;
+                let opt_end = off + block_len - 4;
+                while opt + 4 <= opt_end {
+                    let code = rd.u16(opt)?;
+                    let len = rd.u16(opt + 2)? as usize;
+                    if code == 0 {
+                        break;
+                    }
+                    if code == OPT_IF_TSRESOL && len >= 1 {
+                        let v = rd.slice(opt + 4, 1)?[0];
+                        ts_resolution = if v & 0x80 != 0 {
+                            2f64.powi(-((v & 0x7f) as i32))
+                        } else {
+                            10f64.powi(-(v as i32))
+                        };
+                    }
+                    opt += 4 + ((len + 3) & !3);
+                }
+                interfaces.push(PcapngInterface {
+                    link_type,
+                    ts_resolution,
+                });
+            }
+            EPB => {
+                let if_id = rd.u32(off + 8)? as usize;
+                let iface = interfaces.get(if_id).copied().ok_or_else(|| {
+                    CaptureError::Malformed(format!("packet on undeclared interface {if_id}"))
+                })?;
+                if iface.link_type == LINKTYPE_CAN_SOCKETCAN {
+                    let ts = ((rd.u32(off + 12)? as u64) << 32) | rd.u32(off + 16)? as u64;
+                    let cap_len = rd.u32(off + 20)? as usize;
+                    let body = rd.slice(off + 28, cap_len)?;
+                    if let Some(frame) = socketcan_frame(
+                        body,
+                        Some(ts as f64 * iface.ts_resolution),
+                        Some(if_id.to_string()),
+                    )? {
+                        frames.push(frame);
+                    }
+                }
+            }
+            SPB => {
+                let iface = interfaces.first().copied().ok_or_else(|| {
+                    CaptureError::Malformed("simple packet without an interface".to_string())
+                })?;
+                if iface.link_type == LINKTYPE_CAN_SOCKETCAN {
+                    let body = rd.slice(off + 12, block_len - 16)?;
+                    if let Some(frame) = socketcan_frame(body, None, Some("0".to_string()))? {
+                        frames.push(frame);
+                    }
+                }
+            }
+            _ => {}
+        }
+        off += block_len;
+    }
+
+    if !interfaces.is_empty()
+        && interfaces
+            .iter()
+            .all(|i| i.link_type != LINKTYPE_CAN_SOCKETCAN)
+    {
+        return Err(CaptureError::UnsupportedLinkType(interfaces[0].link_type));
+    }
+    Ok(frames)
+}
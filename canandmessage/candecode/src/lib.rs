@@ -0,0 +1,454 @@
+//! Decodes CAN captures taken with third-party tools into newline-delimited JSON.
+//!
+//! Messages are decoded straight off the canandmessage TOML specs, so no bus needs to be opened
+//! and nothing needs to be regenerated when a spec changes.
+//!
+//! ```no_run
+//! let mut out = std::io::stdout().lock();
+//! candecode::decode_capture(
+//!     std::path::Path::new("capture.log"),
+//!     std::path::Path::new("messages"),
+//!     false,
+//!     &mut out,
+//! )
+//! .unwrap();
+//! ```
+//!
+//! Numeric signals can also be smoothed, differentiated and tracked over a window as they're
+//! decoded, the same way the middleware's telemetry stream does it; see [`SignalProcessing`].
+
+use std::{collections::HashMap, error::Error, io::Write, path::Path};
+
+use canandmessage_parser::{DType, Device, Message, Signal};
+use rdxsignal::SignalProcessor;
+use serde_json::{json, Map, Value};
+
+pub mod capture;
+
+pub use rdxsignal::SignalConfig;
+
+use capture::CapturedFrame;
+
+/// Redux vendor id as used in the FRC CAN id
+const REDUX_VENDOR_ID: u32 = 0xe;
+/// Device type used by the shared CanandDevice spec.
+const CANANDDEVICE_TYPE: u8 = 31;
+
+/// Set of device specs used to decode frames.
+pub struct Registry {
+    devices: Vec<Device>,
+}
+
+impl Registry {
+    pub fn new(devices: Vec<Device>) -> Self {
+        Self { devices }
+    }
+
+    /// Loads every `.toml` spec in a messages folder.
+    pub fn load_dir(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut devices = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let path_buf = entry?.path();
+            if path_buf.extension().and_then(|e| e.to_str()) != Some("toml") {
+                continue;
+            }
+            let devspec = canandmessage_parser::parse_spec(path_buf.as_path())?;
+            devices.push(devspec.into());
+        }
+        Ok(Self::new(devices))
+    }
+
+    /// Picks the spec for a device type/class, falling back on the shared CanandDevice messages.
+    fn device_for(&self, dev_type: u8, dev_class: u8) -> Option<&Device> {
+        self.devices
+            .iter()
+            .find(|d| d.dev_type == dev_type && d.dev_class == dev_class)
+            .or_else(|| self.devices.iter().find(|d| d.dev_type == dev_type))
+            .or_else(|| {
+                self.devices
+                    .iter()
+                    .find(|d| d.dev_type == CANANDDEVICE_TYPE)
+            })
+    }
+
+    /// Decodes a frame into a JSON object.
+    ///
+    /// Returns [`None`] for frames that aren't Redux frames.
+    pub fn decode(&self, frame: &CapturedFrame) -> Option<Value> {
+        if !frame.extended || (frame.id >> 16) & 0xff != REDUX_VENDOR_ID {
+            return None;
+        }
+        let dev_type = ((frame.id >> 24) & 0x1f) as u8;
+        let dev_class = ((frame.id >> 11) & 0x1f) as u8;
+        let msg_id = ((frame.id >> 6) & 0x1f) as u8;
+        let dev_id = (frame.id & 0x3f) as u8;
+
+        let mut out = Map::new();
+        if let Some(ts) = frame.timestamp {
+            out.insert("timestamp".to_string(), json!(ts));
+        }
+        if let Some(iface) = &frame.interface {
+            out.insert("interface".to_string(), json!(iface));
+        }
+        out.insert("id".to_string(), json!(format!("0x{:08x}", frame.id)));
+        out.insert("dev_type".to_string(), json!(dev_type));
+        out.insert("dev_id".to_string(), json!(dev_id));
+
+        let device = self.device_for(dev_type, dev_class);
+        let message = device.and_then(|dev| dev.messages.iter().find(|(_, msg)| msg.id == msg_id));
+        if let Some(dev) = device {
+            out.insert("device".to_string(), json!(dev.name));
+        }
+
+        match message {
+            Some((name, msg)) if !frame.rtr => {
+                out.insert("message".to_string(), json!(name));
+                out.insert("signals".to_string(), decode_message(msg, &frame.data));
+            }
+            _ => {
+                out.insert("api_index".to_string(), json!(msg_id));
+            }
+        }
+        out.insert("data".to_string(), json!(hex_str(&frame.data)));
+        Some(Value::Object(out))
+    }
+}
+
+/// Signals run through a [`SignalProcessor`] as frames are decoded.
+///
+/// Signals are named `MESSAGE.signal`, as in the spec, e.g. `VELOCITY_OUTPUT.velocity`. Each device
+/// sending one gets its own processor. Decoded frames carrying a chosen signal get a `processed`
+/// object with what was computed from it, keyed by signal name. Frames without a timestamp are
+/// left alone, as there's nothing to filter against.
+#[derive(Debug, Clone, Default)]
+pub struct SignalProcessing {
+    config: SignalConfig,
+    /// Chosen signals, as (message, signal)
+    signals: Vec<(String, String)>,
+    /// Processors by device type, device id, message and signal
+    processors: HashMap<(u64, u64, String, String), SignalProcessor>,
+}
+
+impl SignalProcessing {
+    /// Processes each of `signals` with `config`. Returns the first name that isn't
+    /// `MESSAGE.signal` as the error.
+    pub fn new<S: AsRef<str>>(
+        signals: impl IntoIterator<Item = S>,
+        config: SignalConfig,
+    ) -> Result<Self, String> {
+        let signals = signals
+            .into_iter()
+            .map(|name| {
+                let name = name.as_ref();
+                match name.split_once('.') {
+                    Some((msg, sig)) if !msg.is_empty() && !sig.is_empty() => {
+                        Ok((msg.to_string(), sig.to_string()))
+                    }
+                    _ => Err(name.to_string()),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            config,
+            signals,
+            processors: HashMap::new(),
+        })
+    }
+
+    /// Adds the `processed` object to a frame decoded by [`Registry::decode`], if it carries any
+    /// of the chosen signals.
+    pub fn apply(&mut self, decoded: &mut Value) {
+        let Some(frame) = decoded.as_object_mut() else {
+            return;
+        };
+        let (Some(t), Some(dev_type), Some(dev_id), Some(message)) = (
+            frame.get("timestamp").and_then(Value::as_f64),
+            frame.get("dev_type").and_then(Value::as_u64),
+            frame.get("dev_id").and_then(Value::as_u64),
+            frame.get("message").and_then(Value::as_str),
+        ) else {
+            return;
+        };
+        let mut processed = Map::new();
+        for (msg, sig) in self.signals.iter().filter(|(msg, _)| msg == message) {
+            let Some(value) = frame
+                .get("signals")
+                .and_then(|signals| signals.get(sig))
+                .and_then(Value::as_f64)
+            else {
+                continue;
+            };
+            let sample = self
+                .processors
+                .entry((dev_type, dev_id, msg.clone(), sig.clone()))
+                .or_insert_with(|| SignalProcessor::new(self.config))
+                .push(t, value);
+            if let Ok(sample) = serde_json::to_value(sample) {
+                processed.insert(sig.clone(), sample);
+            }
+        }
+        if !processed.is_empty() {
+            frame.insert("processed".to_string(), Value::Object(processed));
+        }
+    }
+}
+
+/// Labels a frame from another vendor with what its FRC CAN id says about it.
+///
+/// Returns [`None`] for standard (11-bit) frames, which don't follow the FRC CAN id layout.
+pub fn label_foreign(frame: &CapturedFrame) -> Option<Value> {
+    if !frame.extended {
+        return None;
+    }
+    let label = frc_can_id::known::label(frame.id);
+
+    let mut out = Map::new();
+    if let Some(ts) = frame.timestamp {
+        out.insert("timestamp".to_string(), json!(ts));
+    }
+    if let Some(iface) = &frame.interface {
+        out.insert("interface".to_string(), json!(iface));
+    }
+    out.insert("id".to_string(), json!(format!("0x{:08x}", frame.id)));
+    out.insert("vendor".to_string(), json!(label.vendor.to_string()));
+    out.insert("dev_type".to_string(), json!(label.device_type.as_u8()));
+    out.insert("dev_id".to_string(), json!(label.device_number));
+    out.insert("api_class".to_string(), json!(label.api_class));
+    out.insert("api_index".to_string(), json!(label.api_index));
+    if let Some(product) = label.product {
+        out.insert("device".to_string(), json!(product));
+    }
+    if let Some(frame) = label.frame {
+        out.insert("frame".to_string(), json!(frame));
+    }
+    out.insert("label".to_string(), json!(label.to_string()));
+    out.insert("data".to_string(), json!(hex_str(&frame.data)));
+    Some(Value::Object(out))
+}
+
+/// Decodes a capture file into newline-delimited JSON, one line per Redux frame.
+/// With `foreign`, frames from other vendors get a line too, labeled by [`label_foreign`].
+///
+/// Returns the number of lines written.
+pub fn decode_capture<W: Write>(
+    capture_path: &Path,
+    messages_dir: &Path,
+    foreign: bool,
+    out: &mut W,
+) -> Result<usize, Box<dyn Error>> {
+    decode_capture_processed(
+        capture_path,
+        messages_dir,
+        foreign,
+        &mut SignalProcessing::default(),
+        out,
+    )
+}
+
+/// Like [`decode_capture`], also running the signals `processing` chose through it.
+pub fn decode_capture_processed<W: Write>(
+    capture_path: &Path,
+    messages_dir: &Path,
+    foreign: bool,
+    processing: &mut SignalProcessing,
+    out: &mut W,
+) -> Result<usize, Box<dyn Error>> {
+    let registry = Registry::load_dir(messages_dir)?;
+    let frames = capture::read_capture(&std::fs::read(capture_path)?)?;
+    let mut written = 0usize;
+    for frame in frames.iter() {
+        let value = match registry.decode(frame) {
+            Some(mut value) => {
+                processing.apply(&mut value);
+                value
+            }
+            None if foreign => match label_foreign(frame) {
+                Some(value) => value,
+                None => continue,
+            },
+            None => continue,
+        };
+        serde_json::to_writer(&mut *out, &value)?;
+        out.write_all(b"\n")?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Writes each Redux frame of a capture into `corpus_dir` as a seed for the `decode` fuzz target,
+/// laid out as `[id: u32 LE][dlc: u8][data]`. Seeds are named after their contents, so repeated
+/// frames and reruns over the same capture don't pile up duplicates.
+///
+/// Returns the number of distinct seeds in the capture.
+pub fn write_fuzz_corpus(capture_path: &Path, corpus_dir: &Path) -> Result<usize, Box<dyn Error>> {
+    let frames = capture::read_capture(&std::fs::read(capture_path)?)?;
+    std::fs::create_dir_all(corpus_dir)?;
+    let mut seeds = std::collections::BTreeSet::new();
+    for frame in frames.iter() {
+        if !frame.extended || (frame.id >> 16) & 0xff != REDUX_VENDOR_ID {
+            continue;
+        }
+        let mut seed = frame.id.to_le_bytes().to_vec();
+        seed.push(frame.data.len() as u8);
+        seed.extend_from_slice(&frame.data);
+        seeds.insert(seed);
+    }
+    for seed in seeds.iter() {
+        std::fs::write(corpus_dir.join(hex_str(seed)), seed)?;
+    }
+    Ok(seeds.len())
+}
+
+fn hex_str(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Reads `width` bits starting at bit `pos`, least significant bit first.
+fn read_bits(data: &[u8], pos: usize, width: usize) -> Option<u64> {
+    if width > 64 || pos + width > data.len() << 3 {
+        return None;
+    }
+    let mut v = 0u64;
+    for i in 0..width {
+        let bit = pos + i;
+        v |= (((data[bit >> 3] >> (bit & 7)) & 1) as u64) << i;
+    }
+    Some(v)
+}
+
+fn sign_extend(v: u64, width: usize) -> i64 {
+    if width == 0 || width >= 64 {
+        return v as i64;
+    }
+    let shift = 64 - width;
+    ((v << shift) as i64) >> shift
+}
+
+fn scaled(raw: f64, factor_num: i64, factor_den: i64, offset: f64) -> Value {
+    if factor_num == factor_den {
+        json!(raw + offset)
+    } else {
+        json!(raw * (factor_num as f64) / (factor_den as f64) + offset)
+    }
+}
+
+fn decode_message(msg: &Message, data: &[u8]) -> Value {
+    let mut pos = 0usize;
+    let mut signals = Map::new();
+    for sig in msg.plain_signals() {
+        decode_signal(sig, data, &mut pos, &mut signals);
+    }
+    // of the muxed signals, only the ones the multiplexor's value selects are in the frame
+    let selected = msg.layout().into_iter().find_map(|(start, sig)| {
+        sig.mux
+            .then(|| read_bits(data, start, sig.dtype.bit_length()))
+            .flatten()
+    });
+    if let Some(group) = msg
+        .mux_groups()
+        .into_iter()
+        .find(|group| Some(group.value) == selected)
+    {
+        for sig in group.signals {
+            decode_signal(sig, data, &mut pos, &mut signals);
+        }
+    }
+    Value::Object(signals)
+}
+
+/// Decodes one signal into `out`, advancing `pos`. Signals that don't fit in the frame are skipped.
+fn decode_signal(sig: &Signal, data: &[u8], pos: &mut usize, out: &mut Map<String, Value>) {
+    let width = sig.dtype.bit_length();
+    let start = *pos;
+    *pos += width;
+
+    let value = match &sig.dtype {
+        DType::None | DType::Pad { .. } | DType::Reserved { .. } => return,
+        DType::Struct { meta } => {
+            let mut inner = Map::new();
+            let mut inner_pos = start;
+            for inner_sig in meta.signals.iter() {
+                decode_signal(inner_sig, data, &mut inner_pos, &mut inner);
+            }
+            if inner.is_empty() {
+                return;
+            }
+            Value::Object(inner)
+        }
+        DType::Array { meta } => {
+            let mut elem_pos = start;
+            // elements that run off the end of the frame are left out
+            let elems: Vec<Value> = (0..meta.len)
+                .filter_map(|i| {
+                    let elem = sig.array_element(i);
+                    let mut slot = Map::new();
+                    decode_signal(&elem, data, &mut elem_pos, &mut slot);
+                    slot.remove(&elem.name)
+                })
+                .collect();
+            if elems.is_empty() {
+                return;
+            }
+            Value::Array(elems)
+        }
+        DType::Buf { meta } => {
+            if start + meta.width > data.len() << 3 {
+                return;
+            }
+            let bytes: Vec<u8> = (0..meta.width)
+                .step_by(8)
+                .filter_map(|i| read_bits(data, start + i, (meta.width - i).min(8)))
+                .map(|b| b as u8)
+                .collect();
+            json!(hex_str(&bytes))
+        }
+        dtype => {
+            let Some(raw) = read_bits(data, start, width) else {
+                return;
+            };
+            // big-endian signals are whole bytes, at least 8 bits
+            let raw = match sig.big_endian {
+                true => raw.swap_bytes() >> (64 - width),
+                false => raw,
+            };
+            match dtype {
+                DType::UInt { meta } => {
+                    if meta.factor_num == meta.factor_den && meta.offset == 0.0 {
+                        json!(raw)
+                    } else {
+                        scaled(raw as f64, meta.factor_num, meta.factor_den, meta.offset)
+                    }
+                }
+                DType::SInt { meta } => {
+                    let raw = sign_extend(raw, meta.width);
+                    if meta.factor_num == meta.factor_den && meta.offset == 0.0 {
+                        json!(raw)
+                    } else {
+                        scaled(raw as f64, meta.factor_num, meta.factor_den, meta.offset)
+                    }
+                }
+                DType::Float { meta } => {
+                    let v = match meta.width {
+                        32 => f32::from_bits(raw as u32) as f64,
+                        64 => f64::from_bits(raw),
+                        _ => return,
+                    };
+                    scaled(v, meta.factor_num, meta.factor_den, meta.offset)
+                }
+                DType::Bool { .. } => json!(raw != 0),
+                DType::Enum { meta } => match meta.values.get(&raw) {
+                    Some(ent) => json!(ent.name),
+                    None => json!(raw),
+                },
+                DType::Bitset { meta } => Value::Object(
+                    meta.flags
+                        .iter()
+                        .map(|f| (f.name.clone(), json!((raw >> f.bit_idx) & 1 != 0)))
+                        .collect(),
+                ),
+                _ => return,
+            }
+        }
+    };
+    out.insert(sig.name.clone(), value);
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use clap::{arg, ArgAction, Command};
+
+fn main() {
+    let m = Command::new("candecode")
+        .version("0.1.0")
+        .about("decodes candump/pcap captures into newline-delimited Redux JSON")
+        .arg(arg!(--"messages" <DIR> "messages folder, defaults to ./messages"))
+        .arg(arg!(--"all" "also print frames from other vendors, labeled by vendor/device/frame"))
+        .arg(arg!(--"fuzz-corpus" <DIR> "write fuzz seeds into DIR instead"))
+        .arg(
+            arg!(--"signal" <SIGNAL> "process MESSAGE.signal, e.g. VELOCITY_OUTPUT.velocity")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--"lowpass-hz" <HZ> "low-pass filter processed signals at HZ")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(arg!(--"derivative" "also give processed signals' rate of change per second"))
+        .arg(
+            arg!(--"window-ms" <MS> "track processed signals' min and max over the last MS")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(arg!(<capture> "candump log, pcap, or pcapng file"))
+        .get_matches();
+
+    let messages = m
+        .get_one::<String>("messages")
+        .map_or("messages", |s| s.as_str());
+    // clap enforces that the positional is present
+    let capture = m.get_one::<String>("capture").unwrap();
+
+    if let Some(corpus) = m.get_one::<String>("fuzz-corpus") {
+        match candecode::write_fuzz_corpus(Path::new(capture), Path::new(corpus)) {
+            Ok(n) => eprintln!("candecode: wrote {n} seeds to {corpus}"),
+            Err(e) => {
+                eprintln!("candecode: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let foreign = m.get_flag("all");
+    let config = candecode::SignalConfig {
+        lowpass_hz: m.get_one::<f64>("lowpass-hz").copied(),
+        derivative: m.get_flag("derivative"),
+        window_secs: m.get_one::<f64>("window-ms").map(|ms| ms / 1000.0),
+    };
+    let signals = m.get_many::<String>("signal").into_iter().flatten();
+    let mut processing = match candecode::SignalProcessing::new(signals, config) {
+        Ok(processing) => processing,
+        Err(name) => {
+            eprintln!("candecode: signals are named MESSAGE.signal, not {name}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut out = std::io::stdout().lock();
+    if let Err(e) = candecode::decode_capture_processed(
+        Path::new(capture),
+        Path::new(messages),
+        foreign,
+        &mut processing,
+        &mut out,
+    ) {
+        eprintln!("candecode: {e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,245 @@
+use std::path::Path;
+
+use candecode::{capture, Registry};
+
+#[test]
+fn test_candump_formats() {
+    let frames = capture::read_candump(
+        "(1700000000.000100) can0 070E07C3#00100000C1FF\n  can0  123   [2]  01 02\n",
+    )
+    .unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].timestamp, Some(1700000000.0001));
+    assert_eq!(frames[0].id, 0x070e07c3);
+    assert!(frames[0].extended);
+    assert_eq!(frames[0].data, [0x00, 0x10, 0x00, 0x00, 0xc1, 0xff]);
+    assert_eq!(frames[1].id, 0x123);
+    assert!(!frames[1].extended);
+    assert_eq!(frames[1].data, [0x01, 0x02]);
+}
+
+#[test]
+fn test_candump_malformed_data() {
+    for line in ["can0 123#01é2", "can0 123#0G", "can0 123#012"] {
+        assert!(matches!(
+            capture::read_candump(line),
+            Err(capture::CaptureError::Malformed(_))
+        ));
+    }
+}
+
+#[test]
+fn test_decode_canandmag_position() {
+    let registry =
+        Registry::load_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../messages")).unwrap();
+    let frames = capture::read_candump("can0 070E07C3#00100000C1FF").unwrap();
+    let value = registry.decode(&frames[0]).unwrap();
+    assert_eq!(value["device"], "Canandmag");
+    assert_eq!(value["message"], "POSITION_OUTPUT");
+    assert_eq!(value["dev_id"], 3);
+    assert_eq!(value["signals"]["relative_position"], 0.25);
+
+    let frames = capture::read_candump("can0 123#0102").unwrap();
+    assert!(registry.decode(&frames[0]).is_none());
+}
+
+#[test]
+fn test_decode_canandcolor_distance() {
+    let registry =
+        Registry::load_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../messages")).unwrap();
+    let frames = capture::read_candump("can0 060E07C2#FFFF").unwrap();
+    let value = registry.decode(&frames[0]).unwrap();
+    assert_eq!(value["device"], "Canandcolor");
+    assert_eq!(value["message"], "DISTANCE_OUTPUT");
+    assert_eq!(value["signals"]["distance"], 1.0);
+}
+
+#[test]
+fn test_label_foreign_frames() {
+    let frames = capture::read_candump("can0 02051841#0102
+can0 123#0102").unwrap();
+    let value = candecode::label_foreign(&frames[0]).unwrap();
+    assert_eq!(value["vendor"], "REV");
+    assert_eq!(value["device"], "SPARK");
+    assert_eq!(value["dev_id"], 1);
+    assert_eq!(value["label"], "REV SPARK #1 Periodic status 1");
+    assert!(candecode::label_foreign(&frames[1]).is_none());
+}
+
+#[test]
+fn test_signal_processing() {
+    let registry =
+        Registry::load_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../messages")).unwrap();
+    let frames = capture::read_candump(
+        "(1.00) can0 070E07C3#00100000C1FF\n(1.50) can0 070E07C3#00200000C1FF\n",
+    )
+    .unwrap();
+    let mut processing = candecode::SignalProcessing::new(
+        ["POSITION_OUTPUT.relative_position"],
+        candecode::SignalConfig {
+            derivative: true,
+            window_secs: Some(1.0),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mut values: Vec<_> = frames
+        .iter()
+        .map(|frame| registry.decode(frame).unwrap())
+        .collect();
+    values.iter_mut().for_each(|value| processing.apply(value));
+    let processed = &values[1]["processed"]["relative_position"];
+    assert_eq!(processed["value"], 0.5);
+    // a quarter rotation in half a second
+    assert_eq!(processed["derivative"], 0.5);
+    assert_eq!(processed["min"], 0.25);
+    assert_eq!(processed["max"], 0.5);
+    assert!(candecode::SignalProcessing::new(["velocity"], Default::default()).is_err());
+}
+
+/// A registry of the CanandDevice spec and `spec`, loaded from a temp dir named `name`.
+fn registry_with(name: &str, spec: &str) -> Registry {
+    let dir = std::env::temp_dir().join(format!("candecode-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let messages = Path::new(env!("CARGO_MANIFEST_DIR")).join("../messages");
+    std::fs::copy(
+        messages.join("cananddevice.toml"),
+        dir.join("cananddevice.toml"),
+    )
+    .unwrap();
+    std::fs::write(dir.join(format!("{name}.toml")), spec).unwrap();
+    let registry = Registry::load_dir(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    registry
+}
+
+#[test]
+fn test_decode_muxed_message() {
+    let registry = registry_with(
+        "paged",
+        r#"name = "Paged"
+base = ["CanandDevice"]
+arch = "esp32c3"
+dev_type = 9
+dev_class = 0
+
+[vendordep]
+java_package = "com.reduxrobotics.misc.paged"
+cpp_namespace = "redux::misc::paged"
+
+[msg.PAGED_OUTPUT]
+id = 20
+min_length = 1
+max_length = 4
+source = "device"
+comment = "Paged output"
+signals = [
+    { name = "page", dtype = "enum:PAGE", mux = true, comment = "Which page follows" },
+    { name = "speed", dtype = "sint:16", muxed_by = "page", muxed_match = ["SPEED"], comment = "Speed" },
+    { name = "lo", dtype = "uint:12", muxed_by = "page", muxed_match = ["LIMITS"], comment = "Low limit" },
+    { name = "hi", dtype = "uint:12", muxed_by = "page", muxed_match = ["LIMITS"], comment = "High limit" },
+]
+
+[enums.PAGE]
+btype = "uint"
+bits = 8
+default_value = "SPEED"
+comment = "Pages"
+[enums.PAGE.values]
+SPEED = { id = 0, comment = "Speed page" }
+LIMITS = { id = 1, comment = "Limits page" }
+IDLE = { id = 2, comment = "Nothing follows" }
+"#,
+    );
+
+    let frames =
+        capture::read_candump("can0 090E0503#0123C1AB\ncan0 090E0503#00FEFF\ncan0 090E0503#02")
+            .unwrap();
+    let limits = registry.decode(&frames[0]).unwrap();
+    assert_eq!(limits["message"], "PAGED_OUTPUT");
+    assert_eq!(
+        limits["signals"],
+        serde_json::json!({"page": "LIMITS", "lo": 0x123, "hi": 0xabc})
+    );
+    let speed = registry.decode(&frames[1]).unwrap();
+    assert_eq!(
+        speed["signals"],
+        serde_json::json!({"page": "SPEED", "speed": -2})
+    );
+    let idle = registry.decode(&frames[2]).unwrap();
+    assert_eq!(idle["signals"], serde_json::json!({"page": "IDLE"}));
+}
+
+#[test]
+fn test_decode_offset_signal() {
+    let registry = registry_with(
+        "thermo",
+        r#"name = "Thermo"
+base = ["CanandDevice"]
+arch = "esp32c3"
+dev_type = 9
+dev_class = 0
+
+[vendordep]
+java_package = "com.reduxrobotics.misc.thermo"
+cpp_namespace = "redux::misc::thermo"
+
+[msg.TEMPERATURE_OUTPUT]
+id = 20
+length = 1
+source = "device"
+comment = "Temperature"
+signals = [
+    { name = "temperature", dtype = "half_degrees", comment = "Temperature" },
+]
+
+[types.half_degrees]
+comment = "Half degrees Celsius from -40"
+btype = "uint"
+bits = 8
+factor = [1, 2]
+offset = -40
+"#,
+    );
+    let frames = capture::read_candump("can0 090E0503#64").unwrap();
+    let value = registry.decode(&frames[0]).unwrap();
+    assert_eq!(value["signals"]["temperature"], 10.0);
+}
+
+#[test]
+fn test_decode_fd_big_endian_message() {
+    let registry = registry_with(
+        "wide",
+        r#"name = "Wide"
+base = ["CanandDevice"]
+arch = "esp32c3"
+dev_type = 9
+dev_class = 0
+
+[vendordep]
+java_package = "com.reduxrobotics.misc.wide"
+cpp_namespace = "redux::misc::wide"
+
+[msg.WIDE_OUTPUT]
+id = 20
+length = 16
+source = "device"
+vendordep = false
+is_public = false
+comment = "Wide output"
+signals = [
+    { name = "count", dtype = "uint:32", byte_order = "big", comment = "Count" },
+    { name = "flags", dtype = "uint:4", comment = "Flags" },
+    { name = "pad", dtype = "pad:4", comment = "" },
+    { name = "temp", dtype = "sint:16", byte_order = "big", comment = "Temperature" },
+    { name = "energy", dtype = "float:64", byte_order = "big", comment = "Energy" },
+]
+"#,
+    );
+    let frames = capture::read_candump("can0 090E0503##10102030405FFFE3FF800000000000000").unwrap();
+    let value = registry.decode(&frames[0]).unwrap();
+    assert_eq!(value["signals"]["count"], 0x01020304);
+    assert_eq!(value["signals"]["flags"], 5);
+    assert_eq!(value["signals"]["temp"], -2);
+    assert_eq!(value["signals"]["energy"], 1.5);
+}
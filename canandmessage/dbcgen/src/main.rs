@@ -1,6 +1,6 @@
-use std::{fmt::Display, path::Path};
+use std::{collections::BTreeMap, fmt::Display, path::Path};
 
-use canandmessage_parser::{utils, DType, Device, Message, Signal, Source};
+use canandmessage_parser::{utils, DType, Device, EnumMeta, Message, Signal, Source};
 use clap::{arg, Command};
 extern crate canandmessage_parser;
 
@@ -40,10 +40,30 @@ NS_ :
 BS_: 
 BU_:";
 
+/// Vector's frame formats, which DBC tools tell CAN FD messages apart by
+static FRAME_FORMAT_DEF: &str = "BA_DEF_ BO_ \"VFrameFormat\" ENUM \"StandardCAN\",\"ExtendedCAN\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"reserved\",\"StandardCAN_FD\",\"ExtendedCAN_FD\";
+BA_DEF_DEF_ \"VFrameFormat\" \"ExtendedCAN\";
+";
+/// `VFrameFormat` of our messages, which all have extended ids, when they're longer than 8 bytes
+const EXTENDED_CAN_FD: u32 = 15;
+
 pub struct DBCBuilder {
     pub dbc: Vec<String>,
     pub dbc_comments: Vec<String>,
     pub float_signals: Vec<String>,
+    pub mux_values: Vec<String>,
+    /// `VAL_` entries naming the values of enum signals
+    pub value_descriptions: Vec<String>,
+    /// `VAL_TABLE_` definitions of the enums the signals use, by enum name
+    pub value_tables: BTreeMap<String, String>,
+    /// Multiplexer indicator (` M`, ` m0`) for the signals being rendered, if any
+    pub mux_indicator: String,
+    /// Multiplexer and value ranges the signals being rendered are present for, if muxed
+    pub mux_ranges: Option<String>,
+    /// The signal being rendered is big-endian
+    pub big_endian: bool,
+    /// `BA_` entries marking messages longer than 8 bytes as CAN FD
+    pub fd_frames: Vec<String>,
     pub reserved_cnt: u32,
     pub is_public: bool,
 }
@@ -94,6 +114,13 @@ impl DBCBuilder {
             dbc: vec![TEMPLATE.to_string()],
             dbc_comments: Vec::new(),
             float_signals: Vec::new(),
+            mux_values: Vec::new(),
+            value_descriptions: Vec::new(),
+            value_tables: BTreeMap::new(),
+            mux_indicator: String::new(),
+            mux_ranges: None,
+            big_endian: false,
+            fd_frames: Vec::new(),
             reserved_cnt: 0,
             is_public,
         }
@@ -103,6 +130,33 @@ impl DBCBuilder {
             .push(format!("SIG_VALTYPE_ {full_id} {name} : 1;\n"))
     }
 
+    /// Names the values of enum signal `name`, and defines its enum's value table if it's the first
+    /// signal to use it.
+    pub fn add_enum_sig(&mut self, full_id: u32, name: &String, meta: &EnumMeta) {
+        let values = value_names(meta);
+        self.value_descriptions
+            .push(format!("VAL_ {full_id} {name}{values};\n"));
+        self.value_tables
+            .entry(meta.name.clone())
+            .or_insert_with(|| format!("VAL_TABLE_ {}{values};\n", meta.name));
+    }
+
+    /// Sets the multiplexing of the signals rendered next from how `sig` is muxed.
+    pub fn set_mux(&mut self, sig: &Signal) {
+        (self.mux_indicator, self.mux_ranges) = match &sig.muxed_by {
+            _ if sig.mux => (" M".to_string(), None),
+            Some(muxed_by) => (
+                format!(" m{}", muxed_by.values[0]),
+                Some(format!(
+                    "{} {}",
+                    muxed_by.selector,
+                    value_ranges(&muxed_by.values)
+                )),
+            ),
+            None => (String::new(), None),
+        };
+    }
+
     pub fn render_sg(
         &mut self,
         pos: &mut u32,
@@ -120,9 +174,20 @@ impl DBCBuilder {
         let sgn = if signed { "-" } else { "+" };
         let scale = _scale.unwrap_or(1.0);
         let offset = _offset.unwrap_or(0.0);
+        let mux = &self.mux_indicator;
+        // big-endian (Motorola) signals start at their most significant bit, which for the whole
+        // bytes they take is the top bit of their first byte
+        let (start, order) = match self.big_endian {
+            true => (*pos + 7, 0),
+            false => (*pos, 1),
+        };
         self.dbc.push(format!(
-            " SG_ {name} : {pos}|{width}@1{sgn} ({scale},{offset}) [{min}|{max}] \"\" {dest}\n"
+            " SG_ {name}{mux} : {start}|{width}@{order}{sgn} ({scale},{offset}) [{min}|{max}] \"\" {dest}\n"
         ));
+        if let Some(ranges) = &self.mux_ranges {
+            self.mux_values
+                .push(format!("SG_MUL_VAL_ {full_id} {name} {ranges};\n"));
+        }
 
         let comment = comment.replace("\n", " ");
         self.dbc_comments
@@ -144,6 +209,7 @@ impl DBCBuilder {
             sig_prefix.as_ref().unwrap_or(&"".to_string()),
             sig.name
         );
+        self.big_endian = sig.big_endian;
         match &sig.dtype {
             DType::None => {
                 return;
@@ -154,7 +220,7 @@ impl DBCBuilder {
                 meta.width,
                 false,
                 Some((meta.factor_num as f64) / (meta.factor_den as f64)),
-                None,
+                Some(meta.offset),
                 meta.min.unwrap_or(0).into(),
                 meta.max
                     .unwrap_or(utils::default_uint_max(meta.width))
@@ -169,7 +235,7 @@ impl DBCBuilder {
                 meta.width,
                 true,
                 Some((meta.factor_num as f64) / (meta.factor_den as f64)),
-                None,
+                Some(meta.offset),
                 meta.min
                     .unwrap_or(utils::default_sint_min(meta.width))
                     .into(),
@@ -201,7 +267,7 @@ impl DBCBuilder {
                     meta.width,
                     false,
                     Some((meta.factor_num as f64) / (meta.factor_den as f64)),
-                    None,
+                    Some(meta.offset),
                     0.0.into(),
                     0.0.into(),
                     &dest,
@@ -250,7 +316,7 @@ impl DBCBuilder {
                     );
                 }
             }
-            DType::Pad { width } => self.render_sg(
+            DType::Pad { width } | DType::Reserved { width } => self.render_sg(
                 pos,
                 &name,
                 *width,
@@ -278,19 +344,22 @@ impl DBCBuilder {
                     &sig.comment,
                 );
             }
-            DType::Enum { meta } => self.render_sg(
-                pos,
-                &name,
-                meta.width,
-                false,
-                None,
-                None,
-                0.0.into(),
-                utils::default_uint_max(meta.width).into(),
-                &dest,
-                full_id,
-                &sig.comment,
-            ),
+            DType::Enum { meta } => {
+                self.add_enum_sig(full_id, &name, meta);
+                self.render_sg(
+                    pos,
+                    &name,
+                    meta.width,
+                    false,
+                    None,
+                    None,
+                    0.0.into(),
+                    utils::default_uint_max(meta.width).into(),
+                    &dest,
+                    full_id,
+                    &sig.comment,
+                );
+            }
             DType::Struct { meta } => {
                 let prefix = match &sig_prefix {
                     Some(p) => format!("{}{}_", p.clone(), meta.name),
@@ -301,6 +370,25 @@ impl DBCBuilder {
                     self.render_signal(pos, dev, sig, Some(prefix.clone()), dest, full_id)
                 });
             }
+            DType::Array { meta } => {
+                // DBC has no arrays, so each element becomes its own signal: `name_0`, `name_1`, ...
+                for i in 0..meta.len {
+                    let elem = sig.array_element(i);
+                    match &elem.dtype {
+                        DType::Struct { meta } => {
+                            let prefix = format!(
+                                "{}{}_",
+                                sig_prefix.as_ref().unwrap_or(&"".to_string()),
+                                elem.name
+                            );
+                            meta.signals.iter().for_each(|sig| {
+                                self.render_signal(pos, dev, sig, Some(prefix.clone()), dest, full_id)
+                            });
+                        }
+                        _ => self.render_signal(pos, dev, &elem, sig_prefix.clone(), dest, full_id),
+                    }
+                }
+            }
         };
     }
 
@@ -322,6 +410,11 @@ impl DBCBuilder {
             "\nBO_ {full_id} {name}: {length} {msg_source}\n",
             name = msg_name.to_lowercase()
         ));
+        if length > 8 {
+            self.fd_frames.push(format!(
+                "BA_ \"VFrameFormat\" BO_ {full_id} {EXTENDED_CAN_FD};\n"
+            ));
+        }
 
         let comment = msg.comment.replace("\n", " ");
 
@@ -330,14 +423,20 @@ impl DBCBuilder {
             name = msg_name.to_lowercase(),
             comment = comment
         ));
-        let mut pos = 0u32;
-        msg.signals.iter().for_each(|sig| {
+        // muxed signals for different multiplexor values overlap, so each starts where it's laid out
+        msg.layout().into_iter().for_each(|(start, sig)| {
+            let mut pos = start as u32;
+            self.set_mux(sig);
             self.render_signal(&mut pos, dev, sig, None, &msg_dest, full_id);
         });
+        self.mux_indicator.clear();
+        self.mux_ranges = None;
     }
 
     pub fn render_device(&mut self, dev: &Device, dev_id: u8) {
         self.dbc.push(format!(" {}\n", dev.name.to_lowercase()));
+        // value tables go between the nodes and the messages, but aren't known until the messages are
+        let tables_at = self.dbc.len();
         //dev.messages.iter().for_each(|(msg_name, msg)| {
         //    self.render_message(dev_id, dev, msg, msg_name)
         //});
@@ -350,13 +449,48 @@ impl DBCBuilder {
             self.render_message(dev_id, dev, msg, msg_name);
         });
 
+        if !self.value_tables.is_empty() {
+            let tables = self.value_tables.values().cloned().collect::<String>();
+            self.dbc.insert(tables_at, format!("\n{tables}"));
+        }
+
         self.dbc.push("\n".to_string());
+        self.dbc.push(self.value_descriptions.join(""));
         self.dbc.push(self.float_signals.join(""));
+        self.dbc.push(self.mux_values.join(""));
         self.dbc.push("\n".to_string());
         self.dbc.push(self.dbc_comments.join(""));
+        if !self.fd_frames.is_empty() {
+            self.dbc
+                .push(format!("\n\n{FRAME_FORMAT_DEF}{}", self.fd_frames.join("")));
+        }
     }
 }
 
+/// Formats multiplexor values as the `a-b, c-d` ranges SG_MUL_VAL_ takes.
+fn value_ranges(values: &[u64]) -> String {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for &value in values {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == value => *end = value,
+            _ => ranges.push((value, value)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| format!("{start}-{end}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats the values of an enum as the ` 0 "NAME" 1 "NAME"` list VAL_ and VAL_TABLE_ take.
+fn value_names(meta: &EnumMeta) -> String {
+    meta.values
+        .iter()
+        .map(|(value, ent)| format!(" {value} \"{}\"", ent.name.replace('"', "'")))
+        .collect()
+}
+
 impl ToString for DBCBuilder {
     fn to_string(&self) -> String {
         self.dbc.join("")
@@ -0,0 +1,137 @@
+//! Feeds arbitrary frames through every generated message decoder and the generic decoders.
+//!
+//! Input is `[id: u32 LE][dlc: u8][data]`, the same layout `candecode --fuzz-corpus` writes seeds
+//! in. Data past `dlc` is dropped and short data is zero-padded, so `dlc` always matches the data
+//! the decoders see. Frames are handed over in an exactly sized heap buffer so that the sanitizer
+//! catches any `get_unchecked` read past the end of the frame.
+#![no_main]
+
+use canandmessage::{
+    generic::{Enumerate, ReportSetting, SetSetting, SettingCommand, WrapperSerializable},
+    traits::{CanandDevice, CanandDeviceMessage, CanandDeviceSetting},
+    CanandMessage, CanandMessageError, CanandMessageWrapper,
+};
+use libfuzzer_sys::fuzz_target;
+
+/// Longest CAN FD payload
+const MAX_DLC: usize = 64;
+
+struct Frame {
+    id: u32,
+    data: Vec<u8>,
+}
+
+impl Frame {
+    fn parse(input: &[u8]) -> Option<Self> {
+        let (id, rest) = input.split_first_chunk::<4>()?;
+        let (&dlc, data) = rest.split_first()?;
+        let mut data = data.to_vec();
+        data.resize((dlc as usize).min(MAX_DLC), 0);
+        Some(Self {
+            id: u32::from_le_bytes(*id),
+            data,
+        })
+    }
+}
+
+impl CanandMessage<Frame> for Frame {
+    fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+    fn get_len(&self) -> u8 {
+        self.data.len() as u8
+    }
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+    fn try_from_data(id: u32, data: &[u8]) -> Result<Frame, CanandMessageError> {
+        if data.len() > MAX_DLC {
+            return Err(CanandMessageError::DataTooLarge(data.len()));
+        }
+        Ok(Frame {
+            id,
+            data: data.to_vec(),
+        })
+    }
+}
+
+fn decode<D: CanandDevice>(frame: &CanandMessageWrapper<Frame>)
+where
+    ReportSetting: TryFrom<D::Message>,
+    D::Setting: TryFrom<ReportSetting>,
+{
+    let Ok(msg) = D::Message::try_from_wrapper(frame) else {
+        return;
+    };
+    let _ = msg.message_index();
+    let _ = format!("{msg:?}");
+    if let Ok(reencoded) = msg.try_into_wrapper::<Frame>(frame.id & 0x3f) {
+        let _ = D::Message::try_from_wrapper(&reencoded);
+    }
+    if let Ok(report) = ReportSetting::try_from(msg) {
+        let _ = D::Setting::try_from(report);
+    }
+}
+
+/// The generic views that only products (not the shared CanandDevice spec) convert to.
+fn decode_product<D: CanandDevice>(frame: &CanandMessageWrapper<Frame>)
+where
+    Enumerate: TryFrom<D::Message>,
+    SetSetting: TryFrom<D::Message>,
+    SettingCommand: TryFrom<D::Message>,
+    D::Setting: TryFrom<SetSetting>,
+{
+    // messages aren't Clone, so each conversion gets a fresh decode
+    let message = || D::Message::try_from_wrapper(frame).ok();
+    let _ = message().map(Enumerate::try_from);
+    let _ = message().map(SettingCommand::try_from);
+    if let Some(Ok(set)) = message().map(SetSetting::try_from) {
+        let _ = D::Setting::try_from(set);
+    }
+}
+
+/// Settings are decoded from the first 7 bytes of the data too, as `[index][value; 6]`.
+fn decode_setting<D: CanandDevice>(data: &[u8]) {
+    let Some((&index, value)) = data.split_first() else {
+        return;
+    };
+    let Some(value) = value.first_chunk::<6>() else {
+        return;
+    };
+    if let Ok(index) = <D::Setting as CanandDeviceSetting>::Index::try_from(index) {
+        if let Ok(setting) = D::Setting::from_address_data(index, value) {
+            let _ = format!("{setting:?}");
+            let _: [u8; 6] = setting.into();
+        }
+    }
+}
+
+fuzz_target!(|input: &[u8]| {
+    let Some(frame) = Frame::parse(input) else {
+        return;
+    };
+    let frame = CanandMessageWrapper(frame);
+
+    decode::<canandmessage::cananddevice::Device>(&frame);
+    decode::<canandmessage::canandmag::Device>(&frame);
+    decode::<canandmessage::canandgyro::Device>(&frame);
+    decode::<canandmessage::canandcolor::Device>(&frame);
+    decode_product::<canandmessage::canandmag::Device>(&frame);
+    decode_product::<canandmessage::canandgyro::Device>(&frame);
+    decode_product::<canandmessage::canandcolor::Device>(&frame);
+
+    decode_setting::<canandmessage::cananddevice::Device>(&frame.data);
+    decode_setting::<canandmessage::canandmag::Device>(&frame.data);
+    decode_setting::<canandmessage::canandgyro::Device>(&frame.data);
+    decode_setting::<canandmessage::canandcolor::Device>(&frame.data);
+
+    let _ = Enumerate::try_from_wrapper(&frame);
+    let _ = ReportSetting::try_from_wrapper(&frame);
+    let _ = SetSetting::try_from_wrapper(&frame);
+    let _ = SettingCommand::try_from_wrapper(&frame);
+    let _ = canandmessage::generic::CanIdArbitrate::try_from_wrapper(&frame);
+    let _ = canandmessage::generic::CanIdError::try_from_wrapper(&frame);
+    let _ = canandmessage::generic::OtaData::try_from_wrapper(&frame);
+    let _ = canandmessage::generic::OtaToHost::try_from_wrapper(&frame);
+    let _ = canandmessage::generic::OtaToDevice::try_from_wrapper(&frame);
+});
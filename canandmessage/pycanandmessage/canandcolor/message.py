@@ -158,7 +158,7 @@ class AtomicBondSpecification(BaseMessage):
 class DistanceOutput(BaseMessage):
     """Distance frame"""
     __meta__ = MessageMeta(device_type=6, id=31, min_length=2, max_length=2)
-    distance: Annotated[int, Signal(0, UInt(width=16, min=0, max=65535, default_value=0, factor_num=1, factor_den=1, offset=0))]
+    distance: Annotated[int, Signal(0, UInt(width=16, min=0, max=65535, default_value=0, factor_num=1, factor_den=65535, offset=0))]
     """16-bit distance value. Actual correspondance to real-world units is config and surface-dependent."""
 
 
@@ -167,11 +167,11 @@ class DistanceOutput(BaseMessage):
 class ColorOutput(BaseMessage):
     """Color frame"""
     __meta__ = MessageMeta(device_type=6, id=30, min_length=8, max_length=8)
-    red: Annotated[int, Signal(0, UInt(width=20, min=0, max=1048575, default_value=0, factor_num=1, factor_den=1, offset=0))]
+    red: Annotated[int, Signal(0, UInt(width=20, min=0, max=1048575, default_value=0, factor_num=1, factor_den=1048575, offset=0))]
     """Red reading magnitude"""
-    green: Annotated[int, Signal(20, UInt(width=20, min=0, max=1048575, default_value=0, factor_num=1, factor_den=1, offset=0))]
+    green: Annotated[int, Signal(20, UInt(width=20, min=0, max=1048575, default_value=0, factor_num=1, factor_den=1048575, offset=0))]
     """Green reading magnitude"""
-    blue: Annotated[int, Signal(40, UInt(width=20, min=0, max=1048575, default_value=0, factor_num=1, factor_den=1, offset=0))]
+    blue: Annotated[int, Signal(40, UInt(width=20, min=0, max=1048575, default_value=0, factor_num=1, factor_den=1048575, offset=0))]
     """Blue reading magnitude"""
     period: Annotated[device_types.ColorIntegrationPeriod, Signal(60, Enum(width=4, dtype=device_types.ColorIntegrationPeriod, default_value=device_types.ColorIntegrationPeriod.PERIOD_25_ms_RESOLUTION_16_bit))]
     """Color integration period"""
@@ -369,7 +369,7 @@ class DigoutSlot:
     """Opcode"""
     immidiate_additive: Annotated[int, Signal(11, SInt(width=21, min=-1048576, max=1048575, default_value=0, factor_num=1, factor_den=1, offset=0))]
     """Additive immidiate"""
-    immidiate_scaling: Annotated[int, Signal(32, UInt(width=8, min=0, max=255, default_value=255, factor_num=1, factor_den=256, offset=0))]
+    immidiate_scaling: Annotated[int, Signal(32, UInt(width=8, min=0, max=255, default_value=255, factor_num=1, factor_den=256, offset=0.00390625))]
     """Scaling immidiate"""
     data_source_a: Annotated[DataSource, Signal(40, Enum(width=4, dtype=DataSource, default_value=DataSource.ZERO))]
     """First ``LHS`` data source"""
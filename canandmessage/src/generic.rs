@@ -1,25 +1,35 @@
-pub(crate) const fn build_frc_can_id(
-    device_type: u8,
-    mfg_code: u8,
-    api_idx: u16,
-    device_number: u8,
-) -> u32 {
-    ((device_type as u32) << 24)
-        | ((mfg_code as u32) << 16)
-        | ((api_idx as u32) << 6)
-        | device_number as u32
-}
+pub(crate) use frc_can_id::build_frc_can_id;
 
 pub(crate) const fn api_index_match(id: u32, index: u8) -> bool {
-    id & build_frc_can_id(0, 0xff, 0x3ff, 0)
+    id & (frc_can_id::MANUFACTURER_MASK | frc_can_id::API_INDEX_MASK)
         == build_frc_can_id(0, crate::REDUX_VENDOR_ID, index as u16, 0)
 }
 
+#[cfg_attr(feature = "device", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct CanMaskFilter {
     pub expect: u32,
     pub mask: u32,
 }
 
+impl CanMaskFilter {
+    /// Whether a CAN id passes the filter.
+    pub const fn matches(&self, id: u32) -> bool {
+        (id ^ self.expect) & self.mask == 0
+    }
+
+    /// Widens the filter to also pass `id`, by no longer checking the bits `id` differs in.
+    ///
+    /// Ids between the two that weren't asked for can get through as well, so readers of a
+    /// widened filter still need to check what they got.
+    pub const fn cover(self, id: u32) -> Self {
+        Self {
+            expect: self.expect,
+            mask: self.mask & !(self.expect ^ id),
+        }
+    }
+}
+
 #[cfg_attr(feature = "device", derive(defmt::Format))]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum SettingCastError {
@@ -61,4 +71,13 @@ pub use enumerate::*;
 mod setting_command;
 pub use setting_command::*;
 
+mod setting_transaction;
+pub use setting_transaction::*;
+
+mod composite_setting;
+pub use composite_setting::*;
+
+mod frame_period;
+pub use frame_period::*;
+
 use crate::CanandMessageError;
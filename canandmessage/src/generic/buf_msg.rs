@@ -23,10 +23,14 @@ macro_rules! impl_64bit_msg {
                 }
 
                 #[allow(unused_comparisons)]
-                if cmsg.get_len() < $min_dlc {
+                if cmsg.get_len() < $min_dlc || cmsg.get_len() > 8 {
                     return Err(MessageCastError::WrongDlc(cmsg.get_len()));
                 }
-                Ok(Self(cmsg.get_data()[0..8].try_into().unwrap()))
+                // frames shorter than 8 bytes are zero-padded
+                let mut data = [0u8; 8];
+                let len = cmsg.get_len() as usize;
+                data[..len].copy_from_slice(&cmsg.get_data()[..len]);
+                Ok(Self(data))
             }
 
             fn try_into_wrapper<T: crate::CanandMessage<T>>(
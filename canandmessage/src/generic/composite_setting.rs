@@ -0,0 +1,132 @@
+use super::{SettingTransaction, TransactionError};
+
+/// Most parts a composite setting can be split across.
+pub const MAX_COMPOSITE_PARTS: usize = 8;
+
+/// Most bytes a composite setting can hold.
+pub const MAX_COMPOSITE_LEN: usize = MAX_COMPOSITE_PARTS * 6;
+
+#[cfg_attr(feature = "device", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CompositeEncoding {
+    /// Raw bytes
+    Bytes,
+    /// UTF-8 text, padded with trailing zeros
+    Utf8,
+}
+
+#[cfg_attr(feature = "device", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CompositeError {
+    /// The value doesn't fit in the parts
+    TooLong,
+    /// The part with this setting index hasn't been read
+    Missing(u8),
+    /// A text value isn't valid UTF-8
+    InvalidUtf8,
+    /// Text continues after the zero padding, so the parts likely come from different writes
+    Inconsistent,
+    /// The write couldn't be built as a transaction
+    Transaction(TransactionError),
+}
+
+impl From<TransactionError> for CompositeError {
+    fn from(value: TransactionError) -> Self {
+        Self::Transaction(value)
+    }
+}
+
+/// A value too long for one setting, split across several 6-byte settings.
+///
+/// Parts are listed lowest bytes first.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CompositeSetting {
+    pub name: &'static str,
+    pub parts: &'static [u8],
+    pub encoding: CompositeEncoding,
+}
+
+impl CompositeSetting {
+    /// Number of bytes the setting can hold.
+    pub const fn capacity(&self) -> usize {
+        self.parts.len() * 6
+    }
+
+    /// Whether `index` is one of this setting's parts.
+    pub fn contains(&self, index: u8) -> bool {
+        self.parts.contains(&index)
+    }
+
+    /// Splits `value` into `(setting index, part value)` pairs, zero-padding the tail.
+    pub fn split<'a>(
+        &'a self,
+        value: &'a [u8],
+    ) -> Result<impl Iterator<Item = (u8, [u8; 6])> + 'a, CompositeError> {
+        if value.len() > self.capacity() {
+            return Err(CompositeError::TooLong);
+        }
+        Ok(self.parts.iter().enumerate().map(move |(i, index)| {
+            let mut part = [0u8; 6];
+            let start = (i * 6).min(value.len());
+            let end = (start + 6).min(value.len());
+            part[..end - start].copy_from_slice(&value[start..end]);
+            (*index, part)
+        }))
+    }
+
+    /// Builds a transaction that writes every part at once, so the device never holds half of
+    /// an old value and half of a new one.
+    pub fn transaction(&self, value: &[u8]) -> Result<SettingTransaction, CompositeError> {
+        let mut txn = SettingTransaction::new();
+        for (index, part) in self.split(value)? {
+            txn.push(index, part)?;
+        }
+        Ok(txn)
+    }
+
+    /// Joins the parts returned by `part` (looked up by setting index) and validates the result.
+    pub fn join(
+        &self,
+        mut part: impl FnMut(u8) -> Option<[u8; 6]>,
+    ) -> Result<CompositeValue, CompositeError> {
+        if self.parts.len() > MAX_COMPOSITE_PARTS {
+            return Err(CompositeError::TooLong);
+        }
+        let mut buf = [0u8; MAX_COMPOSITE_LEN];
+        for (i, index) in self.parts.iter().enumerate() {
+            let value = part(*index).ok_or(CompositeError::Missing(*index))?;
+            buf[i * 6..i * 6 + 6].copy_from_slice(&value);
+        }
+
+        let mut len = self.capacity();
+        if self.encoding == CompositeEncoding::Utf8 {
+            len = buf[..len].iter().position(|b| *b == 0).unwrap_or(len);
+            if buf[len..self.capacity()].iter().any(|b| *b != 0) {
+                return Err(CompositeError::Inconsistent);
+            }
+            if core::str::from_utf8(&buf[..len]).is_err() {
+                return Err(CompositeError::InvalidUtf8);
+            }
+        }
+        Ok(CompositeValue { buf, len })
+    }
+}
+
+/// A joined composite setting value.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CompositeValue {
+    buf: [u8; MAX_COMPOSITE_LEN],
+    len: usize,
+}
+
+impl CompositeValue {
+    /// The value, with zero padding stripped from text.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// The value as text, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        core::str::from_utf8(self.as_bytes()).ok()
+    }
+}
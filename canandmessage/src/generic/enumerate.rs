@@ -83,10 +83,11 @@ macro_rules! impl_conv {
                     crate::$dev::Message::Enumerate {
                         serial,
                         is_bootloader,
+                        reserved,
                     } => Ok(Self {
                         serial,
                         is_bootloader,
-                        reserved: 0,
+                        reserved,
                     }),
                     _ => Err(MessageCastError::WrongMessage(value.raw_message_index())),
                 }
@@ -98,6 +99,7 @@ macro_rules! impl_conv {
                 crate::$dev::Message::Enumerate {
                     serial: value.serial,
                     is_bootloader: value.is_bootloader,
+                    reserved: value.reserved,
                 }
             }
         }
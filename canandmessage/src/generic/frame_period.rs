@@ -0,0 +1,40 @@
+/// The setting that controls how often a periodic frame is sent, in milliseconds.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FramePeriodSetting {
+    /// Name of the periodic message, e.g. `STATUS`
+    pub message: &'static str,
+    pub message_index: u8,
+    /// Name of the setting, e.g. `STATUS_FRAME_PERIOD`
+    pub setting: &'static str,
+    pub setting_index: u8,
+    /// Shortest allowed period; 0 means the frame can be turned off
+    pub min_ms: u16,
+    pub max_ms: u16,
+}
+
+impl FramePeriodSetting {
+    /// Whether a period of 0 turns the frame off.
+    pub const fn can_disable(&self) -> bool {
+        self.min_ms == 0
+    }
+
+    /// Whether the setting allows a period of `period_ms`.
+    pub const fn allows(&self, period_ms: u32) -> bool {
+        period_ms >= self.min_ms as u32 && period_ms <= self.max_ms as u32
+    }
+
+    /// Setting value for a period of `period_ms`, if it's in range.
+    pub fn value(&self, period_ms: u32) -> Option<[u8; 6]> {
+        if !self.allows(period_ms) {
+            return None;
+        }
+        let mut value = [0u8; 6];
+        value[..2].copy_from_slice(&(period_ms as u16).to_le_bytes());
+        Some(value)
+    }
+
+    /// Period in milliseconds held by a setting value.
+    pub const fn period_ms(value: [u8; 6]) -> u16 {
+        u16::from_le_bytes([value[0], value[1]])
+    }
+}
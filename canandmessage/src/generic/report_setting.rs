@@ -48,8 +48,11 @@ impl WrapperSerializable for ReportSetting {
         if !api_index_match(id, cananddevice::MessageIndex::ReportSetting.into()) {
             return Err(MessageCastError::WrongMessage(((id >> 6) & 0xff) as u8));
         }
-        let mut data = [0u8; 8];
         let len = cmsg.get_len() as usize;
+        if len > 8 {
+            return Err(MessageCastError::WrongDlc(cmsg.get_len()));
+        }
+        let mut data = [0u8; 8];
         data[..len].copy_from_slice(&cmsg.get_data()[..len]);
         Ok(data.into())
     }
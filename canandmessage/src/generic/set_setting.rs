@@ -43,8 +43,11 @@ impl WrapperSerializable for SetSetting {
             return Err(MessageCastError::WrongMessage(((id >> 6) & 0xff) as u8));
         }
 
-        let mut data = [0u8; 8];
         let len = cmsg.get_len() as usize;
+        if len > 8 {
+            return Err(MessageCastError::WrongDlc(cmsg.get_len()));
+        }
+        let mut data = [0u8; 8];
         data[..len].copy_from_slice(&cmsg.get_data()[..len]);
         Ok(data.into())
     }
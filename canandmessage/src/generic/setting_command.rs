@@ -20,7 +20,7 @@ impl WrapperSerializable for SettingCommand {
             return Err(MessageCastError::WrongMessage(((id >> 6) & 0xff) as u8));
         }
 
-        if cmsg.get_len() < 1 {
+        if cmsg.get_len() < 1 || cmsg.get_len() > 8 {
             return Err(MessageCastError::WrongDlc(cmsg.get_len()));
         }
 
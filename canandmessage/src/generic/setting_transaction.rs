@@ -0,0 +1,209 @@
+use super::SetSetting;
+use crate::cananddevice::types::SettingFlags;
+
+/// Most settings one transaction can carry: up to 15 held settings (the largest `synch_msg_count`)
+/// plus the setting that commits them.
+pub const MAX_TRANSACTION_LEN: usize = 16;
+
+#[cfg_attr(feature = "device", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TransactionError {
+    /// The transaction already holds [`MAX_TRANSACTION_LEN`] settings
+    Full,
+    /// The transaction has no settings to commit
+    Empty,
+}
+
+/// Builds a group of settings that the device applies all together or not at all.
+///
+/// Every setting but the last is sent with `synch_hold` set, which makes the device queue it instead
+/// of applying it. The last one is the commit: `synch_hold` is clear and `synch_msg_count` holds how
+/// many settings should be waiting in the queue. If the device's queue doesn't match that count,
+/// nothing is applied and the commit's report comes back without `commit_success`.
+///
+/// Original Canandmags don't support this and will apply each setting as it arrives.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SettingTransaction {
+    settings: [SetSetting; MAX_TRANSACTION_LEN],
+    len: usize,
+    ephemeral: bool,
+}
+
+impl SettingTransaction {
+    pub const fn new() -> Self {
+        Self {
+            settings: [SetSetting::new(0, [0; 6], flags(false, false, 0)); MAX_TRANSACTION_LEN],
+            len: 0,
+            ephemeral: false,
+        }
+    }
+
+    /// Makes every setting in the transaction ephemeral.
+    pub const fn ephemeral(mut self, ephemeral: bool) -> Self {
+        self.ephemeral = ephemeral;
+        self
+    }
+
+    /// Adds a setting by raw index and value.
+    pub fn push(&mut self, index: u8, value: [u8; 6]) -> Result<&mut Self, TransactionError> {
+        if self.len >= MAX_TRANSACTION_LEN {
+            return Err(TransactionError::Full);
+        }
+        self.settings[self.len] = SetSetting::new(index, value, flags(false, false, 0));
+        self.len += 1;
+        Ok(self)
+    }
+
+    /// Adds a device-specific setting, e.g. a `canandgyro::Setting`.
+    pub fn push_setting<S: Into<SetSetting>>(
+        &mut self,
+        setting: S,
+    ) -> Result<&mut Self, TransactionError> {
+        let setting = setting.into();
+        self.push(setting.index, setting.value)
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The messages to send, in order, with the hold flags filled in.
+    /// The last one is the commit message.
+    pub fn messages(&self) -> Result<impl Iterator<Item = SetSetting> + '_, TransactionError> {
+        if self.len == 0 {
+            return Err(TransactionError::Empty);
+        }
+        let held = (self.len - 1) as u8;
+        let ephemeral = self.ephemeral;
+        Ok(self.settings[..self.len]
+            .iter()
+            .enumerate()
+            .map(move |(i, setting)| {
+                let commit = i == held as usize;
+                setting.with_flags(if commit {
+                    flags(ephemeral, false, held)
+                } else {
+                    flags(ephemeral, true, 0)
+                })
+            }))
+    }
+}
+
+impl Default for SettingTransaction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const fn flags(ephemeral: bool, synch_hold: bool, synch_msg_count: u8) -> SettingFlags {
+    SettingFlags {
+        ephemeral,
+        synch_hold,
+        synch_msg_count,
+    }
+}
+
+/// What a received [`SetSetting`] means for the transaction in progress.
+#[cfg_attr(feature = "device", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TransactionRole {
+    /// Apply immediately; no transaction involved
+    Immediate,
+    /// Queue until the commit arrives
+    Held,
+    /// Apply this and the queued settings if `expected` of them are queued
+    Commit { expected: u8 },
+}
+
+impl TransactionRole {
+    pub const fn of(flags: &SettingFlags) -> Self {
+        if flags.synch_hold {
+            Self::Held
+        } else if flags.synch_msg_count > 0 {
+            Self::Commit {
+                expected: flags.synch_msg_count,
+            }
+        } else {
+            Self::Immediate
+        }
+    }
+}
+
+/// Result of feeding a setting to a [`TransactionReceiver`].
+#[cfg_attr(feature = "device", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Received<'a> {
+    /// Not part of a transaction; apply it now
+    Immediate(SetSetting),
+    /// Queued
+    Held,
+    /// The commit matched: apply all of these, the commit setting last
+    Commit(&'a [SetSetting]),
+    /// The commit didn't match what was queued, or the queue overflowed. Nothing may be applied.
+    Aborted { expected: u8, queued: u8 },
+}
+
+/// Device-side queue that collects held settings and releases them on a matching commit.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TransactionReceiver {
+    queue: [SetSetting; MAX_TRANSACTION_LEN],
+    len: usize,
+    overflowed: bool,
+}
+
+impl TransactionReceiver {
+    pub const fn new() -> Self {
+        Self {
+            queue: [SetSetting::new(0, [0; 6], flags(false, false, 0)); MAX_TRANSACTION_LEN],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Number of settings currently held.
+    pub const fn queued(&self) -> usize {
+        self.len
+    }
+
+    /// Drops anything held, e.g. on a timeout or when the host resets.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.overflowed = false;
+    }
+
+    pub fn receive(&mut self, setting: SetSetting) -> Received<'_> {
+        match TransactionRole::of(&setting.flags) {
+            TransactionRole::Immediate => Received::Immediate(setting),
+            TransactionRole::Held => {
+                // only 15 can be committed, so the 16th held setting already dooms the transaction
+                if self.len >= MAX_TRANSACTION_LEN - 1 {
+                    self.overflowed = true;
+                } else {
+                    self.queue[self.len] = setting;
+                    self.len += 1;
+                }
+                Received::Held
+            }
+            TransactionRole::Commit { expected } => {
+                let queued = self.len as u8;
+                let overflowed = self.overflowed;
+                self.clear();
+                if overflowed || queued != expected {
+                    return Received::Aborted { expected, queued };
+                }
+                self.queue[queued as usize] = setting;
+                Received::Commit(&self.queue[..=queued as usize])
+            }
+        }
+    }
+}
+
+impl Default for TransactionReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
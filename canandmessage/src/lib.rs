@@ -29,6 +29,46 @@ use canandmessage_defn_macro::gen_alchemist_utils;
 #[cfg(feature = "simulation")]
 use canandmessage_defn_macro::gen_simulation_utils;
 
+// Bit access for the generated decoders and encoders. Signal offsets come from the spec and frames
+// are length-checked before decoding, so skipping the bounds checks is sound; the `paranoid`
+// feature checks them anyway, for CI and fuzzing builds.
+#[cfg(not(feature = "paranoid"))]
+macro_rules! bits_get {
+    ($bits:expr, $idx:expr) => {
+        unsafe { $bits.get_unchecked($idx) }
+    };
+}
+#[cfg(feature = "paranoid")]
+macro_rules! bits_get {
+    ($bits:expr, $idx:expr) => {
+        $bits.get($idx).expect("signal runs past the end of the frame")
+    };
+}
+#[cfg(not(feature = "paranoid"))]
+macro_rules! bits_get_mut {
+    ($bits:expr, $idx:expr) => {
+        unsafe { $bits.get_unchecked_mut($idx) }
+    };
+}
+#[cfg(feature = "paranoid")]
+macro_rules! bits_get_mut {
+    ($bits:expr, $idx:expr) => {
+        $bits.get_mut($idx).expect("signal runs past the end of the frame")
+    };
+}
+#[cfg(not(feature = "paranoid"))]
+macro_rules! bits_set {
+    ($bits:expr, $idx:expr, $value:expr) => {
+        unsafe { $bits.set_unchecked($idx, $value) }
+    };
+}
+#[cfg(feature = "paranoid")]
+macro_rules! bits_set {
+    ($bits:expr, $idx:expr, $value:expr) => {
+        $bits.set($idx, $value)
+    };
+}
+
 #[gen_device_messages(src_file = "messages/cananddevice.toml", mode = "both")]
 /// Messages for the Cananddevice.
 pub mod cananddevice {}
@@ -80,6 +120,26 @@ pub mod simulation {
     use crate::canandcolor;
     use crate::canandgyro;
     use crate::canandmag;
+
+    /// Error from setting a simulated signal or message rate by name.
+    #[derive(Debug)]
+    pub enum SimSignalError {
+        UnknownSignal,
+        UnknownMessage,
+        InvalidValue(serde_json::Error),
+    }
+
+    impl core::fmt::Display for SimSignalError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self {
+                SimSignalError::UnknownSignal => write!(f, "unknown signal"),
+                SimSignalError::UnknownMessage => write!(f, "unknown message"),
+                SimSignalError::InvalidValue(e) => write!(f, "invalid value: {e}"),
+            }
+        }
+    }
+
+    impl std::error::Error for SimSignalError {}
 }
 
 pub struct CanandMessageWrapper<T: CanandMessage<T>>(pub T);
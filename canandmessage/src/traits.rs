@@ -1,5 +1,7 @@
 use core::fmt::Debug;
 
+use frc_can_id::FRCCanId;
+
 use crate::CanandMessageError;
 
 pub trait CanandDevice: Debug + PartialEq + Eq + Clone + Copy {
@@ -10,6 +12,8 @@ pub trait CanandDevice: Debug + PartialEq + Eq + Clone + Copy {
     const DEV_NAME: &'static str;
 
     fn setting_info<'a>() -> &'a [SettingInfo<Self::Setting>];
+    fn composite_settings<'a>() -> &'a [crate::generic::CompositeSetting];
+    fn frame_periods<'a>() -> &'a [crate::generic::FramePeriodSetting];
 }
 
 /// Device messages.
@@ -24,6 +28,7 @@ pub trait CanandDeviceMessage: Sized + core::fmt::Debug {
         + Copy
         + PartialOrd
         + Ord
+        + MessageIndexId
         + defmt::Format;
     #[cfg(not(feature = "device"))]
     type Index: TryFrom<u8, Error = ()>
@@ -34,7 +39,8 @@ pub trait CanandDeviceMessage: Sized + core::fmt::Debug {
         + Clone
         + Copy
         + PartialOrd
-        + Ord;
+        + Ord
+        + MessageIndexId;
 
     /// With the use of a device id, converts the current message into a CanandMessageWrapper which can be dereferenced into type T.
     fn try_into_wrapper<T: crate::CanandMessage<T>>(
@@ -61,6 +67,50 @@ pub trait CanandDeviceMessage: Sized + core::fmt::Debug {
     ) -> Result<Self, ()>;
 }
 
+/// Maps a device's message indexes to and from full FRC CAN ids.
+///
+/// Implemented by every generated `MessageIndex`, so host code can build and pick apart ids
+/// without shifting api indexes around by hand.
+pub trait MessageIndexId: Sized + Copy + Into<u8> + TryFrom<u8, Error = ()> {
+    /// FRC device type the messages are sent with, or [`None`] for messages every device shares
+    /// (e.g. [`cananddevice`](crate::cananddevice)).
+    const DEV_TYPE: Option<u8>;
+
+    /// Gets the full CAN id of this message for device number `device_number`.
+    ///
+    /// Shared messages have no device type of their own and get device type 0; use
+    /// [`frc_can_id_as`](Self::frc_can_id_as) to address them to a specific device.
+    fn frc_can_id(self, device_number: u8) -> FRCCanId {
+        self.frc_can_id_as(Self::DEV_TYPE.unwrap_or(0), device_number)
+    }
+
+    /// Gets the full CAN id of this message for a device of type `device_type`.
+    fn frc_can_id_as(self, device_type: u8, device_number: u8) -> FRCCanId {
+        FRCCanId::new(frc_can_id::build_frc_can_id(
+            device_type,
+            crate::REDUX_VENDOR_ID,
+            self.into() as u16,
+            device_number,
+        ))
+    }
+
+    /// Splits a CAN id into its message index and device number.
+    ///
+    /// Returns [`None`] if the id isn't one of this device's messages.
+    fn from_frc_can_id(id: &FRCCanId) -> Option<(Self, u8)> {
+        if id.manufacturer_code() != crate::REDUX_VENDOR_ID {
+            return None;
+        }
+        if let Some(dev_type) = Self::DEV_TYPE {
+            if id.device_type_code() != dev_type {
+                return None;
+            }
+        }
+        let index = u8::try_from(id.api_index()).ok()?;
+        Some((Self::try_from(index).ok()?, id.device_number()))
+    }
+}
+
 pub trait CanandDeviceSetting: Into<[u8; 6]> + Debug + PartialEq + Clone + Copy {
     #[cfg(feature = "device")]
     type Index: TryFrom<u8, Error = ()>
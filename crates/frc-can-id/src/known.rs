@@ -0,0 +1,202 @@
+//! Labels for frames from well-known FRC CAN devices.
+//!
+//! Real robots mix vendors on one bus, so captures are mostly traffic we don't have message specs
+//! for. This gets such a frame as far as vendor, device type, device number and, for the common
+//! CTRE/REV/NI frames, which frame it is:
+//!
+//! ```
+//! let label = frc_can_id::known::label(0x0205_1800 | 3);
+//! assert_eq!(label.to_string(), "REV SPARK #3 Periodic status 0");
+//! ```
+//!
+//! Frame names follow the vendors' public CAN specs where there is one. Where only the API class is
+//! known, the API index is appended to the class name. Anything not listed is labeled by its raw
+//! API class and index.
+
+use core::fmt;
+
+use crate::{FRCCanDeviceType, FRCCanId, FRCCanVendor};
+
+/// A known frame or API class of one vendor's device type.
+#[derive(Debug, Clone, Copy)]
+struct KnownFrame {
+    vendor: FRCCanVendor,
+    device_type: FRCCanDeviceType,
+    /// 10-bit API id
+    api: u16,
+    /// Bits of the API id that have to match `api`
+    api_mask: u16,
+    name: &'static str,
+    /// Append the API index to `name`, for entries matching a whole API class
+    indexed: bool,
+}
+
+/// Matches exactly one API id.
+const fn frame(
+    vendor: FRCCanVendor,
+    device_type: FRCCanDeviceType,
+    api: u16,
+    name: &'static str,
+) -> KnownFrame {
+    KnownFrame {
+        vendor,
+        device_type,
+        api,
+        api_mask: 0x3ff,
+        name,
+        indexed: false,
+    }
+}
+
+/// Matches every index of an API class.
+const fn class(
+    vendor: FRCCanVendor,
+    device_type: FRCCanDeviceType,
+    api_class: u8,
+    name: &'static str,
+) -> KnownFrame {
+    KnownFrame {
+        vendor,
+        device_type,
+        api: (api_class as u16) << 4,
+        api_mask: 0x3f0,
+        name,
+        indexed: true,
+    }
+}
+
+use FRCCanDeviceType as Dt;
+use FRCCanVendor as V;
+
+/// Checked in order, so specific frames go before the class they belong to.
+#[rustfmt::skip]
+const KNOWN_FRAMES: &[KnownFrame] = &[
+    // roboRIO
+    frame(V::NationalInstruments, Dt::RobotController, 0x061, "Heartbeat"),
+    // Phoenix 5 motor controllers
+    class(V::CtrElectronics, Dt::MotorController, 0x00, "Control index"),
+    class(V::CtrElectronics, Dt::MotorController, 0x05, "Status index"),
+    // CTRE PDP
+    frame(V::CtrElectronics, Dt::PowerDistributionModule, 0x050, "Status 1"),
+    frame(V::CtrElectronics, Dt::PowerDistributionModule, 0x051, "Status 2"),
+    frame(V::CtrElectronics, Dt::PowerDistributionModule, 0x052, "Status 3"),
+    frame(V::CtrElectronics, Dt::PowerDistributionModule, 0x05d, "Energy status"),
+    // CTRE PCM
+    class(V::CtrElectronics, Dt::PneumaticsController, 0x05, "Status index"),
+    // SPARK MAX / SPARK Flex
+    frame(V::Rev, Dt::MotorController, 0x0b2, "Non-roboRIO heartbeat"),
+    class(V::Rev, Dt::MotorController, 0x06, "Periodic status"),
+    // REV PDH and PH
+    class(V::Rev, Dt::PowerDistributionModule, 0x06, "Status"),
+    class(V::Rev, Dt::PneumaticsController, 0x06, "Status"),
+];
+
+/// Likely product behind a vendor's device type.
+const PRODUCTS: &[(FRCCanVendor, FRCCanDeviceType, &str)] = &[
+    (V::NationalInstruments, Dt::RobotController, "roboRIO"),
+    (V::CtrElectronics, Dt::MotorController, "Talon"),
+    (V::CtrElectronics, Dt::GyroSensor, "Pigeon"),
+    (V::CtrElectronics, Dt::DistanceSensor, "CANrange"),
+    (V::CtrElectronics, Dt::Encoder, "CANcoder"),
+    (V::CtrElectronics, Dt::PowerDistributionModule, "PDP"),
+    (V::CtrElectronics, Dt::PneumaticsController, "PCM"),
+    (V::CtrElectronics, Dt::Miscellaneous, "CANdle"),
+    (V::Rev, Dt::MotorController, "SPARK"),
+    (V::Rev, Dt::PowerDistributionModule, "PDH"),
+    (V::Rev, Dt::PneumaticsController, "PH"),
+    (V::KauaiLabs, Dt::GyroSensor, "navX"),
+    (V::PlayingWithFusion, Dt::DistanceSensor, "Time of Flight"),
+    (V::Redux, Dt::GyroSensor, "Canandgyro"),
+    (V::Redux, Dt::Encoder, "Canandmag"),
+    (V::Redux, Dt::DistanceSensor, "Canandcolor"),
+];
+
+/// What is known about a frame from its id alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameLabel {
+    /// Manufacturer
+    pub vendor: FRCCanVendor,
+    /// Device type
+    pub device_type: FRCCanDeviceType,
+    /// Device number, 0-63
+    pub device_number: u8,
+    /// API class, the top 6 bits of the API id
+    pub api_class: u8,
+    /// API index, the bottom 4 bits of the API id
+    pub api_index: u8,
+    /// Likely product, if the vendor only makes one kind of this device type
+    pub product: Option<&'static str>,
+    /// Name of the frame, if it's a known one
+    pub frame: Option<&'static str>,
+    frame_indexed: bool,
+}
+
+/// Labels a 29-bit FRC CAN id.
+pub fn label(id: u32) -> FrameLabel {
+    let id = FRCCanId::new(id);
+    let vendor = id.manufacturer();
+    let device_type = id.device_type();
+    let api = id.api_index();
+    let known = KNOWN_FRAMES
+        .iter()
+        .find(|f| f.vendor == vendor && f.device_type == device_type && api & f.api_mask == f.api);
+    FrameLabel {
+        vendor,
+        device_type,
+        device_number: id.device_number(),
+        api_class: id.api_class(),
+        api_index: id.api_class_index(),
+        product: PRODUCTS
+            .iter()
+            .find(|(v, d, _)| *v == vendor && *d == device_type)
+            .map(|(_, _, product)| *product),
+        frame: known.map(|f| f.name),
+        frame_indexed: known.is_some_and(|f| f.indexed),
+    }
+}
+
+impl fmt::Display for FrameLabel {
+    /// `REV SPARK #3 Periodic status 0`, or `Grapple Encoder #1 api 0x12:3` for frames we don't know.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.vendor)?;
+        match self.product {
+            Some(product) => write!(f, "{product}")?,
+            None => write!(f, "{}", self.device_type)?,
+        }
+        write!(f, " #{}", self.device_number)?;
+        match self.frame {
+            Some(frame) if self.frame_indexed => write!(f, " {frame} {}", self.api_index),
+            Some(frame) => write!(f, " {frame}"),
+            None => write!(f, " api 0x{:02x}:{}", self.api_class, self.api_index),
+        }
+    }
+}
+
+#[test]
+fn test_known_labels() {
+    extern crate std;
+    use std::string::ToString;
+
+    use crate::build_frc_can_id;
+
+    assert_eq!(
+        label(crate::HEARTBEAT_ID).to_string(),
+        "NI roboRIO #0 Heartbeat"
+    );
+    assert_eq!(
+        label(build_frc_can_id(2, 4, 0x051, 7)).to_string(),
+        "CTRE Talon #7 Status index 1"
+    );
+    assert_eq!(
+        label(build_frc_can_id(8, 4, 0x05d, 0)).to_string(),
+        "CTRE PDP #0 Energy status"
+    );
+    assert_eq!(
+        label(build_frc_can_id(2, 5, 0x0b2, 0)).to_string(),
+        "REV SPARK #0 Non-roboRIO heartbeat"
+    );
+    assert_eq!(
+        label(build_frc_can_id(9, 0x22, 0x123, 5)).to_string(),
+        "vendor 0x22 PneumaticsController #5 api 0x12:3"
+    );
+}
@@ -5,6 +5,60 @@
 #![no_std]
 #![warn(missing_docs)]
 use num_enum::{FromPrimitive, IntoPrimitive};
+
+pub mod known;
+
+/// Bit offset of the device number field.
+pub const DEVICE_NUMBER_SHIFT: u32 = 0;
+/// Width of the device number field.
+pub const DEVICE_NUMBER_BITS: u32 = 6;
+/// Bit offset of the API index field.
+pub const API_INDEX_SHIFT: u32 = 6;
+/// Width of the API index field.
+pub const API_INDEX_BITS: u32 = 10;
+/// Bit offset of the manufacturer field.
+pub const MANUFACTURER_SHIFT: u32 = 16;
+/// Width of the manufacturer field.
+pub const MANUFACTURER_BITS: u32 = 8;
+/// Bit offset of the device type field.
+pub const DEVICE_TYPE_SHIFT: u32 = 24;
+/// Width of the device type field.
+pub const DEVICE_TYPE_BITS: u32 = 5;
+
+/// Mask of a `bits` wide field starting at bit `shift`.
+pub const fn field_mask(shift: u32, bits: u32) -> u32 {
+    ((1 << bits) - 1) << shift
+}
+
+/// Mask of the device number field.
+pub const DEVICE_NUMBER_MASK: u32 = field_mask(DEVICE_NUMBER_SHIFT, DEVICE_NUMBER_BITS);
+/// Mask of the API index field.
+pub const API_INDEX_MASK: u32 = field_mask(API_INDEX_SHIFT, API_INDEX_BITS);
+/// Mask of the manufacturer field.
+pub const MANUFACTURER_MASK: u32 = field_mask(MANUFACTURER_SHIFT, MANUFACTURER_BITS);
+/// Mask of the device type field.
+pub const DEVICE_TYPE_MASK: u32 = field_mask(DEVICE_TYPE_SHIFT, DEVICE_TYPE_BITS);
+/// Mask of a whole 29-bit id.
+pub const ID_MASK: u32 = field_mask(0, 29);
+
+// the fields must tile the id exactly, or ids built from parts won't round-trip
+const _: () = {
+    assert!(DEVICE_NUMBER_MASK & API_INDEX_MASK == 0);
+    assert!((DEVICE_NUMBER_MASK | API_INDEX_MASK) & MANUFACTURER_MASK == 0);
+    assert!((DEVICE_NUMBER_MASK | API_INDEX_MASK | MANUFACTURER_MASK) & DEVICE_TYPE_MASK == 0);
+    assert!(DEVICE_NUMBER_MASK | API_INDEX_MASK | MANUFACTURER_MASK | DEVICE_TYPE_MASK == ID_MASK);
+};
+
+/// Mask matching every message from one device, whatever its API index.
+pub const fn device_filter_mask() -> u32 {
+    ID_MASK & !API_INDEX_MASK
+}
+
+/// Mask matching a single message from one device.
+pub const fn message_filter_mask() -> u32 {
+    ID_MASK
+}
+
 /// ID of the CAN heartbeat.
 pub const HEARTBEAT_ID: u32 = 0x01011840;
 /// Redux vendor id.
@@ -12,7 +66,7 @@ pub const REDUX_VENDOR_ID: u8 = 0xe;
 /// Redux enumerate broadcast id.
 pub const REDUX_BROADCAST_ENUMERATE: u32 = build_frc_can_id(0, 0xe, 0, 0);
 /// Generic filter for a device id.
-pub const DEVICE_FILTER: u32 = build_frc_can_id(0x1f, 0xff, 0, 0x3f);
+pub const DEVICE_FILTER: u32 = device_filter_mask();
 /// Global disable actuators packet id.
 pub const GLOBAL_DISABLE: u32 = 0;
 
@@ -43,17 +97,27 @@ impl FRCCanId {
 
     /// Gets the device number.
     pub const fn device_number(&self) -> u8 {
-        (self.0 & 0x3f) as u8
+        ((self.0 & DEVICE_NUMBER_MASK) >> DEVICE_NUMBER_SHIFT) as u8
     }
 
     /// Gets the API index.
     pub const fn api_index(&self) -> u16 {
-        ((self.0 >> 6) & 0x3ff) as u16
+        ((self.0 & API_INDEX_MASK) >> API_INDEX_SHIFT) as u16
+    }
+
+    /// Gets the API class, the upper 6 bits of the API index.
+    pub const fn api_class(&self) -> u8 {
+        (self.api_index() >> 4) as u8
+    }
+
+    /// Gets the index within the API class, the lower 4 bits of the API index.
+    pub const fn api_class_index(&self) -> u8 {
+        (self.api_index() & 0xf) as u8
     }
 
     /// Gets the raw manufacturer code.
     pub const fn manufacturer_code(&self) -> u8 {
-        ((self.0 >> 16) & 0xff) as u8
+        ((self.0 & MANUFACTURER_MASK) >> MANUFACTURER_SHIFT) as u8
     }
 
     /// Gets the manufacturer as an enum.
@@ -63,7 +127,7 @@ impl FRCCanId {
 
     /// Gets the device type id.
     pub const fn device_type_code(&self) -> u8 {
-        ((self.0 >> 24) & 0x1f) as u8
+        ((self.0 & DEVICE_TYPE_MASK) >> DEVICE_TYPE_SHIFT) as u8
     }
 
     /// Gets the device type as an enum.
@@ -207,6 +271,87 @@ impl core::fmt::Debug for FRCCanHeartbeat {
     }
 }
 
+/// Default heartbeat expiry, in microseconds.
+///
+/// Actuators must be disabled if no heartbeat has been seen for this long.
+pub const HEARTBEAT_TIMEOUT_US: u64 = 100_000;
+
+/// Tracks the most recent roboRIO heartbeat to decide whether actuators may be enabled.
+///
+/// This does not allocate or read any clocks; the caller feeds in a monotonic microsecond
+/// timestamp alongside each packet and each query, so the same logic can run in device firmware
+/// and on the host.
+///
+/// The robot is considered enabled if and only if a heartbeat has been seen less than
+/// `TIMEOUT_US` microseconds ago and that heartbeat has [`FRCCanHeartbeat::system_watchdog`] set.
+/// Timestamps that go backwards are treated as expired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatTracker<const TIMEOUT_US: u64 = HEARTBEAT_TIMEOUT_US> {
+    last: Option<(FRCCanHeartbeat, u64)>,
+}
+
+impl<const TIMEOUT_US: u64> HeartbeatTracker<TIMEOUT_US> {
+    /// Creates a tracker that has not seen any heartbeats yet.
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// Records a heartbeat received at `now_us`.
+    pub fn update(&mut self, heartbeat: FRCCanHeartbeat, now_us: u64) {
+        self.last = Some((heartbeat, now_us));
+    }
+
+    /// Feeds an arbitrary CAN frame into the tracker.
+    ///
+    /// Returns true if the frame was a heartbeat.
+    pub fn ingest(&mut self, id: u32, data: &[u8], now_us: u64) -> bool {
+        if id != HEARTBEAT_ID {
+            return false;
+        }
+        let Ok(data) = <[u8; 8]>::try_from(data) else {
+            return false;
+        };
+        self.update(FRCCanHeartbeat::new(data), now_us);
+        true
+    }
+
+    /// Most recent heartbeat, if it has not expired.
+    pub fn current(&self, now_us: u64) -> Option<FRCCanHeartbeat> {
+        let (heartbeat, ts) = self.last?;
+        // a timestamp from the future wraps to a huge age and expires
+        if now_us.wrapping_sub(ts) < TIMEOUT_US {
+            Some(heartbeat)
+        } else {
+            None
+        }
+    }
+
+    /// Most recent heartbeat, regardless of age.
+    pub const fn last_heartbeat(&self) -> Option<FRCCanHeartbeat> {
+        match self.last {
+            Some((heartbeat, _)) => Some(heartbeat),
+            None => None,
+        }
+    }
+
+    /// True if actuators may be energized at `now_us`.
+    pub fn enabled(&self, now_us: u64) -> bool {
+        self.current(now_us)
+            .is_some_and(|heartbeat| heartbeat.system_watchdog())
+    }
+
+    /// Forgets any previously seen heartbeat.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+impl<const TIMEOUT_US: u64> Default for HeartbeatTracker<TIMEOUT_US> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Device type (most significant 5 bits )
 #[derive(
     Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromPrimitive, IntoPrimitive, Hash,
@@ -268,6 +413,15 @@ impl FRCCanDeviceType {
     }
 }
 
+impl core::fmt::Display for FRCCanDeviceType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Reserved(x) => write!(f, "device type {x}"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
 /// Non-exhaustive list of FRC CAN vendors.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromPrimitive, IntoPrimitive)]
 #[repr(u8)]
@@ -350,6 +504,37 @@ impl FRCCanVendor {
     }
 }
 
+impl core::fmt::Display for FRCCanVendor {
+    /// Short name of the vendor, as usually written on a robot.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            Self::Broadcast => "Broadcast",
+            Self::NationalInstruments => "NI",
+            Self::LuminaryMicro => "Luminary Micro",
+            Self::Deka => "DEKA",
+            Self::CtrElectronics => "CTRE",
+            Self::Rev => "REV",
+            Self::Grapple => "Grapple",
+            Self::MindSensors => "MindSensors",
+            Self::TeamUse => "Team",
+            Self::KauaiLabs => "Kauai Labs",
+            Self::Copperforge => "Copperforge",
+            Self::PlayingWithFusion => "Playing with Fusion",
+            Self::Studica => "Studica",
+            Self::ThriftyBot => "The Thrifty Bot",
+            Self::Redux => "Redux",
+            Self::AndyMark => "AndyMark",
+            Self::VividHosting => "Vivid Hosting",
+            Self::Vertos => "Vertos",
+            Self::Swyft => "SWYFT",
+            Self::LumynLabs => "Lumyn Labs",
+            Self::BrushlandLabs => "Brushland Labs",
+            Self::Unknown(x) => return write!(f, "vendor 0x{x:02x}"),
+        };
+        f.write_str(name)
+    }
+}
+
 /// Raw FRC CAN ID builder
 pub const fn build_frc_can_id(
     device_type: u8,
@@ -357,10 +542,10 @@ pub const fn build_frc_can_id(
     api_idx: u16,
     device_number: u8,
 ) -> u32 {
-    ((device_type as u32) << 24)
-        | ((mfg_code as u32) << 16)
-        | ((api_idx as u32) << 6)
-        | device_number as u32
+    ((device_type as u32) << DEVICE_TYPE_SHIFT)
+        | ((mfg_code as u32) << MANUFACTURER_SHIFT)
+        | ((api_idx as u32) << API_INDEX_SHIFT)
+        | ((device_number as u32) << DEVICE_NUMBER_SHIFT)
 }
 
 #[test]
@@ -375,3 +560,23 @@ fn test_roborio_hb() {
     let hb = FRCCanHeartbeat::new(hb_raw_no_watchdog);
     assert!(!hb.system_watchdog());
 }
+
+#[test]
+fn test_heartbeat_tracker() {
+    let hb_raw_enabled = [0x39, 0xc7, 0x0e, 0x7d, 0x13, 0x00, 0x00, 0xff];
+    let hb_raw_disabled = [0xb8, 0x4e, 0x0e, 0xbc, 0x00, 0x00, 0x00, 0xff];
+    let mut tracker: HeartbeatTracker = HeartbeatTracker::new();
+    assert!(!tracker.enabled(0));
+
+    assert!(tracker.ingest(HEARTBEAT_ID, &hb_raw_enabled, 1_000));
+    assert!(tracker.enabled(1_000));
+    assert!(tracker.enabled(1_000 + HEARTBEAT_TIMEOUT_US - 1));
+    assert!(!tracker.enabled(1_000 + HEARTBEAT_TIMEOUT_US));
+    // time going backwards fails safe
+    assert!(!tracker.enabled(999));
+
+    assert!(!tracker.ingest(HEARTBEAT_ID + 1, &hb_raw_enabled, 2_000));
+    assert!(tracker.ingest(HEARTBEAT_ID, &hb_raw_disabled, 2_000));
+    assert!(!tracker.enabled(2_000));
+    assert!(tracker.current(2_000).is_some());
+}
@@ -32,7 +32,8 @@ pub struct CANLinkRxMessage {
     pub message_id: u32,
     /// The bus ID associated with the message.
     pub bus_id: u16,
-    /// Flags (reserved)
+    /// ReduxFIFO message flags in the low byte (`NO_BRS` 0x01, `NO_FD` 0x02, `ESI` 0x40, ...),
+    /// or [`FLAG_NOTICE`]
     pub flags: u16,
     /// Timestamp in microseconds from the FPGA timebase
     pub timestamp: u64,
@@ -114,6 +115,77 @@ impl TryFrom<&[u8]> for CANLinkRxMessage {
     }
 }
 
+/// Set in [`CANLinkRxMessage::flags`] on frames that carry a server notice rather than a bus
+/// message. The notice kind is in the message id slot.
+pub const FLAG_NOTICE: u16 = 0x8000;
+
+/// Notice kind of a [`CANLinkOverflowNotice`].
+pub const NOTICE_OVERFLOW: u32 = 1;
+
+/// Sent by the server when a client isn't reading fast enough to keep up with the bus.
+///
+/// It goes out in place of an RX frame with [`FLAG_NOTICE`] set, at most every so often while
+/// messages are being lost. Counts are since the previous notice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CANLinkOverflowNotice {
+    /// The bus ID the notice is about.
+    pub bus_id: u16,
+    /// Timestamp in microseconds from the FPGA timebase
+    pub timestamp: u64,
+    /// Messages dropped because the send queue was full
+    pub dropped: u32,
+    /// Messages skipped by decimation
+    pub skipped: u32,
+    /// Messages waiting in the send queue
+    pub queued: u32,
+    /// Only every `decimation`th message of each id is being delivered; 1 means all of them are.
+    pub decimation: u16,
+}
+
+impl CANLinkOverflowNotice {
+    const DATA_SIZE: usize = 14;
+}
+
+impl From<CANLinkOverflowNotice> for CANLinkRxMessage {
+    fn from(value: CANLinkOverflowNotice) -> Self {
+        let mut data = [0_u8; 64];
+        serialize_int!(data, value, dropped, 0);
+        serialize_int!(data, value, skipped, 4);
+        serialize_int!(data, value, queued, 8);
+        serialize_int!(data, value, decimation, 12);
+        Self {
+            message_id: NOTICE_OVERFLOW,
+            bus_id: value.bus_id,
+            flags: FLAG_NOTICE,
+            timestamp: value.timestamp,
+            data,
+            data_size: CANLinkOverflowNotice::DATA_SIZE,
+        }
+    }
+}
+
+impl TryFrom<&CANLinkRxMessage> for CANLinkOverflowNotice {
+    type Error = ();
+
+    fn try_from(value: &CANLinkRxMessage) -> Result<Self, Self::Error> {
+        if value.flags & FLAG_NOTICE == 0
+            || value.message_id != NOTICE_OVERFLOW
+            || value.data_size < Self::DATA_SIZE
+        {
+            return Err(());
+        }
+        let data = &value.data;
+        Ok(Self {
+            bus_id: value.bus_id,
+            timestamp: value.timestamp,
+            dropped: extract_int!(data, Self, dropped, 0, u32),
+            skipped: extract_int!(data, Self, skipped, 4, u32),
+            queued: extract_int!(data, Self, queued, 8, u32),
+            decimation: extract_int!(data, Self, decimation, 12, u16),
+        })
+    }
+}
+
 /// Message sent to CANLink to be sent onto bus.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[repr(C)]
@@ -122,7 +194,8 @@ pub struct CANLinkTxMessage {
     pub message_id: u32,
     /// The bus ID associated with the message.
     pub bus_id: u16,
-    /// Flags (reserved)
+    /// ReduxFIFO message flags in the low byte; clear `NO_FD` (0x02) and `NO_BRS` (0x01) to send
+    /// a bit rate switched CAN-FD frame
     pub flags: u16,
     /// This always holds the largest value.
     /// It's this large for convenience reasons/not having to deal with slice ownership
@@ -197,3 +270,24 @@ impl TryFrom<&[u8]> for CANLinkTxMessage {
         })
     }
 }
+
+#[test]
+fn test_overflow_notice_roundtrip() {
+    let notice = CANLinkOverflowNotice {
+        bus_id: 2,
+        timestamp: 123456,
+        dropped: 40,
+        skipped: 7,
+        queued: 2048,
+        decimation: 4,
+    };
+    let mut buffer = CANLinkRxMessage::buffer();
+    let rx_msg: CANLinkRxMessage = notice.into();
+    let wire = rx_msg.serialize_into(&mut buffer);
+    let decoded = CANLinkRxMessage::try_from(wire).unwrap();
+    assert_eq!(CANLinkOverflowNotice::try_from(&decoded), Ok(notice));
+
+    let mut can_msg = decoded;
+    can_msg.flags = 0;
+    assert!(CANLinkOverflowNotice::try_from(&can_msg).is_err());
+}
@@ -1,6 +1,21 @@
 //! RdxCRC: Common CRC stuff for Redux products.
+//!
+//! CRC32/mpeg2 has dedicated fast paths; other CRC-8/16/32 variants go through [`engine::CrcEngine`].
+//!
+//! The `std` feature enables runtime detection of the ARMv8 CRC instructions; without it they're
+//! only used when the target is built with the `crc` target feature.
+//!
+//! The `paranoid` feature bounds-checks the table lookups that are otherwise left unchecked, for CI
+//! and fuzzing builds.
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod engine;
+
+pub use engine::{CrcEngine, CrcParams, crc8_sae_j1850, crc8_smbus, crc16_ibm_3740, crc16_modbus};
+
 /// Common trait for CRC32 impls
 pub trait Crc32 {
     fn init(&mut self);
@@ -23,9 +38,9 @@ pub fn crc4itu_nibble_reverse(init: u8, data: &[u8]) -> (u8, u8) {
     for b in data.iter().rev() {
         // SAFETY: `TABLE_NIBBLE` is 16 elements long and the masking operations constrain the value to < 16.
         unsafe {
-            crc = *TABLE_NIBBLE.get_unchecked((crc ^ (*b & 0xf)) as usize);
+            crc = lookup(&TABLE_NIBBLE, (crc ^ (*b & 0xf)) as usize);
             lag = crc;
-            crc = *TABLE_NIBBLE.get_unchecked((crc ^ (*b >> 4)) as usize);
+            crc = lookup(&TABLE_NIBBLE, (crc ^ (*b >> 4)) as usize);
         }
     }
 
@@ -48,21 +63,170 @@ pub fn crc32_mpeg2(mut crc: u32, data: &[u8]) -> u32 {
         crc = crc ^ ((*b as u32) << 24);
         // SAFETY: The table is 16 elements long and the masking operations constrain the value to < 16.
         unsafe {
-            crc = (crc << 4) ^ CRC32_MPEG2_TABLE.get_unchecked((crc >> 28) as usize);
-            crc = (crc << 4) ^ CRC32_MPEG2_TABLE.get_unchecked((crc >> 28) as usize);
+            crc = (crc << 4) ^ lookup(&CRC32_MPEG2_TABLE, (crc >> 28) as usize);
+            crc = (crc << 4) ^ lookup(&CRC32_MPEG2_TABLE, (crc >> 28) as usize);
         }
     }
     crc
 }
 
+/// `table[idx]`, bounds-checked only with the `paranoid` feature.
+///
+/// # Safety
+/// `idx` has to be in bounds.
+#[inline(always)]
+unsafe fn lookup<T: Copy>(table: &[T], idx: usize) -> T {
+    #[cfg(feature = "paranoid")]
+    {
+        table[idx]
+    }
+    #[cfg(not(feature = "paranoid"))]
+    {
+        debug_assert!(idx < table.len());
+        unsafe { *table.get_unchecked(idx) }
+    }
+}
+
+/// Slice-by-8 lookup tables for CRC32/mpeg2.
+///
+/// `[0]` is the usual byte-at-a-time table; `[k]` advances a byte through `k` more zero bytes.
+static CRC32_MPEG2_SLICE8: [[u32; 256]; 8] = crc32_mpeg2_slice8_tables();
+
+const fn crc32_mpeg2_slice8_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0u32; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C11DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = (prev << 8) ^ tables[0][(prev >> 24) as usize];
+            i += 1;
+        }
+        k += 1;
+    }
+    tables
+}
+
+/// Slice-by-8 implementation of CRC32/mpeg2.
+///
+/// Gives the same results as [`crc32_mpeg2`] but eats 8 bytes per iteration, at the cost of 8 KiB of tables.
+pub fn crc32_mpeg2_slice8(mut crc: u32, data: &[u8]) -> u32 {
+    let t = &CRC32_MPEG2_SLICE8;
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let hi = crc ^ u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let lo = u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        crc = t[7][(hi >> 24) as usize]
+            ^ t[6][((hi >> 16) & 0xff) as usize]
+            ^ t[5][((hi >> 8) & 0xff) as usize]
+            ^ t[4][(hi & 0xff) as usize]
+            ^ t[3][(lo >> 24) as usize]
+            ^ t[2][((lo >> 16) & 0xff) as usize]
+            ^ t[1][((lo >> 8) & 0xff) as usize]
+            ^ t[0][(lo & 0xff) as usize];
+    }
+    for b in chunks.remainder() {
+        crc = (crc << 8) ^ t[0][((crc >> 24) ^ *b as u32) as usize];
+    }
+    crc
+}
+
+/// CRC32/mpeg2 using the ARMv8 CRC32 instructions.
+///
+/// Those implement the bit-reflected CRC32 (same polynomial, LSB first), so the state and every input
+/// byte get bit-reversed on the way in and the state again on the way out.
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "crc")]
+unsafe fn crc32_mpeg2_armv8(crc: u32, data: &[u8]) -> u32 {
+    use core::arch::aarch64::{__crc32b, __crc32d};
+
+    let mut state = crc.reverse_bits();
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let word = u64::from_be_bytes(chunk.try_into().unwrap()).reverse_bits();
+        state = __crc32d(state, word);
+    }
+    for b in chunks.remainder() {
+        state = __crc32b(state, b.reverse_bits());
+    }
+    state.reverse_bits()
+}
+
+/// CRC32/mpeg2 using the ARMv8 CRC32 instructions from AArch32 state.
+///
+/// The 32-bit ARM intrinsics aren't stable, so this goes through inline assembly, and since there's no
+/// stable runtime detection there either it's only built for targets that have the `crc` feature enabled.
+/// See [`crc32_mpeg2_armv8`] for the bit reversal.
+#[cfg(all(target_arch = "arm", target_feature = "crc"))]
+fn crc32_mpeg2_armv7(crc: u32, data: &[u8]) -> u32 {
+    use core::arch::asm;
+
+    let mut state = crc.reverse_bits();
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let word = u32::from_be_bytes(chunk.try_into().unwrap()).reverse_bits();
+        // SAFETY: the crc32w instruction is available with the `crc` target feature and touches only its registers.
+        unsafe {
+            asm!("crc32w {s}, {s}, {w}", s = inout(reg) state, w = in(reg) word, options(pure, nomem, nostack));
+        }
+    }
+    for b in chunks.remainder() {
+        let byte = b.reverse_bits() as u32;
+        // SAFETY: as above.
+        unsafe {
+            asm!("crc32b {s}, {s}, {b}", s = inout(reg) state, b = in(reg) byte, options(pure, nomem, nostack));
+        }
+    }
+    state.reverse_bits()
+}
+
+/// CRC32/mpeg2 with the fastest implementation available on this CPU.
+///
+/// Uses the ARM CRC32 instructions when present and [`crc32_mpeg2_slice8`] otherwise.
+pub fn crc32_mpeg2_fast(crc: u32, data: &[u8]) -> u32 {
+    #[cfg(all(target_arch = "aarch64", target_feature = "crc"))]
+    {
+        // SAFETY: the crc feature is enabled for the whole target.
+        return unsafe { crc32_mpeg2_armv8(crc, data) };
+    }
+    #[cfg(all(target_arch = "aarch64", not(target_feature = "crc"), feature = "std"))]
+    {
+        if std::arch::is_aarch64_feature_detected!("crc") {
+            // SAFETY: we just checked the CPU supports the crc feature.
+            return unsafe { crc32_mpeg2_armv8(crc, data) };
+        }
+    }
+    #[cfg(all(target_arch = "arm", target_feature = "crc"))]
+    {
+        return crc32_mpeg2_armv7(crc, data);
+    }
+    #[allow(unreachable_code)]
+    crc32_mpeg2_slice8(crc, data)
+}
+
 /// Software implementation of crc32/mpeg2 that applies 4-byte padding for consistency with hardware implementations.
 pub fn crc32_mpeg2_pad(crc: u32, data: &[u8]) -> u32 {
     let align = data.len() & 0b11;
     if align == 0 {
-        crc32_mpeg2(crc, data)
+        crc32_mpeg2_fast(crc, data)
     } else {
         const PAD: [u8; 4] = [0u8; 4];
-        crc32_mpeg2(crc32_mpeg2(crc, data), &PAD[..4usize - align])
+        crc32_mpeg2_fast(crc32_mpeg2_fast(crc, data), &PAD[..4usize - align])
     }
 }
 
@@ -85,7 +249,7 @@ impl Crc32 for SoftwareCrc32 {
 
     fn update(&mut self, data: &[u32]) -> u32 {
         for word in data {
-            self.value = crc32_mpeg2(self.value, &word.to_le_bytes());
+            self.value = crc32_mpeg2_fast(self.value, &word.to_le_bytes());
         }
         self.value
     }
@@ -95,3 +259,47 @@ impl Crc32 for SoftwareCrc32 {
         self.value
     }
 }
+
+#[cfg(test)]
+const TEST_DATA: &[u8] =
+    b"The quick brown fox jumps over the lazy dog, and then does it again for good measure!";
+
+#[test]
+fn test_crc32_mpeg2_check() {
+    // catalogue check value for CRC-32/MPEG-2
+    assert_eq!(crc32_mpeg2(0xffff_ffff, b"123456789"), 0x0376E6E7);
+    assert_eq!(crc32_mpeg2_slice8(0xffff_ffff, b"123456789"), 0x0376E6E7);
+    assert_eq!(crc32_mpeg2_fast(0xffff_ffff, b"123456789"), 0x0376E6E7);
+}
+
+#[test]
+fn test_crc32_mpeg2_impls_match() {
+    // every length and alignment up to a few slices, from a few starting values
+    for init in [0, 0xffff_ffff, 0x1234_5678] {
+        for start in 0..8 {
+            for end in start..TEST_DATA.len() {
+                let data = &TEST_DATA[start..end];
+                let expected = crc32_mpeg2(init, data);
+                assert_eq!(
+                    crc32_mpeg2_slice8(init, data),
+                    expected,
+                    "slice8 {start}..{end}"
+                );
+                assert_eq!(
+                    crc32_mpeg2_fast(init, data),
+                    expected,
+                    "fast {start}..{end}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_crc32_mpeg2_chained() {
+    let (a, b) = TEST_DATA.split_at(13);
+    assert_eq!(
+        crc32_mpeg2_fast(crc32_mpeg2_fast(0xffff_ffff, a), b),
+        crc32_mpeg2(0xffff_ffff, TEST_DATA)
+    );
+}
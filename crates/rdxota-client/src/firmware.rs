@@ -0,0 +1,236 @@
+//! Checking firmware files before uploading them.
+//!
+//! RdxOTA v2 firmware files start with a fixed-size header that the device checks before it takes
+//! any data: magic, file format, which product the image is for, its version and the ECIES key
+//! signature. A device that rejects the header only says so after the upload has started, with a
+//! [`Nack`](rdxota_protocol::otav2::Nack) like `HeaderProductMismatch`. [`FirmwareImage`] reads the
+//! same header on the host, so a wrong file can be turned away before any bus traffic, and the
+//! target product and version reported up front.
+//!
+//! All fields are little-endian:
+//!
+//! | Offset | Size | Field                                              |
+//! |--------|------|----------------------------------------------------|
+//! | 0      | 4    | magic, `RDXF`                                      |
+//! | 4      | 1    | file format, [`OTA_VERSION`]                       |
+//! | 5      | 1    | product id, as in serial numers                    |
+//! | 6      | 1    | flags; bit 0 is set on signed images               |
+//! | 7      | 1    | reserved                                           |
+//! | 8      | 2    | firmware year                                      |
+//! | 10     | 1    | firmware minor                                     |
+//! | 11     | 1    | firmware patch                                     |
+//! | 12     | 4    | length of the image after the header               |
+//! | 16     | 64   | ECIES key signature, zeroed on unsigned images     |
+//! | 80     | 48   | header HMAC and reserved space, checked on-device  |
+//!
+//! Only the device can check the signature and HMAC themselves; the host only checks that there is
+//! a signature.
+//!
+//! [`OTA_VERSION`]: rdxota_protocol::otav2::index::OTA_VERSION
+
+use rdxota_protocol::otav2::index::OTA_VERSION;
+use serial_numer::ProductId;
+
+/// Magic every RdxOTA v2 firmware file starts with.
+pub const FIRMWARE_MAGIC: [u8; 4] = *b"RDXF";
+/// Size of the header in front of the image.
+pub const HEADER_LEN: usize = 128;
+
+const FLAG_SIGNED: u8 = 1 << 0;
+const SIGNATURE: core::ops::Range<usize> = 16..80;
+
+/// Version of the firmware in an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    pub year: u16,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl core::fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "v{}.{}.{}", self.year, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirmwareError {
+    /// The file is shorter than a header
+    TooShort(usize),
+    /// The file doesn't start with [`FIRMWARE_MAGIC`]
+    BadMagic,
+    /// The file is in a format this client doesn't upload
+    UnsupportedFormat(u8),
+    /// The file is missing part of its image, or has trailing data
+    LengthMismatch { expected: usize, actual: usize },
+    /// The image isn't signed, so no device will take it
+    Unsigned,
+    /// The image is for another product than the device
+    ProductMismatch { image: ProductId, target: ProductId },
+}
+
+impl core::fmt::Display for FirmwareError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FirmwareError::TooShort(len) => {
+                write!(f, "File is {len} bytes, too short for a firmware header")
+            }
+            FirmwareError::BadMagic => write!(f, "Not an RdxOTA firmware file"),
+            FirmwareError::UnsupportedFormat(v) => {
+                write!(f, "Firmware file uses unsupported RdxOTA version {v}")
+            }
+            FirmwareError::LengthMismatch { expected, actual } => write!(
+                f,
+                "Firmware image should be {expected} bytes, but the file has {actual}"
+            ),
+            FirmwareError::Unsigned => write!(f, "Firmware image is not signed"),
+            FirmwareError::ProductMismatch { image, target } => write!(
+                f,
+                "Firmware file is for {image:?}, but the device is {target:?}"
+            ),
+        }
+    }
+}
+impl core::error::Error for FirmwareError {}
+
+/// A firmware file whose header checked out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareImage<'a> {
+    product: ProductId,
+    version: FirmwareVersion,
+    signed: bool,
+    file: &'a [u8],
+}
+
+impl<'a> FirmwareImage<'a> {
+    /// Reads the header of `file`, the whole firmware file as it's uploaded.
+    pub fn parse(file: &'a [u8]) -> Result<Self, FirmwareError> {
+        let Some(header) = file.first_chunk::<HEADER_LEN>() else {
+            return Err(FirmwareError::TooShort(file.len()));
+        };
+        if header[0..4] != FIRMWARE_MAGIC {
+            return Err(FirmwareError::BadMagic);
+        }
+        if header[4] != OTA_VERSION {
+            return Err(FirmwareError::UnsupportedFormat(header[4]));
+        }
+        let expected = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+        let actual = file.len() - HEADER_LEN;
+        if expected != actual {
+            return Err(FirmwareError::LengthMismatch { expected, actual });
+        }
+        Ok(Self {
+            product: ProductId::from(header[5]),
+            version: FirmwareVersion {
+                year: u16::from_le_bytes([header[8], header[9]]),
+                minor: header[10],
+                patch: header[11],
+            },
+            signed: header[6] & FLAG_SIGNED != 0 && header[SIGNATURE].iter().any(|b| *b != 0),
+            file,
+        })
+    }
+
+    /// Checks that a device of `product` would take the image.
+    pub fn validate_for(&self, product: ProductId) -> Result<(), FirmwareError> {
+        if !self.signed {
+            return Err(FirmwareError::Unsigned);
+        }
+        if self.product != product {
+            return Err(FirmwareError::ProductMismatch {
+                image: self.product,
+                target: product,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn product(&self) -> ProductId {
+        self.product
+    }
+
+    pub fn version(&self) -> FirmwareVersion {
+        self.version
+    }
+
+    /// Whether the header carries a key signature; the device checks that it's valid.
+    pub fn signed(&self) -> bool {
+        self.signed
+    }
+
+    /// The whole file, header included, as it's passed to [`RdxOtaClient::new`].
+    ///
+    /// [`RdxOtaClient::new`]: crate::RdxOtaClient::new
+    pub fn file(&self) -> &'a [u8] {
+        self.file
+    }
+
+    /// The image after the header.
+    pub fn image(&self) -> &'a [u8] {
+        &self.file[HEADER_LEN..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn file(product: u8, flags: u8, image_len: usize) -> [u8; HEADER_LEN + 16] {
+        let mut file = [0u8; HEADER_LEN + 16];
+        file[0..4].copy_from_slice(&FIRMWARE_MAGIC);
+        file[4] = OTA_VERSION;
+        file[5] = product;
+        file[6] = flags;
+        file[8..12].copy_from_slice(&[0xe9, 0x07, 1, 2]);
+        file[12..16].copy_from_slice(&(image_len as u32).to_le_bytes());
+        file[SIGNATURE].fill(0xa5);
+        file
+    }
+
+    #[test]
+    fn test_parse() {
+        let gyro = file(ProductId::Gyro.into(), FLAG_SIGNED, 16);
+        let image = FirmwareImage::parse(&gyro).unwrap();
+        assert_eq!(
+            image.version(),
+            FirmwareVersion {
+                year: 2025,
+                minor: 1,
+                patch: 2
+            }
+        );
+        assert_eq!(image.image().len(), 16);
+        assert_eq!(image.validate_for(ProductId::Gyro), Ok(()));
+        assert_eq!(
+            image.validate_for(ProductId::Sandworm),
+            Err(FirmwareError::ProductMismatch {
+                image: ProductId::Gyro,
+                target: ProductId::Sandworm
+            })
+        );
+
+        let unsigned = file(ProductId::Gyro.into(), 0, 16);
+        let image = FirmwareImage::parse(&unsigned).unwrap();
+        assert_eq!(
+            image.validate_for(ProductId::Gyro),
+            Err(FirmwareError::Unsigned)
+        );
+
+        let truncated = file(ProductId::Gyro.into(), FLAG_SIGNED, 32);
+        assert_eq!(
+            FirmwareImage::parse(&truncated),
+            Err(FirmwareError::LengthMismatch {
+                expected: 32,
+                actual: 16
+            })
+        );
+        assert_eq!(
+            FirmwareImage::parse(&gyro[1..]),
+            Err(FirmwareError::BadMagic)
+        );
+        assert_eq!(
+            FirmwareImage::parse(&gyro[..HEADER_LEN - 1]),
+            Err(FirmwareError::TooShort(HEADER_LEN - 1))
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Flashing several devices on one bus at once.
+//!
+//! A single [`RdxOtaClient`] spends much of its time waiting on its device: for each chunk to be
+//! verified and committed to flash, and for the device to reboot into DFU mode. [`Fleet::run`]
+//! drives a set of clients concurrently on whatever executor awaits it, so those waits are filled
+//! with other devices' chunks.
+//!
+//! Clients that talk through a [`FleetIO`] share the bus under the fleet's [`FleetLimits`]:
+//!
+//! * at most [`FleetLimits::max_streaming`] devices stream chunk data at a time. A device takes a
+//!   streaming slot when it sends the first packet of a chunk and gives it back when it asks for
+//!   the chunk to be verified, so devices waiting on a slot are held back only at chunk boundaries.
+//!   Each device's own flow control is still its chunk handshake, which isn't held back.
+//! * chunk data is paced so the streaming devices together stay under
+//!   [`FleetLimits::max_bytes_per_sec`], leaving the rest of the bus for control traffic and
+//!   whatever else is on it
+//!
+//! [`Fleet::progress`] totals the devices' progress, and can be read from another task while the
+//! fleet runs.
+
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::Poll,
+    time::Duration,
+};
+
+use crate::{ControlMessage, RdxOtaClient, RdxOtaClientError, RdxOtaClientIO, RdxOtaIOError};
+
+/// How often a device waiting for a streaming slot checks for one.
+const SLOT_POLL: Duration = Duration::from_millis(1);
+
+/// How a [`Fleet`] shares the bus between its devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FleetLimits {
+    /// Devices that may stream chunk data at once
+    pub max_streaming: usize,
+    /// Chunk data across every streaming device, in bytes per second. 0 doesn't pace it.
+    pub max_bytes_per_sec: u32,
+}
+
+impl Default for FleetLimits {
+    fn default() -> Self {
+        // chunk data on a classic CAN bus is 8 bytes a frame, so this is about half a 1 Mbit/s bus
+        Self {
+            max_streaming: 4,
+            max_bytes_per_sec: 32_000,
+        }
+    }
+}
+
+/// Progress of a whole fleet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FleetProgress {
+    /// Devices being flashed, including finished ones
+    pub devices: usize,
+    pub finished: usize,
+    pub failed: usize,
+    /// Devices streaming chunk data right now
+    pub streaming: usize,
+    /// Firmware bytes committed, across every device
+    pub written: usize,
+    /// Firmware bytes to write, across every device
+    pub total: usize,
+}
+
+impl FleetProgress {
+    pub fn pct_progress(&self) -> f32 {
+        match self.total {
+            0 => 0.0,
+            total => self.written as f32 * 100.0 / total as f32,
+        }
+    }
+}
+
+/// Shared state of devices being flashed together.
+#[derive(Debug, Default)]
+pub struct Fleet {
+    limits: FleetLimits,
+    devices: AtomicUsize,
+    finished: AtomicUsize,
+    failed: AtomicUsize,
+    streaming: AtomicUsize,
+    written: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl Fleet {
+    pub fn new(limits: FleetLimits) -> Self {
+        Self {
+            limits: FleetLimits {
+                max_streaming: limits.max_streaming.max(1),
+                ..limits
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn limits(&self) -> FleetLimits {
+        self.limits
+    }
+
+    /// Wraps a device's IO so its client shares the bus with the rest of the fleet.
+    pub fn io<IO: RdxOtaClientIO>(&self, io: IO) -> FleetIO<'_, IO> {
+        FleetIO {
+            fleet: self,
+            io,
+            streaming: false,
+            paced_until: 0.0,
+            reported: 0,
+        }
+    }
+
+    pub fn progress(&self) -> FleetProgress {
+        FleetProgress {
+            devices: self.devices.load(Ordering::Relaxed),
+            finished: self.finished.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            streaming: self.streaming.load(Ordering::Relaxed),
+            written: self.written.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Flashes every client concurrently, returning how each went in the same order.
+    ///
+    /// One client failing doesn't stop the others. Only clients whose IO is one of this fleet's
+    /// [`FleetIO`]s are held to its limits.
+    pub async fn run<IO: RdxOtaClientIO, const N: usize>(
+        &self,
+        clients: &mut [RdxOtaClient<'_, '_, IO>; N],
+    ) -> [Result<(), RdxOtaClientError>; N] {
+        let total: usize = clients.iter().map(|client| client.payload.len()).sum();
+        self.total.fetch_add(total, Ordering::Relaxed);
+        self.devices.fetch_add(N, Ordering::Relaxed);
+
+        let mut runs = clients.each_mut().map(|client| Some(client.run()));
+        let mut results = [None; N];
+        core::future::poll_fn(|cx| {
+            let mut pending = false;
+            for (run, result) in runs.iter_mut().zip(results.iter_mut()) {
+                let Some(future) = run.as_mut() else {
+                    continue;
+                };
+                // SAFETY: `runs` is never moved once polling starts, as the closure only borrows
+                // it, and each future is dropped in place once it's done.
+                match unsafe { Pin::new_unchecked(future) }.poll(cx) {
+                    Poll::Ready(outcome) => {
+                        let counter = match outcome {
+                            Ok(()) => &self.finished,
+                            Err(_) => &self.failed,
+                        };
+                        counter.fetch_add(1, Ordering::Relaxed);
+                        *result = Some(outcome);
+                        *run = None;
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+            match pending {
+                true => Poll::Pending,
+                false => Poll::Ready(()),
+            }
+        })
+        .await;
+        results.map(|result| result.expect("every client ran to completion"))
+    }
+}
+
+/// A device's IO, sharing the bus with the rest of its [`Fleet`].
+pub struct FleetIO<'f, IO: RdxOtaClientIO> {
+    fleet: &'f Fleet,
+    io: IO,
+    /// Holds one of the fleet's streaming slots
+    streaming: bool,
+    /// When, on `io`'s clock, the chunk data sent so far is paid for
+    paced_until: f32,
+    /// Bytes written already added to the fleet's total
+    reported: usize,
+}
+
+impl<IO: RdxOtaClientIO> FleetIO<'_, IO> {
+    pub fn inner(&self) -> &IO {
+        &self.io
+    }
+
+    fn release_slot(&mut self) {
+        if core::mem::take(&mut self.streaming) {
+            self.fleet.streaming.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    async fn acquire_slot(&mut self) -> Result<(), RdxOtaIOError> {
+        let max = self.fleet.limits.max_streaming;
+        while !self.streaming {
+            self.streaming = self
+                .fleet
+                .streaming
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                    (n < max).then_some(n + 1)
+                })
+                .is_ok();
+            if !self.streaming {
+                self.io.sleep(SLOT_POLL).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits until `len` more bytes fit under this device's share of the fleet's data rate.
+    async fn pace(&mut self, len: usize) -> Result<(), RdxOtaIOError> {
+        let limits = self.fleet.limits;
+        if limits.max_bytes_per_sec == 0 {
+            return Ok(());
+        }
+        // each streaming device gets an even share, so together they can't go over
+        let share = limits.max_bytes_per_sec as f32 / limits.max_streaming as f32;
+        let now = self.io.now_secs();
+        let start = self.paced_until.max(now);
+        self.paced_until = start + len as f32 / share;
+        if start > now {
+            self.io.sleep(Duration::from_secs_f32(start - now)).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<IO: RdxOtaClientIO> Drop for FleetIO<'_, IO> {
+    fn drop(&mut self) {
+        // a client that fails mid-chunk still gives its slot back
+        self.release_slot();
+    }
+}
+
+impl<IO: RdxOtaClientIO> RdxOtaClientIO for FleetIO<'_, IO> {
+    async fn send(
+        &mut self,
+        id: u32,
+        msg: ControlMessage,
+        timeout: Duration,
+    ) -> Result<(), RdxOtaIOError> {
+        // control messages end a chunk's data
+        self.release_slot();
+        self.io.send(id, msg, timeout).await
+    }
+
+    async fn send_data(
+        &mut self,
+        id: u32,
+        msg: &[u8],
+        timeout: Duration,
+    ) -> Result<(), RdxOtaIOError> {
+        self.acquire_slot().await?;
+        self.pace(msg.len()).await?;
+        self.io.send_data(id, msg, timeout).await
+    }
+
+    async fn recv(&mut self, timeout: Duration) -> Result<ControlMessage, RdxOtaIOError> {
+        self.io.recv(timeout).await
+    }
+
+    async fn sleep(&mut self, timeout: Duration) -> Result<(), RdxOtaIOError> {
+        self.io.sleep(timeout).await
+    }
+
+    fn reset(&mut self) {
+        self.io.reset();
+    }
+
+    async fn update_progress(&mut self, written: usize, pct_progress: f32, speed: f32) {
+        let new = written.saturating_sub(self.reported);
+        self.reported = self.reported.max(written);
+        self.fleet.written.fetch_add(new, Ordering::Relaxed);
+        self.io.update_progress(written, pct_progress, speed).await;
+    }
+
+    fn now_secs(&self) -> f32 {
+        self.io.now_secs()
+    }
+
+    fn transport_size(&self) -> usize {
+        self.io.transport_size()
+    }
+}
@@ -4,6 +4,8 @@
 use core::{future::Future, time::Duration};
 use rdxota_protocol::*;
 
+pub mod firmware;
+pub mod fleet;
 mod v1;
 mod v2;
 
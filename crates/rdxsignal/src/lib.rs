@@ -0,0 +1,212 @@
+//! Host-side processing of sampled device signals.
+//!
+//! Dashboards plotting something like encoder velocity each tend to grow their own smoothing, with
+//! slightly different cutoffs and startup behavior. A [`SignalProcessor`] does it once, the same
+//! way everywhere it's used: a first-order low-pass filter, the rate of change, and the minimum and
+//! maximum over a trailing window, all driven by each sample's own timestamp so uneven frame
+//! periods and dropped frames don't skew them.
+//!
+//! ```
+//! use rdxsignal::{SignalConfig, SignalProcessor};
+//!
+//! let mut velocity = SignalProcessor::new(SignalConfig {
+//!     lowpass_hz: Some(5.0),
+//!     derivative: true,
+//!     window_secs: Some(1.0),
+//! });
+//! let sample = velocity.push(0.02, 1.5);
+//! assert_eq!(sample.max, Some(1.5));
+//! ```
+#![no_std]
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+
+/// What to compute for a signal. The default computes nothing but passes the value through.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SignalConfig {
+    /// Cutoff of the low-pass filter, in Hz
+    pub lowpass_hz: Option<f64>,
+    /// Rate of change per second, of the filtered value if there is one
+    pub derivative: bool,
+    /// Trailing window the minimum and maximum are taken over, in seconds
+    pub window_secs: Option<f64>,
+}
+
+impl SignalConfig {
+    /// Whether this computes anything beyond the value itself.
+    pub fn is_passthrough(&self) -> bool {
+        self.lowpass_hz.is_none() && !self.derivative && self.window_secs.is_none()
+    }
+}
+
+/// A sample and what was computed from it. Fields the [`SignalConfig`] didn't ask for are `None`,
+/// as is the derivative of the first sample.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ProcessedSample {
+    /// Timestamp, in seconds
+    pub t: f64,
+    pub value: f64,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub filtered: Option<f64>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub derivative: Option<f64>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub min: Option<f64>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub max: Option<f64>,
+}
+
+/// Running state of one signal.
+#[derive(Debug, Clone, Default)]
+pub struct SignalProcessor {
+    config: SignalConfig,
+    /// Timestamp and filtered value of the last sample
+    last: Option<(f64, f64)>,
+    /// Candidates for the window's minimum, increasing in both time and value
+    mins: VecDeque<(f64, f64)>,
+    /// Candidates for the window's maximum, increasing in time and decreasing in value
+    maxes: VecDeque<(f64, f64)>,
+}
+
+impl SignalProcessor {
+    pub fn new(config: SignalConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> SignalConfig {
+        self.config
+    }
+
+    /// Forgets every sample so far, as if the signal had just started.
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.mins.clear();
+        self.maxes.clear();
+    }
+
+    /// Takes in `value`, sampled at `t` seconds. A sample older than the last one, as when a
+    /// device reboots or a log starts over, resets the processor first.
+    pub fn push(&mut self, t: f64, value: f64) -> ProcessedSample {
+        if self.last.is_some_and(|(last_t, _)| t < last_t) {
+            self.reset();
+        }
+        let filtered = match (self.config.lowpass_hz, self.last) {
+            (Some(cutoff), Some((last_t, last))) if cutoff > 0.0 => {
+                let dt = t - last_t;
+                let tau = 1.0 / (2.0 * core::f64::consts::PI * cutoff);
+                last + (value - last) * dt / (tau + dt)
+            }
+            _ => value,
+        };
+        let derivative = match self.last {
+            Some((last_t, last)) if self.config.derivative && t > last_t => {
+                Some((filtered - last) / (t - last_t))
+            }
+            _ => None,
+        };
+        self.last = Some((t, filtered));
+
+        let (min, max) = match self.config.window_secs {
+            Some(window) => {
+                let expired = |(sample_t, _): &(f64, f64)| t - *sample_t > window;
+                while self.mins.back().is_some_and(|(_, v)| *v >= value) {
+                    self.mins.pop_back();
+                }
+                self.mins.push_back((t, value));
+                while self.mins.front().is_some_and(expired) {
+                    self.mins.pop_front();
+                }
+                while self.maxes.back().is_some_and(|(_, v)| *v <= value) {
+                    self.maxes.pop_back();
+                }
+                self.maxes.push_back((t, value));
+                while self.maxes.front().is_some_and(expired) {
+                    self.maxes.pop_front();
+                }
+                (
+                    self.mins.front().map(|(_, v)| *v),
+                    self.maxes.front().map(|(_, v)| *v),
+                )
+            }
+            None => (None, None),
+        };
+
+        ProcessedSample {
+            t,
+            value,
+            filtered: self.config.lowpass_hz.map(|_| filtered),
+            derivative,
+            min,
+            max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lowpass() {
+        let mut signal = SignalProcessor::new(SignalConfig {
+            lowpass_hz: Some(1.0),
+            ..Default::default()
+        });
+        assert_eq!(signal.push(0.0, 0.0).filtered, Some(0.0));
+        // a step settles towards the new value without overshooting it
+        let mut last = 0.0;
+        for i in 1..=500 {
+            let filtered = signal.push(i as f64 * 0.01, 1.0).filtered.unwrap();
+            assert!(filtered > last && filtered < 1.0);
+            last = filtered;
+        }
+        assert!(last > 0.99);
+        // one time constant in, a first-order filter is about 63% of the way there
+        signal.reset();
+        signal.push(0.0, 0.0);
+        let tau = 1.0 / (2.0 * core::f64::consts::PI);
+        let mut filtered = 0.0;
+        for i in 1..=1000 {
+            filtered = signal.push(i as f64 * tau / 1000.0, 1.0).filtered.unwrap();
+        }
+        assert!((filtered - 0.632).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_derivative() {
+        let mut signal = SignalProcessor::new(SignalConfig {
+            derivative: true,
+            ..Default::default()
+        });
+        assert_eq!(signal.push(1.0, 10.0).derivative, None);
+        assert_eq!(signal.push(1.5, 12.0).derivative, Some(4.0));
+        // uneven sample spacing still gives the rate per second
+        let derivative = signal.push(1.6, 11.0).derivative.unwrap();
+        assert!((derivative + 10.0).abs() < 1e-9);
+        // time going backwards starts over
+        assert_eq!(signal.push(0.0, 0.0).derivative, None);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut signal = SignalProcessor::new(SignalConfig {
+            window_secs: Some(1.0),
+            ..Default::default()
+        });
+        let extremes = |s: ProcessedSample| (s.min.unwrap(), s.max.unwrap());
+        assert_eq!(extremes(signal.push(0.0, 5.0)), (5.0, 5.0));
+        assert_eq!(extremes(signal.push(0.5, 1.0)), (1.0, 5.0));
+        assert_eq!(extremes(signal.push(1.0, 3.0)), (1.0, 5.0));
+        // the 5 has left the window, then the 1
+        assert_eq!(extremes(signal.push(1.2, 2.0)), (1.0, 3.0));
+        assert_eq!(extremes(signal.push(1.6, 4.0)), (2.0, 4.0));
+    }
+}
@@ -20,6 +20,27 @@ pub const MESSAGE_ARB_ID_RTR: u32 = 0x40000000;
 /// regardless of any configured device id bits.
 pub const MESSAGE_ARB_ID_DEVICE: u32 = 0x20000000;
 
+/// Channel that carries the device's debug console instead of CAN traffic.
+///
+/// Packets on this channel hold console bytes in `data[..data_size]`, with a `message_id` of zero.
+/// Device to host is the device's log output; host to device is console input.
+/// Only devices that set [`DEVICE_CAP_CONSOLE`] use it.
+pub const CONSOLE_CHANNEL: u16 = 0x8000;
+
+/// [`RdxUsbDeviceInfo::capabilities`] bit: the device exposes a console on [`CONSOLE_CHANNEL`].
+pub const DEVICE_CAP_CONSOLE: u8 = 1 << 0;
+/// [`RdxUsbDeviceInfo::capabilities`] bit: the device acknowledges transmitted frames with
+/// [`PACKET_FLAG_TX_ACK`].
+pub const DEVICE_CAP_TX_ACK: u8 = 1 << 1;
+
+/// [`RdxUsbPacket::flags`] bit for transmit acknowledgements.
+///
+/// On a host to device packet, asks the device to acknowledge the frame once it is on the bus.
+/// The device then sends the same packet back with this bit set and `timestamp_ns` set to when
+/// the frame went out. Acknowledgements are not received traffic.
+/// Only devices that set [`DEVICE_CAP_TX_ACK`] honor it.
+pub const PACKET_FLAG_TX_ACK: u8 = 1 << 0;
+
 /// Generic data packet passed to/from RdxUsb APIs.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Pod, Zeroable)]
 #[repr(C, packed)]
@@ -28,8 +49,8 @@ pub struct RdxUsbPacket {
     pub message_id: u32,
     /// Relevant channel. Zero most of the time.
     pub channel: u16,
-    /// Padding byte
-    pub reserved: u8,
+    /// Packet flags, e.g. [`PACKET_FLAG_TX_ACK`]. Zero before protocol version 2.2.
+    pub flags: u8,
     /// Valid data size in bytes.
     pub data_size: u8,
     /// Timestamp since boot (nanoseconds)
@@ -49,7 +70,7 @@ impl RdxUsbPacket {
         Self {
             message_id,
             channel,
-            reserved: 0,
+            flags: 0,
             data_size: if data_size <= 64 { data_size } else { 64 },
             timestamp_ns,
             data,
@@ -103,6 +124,11 @@ impl RdxUsbPacket {
         self.message_id & MESSAGE_ARB_ID_DEVICE != 0
     }
 
+    /// Is the packet a transmit acknowledgement (or, to the device, a request for one)?
+    pub const fn tx_ack(&self) -> bool {
+        self.flags & PACKET_FLAG_TX_ACK != 0
+    }
+
     /// Should always be 80.
     pub const SIZE: usize = core::mem::size_of::<Self>();
 }
@@ -121,8 +147,10 @@ pub struct RdxUsbDeviceInfo {
     pub protocol_version_major: u16,
     /// The minor protocol version
     pub protocol_version_minor: u16,
+    /// Optional features, e.g. [`DEVICE_CAP_CONSOLE`]. Zero before protocol version 2.1.
+    pub capabilities: u8,
     /// Reserved bits
-    pub reserved: [u8; 24],
+    pub reserved: [u8; 23],
 }
 
 impl RdxUsbDeviceInfo {
@@ -136,6 +164,16 @@ impl RdxUsbDeviceInfo {
     pub fn from_buf(buf: [u8; Self::SIZE]) -> Self {
         bytemuck::cast(buf)
     }
+
+    /// Does the device expose a console on [`CONSOLE_CHANNEL`]?
+    pub const fn has_console(&self) -> bool {
+        self.capabilities & DEVICE_CAP_CONSOLE != 0
+    }
+
+    /// Does the device acknowledge transmitted frames?
+    pub const fn has_tx_ack(&self) -> bool {
+        self.capabilities & DEVICE_CAP_TX_ACK != 0
+    }
 }
 
 /// Control requests supported
@@ -143,7 +181,30 @@ impl RdxUsbDeviceInfo {
 #[repr(u8)]
 pub enum RdxUsbCtrl {
     DeviceInfo = 0,
+    /// Host to device OTA packet, sent to the DFU interface of a bootloader in DFU mode.
+    ///
+    /// The data stage is an [`RdxUsbPacket`] cut to its [`RdxUsbPacket::wire_length`], holding an
+    /// RdxOTA message as it would appear on CAN.
+    OtaOut = 1,
+    /// Device to host OTA packet, read from the DFU interface of a bootloader in DFU mode.
+    ///
+    /// Returns the next pending [`RdxUsbPacket`], or nothing if the bootloader has nothing to send.
+    OtaIn = 2,
 }
 
+/// Interface class of a USB DFU interface
+pub const DFU_INTERFACE_CLASS: u8 = 0xfe;
+/// Interface subclass of a USB DFU interface
+pub const DFU_INTERFACE_SUBCLASS: u8 = 0x01;
+/// DFU interface protocol while running firmware that can be switched into DFU mode
+pub const DFU_PROTOCOL_RUNTIME: u8 = 0x01;
+/// DFU interface protocol while in DFU mode. Redux bootloaders take RdxOTA over
+/// [`RdxUsbCtrl::OtaOut`] and [`RdxUsbCtrl::OtaIn`] on this interface.
+pub const DFU_PROTOCOL_DFU_MODE: u8 = 0x02;
+
 /// USB protocol version 2
 pub const PROTOCOL_VERSION_MAJOR_FS: u16 = 2;
+/// Minor version that added [`RdxUsbDeviceInfo::capabilities`] and the console channel
+pub const PROTOCOL_VERSION_MINOR_CONSOLE: u16 = 1;
+/// Minor version that added [`RdxUsbPacket::flags`] and transmit acknowledgements
+pub const PROTOCOL_VERSION_MINOR_TX_ACK: u16 = 2;
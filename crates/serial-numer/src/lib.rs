@@ -176,19 +176,28 @@ impl SerialNumer {
             a + b'0'
         } else {
             const DIFF: u8 = b'A' - 0xa;
-            unsafe { a.unchecked_add(DIFF) }
+            #[cfg(feature = "paranoid")]
+            {
+                assert!(a <= 0xf, "{a:#x} isn't a nibble");
+                a + DIFF
+            }
+            #[cfg(not(feature = "paranoid"))]
+            unsafe {
+                a.unchecked_add(DIFF)
+            }
         }
     }
 
     pub fn to_hex_str<'a>(&self, out_buf: &'a mut [u8; 12]) -> &'a str {
         for i in 0..5usize {
-            unsafe {
-                let v = *self.0.get_unchecked(i);
-                out_buf[i << 1] = Self::to_bcx(v >> 4);
-                out_buf[(i << 1) + 1] = Self::to_bcx(v & 0xf);
-            }
+            #[cfg(feature = "paranoid")]
+            let v = self.0[i];
+            #[cfg(not(feature = "paranoid"))]
+            let v = unsafe { *self.0.get_unchecked(i) };
+            out_buf[i << 1] = Self::to_bcx(v >> 4);
+            out_buf[(i << 1) + 1] = Self::to_bcx(v & 0xf);
         }
-        unsafe { core::str::from_utf8_unchecked(out_buf) }
+        Self::ascii_str(out_buf)
     }
 
     /// Creates serial numers of the form
@@ -227,7 +236,19 @@ impl SerialNumer {
         out_buf[15] = b'-';
         out_buf[16] = Self::to_bcx(self.0[0] >> 4); // crc
 
-        unsafe { core::str::from_utf8_unchecked(out_buf) }
+        Self::ascii_str(out_buf)
+    }
+
+    /// `buf` as a `str`, which it is when every byte came from [`Self::to_bcx`] or is ASCII.
+    fn ascii_str(buf: &[u8]) -> &str {
+        #[cfg(feature = "paranoid")]
+        {
+            core::str::from_utf8(buf).expect("serial numer string isn't ASCII")
+        }
+        #[cfg(not(feature = "paranoid"))]
+        unsafe {
+            core::str::from_utf8_unchecked(buf)
+        }
     }
 
     /// Converts ASCII binary-coded-hexadecimal to a value.
@@ -3,6 +3,9 @@ fn main() {
     if target.contains("linux") {
         // SONAME is necessary for wpilib to function
         println!("cargo:rustc-link-arg=-Wl,-soname,libreduxfifo.so");
+        // HAL symbols are only resolved when first called, and fifocore checks that they exist
+        // before calling any of them, so the library still loads on desktops without the HAL
+        println!("cargo:rustc-link-arg=-Wl,-z,lazy");
     }
 
     build_data::set_GIT_COMMIT_SHORT().unwrap();
@@ -1,8 +1,9 @@
-use axum::response::IntoResponse;
 use axum::response::Json;
-use fifocore::{FIFOCore, error::Error};
+use fifocore::{FIFOCore, backends::MessageBackend};
 use serde::Serialize;
 
+use crate::problem::ApiError;
+
 #[derive(Debug, Serialize)]
 pub struct ListBuses {
     pub buses: Vec<BusEntry>,
@@ -14,17 +15,35 @@ pub struct ListBuses {
 pub struct BusEntry {
     pub id: u16,
     pub params: String,
+    pub alias: Option<String>,
     pub id_cache: fifocore::backends::IdCache,
+    /// Where received message timestamps on this bus come from
+    pub timestamps: fifocore::timestamp::TimestampStatus,
+    /// Estimated share of the bus's bandwidth in use
+    pub utilization: fifocore::utilization::UtilizationStatus,
+    /// Controller error state and overruns
+    pub status: fifocore::bus_status::BusStatus,
+    /// The hardware the bus is on
+    pub info: fifocore::bus_info::BackendInfo,
+    /// Labels of the sessions open on the bus, for telling apart who's writing to it
+    pub session_labels: Vec<String>,
 }
 
 pub fn handle_list_bus(cdn: &FIFOCore) -> ListBuses {
+    let aliases = cdn.bus_aliases();
     cdn.with_buses(|buses| ListBuses {
         buses: buses
             .iter()
             .map(|(&id, ent)| BusEntry {
                 id,
                 params: ent.params().to_string(),
+                alias: aliases.alias_for(ent.params()).map(str::to_string),
                 id_cache: ent.id_cache(),
+                timestamps: ent.timestamps(),
+                utilization: ent.utilization(),
+                status: ent.status(),
+                info: ent.info().backend,
+                session_labels: session_labels(ent.as_ref()),
             })
             .collect(),
         time_now: fifocore::timebase::now_us(),
@@ -32,34 +51,73 @@ pub fn handle_list_bus(cdn: &FIFOCore) -> ListBuses {
     })
 }
 
+fn session_labels(bus: &dyn MessageBackend) -> Vec<String> {
+    let mut labels: Vec<String> = bus
+        .sessions()
+        .into_iter()
+        .filter_map(|ses| bus.session_tag(ses).ok()?.label())
+        .map(|label| label.to_string())
+        .collect();
+    labels.sort();
+    labels.dedup();
+    labels
+}
+
 #[derive(Debug, Serialize)]
 pub struct BusOpenSuccess {
     pub id: u16,
     pub params: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct FIFOCoreError {
-    pub error_id: i32,
-    pub reason: String,
+pub fn handle_open_bus(
+    fifocore: &FIFOCore,
+    bus_name: &str,
+) -> Result<Json<BusOpenSuccess>, ApiError> {
+    fifocore.validate_bus(bus_name)?;
+    let id = fifocore
+        .open_or_get_bus(bus_name)
+        .map_err(|e| ApiError::fifocore(e, format!("Couldn't open bus {bus_name}")))?;
+    Ok(Json(BusOpenSuccess {
+        id,
+        params: bus_name.to_owned(),
+    }))
 }
 
-impl From<Error> for FIFOCoreError {
-    fn from(value: Error) -> Self {
-        Self {
-            error_id: value as i32,
-            reason: value.message().to_owned(),
-        }
-    }
+#[derive(Debug, Serialize)]
+pub struct BusAliasEntry {
+    pub alias: String,
+    pub params: String,
+    /// Id of the bus if it is open
+    pub id: Option<u16>,
 }
 
-pub fn handle_open_bus(fifocore: &FIFOCore, bus_name: &str) -> axum::response::Response {
-    match fifocore.open_or_get_bus(&bus_name) {
-        Ok(id) => Json(BusOpenSuccess {
-            id,
-            params: bus_name.to_owned(),
+pub fn handle_list_aliases(fifocore: &FIFOCore) -> Vec<BusAliasEntry> {
+    fifocore
+        .bus_aliases()
+        .list()
+        .into_iter()
+        .map(|(alias, params)| BusAliasEntry {
+            id: fifocore.bus_matching_params(&params),
+            alias,
+            params,
         })
-        .into_response(),
-        Err(e) => Json(FIFOCoreError::from(e)).into_response(),
-    }
+        .collect()
+}
+
+pub fn handle_set_alias(
+    fifocore: &FIFOCore,
+    alias: &str,
+    params: &str,
+) -> Result<Json<BusAliasEntry>, ApiError> {
+    fifocore::bus_uri::BusUri::from_params(params)?;
+    fifocore.set_bus_alias(alias, params).map_err(|e| {
+        ApiError::fifocore(e, format!("Couldn't alias {alias} to {params}")).with_hint(
+            "Aliases are letters, digits, - and _, not all digits, and must name valid bus params.",
+        )
+    })?;
+    Ok(Json(BusAliasEntry {
+        alias: alias.to_owned(),
+        params: params.to_owned(),
+        id: fifocore.bus_matching_params(params),
+    }))
 }
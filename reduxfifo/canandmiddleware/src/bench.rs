@@ -0,0 +1,378 @@
+//! Bench mode: one switch for running devices on a desk instead of on a robot.
+//!
+//! A bench has no roboRIO, so nothing enables the actuators, and usually no driver station either,
+//! so nothing disables them again if a tool goes wrong. Bench mode sets up the guards for that all
+//! at once:
+//!
+//! * a stand-in roboRIO heartbeat that enables actuators, sent only while a client keeps asking for
+//!   it: each [`Bench::arm_heartbeat`] keeps it going for the configured auto-stop time, after which
+//!   it stops and actuators disable themselves within a heartbeat timeout
+//! * the heartbeat is never sent on a bus where a real roboRIO is heard, so it can't fight one
+//! * every destructive operation needs confirming ([`ConfirmMode::Always`]), and the policy can't be
+//!   loosened while bench mode is on
+//! * writes to each bus are capped with a [`fifocore::tx_rate`] limit, so a runaway client can't
+//!   flood the bus
+//!
+//! Bench mode is switched on when the server starts and stays on until it exits.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fifocore::{
+    FIFOCore, ReduxFIFOMessage, ReduxFIFOSessionConfig,
+    error::Error,
+    tx_rate::{TxRateLimit, TxRateStats},
+};
+use frc_can_id::{FRCCanHeartbeat, HEARTBEAT_ID, HEARTBEAT_TIMEOUT_US};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::{
+    confirm::{ConfirmMode, ConfirmPolicy},
+    log::{log_info, log_warn},
+};
+
+/// How often the stand-in heartbeat is sent, as often as a roboRIO sends its own.
+pub const HEARTBEAT_PERIOD: Duration = Duration::from_millis(20);
+
+/// Bits of the heartbeat that enable actuators: enabled, and the system watchdog.
+const ENABLED_HEARTBEAT: u64 = 1 << 25 | 1 << 28;
+
+/// How bench mode is set up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BenchConfig {
+    /// Send a stand-in roboRIO heartbeat while clients keep arming it
+    pub heartbeat: bool,
+    /// How long the heartbeat keeps going after it was last armed
+    pub auto_stop_ms: u64,
+    /// Cap on writes to each bus
+    pub tx_rate: TxRateLimit,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat: true,
+            auto_stop_ms: 1000,
+            // a quarter of a 1 Mbit/s bus
+            tx_rate: TxRateLimit {
+                frames_per_sec: 2000,
+                burst: 200,
+            },
+        }
+    }
+}
+
+/// Why bench mode wouldn't do something.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BenchError {
+    /// The server wasn't started in bench mode
+    NotEnabled,
+    /// Bench mode was started without the stand-in heartbeat
+    HeartbeatDisabled,
+    /// A roboRIO heartbeat was heard on the bus
+    RoboRioPresent(u16),
+    /// The policy would let an operation through unconfirmed
+    PolicyWeakened,
+    /// The bus couldn't be watched for a roboRIO heartbeat
+    Bus(u16, Error),
+}
+
+impl core::fmt::Display for BenchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BenchError::NotEnabled => f.write_str("the server isn't running in bench mode"),
+            BenchError::HeartbeatDisabled => {
+                f.write_str("bench mode is running without the stand-in heartbeat")
+            }
+            BenchError::RoboRioPresent(bus_id) => {
+                write!(f, "a roboRIO heartbeat was heard on bus {bus_id}")
+            }
+            BenchError::PolicyWeakened => {
+                f.write_str("bench mode needs every destructive operation confirmed")
+            }
+            BenchError::Bus(bus_id, e) => write!(f, "bus {bus_id}: {}", e.message()),
+        }
+    }
+}
+
+/// Where the stand-in heartbeat stands.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HeartbeatStatus {
+    pub running: bool,
+    /// Buses it's being sent on
+    pub buses: Vec<u16>,
+    /// Time left before it stops on its own
+    pub stops_in_ms: u64,
+}
+
+/// Everything bench mode is doing, for clients to show prominently.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchStatus {
+    pub enabled: bool,
+    pub config: Option<BenchConfig>,
+    pub heartbeat: HeartbeatStatus,
+    pub confirm_policy: Option<ConfirmPolicy>,
+    pub tx_rate: TxRateStats,
+}
+
+#[derive(Debug)]
+struct HeartbeatRun {
+    buses: Vec<u16>,
+    /// Shared with the task, which stops once it's passed
+    until: Arc<Mutex<Instant>>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for HeartbeatRun {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    config: Option<BenchConfig>,
+    heartbeat: Option<HeartbeatRun>,
+}
+
+/// Bench mode, if it's on, and its stand-in heartbeat.
+#[derive(Debug, Clone, Default)]
+pub struct Bench {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Bench {
+    /// Switches bench mode on with `config`, capping bus writes right away.
+    pub fn enable(config: BenchConfig) -> Self {
+        log_warn!(
+            "BENCH MODE: heartbeat {}, auto-stop {} ms, writes capped at {} frames/s per bus, \
+             every destructive operation needs confirming",
+            match config.heartbeat {
+                true => "available",
+                false => "off",
+            },
+            config.auto_stop_ms,
+            config.tx_rate.frames_per_sec
+        );
+        fifocore::tx_rate::set_tx_rate_limit(Some(config.tx_rate));
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                config: Some(config),
+                heartbeat: None,
+            })),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.inner.lock().config.is_some()
+    }
+
+    pub fn config(&self) -> Option<BenchConfig> {
+        self.inner.lock().config
+    }
+
+    /// The confirmation policy to start the server with.
+    pub fn confirm_policy(&self) -> ConfirmPolicy {
+        match self.enabled() {
+            true => ConfirmPolicy {
+                factory_reset: ConfirmMode::Always,
+                set_id: ConfirmMode::Always,
+                firmware_flash: ConfirmMode::Always,
+                operator_token: None,
+            },
+            false => ConfirmPolicy::default(),
+        }
+    }
+
+    /// Refuses a new confirmation policy that would let anything through unconfirmed while bench
+    /// mode is on.
+    pub fn check_policy(&self, policy: &ConfirmPolicy) -> Result<(), BenchError> {
+        let modes = [policy.factory_reset, policy.set_id, policy.firmware_flash];
+        match self.enabled() && modes.iter().any(|mode| *mode != ConfirmMode::Always) {
+            true => Err(BenchError::PolicyWeakened),
+            false => Ok(()),
+        }
+    }
+
+    /// Starts the stand-in heartbeat on `bus_ids`, or keeps it going for another auto-stop time.
+    ///
+    /// Buses it isn't already on are watched for a heartbeat timeout first, and refused if a
+    /// roboRIO is heard on any of them.
+    pub async fn arm_heartbeat(
+        &self,
+        fifocore: &FIFOCore,
+        bus_ids: &[u16],
+    ) -> Result<HeartbeatStatus, BenchError> {
+        let (config, running) = {
+            let inner = self.inner.lock();
+            let config = inner.config.ok_or(BenchError::NotEnabled)?;
+            let running = inner
+                .heartbeat
+                .as_ref()
+                .filter(|run| !run.task.is_finished())
+                .map(|run| run.buses.clone())
+                .unwrap_or_default();
+            (config, running)
+        };
+        if !config.heartbeat {
+            return Err(BenchError::HeartbeatDisabled);
+        }
+        let auto_stop = Duration::from_millis(config.auto_stop_ms);
+
+        let new_buses: Vec<u16> = bus_ids
+            .iter()
+            .copied()
+            .filter(|bus_id| !running.contains(bus_id))
+            .collect();
+        for &bus_id in &new_buses {
+            if roborio_present(fifocore, bus_id).await? {
+                log_warn!("Bench heartbeat not started on bus {bus_id}: a roboRIO is on it");
+                return Err(BenchError::RoboRioPresent(bus_id));
+            }
+        }
+
+        let mut inner = self.inner.lock();
+        let until = Instant::now() + auto_stop;
+        match &inner.heartbeat {
+            Some(run) if new_buses.is_empty() && !run.task.is_finished() => {
+                *run.until.lock() = until;
+            }
+            _ => {
+                let mut buses = running;
+                buses.extend(new_buses);
+                buses.sort_unstable();
+                buses.dedup();
+                log_warn!("BENCH MODE: sending an enabling heartbeat on buses {buses:?}");
+                let until = Arc::new(Mutex::new(until));
+                let task = fifocore.runtime().spawn(run_heartbeat(
+                    fifocore.clone(),
+                    buses.clone(),
+                    Arc::clone(&until),
+                ));
+                // replacing the old run aborts its task
+                inner.heartbeat = Some(HeartbeatRun { buses, until, task });
+            }
+        }
+        Ok(heartbeat_status(&inner))
+    }
+
+    /// Stops the stand-in heartbeat now.
+    pub fn stop_heartbeat(&self) -> HeartbeatStatus {
+        let mut inner = self.inner.lock();
+        if inner.heartbeat.take().is_some() {
+            log_info!("Bench heartbeat stopped");
+        }
+        heartbeat_status(&inner)
+    }
+
+    pub fn status(&self, confirm_policy: ConfirmPolicy) -> BenchStatus {
+        let inner = self.inner.lock();
+        BenchStatus {
+            enabled: inner.config.is_some(),
+            config: inner.config,
+            heartbeat: heartbeat_status(&inner),
+            confirm_policy: inner.config.map(|_| confirm_policy),
+            tx_rate: fifocore::tx_rate::tx_rate_stats(),
+        }
+    }
+}
+
+fn heartbeat_status(inner: &Inner) -> HeartbeatStatus {
+    let Some(run) = inner
+        .heartbeat
+        .as_ref()
+        .filter(|run| !run.task.is_finished())
+    else {
+        return HeartbeatStatus::default();
+    };
+    let stops_in = run.until.lock().saturating_duration_since(Instant::now());
+    HeartbeatStatus {
+        running: true,
+        buses: run.buses.clone(),
+        stops_in_ms: stops_in.as_millis() as u64,
+    }
+}
+
+/// Sends the heartbeat on each of `bus_ids` every [`HEARTBEAT_PERIOD`] until `until` passes.
+async fn run_heartbeat(fifocore: FIFOCore, bus_ids: Vec<u16>, until: Arc<Mutex<Instant>>) {
+    let data = FRCCanHeartbeat::new(ENABLED_HEARTBEAT.to_be_bytes()).data();
+    let mut interval = tokio::time::interval(HEARTBEAT_PERIOD);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if Instant::now() >= *until.lock() {
+            log_info!("Bench heartbeat auto-stopped on buses {bus_ids:?}");
+            return;
+        }
+        for &bus_id in &bus_ids {
+            let msg = ReduxFIFOMessage::builder()
+                .bus(bus_id)
+                .id(HEARTBEAT_ID)
+                .data(&data)
+                .fd(false)
+                .build();
+            // a bus over its write cap misses a beat, which actuators ride out
+            let _ = fifocore.write_single(&msg);
+        }
+    }
+}
+
+/// Whether a roboRIO heartbeat is heard on `bus_id` within a heartbeat timeout. Our own heartbeat
+/// isn't echoed back to the session, so only a real roboRIO counts.
+async fn roborio_present(fifocore: &FIFOCore, bus_id: u16) -> Result<bool, BenchError> {
+    let config = ReduxFIFOSessionConfig::new(HEARTBEAT_ID, 0x1fff_ffff);
+    let session = fifocore
+        .open_managed_session(bus_id, 8, config)
+        .map_err(|e| BenchError::Bus(bus_id, e))?;
+    let mut notifier = session
+        .rx_notifier()
+        .map_err(|e| BenchError::Bus(bus_id, e))?;
+    let wait = Duration::from_micros(HEARTBEAT_TIMEOUT_US);
+    match tokio::time::timeout(wait, notifier.wait_for(|size| *size > 0)).await {
+        Ok(Ok(_)) => Ok(true),
+        Ok(Err(_)) => Err(BenchError::Bus(bus_id, Error::BusClosed)),
+        Err(_) => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_enables() {
+        let heartbeat = FRCCanHeartbeat::new(ENABLED_HEARTBEAT.to_be_bytes());
+        assert!(heartbeat.enabled());
+        assert!(heartbeat.system_watchdog());
+        assert!(!heartbeat.autonomous());
+        assert!(!heartbeat.test_mode());
+    }
+
+    #[test]
+    fn test_policy_locked_to_always() {
+        let off = Bench::default();
+        assert!(off.check_policy(&ConfirmPolicy::default()).is_ok());
+
+        let bench = Bench {
+            inner: Arc::new(Mutex::new(Inner {
+                config: Some(BenchConfig::default()),
+                heartbeat: None,
+            })),
+        };
+        let policy = bench.confirm_policy();
+        assert!(bench.check_policy(&policy).is_ok());
+        let loosened = ConfirmPolicy {
+            set_id: ConfirmMode::WhileEnabled,
+            ..policy
+        };
+        assert_eq!(
+            bench.check_policy(&loosened),
+            Err(BenchError::PolicyWeakened)
+        );
+    }
+}
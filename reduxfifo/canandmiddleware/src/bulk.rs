@@ -0,0 +1,105 @@
+//! Everything a client needs on connect, in one response.
+//!
+//! Tools like Alchemist otherwise make a request per bus and per device when they connect, which
+//! adds up to seconds over a congested robot radio. [`BulkState`] bundles the open buses with a
+//! [`MiddlewareSnapshot`] of every session, and carries a weak ETag so a reconnecting client that
+//! already has the state gets back an empty `304 Not Modified`.
+//!
+//! The ETag leaves out fields that tick on their own (capture time, and ages like
+//! `last_active_ms`), so it only changes when something a client would act on does.
+
+use std::{hash::Hasher, sync::Arc};
+
+use fifocore::FIFOCore;
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHasher};
+use serde::Serialize;
+
+use crate::{
+    bus::BusState,
+    snapshot::{BusSnapshot, MiddlewareSnapshot},
+};
+
+/// Fields left out of the ETag because they change on every request.
+const VOLATILE_FIELDS: &[&str] = &[
+    "taken_us",
+    "last_active_ms",
+    "session_age_ms",
+    "heartbeat_age_ms",
+];
+
+/// An open bus, whether or not it has a device session.
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkBus {
+    pub id: u16,
+    pub params: String,
+    pub alias: Option<String>,
+    /// Whether a device session is open on the bus; its devices are under `sessions` if so
+    pub session_open: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkState {
+    /// When the state was captured, in microseconds on the same timebase as message timestamps
+    pub taken_us: i64,
+    pub buses: Vec<BulkBus>,
+    /// Devices, cached settings, faults, and stats of every bus session
+    pub sessions: Vec<BusSnapshot>,
+}
+
+impl BulkState {
+    pub fn capture(
+        fifocore: &FIFOCore,
+        bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+    ) -> Self {
+        let snapshot = MiddlewareSnapshot::capture(fifocore, bus_sessions);
+        let aliases = fifocore.bus_aliases();
+        let mut buses: Vec<BulkBus> = fifocore.with_buses(|buses| {
+            buses
+                .iter()
+                .map(|(&id, ent)| BulkBus {
+                    id,
+                    params: ent.params().to_string(),
+                    alias: aliases.alias_for(ent.params()).map(str::to_string),
+                    session_open: snapshot.buses.iter().any(|bus| bus.bus_id == id),
+                })
+                .collect()
+        });
+        buses.sort_by_key(|bus| bus.id);
+
+        Self {
+            taken_us: snapshot.taken_us,
+            buses,
+            sessions: snapshot.buses,
+        }
+    }
+
+    /// Weak ETag over everything but [`VOLATILE_FIELDS`].
+    pub fn etag(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        strip_volatile(&mut value);
+        let mut hasher = FxHasher::default();
+        hasher.write(value.to_string().as_bytes());
+        format!("W/\"{:016x}\"", hasher.finish())
+    }
+}
+
+fn strip_volatile(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|key, _| !VOLATILE_FIELDS.contains(&key.as_str()));
+            map.values_mut().for_each(strip_volatile);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_volatile),
+        _ => {}
+    }
+}
+
+/// Whether an `If-None-Match` header value matches `etag`.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    // weak comparison: W/ prefixes don't count
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
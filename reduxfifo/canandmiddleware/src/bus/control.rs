@@ -0,0 +1,471 @@
+//! Control-plane traffic scheduling for a bus.
+//!
+//! Setting reads and writes, LED blinks, id changes and reboots all draw from one frame budget
+//! per bus, so no amount of tooling activity can take more than a fixed slice of the bus away from
+//! the robot. Bulk setting fetches run as jobs on top of that budget: each device is asked to dump
+//! every setting with a single `FETCH_SETTINGS` command, a few devices at a time so their replies
+//! don't all land at once, and whatever didn't make it back gets fetched index by index.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use canandmessage::{
+    cananddevice,
+    traits::{CanandDevice, MessageIndexId},
+};
+use fifocore::{FIFOInterface, ReduxFIFOMessage};
+use frc_can_id::FRCCanId;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::bus::device::{Device, DeviceKey, ReduxDeviceType};
+
+/// Default control-plane budget, in frames per second. A saturated 1 Mbit/s bus carries around
+/// 8000 frames a second, so this is about 6% of it.
+pub const DEFAULT_BUDGET_FPS: u32 = 500;
+/// Upper limit accepted for the budget.
+pub const MAX_BUDGET_FPS: u32 = 4000;
+/// Devices allowed to be dumping their settings at once.
+const MAX_CONCURRENT_DUMPS: usize = 4;
+/// How long a device gets to finish a settings dump before we chase the missing ones.
+const DUMP_TIMEOUT: Duration = Duration::from_millis(250);
+/// How long individually fetched settings get to come back.
+const RETRY_TIMEOUT: Duration = Duration::from_millis(100);
+/// Rounds of individual fetches before a device's missing settings are given up on.
+const MAX_RETRIES: u32 = 3;
+/// Assumed reply size of a settings dump from a device whose setting list we don't know.
+const UNKNOWN_DUMP_FRAMES: u32 = 32;
+/// Finished jobs kept around for status queries.
+const JOBS_KEPT: usize = 16;
+
+/// Token bucket that refills at the budgeted frame rate.
+#[derive(Debug)]
+struct FrameBudget {
+    fps: u32,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl FrameBudget {
+    fn new(fps: u32) -> Self {
+        let mut budget = Self {
+            fps,
+            tokens: 0.0,
+            refilled: Instant::now(),
+        };
+        budget.tokens = budget.burst();
+        budget
+    }
+
+    /// 100 ms worth of frames, but always enough to admit the largest single request.
+    fn burst(&self) -> f64 {
+        (self.fps as f64 / 10.0).max(2.0 * UNKNOWN_DUMP_FRAMES as f64)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.refilled = now;
+        self.tokens = (self.tokens + elapsed * self.fps as f64).min(self.burst());
+    }
+
+    /// Takes `frames` if the budget has them.
+    fn try_take(&mut self, now: Instant, frames: u32) -> bool {
+        self.refill(now);
+        if self.tokens < frames as f64 {
+            return false;
+        }
+        self.tokens -= frames as f64;
+        true
+    }
+
+    /// Takes `frames` regardless, for requests that a user is waiting on. Going into debt holds
+    /// back bulk jobs until it's paid off.
+    fn charge(&mut self, frames: u32) {
+        self.refill(Instant::now());
+        self.tokens = (self.tokens - frames as f64).max(-self.burst());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchState {
+    /// Waiting for budget or a dump slot
+    Queued,
+    /// Asked for all settings, waiting for them to come in
+    Dumping,
+    /// Fetching the settings the dump missed one by one
+    Retrying,
+    Done,
+    Failed,
+    /// The job was cancelled before the device finished
+    Cancelled,
+}
+
+/// Progress of one device in a bulk fetch.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceFetch {
+    pub device: String,
+    pub can_id: u32,
+    pub state: FetchState,
+    /// Setting values read back, by setting index
+    pub settings: BTreeMap<u8, [u8; 6]>,
+    /// Settings the device should have but hasn't reported
+    pub missing: Vec<u8>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    key: DeviceKey,
+    /// Readable settings of the product, if we know which product it is
+    #[serde(skip)]
+    expected: Option<Vec<u8>>,
+    #[serde(skip)]
+    since: Instant,
+    #[serde(skip)]
+    retries: u32,
+}
+
+impl DeviceFetch {
+    fn finished(&self) -> bool {
+        matches!(
+            self.state,
+            FetchState::Done | FetchState::Failed | FetchState::Cancelled
+        )
+    }
+
+    fn fail(&mut self, error: impl Into<String>) {
+        self.state = FetchState::Failed;
+        self.error = Some(error.into());
+    }
+
+    /// Copies what the device has reported so far out of its setting cache.
+    fn collect(&mut self, dev: &Device) {
+        match &self.expected {
+            Some(expected) => {
+                self.missing.clear();
+                for index in expected.iter() {
+                    match dev.setting_cache().get(index) {
+                        Some(value) => {
+                            self.settings.insert(*index, *value);
+                        }
+                        None => self.missing.push(*index),
+                    }
+                }
+            }
+            None => self
+                .settings
+                .extend(dev.setting_cache().iter().map(|(k, v)| (*k, *v))),
+        }
+    }
+}
+
+/// A bulk setting fetch across several devices.
+#[derive(Debug, Clone, Serialize)]
+pub struct FetchJob {
+    pub id: u32,
+    pub bus_id: u16,
+    pub done: bool,
+    /// Time spent so far, or in total once done
+    pub elapsed_ms: u64,
+    pub devices: Vec<DeviceFetch>,
+    #[serde(skip)]
+    started: Instant,
+}
+
+/// Per-bus scheduler for control-plane requests. Owned by the bus session.
+#[derive(Debug)]
+pub struct ControlScheduler {
+    bus_id: u16,
+    budget: FrameBudget,
+    jobs: VecDeque<FetchJob>,
+    next_job: u32,
+}
+
+impl ControlScheduler {
+    pub fn new(bus_id: u16) -> Self {
+        Self {
+            bus_id,
+            budget: FrameBudget::new(DEFAULT_BUDGET_FPS),
+            jobs: VecDeque::new(),
+            next_job: 1,
+        }
+    }
+
+    pub fn budget_fps(&self) -> u32 {
+        self.budget.fps
+    }
+
+    pub fn set_budget_fps(&mut self, fps: u32) {
+        self.budget = FrameBudget::new(fps.clamp(1, MAX_BUDGET_FPS));
+    }
+
+    /// Accounts for `frames` sent or caused by a request that was sent immediately.
+    pub fn charge(&mut self, frames: u32) {
+        self.budget.charge(frames);
+    }
+
+    /// Queues a bulk fetch of every readable setting on `devices`, returning the job id.
+    pub fn start_fetch(&mut self, devices: impl IntoIterator<Item = DeviceKey>) -> u32 {
+        let id = self.next_job;
+        self.next_job = self.next_job.wrapping_add(1).max(1);
+        let now = Instant::now();
+        let devices = devices
+            .into_iter()
+            .map(|key| DeviceFetch {
+                device: key.pretty_str(),
+                can_id: key.can_id(),
+                state: FetchState::Queued,
+                settings: BTreeMap::new(),
+                missing: Vec::new(),
+                error: None,
+                key,
+                expected: readable_settings(key.dev_type),
+                since: now,
+                retries: 0,
+            })
+            .collect();
+        self.jobs.push_back(FetchJob {
+            id,
+            bus_id: self.bus_id,
+            done: false,
+            elapsed_ms: 0,
+            devices,
+            started: now,
+        });
+        while self.jobs.len() > JOBS_KEPT {
+            let Some(pos) = self.jobs.iter().position(|job| job.done) else {
+                break;
+            };
+            self.jobs.remove(pos);
+        }
+        id
+    }
+
+    pub fn job(&self, id: u32) -> Option<&FetchJob> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    /// Stops a bulk fetch, keeping what the devices already reported. Devices that were still
+    /// being fetched are marked cancelled, and nothing more is sent for them; a device that was
+    /// dumping may still finish sending its settings into the cache.
+    pub fn cancel_fetch(&mut self, id: u32) -> Option<&FetchJob> {
+        let now = Instant::now();
+        let job = self.jobs.iter_mut().find(|job| job.id == id)?;
+        if !job.done {
+            for fetch in job.devices.iter_mut().filter(|fetch| !fetch.finished()) {
+                fetch.state = FetchState::Cancelled;
+            }
+            job.elapsed_ms = now.duration_since(job.started).as_millis() as u64;
+            job.done = true;
+        }
+        Some(job)
+    }
+
+    /// Advances the bulk fetch jobs as far as the budget allows.
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        devices: &mut FxHashMap<DeviceKey, Device>,
+        fifocore: &dyn FIFOInterface,
+    ) {
+        let mut dumping = self
+            .jobs
+            .iter()
+            .flat_map(|job| job.devices.iter())
+            .filter(|dev| dev.state == FetchState::Dumping)
+            .count();
+
+        for job in self.jobs.iter_mut().filter(|job| !job.done) {
+            for fetch in job.devices.iter_mut().filter(|fetch| !fetch.finished()) {
+                let was_dumping = fetch.state == FetchState::Dumping;
+                let Some(dev) = devices.get_mut(&fetch.key) else {
+                    fetch.fail("device isn't on the bus");
+                    dumping -= was_dumping as usize;
+                    continue;
+                };
+
+                match fetch.state {
+                    FetchState::Queued => {
+                        let frames = 1 + fetch
+                            .expected
+                            .as_ref()
+                            .map_or(UNKNOWN_DUMP_FRAMES, |e| e.len() as u32);
+                        if dumping >= MAX_CONCURRENT_DUMPS || !self.budget.try_take(now, frames) {
+                            continue;
+                        }
+                        // settings come back into the cache; clear it so we can tell what's fresh
+                        dev.setting_cache_mut().clear();
+                        let command = [cananddevice::types::SettingCommand::FetchSettings as u8];
+                        if let Err(e) =
+                            send_setting_command(fifocore, self.bus_id, &fetch.key, &command)
+                        {
+                            fetch.fail(format!("couldn't send fetch: {e}"));
+                            continue;
+                        }
+                        fetch.state = FetchState::Dumping;
+                        fetch.since = now;
+                        dumping += 1;
+                    }
+                    FetchState::Dumping | FetchState::Retrying => {
+                        fetch.collect(dev);
+                        let timeout = if was_dumping {
+                            DUMP_TIMEOUT
+                        } else {
+                            RETRY_TIMEOUT
+                        };
+                        if fetch.expected.is_some() && fetch.missing.is_empty() {
+                            fetch.state = FetchState::Done;
+                        } else if now.duration_since(fetch.since) < timeout {
+                            continue;
+                        } else if fetch.expected.is_none() {
+                            // no list to check against, so whatever came back is everything
+                            fetch.state = FetchState::Done;
+                        } else if fetch.retries >= MAX_RETRIES {
+                            fetch
+                                .fail(format!("{} setting(s) never reported", fetch.missing.len()));
+                        } else if self.budget.try_take(now, 2 * fetch.missing.len() as u32) {
+                            fetch.retries += 1;
+                            fetch.since = now;
+                            fetch.state = FetchState::Retrying;
+                            for index in fetch.missing.iter() {
+                                let command = [
+                                    cananddevice::types::SettingCommand::FetchSettingValue as u8,
+                                    *index,
+                                ];
+                                let _ = send_setting_command(
+                                    fifocore,
+                                    self.bus_id,
+                                    &fetch.key,
+                                    &command,
+                                );
+                            }
+                        } else {
+                            continue;
+                        }
+                        dumping -= was_dumping as usize;
+                    }
+                    FetchState::Done | FetchState::Failed | FetchState::Cancelled => {}
+                }
+            }
+
+            job.elapsed_ms = now.duration_since(job.started).as_millis() as u64;
+            job.done = job.devices.iter().all(DeviceFetch::finished);
+        }
+    }
+}
+
+/// Indices of the readable settings of the product behind `dev_type`, if it's one we have a spec for.
+fn readable_settings(dev_type: ReduxDeviceType) -> Option<Vec<u8>> {
+    fn collect<D: CanandDevice>() -> Vec<u8> {
+        D::setting_info()
+            .iter()
+            .filter(|info| info.readable)
+            .map(|info| info.index.into())
+            .collect()
+    }
+    match dev_type {
+        ReduxDeviceType::Encoder => Some(collect::<canandmessage::canandmag::Device>()),
+        ReduxDeviceType::Gyroscope => Some(collect::<canandmessage::canandgyro::Device>()),
+        ReduxDeviceType::ColorDistanceSensor => {
+            Some(collect::<canandmessage::canandcolor::Device>())
+        }
+        _ => None,
+    }
+}
+
+fn send_setting_command(
+    fifocore: &dyn FIFOInterface,
+    bus_id: u16,
+    key: &DeviceKey,
+    command: &[u8],
+) -> Result<(), fifocore::error::Error> {
+    let base = FRCCanId::new(key.can_id());
+    let id = cananddevice::MessageIndex::SettingCommand
+        .frc_can_id_as(base.device_type_code(), base.device_number());
+    fifocore.write_single(
+        &ReduxFIFOMessage::builder()
+            .bus(bus_id)
+            .id(id.0)
+            .data(command)
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use fifocore::interface::FakeFIFO;
+
+    use super::*;
+
+    #[test]
+    fn test_fetch_job_completes() {
+        let fifo = FakeFIFO::new();
+        fifo.add_bus(0);
+        let key = DeviceKey {
+            dev_type: ReduxDeviceType::Gyroscope,
+            dev_id: 3,
+        };
+        let mut devices = FxHashMap::default();
+        devices.insert(key, Device::new(key));
+
+        let mut control = ControlScheduler::new(0);
+        let job = control.start_fetch([key]);
+        let now = Instant::now();
+        control.poll(now, &mut devices, &fifo);
+
+        let written = fifo.take_written();
+        assert_eq!(written.len(), 1);
+        assert_eq!(
+            written[0].data_slice(),
+            [cananddevice::types::SettingCommand::FetchSettings as u8]
+        );
+        assert_eq!(
+            control.job(job).unwrap().devices[0].state,
+            FetchState::Dumping
+        );
+
+        // the device answers with every setting
+        let dev = devices.get_mut(&key).unwrap();
+        for index in readable_settings(key.dev_type).unwrap() {
+            dev.setting_cache_mut().insert(index, [index; 6]);
+        }
+        control.poll(now, &mut devices, &fifo);
+        let job = control.job(job).unwrap();
+        assert!(job.done);
+        assert_eq!(job.devices[0].state, FetchState::Done);
+        assert!(fifo.written().is_empty());
+    }
+
+    #[test]
+    fn test_fetch_job_cancel() {
+        let fifo = FakeFIFO::new();
+        fifo.add_bus(0);
+        let keys = [3, 4].map(|dev_id| DeviceKey {
+            dev_type: ReduxDeviceType::Gyroscope,
+            dev_id,
+        });
+        let mut devices = FxHashMap::default();
+        for key in keys {
+            devices.insert(key, Device::new(key));
+        }
+
+        let mut control = ControlScheduler::new(0);
+        let job = control.start_fetch(keys);
+        let now = Instant::now();
+        control.poll(now, &mut devices, &fifo);
+        fifo.take_written();
+
+        let cancelled = control.cancel_fetch(job).unwrap();
+        assert!(cancelled.done);
+        assert!(
+            cancelled
+                .devices
+                .iter()
+                .all(|dev| dev.state == FetchState::Cancelled)
+        );
+        // nothing is retried once the dump would have timed out
+        control.poll(now + DUMP_TIMEOUT * 2, &mut devices, &fifo);
+        assert!(fifo.written().is_empty());
+        assert!(control.cancel_fetch(job + 1).is_none());
+    }
+}
@@ -1,11 +1,18 @@
 use std::time::{Duration, Instant};
 
-use canandmessage::{cananddevice, traits::CanandDeviceSetting};
+use canandmessage::{
+    cananddevice,
+    traits::{CanandDeviceSetting, MessageIndexId},
+};
 use fifocore::ReduxFIFOMessage;
 use frc_can_id::{FRCCanDeviceType, FRCCanId};
 use rustc_hash::FxHashMap;
 use serial_numer::{ProductId, SerialNumer};
 
+/// Frames that arrive this soon after a reboot was sent were already queued before it, so they don't
+/// count as the device coming back.
+const REBOOT_HOLDOFF: Duration = Duration::from_millis(100);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ConflictPacket {
     serial: SerialNumer,
@@ -57,30 +64,27 @@ impl DeviceKey {
     pub fn pretty_str(&self) -> String {
         format!("{:?}:{}", self.dev_type, self.dev_id)
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct DeviceName {
-    name0: Option<[u8; 6]>,
-    name1: Option<[u8; 6]>,
-    name2: Option<[u8; 6]>,
+    /// Base Redux CAN id (api index 0) for this device.
+    pub fn can_id(&self) -> u32 {
+        let dev_type = match self.dev_type {
+            ReduxDeviceType::MotorController => FRCCanDeviceType::MotorController,
+            ReduxDeviceType::Gyroscope => FRCCanDeviceType::GyroSensor,
+            ReduxDeviceType::ColorDistanceSensor => FRCCanDeviceType::DistanceSensor,
+            ReduxDeviceType::Encoder => FRCCanDeviceType::Encoder,
+            ReduxDeviceType::Other(other) => FRCCanDeviceType::from(other),
+        };
+        frc_can_id::build_frc_can_id(dev_type.as_u8(), frc_can_id::REDUX_VENDOR_ID, 0, self.dev_id)
+    }
 }
 
-impl DeviceName {
-    pub fn name(&self) -> Option<String> {
-        let name0 = self.name0?;
-        let name1 = self.name1?;
-        let name2 = self.name2?;
-
-        let s: Vec<u8> = name0
-            .iter()
-            .chain(name1.iter())
-            .chain(name2.iter())
-            .take_while(|v| **v != 0)
-            .copied()
-            .collect();
-        Some(String::from_utf8_lossy(&s).into_owned())
-    }
+/// Where a device is in a reboot we asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebootState {
+    /// Waiting for the device to come back before `deadline`
+    Rebooting { since: Instant, deadline: Instant },
+    /// The device is talking again; `since` is when the reboot was sent
+    Returned { since: Instant, at: Instant },
 }
 
 /// collection of information about a specific can id
@@ -97,9 +101,12 @@ pub struct Device {
     device_type: Option<u16>,
     bootloader: bool,
     setting_cache: FxHashMap<u8, [u8; 6]>,
+    // (active, sticky) fault bits from the most recent status frame
+    faults: Option<(u8, u8)>,
 
     conflict_packets: Vec<ConflictPacket>,
     authorized_serial: Option<SerialNumer>,
+    reboot: Option<RebootState>,
 }
 
 impl Device {
@@ -112,11 +119,38 @@ impl Device {
             device_type: None,
             bootloader: false,
             setting_cache: FxHashMap::default(),
+            faults: None,
             conflict_packets: Vec::new(),
             authorized_serial: None,
+            reboot: None,
         }
     }
 
+    pub fn serial_numer(&self) -> Option<SerialNumer> {
+        self.serial_numer
+    }
+
+    pub fn firmware_version(&self) -> Option<cananddevice::types::FirmwareVersion> {
+        self.firmware_version
+    }
+
+    /// Most recent time the device sent anything other than a CAN id conflict report.
+    pub fn last_active(&self) -> Option<Instant> {
+        self.most_recent_active
+    }
+
+    pub fn bootloader(&self) -> bool {
+        self.bootloader
+    }
+
+    /// Active and sticky fault bits from the most recent status frame.
+    ///
+    /// Every Redux product leads its status frame with these two bytes, though what each bit
+    /// means differs by product.
+    pub fn faults(&self) -> Option<(u8, u8)> {
+        self.faults
+    }
+
     pub fn setting_cache(&self) -> &FxHashMap<u8, [u8; 6]> {
         &self.setting_cache
     }
@@ -125,6 +159,31 @@ impl Device {
         &mut self.setting_cache
     }
 
+    pub fn reboot_state(&self) -> Option<RebootState> {
+        self.reboot
+    }
+
+    pub fn rebooting(&self) -> bool {
+        matches!(self.reboot, Some(RebootState::Rebooting { .. }))
+    }
+
+    /// Keeps the device around through a reboot, expecting it back within `window`.
+    ///
+    /// Everything cached about the device survives, except the firmware version and bootloader
+    /// flag, which the device may come back with different values of.
+    pub fn begin_reboot(&mut self, now: Instant, window: Duration) {
+        self.firmware_version = None;
+        self.bootloader = false;
+        self.reboot = Some(RebootState::Rebooting {
+            since: now,
+            deadline: now + window,
+        });
+    }
+
+    pub fn end_reboot(&mut self) {
+        self.reboot = None;
+    }
+
     fn update_recent_active(&mut self, ts: Instant) {
         self.most_recent_active = Some(self.most_recent_active.map_or(ts, |v| ts.max(v)));
     }
@@ -176,6 +235,7 @@ impl Device {
                 cananddevice::Message::Enumerate {
                     serial,
                     is_bootloader,
+                    ..
                 } => {
                     self.serial_numer = Some(SerialNumer::new(serial));
                     self.bootloader = is_bootloader;
@@ -207,17 +267,27 @@ impl Device {
                         _ => {}
                     }
                 }
+                cananddevice::Message::Status { dev_specific } => {
+                    self.faults = Some((dev_specific[0], dev_specific[1]));
+                }
                 _ => {}
             }
         } else {
             let id = FRCCanId(msg.message_id);
-            if id.api_index() == cananddevice::MessageIndex::ReportSetting as u16 {
+            if let Some((cananddevice::MessageIndex::ReportSetting, _)) =
+                cananddevice::MessageIndex::from_frc_can_id(&id)
+            {
                 self.setting_cache
                     .insert(msg.data[0], msg.data[1..7].try_into().unwrap());
             }
         }
         if !is_conflict_packet {
             self.update_recent_active(now);
+            if let Some(RebootState::Rebooting { since, .. }) = self.reboot
+                && now - since >= REBOOT_HOLDOFF
+            {
+                self.reboot = Some(RebootState::Returned { since, at: now });
+            }
         }
     }
 
@@ -226,7 +296,8 @@ impl Device {
     }
 
     pub fn still_on_bus(&mut self, ts: Instant) -> bool {
-        !self.conflict_packets.is_empty()
+        self.rebooting()
+            || !self.conflict_packets.is_empty()
             || self
                 .most_recent_active
                 .map_or(false, |t| (ts - t) <= Duration::from_secs(2))
@@ -308,6 +379,26 @@ pub struct InConflict {
     authorized: Option<SerialNumer>,
 }
 
+/// Human-readable serial numer, or `?` if unknown.
+pub(crate) fn serial_str(serial: Option<SerialNumer>) -> String {
+    let Some(serial) = serial else {
+        return "?".to_string();
+    };
+    let mut buf = [0u8; 17];
+    serial.to_readable_str(&mut buf).to_string()
+}
+
+/// Human-readable firmware version, or `?` if unknown.
+pub(crate) fn firmware_str(fw: Option<cananddevice::types::FirmwareVersion>) -> String {
+    match fw {
+        Some(fw) => format!(
+            "v{}.{}.{}",
+            fw.firmware_year, fw.firmware_minor, fw.firmware_patch
+        ),
+        None => "?".to_string(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
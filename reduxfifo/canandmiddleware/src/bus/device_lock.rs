@@ -0,0 +1,226 @@
+//! Per-device locks that keep control operations from different clients from interleaving.
+//!
+//! Each call into [`BusState`](super::BusState) is atomic under the bus lock, but a control
+//! operation rarely is one call: a name write is followed by a wait for the device to apply it, a
+//! composite read by a wait for every part to come back, and an OTA runs for minutes. Holding a
+//! [`DeviceGuard`] for the whole operation keeps another client's writes to the same device out of
+//! the middle of it. Only operations that go through the middleware take these locks; frames robot
+//! code writes straight onto the bus aren't held back.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use tokio::sync::OwnedMutexGuard;
+
+use crate::bus::device::DeviceKey;
+
+/// How long an operation waits for another one on the same device before giving up.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Contention on one device's lock since it was first taken.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LockStats {
+    /// Times the lock was taken
+    pub acquired: u64,
+    /// Times an operation had to wait for another one to finish first
+    pub contended: u64,
+    /// Times an operation gave up waiting
+    pub timeouts: u64,
+    pub total_wait_us: u64,
+    pub max_wait_us: u64,
+    /// Longest an operation has held the lock
+    pub max_hold_us: u64,
+}
+
+#[derive(Debug, Default)]
+struct DeviceLock {
+    mutex: Arc<tokio::sync::Mutex<()>>,
+    /// Operation holding the lock, if any
+    holder: Mutex<Option<&'static str>>,
+    stats: Mutex<LockStats>,
+}
+
+/// Locks by bus id and device.
+type LockMap = FxHashMap<(u16, DeviceKey), Arc<DeviceLock>>;
+
+/// The control-operation locks of every device the middleware has operated on.
+#[derive(Debug, Clone)]
+pub struct DeviceLocks {
+    inner: Arc<Mutex<LockMap>>,
+    timeout: Duration,
+}
+
+impl Default for DeviceLocks {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOCK_TIMEOUT)
+    }
+}
+
+impl DeviceLocks {
+    /// Locks that give up after waiting `timeout` for the device.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            inner: Default::default(),
+            timeout,
+        }
+    }
+
+    /// Waits for the device at `key` on `bus_id` to be free, then holds it for `operation` until
+    /// the guard is dropped.
+    pub async fn acquire(
+        &self,
+        bus_id: u16,
+        key: DeviceKey,
+        operation: &'static str,
+    ) -> Result<DeviceGuard, LockError> {
+        let lock = self.inner.lock().entry((bus_id, key)).or_default().clone();
+        let start = Instant::now();
+        let guard = match lock.mutex.clone().try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                lock.stats.lock().contended += 1;
+                match tokio::time::timeout(self.timeout, lock.mutex.clone().lock_owned()).await {
+                    Ok(guard) => guard,
+                    Err(_) => {
+                        lock.stats.lock().timeouts += 1;
+                        return Err(LockError {
+                            bus_id,
+                            device: key.pretty_str(),
+                            operation,
+                            holder: *lock.holder.lock(),
+                            waited: start.elapsed(),
+                        });
+                    }
+                }
+            }
+        };
+
+        let waited_us = start.elapsed().as_micros() as u64;
+        {
+            let mut stats = lock.stats.lock();
+            stats.acquired += 1;
+            stats.total_wait_us += waited_us;
+            stats.max_wait_us = stats.max_wait_us.max(waited_us);
+        }
+        *lock.holder.lock() = Some(operation);
+        Ok(DeviceGuard {
+            lock,
+            acquired: Instant::now(),
+            _guard: guard,
+        })
+    }
+
+    /// Who holds each device and how contended it's been, by bus and CAN id.
+    pub fn report(&self) -> Vec<DeviceLockReport> {
+        let mut report: Vec<DeviceLockReport> = self
+            .inner
+            .lock()
+            .iter()
+            .map(|(&(bus_id, key), lock)| DeviceLockReport {
+                bus_id,
+                can_id: key.can_id(),
+                device: key.pretty_str(),
+                holder: *lock.holder.lock(),
+                stats: lock.stats.lock().clone(),
+            })
+            .collect();
+        report.sort_by_key(|entry| (entry.bus_id, entry.can_id));
+        report
+    }
+}
+
+/// Holds a device for one operation; dropping it lets the next one in.
+#[derive(Debug)]
+pub struct DeviceGuard {
+    lock: Arc<DeviceLock>,
+    acquired: Instant,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Drop for DeviceGuard {
+    fn drop(&mut self) {
+        // runs before `_guard` is released, so this can't clear the next holder
+        let held_us = self.acquired.elapsed().as_micros() as u64;
+        let mut stats = self.lock.stats.lock();
+        stats.max_hold_us = stats.max_hold_us.max(held_us);
+        *self.lock.holder.lock() = None;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceLockReport {
+    pub bus_id: u16,
+    pub can_id: u32,
+    pub device: String,
+    /// Operation holding the device right now
+    pub holder: Option<&'static str>,
+    #[serde(flatten)]
+    pub stats: LockStats,
+}
+
+/// Another operation held the device for longer than the lock timeout.
+#[derive(Debug, Clone)]
+pub struct LockError {
+    pub bus_id: u16,
+    pub device: String,
+    pub operation: &'static str,
+    pub holder: Option<&'static str>,
+    pub waited: Duration,
+}
+
+impl core::fmt::Display for LockError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} on bus {} was busy for {} ms",
+            self.device,
+            self.bus_id,
+            self.waited.as_millis()
+        )?;
+        if let Some(holder) = self.holder {
+            write!(f, " with {holder}")?;
+        }
+        write!(f, ", so {} was not started", self.operation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::device::ReduxDeviceType;
+
+    #[tokio::test]
+    async fn test_contention_and_timeout() {
+        let locks = DeviceLocks::new(Duration::from_millis(50));
+        let key = DeviceKey {
+            dev_type: ReduxDeviceType::Gyroscope,
+            dev_id: 2,
+        };
+
+        let held = locks.acquire(0, key, "set_name").await.unwrap();
+        // the same device id on another bus is a different device
+        drop(locks.acquire(1, key, "blink").await.unwrap());
+        let err = locks.acquire(0, key, "reboot").await.unwrap_err();
+        assert_eq!(err.holder, Some("set_name"));
+
+        let waiter = tokio::spawn({
+            let locks = locks.clone();
+            async move { locks.acquire(0, key, "reboot").await.map(drop) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(held);
+        waiter.await.unwrap().unwrap();
+
+        let report = locks.report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].holder, None);
+        assert_eq!(report[0].stats.acquired, 2);
+        assert_eq!(report[0].stats.contended, 2);
+        assert_eq!(report[0].stats.timeouts, 1);
+    }
+}
@@ -0,0 +1,189 @@
+//! How often devices send their periodic frames, looked up per product from the message spec.
+//!
+//! Each periodic frame is controlled by its own setting, and which setting that is differs between
+//! products (a Canandmag's `STATUS_FRAME_PERIOD` even has a different index and range than the
+//! other products'). Callers name the frame by its message index instead, and the spec supplies
+//! the setting and the range it accepts.
+
+use canandmessage::{
+    cananddevice,
+    generic::{FramePeriodSetting, SetSetting},
+    traits::{CanandDevice, MessageIndexId},
+};
+use fifocore::ReduxFIFOMessage;
+use frc_can_id::FRCCanId;
+use serde::Serialize;
+
+use crate::bus::{
+    BusState,
+    device::{DeviceKey, ReduxDeviceType},
+    sanitize_id,
+};
+
+/// Frame period settings of the product behind `dev_type`, if it's one we have a spec for.
+pub fn frame_periods(dev_type: ReduxDeviceType) -> Option<&'static [FramePeriodSetting]> {
+    match dev_type {
+        ReduxDeviceType::Encoder => Some(canandmessage::canandmag::Device::frame_periods()),
+        ReduxDeviceType::Gyroscope => Some(canandmessage::canandgyro::Device::frame_periods()),
+        ReduxDeviceType::ColorDistanceSensor => {
+            Some(canandmessage::canandcolor::Device::frame_periods())
+        }
+        _ => None,
+    }
+}
+
+/// The setting controlling the frame with `message_index` on the device at `key`.
+pub fn frame_period(
+    key: &DeviceKey,
+    message_index: u8,
+) -> Result<&'static FramePeriodSetting, FramePeriodError> {
+    frame_periods(key.dev_type)
+        .ok_or_else(|| FramePeriodError::UnknownProduct(key.pretty_str()))?
+        .iter()
+        .find(|period| period.message_index == message_index)
+        .ok_or(FramePeriodError::NotPeriodic(message_index))
+}
+
+/// A periodic frame and how often the device was last reported to send it.
+#[derive(Debug, Clone, Serialize)]
+pub struct FramePeriodReport {
+    pub message: &'static str,
+    pub message_index: u8,
+    pub setting: &'static str,
+    pub setting_index: u8,
+    pub min_ms: u16,
+    pub max_ms: u16,
+    /// From the setting cache; `None` until the device has reported the setting
+    pub period_ms: Option<u16>,
+}
+
+#[derive(Debug)]
+pub enum FramePeriodError {
+    /// No spec for this kind of device
+    UnknownProduct(String),
+    /// The device has no periodic frame with this message index
+    NotPeriodic(u8),
+    OutOfRange {
+        setting: &'static str,
+        period_ms: u32,
+        min_ms: u16,
+        max_ms: u16,
+    },
+    FIFOCore(fifocore::error::Error),
+}
+
+impl core::fmt::Display for FramePeriodError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FramePeriodError::UnknownProduct(device) => {
+                write!(f, "{device} isn't a product with known frame periods")
+            }
+            FramePeriodError::NotPeriodic(index) => {
+                write!(
+                    f,
+                    "message index {index} isn't a periodic frame on this device"
+                )
+            }
+            FramePeriodError::OutOfRange {
+                setting,
+                period_ms,
+                min_ms,
+                max_ms,
+            } => write!(
+                f,
+                "{period_ms} ms is out of range for {setting} ({min_ms} to {max_ms} ms)"
+            ),
+            FramePeriodError::FIFOCore(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<fifocore::error::Error> for FramePeriodError {
+    fn from(value: fifocore::error::Error) -> Self {
+        Self::FIFOCore(value)
+    }
+}
+
+impl BusState {
+    /// Sets how often the device at `id` sends the frame with `message_index`.
+    ///
+    /// A period of 0 turns the frame off, on frames that allow it.
+    pub fn set_frame_period(
+        &mut self,
+        id: u32,
+        message_index: u8,
+        period_ms: u32,
+    ) -> Result<&'static FramePeriodSetting, FramePeriodError> {
+        let key = DeviceKey::from(FRCCanId(sanitize_id(id)));
+        let period = frame_period(&key, message_index)?;
+        let value = period
+            .value(period_ms)
+            .ok_or(FramePeriodError::OutOfRange {
+                setting: period.setting,
+                period_ms,
+                min_ms: period.min_ms,
+                max_ms: period.max_ms,
+            })?;
+
+        let base = FRCCanId::new(key.can_id());
+        let msg_id = cananddevice::MessageIndex::SetSetting
+            .frc_can_id_as(base.device_type_code(), base.device_number());
+        let body: [u8; 8] = SetSetting::new(
+            period.setting_index,
+            value,
+            cananddevice::types::SettingFlags {
+                ephemeral: false,
+                synch_hold: false,
+                synch_msg_count: 0,
+            },
+        )
+        .into();
+        self.fifocore.write_single(
+            &ReduxFIFOMessage::builder()
+                .bus(self.bus_id)
+                .id(msg_id.0)
+                .data(&body)
+                .build(),
+        )?;
+        self.control.charge(1);
+        if let Some(entry) = self.devices.get_mut(&key) {
+            entry
+                .setting_cache_mut()
+                .remove_entry(&period.setting_index);
+        }
+        Ok(period)
+    }
+
+    /// Asks the device for every frame period setting it has; read them back with
+    /// [`Self::frame_period_report`].
+    pub fn send_fetch_frame_periods(&mut self, id: u32) -> Result<(), FramePeriodError> {
+        let key = DeviceKey::from(FRCCanId(sanitize_id(id)));
+        let periods = frame_periods(key.dev_type)
+            .ok_or_else(|| FramePeriodError::UnknownProduct(key.pretty_str()))?;
+        for period in periods {
+            self.send_fetch_setting(id, period.setting_index)?;
+        }
+        Ok(())
+    }
+
+    /// Every periodic frame of the device at `id`, with the periods it has reported so far.
+    pub fn frame_period_report(&self, id: u32) -> Result<Vec<FramePeriodReport>, FramePeriodError> {
+        let key = DeviceKey::from(FRCCanId(sanitize_id(id)));
+        let periods = frame_periods(key.dev_type)
+            .ok_or_else(|| FramePeriodError::UnknownProduct(key.pretty_str()))?;
+        Ok(periods
+            .iter()
+            .map(|period| FramePeriodReport {
+                message: period.message,
+                message_index: period.message_index,
+                setting: period.setting,
+                setting_index: period.setting_index,
+                min_ms: period.min_ms,
+                max_ms: period.max_ms,
+                period_ms: self
+                    .setting_cache(id, period.setting_index)
+                    .map(|stg| FramePeriodSetting::period_ms(stg.data)),
+            })
+            .collect())
+    }
+}
@@ -3,8 +3,14 @@ use std::{
     time::{Duration, Instant},
 };
 
-use canandmessage::traits::CanandDeviceMessage;
-use fifocore::{FIFOCore, ReduxFIFOMessage, Session};
+use canandmessage::{
+    CanandMessageWrapper,
+    generic::{
+        CompositeError, CompositeSetting, CompositeValue, ReportSetting, WrapperSerializable,
+    },
+    traits::{CanandDeviceMessage, MessageIndexId},
+};
+use fifocore::{FIFOCore, FIFOInterface, ReduxFIFOMessage, ReduxFIFOSessionConfig, Session};
 use frc_can_id::{FRCCanId, FRCCanVendor, build_frc_can_id};
 use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
@@ -12,37 +18,71 @@ use serial_numer::SerialNumer;
 use tokio::task::JoinHandle;
 
 use crate::{
-    bus::device::{Device, DeviceKey, DeviceType},
-    log::log_error,
+    bus::{
+        control::ControlScheduler,
+        device::{Device, DeviceKey, DeviceType, RebootState},
+        power::PowerMonitor,
+        presence::{PresenceChange, PresenceLog},
+        setting::SettingWaiters,
+        setting_change::SettingChanges,
+    },
+    log::{log_error, log_warn},
+    profile::{BusProfiler, Profiles},
+    watchdog::Heartbeat,
 };
 
+pub mod control;
 pub mod device;
+pub mod device_lock;
+pub mod frame_period;
+pub mod power;
+pub mod presence;
+pub mod setting;
+pub mod setting_change;
+
+/// How long a device has to come back after we reboot it before it's given up on.
+pub const REBOOT_WINDOW: Duration = Duration::from_secs(10);
+/// How long a device back from a reboot gets to report its firmware version before we announce it
+/// without one.
+const FIRMWARE_REFETCH_GRACE: Duration = Duration::from_secs(1);
 
 const fn sanitize_id(id: u32) -> u32 {
     (id & build_frc_can_id(0x1f, 0x00, 0x0, 0x3f)) | 0x0e0000
 }
 
-const fn expand<T: Copy, const N: usize, const M: usize>(v: [T; N], p: T) -> [T; M] {
-    assert!(M > N);
-    let mut dest = [p; M];
-    dest.split_at_mut(N).0.copy_from_slice(&v);
-    dest
-}
-
 #[derive(Debug)]
 pub struct BusState {
     /// known devices
     pub devices: FxHashMap<DeviceKey, Device>,
     pub task: JoinHandle<()>,
-    pub fifocore: FIFOCore,
+    pub fifocore: Arc<dyn FIFOInterface>,
     pub bus_id: u16,
 
     pub stale_device: Option<DeviceKey>,
     pub enumerate_limiter: u32,
+    /// device arrival/departure log
+    pub presence: PresenceLog,
+    /// bus voltage and brownouts, from power distribution frames
+    pub power: PowerMonitor,
+    /// applies default settings profiles to new devices
+    pub profiler: BusProfiler,
+    /// budgets control-plane traffic and runs bulk setting fetches
+    pub control: ControlScheduler,
+    /// beaten by `task` each time it services the bus
+    pub heartbeat: Heartbeat,
+    /// setting requests waiting for the device to report back
+    pub settings: SettingWaiters,
+    /// settings devices changed on their own
+    pub setting_changes: SettingChanges,
 }
 
 impl BusState {
-    pub fn new(task: JoinHandle<()>, fifocore: FIFOCore, bus_id: u16) -> Self {
+    pub fn new(
+        task: JoinHandle<()>,
+        fifocore: Arc<dyn FIFOInterface>,
+        bus_id: u16,
+        profiles: Profiles,
+    ) -> Self {
         Self {
             devices: Default::default(),
             task,
@@ -50,16 +90,31 @@ impl BusState {
             bus_id,
             enumerate_limiter: 0,
             stale_device: None,
+            presence: PresenceLog::new(bus_id),
+            power: PowerMonitor::new(bus_id),
+            profiler: BusProfiler::new(bus_id, profiles),
+            control: ControlScheduler::new(bus_id),
+            heartbeat: Heartbeat::new(),
+            settings: SettingWaiters::default(),
+            setting_changes: SettingChanges::new(bus_id),
         }
     }
 
     pub fn ingest_buffer(&mut self, msgs: &fifocore::ReadBuffer) {
+        let now = Instant::now();
+        let mut returned = Vec::new();
         for msg in msgs.iter() {
             let can_id = FRCCanId::new(msg.id());
             if can_id.manufacturer() != FRCCanVendor::Redux {
                 return;
             }
 
+            self.setting_changes.note_host_frame(now, msg);
+            if msg.tx() {
+                // our own frames, echoed back; they say nothing about the device
+                continue;
+            }
+
             let device_key: DeviceKey = can_id.into();
             if let Some(stale) = self.stale_device && stale == device_key {
                 // REST has signaled that this device could be a ghost device (e.g. from can id change), so we'll ignore it this loop
@@ -72,15 +127,49 @@ impl BusState {
             let Some(dev) = self.devices.get_mut(&device_key) else {
                 return;
             };
+            let was_rebooting = dev.rebooting();
+            let report = ReportSetting::try_from_wrapper(&CanandMessageWrapper(*msg)).ok();
+            let previous = report.and_then(|r| dev.setting_cache().get(&r.index).copied());
             dev.handle_msg(msg);
+            if let Some(report) = report {
+                self.setting_changes.report(now, device_key, dev, &report, previous);
+                self.settings.report(device_key, report);
+            }
+            if was_rebooting && !dev.rebooting() {
+                returned.push(device_key);
+            }
         }
         self.stale_device = None;
+
+        // the device may have come back with new firmware
+        for key in returned {
+            let _ = self.send_fetch_setting(
+                key.can_id(),
+                canandmessage::cananddevice::types::Setting::FirmwareVersion as u8,
+            );
+        }
+    }
+
+    /// Takes in frames from the power distribution session; see [`power`].
+    pub fn ingest_power(&mut self, msgs: &fifocore::ReadBuffer) {
+        let now = Instant::now();
+        for msg in msgs.iter() {
+            self.power.ingest(now, msg);
+        }
     }
 
     pub fn poll(&mut self) {
         let now = Instant::now();
         self.devices.values_mut().for_each(|d| d.poll(now));
+        self.poll_reboots(now);
         self.devices.retain(|_, d| d.still_on_bus(now));
+        self.power.poll(now);
+        self.setting_changes.poll(now);
+        self.presence
+            .update(now, &self.devices, self.power.recent_brownout(now));
+        self.profiler
+            .poll(now, &mut self.devices, &*self.fifocore, &mut self.control);
+        self.control.poll(now, &mut self.devices, &*self.fifocore);
         if self.enumerate_limiter % 100 == 0 {
             // every half second or so we enumerate the bus.
             let _ = self.enumerate();
@@ -89,6 +178,46 @@ impl BusState {
         self.enumerate_limiter = self.enumerate_limiter.wrapping_add(1);
     }
 
+    /// Announces devices that came back from a reboot, and drops the ones that didn't in time.
+    fn poll_reboots(&mut self, now: Instant) {
+        let mut timed_out = Vec::new();
+        for (key, dev) in self.devices.iter_mut() {
+            match dev.reboot_state() {
+                Some(RebootState::Rebooting { since, deadline }) if now >= deadline => {
+                    let took = (now - since).as_secs_f32();
+                    self.presence
+                        .reboot(key, dev, PresenceChange::RebootTimedOut, Some(took));
+                    timed_out.push(*key);
+                }
+                Some(RebootState::Returned { since, at })
+                    if dev.firmware_version().is_some() || now - at >= FIRMWARE_REFETCH_GRACE =>
+                {
+                    let took = (at - since).as_secs_f32();
+                    self.presence
+                        .reboot(key, dev, PresenceChange::Returned, Some(took));
+                    dev.end_reboot();
+                }
+                _ => {}
+            }
+        }
+        for key in timed_out {
+            self.devices.remove(&key);
+        }
+    }
+
+    /// Keeps `key` and everything known about it while it reboots, instead of dropping it and
+    /// waiting for enumeration to find it again.
+    ///
+    /// Does nothing for a device we haven't seen.
+    pub fn expect_reboot(&mut self, key: DeviceKey) {
+        let Some(dev) = self.devices.get_mut(&key) else {
+            return;
+        };
+        dev.begin_reboot(Instant::now(), REBOOT_WINDOW);
+        self.presence
+            .reboot(&key, dev, PresenceChange::Rebooting, None);
+    }
+
     pub fn clear_known_devices(&mut self) {
         self.devices.clear();
     }
@@ -121,6 +250,7 @@ impl BusState {
         msg.0.bus_id = self.bus_id;
 
         self.fifocore.write_single(&msg)?;
+        self.control.charge(1);
         self.enumerate()?;
         // If we know the device exists, we set the known serial number of the device to the one we arbitrate with.
         let key = DeviceKey::from(FRCCanId(id));
@@ -132,17 +262,14 @@ impl BusState {
     }
 
     pub fn enumerate(&self) -> Result<(), fifocore::error::Error> {
-        let msg = ReduxFIFOMessage::id_data(
-            self.bus_id,
-            frc_can_id::REDUX_BROADCAST_ENUMERATE,
-            [0u8; _],
-            0,
-            0,
-        );
+        let msg = ReduxFIFOMessage::builder()
+            .bus(self.bus_id)
+            .id(frc_can_id::REDUX_BROADCAST_ENUMERATE)
+            .build();
         self.fifocore.write_single(&msg)
     }
 
-    pub fn blink(&self, id: u32, value: u8) -> Result<(), fifocore::error::Error> {
+    pub fn blink(&mut self, id: u32, value: u8) -> Result<(), fifocore::error::Error> {
         let id = sanitize_id(id);
         let mut msg: canandmessage::CanandMessageWrapper<ReduxFIFOMessage> =
             canandmessage::cananddevice::Message::PartyMode { party_level: value }
@@ -153,6 +280,7 @@ impl BusState {
                 })?;
         msg.0.bus_id = self.bus_id;
         self.fifocore.write_single(&msg)?;
+        self.control.charge(1);
         Ok(())
     }
 
@@ -175,6 +303,8 @@ impl BusState {
             })?;
         msg.0.bus_id = self.bus_id;
         self.fifocore.write_single(&msg)?;
+        // the device reports the new value back
+        self.control.charge(2);
         // If we are setting an id on an arbitrated device, we remove its serial numer from the conflict pool.
         // If we are not, we move the device from the known device pool and leave it to enumeration to pick up the device again.
         let key = DeviceKey::from(FRCCanId(id));
@@ -197,62 +327,145 @@ impl BusState {
     pub fn send_fetch_setting(&mut self, id: u32, index: u8) -> Result<(), fifocore::error::Error> {
         let id = FRCCanId(sanitize_id(id));
 
-        let fetch_setting_id = build_frc_can_id(
-            id.device_type_code(),
-            id.manufacturer_code(),
-            canandmessage::cananddevice::MessageIndex::SettingCommand as u16,
-            id.device_number(),
-        );
+        let fetch_setting_id = canandmessage::cananddevice::MessageIndex::SettingCommand
+            .frc_can_id_as(id.device_type_code(), id.device_number())
+            .0;
 
-        let msg = expand(
-            [
+        let msg = ReduxFIFOMessage::builder()
+            .bus(self.bus_id)
+            .id(fetch_setting_id)
+            .data(&[
                 canandmessage::cananddevice::types::SettingCommand::FetchSettingValue as u8,
                 index,
-            ],
-            0,
-        );
-        let msg = ReduxFIFOMessage::id_data(self.bus_id, fetch_setting_id, msg, 2, 0);
+            ])
+            .build();
         let key = DeviceKey::from(id);
         if let Some(entry) = self.devices.get_mut(&key) {
             entry.setting_cache_mut().remove_entry(&index);
         }
         self.fifocore.write_single(&msg)?;
+        self.control.charge(2);
+        Ok(())
+    }
+
+    /// Resets every resettable setting to its factory default. The device reports them all back.
+    pub fn send_factory_reset(&mut self, id: u32) -> Result<(), fifocore::error::Error> {
+        let id = FRCCanId(sanitize_id(id));
+
+        let setting_command_id = canandmessage::cananddevice::MessageIndex::SettingCommand
+            .frc_can_id_as(id.device_type_code(), id.device_number())
+            .0;
+
+        let msg = ReduxFIFOMessage::builder()
+            .bus(self.bus_id)
+            .id(setting_command_id)
+            .data(&[canandmessage::cananddevice::types::SettingCommand::ResetFactoryDefault as u8])
+            .build();
+        let key = DeviceKey::from(id);
+        if let Some(entry) = self.devices.get_mut(&key) {
+            entry.setting_cache_mut().clear();
+        }
+        self.fifocore.write_single(&msg)?;
+        self.control.charge(2);
         Ok(())
     }
 
-    pub fn send_set_name(&mut self, id: u32, name: &str) -> Result<(), fifocore::error::Error> {
+    /// Writes the raw `value` of one setting.
+    pub fn send_set_setting(
+        &mut self,
+        id: u32,
+        index: u8,
+        value: [u8; 6],
+    ) -> Result<(), fifocore::error::Error> {
         let id = FRCCanId(sanitize_id(id));
+        let msg_id = canandmessage::cananddevice::MessageIndex::SetSetting
+            .frc_can_id_as(id.device_type_code(), id.device_number())
+            .0;
+        let body: [u8; 8] = canandmessage::generic::SetSetting::new(
+            index,
+            value,
+            canandmessage::cananddevice::types::SettingFlags {
+                ephemeral: false,
+                synch_hold: false,
+                synch_msg_count: 0,
+            },
+        )
+        .into();
+        self.fifocore.write_single(
+            &ReduxFIFOMessage::builder()
+                .bus(self.bus_id)
+                .id(msg_id)
+                .data(&body)
+                .build(),
+        )?;
+        // the device reports the new value back
+        self.control.charge(2);
+        if let Some(entry) = self.devices.get_mut(&DeviceKey::from(id)) {
+            entry.setting_cache_mut().remove_entry(&index);
+        }
+        Ok(())
+    }
 
-        let set_setting_id = build_frc_can_id(
-            id.device_type_code(),
-            id.manufacturer_code(),
-            canandmessage::cananddevice::MessageIndex::SetSetting as u16,
-            id.device_number(),
-        );
-        let mut name_buf = [0_u8; 18];
-        let name_len = name.as_bytes().len().min(name_buf.len());
-        name_buf[..name_len].copy_from_slice(&name.as_bytes()[..name_len]);
-        let name_indexes = [
-            (canandmessage::cananddevice::types::Setting::Name0 as u8, 0_usize),
-            (canandmessage::cananddevice::types::Setting::Name1 as u8, 6_usize),
-            (canandmessage::cananddevice::types::Setting::Name2 as u8, 12_usize),
-        ];
+    /// Writes every part of a composite setting, such as the device name, as one transaction.
+    pub fn send_set_composite(
+        &mut self,
+        id: u32,
+        composite: &CompositeSetting,
+        value: &[u8],
+    ) -> Result<(), fifocore::error::Error> {
+        let id = FRCCanId(sanitize_id(id));
+        let txn = composite.transaction(value).map_err(|e| {
+            log_error!("Could not build {} write: {e:?}", composite.name);
+            match e {
+                CompositeError::TooLong => fifocore::error::Error::DataTooLong,
+                _ => fifocore::error::Error::BusWriteFail,
+            }
+        })?;
 
+        let (device_type, device_number) = (id.device_type_code(), id.device_number());
         let key = DeviceKey::from(id);
-        for (stg_idx, chunk_start) in name_indexes {
-            let mut body = [0_u8; 8];
-            body[0] = stg_idx;
-            body[1..7].copy_from_slice(&name_buf[chunk_start..chunk_start + 6]);
-            let msg = ReduxFIFOMessage::id_data(self.bus_id, set_setting_id, expand(body, 0), 8, 0);
+        for setting in txn
+            .messages()
+            .map_err(|_| fifocore::error::Error::BusWriteFail)?
+        {
+            let mut msg: canandmessage::CanandMessageWrapper<ReduxFIFOMessage> = setting
+                .try_into_wrapper(device_type, device_number)
+                .map_err(|e| {
+                    log_error!("Could not serialize {} message: {e}", composite.name);
+                    fifocore::error::Error::BusWriteFail
+                })?;
+            msg.0.bus_id = self.bus_id;
             self.fifocore.write_single(&msg)?;
+            self.control.charge(2);
             if let Some(entry) = self.devices.get_mut(&key) {
-                entry.setting_cache_mut().remove_entry(&stg_idx);
+                entry.setting_cache_mut().remove_entry(&setting.index);
             }
         }
 
         Ok(())
     }
 
+    /// Asks the device for every part of a composite setting; read it back with [`Self::composite_value`].
+    pub fn send_fetch_composite(
+        &mut self,
+        id: u32,
+        composite: &CompositeSetting,
+    ) -> Result<(), fifocore::error::Error> {
+        for &index in composite.parts {
+            self.send_fetch_setting(id, index)?;
+        }
+        Ok(())
+    }
+
+    /// Joins the cached parts of a composite setting.
+    pub fn composite_value(
+        &self,
+        id: u32,
+        composite: &CompositeSetting,
+    ) -> Result<CompositeValue, CompositeError> {
+        composite.join(|index| self.setting_cache(id, index).map(|stg| stg.data))
+    }
+
     pub fn send_reboot(&mut self, id: u32, bootloader: bool) -> Result<(), fifocore::error::Error> {
         let id = FRCCanId(sanitize_id(id));
         const BOOT_NORMALLY: rdxota_protocol::otav2::Command = rdxota_protocol::otav2::Command::SysCtl([
@@ -262,19 +475,22 @@ impl BusState {
             rdxota_protocol::otav2::index::sysctl::BOOT_TO_DFU, 0, 0, 0, 0, 0, 0
         ]);
 
-        let message_id = build_frc_can_id(
-            id.device_type_code(),
-            id.manufacturer_code(),
-            canandmessage::cananddevice::MessageIndex::OtaToDevice as u16,
-            id.device_number(),
-        );
-        let msg = ReduxFIFOMessage::id_data(self.bus_id, message_id, expand::<_, 8, _>(if bootloader {
+        let message_id = canandmessage::cananddevice::MessageIndex::OtaToDevice
+            .frc_can_id_as(id.device_type_code(), id.device_number())
+            .0;
+        let command: [u8; 8] = if bootloader {
             BOOT_TO_DFU.into()
         } else {
             BOOT_NORMALLY.into()
-        }, 0), 8, 0);
+        };
+        let msg = ReduxFIFOMessage::builder()
+            .bus(self.bus_id)
+            .id(message_id)
+            .data(&command)
+            .build();
         self.fifocore.write_single(&msg)?;
-        self.devices.remove(&id.into());
+        self.control.charge(1);
+        self.expect_reboot(id.into());
 
         Ok(())
     }
@@ -293,15 +509,128 @@ impl BusState {
     }
 }
 
+impl Drop for BusState {
+    fn drop(&mut self) {
+        self.presence.log_summary();
+    }
+}
+
+/// Looks up a composite setting shared by all Redux devices, e.g. `name`.
+pub fn composite_setting(name: &str) -> Option<&'static CompositeSetting> {
+    canandmessage::cananddevice::COMPOSITE_SETTINGS
+        .iter()
+        .find(|composite| composite.name.eq_ignore_ascii_case(name))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct FetchSetting {
     pub index: u8,
     pub data: [u8; 6],
 }
 
+/// Opens a device session on `bus_id` and starts its polling task.
+///
+/// `guard` must be the locked contents of `bus_sessions`; it's released before the task starts.
+pub fn open_bus_state(
+    mut guard: parking_lot::MutexGuard<'_, FxHashMap<u16, BusState>>,
+    bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+    fifocore: &FIFOCore,
+    bus_id: u16,
+    profiles: &Profiles,
+) -> Result<(), fifocore::error::Error> {
+    let session = open_device_session(fifocore, bus_id)?;
+    let power_session = open_power_session(fifocore, bus_id);
+    let (start_send, start_gate) = tokio::sync::oneshot::channel();
+
+    let task = tokio::task::spawn(bus_session(
+        start_gate,
+        session,
+        power_session,
+        bus_sessions.clone(),
+    ));
+    guard.insert(bus_id, BusState::new(task, fifocore.handle(), bus_id, profiles.clone()));
+    drop(guard);
+    let _ = start_send.send(());
+    Ok(())
+}
+
+/// Asks every bus in `bus_ids` to enumerate, first opening a device session on the ones that
+/// don't have one yet.
+///
+/// Returns a line for each bus that couldn't be enumerated.
+pub fn enumerate_buses(
+    bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+    fifocore: &FIFOCore,
+    bus_ids: &[u16],
+    profiles: &Profiles,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for &bus_id in bus_ids {
+        let guard = bus_sessions.lock();
+        let result = match guard.get(&bus_id) {
+            Some(state) => state.enumerate(),
+            None => open_bus_state(guard, bus_sessions, fifocore, bus_id, profiles).and_then(|_| {
+                bus_sessions
+                    .lock()
+                    .get(&bus_id)
+                    .map_or(Ok(()), |state| state.enumerate())
+            }),
+        };
+        if let Err(e) = result {
+            log_warn!("Couldn't enumerate bus {bus_id}: {e}");
+            errors.push(format!("bus {bus_id}: {e}"));
+        }
+    }
+    errors
+}
+
+/// Replaces the polling task of `state` with a fresh one on a new session, keeping everything
+/// already learned about the bus.
+///
+/// Callers hold the `bus_sessions` lock that `state` lives in, so the new task can't start
+/// polling before that's released.
+pub fn restart_bus_task(
+    state: &mut BusState,
+    bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+) -> Result<(), fifocore::error::Error> {
+    state.task.abort();
+    let session = open_device_session(&*state.fifocore, state.bus_id)?;
+    let power_session = open_power_session(&*state.fifocore, state.bus_id);
+    let (start_send, start_gate) = tokio::sync::oneshot::channel();
+    let _ = start_send.send(());
+    state.heartbeat.beat();
+    state.task = tokio::task::spawn(bus_session(
+        start_gate,
+        session,
+        power_session,
+        bus_sessions.clone(),
+    ));
+    Ok(())
+}
+
+fn open_device_session(
+    fifocore: &dyn FIFOInterface,
+    bus_id: u16,
+) -> Result<Session, fifocore::error::Error> {
+    let mut config = ReduxFIFOSessionConfig::new(0x0e0000, 0xff0000);
+    // our own setting writes, whoever made them, tell solicited setting reports apart
+    config.echo_tx = true;
+    fifocore.open_managed_session(bus_id, 256, config)
+}
+
+/// Opens a session on the PDP/PDH frames of `bus_id`, which the bus does without if it can't.
+fn open_power_session(fifocore: &dyn FIFOInterface, bus_id: u16) -> Option<Session> {
+    let (id, mask) = power::POWER_FILTER;
+    fifocore
+        .open_managed_session(bus_id, 64, ReduxFIFOSessionConfig::new(id, mask))
+        .inspect_err(|e| log_warn!("[bus {bus_id}] Not watching bus voltage: {e}"))
+        .ok()
+}
+
 pub async fn bus_session(
     start_gate: tokio::sync::oneshot::Receiver<()>,
     session: Session,
+    power_session: Option<Session>,
     bus_sessions: Arc<Mutex<FxHashMap<u16, BusState>>>,
 ) {
     // we need to wait for the bus session map to be populated before the actual logic of this task starts.
@@ -309,6 +638,7 @@ pub async fn bus_session(
 
     let bus = session.session().bus_id();
     let mut buffer = session.read_buffer(256);
+    let mut power_buffer = power_session.as_ref().map(|session| session.read_buffer(64));
     let mut interval = tokio::time::interval(Duration::from_millis(5));
     loop {
         interval.tick().await;
@@ -317,11 +647,19 @@ pub async fn bus_session(
             log_error!("[ReduxCore] Read session failed: {e}");
             return;
         }
+        let power_read = match (&power_session, &mut power_buffer) {
+            (Some(session), Some(buffer)) => session.read_barrier(buffer).is_ok(),
+            _ => false,
+        };
         let mut bus_ses = bus_sessions.lock();
         let Some(state) = bus_ses.get_mut(&bus) else {
             return;
         };
+        state.heartbeat.beat();
         state.ingest_buffer(&buffer);
+        if let (true, Some(buffer)) = (power_read, &power_buffer) {
+            state.ingest_power(buffer);
+        }
         state.poll();
         drop(bus_ses);
     }
@@ -0,0 +1,322 @@
+//! Bus voltage from power distribution status frames.
+//!
+//! A lot of "device dropped off the bus" reports turn out to be the robot browning out, and the PDP
+//! or PDH is already reporting the battery voltage on the same bus. This watches the voltage in the
+//! CTRE PDP's status 3 frame and the REV PDH's status 4 frame (which also carries the PDH's own
+//! brownout fault), logs when it sags to brownout levels and recovers, and broadcasts those as
+//! [`PowerEvent`]s to [subscribers](subscribe). Devices lost during or shortly after a brownout
+//! have it noted on their [`PresenceEvent`](super::presence::PresenceEvent).
+
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use fifocore::ReduxFIFOMessage;
+use frc_can_id::{FRCCanDeviceType, FRCCanId, FRCCanVendor};
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::log::{log_info, log_warn};
+
+/// Voltage the roboRIO 1 browns out at; the roboRIO 2 defaults to a little under it.
+pub const BROWNOUT_VOLTS: f32 = 6.8;
+/// Voltage a brownout is over at, a bit above [`BROWNOUT_VOLTS`] so a sagging battery doesn't
+/// flap in and out of one.
+const RECOVERED_VOLTS: f32 = 7.5;
+/// How long after a brownout a lost device is still put down to it.
+pub const BROWNOUT_BLAME_WINDOW: Duration = Duration::from_secs(2);
+/// Power distribution that hasn't reported for this long is dropped.
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// API id of the CTRE PDP's status 3 frame, which has the bus voltage.
+const PDP_STATUS_3: u16 = 0x052;
+/// API id of the REV PDH's status 4 frame, which has the bus voltage and fault flags.
+const PDH_STATUS_4: u16 = 0x064;
+
+/// Filter matching every power distribution frame, for a session to read them with.
+pub const POWER_FILTER: (u32, u32) = (
+    (FRCCanDeviceType::PowerDistributionModule.as_u8() as u32) << 24,
+    0x1f << 24,
+);
+
+/// What a power distribution status frame said.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerReading {
+    /// e.g. `"PDH"`
+    pub product: &'static str,
+    pub device_number: u8,
+    pub volts: f32,
+    /// Set by the PDH while it's browned out itself
+    pub brownout_fault: bool,
+}
+
+/// Decodes the voltage out of a PDP or PDH status frame, or [`None`] for any other frame.
+pub fn decode(msg: &ReduxFIFOMessage) -> Option<PowerReading> {
+    let id = FRCCanId::new(msg.id());
+    if msg.short_id() || id.device_type() != FRCCanDeviceType::PowerDistributionModule {
+        return None;
+    }
+    let data = msg.data_slice();
+    match (id.manufacturer(), id.api_index()) {
+        (FRCCanVendor::CtrElectronics, PDP_STATUS_3) if data.len() >= 8 => Some(PowerReading {
+            product: "PDP",
+            device_number: id.device_number(),
+            // 50 mV per bit from 4 V
+            volts: data[6] as f32 * 0.05 + 4.0,
+            brownout_fault: false,
+        }),
+        (FRCCanVendor::Rev, PDH_STATUS_4) if data.len() >= 3 => Some(PowerReading {
+            product: "PDH",
+            device_number: id.device_number(),
+            // 12 bits of 1/128 V
+            volts: (u16::from_le_bytes([data[0], data[1]]) & 0xfff) as f32 / 128.0,
+            brownout_fault: data[2] & 0x1 != 0,
+        }),
+        _ => None,
+    }
+}
+
+/// A brownout starting or ending.
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerEvent {
+    pub bus_id: u16,
+    /// Power distribution that reported it, e.g. `"PDH #1"`
+    pub source: String,
+    pub change: PowerChange,
+    /// Lowest voltage seen during the brownout so far
+    pub min_volts: f32,
+    /// Seconds the brownout lasted, once it's over
+    pub duration_s: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerChange {
+    Brownout,
+    Recovered,
+}
+
+static EVENTS: LazyLock<broadcast::Sender<PowerEvent>> = LazyLock::new(|| broadcast::channel(64).0);
+
+/// Listens to brownouts on every bus from now on.
+pub fn subscribe() -> broadcast::Receiver<PowerEvent> {
+    EVENTS.subscribe()
+}
+
+/// A brownout a device loss is put down to, noted on its presence event.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BrownoutNote {
+    /// Seconds between the start of the brownout and the loss
+    pub after_s: f32,
+    pub min_volts: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Brownout {
+    since: Instant,
+    /// When it ended, if it has
+    until: Option<Instant>,
+    min_volts: f32,
+}
+
+/// What the REST API reports about a bus's power.
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerStatus {
+    /// Latest voltage from each power distribution, keyed like `"PDH #1"`
+    pub volts: FxHashMap<String, f32>,
+    pub browned_out: bool,
+    /// Brownouts since the bus session was opened
+    pub brownouts: u32,
+    /// Lowest voltage seen since the bus session was opened
+    pub min_volts: Option<f32>,
+}
+
+/// Tracks power distribution readings and brownouts on a single bus.
+#[derive(Debug)]
+pub struct PowerMonitor {
+    bus_id: u16,
+    /// Latest reading of each power distribution and when it came in
+    sources: FxHashMap<String, (PowerReading, Instant)>,
+    brownout: Option<Brownout>,
+    brownouts: u32,
+    min_volts: Option<f32>,
+}
+
+impl PowerMonitor {
+    pub fn new(bus_id: u16) -> Self {
+        Self {
+            bus_id,
+            sources: FxHashMap::default(),
+            brownout: None,
+            brownouts: 0,
+            min_volts: None,
+        }
+    }
+
+    pub fn ingest(&mut self, now: Instant, msg: &ReduxFIFOMessage) {
+        let Some(reading) = decode(msg) else {
+            return;
+        };
+        let source = format!("{} #{}", reading.product, reading.device_number);
+        self.min_volts = Some(
+            self.min_volts
+                .map_or(reading.volts, |v| v.min(reading.volts)),
+        );
+
+        let low = reading.volts < BROWNOUT_VOLTS || reading.brownout_fault;
+        match &mut self.brownout {
+            Some(brownout) if brownout.until.is_none() => {
+                brownout.min_volts = brownout.min_volts.min(reading.volts);
+                if !low && reading.volts >= RECOVERED_VOLTS {
+                    brownout.until = Some(now);
+                    let duration_s = (now - brownout.since).as_secs_f32();
+                    log_info!(
+                        "[bus {}] recovered from brownout after {duration_s:.2}s: {source} at \
+                         {:.2} V, {:.2} V at worst",
+                        self.bus_id,
+                        reading.volts,
+                        brownout.min_volts
+                    );
+                    let _ = EVENTS.send(PowerEvent {
+                        bus_id: self.bus_id,
+                        source: source.clone(),
+                        change: PowerChange::Recovered,
+                        min_volts: brownout.min_volts,
+                        duration_s: Some(duration_s),
+                    });
+                }
+            }
+            _ if low => {
+                self.brownouts = self.brownouts.saturating_add(1);
+                self.brownout = Some(Brownout {
+                    since: now,
+                    until: None,
+                    min_volts: reading.volts,
+                });
+                log_warn!(
+                    "[bus {}] brownout: {source} at {:.2} V{}",
+                    self.bus_id,
+                    reading.volts,
+                    if reading.brownout_fault {
+                        " (PDH brownout fault)"
+                    } else {
+                        ""
+                    }
+                );
+                let _ = EVENTS.send(PowerEvent {
+                    bus_id: self.bus_id,
+                    source: source.clone(),
+                    change: PowerChange::Brownout,
+                    min_volts: reading.volts,
+                    duration_s: None,
+                });
+            }
+            _ => {}
+        }
+        self.sources.insert(source, (reading, now));
+    }
+
+    /// Forgets power distribution that stopped reporting.
+    pub fn poll(&mut self, now: Instant) {
+        self.sources
+            .retain(|_, (_, seen)| now - *seen < SOURCE_TIMEOUT);
+    }
+
+    /// The brownout going on at `now` or that ended within [`BROWNOUT_BLAME_WINDOW`] of it.
+    pub fn recent_brownout(&self, now: Instant) -> Option<BrownoutNote> {
+        let brownout = self.brownout?;
+        if brownout
+            .until
+            .is_some_and(|until| now - until > BROWNOUT_BLAME_WINDOW)
+        {
+            return None;
+        }
+        Some(BrownoutNote {
+            after_s: (now - brownout.since).as_secs_f32(),
+            min_volts: brownout.min_volts,
+        })
+    }
+
+    pub fn status(&self) -> PowerStatus {
+        PowerStatus {
+            volts: self
+                .sources
+                .iter()
+                .map(|(source, (reading, _))| (source.clone(), reading.volts))
+                .collect(),
+            browned_out: self.brownout.is_some_and(|b| b.until.is_none()),
+            brownouts: self.brownouts,
+            min_volts: self.min_volts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use frc_can_id::build_frc_can_id;
+
+    use super::*;
+
+    fn pdh_status_4(volts: f32, brownout_fault: bool) -> ReduxFIFOMessage {
+        let raw = ((volts * 128.0) as u16).to_le_bytes();
+        ReduxFIFOMessage::builder()
+            .id(build_frc_can_id(8, 5, PDH_STATUS_4, 1))
+            .data(&[raw[0], raw[1], brownout_fault as u8, 0, 0, 0, 0, 0])
+            .build()
+    }
+
+    #[test]
+    fn test_decode() {
+        let pdp = ReduxFIFOMessage::builder()
+            .id(build_frc_can_id(8, 4, PDP_STATUS_3, 0))
+            .data(&[0, 0, 0, 0, 0, 0, 180, 30])
+            .build();
+        let reading = decode(&pdp).unwrap();
+        assert_eq!(reading.product, "PDP");
+        assert!((reading.volts - 13.0).abs() < 1e-3);
+
+        let reading = decode(&pdh_status_4(12.5, true)).unwrap();
+        assert_eq!((reading.product, reading.device_number), ("PDH", 1));
+        assert_eq!(reading.volts, 12.5);
+        assert!(reading.brownout_fault);
+
+        let spark = ReduxFIFOMessage::builder()
+            .id(build_frc_can_id(2, 5, PDH_STATUS_4, 1))
+            .data(&[0; 8])
+            .build();
+        assert!(decode(&spark).is_none());
+    }
+
+    #[test]
+    fn test_brownout_blame() {
+        let start = Instant::now();
+        let mut power = PowerMonitor::new(0);
+        power.ingest(start, &pdh_status_4(12.0, false));
+        assert!(power.recent_brownout(start).is_none());
+
+        power.ingest(start + Duration::from_millis(20), &pdh_status_4(6.5, false));
+        power.ingest(start + Duration::from_millis(40), &pdh_status_4(7.0, false));
+        let status = power.status();
+        assert!(status.browned_out);
+        assert_eq!(status.brownouts, 1);
+
+        // still too low to count as recovered
+        let recovered = start + Duration::from_millis(60);
+        power.ingest(recovered, &pdh_status_4(7.2, false));
+        assert!(power.status().browned_out);
+        power.ingest(recovered, &pdh_status_4(11.0, false));
+        assert!(!power.status().browned_out);
+
+        let note = power
+            .recent_brownout(recovered + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(note.min_volts, 6.5);
+        assert!(
+            power
+                .recent_brownout(recovered + BROWNOUT_BLAME_WINDOW + Duration::from_millis(1))
+                .is_none()
+        );
+    }
+}
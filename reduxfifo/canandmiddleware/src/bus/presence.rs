@@ -0,0 +1,460 @@
+//! Concise device arrival/departure logging.
+//!
+//! The bus task sees every packet, but what people actually want out of a post-match log is
+//! "which devices showed up, which ones dropped off, and when". This module tracks that per device,
+//! rate-limits the resulting log lines so a flapping device can't flood the log, and prints a
+//! summary table when the bus session is closed. Every arrival and departure, logged or not, is
+//! also broadcast as a [`PresenceEvent`] to anyone who [subscribed](subscribe), as are devices
+//! going down for a reboot we asked for and coming back (or not) afterwards.
+
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use canandmessage::cananddevice;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use serial_numer::SerialNumer;
+use tokio::sync::broadcast;
+
+use crate::{
+    bus::{
+        device::{Device, DeviceKey, firmware_str, serial_str},
+        power::BrownoutNote,
+    },
+    log::{log_info, log_warn},
+};
+
+/// Minimum time between two presence log lines for the same device.
+const LOG_HOLDOFF: Duration = Duration::from_secs(10);
+/// How long we wait on an enumerate response before announcing a device without a serial numer.
+const IDENTIFY_GRACE: Duration = Duration::from_secs(1);
+
+/// A device arrival, departure, or reboot.
+#[derive(Debug, Clone, Serialize)]
+pub struct PresenceEvent {
+    pub bus_id: u16,
+    pub device: String,
+    pub change: PresenceChange,
+    pub serial: String,
+    pub firmware: String,
+    /// Seconds the device had been present, for departures
+    pub present_for_s: Option<f32>,
+    /// Seconds since the reboot was sent, for returns and reboot timeouts
+    pub reboot_s: Option<f32>,
+    /// The brownout going on when the device was lost, or that had just ended; see
+    /// [`power`](super::power)
+    pub brownout: Option<BrownoutNote>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceChange {
+    Arrived,
+    Lost,
+    /// Sent a reboot; the device keeps its identity while it's down
+    Rebooting,
+    /// Came back from a reboot, with its firmware version re-fetched
+    Returned,
+    /// Didn't come back from a reboot in time, and is dropped
+    RebootTimedOut,
+}
+
+static EVENTS: LazyLock<broadcast::Sender<PresenceEvent>> =
+    LazyLock::new(|| broadcast::channel(64).0);
+
+/// Listens to presence events from every bus from now on.
+pub fn subscribe() -> broadcast::Receiver<PresenceEvent> {
+    EVENTS.subscribe()
+}
+
+#[derive(Debug, Clone)]
+struct PresenceRecord {
+    first_seen: Instant,
+    /// Most recent time the device was announced as present
+    last_arrival: Instant,
+    present: bool,
+    /// Set while the device is on the bus but hasn't been announced yet
+    pending_since: Option<Instant>,
+    arrivals: u32,
+    departures: u32,
+    serial: Option<SerialNumer>,
+    firmware: Option<cananddevice::types::FirmwareVersion>,
+    /// Earliest time the next log line for this device may be emitted
+    next_log: Instant,
+    /// Events that happened during the holdoff and were not logged
+    suppressed: u32,
+}
+
+impl PresenceRecord {
+    /// Returns the number of suppressed events to report if a line should be logged now.
+    fn take_log_slot(&mut self, now: Instant) -> Option<u32> {
+        if now < self.next_log {
+            self.suppressed = self.suppressed.saturating_add(1);
+            return None;
+        }
+        self.next_log = now + LOG_HOLDOFF;
+        Some(core::mem::take(&mut self.suppressed))
+    }
+
+    /// Returns the number of suppressed events if the holdoff they were suppressed by is over, so
+    /// the state they left the device in can be logged.
+    fn take_held_back(&mut self, now: Instant) -> Option<u32> {
+        if self.suppressed == 0 || now < self.next_log {
+            return None;
+        }
+        self.take_log_slot(now)
+    }
+}
+
+/// Tracks device presence on a single bus.
+#[derive(Debug)]
+pub struct PresenceLog {
+    bus_id: u16,
+    opened: Instant,
+    records: FxHashMap<DeviceKey, PresenceRecord>,
+}
+
+impl PresenceLog {
+    pub fn new(bus_id: u16) -> Self {
+        Self {
+            bus_id,
+            opened: Instant::now(),
+            records: FxHashMap::default(),
+        }
+    }
+
+    /// Diffs the currently known devices against what we've previously announced.
+    ///
+    /// `brownout` is noted on the devices found lost, as what likely took them off the bus.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        devices: &FxHashMap<DeviceKey, Device>,
+        brownout: Option<BrownoutNote>,
+    ) {
+        for (key, dev) in devices.iter() {
+            // give enumerate a moment to tell us who this is before we say anything
+            let identified = dev.serial_numer().is_some();
+            let record = self.records.entry(*key).or_insert_with(|| PresenceRecord {
+                first_seen: now,
+                last_arrival: now,
+                present: false,
+                pending_since: None,
+                arrivals: 0,
+                departures: 0,
+                serial: None,
+                firmware: None,
+                next_log: now,
+                suppressed: 0,
+            });
+            if let Some(serial) = dev.serial_numer() {
+                record.serial = Some(serial);
+            }
+            if let Some(fw) = dev.firmware_version() {
+                record.firmware = Some(fw);
+            }
+
+            if record.present {
+                continue;
+            }
+            let pending_since = *record.pending_since.get_or_insert(now);
+            if !identified && now - pending_since < IDENTIFY_GRACE {
+                continue;
+            }
+            record.pending_since = None;
+            record.present = true;
+            record.arrivals = record.arrivals.saturating_add(1);
+            record.last_arrival = now;
+            if let Some(suppressed) = record.take_log_slot(now) {
+                log_info!(
+                    "[bus {}] device {} seen: serial {}, fw {}{}",
+                    self.bus_id,
+                    key.pretty_str(),
+                    serial_str(record.serial),
+                    firmware_str(record.firmware),
+                    suppressed_str(suppressed)
+                );
+            }
+            let _ = EVENTS.send(PresenceEvent {
+                bus_id: self.bus_id,
+                device: key.pretty_str(),
+                change: PresenceChange::Arrived,
+                serial: serial_str(record.serial),
+                firmware: firmware_str(record.firmware),
+                present_for_s: None,
+                reboot_s: None,
+                brownout: None,
+            });
+        }
+
+        for (key, record) in self.records.iter_mut() {
+            if devices.contains_key(key) {
+                continue;
+            }
+            record.pending_since = None;
+            if !record.present {
+                continue;
+            }
+            record.present = false;
+            record.departures = record.departures.saturating_add(1);
+            if let Some(suppressed) = record.take_log_slot(now) {
+                log_warn!(
+                    "[bus {}] device {} lost after {:.1}s: serial {}, fw {}{}{}",
+                    self.bus_id,
+                    key.pretty_str(),
+                    (now - record.last_arrival).as_secs_f32(),
+                    serial_str(record.serial),
+                    firmware_str(record.firmware),
+                    brownout.map_or(String::new(), |b| format!(
+                        ", {:.1}s into a brownout to {:.2} V",
+                        b.after_s, b.min_volts
+                    )),
+                    suppressed_str(suppressed)
+                );
+            }
+            let _ = EVENTS.send(PresenceEvent {
+                bus_id: self.bus_id,
+                device: key.pretty_str(),
+                change: PresenceChange::Lost,
+                serial: serial_str(record.serial),
+                firmware: firmware_str(record.firmware),
+                present_for_s: Some((now - record.last_arrival).as_secs_f32()),
+                reboot_s: None,
+                brownout,
+            });
+        }
+
+        // a device that changed during its holdoff and then settled would otherwise never have its
+        // last state logged
+        for (key, record) in self.records.iter_mut() {
+            let Some(suppressed) = record.take_held_back(now) else {
+                continue;
+            };
+            log_info!(
+                "[bus {}] device {} {} after the holdoff: serial {}, fw {}{}",
+                self.bus_id,
+                key.pretty_str(),
+                if record.present { "present" } else { "lost" },
+                serial_str(record.serial),
+                firmware_str(record.firmware),
+                suppressed_str(suppressed)
+            );
+        }
+    }
+
+    /// Logs and broadcasts a step in the reboot of `key`. `reboot_s` is the time since the reboot was
+    /// sent, for everything but [`PresenceChange::Rebooting`].
+    pub fn reboot(
+        &self,
+        key: &DeviceKey,
+        dev: &Device,
+        change: PresenceChange,
+        reboot_s: Option<f32>,
+    ) {
+        let serial = dev
+            .serial_numer()
+            .or_else(|| self.records.get(key).and_then(|record| record.serial));
+        match change {
+            PresenceChange::Rebooting => log_info!(
+                "[bus {}] device {} rebooting: serial {}",
+                self.bus_id,
+                key.pretty_str(),
+                serial_str(serial)
+            ),
+            PresenceChange::Returned => log_info!(
+                "[bus {}] device {} back from reboot after {:.1}s: serial {}, fw {}",
+                self.bus_id,
+                key.pretty_str(),
+                reboot_s.unwrap_or_default(),
+                serial_str(serial),
+                firmware_str(dev.firmware_version())
+            ),
+            _ => log_warn!(
+                "[bus {}] device {} did not come back from reboot after {:.1}s: serial {}",
+                self.bus_id,
+                key.pretty_str(),
+                reboot_s.unwrap_or_default(),
+                serial_str(serial)
+            ),
+        }
+        let _ = EVENTS.send(PresenceEvent {
+            bus_id: self.bus_id,
+            device: key.pretty_str(),
+            change,
+            serial: serial_str(serial),
+            firmware: firmware_str(dev.firmware_version()),
+            present_for_s: None,
+            reboot_s,
+            brownout: None,
+        });
+    }
+
+    /// Number of times `key` has been announced as present and as lost.
+    pub fn counts(&self, key: &DeviceKey) -> (u32, u32) {
+        self.records
+            .get(key)
+            .map_or((0, 0), |record| (record.arrivals, record.departures))
+    }
+
+    /// When this bus session was opened.
+    pub fn opened(&self) -> Instant {
+        self.opened
+    }
+
+    /// Logs a table of every device seen over the lifetime of this bus session.
+    pub fn log_summary(&self) {
+        let mut keys: Vec<&DeviceKey> = self.records.keys().collect();
+        keys.sort_by_key(|k| k.pretty_str());
+        log_info!(
+            "[bus {}] session summary: {} device(s) seen over {:.1}s",
+            self.bus_id,
+            keys.len(),
+            self.opened.elapsed().as_secs_f32()
+        );
+        log_info!(
+            "[bus {}]   {:<24} {:<17} {:<10} {:>10} {:>5} {:>5}",
+            self.bus_id,
+            "device",
+            "serial",
+            "firmware",
+            "first seen",
+            "seen",
+            "lost"
+        );
+        for key in keys {
+            let record = &self.records[key];
+            log_info!(
+                "[bus {}]   {:<24} {:<17} {:<10} {:>9.1}s {:>5} {:>5}",
+                self.bus_id,
+                key.pretty_str(),
+                serial_str(record.serial),
+                firmware_str(record.firmware),
+                (record.first_seen - self.opened).as_secs_f32(),
+                record.arrivals,
+                record.departures
+            );
+        }
+    }
+}
+
+fn suppressed_str(suppressed: u32) -> String {
+    if suppressed == 0 {
+        String::new()
+    } else {
+        format!(" ({suppressed} earlier events suppressed)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bus::device::ReduxDeviceType;
+
+    use super::*;
+
+    const KEY: DeviceKey = DeviceKey {
+        dev_type: ReduxDeviceType::Encoder,
+        dev_id: 3,
+    };
+
+    fn devices(present: bool) -> FxHashMap<DeviceKey, Device> {
+        let mut devices = FxHashMap::default();
+        if present {
+            devices.insert(KEY, Device::new(KEY));
+        }
+        devices
+    }
+
+    /// Presence events sent for `bus_id` since the last call.
+    fn changes(
+        events: &mut broadcast::Receiver<PresenceEvent>,
+        bus_id: u16,
+    ) -> Vec<PresenceChange> {
+        core::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| event.bus_id == bus_id)
+            .map(|event| event.change)
+            .collect()
+    }
+
+    #[test]
+    fn test_arrive_leave() {
+        let mut events = subscribe();
+        let mut log = PresenceLog::new(0x7f00);
+        let t0 = Instant::now();
+
+        // unidentified devices get a grace period to enumerate before they're announced
+        log.update(t0, &devices(true), None);
+        assert_eq!(log.counts(&KEY), (0, 0));
+        log.update(t0 + IDENTIFY_GRACE, &devices(true), None);
+        assert_eq!(log.counts(&KEY), (1, 0));
+        log.update(t0 + IDENTIFY_GRACE * 2, &devices(true), None);
+        assert_eq!(log.counts(&KEY), (1, 0));
+
+        log.update(t0 + IDENTIFY_GRACE * 3, &devices(false), None);
+        assert_eq!(log.counts(&KEY), (1, 1));
+        assert_eq!(
+            changes(&mut events, 0x7f00),
+            [PresenceChange::Arrived, PresenceChange::Lost]
+        );
+
+        // leaving during the grace period announces nothing
+        log.update(t0 + IDENTIFY_GRACE * 4, &devices(true), None);
+        log.update(t0 + IDENTIFY_GRACE * 5, &devices(false), None);
+        assert_eq!(log.counts(&KEY), (1, 1));
+        assert!(changes(&mut events, 0x7f00).is_empty());
+    }
+
+    #[test]
+    fn test_flapping() {
+        let mut events = subscribe();
+        let mut log = PresenceLog::new(0x7f01);
+        let t0 = Instant::now();
+        log.update(t0, &devices(true), None);
+        log.update(t0 + IDENTIFY_GRACE, &devices(true), None);
+
+        // every change is counted and broadcast, but only the first is logged in the holdoff. Each
+        // return waits out the identify grace again before it counts.
+        let flaps = [false, true, true, false, true, true];
+        for (i, present) in (2..).zip(flaps) {
+            log.update(t0 + IDENTIFY_GRACE * i, &devices(present), None);
+        }
+        assert_eq!(log.counts(&KEY), (3, 2));
+        assert_eq!(changes(&mut events, 0x7f01).len(), 5);
+        assert_eq!(log.records[&KEY].suppressed, 4);
+    }
+
+    #[test]
+    fn test_holdoff() {
+        let mut log = PresenceLog::new(0x7f02);
+        let t0 = Instant::now();
+        let arrived = t0 + IDENTIFY_GRACE;
+        log.update(t0, &devices(true), None);
+        log.update(arrived, &devices(true), None);
+        log.update(arrived + Duration::from_secs(1), &devices(false), None);
+        log.update(arrived + Duration::from_secs(2), &devices(true), None);
+        log.update(arrived + Duration::from_secs(3), &devices(true), None);
+        assert_eq!(log.records[&KEY].suppressed, 2);
+
+        // the state the device settled in is logged once the holdoff ends, with nothing changing
+        log.update(
+            arrived + LOG_HOLDOFF - Duration::from_millis(1),
+            &devices(true),
+            None,
+        );
+        assert_eq!(log.records[&KEY].suppressed, 2);
+        log.update(arrived + LOG_HOLDOFF, &devices(true), None);
+        let record = &log.records[&KEY];
+        assert_eq!(record.suppressed, 0);
+        assert_eq!(record.next_log, arrived + LOG_HOLDOFF * 2);
+
+        // which starts a new holdoff
+        log.update(
+            arrived + LOG_HOLDOFF + Duration::from_secs(1),
+            &devices(false),
+            None,
+        );
+        assert_eq!(log.records[&KEY].suppressed, 1);
+        assert_eq!(log.counts(&KEY), (2, 2));
+    }
+}
@@ -0,0 +1,420 @@
+//! Setting reads and writes that wait for the device to answer.
+//!
+//! [`BusState::send_fetch_setting`] and [`BusState::send_set_setting`] only send the request, and
+//! the device's `REPORT_SETTING` ends up in the setting cache whenever the bus task next reads it.
+//! [`fetch_setting`] and [`set_setting_confirmed`] instead register for that report before
+//! sending, wait for it, send again each time it doesn't come in time, and decode it into the
+//! device's generated setting type. A write is only confirmed if the device reports back the value
+//! that was sent; devices clamp out of range values rather than refusing them, so a clamped write
+//! comes back as [`SettingError::Mismatch`].
+
+use core::fmt;
+use std::time::Duration;
+
+use canandmessage::{
+    generic::ReportSetting,
+    traits::{CanandDevice, CanandDeviceSetting},
+};
+use fifocore::error::Error;
+use frc_can_id::FRCCanId;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tokio::sync::oneshot;
+
+use crate::bus::{BusState, device::DeviceKey};
+
+/// How long a device gets to report a setting back before the request is sent again.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(100);
+/// Times a request is sent before giving up on the device.
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+
+/// How long to wait for a report, and how many times to ask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettingRetry {
+    /// Wait for each attempt
+    pub timeout: Duration,
+    /// Requests sent in all, at least one
+    pub attempts: u32,
+}
+
+impl Default for SettingRetry {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            attempts: DEFAULT_ATTEMPTS,
+        }
+    }
+}
+
+/// Why a setting couldn't be read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingError {
+    /// The request couldn't be sent
+    Fifo(Error),
+    /// The middleware has no device session on the bus
+    NoSession,
+    /// The device didn't report the setting back after this many requests
+    Timeout { attempts: u32 },
+    /// The device reported the setting back, but refused it
+    Rejected(u8),
+    /// The device reported a value that doesn't decode as the setting
+    InvalidSetting(u8),
+    /// The device took the write, but reports a different value, e.g. because it clamped it
+    Mismatch {
+        index: u8,
+        sent: [u8; 6],
+        reported: [u8; 6],
+    },
+    /// The bus's device session closed while waiting
+    Closed,
+}
+
+impl fmt::Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingError::Fifo(e) => write!(f, "{e}"),
+            SettingError::NoSession => f.write_str("no device session open on the bus"),
+            SettingError::Timeout { attempts } => write!(
+                f,
+                "device did not report the setting back after {attempts} requests"
+            ),
+            SettingError::Rejected(index) => write!(f, "device rejected setting {index:#x}"),
+            SettingError::InvalidSetting(index) => {
+                write!(f, "device reported an invalid value for setting {index:#x}")
+            }
+            SettingError::Mismatch {
+                index,
+                sent,
+                reported,
+            } => write!(
+                f,
+                "setting {index:#x} was written as {sent:02x?} but reads back {reported:02x?}"
+            ),
+            SettingError::Closed => f.write_str("device session closed"),
+        }
+    }
+}
+
+impl std::error::Error for SettingError {}
+
+impl From<Error> for SettingError {
+    fn from(value: Error) -> Self {
+        SettingError::Fifo(value)
+    }
+}
+
+/// Requests waiting on a `REPORT_SETTING`, by device and setting index.
+#[derive(Debug, Default)]
+pub struct SettingWaiters {
+    waiting: FxHashMap<(DeviceKey, u8), Vec<oneshot::Sender<ReportSetting>>>,
+}
+
+impl SettingWaiters {
+    /// Resolves with the next report of setting `index` from `key`.
+    pub fn watch(&mut self, key: DeviceKey, index: u8) -> oneshot::Receiver<ReportSetting> {
+        // waits that timed out leave their sender behind
+        self.waiting.retain(|_, senders| {
+            senders.retain(|sender| !sender.is_closed());
+            !senders.is_empty()
+        });
+        let (send, recv) = oneshot::channel();
+        self.waiting.entry((key, index)).or_default().push(send);
+        recv
+    }
+
+    /// Hands a report from `key` to everything waiting on it.
+    pub fn report(&mut self, key: DeviceKey, report: ReportSetting) {
+        for sender in self
+            .waiting
+            .remove(&(key, report.index))
+            .into_iter()
+            .flatten()
+        {
+            let _ = sender.send(report);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiting.is_empty()
+    }
+}
+
+/// Reads setting `index` from device `id` on `bus_id`.
+pub async fn fetch_setting<D: CanandDevice>(
+    bus_sessions: &Mutex<FxHashMap<u16, BusState>>,
+    bus_id: u16,
+    id: u32,
+    index: <D::Setting as CanandDeviceSetting>::Index,
+    retry: SettingRetry,
+) -> Result<D::Setting, SettingError> {
+    let index: u8 = index.into();
+    let report = request(bus_sessions, bus_id, id, index, retry, |bus| {
+        bus.send_fetch_setting(id, index)
+    })
+    .await?;
+    decode::<D>(report)
+}
+
+/// Writes `setting` to device `id` on `bus_id`, and checks the device reports the same value back.
+pub async fn set_setting_confirmed<D: CanandDevice>(
+    bus_sessions: &Mutex<FxHashMap<u16, BusState>>,
+    bus_id: u16,
+    id: u32,
+    setting: D::Setting,
+    retry: SettingRetry,
+) -> Result<D::Setting, SettingError> {
+    let index = setting.raw_index();
+    let value: [u8; 6] = setting.into();
+    let report = request(bus_sessions, bus_id, id, index, retry, |bus| {
+        bus.send_set_setting(id, index, value)
+    })
+    .await?;
+    confirm(value, &report)?;
+    decode::<D>(report)
+}
+
+/// Reads the raw value of setting `index`, for callers that only know the setting by index.
+pub async fn fetch_setting_raw(
+    bus_sessions: &Mutex<FxHashMap<u16, BusState>>,
+    bus_id: u16,
+    id: u32,
+    index: u8,
+    retry: SettingRetry,
+) -> Result<[u8; 6], SettingError> {
+    let report = request(bus_sessions, bus_id, id, index, retry, |bus| {
+        bus.send_fetch_setting(id, index)
+    })
+    .await?;
+    Ok(report.value)
+}
+
+/// Writes the raw `value` of setting `index`, and checks the device reports the same value back.
+pub async fn set_setting_raw_confirmed(
+    bus_sessions: &Mutex<FxHashMap<u16, BusState>>,
+    bus_id: u16,
+    id: u32,
+    index: u8,
+    value: [u8; 6],
+    retry: SettingRetry,
+) -> Result<(), SettingError> {
+    let report = request(bus_sessions, bus_id, id, index, retry, |bus| {
+        bus.send_set_setting(id, index, value)
+    })
+    .await?;
+    confirm(value, &report)
+}
+
+/// Sends with `send` until the device reports setting `index` back.
+async fn request(
+    bus_sessions: &Mutex<FxHashMap<u16, BusState>>,
+    bus_id: u16,
+    id: u32,
+    index: u8,
+    retry: SettingRetry,
+    mut send: impl FnMut(&mut BusState) -> Result<(), Error>,
+) -> Result<ReportSetting, SettingError> {
+    let key = DeviceKey::from(FRCCanId(id));
+    let attempts = retry.attempts.max(1);
+    for _ in 0..attempts {
+        let report = {
+            let mut bus_sessions = bus_sessions.lock();
+            let bus = bus_sessions
+                .get_mut(&bus_id)
+                .ok_or(SettingError::NoSession)?;
+            // registered before sending, so that a quick report isn't missed
+            let report = bus.settings.watch(key, index);
+            send(bus)?;
+            report
+        };
+        match tokio::time::timeout(retry.timeout, report).await {
+            Ok(Ok(report)) if !report.flags.set_success() => {
+                return Err(SettingError::Rejected(index));
+            }
+            Ok(Ok(report)) => return Ok(report),
+            // the bus state was dropped
+            Ok(Err(_)) => return Err(SettingError::Closed),
+            Err(_) => {}
+        }
+    }
+    Err(SettingError::Timeout { attempts })
+}
+
+/// Checks a write of `value` was reported back as written.
+fn confirm(value: [u8; 6], report: &ReportSetting) -> Result<(), SettingError> {
+    if report.value != value {
+        return Err(SettingError::Mismatch {
+            index: report.index,
+            sent: value,
+            reported: report.value,
+        });
+    }
+    Ok(())
+}
+
+fn decode<D: CanandDevice>(report: ReportSetting) -> Result<D::Setting, SettingError> {
+    let address = <D::Setting as CanandDeviceSetting>::Index::try_from(report.index)
+        .map_err(|_| SettingError::InvalidSetting(report.index))?;
+    D::Setting::from_address_data(address, &report.value)
+        .map_err(|_| SettingError::InvalidSetting(report.index))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use canandmessage::{
+        CanandMessageWrapper,
+        cananddevice::{self, types::SettingReportFlags},
+        canandgyro,
+        generic::WrapperSerializable,
+        traits::MessageIndexId,
+    };
+    use fifocore::{
+        FIFOInterface, ReadBuffer, ReduxFIFOMessage, ReduxFIFOSession, interface::FakeFIFO,
+    };
+
+    use super::*;
+    use crate::{bus::device::ReduxDeviceType, profile::Profiles};
+
+    const GYRO: DeviceKey = DeviceKey {
+        dev_type: ReduxDeviceType::Gyroscope,
+        dev_id: 3,
+    };
+
+    /// Report flags of a setting that was read or written successfully.
+    fn success() -> SettingReportFlags {
+        SettingReportFlags::from_bitfield(1)
+    }
+
+    fn bus(fifo: &FakeFIFO) -> Arc<Mutex<FxHashMap<u16, BusState>>> {
+        fifo.add_bus(0);
+        let state = BusState::new(
+            tokio::spawn(async {}),
+            fifo.handle(),
+            0,
+            Profiles::default(),
+        );
+        let mut buses = FxHashMap::default();
+        buses.insert(0, state);
+        Arc::new(Mutex::new(buses))
+    }
+
+    /// Has the gyro report `value` for `index`, once something has been sent to it.
+    async fn answer(
+        fifo: &FakeFIFO,
+        buses: &Mutex<FxHashMap<u16, BusState>>,
+        index: u8,
+        value: [u8; 6],
+        flags: SettingReportFlags,
+    ) -> Vec<ReduxFIFOMessage> {
+        let written = loop {
+            let written = fifo.take_written();
+            if !written.is_empty() {
+                break written;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        };
+        let msg: CanandMessageWrapper<ReduxFIFOMessage> = ReportSetting::new(index, value, flags)
+            .try_into_wrapper(canandgyro::Device::DEV_TYPE, GYRO.dev_id)
+            .unwrap();
+        let mut read_buf = ReadBuffer::new(ReduxFIFOSession::from_parts(0, 0), 4);
+        read_buf.add_message(msg.0);
+        buses.lock().get_mut(&0).unwrap().ingest_buffer(&read_buf);
+        written
+    }
+
+    #[tokio::test]
+    async fn test_fetch_setting() {
+        let fifo = FakeFIFO::new();
+        let buses = bus(&fifo);
+        let index = canandgyro::types::Setting::YawFramePeriod;
+        let fetch = tokio::spawn({
+            let buses = buses.clone();
+            async move {
+                fetch_setting::<canandgyro::Device>(
+                    &buses,
+                    0,
+                    GYRO.can_id(),
+                    index,
+                    SettingRetry::default(),
+                )
+                .await
+            }
+        });
+        let written = answer(&fifo, &buses, index as u8, [20, 0, 0, 0, 0, 0], success()).await;
+        assert_eq!(
+            written[0].data_slice(),
+            [
+                cananddevice::types::SettingCommand::FetchSettingValue as u8,
+                index as u8
+            ]
+        );
+        assert_eq!(
+            fetch.await.unwrap(),
+            Ok(canandgyro::Setting::YawFramePeriod(20))
+        );
+        assert!(buses.lock()[&0].settings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_setting_mismatch() {
+        let fifo = FakeFIFO::new();
+        let buses = bus(&fifo);
+        let set = tokio::spawn({
+            let buses = buses.clone();
+            async move {
+                set_setting_confirmed::<canandgyro::Device>(
+                    &buses,
+                    0,
+                    GYRO.can_id(),
+                    canandgyro::Setting::YawFramePeriod(5000),
+                    SettingRetry::default(),
+                )
+                .await
+            }
+        });
+        let index = canandgyro::types::Setting::YawFramePeriod as u8;
+        let written = answer(&fifo, &buses, index, [0xff, 0, 0, 0, 0, 0], success()).await;
+        assert!(matches!(
+            cananddevice::MessageIndex::from_frc_can_id(&FRCCanId(written[0].id())),
+            Some((cananddevice::MessageIndex::SetSetting, _))
+        ));
+        assert_eq!(written[0].data_slice()[0], index);
+        assert!(matches!(
+            set.await.unwrap(),
+            Err(SettingError::Mismatch { index: i, .. }) if i == index
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_setting_retries() {
+        let fifo = FakeFIFO::new();
+        let buses = bus(&fifo);
+        let retry = SettingRetry {
+            timeout: Duration::from_millis(10),
+            attempts: 3,
+        };
+        let fetched = fetch_setting::<canandgyro::Device>(
+            &buses,
+            0,
+            GYRO.can_id(),
+            canandgyro::types::Setting::YawFramePeriod,
+            retry,
+        )
+        .await;
+        assert_eq!(fetched, Err(SettingError::Timeout { attempts: 3 }));
+        assert_eq!(fifo.take_written().len(), 3);
+
+        assert_eq!(
+            fetch_setting::<canandgyro::Device>(
+                &buses,
+                1,
+                GYRO.can_id(),
+                canandgyro::types::Setting::YawFramePeriod,
+                retry,
+            )
+            .await,
+            Err(SettingError::NoSession)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Settings a device changed on its own.
+//!
+//! Devices report a setting back whenever a host writes or fetches it, but some also change
+//! settings by themselves: a Canandmag zeroed with its button reports its new zero offset without
+//! anybody asking. That report looks like any other, so the cached value would quietly drift from
+//! what a UI last showed.
+//!
+//! So every setting write or setting command seen going to a device opens a window in which that
+//! device's reports are taken as answers. This host's own writes, from any client, are seen through
+//! the device session's TX echo; other hosts' writes are seen on the bus. A report outside any
+//! window that changes a value we had cached is a device-initiated change. It's kept in the bus's
+//! [recent changes](SettingChanges::recent) and broadcast as a [`SettingChangeEvent`] to anyone
+//! who [subscribed](subscribe).
+
+use std::{
+    collections::VecDeque,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use canandmessage::{
+    CanandMessageWrapper,
+    generic::{ReportSetting, SetSetting, SettingCommand, WrapperSerializable},
+};
+use fifocore::ReduxFIFOMessage;
+use frc_can_id::FRCCanId;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{
+    bus::device::{Device, DeviceKey, serial_str},
+    log::log_info,
+};
+
+/// How long after a write or setting command the device's reports count as answering it. Long
+/// enough for a fetch of every setting to finish.
+const REQUEST_WINDOW: Duration = Duration::from_secs(2);
+/// Device-initiated changes kept per bus.
+const RECENT_CHANGES: usize = 64;
+
+/// A setting the device changed without a host asking it to.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingChangeEvent {
+    pub bus_id: u16,
+    pub device: String,
+    pub serial: String,
+    pub index: u8,
+    pub value: [u8; 6],
+    /// The value we had cached before the device changed it
+    pub previous: [u8; 6],
+    /// Milliseconds since the Unix epoch
+    pub at_ms: u64,
+}
+
+static EVENTS: LazyLock<broadcast::Sender<SettingChangeEvent>> =
+    LazyLock::new(|| broadcast::channel(64).0);
+
+/// Listens to device-initiated setting changes from every bus from now on.
+pub fn subscribe() -> broadcast::Receiver<SettingChangeEvent> {
+    EVENTS.subscribe()
+}
+
+/// Tells device-initiated setting reports on one bus apart from answers to hosts.
+#[derive(Debug)]
+pub struct SettingChanges {
+    bus_id: u16,
+    /// Settings a host wrote or fetched, until their window closes
+    requested: FxHashMap<(DeviceKey, u8), Instant>,
+    /// Devices a host asked for every setting of at once, e.g. by fetching them all or by a
+    /// factory reset
+    requested_all: FxHashMap<DeviceKey, Instant>,
+    recent: VecDeque<SettingChangeEvent>,
+}
+
+impl SettingChanges {
+    pub fn new(bus_id: u16) -> Self {
+        Self {
+            bus_id,
+            requested: Default::default(),
+            requested_all: Default::default(),
+            recent: VecDeque::with_capacity(RECENT_CHANGES),
+        }
+    }
+
+    /// Opens a window for the reports `msg` asks for, if it's a setting write or command from a
+    /// host.
+    pub fn note_host_frame(&mut self, now: Instant, msg: &ReduxFIFOMessage) {
+        let key = DeviceKey::from(FRCCanId(msg.id()));
+        let frame = CanandMessageWrapper(*msg);
+        let until = now + REQUEST_WINDOW;
+        if let Ok(set) = SetSetting::try_from_wrapper(&frame) {
+            self.requested.insert((key, set.index), until);
+        } else if let Ok(command) = SettingCommand::try_from_wrapper(&frame) {
+            match command {
+                SettingCommand::FetchSettingValue(index) => {
+                    self.requested.insert((key, index), until);
+                }
+                // fetching every setting or resetting them all
+                _ => {
+                    self.requested_all.insert(key, until);
+                }
+            }
+        }
+    }
+
+    /// Sorts a report from `dev` into an answer or a device-initiated change. `previous` is what
+    /// was cached for the setting before the report came in.
+    pub fn report(
+        &mut self,
+        now: Instant,
+        key: DeviceKey,
+        dev: &Device,
+        report: &ReportSetting,
+        previous: Option<[u8; 6]>,
+    ) {
+        let answered = |until: Option<&Instant>| until.is_some_and(|until| now < *until);
+        if answered(self.requested.get(&(key, report.index)))
+            || answered(self.requested_all.get(&key))
+        {
+            return;
+        }
+        // nothing to drift from if we hadn't cached it
+        let Some(previous) = previous.filter(|previous| *previous != report.value) else {
+            return;
+        };
+        let event = SettingChangeEvent {
+            bus_id: self.bus_id,
+            device: key.pretty_str(),
+            serial: serial_str(dev.serial_numer()),
+            index: report.index,
+            value: report.value,
+            previous,
+            at_ms: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        };
+        log_info!(
+            "[bus {}] {} changed setting {:#04x} on its own: {:02x?} -> {:02x?}",
+            self.bus_id,
+            event.device,
+            event.index,
+            event.previous,
+            event.value
+        );
+        if self.recent.len() == RECENT_CHANGES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(event.clone());
+        // nobody listening is fine
+        let _ = EVENTS.send(event);
+    }
+
+    /// Closes the windows that have run out.
+    pub fn poll(&mut self, now: Instant) {
+        self.requested.retain(|_, until| now < *until);
+        self.requested_all.retain(|_, until| now < *until);
+    }
+
+    /// Device-initiated changes on the bus, oldest first.
+    pub fn recent(&self) -> Vec<SettingChangeEvent> {
+        self.recent.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use canandmessage::cananddevice::types::SettingReportFlags;
+
+    use super::*;
+
+    #[test]
+    fn test_unsolicited_reports() {
+        let id = FRCCanId(0x070e0001);
+        let key = DeviceKey::from(FRCCanId(id.0));
+        let dev = Device::new(key);
+        let mut changes = SettingChanges::new(0);
+        let now = Instant::now();
+        let report = ReportSetting::new(
+            0xff,
+            [1, 0, 0, 0, 0, 0],
+            SettingReportFlags::from_bitfield(1),
+        );
+
+        // uncached settings have nothing to drift from, and unchanged ones didn't change
+        changes.report(now, key, &dev, &report, None);
+        changes.report(now, key, &dev, &report, Some(report.value));
+        assert!(changes.recent().is_empty());
+
+        // a fetch of the setting answers the report
+        let fetch: CanandMessageWrapper<ReduxFIFOMessage> = SettingCommand::FetchSettingValue(0xff)
+            .try_into_wrapper(id.device_type_code(), id.device_number())
+            .unwrap();
+        changes.note_host_frame(now, &fetch.0);
+        changes.report(now, key, &dev, &report, Some([0; 6]));
+        assert!(changes.recent().is_empty());
+
+        // but not once its window has closed
+        let later = now + REQUEST_WINDOW;
+        changes.poll(later);
+        changes.report(later, key, &dev, &report, Some([0; 6]));
+        let recent = changes.recent();
+        assert_eq!(recent.len(), 1);
+        assert_eq!((recent[0].index, recent[0].previous), (0xff, [0; 6]));
+    }
+}
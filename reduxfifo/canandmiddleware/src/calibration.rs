@@ -0,0 +1,410 @@
+//! Canandgyro calibration, started by the middleware and followed through to the end.
+//!
+//! A gyro calibrates its zero-rate offset when sent `CALIBRATE`. It raises the `calibrating`
+//! fault flag in its status frame while it runs and sends `CALIBRATION_STATUS` once it's done, but
+//! it doesn't know whether it was kept still, and an offset measured while the robot was moving
+//! makes the yaw drift until the next calibration.
+//!
+//! So before sending the command, the gyro's angular velocity frames are watched for a while and
+//! calibration isn't started if it's turning. Motion is watched during calibration too; the
+//! device can't be told to stop, so a calibration that was moved during is reported as disturbed
+//! and should be run again. For the same reason, [cancelling](CalibrationTask::cancel) a
+//! calibration only stops the middleware following it.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use canandmessage::{CanandMessageWrapper, canandgyro, traits::CanandDeviceMessage};
+use fifocore::{
+    FIFOCore, ReadBuffer, ReduxFIFOMessage, ReduxFIFOSessionConfig, Session, cancel::CancelToken,
+    error::Error,
+};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{
+    bus::{device::DeviceKey, device_lock::DeviceGuard},
+    log::{log_info, log_warn},
+};
+
+/// How long the gyro is watched for motion before calibrating by default.
+pub const DEFAULT_STILL_WINDOW: Duration = Duration::from_millis(500);
+/// Fastest rotation on any axis, in degrees per second, that still counts as holding still. An
+/// uncalibrated gyro can read a few degrees per second at rest, so this can't be much lower.
+pub const DEFAULT_MAX_RATE_DPS: f32 = 5.0;
+/// How long calibration gets to finish by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Scale of the `angular_velocity` type in the spec
+const DPS_PER_LSB: f32 = 2000.0 / 32767.0;
+
+/// Which calibration to run, as in the `CALIBRATE` message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibrationType {
+    /// Update the zero-rate offset until the next boot
+    #[default]
+    Normal,
+    /// Also save the offset to flash, to start from on the next boot
+    SaveZro,
+    TempCal0,
+    TempCal1,
+}
+
+impl From<CalibrationType> for canandgyro::types::CalibrationType {
+    fn from(value: CalibrationType) -> Self {
+        match value {
+            CalibrationType::Normal => Self::Normal,
+            CalibrationType::SaveZro => Self::SaveZro,
+            CalibrationType::TempCal0 => Self::TempCal0,
+            CalibrationType::TempCal1 => Self::TempCal1,
+        }
+    }
+}
+
+impl core::str::FromStr for CalibrationType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_string())).map_err(|_| ())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationOptions {
+    pub calibration_type: CalibrationType,
+    /// How long to check the gyro is still before calibrating; zero skips the check
+    pub still_window: Duration,
+    pub max_rate_dps: f32,
+    pub timeout: Duration,
+}
+
+impl Default for CalibrationOptions {
+    fn default() -> Self {
+        Self {
+            calibration_type: CalibrationType::default(),
+            still_window: DEFAULT_STILL_WINDOW,
+            max_rate_dps: DEFAULT_MAX_RATE_DPS,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibrationState {
+    /// Checking that the gyro is still
+    Precheck,
+    /// Calibration command sent, waiting for the gyro to finish
+    Calibrating,
+    /// Finished without the gyro moving
+    Done,
+    /// Not started because the gyro was turning
+    NotStill,
+    /// Finished, but the gyro turned while calibrating; run it again
+    Disturbed,
+    /// The gyro didn't finish within the timeout
+    TimedOut,
+    Failed,
+    /// Cancelled through the API; a gyro already sent the command still finishes calibrating
+    Cancelled,
+}
+
+impl CalibrationState {
+    pub fn finished(self) -> bool {
+        !matches!(self, Self::Precheck | Self::Calibrating)
+    }
+}
+
+/// Progress of one calibration.
+#[derive(Debug, Clone, Serialize)]
+pub struct CalibrationStatus {
+    pub state: CalibrationState,
+    pub calibration_type: CalibrationType,
+    /// Time since the calibration was requested
+    pub elapsed_ms: u64,
+    /// Fastest rotation on any axis before calibration started, in degrees per second
+    pub precheck_max_dps: Option<f32>,
+    /// Fastest rotation on any axis while calibrating, in degrees per second
+    pub calibrating_max_dps: Option<f32>,
+    /// What told us the gyro was done: `calibration_status` or `status_flag`
+    pub completed_by: Option<&'static str>,
+    pub error: Option<String>,
+}
+
+/// Calibrations by bus id and gyro.
+pub type CalibrationMap = FxHashMap<(u16, DeviceKey), Arc<CalibrationTask>>;
+
+/// A calibration running in the background.
+#[derive(Debug)]
+pub struct CalibrationTask {
+    status: watch::Receiver<CalibrationStatus>,
+    started: Instant,
+    cancel: CancelToken,
+    task: JoinHandle<()>,
+}
+
+impl CalibrationTask {
+    /// Starts calibrating the gyro at `key` on `bus_id`, holding `device` until it's done.
+    pub fn start(
+        fifocore: FIFOCore,
+        bus_id: u16,
+        key: DeviceKey,
+        options: CalibrationOptions,
+        device: DeviceGuard,
+    ) -> Self {
+        let (status_send, status) = watch::channel(CalibrationStatus {
+            state: CalibrationState::Precheck,
+            calibration_type: options.calibration_type,
+            elapsed_ms: 0,
+            precheck_max_dps: None,
+            calibrating_max_dps: None,
+            completed_by: None,
+            error: None,
+        });
+        let started = Instant::now();
+        let cancel = CancelToken::new();
+        let token = cancel.clone();
+        let task = tokio::spawn(async move {
+            let mut run = Run {
+                status: status_send,
+                started,
+            };
+            let result = token.run(run.calibrate(&fifocore, bus_id, key, options)).await;
+            // let go of the gyro first, so it's free by the time a cancel says it's done
+            drop(device);
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => run.update(|status| {
+                    status.state = CalibrationState::Failed;
+                    status.error = Some(e.to_string());
+                }),
+                Err(_) => run.update(|status| status.state = CalibrationState::Cancelled),
+            }
+            let status = run.status.borrow().clone();
+            match status.state {
+                CalibrationState::Done => log_info!(
+                    "[bus {bus_id}] {} calibrated in {} ms",
+                    key.pretty_str(),
+                    status.elapsed_ms
+                ),
+                state => log_warn!(
+                    "[bus {bus_id}] {} calibration ended {state:?}{}",
+                    key.pretty_str(),
+                    status.error.map(|e| format!(": {e}")).unwrap_or_default()
+                ),
+            }
+        });
+        Self {
+            status,
+            started,
+            cancel,
+            task,
+        }
+    }
+
+    pub fn status(&self) -> CalibrationStatus {
+        let mut status = self.status.borrow().clone();
+        if !status.state.finished() {
+            status.elapsed_ms = self.started.elapsed().as_millis() as u64;
+        }
+        status
+    }
+
+    /// Waits up to `timeout` for the calibration to finish, then returns where it's at.
+    pub async fn wait(&self, timeout: Duration) -> CalibrationStatus {
+        let mut status = self.status.clone();
+        let _ = tokio::time::timeout(timeout, status.wait_for(|s| s.state.finished())).await;
+        self.status()
+    }
+
+    /// Stops following the calibration and lets go of the gyro, returning the final status.
+    pub async fn cancel(&self) -> CalibrationStatus {
+        self.cancel.cancel();
+        let mut status = self.status.clone();
+        // the task reports it was cancelled as soon as it's next polled
+        let _ = status.wait_for(|s| s.state.finished()).await;
+        self.status()
+    }
+}
+
+impl Drop for CalibrationTask {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Run {
+    status: watch::Sender<CalibrationStatus>,
+    started: Instant,
+}
+
+impl Run {
+    fn update(&mut self, f: impl FnOnce(&mut CalibrationStatus)) {
+        let elapsed_ms = self.started.elapsed().as_millis() as u64;
+        self.status.send_modify(|status| {
+            f(status);
+            status.elapsed_ms = elapsed_ms;
+        });
+    }
+
+    async fn calibrate(
+        &mut self,
+        fifocore: &FIFOCore,
+        bus_id: u16,
+        key: DeviceKey,
+        options: CalibrationOptions,
+    ) -> Result<(), Error> {
+        let config = ReduxFIFOSessionConfig::new(key.can_id(), frc_can_id::DEVICE_FILTER);
+        let mut frames = GyroFrames::open(fifocore, bus_id, config)?;
+
+        if !options.still_window.is_zero() {
+            let mut max_dps = None::<f32>;
+            let deadline = tokio::time::Instant::now() + options.still_window;
+            frames
+                .watch(deadline, |msg| {
+                    if let Some(dps) = rate_dps(&msg) {
+                        max_dps = Some(max_dps.map_or(dps, |max| max.max(dps)));
+                    }
+                    false
+                })
+                .await?;
+            self.update(|status| status.precheck_max_dps = max_dps);
+            match max_dps {
+                None => {
+                    self.update(|status| {
+                        status.state = CalibrationState::Failed;
+                        status.error = Some(
+                            "no angular velocity frames to check the gyro is still; enable its \
+                             angular velocity frame or skip the check"
+                                .to_string(),
+                        );
+                    });
+                    return Ok(());
+                }
+                Some(dps) if dps > options.max_rate_dps => {
+                    self.update(|status| status.state = CalibrationState::NotStill);
+                    return Ok(());
+                }
+                Some(_) => {}
+            }
+        }
+
+        let msg: CanandMessageWrapper<ReduxFIFOMessage> = canandgyro::Message::Calibrate {
+            calibration_type: options.calibration_type.into(),
+            reserved: 0,
+        }
+        .try_into_wrapper(key.can_id())
+        .map_err(|_| Error::BusWriteFail)?;
+        let mut msg = msg.0;
+        msg.bus_id = bus_id;
+        fifocore.write_single(&msg)?;
+        self.update(|status| status.state = CalibrationState::Calibrating);
+
+        let mut max_dps = None::<f32>;
+        let mut completed_by = None;
+        // the first status frames can be from before the gyro got the command
+        let mut flag_seen = false;
+        let deadline = tokio::time::Instant::now() + options.timeout;
+        frames
+            .watch(deadline, |msg| {
+                match msg {
+                    canandgyro::Message::CalibrationStatus { .. } => {
+                        completed_by = Some("calibration_status");
+                    }
+                    canandgyro::Message::Status { faults, .. } => {
+                        if faults.calibrating() {
+                            flag_seen = true;
+                        } else if flag_seen {
+                            completed_by = Some("status_flag");
+                        }
+                    }
+                    msg => {
+                        if let Some(dps) = rate_dps(&msg) {
+                            max_dps = Some(max_dps.map_or(dps, |max| max.max(dps)));
+                        }
+                    }
+                }
+                completed_by.is_some()
+            })
+            .await?;
+
+        self.update(|status| {
+            status.calibrating_max_dps = max_dps;
+            status.completed_by = completed_by;
+            status.state = match (completed_by, max_dps) {
+                (None, _) => CalibrationState::TimedOut,
+                (Some(_), Some(dps)) if dps > options.max_rate_dps => CalibrationState::Disturbed,
+                (Some(_), _) => CalibrationState::Done,
+            };
+        });
+        Ok(())
+    }
+}
+
+/// Fastest rotation on any axis in an angular velocity frame.
+fn rate_dps(msg: &canandgyro::Message) -> Option<f32> {
+    match msg {
+        canandgyro::Message::AngularVelocityOutput { yaw, pitch, roll } => {
+            let lsb = yaw
+                .unsigned_abs()
+                .max(pitch.unsigned_abs())
+                .max(roll.unsigned_abs());
+            Some(lsb as f32 * DPS_PER_LSB)
+        }
+        _ => None,
+    }
+}
+
+/// Frames from one gyro, decoded.
+struct GyroFrames {
+    session: Session,
+    notifier: watch::Receiver<u32>,
+    buffer: ReadBuffer,
+}
+
+impl GyroFrames {
+    fn open(
+        fifocore: &FIFOCore,
+        bus_id: u16,
+        config: ReduxFIFOSessionConfig,
+    ) -> Result<Self, Error> {
+        let session = fifocore.open_managed_session(bus_id, 64, config)?;
+        let notifier = session.rx_notifier()?;
+        let buffer = session.read_buffer(64);
+        Ok(Self {
+            session,
+            notifier,
+            buffer,
+        })
+    }
+
+    /// Hands each frame to `f` until it returns true or `deadline` passes.
+    async fn watch(
+        &mut self,
+        deadline: tokio::time::Instant,
+        mut f: impl FnMut(canandgyro::Message) -> bool,
+    ) -> Result<(), Error> {
+        loop {
+            match tokio::time::timeout_at(deadline, self.notifier.wait_for(|size| *size > 0)).await
+            {
+                // holding the borrow blocks the bus from delivering more messages
+                Ok(Ok(size)) => drop(size),
+                Ok(Err(_)) => return Err(Error::SessionClosed),
+                Err(_) => return Ok(()),
+            }
+            self.session.read_barrier(&mut self.buffer)?;
+            for msg in self.buffer.iter() {
+                let Ok(msg) = canandgyro::Message::try_from(CanandMessageWrapper(*msg)) else {
+                    continue;
+                };
+                if f(msg) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,390 @@
+//! Operator confirmation for operations that can wreck a robot if they happen at the wrong time.
+//!
+//! Factory resets, CAN id changes and firmware flashes all take a device out of whatever the robot
+//! code expects of it, and a stray click in a configurator is enough to ask for one mid-match. So
+//! these go through a [`Confirmations`] check first. When the [`ConfirmPolicy`] wants a
+//! confirmation, the request is refused with a one-time token naming the operation and device; the
+//! client confirms by making the same request again with `?confirm=<token>` before the token
+//! expires. A standing operator token, if one is configured, confirms anything on its own, for
+//! tools that have already asked the operator themselves.
+//!
+//! Whether the robot is enabled is read off the roboRIO heartbeat, so
+//! [`ConfirmMode::WhileEnabled`] only holds operations back while a match or practice is running.
+
+use std::{
+    hash::{BuildHasher, Hasher, RandomState},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fifocore::{FIFOCore, ReduxFIFOSessionConfig};
+use frc_can_id::{HEARTBEAT_ID, HEARTBEAT_TIMEOUT_US, HeartbeatTracker};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::log::{log_info, log_warn};
+
+/// How long a confirmation token stays good for.
+pub const CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An operation that needs confirming under some policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    FactoryReset,
+    SetId,
+    FirmwareFlash,
+}
+
+impl core::fmt::Display for Operation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Operation::FactoryReset => "factory reset",
+            Operation::SetId => "CAN id change",
+            Operation::FirmwareFlash => "firmware flash",
+        })
+    }
+}
+
+/// When an operation needs confirming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmMode {
+    Always,
+    /// Only while the robot is enabled
+    WhileEnabled,
+    Never,
+}
+
+/// Which operations need confirming, and the standing token that confirms them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmPolicy {
+    pub factory_reset: ConfirmMode,
+    pub set_id: ConfirmMode,
+    pub firmware_flash: ConfirmMode,
+    /// Confirms any operation, and is needed to change the policy once set. Never reported back.
+    #[serde(skip_serializing)]
+    pub operator_token: Option<String>,
+}
+
+impl Default for ConfirmPolicy {
+    fn default() -> Self {
+        Self {
+            factory_reset: ConfirmMode::Always,
+            set_id: ConfirmMode::WhileEnabled,
+            firmware_flash: ConfirmMode::Always,
+            operator_token: None,
+        }
+    }
+}
+
+impl ConfirmPolicy {
+    pub fn mode(&self, operation: Operation) -> ConfirmMode {
+        match operation {
+            Operation::FactoryReset => self.factory_reset,
+            Operation::SetId => self.set_id,
+            Operation::FirmwareFlash => self.firmware_flash,
+        }
+    }
+}
+
+/// What a client needs to confirm an operation that was held back.
+#[derive(Debug, Clone, Serialize)]
+pub struct Challenge {
+    pub operation: Operation,
+    /// Device the operation is on, e.g. `bus 0 device 0x070e0001`
+    pub target: String,
+    /// Repeat the request with `?confirm=<token>` to go ahead
+    pub token: String,
+    pub expires_in_ms: u64,
+    /// The roboRIO heartbeat said the robot was enabled
+    pub robot_enabled: bool,
+}
+
+/// Why an operation can't go ahead.
+#[derive(Debug, Clone)]
+pub enum ConfirmError {
+    /// Confirm with the challenge's token
+    Required {
+        challenge: Challenge,
+        /// A token was given, but it was for something else or had expired
+        rejected: bool,
+    },
+    /// The policy can only be changed with the operator token
+    PolicyLocked,
+}
+
+impl core::fmt::Display for ConfirmError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ConfirmError::Required {
+                challenge,
+                rejected,
+            } => {
+                let enabled = match challenge.robot_enabled {
+                    true => " while the robot is enabled",
+                    false => "",
+                };
+                write!(
+                    f,
+                    "{} of {}{enabled} needs confirming",
+                    challenge.operation, challenge.target
+                )?;
+                if *rejected {
+                    f.write_str("; the token given is expired or for another operation")?;
+                }
+                Ok(())
+            }
+            ConfirmError::PolicyLocked => f.write_str("the confirmation policy is locked"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    operation: Operation,
+    target: String,
+    expires: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    policy: ConfirmPolicy,
+    pending: FxHashMap<String, Pending>,
+    issued: u64,
+}
+
+/// The confirmation policy and the tokens handed out under it.
+#[derive(Debug, Clone, Default)]
+pub struct Confirmations {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Confirmations {
+    pub fn new(policy: ConfirmPolicy) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                policy,
+                ..Default::default()
+            })),
+        }
+    }
+
+    pub fn policy(&self) -> ConfirmPolicy {
+        self.inner.lock().policy.clone()
+    }
+
+    /// Replaces the policy. Once an operator token is set, `token` has to match it.
+    pub fn set_policy(
+        &self,
+        policy: ConfirmPolicy,
+        token: Option<&str>,
+    ) -> Result<(), ConfirmError> {
+        let mut inner = self.inner.lock();
+        if let Some(operator) = &inner.policy.operator_token
+            && token != Some(operator.as_str())
+        {
+            return Err(ConfirmError::PolicyLocked);
+        }
+        log_info!(
+            "Confirmation policy changed: factory_reset {:?}, set_id {:?}, firmware_flash {:?}",
+            policy.factory_reset,
+            policy.set_id,
+            policy.firmware_flash
+        );
+        inner.policy = policy;
+        // tokens handed out under the old policy shouldn't carry over
+        inner.pending.clear();
+        Ok(())
+    }
+
+    /// Lets `operation` on `target` go ahead if the policy doesn't want it confirmed, or if
+    /// `token` confirms it. Otherwise hands out a token to confirm it with.
+    ///
+    /// The robot is checked for being enabled on `bus_id` only if the policy cares.
+    pub async fn check(
+        &self,
+        fifocore: &FIFOCore,
+        operation: Operation,
+        bus_id: Option<u16>,
+        target: &str,
+        token: Option<&str>,
+    ) -> Result<(), ConfirmError> {
+        let mode = self.inner.lock().policy.mode(operation);
+        let robot_enabled = match (mode, bus_id) {
+            (ConfirmMode::Never, _) => return Ok(()),
+            (ConfirmMode::Always, _) => None,
+            (ConfirmMode::WhileEnabled, Some(bus_id)) => {
+                Some(robot_enabled(fifocore, bus_id).await)
+            }
+            (ConfirmMode::WhileEnabled, None) => Some(false),
+        };
+        if robot_enabled == Some(false) {
+            return Ok(());
+        }
+        self.confirm(
+            operation,
+            target,
+            robot_enabled.unwrap_or(false),
+            token,
+            Instant::now(),
+        )
+    }
+
+    fn confirm(
+        &self,
+        operation: Operation,
+        target: &str,
+        robot_enabled: bool,
+        token: Option<&str>,
+        now: Instant,
+    ) -> Result<(), ConfirmError> {
+        let mut inner = self.inner.lock();
+        inner.pending.retain(|_, pending| pending.expires > now);
+        if let Some(token) = token {
+            if inner.policy.operator_token.as_deref() == Some(token) {
+                log_info!("{operation} of {target} confirmed by operator token");
+                return Ok(());
+            }
+            let matches = inner
+                .pending
+                .get(token)
+                .is_some_and(|pending| pending.operation == operation && pending.target == target);
+            if matches {
+                inner.pending.remove(token);
+                log_info!("{operation} of {target} confirmed");
+                return Ok(());
+            }
+            log_warn!("Rejected confirmation token for {operation} of {target}");
+        }
+
+        let rejected = token.is_some();
+        inner.issued += 1;
+        let token = new_token(inner.issued);
+        inner.pending.insert(
+            token.clone(),
+            Pending {
+                operation,
+                target: target.to_owned(),
+                expires: now + CONFIRM_TIMEOUT,
+            },
+        );
+        Err(ConfirmError::Required {
+            challenge: Challenge {
+                operation,
+                target: target.to_owned(),
+                token,
+                expires_in_ms: CONFIRM_TIMEOUT.as_millis() as u64,
+                robot_enabled,
+            },
+            rejected,
+        })
+    }
+}
+
+/// A token nobody could have guessed ahead of time.
+fn new_token(issued: u64) -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(issued);
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    format!("{:016x}", hasher.finish())
+}
+
+/// Whether a roboRIO heartbeat on `bus_id` says the robot is enabled, waiting up to a heartbeat
+/// timeout to hear one. A bus that can't be read is taken as enabled, to be safe.
+pub async fn robot_enabled(fifocore: &FIFOCore, bus_id: u16) -> bool {
+    let config = ReduxFIFOSessionConfig::new(HEARTBEAT_ID, 0x1fff_ffff);
+    let Ok(session) = fifocore.open_managed_session(bus_id, 8, config) else {
+        return true;
+    };
+    let Ok(mut notifier) = session.rx_notifier() else {
+        return true;
+    };
+    let mut buffer = session.read_buffer(8);
+    let mut tracker = HeartbeatTracker::<HEARTBEAT_TIMEOUT_US>::new();
+    let wait = Duration::from_micros(HEARTBEAT_TIMEOUT_US);
+    match tokio::time::timeout(wait, notifier.wait_for(|size| *size > 0)).await {
+        // holding the borrow blocks the bus from delivering more messages
+        Ok(Ok(size)) => drop(size),
+        Ok(Err(_)) => return true,
+        // no roboRIO on the bus
+        Err(_) => return false,
+    }
+    if session.read_barrier(&mut buffer).is_err() {
+        return true;
+    }
+    let mut last = 0;
+    for msg in buffer.iter() {
+        tracker.ingest(msg.id(), msg.data_slice(), msg.timestamp);
+        last = last.max(msg.timestamp);
+    }
+    tracker.enabled(last)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_confirm_tokens() {
+        let confirmations = Confirmations::default();
+        let now = Instant::now();
+        let target = "bus 0 device 0x070e0001";
+        let Err(ConfirmError::Required {
+            challenge,
+            rejected,
+        }) = confirmations.confirm(Operation::FactoryReset, target, false, None, now)
+        else {
+            panic!("factory reset should need confirming");
+        };
+        assert!(!rejected);
+
+        // a token only confirms what it was handed out for
+        let token = Some(challenge.token.as_str());
+        let other = confirmations.confirm(Operation::SetId, target, false, token, now);
+        assert!(matches!(
+            other,
+            Err(ConfirmError::Required { rejected: true, .. })
+        ));
+        let confirmed = confirmations.confirm(Operation::FactoryReset, target, false, token, now);
+        assert!(confirmed.is_ok());
+        // and only once
+        let again = confirmations.confirm(Operation::FactoryReset, target, false, token, now);
+        assert!(again.is_err());
+
+        let Err(ConfirmError::Required { challenge, .. }) =
+            confirmations.confirm(Operation::FactoryReset, target, false, None, now)
+        else {
+            panic!("factory reset should need confirming");
+        };
+        let later = now + CONFIRM_TIMEOUT;
+        let token = Some(challenge.token.as_str());
+        let expired = confirmations.confirm(Operation::FactoryReset, target, false, token, later);
+        assert!(expired.is_err());
+    }
+
+    #[test]
+    fn test_operator_token() {
+        let confirmations = Confirmations::default();
+        let policy = ConfirmPolicy {
+            operator_token: Some("pit".to_owned()),
+            ..Default::default()
+        };
+        confirmations.set_policy(policy, None).unwrap();
+        let now = Instant::now();
+        let flash = confirmations.confirm(Operation::FirmwareFlash, "x", false, Some("pit"), now);
+        assert!(flash.is_ok());
+
+        let unlocked = ConfirmPolicy::default();
+        assert!(confirmations.set_policy(unlocked.clone(), None).is_err());
+        assert!(confirmations.set_policy(unlocked, Some("pit")).is_ok());
+        assert_eq!(confirmations.policy().operator_token, None);
+    }
+}
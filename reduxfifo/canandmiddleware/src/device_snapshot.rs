@@ -0,0 +1,507 @@
+//! Snapshots of one device's settings, for saving to a file and applying back later.
+//!
+//! Where a [settings file](crate::settings_file) covers every device on the robot and matches them
+//! by serial numer, a snapshot is a single device, taken from and restored to a bus and CAN id:
+//! Alchemist's "export device config" and "apply config to device". Taking one reads every
+//! readable setting, read-only ones like the firmware version included, so the file also records
+//! what the device was. Settings are keyed by the same names as in settings files and hold raw
+//! 48-bit values.
+//!
+//! Restoring writes every writable setting in the snapshot except the CAN id, one at a time, each
+//! confirmed by the device reporting back the value written (see [`crate::bus::setting`]). The
+//! device is then read back and diffed against the snapshot with the generated settings structs'
+//! `get_changed`, so the report lists whatever didn't end up as saved, whether because a write
+//! failed or because the setting is read-only.
+
+use std::collections::BTreeMap;
+
+use canandmessage::{
+    alchemist::{CanandcolorSettings, CanandgyroSettings, CanandmagSettings},
+    cananddevice,
+    traits::{CanandDevice, CanandDeviceSetting},
+};
+use frc_can_id::FRCCanId;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bus::{
+        BusState,
+        device::{DeviceKey, firmware_str, serial_str},
+        setting::{self, SettingError, SettingRetry},
+    },
+    profile::{ProfileProduct, normalize_name},
+};
+
+/// Value of `schema` in every snapshot.
+pub const SCHEMA: &str = "redux-device-snapshot";
+/// Newest snapshot version this build reads, and the one it writes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The settings of one device, as read from it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    pub schema: String,
+    pub version: u32,
+    pub product: ProfileProduct,
+    /// Serial numer of the device the snapshot was taken from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    /// Firmware the device was running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    /// Unix time the snapshot was taken, in microseconds
+    #[serde(default)]
+    pub taken_us: i64,
+    /// Setting name to raw setting value, e.g. `{"VelocityFramePeriod": 10}`
+    pub settings: BTreeMap<String, u64>,
+}
+
+/// What the spec says about one setting.
+#[derive(Debug)]
+struct SettingSpec {
+    index: u8,
+    name: String,
+    readable: bool,
+    writable: bool,
+}
+
+fn specs(product: ProfileProduct) -> Vec<SettingSpec> {
+    fn collect<D: CanandDevice>() -> Vec<SettingSpec> {
+        D::setting_info()
+            .iter()
+            .map(|info| SettingSpec {
+                index: info.index.into(),
+                name: format!("{:?}", info.index),
+                readable: info.readable,
+                writable: info.writable,
+            })
+            .collect()
+    }
+    match product {
+        ProfileProduct::Canandmag => collect::<canandmessage::canandmag::Device>(),
+        ProfileProduct::Canandgyro => collect::<canandmessage::canandgyro::Device>(),
+        ProfileProduct::Canandcolor => collect::<canandmessage::canandcolor::Device>(),
+    }
+}
+
+fn raw_u64(value: &[u8; 6]) -> u64 {
+    let mut raw = [0u8; 8];
+    raw[..6].copy_from_slice(value);
+    u64::from_le_bytes(raw)
+}
+
+fn raw_bytes(value: u64) -> [u8; 6] {
+    value.to_le_bytes()[..6].try_into().unwrap()
+}
+
+/// How writing one setting went.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum WriteOutcome {
+    /// The device reported back the value written
+    Confirmed,
+    /// The device took the write, but reports a different value, e.g. because it clamped it
+    Mismatch {
+        reported: u64,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// One setting written on restore.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingWrite {
+    pub name: String,
+    pub value: u64,
+    #[serde(flatten)]
+    pub outcome: WriteOutcome,
+}
+
+/// A setting that doesn't have the snapshot's value on the device after restoring.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SettingDiff {
+    pub name: String,
+    pub snapshot: u64,
+    /// Value read back from the device, or `None` if it couldn't be read
+    pub device: Option<u64>,
+    pub writable: bool,
+}
+
+/// What restoring a snapshot did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RestoreReport {
+    pub product: ProfileProduct,
+    /// Serial numer of the device restored to
+    pub serial: Option<String>,
+    /// Whether every write was confirmed and the device reads back as the snapshot
+    pub matches: bool,
+    pub writes: Vec<SettingWrite>,
+    /// Settings that differ from the snapshot, read back after writing
+    pub diff: Vec<SettingDiff>,
+}
+
+impl DeviceSnapshot {
+    /// Reads every readable setting of device `can_id` on `bus_id`.
+    ///
+    /// Settings the device refuses to report, such as ones its firmware predates, are left out;
+    /// one it doesn't answer for at all fails the snapshot.
+    pub async fn take(
+        bus_sessions: &Mutex<FxHashMap<u16, BusState>>,
+        bus_id: u16,
+        can_id: u32,
+        retry: SettingRetry,
+    ) -> Result<Self, SnapshotError> {
+        let key = DeviceKey::from(FRCCanId::new(can_id));
+        let product = ProfileProduct::for_device(&key)
+            .ok_or_else(|| SnapshotError::UnknownProduct(key.pretty_str()))?;
+
+        let mut settings = BTreeMap::new();
+        for spec in specs(product).into_iter().filter(|spec| spec.readable) {
+            match setting::fetch_setting_raw(bus_sessions, bus_id, can_id, spec.index, retry).await
+            {
+                Ok(value) => {
+                    settings.insert(spec.name, raw_u64(&value));
+                }
+                Err(SettingError::Rejected(_)) => {}
+                Err(e) => return Err(SnapshotError::Read(spec.name, e)),
+            }
+        }
+
+        // the fetches leave the serial numer and firmware version in the device's cache
+        let (serial, firmware) = {
+            let guard = bus_sessions.lock();
+            let dev = guard.get(&bus_id).and_then(|state| state.devices.get(&key));
+            (
+                dev.and_then(|dev| dev.serial_numer())
+                    .map(|serial| serial_str(Some(serial))),
+                dev.and_then(|dev| dev.firmware_version())
+                    .map(|fw| firmware_str(Some(fw))),
+            )
+        };
+        Ok(Self {
+            schema: SCHEMA.to_string(),
+            version: SCHEMA_VERSION,
+            product,
+            serial,
+            firmware,
+            taken_us: fifocore::timebase::now_us(),
+            settings,
+        })
+    }
+
+    /// Writes the snapshot's settings to device `can_id` on `bus_id`, then reads them back.
+    ///
+    /// The whole snapshot is checked before anything is written. Failed writes don't stop the
+    /// restore; they're in the report along with the settings they left different.
+    pub async fn restore(
+        &self,
+        bus_sessions: &Mutex<FxHashMap<u16, BusState>>,
+        bus_id: u16,
+        can_id: u32,
+        retry: SettingRetry,
+    ) -> Result<RestoreReport, SnapshotError> {
+        let settings = self.resolve()?;
+        let key = DeviceKey::from(FRCCanId::new(can_id));
+        let product = ProfileProduct::for_device(&key)
+            .ok_or_else(|| SnapshotError::UnknownProduct(key.pretty_str()))?;
+        if product != self.product {
+            return Err(SnapshotError::WrongProduct {
+                snapshot: self.product,
+                device: product,
+            });
+        }
+
+        let mut writes = Vec::new();
+        for (spec, value) in settings.iter() {
+            // the snapshot is restored to whatever id the device has now
+            if !spec.writable || spec.index == cananddevice::types::Setting::CanId as u8 {
+                continue;
+            }
+            let outcome = match setting::set_setting_raw_confirmed(
+                bus_sessions,
+                bus_id,
+                can_id,
+                spec.index,
+                raw_bytes(*value),
+                retry,
+            )
+            .await
+            {
+                Ok(()) => WriteOutcome::Confirmed,
+                Err(SettingError::Mismatch { reported, .. }) => WriteOutcome::Mismatch {
+                    reported: raw_u64(&reported),
+                },
+                Err(e) => WriteOutcome::Failed {
+                    error: e.to_string(),
+                },
+            };
+            writes.push(SettingWrite {
+                name: spec.name.clone(),
+                value: *value,
+                outcome,
+            });
+        }
+
+        let mut diff = Vec::new();
+        let mut expected = Vec::new();
+        let mut actual = Vec::new();
+        for (spec, value) in settings.iter().filter(|(spec, _)| spec.readable) {
+            match setting::fetch_setting_raw(bus_sessions, bus_id, can_id, spec.index, retry).await
+            {
+                Ok(read) => {
+                    expected.push((spec.index, raw_bytes(*value)));
+                    actual.push((spec.index, read));
+                }
+                Err(_) => diff.push(SettingDiff {
+                    name: spec.name.clone(),
+                    snapshot: *value,
+                    device: None,
+                    writable: spec.writable,
+                }),
+            }
+        }
+        for index in changed(product, &expected, &actual) {
+            let Some((spec, value)) = settings.iter().find(|(spec, _)| spec.index == index) else {
+                continue;
+            };
+            let read = actual
+                .iter()
+                .find(|(actual_index, _)| *actual_index == index)
+                .map(|(_, read)| raw_u64(read));
+            diff.push(SettingDiff {
+                name: spec.name.clone(),
+                snapshot: *value,
+                device: read,
+                writable: spec.writable,
+            });
+        }
+
+        let serial = bus_sessions
+            .lock()
+            .get(&bus_id)
+            .and_then(|state| state.devices.get(&key))
+            .and_then(|dev| dev.serial_numer())
+            .map(|serial| serial_str(Some(serial)));
+        Ok(RestoreReport {
+            product,
+            serial,
+            matches: diff.is_empty()
+                && writes
+                    .iter()
+                    .all(|write| write.outcome == WriteOutcome::Confirmed),
+            writes,
+            diff,
+        })
+    }
+
+    /// Checks the header and resolves every setting name, without touching the device.
+    fn resolve(&self) -> Result<Vec<(SettingSpec, u64)>, SnapshotError> {
+        if self.schema != SCHEMA {
+            return Err(SnapshotError::WrongSchema(self.schema.clone()));
+        }
+        if self.version == 0 || self.version > SCHEMA_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(self.version));
+        }
+        let mut known = specs(self.product);
+        let mut settings = Vec::with_capacity(self.settings.len());
+        for (name, value) in self.settings.iter() {
+            let wanted = normalize_name(name);
+            let Some(pos) = known
+                .iter()
+                .position(|spec| normalize_name(&spec.name) == wanted)
+            else {
+                return Err(SnapshotError::UnknownSetting(name.clone()));
+            };
+            if *value >= 1 << 48 {
+                return Err(SnapshotError::ValueTooLarge(name.clone(), *value));
+            }
+            settings.push((known.swap_remove(pos), *value));
+        }
+        settings.sort_by_key(|(spec, _)| spec.index);
+        Ok(settings)
+    }
+}
+
+/// Alchemist's generated settings structs, whose `get_changed` diffs two of them.
+trait GeneratedSettings: Default {
+    /// Sets one setting from its raw value, if it decodes.
+    fn load(&mut self, index: u8, value: &[u8; 6]);
+    /// Indices of the settings `other` has a different value for.
+    fn changed_from(&self, other: &Self) -> Vec<u8>;
+}
+
+macro_rules! generated_settings {
+    ($($settings:ident => $dev:ident),* $(,)?) => {$(
+        impl GeneratedSettings for $settings {
+            fn load(&mut self, index: u8, value: &[u8; 6]) {
+                let Ok(address) = canandmessage::$dev::types::Setting::try_from(index) else {
+                    return;
+                };
+                if let Ok(setting) = canandmessage::$dev::Setting::from_address_data(address, value)
+                {
+                    self.process(address, setting);
+                }
+            }
+
+            fn changed_from(&self, other: &Self) -> Vec<u8> {
+                self.get_changed(other)
+                    .into_iter()
+                    .map(|(address, _)| address.into())
+                    .collect()
+            }
+        }
+    )*};
+}
+
+generated_settings! {
+    CanandmagSettings => canandmag,
+    CanandgyroSettings => canandgyro,
+    CanandcolorSettings => canandcolor,
+}
+
+fn diff_with<S: GeneratedSettings>(
+    expected: &[(u8, [u8; 6])],
+    actual: &[(u8, [u8; 6])],
+) -> Vec<u8> {
+    let mut want = S::default();
+    let mut have = S::default();
+    for (index, value) in expected {
+        want.load(*index, value);
+    }
+    for (index, value) in actual {
+        have.load(*index, value);
+    }
+    have.changed_from(&want)
+}
+
+/// Indices of the settings `actual` doesn't have `expected`'s value for. The CAN id, serial
+/// numer, name and firmware version aren't compared.
+fn changed(
+    product: ProfileProduct,
+    expected: &[(u8, [u8; 6])],
+    actual: &[(u8, [u8; 6])],
+) -> Vec<u8> {
+    match product {
+        ProfileProduct::Canandmag => diff_with::<CanandmagSettings>(expected, actual),
+        ProfileProduct::Canandgyro => diff_with::<CanandgyroSettings>(expected, actual),
+        ProfileProduct::Canandcolor => diff_with::<CanandcolorSettings>(expected, actual),
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The device isn't a product with known settings
+    UnknownProduct(String),
+    WrongSchema(String),
+    UnsupportedVersion(u32),
+    /// The snapshot is of a different product than the device
+    WrongProduct {
+        snapshot: ProfileProduct,
+        device: ProfileProduct,
+    },
+    UnknownSetting(String),
+    /// (setting name, value)
+    ValueTooLarge(String, u64),
+    /// (setting name, why it couldn't be read)
+    Read(String, SettingError),
+}
+
+impl core::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::UnknownProduct(device) => {
+                write!(f, "{device} isn't a product with known settings")
+            }
+            SnapshotError::WrongSchema(schema) => {
+                write!(
+                    f,
+                    "not a device snapshot: schema is {schema:?}, not {SCHEMA:?}"
+                )
+            }
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {version} isn't supported (newest is {SCHEMA_VERSION})"
+            ),
+            SnapshotError::WrongProduct { snapshot, device } => {
+                write!(
+                    f,
+                    "snapshot of a {snapshot:?} can't be restored to a {device:?}"
+                )
+            }
+            SnapshotError::UnknownSetting(name) => write!(f, "no setting named {name:?}"),
+            SnapshotError::ValueTooLarge(name, value) => {
+                write!(f, "value {value} for {name:?} doesn't fit in 48 bits")
+            }
+            SnapshotError::Read(name, e) => write!(f, "couldn't read {name}: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for SnapshotError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot(settings: &[(&str, u64)]) -> DeviceSnapshot {
+        DeviceSnapshot {
+            schema: SCHEMA.to_string(),
+            version: SCHEMA_VERSION,
+            product: ProfileProduct::Canandgyro,
+            serial: None,
+            firmware: None,
+            taken_us: 0,
+            settings: settings
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let resolved = snapshot(&[("yaw_frame_period", 20), ("CanId", 3)])
+            .resolve()
+            .unwrap();
+        let indices: Vec<u8> = resolved.iter().map(|(spec, _)| spec.index).collect();
+        assert_eq!(
+            indices,
+            [
+                cananddevice::types::Setting::CanId as u8,
+                canandmessage::canandgyro::types::Setting::YawFramePeriod as u8,
+            ]
+        );
+
+        assert!(matches!(
+            snapshot(&[("NotASetting", 1)]).resolve(),
+            Err(SnapshotError::UnknownSetting(_))
+        ));
+        assert!(matches!(
+            snapshot(&[("YawFramePeriod", 1 << 48)]).resolve(),
+            Err(SnapshotError::ValueTooLarge(..))
+        ));
+        let mut wrong = snapshot(&[]);
+        wrong.schema = "redux-settings".to_string();
+        assert!(matches!(
+            wrong.resolve(),
+            Err(SnapshotError::WrongSchema(_))
+        ));
+    }
+
+    #[test]
+    fn test_changed() {
+        let yaw = canandmessage::canandgyro::types::Setting::YawFramePeriod as u8;
+        let can_id = cananddevice::types::Setting::CanId as u8;
+        let expected = [(yaw, raw_bytes(20)), (can_id, raw_bytes(3))];
+        assert!(changed(ProfileProduct::Canandgyro, &expected, &expected).is_empty());
+        // the CAN id is expected to differ between devices
+        let actual = [(yaw, raw_bytes(10)), (can_id, raw_bytes(4))];
+        assert_eq!(
+            changed(ProfileProduct::Canandgyro, &expected, &actual),
+            [yaw]
+        );
+    }
+}
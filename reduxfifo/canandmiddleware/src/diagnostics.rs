@@ -0,0 +1,341 @@
+//! Async diagnostics: a [tokio-console](https://github.com/tokio-rs/console) endpoint that can be
+//! turned on and off while the server runs.
+//!
+//! Builds with the `tokio-console` feature (and `RUSTFLAGS="--cfg tokio_unstable"`, which tokio
+//! needs to report its tasks) [`install`] a tracing subscriber that does nothing until the console
+//! is started, so a production install can be looked into for a session without redeploying:
+//!
+//! * [`start_console`] starts the console's gRPC endpoint on any address, for in-process callers
+//! * [`Diagnostics::start_console`] is the same behind a [`DiagnosticsPolicy`], for REST clients
+//!
+//! The console only sees tasks spawned after it's started, and the endpoint itself has no
+//! authentication, so anyone who can reach it can watch every task. Keep it on loopback and tunnel
+//! to it (`ssh -L 6669:localhost:6669`); binding anywhere else over REST takes a diagnostics token.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Instant,
+};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::log::log_info;
+#[cfg(feature = "tokio-console")]
+use crate::log::{log_error, log_warn};
+
+/// Where tokio-console looks by default.
+pub const DEFAULT_CONSOLE_ADDR: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), 6669);
+
+/// Whether the console is running, and where.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsoleStatus {
+    /// Built with the `tokio-console` feature
+    pub built: bool,
+    /// The diagnostics subscriber is the global tracing subscriber
+    pub installed: bool,
+    pub running: bool,
+    pub addr: Option<SocketAddr>,
+    pub uptime_ms: Option<u64>,
+}
+
+/// Why the console wouldn't start or stop.
+#[derive(Debug)]
+pub enum DiagnosticsError {
+    /// Built without the `tokio-console` feature
+    NotBuilt,
+    /// Something else set the global tracing subscriber first
+    NotInstalled,
+    /// A diagnostics token is set and wasn't passed
+    Locked,
+    /// Binding off loopback without a diagnostics token set
+    RemoteNeedsToken(SocketAddr),
+    /// The address couldn't be listened on
+    Bind(SocketAddr, std::io::Error),
+}
+
+impl core::fmt::Display for DiagnosticsError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DiagnosticsError::NotBuilt => {
+                f.write_str("this build doesn't have the tokio-console feature")
+            }
+            DiagnosticsError::NotInstalled => {
+                f.write_str("another tracing subscriber was installed before diagnostics")
+            }
+            DiagnosticsError::Locked => f.write_str("the diagnostics token is missing or wrong"),
+            DiagnosticsError::RemoteNeedsToken(addr) => write!(
+                f,
+                "{addr} isn't a loopback address, which needs a diagnostics token to be set"
+            ),
+            DiagnosticsError::Bind(addr, e) => write!(f, "couldn't listen on {addr}: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for DiagnosticsError {}
+
+/// Who may start and stop the console over REST.
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticsPolicy {
+    /// Has to be passed to start or stop the console, and allows binding off loopback. Without
+    /// one, anyone who can reach the server can start the console on loopback.
+    pub token: Option<String>,
+}
+
+impl DiagnosticsPolicy {
+    /// Checks that `token` may start the console on `addr`, or stop it if `addr` is `None`.
+    pub fn authorize(
+        &self,
+        addr: Option<SocketAddr>,
+        token: Option<&str>,
+    ) -> Result<(), DiagnosticsError> {
+        match &self.token {
+            Some(expected) if token != Some(expected.as_str()) => Err(DiagnosticsError::Locked),
+            Some(_) => Ok(()),
+            None => match addr {
+                Some(addr) if !addr.ip().is_loopback() => {
+                    Err(DiagnosticsError::RemoteNeedsToken(addr))
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+/// The console, as the REST server manages it.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    policy: Arc<DiagnosticsPolicy>,
+}
+
+impl Diagnostics {
+    pub fn new(policy: DiagnosticsPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+        }
+    }
+
+    /// [`start_console`], if `token` is allowed to.
+    pub fn start_console(
+        &self,
+        runtime: &tokio::runtime::Handle,
+        addr: SocketAddr,
+        token: Option<&str>,
+    ) -> Result<ConsoleStatus, DiagnosticsError> {
+        self.policy.authorize(Some(addr), token)?;
+        start_console(runtime, addr)
+    }
+
+    /// [`stop_console`], if `token` is allowed to.
+    pub fn stop_console(&self, token: Option<&str>) -> Result<ConsoleStatus, DiagnosticsError> {
+        self.policy.authorize(None, token)?;
+        Ok(stop_console())
+    }
+}
+
+#[derive(Debug)]
+struct Running {
+    addr: SocketAddr,
+    since: Instant,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[derive(Debug)]
+struct ConsoleState {
+    running: Option<Running>,
+}
+
+static CONSOLE: Mutex<ConsoleState> = Mutex::new(ConsoleState { running: None });
+
+#[cfg(feature = "tokio-console")]
+mod subscriber {
+    use std::sync::OnceLock;
+
+    use console_subscriber::ConsoleLayer;
+    use tracing_subscriber::{
+        Registry,
+        filter::{LevelFilter, Targets},
+        layer::{Layered, SubscriberExt},
+        reload,
+        util::SubscriberInitExt,
+    };
+
+    type Base = Layered<reload::Layer<Targets, Registry>, Registry>;
+
+    pub(super) struct Handles {
+        filter: reload::Handle<Targets, Registry>,
+        console: reload::Handle<Option<ConsoleLayer>, Base>,
+    }
+
+    pub(super) static HANDLES: OnceLock<Handles> = OnceLock::new();
+
+    pub(super) fn install() -> bool {
+        // off until the console starts, so an idle install doesn't track every span
+        let (filter, filter_handle) = reload::Layer::new(Targets::new());
+        let (console, console_handle) = reload::Layer::new(None::<ConsoleLayer>);
+        if tracing_subscriber::registry()
+            .with(filter)
+            .with(console)
+            .try_init()
+            .is_err()
+        {
+            return false;
+        }
+        let _ = HANDLES.set(Handles {
+            filter: filter_handle,
+            console: console_handle,
+        });
+        true
+    }
+
+    impl Handles {
+        pub(super) fn set(&self, console: Option<ConsoleLayer>) {
+            let filter = match console.is_some() {
+                true => Targets::new()
+                    .with_target("tokio", LevelFilter::TRACE)
+                    .with_target("runtime", LevelFilter::TRACE),
+                false => Targets::new(),
+            };
+            let _ = self.console.reload(console);
+            let _ = self.filter.reload(filter);
+        }
+    }
+}
+
+/// Installs the diagnostics subscriber as the global tracing subscriber, with the console off.
+/// Call it once, before the runtime starts, so nothing else takes the spot. Returns whether it
+/// was installed; it never is without the `tokio-console` feature.
+pub fn install() -> bool {
+    #[cfg(feature = "tokio-console")]
+    {
+        subscriber::install()
+    }
+    #[cfg(not(feature = "tokio-console"))]
+    {
+        false
+    }
+}
+
+/// Starts the console's endpoint on `addr`, moving it if it's already running.
+pub fn start_console(
+    runtime: &tokio::runtime::Handle,
+    addr: SocketAddr,
+) -> Result<ConsoleStatus, DiagnosticsError> {
+    #[cfg(feature = "tokio-console")]
+    {
+        let handles = subscriber::HANDLES
+            .get()
+            .ok_or(DiagnosticsError::NotInstalled)?;
+        let mut state = CONSOLE.lock();
+        if let Some(running) = state.running.take() {
+            running.task.abort();
+            handles.set(None);
+        }
+        // the server binds once it's polled, so catch a bad address while there's a caller to
+        // tell about it
+        drop(std::net::TcpListener::bind(addr).map_err(|e| DiagnosticsError::Bind(addr, e))?);
+
+        let (layer, server) = console_subscriber::ConsoleLayer::builder()
+            .with_default_env()
+            .server_addr(addr)
+            .build();
+        handles.set(Some(layer));
+        let task = runtime.spawn(async move {
+            if let Err(e) = server.serve().await {
+                log_error!("tokio-console on {addr} stopped: {e}");
+                // unless it's already been replaced by another one
+                let mut state = CONSOLE.lock();
+                let id = tokio::task::id();
+                if state.running.as_ref().is_some_and(|r| r.task.id() == id) {
+                    state.running = None;
+                    if let Some(handles) = subscriber::HANDLES.get() {
+                        handles.set(None);
+                    }
+                }
+            }
+        });
+        if !addr.ip().is_loopback() {
+            log_warn!("tokio-console listening on {addr}, which isn't loopback");
+        }
+        log_info!("tokio-console started on {addr}");
+        state.running = Some(Running {
+            addr,
+            since: Instant::now(),
+            task,
+        });
+        Ok(status_of(&state))
+    }
+    #[cfg(not(feature = "tokio-console"))]
+    {
+        let _ = (runtime, addr);
+        Err(DiagnosticsError::NotBuilt)
+    }
+}
+
+/// Stops the console, if it's running.
+pub fn stop_console() -> ConsoleStatus {
+    let mut state = CONSOLE.lock();
+    if let Some(running) = state.running.take() {
+        running.task.abort();
+        // dropping the layer winds down the aggregator the server left behind
+        #[cfg(feature = "tokio-console")]
+        if let Some(handles) = subscriber::HANDLES.get() {
+            handles.set(None);
+        }
+        log_info!("tokio-console on {} stopped", running.addr);
+    }
+    status_of(&state)
+}
+
+pub fn console_status() -> ConsoleStatus {
+    status_of(&CONSOLE.lock())
+}
+
+fn status_of(state: &ConsoleState) -> ConsoleStatus {
+    ConsoleStatus {
+        built: cfg!(feature = "tokio-console"),
+        #[cfg(feature = "tokio-console")]
+        installed: subscriber::HANDLES.get().is_some(),
+        #[cfg(not(feature = "tokio-console"))]
+        installed: false,
+        running: state.running.is_some(),
+        addr: state.running.as_ref().map(|r| r.addr),
+        uptime_ms: state
+            .running
+            .as_ref()
+            .map(|r| r.since.elapsed().as_millis() as u64),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_authorize() {
+        let remote: SocketAddr = "0.0.0.0:6669".parse().unwrap();
+        let open = DiagnosticsPolicy::default();
+        assert!(open.authorize(Some(DEFAULT_CONSOLE_ADDR), None).is_ok());
+        assert!(open.authorize(None, None).is_ok());
+        assert!(matches!(
+            open.authorize(Some(remote), None),
+            Err(DiagnosticsError::RemoteNeedsToken(_))
+        ));
+
+        let locked = DiagnosticsPolicy {
+            token: Some("pit".to_owned()),
+        };
+        assert!(matches!(
+            locked.authorize(Some(DEFAULT_CONSOLE_ADDR), None),
+            Err(DiagnosticsError::Locked)
+        ));
+        assert!(matches!(
+            locked.authorize(None, Some("nope")),
+            Err(DiagnosticsError::Locked)
+        ));
+        assert!(locked.authorize(Some(remote), Some("pit")).is_ok());
+    }
+}
@@ -0,0 +1,223 @@
+//! Firmware images bundled with the tools, and which devices they'd update.
+//!
+//! An index lists the firmware files shipped alongside Alchemist or the standalone server, one per
+//! product and version:
+//!
+//! ```json
+//! {
+//!   "firmware": [
+//!     { "product": "canandgyro", "version": "2025.2.0", "file": "canandgyro-2025.2.0.rdxf" },
+//!     { "product": "canandmag", "version": "2025.1.3", "file": "canandmag-2025.1.3.rdxf" }
+//!   ]
+//! }
+//! ```
+//!
+//! Products are named as in [`firmware_notes`](crate::firmware_notes). Relative `file` paths are
+//! taken relative to the index file, and every file has to exist when the index is loaded so an
+//! update started from a [`FirmwareStatus`] doesn't fail halfway through a fleet.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use frc_can_id::FRCCanId;
+use parking_lot::{Mutex, RwLock};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bus::{
+        BusState,
+        device::{DeviceKey, serial_str},
+    },
+    firmware_notes::{Version, product_name},
+    inventory::InventoryReport,
+};
+
+/// One firmware file in the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledFirmware {
+    pub product: String,
+    pub version: Version,
+    pub file: PathBuf,
+}
+
+/// Bundled firmware; see the module docs for the format.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FirmwareIndexFile {
+    pub firmware: Vec<BundledFirmware>,
+}
+
+impl FirmwareIndexFile {
+    /// Loads an index, resolving its file paths against the directory it's in.
+    pub fn load_file(path: &Path) -> Result<Self, FirmwareIndexError> {
+        let data = std::fs::read(path).map_err(FirmwareIndexError::Io)?;
+        let mut index: Self = serde_json::from_slice(&data).map_err(FirmwareIndexError::Json)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for fw in index.firmware.iter_mut() {
+            fw.file = dir.join(&fw.file);
+        }
+        index.validate()?;
+        Ok(index)
+    }
+
+    /// Checks that every file the index lists is there.
+    pub fn validate(&self) -> Result<(), FirmwareIndexError> {
+        match self.firmware.iter().find(|fw| !fw.file.is_file()) {
+            Some(fw) => Err(FirmwareIndexError::MissingFile(fw.file.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Newest firmware bundled for `product`.
+    pub fn latest(&self, product: &str) -> Option<&BundledFirmware> {
+        self.firmware
+            .iter()
+            .filter(|fw| fw.product == product)
+            .max_by_key(|fw| fw.version)
+    }
+}
+
+/// How one device's firmware compares to what's bundled.
+#[derive(Debug, Clone, Serialize)]
+pub struct FirmwareStatus {
+    pub bus_id: u16,
+    /// Device type and CAN id, e.g. `Encoder:3`
+    pub device: String,
+    pub can_id: u32,
+    pub product: Option<&'static str>,
+    pub serial: Option<String>,
+    /// Unset if the device didn't report it
+    pub firmware: Option<Version>,
+    pub bootloader: bool,
+    /// Newest firmware bundled for the product
+    pub bundled: Option<BundledFirmware>,
+    /// The bundled firmware is newer than what the device runs. Devices stuck in their bootloader
+    /// always need it.
+    pub update_available: bool,
+}
+
+/// Firmware of every device on every bus, against what's bundled.
+#[derive(Debug, Clone, Serialize)]
+pub struct FirmwareStatusReport {
+    pub devices: Vec<FirmwareStatus>,
+    /// Buses that could not be inventoried, and why
+    pub errors: Vec<String>,
+}
+
+/// The firmware index in use, shared by the REST server. Nothing is bundled until one is set.
+#[derive(Debug, Clone, Default)]
+pub struct FirmwareIndex {
+    index: Arc<RwLock<FirmwareIndexFile>>,
+}
+
+impl FirmwareIndex {
+    /// Replaces the index, returning how many files it has.
+    pub fn set(&self, index: FirmwareIndexFile) -> Result<usize, FirmwareIndexError> {
+        index.validate()?;
+        let count = index.firmware.len();
+        *self.index.write() = index;
+        Ok(count)
+    }
+
+    pub fn load_file(&self, path: &Path) -> Result<usize, FirmwareIndexError> {
+        self.set(FirmwareIndexFile::load_file(path)?)
+    }
+
+    pub fn index(&self) -> FirmwareIndexFile {
+        self.index.read().clone()
+    }
+
+    /// Compares every device in `inventory` to the bundled firmware.
+    pub fn status(
+        &self,
+        inventory: &InventoryReport,
+        bus_sessions: &Mutex<FxHashMap<u16, BusState>>,
+    ) -> FirmwareStatusReport {
+        let index = self.index.read();
+        let bus_sessions = bus_sessions.lock();
+        let devices = inventory
+            .devices
+            .iter()
+            .map(|ent| {
+                let key = DeviceKey::from(FRCCanId::new(ent.can_id));
+                let dev = bus_sessions
+                    .get(&ent.bus_id)
+                    .and_then(|state| state.devices.get(&key));
+                let serial = dev.and_then(|dev| dev.serial_numer());
+                let firmware = dev
+                    .and_then(|dev| dev.firmware_version())
+                    .map(Version::from);
+                let product = product_name(&key, serial);
+                let bundled = product.and_then(|product| index.latest(product)).cloned();
+                let update_available = bundled.as_ref().is_some_and(|bundled| {
+                    ent.bootloader || firmware.is_some_and(|fw| bundled.version > fw)
+                });
+                FirmwareStatus {
+                    bus_id: ent.bus_id,
+                    device: ent.device.clone(),
+                    can_id: ent.can_id,
+                    product,
+                    serial: serial.map(|s| serial_str(Some(s))),
+                    firmware,
+                    bootloader: ent.bootloader,
+                    bundled,
+                    update_available,
+                }
+            })
+            .collect();
+        FirmwareStatusReport {
+            devices,
+            errors: inventory.errors.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FirmwareIndexError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The index lists a file that isn't there
+    MissingFile(PathBuf),
+}
+
+impl core::fmt::Display for FirmwareIndexError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FirmwareIndexError::Io(e) => write!(f, "couldn't read firmware index: {e}"),
+            FirmwareIndexError::Json(e) => write!(f, "invalid firmware index: {e}"),
+            FirmwareIndexError::MissingFile(path) => {
+                write!(f, "firmware file {} doesn't exist", path.display())
+            }
+        }
+    }
+}
+
+impl core::error::Error for FirmwareIndexError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_latest() {
+        let index: FirmwareIndexFile = serde_json::from_str(
+            r#"{"firmware": [
+                {"product": "canandgyro", "version": "2025.1.0", "file": "a.rdxf"},
+                {"product": "canandgyro", "version": "2025.2.0", "file": "b.rdxf"},
+                {"product": "canandmag", "version": "2026.0.0", "file": "c.rdxf"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            index.latest("canandgyro").unwrap().version,
+            "2025.2.0".parse().unwrap()
+        );
+        assert!(index.latest("canandcolor").is_none());
+        assert!(matches!(
+            index.validate(),
+            Err(FirmwareIndexError::MissingFile(_))
+        ));
+    }
+}
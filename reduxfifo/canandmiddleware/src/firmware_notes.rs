@@ -0,0 +1,322 @@
+//! Release notes and known issues for device firmware.
+//!
+//! Lookups go through a [`FirmwareMetadataProvider`], so where the data comes from is up to
+//! whoever starts the server. The one provided here is [`MetadataBundle`], a JSON file shipped
+//! alongside the tools, which works without a network connection:
+//!
+//! ```json
+//! {
+//!   "products": {
+//!     "canandgyro": {
+//!       "releases": [{ "version": "2025.1.0", "date": "2025-01-04", "notes": "Season release" }],
+//!       "known_issues": [{
+//!         "id": "yaw-drift",
+//!         "summary": "Yaw drifts after long periods at rest",
+//!         "severity": "warning",
+//!         "introduced_in": "2024.0.0",
+//!         "fixed_in": "2025.2.0",
+//!         "workaround": "Update firmware"
+//!       }]
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Products are named like the devices are sold: `canandmag`, `canandgyro`, `canandcolor`, ...
+
+use std::{fmt, path::Path, str::FromStr, sync::Arc};
+
+use parking_lot::RwLock;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use serial_numer::{ProductId, SerialNumer};
+
+use crate::{
+    bus::device::{DeviceKey, ReduxDeviceType},
+    firmware_index::FirmwareIndex,
+    migration::Migrations,
+};
+
+/// Firmware version, `year.minor.patch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub year: u16,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl From<canandmessage::cananddevice::types::FirmwareVersion> for Version {
+    fn from(value: canandmessage::cananddevice::types::FirmwareVersion) -> Self {
+        Self {
+            year: value.firmware_year,
+            minor: value.firmware_minor,
+            patch: value.firmware_patch,
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = ();
+
+    /// Parses `2025.1.0`, with or without a leading `v`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let mut parts = s.split('.');
+        let year = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+        let minor = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+        let patch = parts.next().ok_or(())?.parse().map_err(|_| ())?;
+        if parts.next().is_some() {
+            return Err(());
+        }
+        Ok(Self { year, minor, patch })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.year, self.minor, self.patch)
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid firmware version {s:?}")))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Notes for a single firmware release.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseNote {
+    pub version: Version,
+    #[serde(default)]
+    pub date: Option<String>,
+    pub notes: String,
+}
+
+/// A known firmware bug, affecting versions from `introduced_in` up to but not including `fixed_in`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownIssue {
+    pub id: String,
+    pub summary: String,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Unset means every version before `fixed_in`
+    #[serde(default)]
+    pub introduced_in: Option<Version>,
+    /// Unset means not fixed yet
+    #[serde(default)]
+    pub fixed_in: Option<Version>,
+    #[serde(default)]
+    pub workaround: Option<String>,
+}
+
+impl KnownIssue {
+    pub fn affects(&self, version: Version) -> bool {
+        self.introduced_in.is_none_or(|v| version >= v) && self.fixed_in.is_none_or(|v| version < v)
+    }
+}
+
+/// Everything known about one firmware version of a product.
+#[derive(Debug, Clone, Serialize)]
+pub struct FirmwareNotes {
+    pub product: String,
+    pub version: Version,
+    /// Notes for this exact release, if there are any
+    pub release: Option<ReleaseNote>,
+    /// Newest release we know of
+    pub latest: Option<Version>,
+    pub update_available: bool,
+    /// Issues that affect this version
+    pub known_issues: Vec<KnownIssue>,
+}
+
+/// Source of firmware release notes and known issues.
+pub trait FirmwareMetadataProvider: fmt::Debug + Send + Sync {
+    /// Looks up `version` of `product`. Returns `None` if the provider knows nothing about the product.
+    fn lookup(&self, product: &str, version: Version) -> Option<FirmwareNotes>;
+}
+
+/// Release notes and known issues of one product.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProductMetadata {
+    #[serde(default)]
+    pub releases: Vec<ReleaseNote>,
+    #[serde(default)]
+    pub known_issues: Vec<KnownIssue>,
+}
+
+/// Metadata bundled as a JSON file; see the module docs for the format.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataBundle {
+    pub products: FxHashMap<String, ProductMetadata>,
+}
+
+impl MetadataBundle {
+    pub fn load_file(path: &Path) -> Result<Self, MetadataError> {
+        let data = std::fs::read(path).map_err(MetadataError::Io)?;
+        serde_json::from_slice(&data).map_err(MetadataError::Json)
+    }
+}
+
+impl FirmwareMetadataProvider for MetadataBundle {
+    fn lookup(&self, product: &str, version: Version) -> Option<FirmwareNotes> {
+        let meta = self.products.get(product)?;
+        let latest = meta.releases.iter().map(|r| r.version).max();
+        Some(FirmwareNotes {
+            product: product.to_string(),
+            version,
+            release: meta.releases.iter().find(|r| r.version == version).cloned(),
+            latest,
+            update_available: latest.is_some_and(|latest| latest > version),
+            known_issues: meta
+                .known_issues
+                .iter()
+                .filter(|issue| issue.affects(version))
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+/// The firmware metadata provider in use, shared by the REST server. Lookups return nothing until one is set.
+#[derive(Debug, Clone, Default)]
+pub struct FirmwareMetadata {
+    provider: Arc<RwLock<Option<Arc<dyn FirmwareMetadataProvider>>>>,
+    migrations: Migrations,
+    bundled: FirmwareIndex,
+}
+
+impl FirmwareMetadata {
+    pub fn set_provider(&self, provider: impl FirmwareMetadataProvider + 'static) {
+        *self.provider.write() = Some(Arc::new(provider));
+    }
+
+    /// Uses a [`MetadataBundle`] from `path`, returning how many products it covers.
+    pub fn load_file(&self, path: &Path) -> Result<usize, MetadataError> {
+        let bundle = MetadataBundle::load_file(path)?;
+        let count = bundle.products.len();
+        self.set_provider(bundle);
+        Ok(count)
+    }
+
+    /// How settings carry across firmware updates, for restoring settings files.
+    pub fn migrations(&self) -> &Migrations {
+        &self.migrations
+    }
+
+    /// Firmware files shipped alongside the tools, for finding outdated devices.
+    pub fn bundled(&self) -> &FirmwareIndex {
+        &self.bundled
+    }
+
+    pub fn lookup(&self, product: &str, version: Version) -> Option<FirmwareNotes> {
+        let provider = self.provider.read().clone()?;
+        provider.lookup(product, version)
+    }
+
+    /// Looks up the firmware a device reported, if we know enough about the device to do so.
+    pub fn for_device(
+        &self,
+        key: &DeviceKey,
+        serial: Option<SerialNumer>,
+        firmware: Option<canandmessage::cananddevice::types::FirmwareVersion>,
+    ) -> Option<FirmwareNotes> {
+        self.lookup(product_name(key, serial)?, firmware?.into())
+    }
+}
+
+/// Product name of a device, from its serial numer if we have it and its CAN device type otherwise.
+pub fn product_name(key: &DeviceKey, serial: Option<SerialNumer>) -> Option<&'static str> {
+    if let Some(serial) = serial {
+        match serial.product_id() {
+            ProductId::Encoder => return Some("canandmag"),
+            ProductId::Gyro => return Some("canandgyro"),
+            ProductId::CanAdapter => return Some("canandapter"),
+            ProductId::Sandworm => return Some("canandcolor"),
+            ProductId::Nitrate => return Some("nitrate"),
+            _ => {}
+        }
+    }
+    match key.dev_type {
+        ReduxDeviceType::Encoder => Some("canandmag"),
+        ReduxDeviceType::Gyroscope => Some("canandgyro"),
+        ReduxDeviceType::ColorDistanceSensor => Some("canandcolor"),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::Io(e) => write!(f, "couldn't read firmware metadata: {e}"),
+            MetadataError::Json(e) => write!(f, "invalid firmware metadata file: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for MetadataError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bundle_lookup() {
+        let bundle: MetadataBundle = serde_json::from_str(
+            r#"{"products": {"canandgyro": {
+                "releases": [
+                    {"version": "2025.1.0", "notes": "season release"},
+                    {"version": "2025.2.0", "notes": "fixes yaw drift"}
+                ],
+                "known_issues": [{
+                    "id": "yaw-drift", "summary": "yaw drifts", "severity": "warning",
+                    "introduced_in": "2024.0.0", "fixed_in": "2025.2.0"
+                }]
+            }}}"#,
+        )
+        .unwrap();
+
+        let old = bundle
+            .lookup("canandgyro", "v2025.1.0".parse().unwrap())
+            .unwrap();
+        assert_eq!(old.release.unwrap().notes, "season release");
+        assert!(old.update_available);
+        assert_eq!(old.known_issues.len(), 1);
+
+        let fixed = bundle
+            .lookup("canandgyro", "2025.2.0".parse().unwrap())
+            .unwrap();
+        assert!(!fixed.update_available);
+        assert!(fixed.known_issues.is_empty());
+
+        assert!(
+            bundle
+                .lookup("canandmag", "2025.1.0".parse().unwrap())
+                .is_none()
+        );
+        assert!("2025.1".parse::<Version>().is_err());
+    }
+}
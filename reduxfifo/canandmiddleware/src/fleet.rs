@@ -0,0 +1,219 @@
+//! Devices across every bus session, with a device reachable on several buses shown once.
+//!
+//! On bridged networks the same device can be seen by more than one bus session, and each session
+//! tracks it separately. Devices that report the same serial numer are merged into one
+//! [`FleetDevice`] listing every address it's reachable at, best first. Control operations sent by
+//! serial numer go to the first address, and move on to the next if writing to that bus fails.
+//!
+//! Devices that haven't reported a serial numer yet can't be matched up, so each is listed on its
+//! own.
+
+use std::{collections::BTreeMap, time::Instant};
+
+use fifocore::error::Error;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use serial_numer::SerialNumer;
+
+use crate::{
+    bus::{
+        BusState,
+        device::{Device, DeviceKey, DeviceType, firmware_str, serial_str},
+    },
+    log::*,
+};
+
+/// Addresses whose device has been quiet for longer than this, in milliseconds, are only used once
+/// the ones that haven't are exhausted.
+pub const STALE_ADDRESS_MS: u64 = 1000;
+
+/// One bus a device can be reached on.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceAddress {
+    pub bus_id: u16,
+    /// Device type and CAN id on this bus, e.g. `Encoder:3`
+    pub device: String,
+    pub can_id: u32,
+    /// Milliseconds since the device last sent a frame on this bus
+    pub last_active_ms: Option<u64>,
+    pub rebooting: bool,
+}
+
+impl DeviceAddress {
+    fn new(bus_id: u16, key: &DeviceKey, dev: &Device, now: Instant) -> Self {
+        Self {
+            bus_id,
+            device: key.pretty_str(),
+            can_id: key.can_id(),
+            last_active_ms: dev
+                .last_active()
+                .map(|ts| now.saturating_duration_since(ts).as_millis() as u64),
+            rebooting: dev.rebooting(),
+        }
+    }
+
+    /// Sort key for picking an address: usable ones first, then by bus id so the pick doesn't
+    /// flap between buses that are both fine.
+    fn preference(&self) -> (bool, bool, u16) {
+        let stale = self.last_active_ms.is_none_or(|age| age > STALE_ADDRESS_MS);
+        (self.rebooting, stale, self.bus_id)
+    }
+}
+
+/// A device and every bus it's been seen on.
+#[derive(Debug, Clone, Serialize)]
+pub struct FleetDevice {
+    pub serial: Option<String>,
+    /// As reported on the preferred address
+    pub device_type: DeviceType,
+    pub firmware: Option<String>,
+    /// Best first; control operations by serial numer go to the first one
+    pub addresses: Vec<DeviceAddress>,
+}
+
+/// Serial numer a device can be merged on, if it has reported a real one.
+fn merge_serial(dev: &Device) -> Option<SerialNumer> {
+    dev.serial_numer()
+        .filter(|serial| !(serial.is_zero() || serial.is_unset()))
+}
+
+/// Lists every device on every bus session, merging devices with the same serial numer.
+pub fn devices(bus_sessions: &FxHashMap<u16, BusState>) -> Vec<FleetDevice> {
+    let now = Instant::now();
+    let mut merged: BTreeMap<String, Vec<(DeviceAddress, &Device)>> = BTreeMap::new();
+    let mut unmerged = Vec::new();
+    for (&bus_id, state) in bus_sessions.iter() {
+        for (key, dev) in state.devices.iter() {
+            let address = DeviceAddress::new(bus_id, key, dev, now);
+            match merge_serial(dev) {
+                Some(serial) => merged
+                    .entry(serial_str(Some(serial)))
+                    .or_default()
+                    .push((address, dev)),
+                None => unmerged.push(vec![(address, dev)]),
+            }
+        }
+    }
+
+    let mut devices: Vec<FleetDevice> = merged
+        .into_values()
+        .chain(unmerged)
+        .map(|mut seen| {
+            seen.sort_by_key(|(address, _)| address.preference());
+            let dev = seen[0].1;
+            FleetDevice {
+                serial: dev.serial_numer().map(|s| serial_str(Some(s))),
+                device_type: dev.dev_type(now),
+                firmware: dev.firmware_version().map(|fw| firmware_str(Some(fw))),
+                addresses: seen.into_iter().map(|(address, _)| address).collect(),
+            }
+        })
+        .collect();
+    devices.sort_by_key(|dev| {
+        let first = &dev.addresses[0];
+        (dev.serial.is_none(), first.bus_id, first.can_id)
+    });
+    devices
+}
+
+/// Addresses of the device with `serial`, best first.
+pub fn addresses(
+    bus_sessions: &FxHashMap<u16, BusState>,
+    serial: SerialNumer,
+) -> Vec<DeviceAddress> {
+    let now = Instant::now();
+    let mut addresses: Vec<DeviceAddress> = bus_sessions
+        .iter()
+        .flat_map(|(&bus_id, state)| {
+            state
+                .devices
+                .iter()
+                .filter(|(_, dev)| merge_serial(dev) == Some(serial))
+                .map(move |(key, dev)| DeviceAddress::new(bus_id, key, dev, now))
+        })
+        .collect();
+    addresses.sort_by_key(DeviceAddress::preference);
+    addresses
+}
+
+/// Which address a routed operation went out on.
+#[derive(Debug, Clone, Serialize)]
+pub struct Routed {
+    pub bus_id: u16,
+    pub can_id: u32,
+    /// Addresses tried and given up on before this one
+    pub failovers: u32,
+}
+
+#[derive(Debug)]
+pub enum RouteError {
+    /// No bus session has seen a device with this serial numer
+    UnknownSerial(String),
+    /// Every address failed; this is the error from the last one
+    FIFOCore(Error),
+}
+
+impl core::fmt::Display for RouteError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RouteError::UnknownSerial(serial) => {
+                write!(f, "No device with serial numer {serial} has been seen")
+            }
+            RouteError::FIFOCore(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Whether an error writing to a bus says to try the device's next address rather than give up.
+fn fails_over(err: Error) -> bool {
+    matches!(
+        err,
+        Error::InvalidBus
+            | Error::BusClosed
+            | Error::BusWriteFail
+            | Error::BusBufferFull
+            | Error::BusDeviceBusy
+            | Error::UsbClosed
+    )
+}
+
+/// Runs `op` against the device with `serial` on its preferred address, failing over to the next
+/// when a bus can't take the write.
+///
+/// `op` gets the bus session and the device's base CAN id on that bus.
+pub fn route(
+    bus_sessions: &mut FxHashMap<u16, BusState>,
+    serial: SerialNumer,
+    mut op: impl FnMut(&mut BusState, u32) -> Result<(), Error>,
+) -> Result<Routed, RouteError> {
+    let addresses = addresses(bus_sessions, serial);
+    let mut last_err = None;
+    for (failovers, address) in addresses.iter().enumerate() {
+        let Some(state) = bus_sessions.get_mut(&address.bus_id) else {
+            continue;
+        };
+        match op(state, address.can_id) {
+            Ok(()) => {
+                return Ok(Routed {
+                    bus_id: address.bus_id,
+                    can_id: address.can_id,
+                    failovers: failovers as u32,
+                });
+            }
+            Err(e) if fails_over(e) => {
+                log_warn!(
+                    "[bus {}] couldn't reach {} {}: {e}; trying its next address",
+                    address.bus_id,
+                    serial_str(Some(serial)),
+                    address.device
+                );
+                last_err = Some(e);
+            }
+            Err(e) => return Err(RouteError::FIFOCore(e)),
+        }
+    }
+    Err(match last_err {
+        Some(e) => RouteError::FIFOCore(e),
+        None => RouteError::UnknownSerial(serial_str(Some(serial))),
+    })
+}
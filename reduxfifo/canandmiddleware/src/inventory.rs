@@ -0,0 +1,182 @@
+//! Firmware inventory reports.
+//!
+//! Collects the product, serial numer, hardware revision and firmware version of every Redux
+//! device on every open bus, for pre-event inspection and for keeping track of which robots
+//! still need firmware updates.
+//!
+//! A report is written as a JSON file plus a plain-text table. The JSON carries a CRC32 over its
+//! own contents (with `checksum` zeroed) so a report that was edited by hand can be told apart
+//! from one that came straight out of ReduxFIFO. This is an integrity check, not a signature.
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+use fifocore::FIFOCore;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::{
+    bus::{
+        self, BusState,
+        device::{DeviceType, firmware_str, serial_str},
+    },
+    profile::Profiles,
+};
+
+/// One device in an inventory report.
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryEntry {
+    pub bus_id: u16,
+    /// Bus open params, e.g. `halcan` or `rdxusb:0`
+    pub bus: String,
+    /// Device type and CAN id, e.g. `Encoder:3`
+    pub device: String,
+    pub can_id: u32,
+    pub device_type: DeviceType,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+    pub revision: Option<u8>,
+    pub firmware: Option<String>,
+    pub bootloader: bool,
+}
+
+/// Inventory of every Redux device seen across all buses.
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryReport {
+    /// Unix time the report was generated, in microseconds
+    pub generated_us: i64,
+    pub reduxfifo_version: &'static str,
+    pub devices: Vec<InventoryEntry>,
+    /// Buses that could not be inventoried, and why
+    pub errors: Vec<String>,
+    /// CRC32/MPEG-2 of this report serialized with `checksum` set to 0
+    pub checksum: u32,
+}
+
+impl InventoryReport {
+    /// Enumerates every bus, asks each device for its firmware version and collects the results.
+    ///
+    /// Buses that don't have a device session in `bus_sessions` get one opened, which applies
+    /// `profiles` like any other session. `settle` is how long to wait for enumerate and firmware
+    /// version responses.
+    pub async fn collect(
+        fifocore: &FIFOCore,
+        bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+        profiles: &Profiles,
+        settle: Duration,
+    ) -> Self {
+        let mut bus_ids = fifocore.buses();
+        bus_ids.sort_unstable();
+        let errors = bus::enumerate_buses(bus_sessions, fifocore, &bus_ids, profiles);
+        tokio::time::sleep(settle).await;
+
+        // devices only report their firmware version when asked
+        for &bus_id in bus_ids.iter() {
+            let mut guard = bus_sessions.lock();
+            let Some(state) = guard.get_mut(&bus_id) else {
+                continue;
+            };
+            let ids: Vec<u32> = state.devices.keys().map(|k| k.can_id()).collect();
+            for id in ids {
+                let _ = state.send_fetch_setting(
+                    id,
+                    canandmessage::cananddevice::types::Setting::FirmwareVersion as u8,
+                );
+            }
+        }
+        tokio::time::sleep(settle).await;
+
+        let params: FxHashMap<u16, String> = fifocore.with_buses(|buses| {
+            buses
+                .iter()
+                .map(|(&id, ent)| (id, ent.params().to_string()))
+                .collect()
+        });
+        let now = std::time::Instant::now();
+        let mut devices = Vec::new();
+        {
+            let guard = bus_sessions.lock();
+            for &bus_id in bus_ids.iter() {
+                let Some(state) = guard.get(&bus_id) else {
+                    continue;
+                };
+                for (key, dev) in state.devices.iter() {
+                    let serial = dev.serial_numer();
+                    devices.push(InventoryEntry {
+                        bus_id,
+                        bus: params.get(&bus_id).cloned().unwrap_or_default(),
+                        device: key.pretty_str(),
+                        can_id: key.can_id(),
+                        device_type: dev.dev_type(now),
+                        product: serial.map(|s| format!("{:?}", s.product_id())),
+                        serial: serial.map(|s| serial_str(Some(s))),
+                        revision: serial.map(|s| s.revision_id()),
+                        firmware: dev.firmware_version().map(|fw| firmware_str(Some(fw))),
+                        bootloader: dev.bootloader(),
+                    });
+                }
+            }
+        }
+        devices.sort_by(|a, b| (a.bus_id, &a.device).cmp(&(b.bus_id, &b.device)));
+
+        let mut report = Self {
+            generated_us: fifocore::timebase::now_us(),
+            reduxfifo_version: env!("CARGO_PKG_VERSION"),
+            devices,
+            errors,
+            checksum: 0,
+        };
+        report.checksum = report.compute_checksum();
+        report
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        let mut unsigned = self.clone();
+        unsigned.checksum = 0;
+        let json = serde_json::to_vec(&unsigned).unwrap_or_default();
+        rdxcrc::crc32_mpeg2(0xffff_ffff, &json)
+    }
+
+    /// Human-readable table of the report.
+    pub fn to_table(&self) -> String {
+        let mut out = format!(
+            "ReduxFIFO {} firmware inventory, generated at unix time {}.{:06}\n",
+            self.reduxfifo_version,
+            self.generated_us / 1_000_000,
+            self.generated_us % 1_000_000
+        );
+        out.push_str(&format!(
+            "{:<4} {:<24} {:<12} {:<17} {:>3} {:<12}\n",
+            "bus", "device", "product", "serial", "rev", "firmware"
+        ));
+        for ent in self.devices.iter() {
+            let firmware = match (&ent.firmware, ent.bootloader) {
+                (_, true) => "bootloader".to_string(),
+                (Some(fw), false) => fw.clone(),
+                (None, false) => "?".to_string(),
+            };
+            out.push_str(&format!(
+                "{:<4} {:<24} {:<12} {:<17} {:>3} {:<12}\n",
+                ent.bus_id,
+                ent.device,
+                ent.product.as_deref().unwrap_or("?"),
+                ent.serial.as_deref().unwrap_or("?"),
+                ent.revision.map_or("?".to_string(), |r| r.to_string()),
+                firmware
+            ));
+        }
+        for err in self.errors.iter() {
+            out.push_str(&format!("error: {err}\n"));
+        }
+        out.push_str(&format!("checksum: {:08x}\n", self.checksum));
+        out
+    }
+
+    /// Writes the report to `{stem}.json` and `{stem}.txt`.
+    pub fn write_files(&self, stem: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(stem.with_extension("json"), json)?;
+        std::fs::write(stem.with_extension("txt"), self.to_table())?;
+        Ok(())
+    }
+}
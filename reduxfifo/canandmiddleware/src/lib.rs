@@ -1,6 +1,39 @@
 pub mod backend;
+pub mod bench;
+pub mod bulk;
 pub mod ota;
 pub mod bus;
+pub mod calibration;
+pub mod confirm;
+pub mod device_snapshot;
+pub mod diagnostics;
+pub mod firmware_index;
+pub mod firmware_notes;
+pub mod fleet;
+pub mod inventory;
 pub mod log;
+pub mod migration;
+pub mod mirror;
+#[cfg(windows)]
+pub mod named_pipe;
+pub mod problem;
+pub mod profile;
 pub mod rest_server;
+pub mod schedule;
+pub mod settings_file;
+#[cfg(feature = "simulation")]
+pub mod sim;
+pub mod snapshot;
+pub mod telemetry;
+pub mod watchdog;
 pub mod websocket;
+
+fifocore::feature_matrix! {
+    /// Features canandmiddleware was built with.
+    pub struct Capabilities {
+        /// Virtual devices on `sim:` buses, controlled over REST
+        simulation = "simulation",
+        /// A tokio-console endpoint, started at runtime
+        tokio_console = "tokio-console",
+    }
+}
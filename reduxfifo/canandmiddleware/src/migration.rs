@@ -0,0 +1,349 @@
+//! Settings migrations between firmware versions.
+//!
+//! Settings files store values by setting name as the device reported them, so a setting that was
+//! only renumbered comes back right on its own. One that was renamed, rescaled or dropped by a
+//! firmware update does not, and writing the old value to the new firmware would configure the
+//! device wrong. A migration table says how to carry values across such updates:
+//!
+//! ```json
+//! {
+//!   "migrations": [{
+//!     "product": "canandmag",
+//!     "from": "2024.0.0",
+//!     "to": "2025.0.0",
+//!     "transforms": [
+//!       { "op": "rename", "from": "VelocityWindow", "to": "VelocityFilterWidth" },
+//!       { "op": "scale", "setting": "VelocityFilterWidth", "multiply": 1000, "divide": 1 },
+//!       { "op": "remove", "setting": "LegacyMode" },
+//!       { "op": "set", "setting": "ZeroOffsetMode", "value": 1 }
+//!     ]
+//!   }]
+//! }
+//! ```
+//!
+//! A migration takes values saved by firmware from `from` up to but not including `to` into `to`'s
+//! layout. When a file is imported onto a device running newer firmware than the file was exported
+//! from, the product's migrations are chained in order of `to`, starting at the file's version and
+//! stopping at the device's. A chain that would start a migration on settings older than its
+//! `from` is refused, since nothing says what they looked like there. Devices without a known
+//! firmware version on either side aren't migrated.
+
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    firmware_notes::Version,
+    profile::{ProfileProduct, normalize_name},
+};
+
+/// One change a firmware update made to a product's settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Transform {
+    /// The setting is called `to` now
+    Rename { from: String, to: String },
+    /// The value is multiplied by `multiply / divide`, rounding down
+    Scale {
+        setting: String,
+        #[serde(default = "one")]
+        multiply: u64,
+        #[serde(default = "one")]
+        divide: u64,
+    },
+    /// The setting no longer exists
+    Remove { setting: String },
+    /// The setting gets this value regardless of what was saved
+    Set { setting: String, value: u64 },
+}
+
+fn one() -> u64 {
+    1
+}
+
+/// Finds the saved setting `name` refers to, ignoring case and underscores like everywhere else.
+fn find(settings: &BTreeMap<String, u64>, name: &str) -> Option<String> {
+    let wanted = normalize_name(name);
+    settings
+        .keys()
+        .find(|key| normalize_name(key) == wanted)
+        .cloned()
+}
+
+impl Transform {
+    /// Applies the transform, describing what it changed. Transforms of settings that weren't
+    /// saved change nothing.
+    fn apply(&self, settings: &mut BTreeMap<String, u64>) -> Option<String> {
+        match self {
+            Transform::Rename { from, to } => {
+                let value = settings.remove(&find(settings, from)?)?;
+                settings.insert(to.clone(), value);
+                Some(format!("renamed {from} to {to}"))
+            }
+            Transform::Scale {
+                setting,
+                multiply,
+                divide,
+            } => {
+                let value = settings.get_mut(&find(settings, setting)?)?;
+                let old = *value;
+                let scaled = old as u128 * *multiply as u128 / *divide as u128;
+                *value = scaled.min(u64::MAX as u128) as u64;
+                Some(format!("scaled {setting} from {old} to {value}"))
+            }
+            Transform::Remove { setting } => {
+                settings.remove(&find(settings, setting)?)?;
+                Some(format!("removed {setting}"))
+            }
+            Transform::Set { setting, value } => {
+                if let Some(key) = find(settings, setting) {
+                    settings.remove(&key);
+                }
+                settings.insert(setting.clone(), *value);
+                Some(format!("set {setting} to {value}"))
+            }
+        }
+    }
+}
+
+/// How a product's settings changed between two firmware versions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Migration {
+    pub product: ProfileProduct,
+    pub from: Version,
+    pub to: Version,
+    pub transforms: Vec<Transform>,
+}
+
+/// What one migration did to the settings of one device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AppliedMigration {
+    pub from: Version,
+    pub to: Version,
+    /// One line per transform that changed something
+    pub changes: Vec<String>,
+}
+
+/// Every known migration; see the module docs for the format.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationTable {
+    #[serde(default)]
+    pub migrations: Vec<Migration>,
+}
+
+impl MigrationTable {
+    /// Checks that every migration goes forward, doesn't divide by zero, and that no two
+    /// migrations of a product end at the same version, so chains are never ambiguous.
+    pub fn validate(&self) -> Result<(), MigrationError> {
+        for (i, migration) in self.migrations.iter().enumerate() {
+            if migration.from >= migration.to {
+                return Err(MigrationError::Backwards(
+                    migration.product,
+                    migration.from,
+                    migration.to,
+                ));
+            }
+            if migration
+                .transforms
+                .iter()
+                .any(|t| matches!(t, Transform::Scale { divide: 0, .. }))
+            {
+                return Err(MigrationError::DivideByZero(
+                    migration.product,
+                    migration.to,
+                ));
+            }
+            if self.migrations[..i]
+                .iter()
+                .any(|m| m.product == migration.product && m.to == migration.to)
+            {
+                return Err(MigrationError::Duplicate(migration.product, migration.to));
+            }
+        }
+        Ok(())
+    }
+
+    /// Carries `settings` saved by firmware `saved` forward to `running`, returning what each
+    /// migration along the way changed.
+    pub fn migrate(
+        &self,
+        product: ProfileProduct,
+        saved: Version,
+        running: Version,
+        settings: &mut BTreeMap<String, u64>,
+    ) -> Result<Vec<AppliedMigration>, MigrationError> {
+        let mut steps: Vec<&Migration> = self
+            .migrations
+            .iter()
+            .filter(|m| m.product == product && m.to > saved && m.to <= running)
+            .collect();
+        steps.sort_by_key(|m| m.to);
+
+        let mut current = saved;
+        let mut applied = Vec::with_capacity(steps.len());
+        for step in steps {
+            if step.from > current {
+                return Err(MigrationError::NoPath(product, current, step.from));
+            }
+            applied.push(AppliedMigration {
+                from: current,
+                to: step.to,
+                changes: step
+                    .transforms
+                    .iter()
+                    .filter_map(|t| t.apply(settings))
+                    .collect(),
+            });
+            current = step.to;
+        }
+        Ok(applied)
+    }
+
+    pub fn load_file(path: &Path) -> Result<Self, MigrationError> {
+        let data = std::fs::read(path).map_err(MigrationError::Io)?;
+        let table: Self = serde_json::from_slice(&data).map_err(MigrationError::Json)?;
+        table.validate()?;
+        Ok(table)
+    }
+}
+
+/// The migration table in use, shared by the REST server.
+#[derive(Debug, Clone, Default)]
+pub struct Migrations {
+    table: Arc<RwLock<MigrationTable>>,
+}
+
+impl Migrations {
+    /// Replaces the table, returning how many migrations it has.
+    pub fn set(&self, table: MigrationTable) -> Result<usize, MigrationError> {
+        table.validate()?;
+        let count = table.migrations.len();
+        *self.table.write() = table;
+        Ok(count)
+    }
+
+    pub fn load_file(&self, path: &Path) -> Result<usize, MigrationError> {
+        self.set(MigrationTable::load_file(path)?)
+    }
+
+    pub fn table(&self) -> MigrationTable {
+        self.table.read().clone()
+    }
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// (product, from, to)
+    Backwards(ProfileProduct, Version, Version),
+    /// (product, to)
+    DivideByZero(ProfileProduct, Version),
+    /// (product, to)
+    Duplicate(ProfileProduct, Version),
+    /// (product, version the settings are at, oldest version the next migration takes)
+    NoPath(ProfileProduct, Version, Version),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl core::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MigrationError::Backwards(product, from, to) => {
+                write!(
+                    f,
+                    "{product:?} migration from {from} to {to} doesn't go forward"
+                )
+            }
+            MigrationError::DivideByZero(product, to) => {
+                write!(f, "{product:?} migration to {to} divides by zero")
+            }
+            MigrationError::Duplicate(product, to) => {
+                write!(f, "more than one {product:?} migration to {to}")
+            }
+            MigrationError::NoPath(product, at, from) => write!(
+                f,
+                "no {product:?} migration from {at}; the next one starts at {from}"
+            ),
+            MigrationError::Io(e) => write!(f, "couldn't read migration table: {e}"),
+            MigrationError::Json(e) => write!(f, "invalid migration table: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for MigrationError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_chain() {
+        let table: MigrationTable = serde_json::from_str(
+            r#"{"migrations": [
+                {"product": "canandmag", "from": "2025.0.0", "to": "2026.0.0", "transforms": [
+                    {"op": "scale", "setting": "VelocityWindow", "multiply": 10}
+                ]},
+                {"product": "canandmag", "from": "2024.0.0", "to": "2025.0.0", "transforms": [
+                    {"op": "rename", "from": "velocity_width", "to": "VelocityWindow"},
+                    {"op": "remove", "setting": "NotSaved"}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        table.validate().unwrap();
+
+        let saved = BTreeMap::from([("VelocityWidth".to_string(), 5)]);
+        let mut settings = saved.clone();
+        let applied = table
+            .migrate(
+                ProfileProduct::Canandmag,
+                version("2024.1.0"),
+                version("2026.0.1"),
+                &mut settings,
+            )
+            .unwrap();
+        assert_eq!(
+            settings,
+            BTreeMap::from([("VelocityWindow".to_string(), 50)])
+        );
+        assert_eq!(applied.len(), 2);
+        assert_eq!(applied[0].changes.len(), 1);
+
+        // stopping at the device's version, and leaving other products alone
+        let mut settings = saved.clone();
+        let applied = table
+            .migrate(
+                ProfileProduct::Canandmag,
+                version("2024.1.0"),
+                version("2025.0.0"),
+                &mut settings,
+            )
+            .unwrap();
+        assert_eq!(applied.len(), 1);
+        let mut untouched = saved.clone();
+        let applied = table
+            .migrate(
+                ProfileProduct::Canandgyro,
+                version("2024.1.0"),
+                version("2026.0.0"),
+                &mut untouched,
+            )
+            .unwrap();
+        assert!(applied.is_empty() && untouched == saved);
+
+        assert!(matches!(
+            table.migrate(
+                ProfileProduct::Canandmag,
+                version("2023.0.0"),
+                version("2026.0.0"),
+                &mut saved.clone(),
+            ),
+            Err(MigrationError::NoPath(..))
+        ));
+    }
+}
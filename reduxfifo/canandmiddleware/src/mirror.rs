@@ -0,0 +1,171 @@
+//! Mirrors bus traffic to a UDP multicast group.
+//!
+//! This lets external analysis tools tap into live traffic without speaking CANLink.
+//! Each frame is sent as its own datagram, using the same layout as CANLink RX messages:
+//!
+//! | offset | size | field                                                |
+//! |--------|------|------------------------------------------------------|
+//! | 0      | 4    | message id (u32 LE, 29-bit id + ReduxFIFO id flags)  |
+//! | 4      | 2    | bus id (u16 LE)                                      |
+//! | 6      | 2    | flags (u16 LE, ReduxFIFO message flags)              |
+//! | 8      | 8    | timestamp in microseconds (u64 LE)                   |
+//! | 16     | 0-64 | data; the length is the datagram length minus 16     |
+
+use std::{
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::Arc,
+    time::Duration,
+};
+
+use fifocore::{FIFOCore, ReduxFIFOSessionConfig};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tokio::{net::UdpSocket, task::JoinHandle};
+
+use crate::log::{log_error, log_info};
+
+/// Where and how to mirror a bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MirrorConfig {
+    pub bus_id: u16,
+    /// Multicast group and port to send to
+    pub group: SocketAddrV4,
+    /// Multicast TTL. 1 keeps traffic on the local network segment.
+    pub ttl: u32,
+}
+
+impl MirrorConfig {
+    pub fn new(bus_id: u16, group: SocketAddrV4) -> Self {
+        Self {
+            bus_id,
+            group,
+            ttl: 1,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Mirror {
+    config: MirrorConfig,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Mirror {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Set of running bus mirrors, shared between the REST server and whoever else starts them.
+#[derive(Debug, Clone, Default)]
+pub struct Mirrors {
+    mirrors: Arc<Mutex<FxHashMap<u16, Mirror>>>,
+}
+
+impl Mirrors {
+    /// Starts mirroring a bus, replacing any existing mirror on that bus.
+    pub fn start(&self, fifocore: &FIFOCore, config: MirrorConfig) -> Result<(), MirrorError> {
+        if !config.group.ip().is_multicast() {
+            return Err(MirrorError::NotMulticast(*config.group.ip()));
+        }
+        let mut session_config = ReduxFIFOSessionConfig::new(0, 0);
+        session_config.echo_tx = true;
+        let session = fifocore
+            .open_managed_session(config.bus_id, 256, session_config)
+            .map_err(MirrorError::FIFOCore)?;
+
+        let socket = std::net::UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)))
+            .map_err(MirrorError::Io)?;
+        socket
+            .set_multicast_ttl_v4(config.ttl)
+            .map_err(MirrorError::Io)?;
+        socket.set_nonblocking(true).map_err(MirrorError::Io)?;
+
+        // the socket has to be registered on the runtime fifocore runs on
+        let _guard = fifocore.runtime().enter();
+        let socket = UdpSocket::from_std(socket).map_err(MirrorError::Io)?;
+        let task = fifocore
+            .runtime()
+            .spawn(mirror_task(session, socket, config));
+        log_info!(
+            "Mirroring bus {} to udp://{}",
+            config.bus_id,
+            config.group
+        );
+        self.mirrors
+            .lock()
+            .insert(config.bus_id, Mirror { config, task });
+        Ok(())
+    }
+
+    /// Stops mirroring a bus. Returns false if it wasn't being mirrored.
+    pub fn stop(&self, bus_id: u16) -> bool {
+        self.mirrors.lock().remove(&bus_id).is_some()
+    }
+
+    /// Currently running mirrors.
+    pub fn list(&self) -> Vec<MirrorConfig> {
+        let mut configs: Vec<MirrorConfig> = self
+            .mirrors
+            .lock()
+            .values()
+            .filter(|m| !m.task.is_finished())
+            .map(|m| m.config)
+            .collect();
+        configs.sort_by_key(|c| c.bus_id);
+        configs
+    }
+}
+
+#[derive(Debug)]
+pub enum MirrorError {
+    NotMulticast(Ipv4Addr),
+    FIFOCore(fifocore::error::Error),
+    Io(std::io::Error),
+}
+
+impl core::fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MirrorError::NotMulticast(addr) => write!(f, "{addr} is not a multicast address"),
+            MirrorError::FIFOCore(e) => write!(f, "{e}"),
+            MirrorError::Io(e) => write!(f, "socket error: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for MirrorError {}
+
+async fn mirror_task(session: fifocore::Session, socket: UdpSocket, config: MirrorConfig) {
+    let mut read_buf = session.read_buffer(256);
+    let mut interval = tokio::time::interval(Duration::from_millis(5));
+    loop {
+        interval.tick().await;
+        if let Err(e) = session.read_barrier(&mut read_buf) {
+            log_error!("[mirror] Read session on bus {} failed: {e}", config.bus_id);
+            return;
+        }
+        for msg in read_buf.iter() {
+            let rx_msg = rdxcanlink_protocol::CANLinkRxMessage {
+                message_id: msg.message_id,
+                bus_id: msg.bus_id,
+                flags: msg.flags as u16,
+                timestamp: msg.timestamp,
+                data: msg.data,
+                data_size: msg.data_size as usize,
+            };
+            let mut buffer = rdxcanlink_protocol::CANLinkRxMessage::buffer();
+            // a full send buffer just means we drop frames; analyzers are best-effort anyway
+            if let Err(e) = socket
+                .try_send_to(rx_msg.serialize_into(&mut buffer), config.group.into())
+                .or_else(|e| match e.kind() {
+                    std::io::ErrorKind::WouldBlock => Ok(0),
+                    _ => Err(e),
+                })
+            {
+                log_error!("[mirror] Send to {} failed: {e}", config.group);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! The REST and WebSocket API over a local named pipe, on Windows.
+//!
+//! A pipe reaches the server without going through the network stack, so clients on the same
+//! machine keep working with the firewall closed or no network adapter up, and it can't be reached
+//! from another machine. Requests are the same HTTP as over TCP; only the transport differs.
+//!
+//! The pipe is opened to administrators, SYSTEM and interactive users, so a desktop app can talk to
+//! a server running as a service under another account.
+
+use std::{ffi::c_void, io, ptr, sync::LazyLock};
+
+use axum::Router;
+use tokio::{
+    net::windows::named_pipe::{NamedPipeServer, ServerOptions},
+    sync::watch,
+};
+
+use crate::log::{log_error, log_info, log_warn};
+
+/// Name of the pipe the server listens on.
+pub const PIPE_NAME: &str = r"\\.\pipe\reduxfifo";
+
+/// Full access for SYSTEM and administrators, read and write for interactive users.
+const PIPE_SDDL: &str = "D:P(A;;GA;;;SY)(A;;GA;;;BA)(A;;GRGW;;;IU)";
+
+#[repr(C)]
+struct SecurityAttributes {
+    length: u32,
+    security_descriptor: *mut c_void,
+    inherit_handle: i32,
+}
+
+#[link(name = "advapi32")]
+unsafe extern "system" {
+    fn ConvertStringSecurityDescriptorToSecurityDescriptorW(
+        sddl: *const u16,
+        revision: u32,
+        security_descriptor: *mut *mut c_void,
+        size: *mut u32,
+    ) -> i32;
+}
+
+/// The pipe's security descriptor, made once and kept for the life of the process.
+static SECURITY_DESCRIPTOR: LazyLock<usize> = LazyLock::new(|| {
+    let sddl: Vec<u16> = PIPE_SDDL.encode_utf16().chain([0]).collect();
+    let mut descriptor = ptr::null_mut();
+    // SDDL_REVISION_1
+    let ok = unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl.as_ptr(),
+            1,
+            &mut descriptor,
+            ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        log_warn!(
+            "couldn't make the pipe's security descriptor ({}); only this account can connect",
+            io::Error::last_os_error()
+        );
+        return 0;
+    }
+    descriptor as usize
+});
+
+fn create_pipe(first: bool) -> io::Result<NamedPipeServer> {
+    let mut options = ServerOptions::new();
+    options.first_pipe_instance(first).reject_remote_clients(true);
+    let mut attributes = SecurityAttributes {
+        length: size_of::<SecurityAttributes>() as u32,
+        security_descriptor: *SECURITY_DESCRIPTOR as *mut c_void,
+        inherit_handle: 0,
+    };
+    let attributes = match *SECURITY_DESCRIPTOR {
+        0 => ptr::null_mut(),
+        _ => &mut attributes as *mut SecurityAttributes as *mut c_void,
+    };
+    unsafe { options.create_with_security_attributes_raw(PIPE_NAME, attributes) }
+}
+
+/// Hands out connections to the pipe, keeping an instance waiting for the next client.
+struct PipeListener {
+    next: NamedPipeServer,
+}
+
+impl axum::serve::Listener for PipeListener {
+    type Io = NamedPipeServer;
+    type Addr = ();
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            if let Err(e) = self.next.connect().await {
+                log_warn!("named pipe connect failed: {e}");
+            }
+            match create_pipe(false) {
+                Ok(next) => return (std::mem::replace(&mut self.next, next), ()),
+                Err(e) => {
+                    log_error!("couldn't open another named pipe instance: {e}");
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(())
+    }
+}
+
+/// Serves `app` on [`PIPE_NAME`] until shutdown. Gives up if another server already has the pipe.
+pub(crate) async fn serve(app: Router, mut shutdown_pipe: watch::Receiver<bool>) {
+    let next = match create_pipe(true) {
+        Ok(next) => next,
+        Err(e) => {
+            log_warn!("not serving on {PIPE_NAME}: {e}");
+            return;
+        }
+    };
+    log_info!("Starting CANLink server on {PIPE_NAME}");
+    let server = axum::serve(PipeListener { next }, app).with_graceful_shutdown(async move {
+        shutdown_pipe.wait_for(|f| *f).await.ok();
+    });
+    if let Err(e) = server.await {
+        log_error!("named pipe server error: {e}");
+    }
+}
@@ -1,25 +1,43 @@
 use std::{
     collections::VecDeque,
+    convert::Infallible,
     sync::Arc,
     time::{Duration, Instant},
 };
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{HeaderValue, StatusCode},
-    response::IntoResponse,
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use frc_can_id::FRCCanId;
+use futures::Stream;
+use parking_lot::Mutex;
+use rdxota_client::{
+    ControlMessage, RdxOtaClient, RdxOtaClientIO, RdxOtaIOError,
+    firmware::{FirmwareError, FirmwareImage},
+};
+use rustc_hash::FxHashMap;
+use serial_numer::ProductId;
+use tokio::sync::watch;
+
+use crate::{
+    bus::{BusState, device::DeviceKey, device_lock::DeviceGuard},
+    confirm::Operation,
+    log::*,
+    problem::ApiError,
+    rest_server::AppState,
 };
-use rdxota_client::{ControlMessage, RdxOtaClient, RdxOtaClientIO, RdxOtaIOError};
-use tokio::{sync::watch, task::JoinHandle};
-
-use crate::{log::*, rest_server::AppState};
 use fifocore::{
-    FIFOCore, ReadBuffer, ReduxFIFOMessage, ReduxFIFOSessionConfig, Session, error::Error,
+    FIFOCore, ReadBuffer, ReduxFIFOMessage, ReduxFIFOSessionConfig, Session,
+    backends::rdxusb::DfuOtaLink, cancel::CancelToken, error::Error,
 };
 
 /// Glue between reduxfifo and rdxota-client
 pub struct ClientIO {
-    fifocore: FIFOCore,
     session: Session,
     bus: u16,
     polling_interval: Duration,
@@ -41,15 +59,15 @@ impl ClientIO {
             bus,
             64,
             ReduxFIFOSessionConfig::new(
-                (id & 0x1fff003f) | ((rdxota_protocol::OTA_MESSAGE_TO_HOST as u32) << 6),
+                (id & frc_can_id::DEVICE_FILTER) | ((rdxota_protocol::OTA_MESSAGE_TO_HOST as u32) << 6),
                 0x1fffffff,
             ),
         )?;
+        session.set_label("ota")?;
         let next_buf = session.read_buffer(64);
         let max_packet_size = fifocore.max_packet_size(bus)?;
 
         Ok(Self {
-            fifocore,
             session,
             bus,
             polling_interval: Duration::from_micros(1000),
@@ -68,7 +86,7 @@ impl ClientIO {
     ) -> Result<(), RdxOtaIOError> {
         let start = Instant::now();
         while Instant::now() - start < timeout {
-            match self.fifocore.write_single(&msg) {
+            match self.session.write_single(msg) {
                 Ok(()) => {
                     return Ok(());
                 }
@@ -94,9 +112,11 @@ impl RdxOtaClientIO for ClientIO {
         msg: ControlMessage,
         timeout: core::time::Duration,
     ) -> Result<(), RdxOtaIOError> {
-        let mut data = [0_u8; 64];
-        data[..msg.length as usize].copy_from_slice(&msg.data[..msg.length as usize]);
-        let msg = ReduxFIFOMessage::id_data(self.bus, id, data, msg.length, 0);
+        let msg = ReduxFIFOMessage::builder()
+            .bus(self.bus)
+            .id(id)
+            .data(&msg.data[..msg.length as usize])
+            .build();
         self.send_msg(&msg, timeout).await
     }
 
@@ -111,9 +131,7 @@ impl RdxOtaClientIO for ClientIO {
                 "Message length is too large for transport layer size",
             ));
         }
-        let mut data = [0_u8; 64];
-        data[..msg.len()].copy_from_slice(msg);
-        let msg = ReduxFIFOMessage::id_data(self.bus, id, data, msg.len() as u8, 0);
+        let msg = ReduxFIFOMessage::builder().bus(self.bus).id(id).data(msg).build();
 
         self.send_msg(&msg, timeout).await
     }
@@ -175,13 +193,7 @@ impl RdxOtaClientIO for ClientIO {
     }
 
     async fn update_progress(&mut self, written: usize, pct_progress: f32, speed: f32) {
-        self.status.send_replace(OtaFlashStatus {
-            state: OtaFlashState::Running,
-            written,
-            pct_progress: pct_progress as f64,
-            speed: speed as f64,
-            error_text: None,
-        });
+        send_progress(&self.status, written, pct_progress, speed);
     }
 
     fn transport_size(&self) -> usize {
@@ -189,12 +201,148 @@ impl RdxOtaClientIO for ClientIO {
     }
 }
 
+/// Glue between a bootloader in DFU mode over USB and rdxota-client
+pub struct UsbClientIO {
+    link: DfuOtaLink,
+    polling_interval: Duration,
+    status: Arc<watch::Sender<OtaFlashStatus>>,
+    /// Whether replies still queued on the bootloader should be dropped before the next send
+    drain: bool,
+    start_ts: Instant,
+}
+
+impl UsbClientIO {
+    pub async fn open(
+        serial: &str,
+        status: Arc<watch::Sender<OtaFlashStatus>>,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            link: DfuOtaLink::open(serial).await?,
+            polling_interval: Duration::from_micros(1000),
+            status,
+            drain: false,
+            start_ts: Instant::now(),
+        })
+    }
+
+    async fn send_packet(
+        &mut self,
+        id: u32,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<(), RdxOtaIOError> {
+        if self.drain {
+            // replies are queued on the bootloader rather than here, so reset() can only flag them
+            while self
+                .link
+                .poll(timeout)
+                .await
+                .map_err(|e| RdxOtaIOError::Other(e.message()))?
+                .is_some()
+            {}
+            self.drain = false;
+        }
+        self.link
+            .send(id, data, timeout)
+            .await
+            .map_err(|e| RdxOtaIOError::Other(e.message()))
+    }
+}
+
+impl RdxOtaClientIO for UsbClientIO {
+    async fn send(
+        &mut self,
+        id: u32,
+        msg: ControlMessage,
+        timeout: core::time::Duration,
+    ) -> Result<(), RdxOtaIOError> {
+        self.send_packet(id, &msg.data[..msg.length as usize], timeout)
+            .await
+    }
+
+    async fn send_data(
+        &mut self,
+        id: u32,
+        msg: &[u8],
+        timeout: core::time::Duration,
+    ) -> Result<(), RdxOtaIOError> {
+        if msg.len() > self.transport_size() {
+            return Err(RdxOtaIOError::Other(
+                "Message length is too large for transport layer size",
+            ));
+        }
+        self.send_packet(id, msg, timeout).await
+    }
+
+    async fn recv(
+        &mut self,
+        timeout: core::time::Duration,
+    ) -> Result<ControlMessage, RdxOtaIOError> {
+        let start = Instant::now();
+        loop {
+            let packet = self
+                .link
+                .poll(timeout)
+                .await
+                .map_err(|e| RdxOtaIOError::Other(e.message()))?;
+            if let Some(packet) = packet {
+                return Ok(ControlMessage::new(
+                    &packet.data[..packet.data_size as usize],
+                ));
+            }
+            if Instant::now() - start >= timeout {
+                return Err(RdxOtaIOError::RecvTimeout);
+            }
+            tokio::time::sleep(self.polling_interval).await;
+        }
+    }
+
+    async fn sleep(&mut self, timeout: core::time::Duration) -> Result<(), RdxOtaIOError> {
+        tokio::time::sleep(timeout).await;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.drain = true;
+    }
+
+    fn now_secs(&self) -> f32 {
+        (Instant::now() - self.start_ts).as_secs_f32()
+    }
+
+    async fn update_progress(&mut self, written: usize, pct_progress: f32, speed: f32) {
+        send_progress(&self.status, written, pct_progress, speed);
+    }
+
+    fn transport_size(&self) -> usize {
+        64
+    }
+}
+
+fn send_progress(
+    status: &watch::Sender<OtaFlashStatus>,
+    written: usize,
+    pct_progress: f32,
+    speed: f32,
+) {
+    status.send_replace(OtaFlashStatus {
+        state: OtaFlashState::Running,
+        written,
+        pct_progress: pct_progress as f64,
+        speed: speed as f64,
+        error_text: None,
+    });
+}
+
 async fn run_ota(
     fifocore: FIFOCore,
+    bus_sessions: Arc<Mutex<FxHashMap<u16, BusState>>>,
     bus: u16,
     id: u32,
     payload: Vec<u8>,
     status: Arc<watch::Sender<OtaFlashStatus>>,
+    // held until the flash is done or cancelled
+    _device: DeviceGuard,
 ) {
     let mut scratch_buf = [0_u8; 64];
 
@@ -212,6 +360,10 @@ async fn run_ota(
     let mut runner = RdxOtaClient::new(&payload, &mut scratch_buf, id, io);
     match runner.run().await {
         Ok(()) => {
+            // the client boots the new firmware as its last step
+            if let Some(state) = bus_sessions.lock().get_mut(&bus) {
+                state.expect_reboot(FRCCanId(id).into());
+            }
             let new_state = status.borrow().swap_state(OtaFlashState::Finished, None);
             status.send_replace(new_state);
         }
@@ -223,6 +375,41 @@ async fn run_ota(
     }
 }
 
+/// Flashes a device sitting in DFU mode over USB, with no CAN bus involved.
+async fn run_usb_ota(serial: String, payload: Vec<u8>, status: Arc<watch::Sender<OtaFlashStatus>>) {
+    let mut scratch_buf = [0_u8; 64];
+
+    let io = match UsbClientIO::open(&serial, status.clone()).await {
+        Ok(io) => io,
+        Err(e) => {
+            log_error!("[RdxOTA] Failed to open {serial} in DFU mode: {e}");
+            let new_state = status
+                .borrow()
+                .swap_state(OtaFlashState::Fail, Some(format!("{e}")));
+            status.send_replace(new_state);
+            return;
+        }
+    };
+    let new_state = status.borrow().swap_state(OtaFlashState::Running, None);
+    status.send_replace(new_state);
+    // the bootloader answers to any id when addressed directly over USB
+    let id = frc_can_id::build_frc_can_id(0, frc_can_id::REDUX_VENDOR_ID, 0, 0);
+    let mut runner = RdxOtaClient::new(&payload, &mut scratch_buf, id, io);
+    match runner.run().await {
+        Ok(()) => {
+            let new_state = status.borrow().swap_state(OtaFlashState::Finished, None);
+            status.send_replace(new_state);
+        }
+        Err(e) => {
+            log_error!("USB OTA of {serial} failed: {e}");
+            let new_state = status
+                .borrow()
+                .swap_state(OtaFlashState::Fail, Some(format!("{e}")));
+            status.send_replace(new_state);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) struct OtaAddress {
     bus_id: u16,
@@ -237,42 +424,77 @@ impl OtaAddress {
         (self.device_id >> 16 & 0xff) == 0xe
     }
 
-    pub fn parse_path(bus_str: &str, id_str: &str) -> Result<Self, axum::response::Response> {
-        let Ok(bus) = u16::from_str_radix(bus_str, 16) else {
-            return Err((StatusCode::BAD_REQUEST, "Invalid bus parameter").into_response());
+    /// Parses `/ota/{bus}/{id}`. The bus is an alias or bus params of an open bus, or else a hex id.
+    pub fn parse_path(fifocore: &FIFOCore, bus_str: &str, id_str: &str) -> Result<Self, ApiError> {
+        let Some(bus) = fifocore
+            .bus_matching_params(bus_str)
+            .or_else(|| u16::from_str_radix(bus_str, 16).ok())
+        else {
+            return Err(ApiError::invalid_param("bus", bus_str));
         };
         let Ok(id) = u32::from_str_radix(id_str, 16) else {
-            return Err((StatusCode::BAD_REQUEST, "Invalid ID parameter").into_response());
+            return Err(ApiError::invalid_param("id", id_str));
         };
         Ok(Self::new(bus, id))
     }
 }
 
 pub(crate) struct OtaTask {
-    pub(crate) task: JoinHandle<()>,
+    /// Stops the flash at its next await, dropping its session and device lock
+    pub(crate) cancel: CancelToken,
     pub(crate) status_send: Arc<watch::Sender<OtaFlashStatus>>,
     pub(crate) status_recv: watch::Receiver<OtaFlashStatus>,
 }
 
 impl OtaTask {
-    pub fn new(fifocore: FIFOCore, address: OtaAddress, payload: Vec<u8>) -> Self {
+    pub fn new(
+        fifocore: FIFOCore,
+        bus_sessions: Arc<Mutex<FxHashMap<u16, BusState>>>,
+        address: OtaAddress,
+        payload: Vec<u8>,
+        device: DeviceGuard,
+    ) -> Self {
         let (status_sender, status_recv) = watch::channel(OtaFlashStatus::default());
         let status_send = Arc::new(status_sender);
+        let cancel = CancelToken::new();
+        let token = cancel.clone();
+        let ota = run_ota(
+            fifocore.clone(),
+            bus_sessions,
+            address.bus_id,
+            address.device_id,
+            payload,
+            status_send.clone(),
+            device,
+        );
+        fifocore.runtime().spawn(async move {
+            let _ = token.run(ota).await;
+        });
         Self {
-            task: fifocore.runtime().spawn(run_ota(
-                fifocore,
-                address.bus_id,
-                address.device_id,
-                payload,
-                status_send.clone(),
-            )),
+            cancel,
             status_send,
             status_recv: status_recv,
         }
     }
 
+    pub fn new_usb(fifocore: &FIFOCore, serial: String, payload: Vec<u8>) -> Self {
+        let (status_sender, status_recv) = watch::channel(OtaFlashStatus::default());
+        let status_send = Arc::new(status_sender);
+        let cancel = CancelToken::new();
+        let token = cancel.clone();
+        let ota = run_usb_ota(serial, payload, status_send.clone());
+        fifocore.runtime().spawn(async move {
+            let _ = token.run(ota).await;
+        });
+        Self {
+            cancel,
+            status_send,
+            status_recv,
+        }
+    }
+
     pub fn abort(&self) {
-        self.task.abort();
+        self.cancel.cancel();
         self.status_send.send_replace(OtaFlashStatus {
             state: OtaFlashState::Abort,
             written: 0,
@@ -285,7 +507,7 @@ impl OtaTask {
 
 impl Drop for OtaTask {
     fn drop(&mut self) {
-        self.task.abort();
+        self.cancel.cancel();
         self.status_send.send_replace(OtaFlashStatus {
             state: OtaFlashState::Abort,
             written: 0,
@@ -307,6 +529,16 @@ pub enum OtaFlashState {
     Finished = 4,
 }
 
+impl OtaFlashState {
+    /// Whether the flash has stopped, one way or another.
+    pub fn is_done(self) -> bool {
+        matches!(
+            self,
+            OtaFlashState::Fail | OtaFlashState::Abort | OtaFlashState::Finished
+        )
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default, serde::Serialize, serde::Deserialize)]
 pub struct OtaFlashStatus {
     /// flashing state
@@ -333,25 +565,115 @@ impl OtaFlashStatus {
     }
 }
 
+/// Least time between progress events; the client reports progress on every packet.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Streams a flash's status as server-sent `progress` events: the current status right away, then
+/// whenever it changes, at most every [`PROGRESS_INTERVAL`]. Ends after the flash stops.
+fn progress_events(
+    recv: watch::Receiver<OtaFlashStatus>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = futures::stream::unfold((Some(recv), true), |(recv, first)| async move {
+        let mut recv = recv?;
+        if !first {
+            tokio::time::sleep(PROGRESS_INTERVAL).await;
+            // the job was dropped without a last word
+            recv.changed().await.ok()?;
+        }
+        let status = recv.borrow_and_update().clone();
+        let next = if status.state.is_done() {
+            None
+        } else {
+            Some(recv)
+        };
+        let event = Event::default()
+            .event("progress")
+            .json_data(&status)
+            .unwrap_or_default();
+        Some((Ok(event), (next, false)))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Turns away firmware the device wouldn't take before anything is sent: a file with a broken
+/// header, or one for another product than `product`, if that's known. Files without an RdxOTA v2
+/// header are left for the device to check, as older bootloaders take raw images.
+fn check_firmware(file: &[u8], product: Option<ProductId>, target: &str) -> Result<(), ApiError> {
+    let image = match FirmwareImage::parse(file) {
+        Ok(image) => image,
+        Err(FirmwareError::BadMagic | FirmwareError::TooShort(_)) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    match product {
+        Some(product) => image.validate_for(product)?,
+        None if !image.signed() => return Err(FirmwareError::Unsigned.into()),
+        None => {}
+    }
+    log_info!(
+        "Flashing {:?} firmware {} to {target}",
+        image.product(),
+        image.version()
+    );
+    Ok(())
+}
+
 /// ------- Web server endpoints
 
+/// `/ota/{bus}/{id}/start?confirm=<token>`, with the firmware as the body
+///
+/// Needs confirming, by default; see [`crate::confirm`].
 pub(crate) async fn ota_start_handler(
     State(state): State<AppState>,
     Path((bus_str, id_str)): Path<(String, String)>,
+    Query(params): Query<FxHashMap<String, String>>,
     body: axum::body::Bytes,
 ) -> axum::response::Response {
-    let addr = match OtaAddress::parse_path(&bus_str, &id_str) {
+    let addr = match OtaAddress::parse_path(&state.fifocore, &bus_str, &id_str) {
         Ok(a) => a,
         Err(e) => {
-            return e;
+            return e.into_response();
         }
     };
 
     if !addr.valid() {
-        return (StatusCode::BAD_REQUEST, "-_-").into_response();
+        return ApiError::invalid_param("id", id_str)
+            .with_hint("OTA is only supported on Redux devices.")
+            .into_response();
+    }
+    let product = state
+        .bus_sessions
+        .lock()
+        .get(&addr.bus_id)
+        .and_then(|bus| bus.devices.get(&DeviceKey::from(FRCCanId(addr.device_id))))
+        .and_then(|dev| dev.serial_numer())
+        .map(|serial| serial.product_id());
+    let target = format!("{:08x} on bus {}", addr.device_id, addr.bus_id);
+    if let Err(e) = check_firmware(&body, product, &target) {
+        return e.into_response();
+    }
+    let confirmed = crate::rest_server::confirm_device(
+        &state,
+        Operation::FirmwareFlash,
+        addr.bus_id,
+        addr.device_id,
+        &params,
+    )
+    .await;
+    if let Err(e) = confirmed {
+        return e.into_response();
     }
-    let mut ota_clients = state.ota_clients.lock();
-    ota_clients.insert(addr, OtaTask::new(state.fifocore, addr, body.to_vec()));
+    // a job already running on the device has to let go of it first
+    drop(state.ota_clients.lock().remove(&addr));
+    let device = match state
+        .device_locks
+        .acquire(addr.bus_id, FRCCanId(addr.device_id).into(), "ota")
+        .await
+    {
+        Ok(device) => device,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let task = OtaTask::new(state.fifocore, state.bus_sessions, addr, body.to_vec(), device);
+    state.ota_clients.lock().insert(addr, task);
     (StatusCode::OK, ":3c").into_response()
 }
 
@@ -359,10 +681,10 @@ pub(crate) async fn ota_status_handler(
     State(state): State<AppState>,
     Path((bus_str, id_str)): Path<(String, String)>,
 ) -> axum::response::Response {
-    let addr = match OtaAddress::parse_path(&bus_str, &id_str) {
+    let addr = match OtaAddress::parse_path(&state.fifocore, &bus_str, &id_str) {
         Ok(a) => a,
         Err(e) => {
-            return e;
+            return e.into_response();
         }
     };
 
@@ -377,14 +699,36 @@ pub(crate) async fn ota_status_handler(
     response
 }
 
+/// `/ota/{bus}/{id}/progress`: the flash's status as a stream of server-sent events
+pub(crate) async fn ota_progress_handler(
+    State(state): State<AppState>,
+    Path((bus_str, id_str)): Path<(String, String)>,
+) -> axum::response::Response {
+    let addr = match OtaAddress::parse_path(&state.fifocore, &bus_str, &id_str) {
+        Ok(a) => a,
+        Err(e) => {
+            return e.into_response();
+        }
+    };
+    let recv = state
+        .ota_clients
+        .lock()
+        .get(&addr)
+        .map(|inst| inst.status_recv.clone());
+    match recv {
+        Some(recv) => progress_events(recv).into_response(),
+        None => ApiError::ota_not_found(&format!("{id_str} on bus {bus_str}")).into_response(),
+    }
+}
+
 pub(crate) async fn ota_abort_handler(
     State(state): State<AppState>,
     Path((bus_str, id_str)): Path<(String, String)>,
 ) -> axum::response::Response {
-    let addr = match OtaAddress::parse_path(&bus_str, &id_str) {
+    let addr = match OtaAddress::parse_path(&state.fifocore, &bus_str, &id_str) {
         Ok(a) => a,
         Err(e) => {
-            return e;
+            return e.into_response();
         }
     };
     match state.ota_clients.lock().remove(&addr) {
@@ -396,3 +740,70 @@ pub(crate) async fn ota_abort_handler(
     }
     .into_response()
 }
+
+/// `/ota/usb/{serial}/start?confirm=<token>`: flash a device in DFU mode over USB
+pub(crate) async fn usb_ota_start_handler(
+    State(state): State<AppState>,
+    Path(serial): Path<String>,
+    Query(params): Query<FxHashMap<String, String>>,
+    body: axum::body::Bytes,
+) -> axum::response::Response {
+    let token = params.get("confirm").map(String::as_str);
+    let target = format!("USB device {serial}");
+    if let Err(e) = check_firmware(&body, None, &target) {
+        return e.into_response();
+    }
+    let confirmed = state
+        .confirmations
+        .check(&state.fifocore, Operation::FirmwareFlash, None, &target, token)
+        .await;
+    if let Err(e) = confirmed {
+        return ApiError::from(e).into_response();
+    }
+    let task = OtaTask::new_usb(&state.fifocore, serial.clone(), body.to_vec());
+    state.usb_ota_clients.lock().insert(serial, task);
+    (StatusCode::OK, ":3c").into_response()
+}
+
+pub(crate) async fn usb_ota_status_handler(
+    State(state): State<AppState>,
+    Path(serial): Path<String>,
+) -> axum::response::Response {
+    let json = state
+        .usb_ota_clients
+        .lock()
+        .get(&serial)
+        .map(|inst| inst.status_recv.borrow().clone())
+        .unwrap_or_default();
+    (StatusCode::OK, axum::Json(json)).into_response()
+}
+
+/// `/ota/usb/{serial}/progress`: the USB flash's status as a stream of server-sent events
+pub(crate) async fn usb_ota_progress_handler(
+    State(state): State<AppState>,
+    Path(serial): Path<String>,
+) -> axum::response::Response {
+    let recv = state
+        .usb_ota_clients
+        .lock()
+        .get(&serial)
+        .map(|inst| inst.status_recv.clone());
+    match recv {
+        Some(recv) => progress_events(recv).into_response(),
+        None => ApiError::ota_not_found(&format!("USB device {serial}")).into_response(),
+    }
+}
+
+pub(crate) async fn usb_ota_abort_handler(
+    State(state): State<AppState>,
+    Path(serial): Path<String>,
+) -> axum::response::Response {
+    match state.usb_ota_clients.lock().remove(&serial) {
+        Some(inst) => {
+            inst.abort();
+            (StatusCode::OK, ">w<")
+        }
+        None => (StatusCode::OK, "-w-"),
+    }
+    .into_response()
+}
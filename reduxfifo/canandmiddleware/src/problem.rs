@@ -0,0 +1,645 @@
+//! RFC 7807-style error responses for the REST server.
+//!
+//! Every error response is `application/problem+json` with a stable `code` that clients can
+//! switch on. Errors that come out of fifocore use the [`fifocore::error::Error`] variant name as
+//! their code and also carry the numeric `error_id`/`reason` pair older clients expect.
+
+use axum::{
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use fifocore::{bus_uri::BusUriError, error::Error};
+use rdxota_client::firmware::FirmwareError;
+use serde::Serialize;
+
+use crate::bench::BenchError;
+use crate::bus::device_lock::LockError;
+use crate::bus::frame_period::FramePeriodError;
+use crate::bus::setting::SettingError;
+use crate::confirm::{Challenge, ConfirmError};
+use crate::device_snapshot::SnapshotError;
+use crate::diagnostics::DiagnosticsError;
+use crate::firmware_index::FirmwareIndexError;
+use crate::fleet::RouteError;
+use crate::migration::MigrationError;
+use crate::mirror::MirrorError;
+use crate::profile::ProfileError;
+use crate::settings_file::SettingsFileError;
+#[cfg(feature = "simulation")]
+use crate::sim::SimError;
+use crate::telemetry::TelemetryError;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProblemDetails {
+    /// URI identifying the problem type
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Short, human-readable summary of the problem type
+    pub title: &'static str,
+    /// HTTP status code
+    pub status: u16,
+    /// Explanation specific to this occurrence
+    pub detail: String,
+    /// Stable machine-readable error code
+    pub code: &'static str,
+    /// fifocore error number, if this came from fifocore
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_id: Option<i32>,
+    /// fifocore error message, if this came from fifocore
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+    /// Suggestion for what the user can do about it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+    /// How to confirm an operation that was held back for confirmation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirmation: Option<Challenge>,
+}
+
+/// Error returned from REST handlers.
+#[derive(Debug, Clone)]
+pub struct ApiError(pub(crate) Box<ProblemDetails>);
+
+impl ApiError {
+    fn new(status: StatusCode, code: &'static str, title: &'static str, detail: String) -> Self {
+        Self(Box::new(ProblemDetails {
+            problem_type: format!("urn:reduxfifo:error:{code}"),
+            title,
+            status: status.as_u16(),
+            detail,
+            code,
+            error_id: None,
+            reason: None,
+            hint: None,
+            confirmation: None,
+        }))
+    }
+
+    pub fn with_hint(mut self, hint: &'static str) -> Self {
+        self.0.hint = Some(hint);
+        self
+    }
+
+    /// A required query or path parameter was not supplied.
+    pub fn missing_param(key: &str) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "MissingParameter",
+            "Missing parameter",
+            format!("Missing parameter `{key}`"),
+        )
+    }
+
+    /// A query or path parameter could not be parsed.
+    pub fn invalid_param(key: &str, value: impl core::fmt::Debug) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidParameter",
+            "Invalid parameter",
+            format!("Invalid value {value:?} for parameter `{key}`"),
+        )
+    }
+
+    /// The REST device session for this bus has not been opened.
+    pub fn bus_session_not_open(bus_id: u16) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "BusSessionNotOpen",
+            "Bus session not open",
+            format!("No device session is open on bus {bus_id}"),
+        )
+        .with_hint("Open one with /sessions/open/{bus} first.")
+    }
+
+    /// No device with this CAN id has been seen on the bus.
+    pub fn device_not_found(bus_id: u16, device: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "DeviceNotFound",
+            "Device not found",
+            format!("No device {device} has been seen on bus {bus_id}"),
+        )
+        .with_hint("Enumerate the bus with /sessions/{bus}/enumerate and try again.")
+    }
+
+    /// No bulk settings fetch with this id, or it finished long enough ago to have been dropped.
+    pub fn fetch_job_not_found(bus_id: u16, job: u32) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "FetchJobNotFound",
+            "Fetch job not found",
+            format!("No settings fetch job {job} on bus {bus_id}"),
+        )
+    }
+
+    /// No calibration has been started on this device since the server started.
+    pub fn calibration_not_found(bus_id: u16, device: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "CalibrationNotFound",
+            "Calibration not found",
+            format!("No calibration of {device} on bus {bus_id} has been started"),
+        )
+    }
+
+    pub fn ota_not_found(target: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "OtaNotFound",
+            "Firmware update not found",
+            format!("No firmware update of {target} is running or has finished"),
+        )
+        .with_hint("Start one by POSTing the firmware to its start route.")
+    }
+
+    /// The parts of a composite setting read back from the device don't form a valid value.
+    pub fn composite_invalid(name: &str, err: canandmessage::generic::CompositeError) -> Self {
+        Self::new(
+            StatusCode::CONFLICT,
+            "CompositeSettingInvalid",
+            "Composite setting invalid",
+            format!("The parts of setting `{name}` don't form a valid value: {err:?}"),
+        )
+        .with_hint("The parts may come from different writes; write the setting again.")
+    }
+
+    /// A fifocore error, with extra context about what we were doing.
+    pub fn fifocore(err: Error, context: impl core::fmt::Display) -> Self {
+        let mut this = Self::from(err);
+        this.0.detail = format!("{context}: {}", err.message());
+        this
+    }
+
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.0.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// HTTP status and an actionable hint for each fifocore error.
+fn fifocore_status(err: Error) -> (StatusCode, Option<&'static str>) {
+    match err {
+        Error::Unknown | Error::NullArgument | Error::JavaInvalidByteBuffer => {
+            (StatusCode::INTERNAL_SERVER_ERROR, None)
+        }
+        Error::NotInitialized => (StatusCode::SERVICE_UNAVAILABLE, None),
+        Error::NotAcknowledged => (StatusCode::BAD_REQUEST, None),
+        Error::InvalidBus => (
+            StatusCode::NOT_FOUND,
+            Some("Check the bus id or params string; /buses lists the open buses."),
+        ),
+        Error::BusAlreadyOpened => (StatusCode::CONFLICT, None),
+        Error::MaxBusesOpened => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some("Close buses that are no longer in use."),
+        ),
+        Error::BusNotSupported => (
+            StatusCode::NOT_IMPLEMENTED,
+            Some("This bus type is not available on this platform."),
+        ),
+        Error::BusClosed => (StatusCode::CONFLICT, Some("Reopen the bus.")),
+        Error::FailedToOpenBus => (
+            StatusCode::BAD_GATEWAY,
+            Some("Check that the adapter is plugged in and the params string is correct."),
+        ),
+        Error::BusReadFail | Error::BusWriteFail => (
+            StatusCode::BAD_GATEWAY,
+            Some("Check the adapter connection and bus wiring."),
+        ),
+        Error::BusBufferFull => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some("The bus is saturated; retry later."),
+        ),
+        Error::BusDeviceBusy => (
+            StatusCode::CONFLICT,
+            Some("The bus serial port or USB device is busy; close other tools using it."),
+        ),
+        Error::TxPending => (StatusCode::GATEWAY_TIMEOUT, None),
+        Error::TxUnconfirmed => (
+            StatusCode::GATEWAY_TIMEOUT,
+            Some("Open the bus with ;confirm_tx on a backend that can confirm writes."),
+        ),
+        Error::RoboRioPresent => (
+            StatusCode::CONFLICT,
+            Some("The roboRIO enables the bus itself; a stand-in heartbeat would fight it."),
+        ),
+        Error::InvalidSessionID => (StatusCode::NOT_FOUND, None),
+        Error::SessionAlreadyOpened => (StatusCode::CONFLICT, None),
+        Error::MaxSessionsOpened => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some("Close sessions that are no longer in use."),
+        ),
+        Error::SessionClosed => (StatusCode::CONFLICT, None),
+        Error::MessageReceiveTimeout => (
+            StatusCode::GATEWAY_TIMEOUT,
+            Some("The device did not respond; check that it is powered and on the bus."),
+        ),
+        Error::MemoryLimitReached => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some("Session buffers are at the configured memory limit; close unused sessions."),
+        ),
+        Error::InvalidLabel => (
+            StatusCode::BAD_REQUEST,
+            Some("Use a label of 1 to 32 printable ASCII characters, without spaces."),
+        ),
+        Error::Cancelled => (StatusCode::CONFLICT, None),
+        Error::HalCanOpenSessionFail => (StatusCode::BAD_GATEWAY, None),
+        Error::UsbClosed => (
+            StatusCode::BAD_GATEWAY,
+            Some("The USB device disconnected; reconnect it and reopen the bus."),
+        ),
+        Error::UsbDeviceNotFound => (
+            StatusCode::NOT_FOUND,
+            Some("Check that the device is plugged in over USB and the serial number is right."),
+        ),
+        Error::UsbEnumerateFail => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Some("Check that this program is allowed to access USB devices."),
+        ),
+        Error::DataTooLong => (StatusCode::BAD_REQUEST, None),
+    }
+}
+
+impl From<BusUriError> for ApiError {
+    fn from(err: BusUriError) -> Self {
+        let detail = err.to_string();
+        match err {
+            // these aren't mistakes in the params, so they get the same response as from opening
+            BusUriError::PathNotFound(_) | BusUriError::NotSupported(_) => {
+                let mut this = Self::from(Error::from(err));
+                this.0.detail = detail;
+                this
+            }
+            _ => Self::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidBusParams",
+                "Invalid bus params",
+                detail,
+            ),
+        }
+    }
+}
+
+impl From<MirrorError> for ApiError {
+    fn from(err: MirrorError) -> Self {
+        match err {
+            MirrorError::FIFOCore(e) => Self::fifocore(e, "Couldn't open mirror session"),
+            MirrorError::NotMulticast(addr) => Self::invalid_param("addr", addr)
+                .with_hint("Use an address in 224.0.0.0/4, e.g. 239.0.0.1:7245."),
+            MirrorError::Io(e) => Self::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "SocketError",
+                "Socket error",
+                e.to_string(),
+            ),
+        }
+    }
+}
+
+impl From<RouteError> for ApiError {
+    fn from(err: RouteError) -> Self {
+        let detail = err.to_string();
+        match err {
+            RouteError::FIFOCore(e) => Self::fifocore(e, "Couldn't reach the device on any bus"),
+            RouteError::UnknownSerial(_) => Self::new(
+                StatusCode::NOT_FOUND,
+                "UnknownSerial",
+                "No device with that serial numer",
+                detail,
+            )
+            .with_hint("Open a session on the device's bus and enumerate it first."),
+        }
+    }
+}
+
+impl From<LockError> for ApiError {
+    fn from(err: LockError) -> Self {
+        Self::new(
+            StatusCode::CONFLICT,
+            "DeviceLocked",
+            "Device locked",
+            err.to_string(),
+        )
+        .with_hint("Another client is operating on this device; retry once it's done.")
+    }
+}
+
+impl From<ConfirmError> for ApiError {
+    fn from(err: ConfirmError) -> Self {
+        let detail = err.to_string();
+        match err {
+            ConfirmError::Required { challenge, .. } => {
+                let mut this = Self::new(
+                    StatusCode::PRECONDITION_REQUIRED,
+                    "ConfirmationRequired",
+                    "Confirmation required",
+                    detail,
+                )
+                .with_hint("Check with the operator, then repeat the request with its token.");
+                this.0.confirmation = Some(challenge);
+                this
+            }
+            ConfirmError::PolicyLocked => Self::new(
+                StatusCode::FORBIDDEN,
+                "ConfirmPolicyLocked",
+                "Confirmation policy locked",
+                detail,
+            )
+            .with_hint("Pass the operator token as ?confirm=<token> to change the policy."),
+        }
+    }
+}
+
+impl From<BenchError> for ApiError {
+    fn from(err: BenchError) -> Self {
+        let detail = err.to_string();
+        match err {
+            BenchError::NotEnabled | BenchError::HeartbeatDisabled => Self::new(
+                StatusCode::CONFLICT,
+                "BenchModeOff",
+                "Bench mode off",
+                detail,
+            )
+            .with_hint("Start the server with --bench to use the bench heartbeat."),
+            BenchError::RoboRioPresent(_) => Self::new(
+                StatusCode::CONFLICT,
+                "RoboRioPresent",
+                "roboRIO on the bus",
+                detail,
+            )
+            .with_hint("Enable the robot from the driver station instead."),
+            BenchError::PolicyWeakened => Self::new(
+                StatusCode::FORBIDDEN,
+                "BenchPolicyLocked",
+                "Confirmation policy locked by bench mode",
+                detail,
+            ),
+            BenchError::Bus(bus_id, e) => Self::fifocore(e, format!("Bus {bus_id}")),
+        }
+    }
+}
+
+impl From<DiagnosticsError> for ApiError {
+    fn from(err: DiagnosticsError) -> Self {
+        let detail = err.to_string();
+        match err {
+            DiagnosticsError::NotBuilt => Self::new(
+                StatusCode::NOT_IMPLEMENTED,
+                "DiagnosticsNotBuilt",
+                "Diagnostics not built",
+                detail,
+            )
+            .with_hint("Build with the tokio-console feature and --cfg tokio_unstable."),
+            DiagnosticsError::NotInstalled => Self::new(
+                StatusCode::CONFLICT,
+                "DiagnosticsNotInstalled",
+                "Diagnostics not installed",
+                detail,
+            ),
+            DiagnosticsError::Locked => Self::new(
+                StatusCode::FORBIDDEN,
+                "DiagnosticsLocked",
+                "Diagnostics locked",
+                detail,
+            )
+            .with_hint("Pass the diagnostics token as ?token=<token>."),
+            DiagnosticsError::RemoteNeedsToken(_) => Self::new(
+                StatusCode::FORBIDDEN,
+                "DiagnosticsLocked",
+                "Diagnostics locked",
+                detail,
+            )
+            .with_hint(
+                "Bind tokio-console to loopback and tunnel to it, or start the server with \
+                 --diagnostics-token.",
+            ),
+            DiagnosticsError::Bind(..) => Self::new(
+                StatusCode::CONFLICT,
+                "AddressUnavailable",
+                "Address unavailable",
+                detail,
+            ),
+        }
+    }
+}
+
+impl From<FramePeriodError> for ApiError {
+    fn from(err: FramePeriodError) -> Self {
+        let detail = err.to_string();
+        match err {
+            FramePeriodError::FIFOCore(e) => Self::fifocore(e, "Couldn't reach the device"),
+            FramePeriodError::UnknownProduct(_) => Self::new(
+                StatusCode::NOT_FOUND,
+                "UnknownProduct",
+                "No frame periods for this device",
+                detail,
+            ),
+            FramePeriodError::NotPeriodic(index) => Self::invalid_param("message_index", index)
+                .with_hint("List the device's frame_periods to see which messages are periodic."),
+            FramePeriodError::OutOfRange { .. } => Self::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidParameter",
+                "Invalid parameter",
+                detail,
+            ),
+        }
+    }
+}
+
+impl From<ProfileError> for ApiError {
+    fn from(err: ProfileError) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidProfile",
+            "Invalid settings profile",
+            err.to_string(),
+        )
+    }
+}
+
+impl From<MigrationError> for ApiError {
+    fn from(err: MigrationError) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidMigrations",
+            "Invalid settings migration table",
+            err.to_string(),
+        )
+    }
+}
+
+impl From<FirmwareIndexError> for ApiError {
+    fn from(err: FirmwareIndexError) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidFirmwareIndex",
+            "Invalid firmware index",
+            err.to_string(),
+        )
+    }
+}
+
+impl From<FirmwareError> for ApiError {
+    fn from(err: FirmwareError) -> Self {
+        match err {
+            FirmwareError::ProductMismatch { .. } => Self::new(
+                StatusCode::CONFLICT,
+                "FirmwareProductMismatch",
+                "Firmware is for another product",
+                err.to_string(),
+            )
+            .with_hint("Check that the firmware file is for this device."),
+            _ => Self::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidFirmware",
+                "Invalid firmware file",
+                err.to_string(),
+            ),
+        }
+    }
+}
+
+impl From<TelemetryError> for ApiError {
+    fn from(err: TelemetryError) -> Self {
+        let detail = err.to_string();
+        match err {
+            TelemetryError::UnsupportedDevice(_) => Self::new(
+                StatusCode::NOT_IMPLEMENTED,
+                "TelemetryUnsupported",
+                "No telemetry for this device",
+                detail,
+            ),
+            TelemetryError::NoSignals | TelemetryError::UnknownSignal(_) => Self::new(
+                StatusCode::BAD_REQUEST,
+                "UnknownSignal",
+                "Unknown signal",
+                detail,
+            )
+            .with_hint("Signals are named like VelocityOutput_velocity, as in Alchemist."),
+        }
+    }
+}
+
+impl From<SettingsFileError> for ApiError {
+    fn from(err: SettingsFileError) -> Self {
+        let status = match err {
+            SettingsFileError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        Self::new(
+            status,
+            "InvalidSettingsFile",
+            "Invalid settings file",
+            err.to_string(),
+        )
+    }
+}
+
+impl From<SnapshotError> for ApiError {
+    fn from(err: SnapshotError) -> Self {
+        let detail = err.to_string();
+        match err {
+            SnapshotError::UnknownProduct(_) => Self::new(
+                StatusCode::NOT_FOUND,
+                "UnknownProduct",
+                "No settings known for this device",
+                detail,
+            ),
+            SnapshotError::WrongProduct { .. } => Self::new(
+                StatusCode::CONFLICT,
+                "WrongProduct",
+                "Snapshot is of a different product",
+                detail,
+            ),
+            SnapshotError::Read(_, SettingError::Fifo(e)) => {
+                Self::fifocore(e, "Couldn't reach the device")
+            }
+            SnapshotError::Read(_, SettingError::NoSession) => Self::new(
+                StatusCode::NOT_FOUND,
+                "BusSessionNotOpen",
+                "Bus session not open",
+                detail,
+            )
+            .with_hint("Open one with /sessions/open/{bus} first."),
+            SnapshotError::Read(..) => Self::new(
+                StatusCode::GATEWAY_TIMEOUT,
+                "DeviceNotResponding",
+                "Device not responding",
+                detail,
+            )
+            .with_hint("Check the device is powered and on the bus, or raise ?timeout=."),
+            _ => Self::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidSnapshot",
+                "Invalid device snapshot",
+                detail,
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "simulation")]
+impl From<SimError> for ApiError {
+    fn from(err: SimError) -> Self {
+        let detail = err.to_string();
+        match err {
+            SimError::FIFOCore(e) => Self::fifocore(e, "Couldn't open simulator session"),
+            SimError::NotSimBus(_) => Self::new(
+                StatusCode::CONFLICT,
+                "NotSimBus",
+                "Bus is not a simulation bus",
+                detail,
+            )
+            .with_hint("Open a bus with params like sim:test to simulate devices on it."),
+            SimError::InvalidDeviceId(id) => Self::invalid_param("id", id),
+            SimError::AlreadyExists(..) => Self::new(
+                StatusCode::CONFLICT,
+                "SimDeviceExists",
+                "Simulated device already exists",
+                detail,
+            )
+            .with_hint("Pass \"conflict\": true to put another device on the same id."),
+            SimError::SerialInUse(..) => Self::new(
+                StatusCode::CONFLICT,
+                "SimSerialInUse",
+                "Simulated device serial already in use",
+                detail,
+            )
+            .with_hint("Devices sharing an id need distinct serials."),
+            SimError::NoSuchDevice(..) => Self::new(
+                StatusCode::NOT_FOUND,
+                "NoSuchSimDevice",
+                "No such simulated device",
+                detail,
+            ),
+            SimError::Signal(..) => Self::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidSimSignal",
+                "Invalid simulated signal",
+                detail,
+            ),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        let (status, hint) = fifocore_status(err);
+        let mut this = Self::new(status, err.name(), err.message(), err.message().to_owned());
+        this.0.error_id = Some(err as i32);
+        this.0.reason = Some(err.message());
+        this.0.hint = hint;
+        this
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let mut response = (status, axum::Json(*self.0)).into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        response
+    }
+}
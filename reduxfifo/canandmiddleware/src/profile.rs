@@ -0,0 +1,494 @@
+//! Default settings profiles for factory-fresh devices.
+//!
+//! A profile says what a product's settings should be on this robot, e.g. "every Canandmag sends
+//! velocity every 10 ms". When a device of that product first shows up on a bus with a device
+//! session open, its profile settings are read back; if they are all still at their factory
+//! defaults, the profile is written to it. Devices that were already configured by hand are left
+//! alone. Every decision goes to the log and to an audit trail served over REST, and profiles can
+//! be set to dry-run so teams can check what would happen before letting it write anything.
+
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use canandmessage::{
+    cananddevice,
+    traits::{CanandDevice, MessageIndexId},
+};
+use fifocore::{FIFOInterface, ReduxFIFOMessage};
+use frc_can_id::FRCCanId;
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use serial_numer::SerialNumer;
+
+use crate::{
+    bus::{
+        control::ControlScheduler,
+        device::{Device, DeviceKey, ReduxDeviceType, serial_str},
+    },
+    log::{log_error, log_info, log_warn},
+};
+
+/// How long to wait for a device to report its current settings.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(2);
+/// Audit entries kept in memory.
+const AUDIT_LEN: usize = 256;
+
+/// Products a profile can target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileProduct {
+    Canandmag,
+    Canandgyro,
+    Canandcolor,
+}
+
+impl ProfileProduct {
+    pub(crate) fn for_device(key: &DeviceKey) -> Option<Self> {
+        match key.dev_type {
+            ReduxDeviceType::Encoder => Some(Self::Canandmag),
+            ReduxDeviceType::Gyroscope => Some(Self::Canandgyro),
+            ReduxDeviceType::ColorDistanceSensor => Some(Self::Canandcolor),
+            _ => None,
+        }
+    }
+
+    /// (index, name, writable, factory default) of every setting the product has.
+    pub(crate) fn settings(self) -> Vec<(u8, String, bool, [u8; 6])> {
+        fn collect<D: CanandDevice>() -> Vec<(u8, String, bool, [u8; 6])> {
+            D::setting_info()
+                .iter()
+                .map(|info| {
+                    (
+                        info.index.into(),
+                        format!("{:?}", info.index),
+                        info.writable,
+                        info.default_value.into(),
+                    )
+                })
+                .collect()
+        }
+        match self {
+            Self::Canandmag => collect::<canandmessage::canandmag::Device>(),
+            Self::Canandgyro => collect::<canandmessage::canandgyro::Device>(),
+            Self::Canandcolor => collect::<canandmessage::canandcolor::Device>(),
+        }
+    }
+}
+
+/// Setting names are matched ignoring case and underscores, so the spec's `VELOCITY_FRAME_PERIOD`,
+/// `velocity_frame_period` and `VelocityFramePeriod` all name the same setting.
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Settings to apply to every factory-fresh device of a product.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsProfile {
+    pub product: ProfileProduct,
+    /// Setting name to raw setting value, e.g. `{"velocity_frame_period": 10}`
+    pub settings: FxHashMap<String, u64>,
+    /// Only log what would be written
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone)]
+struct ResolvedSetting {
+    index: u8,
+    name: String,
+    value: [u8; 6],
+    default: [u8; 6],
+}
+
+#[derive(Debug, Clone)]
+struct ResolvedProfile {
+    profile: SettingsProfile,
+    settings: Vec<ResolvedSetting>,
+}
+
+impl ResolvedProfile {
+    fn resolve(profile: SettingsProfile) -> Result<Self, ProfileError> {
+        let known = profile.product.settings();
+        let mut settings = Vec::with_capacity(profile.settings.len());
+        for (name, value) in profile.settings.iter() {
+            let wanted = normalize_name(name);
+            let Some((index, spec_name, writable, default)) = known
+                .iter()
+                .find(|(_, spec_name, _, _)| normalize_name(spec_name) == wanted)
+            else {
+                return Err(ProfileError::UnknownSetting(name.clone()));
+            };
+            if !writable {
+                return Err(ProfileError::ReadOnly(name.clone()));
+            }
+            if *value >= 1 << 48 {
+                return Err(ProfileError::ValueTooLarge(name.clone(), *value));
+            }
+            settings.push(ResolvedSetting {
+                index: *index,
+                name: spec_name.clone(),
+                value: value.to_le_bytes()[..6].try_into().unwrap(),
+                default: *default,
+            });
+        }
+        settings.sort_by_key(|s| s.index);
+        Ok(Self { profile, settings })
+    }
+}
+
+/// What happened when a device was checked against its profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// The device was factory-fresh and the profile was written to it
+    Applied { settings: Vec<String> },
+    /// The device was factory-fresh, but the profile is a dry run
+    DryRun { settings: Vec<String> },
+    /// Some settings were already changed from their defaults, so the device was left alone
+    NotFactoryFresh { changed: Vec<String> },
+    /// The device didn't report its settings in time
+    NoResponse,
+    /// Writing a setting failed
+    WriteFailed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// Unix time in microseconds
+    pub timestamp_us: i64,
+    pub bus_id: u16,
+    /// Device type and CAN id, e.g. `Encoder:3`
+    pub device: String,
+    pub serial: String,
+    pub product: ProfileProduct,
+    #[serde(flatten)]
+    pub outcome: AuditOutcome,
+}
+
+#[derive(Debug, Default)]
+struct ProfilesInner {
+    profiles: FxHashMap<ProfileProduct, ResolvedProfile>,
+    audit: VecDeque<AuditEntry>,
+}
+
+/// Configured profiles and their audit trail, shared by the REST server and every bus session.
+#[derive(Debug, Clone, Default)]
+pub struct Profiles {
+    inner: Arc<Mutex<ProfilesInner>>,
+}
+
+impl Profiles {
+    /// Adds a profile, replacing any existing profile for the same product.
+    pub fn set(&self, profile: SettingsProfile) -> Result<(), ProfileError> {
+        let resolved = ResolvedProfile::resolve(profile)?;
+        log_info!(
+            "[profile] {:?} profile set: {}{}",
+            resolved.profile.product,
+            assignments(&resolved.settings).join(", "),
+            if resolved.profile.dry_run { " (dry run)" } else { "" }
+        );
+        self.inner
+            .lock()
+            .profiles
+            .insert(resolved.profile.product, resolved);
+        Ok(())
+    }
+
+    /// Removes a product's profile. Returns false if it had none.
+    pub fn remove(&self, product: ProfileProduct) -> bool {
+        self.inner.lock().profiles.remove(&product).is_some()
+    }
+
+    pub fn list(&self) -> Vec<SettingsProfile> {
+        let mut profiles: Vec<SettingsProfile> = self
+            .inner
+            .lock()
+            .profiles
+            .values()
+            .map(|p| p.profile.clone())
+            .collect();
+        profiles.sort_by_key(|p| p.product as u8);
+        profiles
+    }
+
+    /// Loads profiles from a JSON file holding a list of [`SettingsProfile`]s.
+    pub fn load_file(&self, path: &Path) -> Result<usize, ProfileError> {
+        let data = std::fs::read(path).map_err(ProfileError::Io)?;
+        let profiles: Vec<SettingsProfile> =
+            serde_json::from_slice(&data).map_err(ProfileError::Json)?;
+        let count = profiles.len();
+        for profile in profiles {
+            self.set(profile)?;
+        }
+        Ok(count)
+    }
+
+    /// Most recent profile decisions, oldest first.
+    pub fn audit(&self) -> Vec<AuditEntry> {
+        self.inner.lock().audit.iter().cloned().collect()
+    }
+
+    fn get(&self, product: ProfileProduct) -> Option<ResolvedProfile> {
+        self.inner.lock().profiles.get(&product).cloned()
+    }
+
+    fn record(&self, entry: AuditEntry) {
+        let what = match &entry.outcome {
+            AuditOutcome::Applied { settings } => format!("applied {}", settings.join(", ")),
+            AuditOutcome::DryRun { settings } => {
+                format!("would apply {} (dry run)", settings.join(", "))
+            }
+            AuditOutcome::NotFactoryFresh { changed } => {
+                format!("not factory-fresh ({} changed), left alone", changed.join(", "))
+            }
+            AuditOutcome::NoResponse => "didn't report its settings, skipped".to_string(),
+            AuditOutcome::WriteFailed { error } => format!("write failed: {error}"),
+        };
+        let line = format!(
+            "[profile] [bus {}] {} ({}): {what}",
+            entry.bus_id, entry.device, entry.serial
+        );
+        match entry.outcome {
+            AuditOutcome::WriteFailed { .. } => log_error!("{line}"),
+            AuditOutcome::NoResponse => log_warn!("{line}"),
+            _ => log_info!("{line}"),
+        }
+
+        let mut inner = self.inner.lock();
+        if inner.audit.len() >= AUDIT_LEN {
+            inner.audit.pop_front();
+        }
+        inner.audit.push_back(entry);
+    }
+}
+
+/// `name=value` for each setting.
+fn assignments(settings: &[ResolvedSetting]) -> Vec<String> {
+    settings
+        .iter()
+        .map(|s| {
+            let mut raw = [0u8; 8];
+            raw[..6].copy_from_slice(&s.value);
+            format!("{}={}", s.name, u64::from_le_bytes(raw))
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct PendingCheck {
+    serial: SerialNumer,
+    profile: ResolvedProfile,
+    started: Instant,
+}
+
+/// Applies profiles to devices on one bus. Owned by the bus session.
+#[derive(Debug)]
+pub struct BusProfiler {
+    bus_id: u16,
+    profiles: Profiles,
+    /// Devices that were already checked, by serial numer so a swapped device gets checked too
+    checked: FxHashSet<[u8; 6]>,
+    pending: FxHashMap<DeviceKey, PendingCheck>,
+}
+
+impl BusProfiler {
+    pub fn new(bus_id: u16, profiles: Profiles) -> Self {
+        Self {
+            bus_id,
+            profiles,
+            checked: FxHashSet::default(),
+            pending: FxHashMap::default(),
+        }
+    }
+
+    /// Starts checks on newly seen devices and finishes the checks whose settings came back.
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        devices: &mut FxHashMap<DeviceKey, Device>,
+        fifocore: &dyn FIFOInterface,
+        control: &mut ControlScheduler,
+    ) {
+        for (key, dev) in devices.iter_mut() {
+            let Some(serial) = dev.serial_numer() else {
+                continue;
+            };
+            if dev.bootloader() || self.checked.contains(&<[u8; 6]>::from(serial)) {
+                continue;
+            }
+            let Some(product) = ProfileProduct::for_device(key) else {
+                continue;
+            };
+            self.checked.insert(serial.into());
+            let Some(profile) = self.profiles.get(product) else {
+                continue;
+            };
+            for setting in profile.settings.iter() {
+                dev.setting_cache_mut().remove(&setting.index);
+                let _ = self.send_fetch(key, setting.index, fifocore);
+            }
+            control.charge(2 * profile.settings.len() as u32);
+            self.pending.insert(
+                *key,
+                PendingCheck {
+                    serial,
+                    profile,
+                    started: now,
+                },
+            );
+        }
+
+        let mut done = Vec::new();
+        for (key, check) in self.pending.iter() {
+            let Some(dev) = devices.get_mut(key) else {
+                done.push((*key, None));
+                continue;
+            };
+            let reported: Option<Vec<[u8; 6]>> = check
+                .profile
+                .settings
+                .iter()
+                .map(|s| dev.setting_cache().get(&s.index).copied())
+                .collect();
+            let outcome = match reported {
+                Some(values) => self.decide(key, check, &values, dev, fifocore, control),
+                None if now.duration_since(check.started) > FETCH_TIMEOUT => {
+                    AuditOutcome::NoResponse
+                }
+                None => continue,
+            };
+            done.push((*key, Some(outcome)));
+        }
+
+        for (key, outcome) in done {
+            let Some(check) = self.pending.remove(&key) else {
+                continue;
+            };
+            if let Some(outcome) = outcome {
+                self.profiles.record(AuditEntry {
+                    timestamp_us: fifocore::timebase::now_us(),
+                    bus_id: self.bus_id,
+                    device: key.pretty_str(),
+                    serial: serial_str(Some(check.serial)),
+                    product: check.profile.profile.product,
+                    outcome,
+                });
+            }
+        }
+    }
+
+    fn decide(
+        &self,
+        key: &DeviceKey,
+        check: &PendingCheck,
+        values: &[[u8; 6]],
+        dev: &mut Device,
+        fifocore: &dyn FIFOInterface,
+        control: &mut ControlScheduler,
+    ) -> AuditOutcome {
+        let settings = &check.profile.settings;
+        let changed: Vec<String> = settings
+            .iter()
+            .zip(values)
+            .filter(|(s, value)| s.default != **value)
+            .map(|(s, _)| s.name.clone())
+            .collect();
+        if !changed.is_empty() {
+            return AuditOutcome::NotFactoryFresh { changed };
+        }
+
+        let applied = assignments(settings);
+        if check.profile.profile.dry_run {
+            return AuditOutcome::DryRun { settings: applied };
+        }
+        for setting in settings.iter() {
+            if let Err(e) = self.send_set(key, setting, fifocore) {
+                return AuditOutcome::WriteFailed {
+                    error: format!("{}: {e}", setting.name),
+                };
+            }
+            control.charge(2);
+            dev.setting_cache_mut().remove(&setting.index);
+        }
+        AuditOutcome::Applied { settings: applied }
+    }
+
+    fn send_fetch(
+        &self,
+        key: &DeviceKey,
+        index: u8,
+        fifocore: &dyn FIFOInterface,
+    ) -> Result<(), fifocore::error::Error> {
+        let base = FRCCanId::new(key.can_id());
+        let id = cananddevice::MessageIndex::SettingCommand
+            .frc_can_id_as(base.device_type_code(), base.device_number());
+        fifocore.write_single(
+            &ReduxFIFOMessage::builder()
+                .bus(self.bus_id)
+                .id(id.0)
+                .data(&[cananddevice::types::SettingCommand::FetchSettingValue as u8, index])
+                .build(),
+        )
+    }
+
+    fn send_set(
+        &self,
+        key: &DeviceKey,
+        setting: &ResolvedSetting,
+        fifocore: &dyn FIFOInterface,
+    ) -> Result<(), fifocore::error::Error> {
+        let base = FRCCanId::new(key.can_id());
+        let id = cananddevice::MessageIndex::SetSetting
+            .frc_can_id_as(base.device_type_code(), base.device_number());
+        let body: [u8; 8] = canandmessage::generic::SetSetting::new(
+            setting.index,
+            setting.value,
+            cananddevice::types::SettingFlags {
+                ephemeral: false,
+                synch_hold: false,
+                synch_msg_count: 0,
+            },
+        )
+        .into();
+        fifocore.write_single(
+            &ReduxFIFOMessage::builder()
+                .bus(self.bus_id)
+                .id(id.0)
+                .data(&body)
+                .build(),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    UnknownSetting(String),
+    ReadOnly(String),
+    ValueTooLarge(String, u64),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl core::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProfileError::UnknownSetting(name) => write!(f, "no setting named {name:?}"),
+            ProfileError::ReadOnly(name) => write!(f, "setting {name:?} can't be written"),
+            ProfileError::ValueTooLarge(name, value) => {
+                write!(f, "value {value} for {name:?} doesn't fit in 48 bits")
+            }
+            ProfileError::Io(e) => write!(f, "couldn't read profiles: {e}"),
+            ProfileError::Json(e) => write!(f, "invalid profiles file: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for ProfileError {}
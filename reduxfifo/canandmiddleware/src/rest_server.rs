@@ -1,24 +1,68 @@
-use std::{sync::Arc, time::Duration};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
     Router,
     extract::{Path, Query, State, WebSocketUpgrade},
-    http::StatusCode,
-    response::{Html, IntoResponse, Json},
-    routing::{get, post},
+    http::header,
+    response::{
+        Html, IntoResponse, Json,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{delete, get, post},
 };
+use futures::Stream;
 use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
-use tokio::sync::watch;
+use tokio::sync::{broadcast::error::RecvError, watch};
 use tower_http::cors::{Any, CorsLayer};
 
 use crate::log::*;
 use crate::ota::{OtaAddress, OtaTask};
 use crate::{
-    backend::{self, FIFOCoreError},
-    bus::{self, BusState, device::DeviceType},
+    backend,
+    bench::{Bench, BenchStatus, HeartbeatStatus},
+    bulk::{self, BulkState},
+    calibration::{CalibrationMap, CalibrationOptions, CalibrationStatus, CalibrationTask},
+    confirm::{ConfirmPolicy, Confirmations, Operation},
+    bus::{
+        self, BusState,
+        control::FetchJob,
+        device::{DeviceKey, DeviceType, ReduxDeviceType},
+        device_lock::{DeviceGuard, DeviceLockReport, DeviceLocks},
+        frame_period::FramePeriodReport,
+        setting::SettingRetry,
+        setting_change::{self, SettingChangeEvent},
+    },
+    device_snapshot::{DeviceSnapshot, RestoreReport},
+    diagnostics::{ConsoleStatus, DEFAULT_CONSOLE_ADDR, Diagnostics},
+    inventory::InventoryReport,
+    firmware_index::{FirmwareIndexFile, FirmwareStatusReport},
+    firmware_notes::{FirmwareMetadata, FirmwareNotes, Version},
+    fleet::{self, FleetDevice, Routed},
+    migration::MigrationTable,
+    snapshot::MiddlewareSnapshot,
+    telemetry::Telemetry,
+    mirror::{MirrorConfig, Mirrors},
+    problem::ApiError,
+    profile::{AuditEntry, ProfileProduct, Profiles, SettingsProfile},
+    settings_file::{ImportResult, SettingsFile},
+    websocket::BackpressureConfig,
 };
-use fifocore::{FIFOCore, ReduxFIFOSessionConfig, error::Error};
+use fifocore::{
+    FIFOCore, ReduxFIFOSession, ReduxFIFOSessionConfig,
+    bus_alias::BusRef,
+    error::Error,
+    dispatch::{DispatchStats, SessionWait},
+    estop::{self, GlobalDisableReport},
+    latency::LatencyReport,
+    limits::{DropStats, MemoryLimits},
+    pause::{DEFAULT_PAUSE_CAP, PauseStatus},
+    trace_tag::TraceTag,
+    write_batch::WriteBatchStats,
+};
+use frc_can_id::FRCCanId;
+use rdxsignal::SignalConfig;
+use serial_numer::SerialNumer;
 
 // -----------------------
 
@@ -50,7 +94,23 @@ const fn banner() -> &'static str {
 pub(crate) struct AppState {
     pub(crate) fifocore: FIFOCore,
     pub(crate) ota_clients: Arc<Mutex<FxHashMap<OtaAddress, OtaTask>>>,
+    /// USB OTAs of devices in DFU mode, by USB serial number
+    pub(crate) usb_ota_clients: Arc<Mutex<FxHashMap<String, OtaTask>>>,
     pub(crate) bus_sessions: Arc<Mutex<FxHashMap<u16, BusState>>>,
+    /// held across multi-step control operations so clients don't interleave them
+    pub(crate) device_locks: DeviceLocks,
+    /// holds destructive operations back until an operator confirms them
+    pub(crate) confirmations: Confirmations,
+    pub(crate) bench: Bench,
+    /// who may start tokio-console
+    pub(crate) diagnostics: Diagnostics,
+    /// Gyro calibrations, kept after they finish so their result can be read back
+    pub(crate) calibrations: Arc<Mutex<CalibrationMap>>,
+    pub(crate) mirrors: Mirrors,
+    pub(crate) profiles: Profiles,
+    pub(crate) firmware_metadata: FirmwareMetadata,
+    #[cfg(feature = "simulation")]
+    pub(crate) simulator: crate::sim::Simulator,
 }
 
 // These are in order of their `.route` definitions
@@ -69,14 +129,67 @@ async fn configurator_handler() -> Html<&'static str> {
     Html(include_str!("html/configurator.html"))
 }
 
-/// `/ws/{bus}`
+/// `/ws/{bus}?overflow=drop&queue=4096&echo=false&label=websocket`
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
-    Path(bus_id): Path<u16>,
-) -> axum::response::Response {
+    Path(bus): Path<BusRef>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<axum::response::Response, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let mut backpressure = BackpressureConfig::default();
+    if params.contains_key("overflow") {
+        backpressure.policy = pull_key(&params, "overflow", |v| v.parse().ok())?;
+    }
+    if params.contains_key("queue") {
+        backpressure.queue_len = pull_key(&params, "queue", |v| {
+            v.parse()
+                .ok()
+                .filter(|len| (1..=BackpressureConfig::MAX_QUEUE_LEN).contains(len))
+        })?;
+    }
+    // also send what this host transmits, flagged as TX
+    let echo_tx = if params.contains_key("echo") {
+        pull_key(&params, "echo", |v| v.parse().ok())?
+    } else {
+        false
+    };
+    // what the client's writes are attributed to in bus logs, e.g. `alchemist`
+    let tag = if params.contains_key("label") {
+        pull_key(&params, "label", |v| TraceTag::intern(v).ok())?
+    } else {
+        TraceTag::intern("websocket").unwrap_or_default()
+    };
     let fifocore = state.fifocore;
-    ws.on_upgrade(move |socket| crate::websocket::handle_socket(socket, fifocore, bus_id))
+    Ok(ws.on_upgrade(move |socket| {
+        crate::websocket::handle_socket(socket, fifocore, bus_id, backpressure, echo_tx, tag)
+    }))
+}
+
+/// `/console/{serial}`: websocket to the debug console of an RdxUSB device
+async fn console_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(serial): Path<String>,
+) -> Result<axum::response::Response, ApiError> {
+    let console = state
+        .fifocore
+        .open_console(&serial)
+        .await
+        .map_err(|e| ApiError::fifocore(e, "Couldn't open console"))?;
+    Ok(ws.on_upgrade(move |socket| crate::websocket::handle_console(socket, console)))
+}
+
+/// `/usb/devices`: Redux devices attached over USB, including ones that can't be opened as a bus
+async fn usb_devices_handler(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<fifocore::backends::rdxusb::UsbDeviceListing>>, ApiError> {
+    state
+        .fifocore
+        .usb_devices()
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::fifocore(e, "Couldn't list USB devices"))
 }
 
 /// `/buses`
@@ -84,49 +197,74 @@ async fn list_bus_handler(State(state): State<AppState>) -> Json<backend::ListBu
     Json(backend::handle_list_bus(&state.fifocore))
 }
 
+/// `/buses/{bus}/info`
+async fn bus_info_handler(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+) -> Result<Json<fifocore::bus_info::BusInfo>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    state
+        .fifocore
+        .bus_info(bus_id)
+        .map(Json)
+        .map_err(|e| ApiError::fifocore(e, format!("Bus {bus}")))
+}
+
 /// `/buses/open?params=...` where `params` is the bus open params
 async fn open_bus_handler(
     State(state): State<AppState>,
     Query(params): Query<FxHashMap<String, String>>,
-) -> axum::response::Response {
-    let Some(bus_name) = params.get("params") else {
-        let mut response = Json(()).into_response();
-        *response.status_mut() = StatusCode::BAD_REQUEST;
-        return response;
-    };
+) -> Result<Json<backend::BusOpenSuccess>, ApiError> {
+    let bus_name = pull_key(&params, "params", |v| Some(v.as_str()))?;
     backend::handle_open_bus(&state.fifocore, bus_name)
 }
 
+/// `/buses/aliases`
+async fn list_aliases_handler(State(state): State<AppState>) -> Json<Vec<backend::BusAliasEntry>> {
+    Json(backend::handle_list_aliases(&state.fifocore))
+}
+
+/// `/buses/aliases/{alias}/set?params=...`
+async fn set_alias_handler(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<backend::BusAliasEntry>, ApiError> {
+    let bus_params = pull_key(&params, "params", |v| Some(v.as_str()))?;
+    backend::handle_set_alias(&state.fifocore, &alias, bus_params)
+}
+
+/// `/buses/aliases/{alias}/remove`
+async fn remove_alias_handler(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+) -> Json<bool> {
+    Json(state.fifocore.remove_bus_alias(&alias))
+}
+
 fn sessions_open_bus_inner<'a>(
-    mut bus_sessions: parking_lot::MutexGuard<'a, FxHashMap<u16, BusState>>,
+    bus_sessions: parking_lot::MutexGuard<'a, FxHashMap<u16, BusState>>,
     state: &AppState,
     bus_id: u16,
-) -> Result<(), Json<FIFOCoreError>> {
-    let config = ReduxFIFOSessionConfig::new(0x0e0000, 0xff0000);
-    let session = state
-        .fifocore
-        .open_managed_session(bus_id, 256, config)
-        .map_err(|e| Json::<FIFOCoreError>(e.into()))?;
-    let (start_send, start_gate) = tokio::sync::oneshot::channel();
-
-    let task = tokio::task::spawn(bus::bus_session(
-        start_gate,
-        session,
-        state.bus_sessions.clone(),
-    ));
-    bus_sessions.insert(bus_id, BusState::new(task, state.fifocore.clone(), bus_id));
-    drop(bus_sessions);
-    let _ = start_send.send(());
-    Ok(())
+) -> Result<(), ApiError> {
+    bus::open_bus_state(
+        bus_sessions,
+        &state.bus_sessions,
+        &state.fifocore,
+        bus_id,
+        &state.profiles,
+    )
+        .map_err(|e| ApiError::fifocore(e, format!("Couldn't open a session on bus {bus_id}")))
 }
 
 /// `sessions/open/{bus}`
 async fn session_open_bus(
     State(state): State<AppState>,
-    Path(bus_id): Path<u16>,
-) -> Result<Json<()>, Json<FIFOCoreError>> {
+    Path(bus): Path<BusRef>,
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     if !state.fifocore.buses().contains(&bus_id) {
-        return Err(Json(backend::FIFOCoreError::from(Error::InvalidBus)));
+        return Err(ApiError::fifocore(Error::InvalidBus, format!("Bus {bus_id}")));
     };
     let bus_sessions = state.bus_sessions.lock();
     if !bus_sessions.contains_key(&bus_id) {
@@ -136,30 +274,36 @@ async fn session_open_bus(
 }
 
 /// `sessions/close/{bus}`
-async fn session_close_bus(State(state): State<AppState>, Path(bus_id): Path<u16>) -> Json<()> {
+async fn session_close_bus(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let mut bus_sessions = state.bus_sessions.lock();
     drop(bus_sessions.remove(&bus_id));
-    Json(())
+    Ok(Json(()))
 }
 
 /// `sessions/{bus}/enumerate`
 async fn session_enumerate_bus(
     State(state): State<AppState>,
-    Path(bus_id): Path<u16>,
-) -> Result<Json<()>, Json<FIFOCoreError>> {
+    Path(bus): Path<BusRef>,
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let mut bus_sessions = state.bus_sessions.lock();
-    let Some(state) = bus_sessions.get_mut(&bus_id) else {
-        return Err(Json(fifocore::error::Error::InvalidBus.into()));
-    };
-    state.enumerate().map_err(|e| Json(e.into()))?;
+    let state = bus_state(&mut bus_sessions, bus_id)?;
+    state
+        .enumerate()
+        .map_err(|e| ApiError::fifocore(e, "Couldn't send enumerate"))?;
     Ok(Json(()))
 }
 
 /// `sessions/{bus}/devices/list`
 async fn session_list_devices(
     State(state): State<AppState>,
-    Path(bus_id): Path<u16>,
-) -> Result<Json<FxHashMap<String, DeviceType>>, Json<FIFOCoreError>> {
+    Path(bus): Path<BusRef>,
+) -> Result<Json<FxHashMap<String, DeviceType>>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let bus_sessions = state.bus_sessions.lock();
     if let Some(state) = bus_sessions.get(&bus_id) {
         Ok(Json(state.known_devices()))
@@ -169,15 +313,56 @@ async fn session_list_devices(
     }
 }
 
+/// `sessions/{bus}/power`
+async fn session_power(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+) -> Result<Json<crate::bus::power::PowerStatus>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let mut bus_sessions = state.bus_sessions.lock();
+    let state = bus_state(&mut bus_sessions, bus_id)?;
+    Ok(Json(state.power.status()))
+}
+
+/// `sessions/{bus}/setting_changes`: settings devices on the bus changed on their own, oldest first
+async fn session_setting_changes(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+) -> Result<Json<Vec<SettingChangeEvent>>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let mut bus_sessions = state.bus_sessions.lock();
+    let state = bus_state(&mut bus_sessions, bus_id)?;
+    Ok(Json(state.setting_changes.recent()))
+}
+
+/// `setting_changes/events`: streams settings devices change on their own, on every bus, as
+/// server-sent `setting_change` events. Changes a slow client missed are skipped.
+async fn setting_change_events() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = futures::stream::unfold(setting_change::subscribe(), |mut recv| async move {
+        let change = loop {
+            match recv.recv().await {
+                Ok(change) => break change,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        };
+        let event = Event::default()
+            .event("setting_change")
+            .json_data(&change)
+            .unwrap_or_default();
+        Some((Ok(event), recv))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
 /// `sessions/{bus}/devices/clear`
 async fn session_clear_devices(
     State(state): State<AppState>,
-    Path(bus_id): Path<u16>,
-) -> Result<Json<()>, StatusCode> {
+    Path(bus): Path<BusRef>,
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let mut bus_sessions = state.bus_sessions.lock();
-    let Some(state) = bus_sessions.get_mut(&bus_id) else {
-        return Err(StatusCode::NOT_FOUND);
-    };
+    let state = bus_state(&mut bus_sessions, bus_id)?;
     state.clear_known_devices();
     Ok(Json(()))
 }
@@ -185,9 +370,10 @@ async fn session_clear_devices(
 /// `sessions/{bus}/devices/arbitrate?serial=`
 async fn session_arb_device(
     State(state): State<AppState>,
-    Path((bus_id, device_id_hex)): Path<(u16, String)>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
     Query(params): Query<FxHashMap<String, String>>,
-) -> Result<Json<()>, StatusCode> {
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let device_id = session_hex(&device_id_hex)?;
     let serial_numer = pull_key(&params, "serial", |v| {
         serial_numer::SerialNumer::from_readable_str(v, true)
@@ -198,7 +384,7 @@ async fn session_arb_device(
 
     state.arbitrate(device_id, serial_numer).map_err(|e| {
         log_error!("Couldn't arbitrate ids on {device_id_hex}: {e}!");
-        StatusCode::INTERNAL_SERVER_ERROR
+        ApiError::fifocore(e, format!("Couldn't arbitrate ids on {device_id_hex}"))
     })?;
     Ok(Json(()))
 }
@@ -206,9 +392,10 @@ async fn session_arb_device(
 /// `sessions/{bus}/devices/{device}/blink?r=1`
 async fn session_blink_device(
     State(state): State<AppState>,
-    Path((bus_id, device_id_hex)): Path<(u16, String)>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
     Query(params): Query<FxHashMap<String, u8>>,
-) -> Result<Json<()>, StatusCode> {
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let device_id = session_hex(&device_id_hex)?;
     let value = pull_key(&params, "r", |v| Some(*v))?;
 
@@ -217,43 +404,50 @@ async fn session_blink_device(
 
     state.blink(device_id, value).map_err(|e| {
         log_error!("Couldn't blink LED: {e}");
-        StatusCode::INTERNAL_SERVER_ERROR
+        ApiError::fifocore(e, format!("Couldn't blink {device_id_hex}"))
     })?;
     Ok(Json(()))
 }
 
-/// `sessions/{bus}/devices/{device}/set_id?id=1`
+/// `sessions/{bus}/devices/{device}/set_id?id=1&confirm=<token>`
+///
+/// Needs confirming while the robot is enabled, by default; see [`crate::confirm`].
 async fn session_set_id_device(
     State(state): State<AppState>,
-    Path((bus_id, device_id_hex)): Path<(u16, String)>,
-    Query(params): Query<FxHashMap<String, u8>>,
-) -> Result<Json<()>, StatusCode> {
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let device_id = session_hex(&device_id_hex)?;
-    let new_id = pull_key(&params, "id", |v| Some(*v))?;
+    let new_id = pull_key(&params, "id", |v| v.parse::<u8>().ok())?;
 
+    confirm_device(&state, Operation::SetId, bus_id, device_id, &params).await?;
+    let _device = lock_device(&state, bus_id, device_id, "set_id").await?;
     let mut bus_sessions = state.bus_sessions.lock();
     let state = bus_state(&mut bus_sessions, bus_id)?;
     state.set_id(device_id, new_id).map_err(|e| {
         log_error!("Couldn't set device ID on {device_id_hex}: {e}!");
-        StatusCode::INTERNAL_SERVER_ERROR
+        ApiError::fifocore(e, format!("Couldn't set device ID on {device_id_hex}"))
     })?;
     Ok(Json(()))
 }
 
 async fn session_fetch_setting(
     State(state): State<AppState>,
-    Path((bus_id, device_id_hex)): Path<(u16, String)>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
     Query(params): Query<FxHashMap<String, String>>,
-) -> Result<Json<Option<crate::bus::FetchSetting>>, StatusCode> {
+) -> Result<Json<Option<crate::bus::FetchSetting>>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let device_id = session_hex(&device_id_hex)?;
     let index = pull_key(&params, "index", |v| v.parse::<u8>().ok())?;
 
+    let _device = lock_device(&state, bus_id, device_id, "fetch_setting").await?;
     {
         let mut bus_sessions = state.bus_sessions.lock();
         let state = bus_state(&mut bus_sessions, bus_id)?;
         state.send_fetch_setting(device_id, index).map_err(|e| {
-            log_error!("Couldn't set device ID on {device_id_hex}: {e}!");
-            StatusCode::INTERNAL_SERVER_ERROR
+            log_error!("Couldn't fetch setting on {device_id_hex}: {e}!");
+            ApiError::fifocore(e, format!("Couldn't fetch setting on {device_id_hex}"))
         })?;
     }
 
@@ -275,18 +469,136 @@ async fn session_fetch_setting(
 
 async fn session_set_name(
     State(state): State<AppState>,
-    Path((bus_id, device_id_hex)): Path<(u16, String)>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
     Query(params): Query<FxHashMap<String, String>>,
-) -> Result<Json<()>, StatusCode> {
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let device_id = session_hex(&device_id_hex)?;
-    let name: String = pull_key(&params, "name", |v| Some(v.clone()))?;
+    let mut name: String = pull_key(&params, "name", |v| Some(v.clone()))?;
+    let composite = find_composite("name")?;
+    // this endpoint has always truncated long names rather than rejecting them
+    while name.len() > composite.capacity() {
+        name.pop();
+    }
+    let _device = lock_device(&state, bus_id, device_id, "set_name").await?;
     {
         let mut bus_sessions = state.bus_sessions.lock();
         let state = bus_state(&mut bus_sessions, bus_id)?;
-        state.send_set_name(device_id, &name).map_err(|e| {
-            log_error!("Couldn't set device ID on {device_id_hex}: {e}!");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        state
+            .send_set_composite(device_id, composite, name.as_bytes())
+            .map_err(|e| {
+                log_error!("Couldn't set name on {device_id_hex}: {e}!");
+                ApiError::fifocore(e, format!("Couldn't set name on {device_id_hex}"))
+            })?;
+    }
+
+    tokio::time::sleep(Duration::from_millis(
+        params
+            .get("wait")
+            .and_then(|w| w.parse::<u64>().ok())
+            .unwrap_or(50),
+    ))
+    .await;
+
+    Ok(Json(()))
+}
+
+fn find_composite(
+    name: &str,
+) -> Result<&'static canandmessage::generic::CompositeSetting, ApiError> {
+    crate::bus::composite_setting(name).ok_or_else(|| ApiError::invalid_param("name", name))
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct CompositeReport {
+    name: &'static str,
+    /// Joined value, with text padding stripped
+    data: Vec<u8>,
+    /// The value as text, for text-encoded settings
+    text: Option<String>,
+}
+
+async fn session_fetch_composite(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex, name)): Path<(BusRef, String, String)>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<Option<CompositeReport>>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let composite = find_composite(&name)?;
+
+    let _device = lock_device(&state, bus_id, device_id, "fetch_composite").await?;
+    {
+        let mut bus_sessions = state.bus_sessions.lock();
+        let state = bus_state(&mut bus_sessions, bus_id)?;
+        state
+            .send_fetch_composite(device_id, composite)
+            .map_err(|e| {
+                log_error!("Couldn't fetch {name} on {device_id_hex}: {e}!");
+                ApiError::fifocore(e, format!("Couldn't fetch {name} on {device_id_hex}"))
+            })?;
+    }
+
+    tokio::time::sleep(Duration::from_millis(
+        params
+            .get("wait")
+            .and_then(|w| w.parse::<u64>().ok())
+            .unwrap_or(50),
+    ))
+    .await;
+
+    let bus_sessions = state.bus_sessions.lock();
+    let Some(bus_state) = bus_sessions.get(&bus_id) else {
+        return Ok(Json(None));
+    };
+    match bus_state.composite_value(device_id, composite) {
+        Ok(value) => Ok(Json(Some(CompositeReport {
+            name: composite.name,
+            data: value.as_bytes().to_vec(),
+            text: match composite.encoding {
+                canandmessage::generic::CompositeEncoding::Utf8 => {
+                    value.as_str().map(str::to_owned)
+                }
+                canandmessage::generic::CompositeEncoding::Bytes => None,
+            },
+        }))),
+        // the device didn't answer for every part in time
+        Err(canandmessage::generic::CompositeError::Missing(_)) => Ok(Json(None)),
+        Err(e) => Err(ApiError::composite_invalid(composite.name, e)),
+    }
+}
+
+async fn session_set_composite(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex, name)): Path<(BusRef, String, String)>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let composite = find_composite(&name)?;
+    let value: Vec<u8> = match composite.encoding {
+        canandmessage::generic::CompositeEncoding::Utf8 => {
+            pull_key(&params, "value", |v| Some(v.clone().into_bytes()))?
+        }
+        canandmessage::generic::CompositeEncoding::Bytes => {
+            pull_key(&params, "value", |v| parse_hex_bytes(v))?
+        }
+    };
+    if value.len() > composite.capacity() {
+        return Err(ApiError::invalid_param("value", value.len())
+            .with_hint("The value is longer than the setting can hold."));
+    }
+
+    let _device = lock_device(&state, bus_id, device_id, "set_composite").await?;
+    {
+        let mut bus_sessions = state.bus_sessions.lock();
+        let state = bus_state(&mut bus_sessions, bus_id)?;
+        state
+            .send_set_composite(device_id, composite, &value)
+            .map_err(|e| {
+                log_error!("Couldn't set {name} on {device_id_hex}: {e}!");
+                ApiError::fifocore(e, format!("Couldn't set {name} on {device_id_hex}"))
+            })?;
     }
 
     tokio::time::sleep(Duration::from_millis(
@@ -300,54 +612,1096 @@ async fn session_set_name(
     Ok(Json(()))
 }
 
+/// `sessions/{bus}/devices/{device_id}/frame_periods`: fetches and reports every frame period
+async fn session_frame_periods(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<Vec<FramePeriodReport>>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let _device = lock_device(&state, bus_id, device_id, "fetch_frame_periods").await?;
+    {
+        let mut bus_sessions = state.bus_sessions.lock();
+        bus_state(&mut bus_sessions, bus_id)?.send_fetch_frame_periods(device_id)?;
+    }
+
+    tokio::time::sleep(Duration::from_millis(
+        params
+            .get("wait")
+            .and_then(|w| w.parse::<u64>().ok())
+            .unwrap_or(50),
+    ))
+    .await;
+
+    let mut bus_sessions = state.bus_sessions.lock();
+    Ok(Json(
+        bus_state(&mut bus_sessions, bus_id)?.frame_period_report(device_id)?,
+    ))
+}
+
+/// `sessions/{bus}/devices/{device_id}/frame_periods/{message_index}/set?period=<ms>`
+async fn session_set_frame_period(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex, message_index)): Path<(BusRef, String, u8)>,
+    Query(params): Query<FxHashMap<String, u32>>,
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let period_ms = pull_key(&params, "period", |v| Some(*v))?;
+
+    let _device = lock_device(&state, bus_id, device_id, "set_frame_period").await?;
+    let mut bus_sessions = state.bus_sessions.lock();
+    bus_state(&mut bus_sessions, bus_id)?.set_frame_period(device_id, message_index, period_ms)?;
+    Ok(Json(()))
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// `POST sessions/{bus}/devices/{device_id}/factory_reset?confirm=<token>`
+///
+/// Needs confirming, by default; see [`crate::confirm`].
+async fn session_factory_reset(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    confirm_device(&state, Operation::FactoryReset, bus_id, device_id, &params).await?;
+    let _device = lock_device(&state, bus_id, device_id, "factory_reset").await?;
+    let mut bus_sessions = state.bus_sessions.lock();
+    let state = bus_state(&mut bus_sessions, bus_id)?;
+    state.send_factory_reset(device_id).map_err(|e| {
+        log_error!("Couldn't factory reset {device_id_hex}: {e}!");
+        ApiError::fifocore(e, format!("Couldn't factory reset {device_id_hex}"))
+    })?;
+    Ok(Json(()))
+}
+
 async fn session_reboot(
     State(state): State<AppState>,
-    Path((bus_id, device_id_hex)): Path<(u16, String)>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
     Query(params): Query<FxHashMap<String, bool>>,
-) -> Result<Json<()>, StatusCode> {
+) -> Result<Json<()>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
     let device_id = session_hex(&device_id_hex)?;
     let bootloader = params.get("bootloader").copied().unwrap_or(false);
+    let _device = lock_device(&state, bus_id, device_id, "reboot").await?;
     {
         let mut bus_sessions = state.bus_sessions.lock();
         let state = bus_state(&mut bus_sessions, bus_id)?;
         state.send_reboot(device_id, bootloader).map_err(|e| {
             log_error!("Couldn't send reboot on {device_id_hex}: {e}!");
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::fifocore(e, format!("Couldn't send reboot on {device_id_hex}"))
         })?;
     }
 
     Ok(Json(()))
 }
 
-fn session_hex(device_id_hex: &str) -> Result<u32, StatusCode> {
+/// `POST sessions/{bus}/devices/{device_id}/calibrate?type=normal&still_ms=500&max_dps=5`
+///
+/// Starts calibrating a Canandgyro once it has held still for `still_ms`. Poll `calibrate/status`
+/// for how it went.
+async fn session_calibrate(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<CalibrationStatus>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let key = DeviceKey::from(FRCCanId::new(device_id));
+    if key.dev_type != ReduxDeviceType::Gyroscope {
+        return Err(ApiError::invalid_param("device_id", device_id_hex)
+            .with_hint("Only Canandgyros can be calibrated."));
+    }
+    let mut options = CalibrationOptions::default();
+    if params.contains_key("type") {
+        options.calibration_type = pull_key(&params, "type", |v| v.parse().ok())?;
+    }
+    if params.contains_key("still_ms") {
+        options.still_window =
+            Duration::from_millis(pull_key(&params, "still_ms", |v| v.parse().ok())?);
+    }
+    if params.contains_key("max_dps") {
+        options.max_rate_dps = pull_key(&params, "max_dps", |v| v.parse().ok())?;
+    }
+    if params.contains_key("timeout") {
+        options.timeout = Duration::from_millis(pull_key(&params, "timeout", |v| v.parse().ok())?);
+    }
+
+    let device = lock_device(&state, bus_id, device_id, "calibrate").await?;
+    let task = CalibrationTask::start(state.fifocore.clone(), bus_id, key, options, device);
+    let status = task.status();
+    state.calibrations.lock().insert((bus_id, key), Arc::new(task));
+    Ok(Json(status))
+}
+
+/// `sessions/{bus}/devices/{device_id}/calibrate/status?wait=0`
+///
+/// The last calibration of the gyro, after waiting up to `wait` ms for it to finish.
+async fn session_calibration_status(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+    Query(params): Query<FxHashMap<String, u64>>,
+) -> Result<Json<CalibrationStatus>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let key = DeviceKey::from(FRCCanId::new(session_hex(&device_id_hex)?));
+    let task = state
+        .calibrations
+        .lock()
+        .get(&(bus_id, key))
+        .cloned()
+        .ok_or_else(|| ApiError::calibration_not_found(bus_id, &key.pretty_str()))?;
+    let wait = params.get("wait").copied().unwrap_or(0);
+    Ok(Json(task.wait(Duration::from_millis(wait)).await))
+}
+
+/// `DELETE sessions/{bus}/devices/{device_id}/calibrate`
+///
+/// Cancels the gyro's calibration, releasing the device, and returns how far it got.
+async fn session_calibration_cancel(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+) -> Result<Json<CalibrationStatus>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let key = DeviceKey::from(FRCCanId::new(session_hex(&device_id_hex)?));
+    let task = state
+        .calibrations
+        .lock()
+        .get(&(bus_id, key))
+        .cloned()
+        .ok_or_else(|| ApiError::calibration_not_found(bus_id, &key.pretty_str()))?;
+    Ok(Json(task.cancel().await))
+}
+
+/// `?timeout=<ms>&attempts=<n>` for each setting read or write, defaulting to [`SettingRetry`]'s.
+fn setting_retry(params: &FxHashMap<String, u64>) -> Result<SettingRetry, ApiError> {
+    let mut retry = SettingRetry::default();
+    if params.contains_key("timeout") {
+        retry.timeout = Duration::from_millis(pull_key(params, "timeout", |v| Some(*v))?);
+    }
+    if params.contains_key("attempts") {
+        retry.attempts = pull_key(params, "attempts", |v| u32::try_from(*v).ok())?;
+    }
+    Ok(retry)
+}
+
+/// `sessions/{bus}/devices/{device_id}/snapshot?timeout=100&attempts=3`
+///
+/// Reads every readable setting of the device into a snapshot; see [`crate::device_snapshot`].
+async fn session_device_snapshot(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+    Query(params): Query<FxHashMap<String, u64>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let retry = setting_retry(&params)?;
+    let _device = lock_device(&state, bus_id, device_id, "snapshot").await?;
+    let snapshot = DeviceSnapshot::take(&state.bus_sessions, bus_id, device_id, retry).await?;
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"redux-device.json\"",
+        )],
+        Json(snapshot),
+    ))
+}
+
+/// `POST sessions/{bus}/devices/{device_id}/restore?timeout=100&attempts=3` with a snapshot
+///
+/// Writes the snapshot's settings to the device, confirming each, and reports what still differs.
+async fn session_device_restore(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+    Query(params): Query<FxHashMap<String, u64>>,
+    Json(snapshot): Json<DeviceSnapshot>,
+) -> Result<Json<RestoreReport>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let retry = setting_retry(&params)?;
+    let _device = lock_device(&state, bus_id, device_id, "restore").await?;
+    Ok(Json(
+        snapshot
+            .restore(&state.bus_sessions, bus_id, device_id, retry)
+            .await?,
+    ))
+}
+
+/// `sessions/{bus}/devices/{device_id}/telemetry?signals=VelocityOutput_velocity&lowpass_hz=5`
+///
+/// Streams the device's `signals`, comma-separated, as server-sent `telemetry` events, each
+/// processed per `lowpass_hz`, `derivative=true` and `window_ms`; see [`crate::telemetry`].
+async fn session_device_telemetry(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let mut config = SignalConfig::default();
+    if params.contains_key("lowpass_hz") {
+        config.lowpass_hz = Some(pull_key(&params, "lowpass_hz", |v| v.parse().ok())?);
+    }
+    if params.contains_key("derivative") {
+        config.derivative = pull_key(&params, "derivative", |v| v.parse().ok())?;
+    }
+    if params.contains_key("window_ms") {
+        let window_ms: u64 = pull_key(&params, "window_ms", |v| v.parse().ok())?;
+        config.window_secs = Some(window_ms as f64 / 1000.0);
+    }
+    let signals: Vec<&str> = params
+        .get("signals")
+        .map(|signals| signals.split(',').filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    let telemetry = Telemetry::new(DeviceKey::from(FRCCanId::new(device_id)), &signals, config)?;
+
+    let mask = frc_can_id::device_filter_mask();
+    let session = state.fifocore.open_managed_session(
+        bus_id,
+        256,
+        ReduxFIFOSessionConfig::new(device_id & mask, mask),
+    )?;
+    let _ = session.set_label("telemetry");
+    let buffer = session.read_buffer(256);
+    let interval = tokio::time::interval(Duration::from_millis(10));
+    let stream = futures::stream::unfold(
+        (session, buffer, interval, telemetry, Vec::new()),
+        |(session, mut buffer, mut interval, mut telemetry, mut queued)| async move {
+            while queued.is_empty() {
+                interval.tick().await;
+                // the session closing with its bus ends the stream
+                session.read_barrier(&mut buffer).ok()?;
+                for msg in buffer.iter() {
+                    queued.extend(telemetry.ingest(msg));
+                }
+                queued.reverse();
+            }
+            let sample = queued.pop()?;
+            let event = Event::default()
+                .event("telemetry")
+                .json_data(&sample)
+                .unwrap_or_default();
+            Some((Ok(event), (session, buffer, interval, telemetry, queued)))
+        },
+    );
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// `/devices`: every device on every open session, with devices seen on several buses merged
+async fn fleet_devices(State(state): State<AppState>) -> Json<Vec<FleetDevice>> {
+    Json(fleet::devices(&state.bus_sessions.lock()))
+}
+
+fn fleet_serial(serial: &str) -> Result<SerialNumer, ApiError> {
+    SerialNumer::from_readable_str(serial, true)
+        .ok_or_else(|| ApiError::invalid_param("serial", serial))
+}
+
+/// `/devices/{serial}/blink?r=1`
+async fn fleet_blink(
+    State(state): State<AppState>,
+    Path(serial_str): Path<String>,
+    Query(params): Query<FxHashMap<String, u8>>,
+) -> Result<Json<Routed>, ApiError> {
+    let serial = fleet_serial(&serial_str)?;
+    let value = pull_key(&params, "r", |v| Some(*v))?;
+    let routed = fleet::route(&mut state.bus_sessions.lock(), serial, |bus, can_id| {
+        bus.blink(can_id, value)
+    })?;
+    Ok(Json(routed))
+}
+
+/// `/devices/{serial}/reboot?bootloader=false`
+async fn fleet_reboot(
+    State(state): State<AppState>,
+    Path(serial_str): Path<String>,
+    Query(params): Query<FxHashMap<String, bool>>,
+) -> Result<Json<Routed>, ApiError> {
+    let serial = fleet_serial(&serial_str)?;
+    let bootloader = params.get("bootloader").copied().unwrap_or(false);
+    let mut bus_sessions = state.bus_sessions.lock();
+    let routed = fleet::route(&mut bus_sessions, serial, |bus, can_id| {
+        bus.send_reboot(can_id, bootloader)
+    })?;
+    // the other buses will see the device go quiet too
+    for address in fleet::addresses(&bus_sessions, serial) {
+        if !address.rebooting
+            && let Some(bus) = bus_sessions.get_mut(&address.bus_id)
+        {
+            bus.expect_reboot(DeviceKey::from(FRCCanId::new(address.can_id)));
+        }
+    }
+    Ok(Json(routed))
+}
+
+/// `/devices/{serial}/fetch_setting?index=0&wait=50`
+async fn fleet_fetch_setting(
+    State(state): State<AppState>,
+    Path(serial_str): Path<String>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<Option<crate::bus::FetchSetting>>, ApiError> {
+    let serial = fleet_serial(&serial_str)?;
+    let index = pull_key(&params, "index", |v| v.parse::<u8>().ok())?;
+    let routed = fleet::route(&mut state.bus_sessions.lock(), serial, |bus, can_id| {
+        bus.send_fetch_setting(can_id, index)
+    })?;
+
+    tokio::time::sleep(Duration::from_millis(
+        params
+            .get("wait")
+            .and_then(|w| w.parse::<u64>().ok())
+            .unwrap_or(50),
+    ))
+    .await;
+
+    let bus_sessions = state.bus_sessions.lock();
+    Ok(Json(bus_sessions.get(&routed.bus_id).and_then(
+        |bus_state| bus_state.setting_cache(routed.can_id, index),
+    )))
+}
+
+/// What we know about one device, including notes on the firmware it runs.
+#[derive(Debug, Clone, serde::Serialize)]
+struct DeviceInfo {
+    device: String,
+    can_id: u32,
+    device_type: DeviceType,
+    product: Option<&'static str>,
+    serial: Option<String>,
+    firmware: Option<String>,
+    bootloader: bool,
+    /// Release notes and known issues, if a firmware metadata provider is configured
+    firmware_notes: Option<FirmwareNotes>,
+}
+
+/// `sessions/{bus}/devices/{device}/info`
+async fn session_device_info(
+    State(state): State<AppState>,
+    Path((bus, device_id_hex)): Path<(BusRef, String)>,
+) -> Result<Json<DeviceInfo>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let device_id = session_hex(&device_id_hex)?;
+    let key = DeviceKey::from(FRCCanId::new(device_id));
+    let (dev_type, serial, firmware, bootloader) = {
+        let mut bus_sessions = state.bus_sessions.lock();
+        let bus = bus_state(&mut bus_sessions, bus_id)?;
+        let dev = bus
+            .devices
+            .get(&key)
+            .ok_or_else(|| ApiError::device_not_found(bus_id, &key.pretty_str()))?;
+        (
+            dev.dev_type(std::time::Instant::now()),
+            dev.serial_numer(),
+            dev.firmware_version(),
+            dev.bootloader(),
+        )
+    };
+
+    Ok(Json(DeviceInfo {
+        device: key.pretty_str(),
+        can_id: key.can_id(),
+        device_type: dev_type,
+        product: crate::firmware_notes::product_name(&key, serial),
+        serial: serial.map(|s| bus::device::serial_str(Some(s))),
+        firmware: firmware.map(|fw| bus::device::firmware_str(Some(fw))),
+        bootloader,
+        firmware_notes: state.firmware_metadata.for_device(&key, serial, firmware),
+    }))
+}
+
+/// `firmware/notes?product=canandgyro&version=2025.1.0`
+async fn firmware_notes_lookup(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<Option<FirmwareNotes>>, ApiError> {
+    let product = pull_key(&params, "product", |v| Some(v.to_ascii_lowercase()))?;
+    let version = pull_key(&params, "version", |v| v.parse::<Version>().ok())?;
+    Ok(Json(state.firmware_metadata.lookup(&product, version)))
+}
+
+/// `POST sessions/{bus}/settings/fetch?devices=7060183,7100183`
+///
+/// Starts reading back every setting of the listed devices, or of every known device outside the
+/// bootloader if `devices` is left out. Poll the returned job for results.
+async fn session_fetch_all_settings(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<FetchJob>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let mut bus_sessions = state.bus_sessions.lock();
+    let state = bus_state(&mut bus_sessions, bus_id)?;
+    let mut keys: Vec<DeviceKey> = match params.get("devices") {
+        Some(list) => list
+            .split(',')
+            .filter(|id| !id.is_empty())
+            .map(|id| session_hex(id).map(|id| DeviceKey::from(FRCCanId::new(id))))
+            .collect::<Result<_, _>>()?,
+        None => state
+            .devices
+            .iter()
+            .filter(|(_, dev)| !dev.bootloader())
+            .map(|(key, _)| *key)
+            .collect(),
+    };
+    keys.sort_by_key(|key| key.can_id());
+    keys.dedup();
+    let job = state.control.start_fetch(keys);
+    state
+        .control
+        .job(job)
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| ApiError::fetch_job_not_found(bus_id, job))
+}
+
+/// `sessions/{bus}/settings/fetch/{job}`
+async fn session_fetch_all_status(
+    State(state): State<AppState>,
+    Path((bus, job)): Path<(BusRef, u32)>,
+) -> Result<Json<FetchJob>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let mut bus_sessions = state.bus_sessions.lock();
+    let state = bus_state(&mut bus_sessions, bus_id)?;
+    state
+        .control
+        .job(job)
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| ApiError::fetch_job_not_found(bus_id, job))
+}
+
+/// `DELETE sessions/{bus}/settings/fetch/{job}`
+///
+/// Stops a bulk fetch, returning the settings fetched before it was cancelled.
+async fn session_fetch_all_cancel(
+    State(state): State<AppState>,
+    Path((bus, job)): Path<(BusRef, u32)>,
+) -> Result<Json<FetchJob>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let mut bus_sessions = state.bus_sessions.lock();
+    let state = bus_state(&mut bus_sessions, bus_id)?;
+    state
+        .control
+        .cancel_fetch(job)
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| ApiError::fetch_job_not_found(bus_id, job))
+}
+
+/// `sessions/{bus}/control/budget?fps=500`
+///
+/// Returns the bus's control-plane frame budget, after setting it if `fps` is given.
+async fn session_control_budget(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<u32>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let fps = if params.contains_key("fps") {
+        Some(pull_key(&params, "fps", |v| {
+            v.parse::<u32>()
+                .ok()
+                .filter(|fps| (1..=bus::control::MAX_BUDGET_FPS).contains(fps))
+        })?)
+    } else {
+        None
+    };
+    let mut bus_sessions = state.bus_sessions.lock();
+    let state = bus_state(&mut bus_sessions, bus_id)?;
+    if let Some(fps) = fps {
+        state.control.set_budget_fps(fps);
+    }
+    Ok(Json(state.control.budget_fps()))
+}
+
+/// `/mirror`
+async fn mirror_list(State(state): State<AppState>) -> Json<Vec<MirrorConfig>> {
+    Json(state.mirrors.list())
+}
+
+/// `/mirror/{bus}/start?addr=239.0.0.1:7245&ttl=1`
+async fn mirror_start(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<MirrorConfig>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let group = pull_key(&params, "addr", |v| v.parse().ok())?;
+    let mut config = MirrorConfig::new(bus_id, group);
+    if params.contains_key("ttl") {
+        config.ttl = pull_key(&params, "ttl", |v| v.parse().ok())?;
+    }
+    state.mirrors.start(&state.fifocore, config)?;
+    Ok(Json(config))
+}
+
+/// `/mirror/{bus}/stop`
+async fn mirror_stop(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+) -> Result<Json<bool>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    Ok(Json(state.mirrors.stop(bus_id)))
+}
+
+/// `/profiles`
+async fn profile_list(State(state): State<AppState>) -> Json<Vec<SettingsProfile>> {
+    Json(state.profiles.list())
+}
+
+/// `POST /profiles` with a [`SettingsProfile`]
+async fn profile_set(
+    State(state): State<AppState>,
+    Json(profile): Json<SettingsProfile>,
+) -> Result<Json<()>, ApiError> {
+    state.profiles.set(profile)?;
+    Ok(Json(()))
+}
+
+/// `/profiles/{product}/remove`
+async fn profile_remove(
+    State(state): State<AppState>,
+    Path(product): Path<ProfileProduct>,
+) -> Json<bool> {
+    Json(state.profiles.remove(product))
+}
+
+/// `/profiles/audit`
+async fn profile_audit(State(state): State<AppState>) -> Json<Vec<AuditEntry>> {
+    Json(state.profiles.audit())
+}
+
+/// `/settings/export?wait=500&timeout=5000`
+///
+/// Every Redux device's settings as a settings file, waiting `wait` ms for buses to enumerate and
+/// at most `timeout` ms in total.
+async fn settings_export(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let wait = if params.contains_key("wait") {
+        pull_key(&params, "wait", |v| v.parse::<u64>().ok())?
+    } else {
+        500
+    };
+    let timeout = if params.contains_key("timeout") {
+        pull_key(&params, "timeout", |v| v.parse::<u64>().ok())?
+    } else {
+        5000
+    };
+    let file = SettingsFile::export(
+        &state.fifocore,
+        &state.bus_sessions,
+        &state.profiles,
+        Duration::from_millis(wait),
+        Duration::from_millis(timeout),
+    )
+    .await;
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"redux-settings.json\"",
+        )],
+        Json(file),
+    ))
+}
+
+/// `POST /settings/import?dry_run=false`
+async fn settings_import(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, bool>>,
+    Json(file): Json<SettingsFile>,
+) -> Result<Json<Vec<ImportResult>>, ApiError> {
+    let dry_run = params.get("dry_run").copied().unwrap_or(false);
+    let migrations = state.firmware_metadata.migrations().table();
+    Ok(Json(file.import(
+        &mut state.bus_sessions.lock(),
+        &migrations,
+        dry_run,
+    )?))
+}
+
+/// `/settings/migrations`
+async fn settings_migrations(State(state): State<AppState>) -> Json<MigrationTable> {
+    Json(state.firmware_metadata.migrations().table())
+}
+
+/// `POST /settings/migrations` with a [`MigrationTable`], replacing the one in use
+async fn settings_migrations_set(
+    State(state): State<AppState>,
+    Json(table): Json<MigrationTable>,
+) -> Result<Json<usize>, ApiError> {
+    Ok(Json(state.firmware_metadata.migrations().set(table)?))
+}
+
+/// `/inventory?wait=500`
+async fn inventory_json(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<InventoryReport>, ApiError> {
+    Ok(Json(collect_inventory(&state, &params).await?))
+}
+
+/// `/inventory/table?wait=500`
+async fn inventory_table(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let report = collect_inventory(&state, &params).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "inline; filename=\"redux-inventory.txt\"",
+            ),
+        ],
+        report.to_table(),
+    ))
+}
+
+/// `/inventory/firmware?wait=500`
+///
+/// Every device's firmware against the newest bundled for its product, for updating outdated
+/// devices; see [`crate::firmware_index`].
+async fn inventory_firmware(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<FirmwareStatusReport>, ApiError> {
+    let report = collect_inventory(&state, &params).await?;
+    Ok(Json(
+        state
+            .firmware_metadata
+            .bundled()
+            .status(&report, &state.bus_sessions),
+    ))
+}
+
+/// `/firmware/index`
+async fn firmware_index(State(state): State<AppState>) -> Json<FirmwareIndexFile> {
+    Json(state.firmware_metadata.bundled().index())
+}
+
+/// `POST /firmware/index` with a [`FirmwareIndexFile`], replacing the one in use
+async fn firmware_index_set(
+    State(state): State<AppState>,
+    Json(index): Json<FirmwareIndexFile>,
+) -> Result<Json<usize>, ApiError> {
+    Ok(Json(state.firmware_metadata.bundled().set(index)?))
+}
+
+/// `/snapshot`
+async fn snapshot_json(State(state): State<AppState>) -> Json<MiddlewareSnapshot> {
+    Json(MiddlewareSnapshot::capture(&state.fifocore, &state.bus_sessions))
+}
+
+/// `/bulk/state`: buses, devices, cached settings, faults and stats in one response
+async fn bulk_state(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> axum::response::Response {
+    let bulk = BulkState::capture(&state.fifocore, &state.bus_sessions);
+    let etag = bulk.etag();
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| bulk::etag_matches(v, &etag));
+
+    let mut response = if not_modified {
+        axum::http::StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Json(bulk).into_response()
+    };
+    if let Ok(etag) = axum::http::HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    response
+}
+
+#[derive(Debug, serde::Serialize)]
+struct MemoryStatus {
+    limits: MemoryLimits,
+    drops: DropStats,
+}
+
+/// `/memory`
+async fn memory_status() -> Json<MemoryStatus> {
+    Json(MemoryStatus {
+        limits: fifocore::limits::memory_limits(),
+        drops: fifocore::limits::drop_stats(),
+    })
+}
+
+/// `/latency`
+async fn latency_report() -> Json<LatencyReport> {
+    Json(fifocore::latency::report())
+}
+
+/// `/latency/table`
+async fn latency_table() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        fifocore::latency::report().to_string(),
+    )
+}
+
+/// `/latency/start`
+async fn latency_start() -> Json<LatencyReport> {
+    fifocore::latency::set_enabled(true);
+    Json(fifocore::latency::report())
+}
+
+/// `/latency/stop`
+async fn latency_stop() -> Json<LatencyReport> {
+    fifocore::latency::set_enabled(false);
+    Json(fifocore::latency::report())
+}
+
+/// `/latency/reset`
+async fn latency_reset() -> Json<LatencyReport> {
+    fifocore::latency::reset();
+    Json(fifocore::latency::report())
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SessionDispatch {
+    bus_id: u16,
+    session: u32,
+    #[serde(flatten)]
+    wait: SessionWait,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct DispatchStatus {
+    totals: DispatchStats,
+    sessions: Vec<SessionDispatch>,
+    /// Writes coalesced by the slcan and RdxUSB backends
+    writes: WriteBatchStats,
+}
+
+/// `/dispatch`
+async fn dispatch_status(State(state): State<AppState>) -> Json<DispatchStatus> {
+    let mut sessions = Vec::new();
+    for bus_id in state.fifocore.buses() {
+        for session in state.fifocore.sessions(bus_id) {
+            // the session may close between listing and lookup
+            if let Ok(wait) = state.fifocore.session_wait(session) {
+                sessions.push(SessionDispatch {
+                    bus_id,
+                    session: session.ses_id(),
+                    wait,
+                });
+            }
+        }
+    }
+    sessions.sort_by_key(|ses| (ses.bus_id, ses.session));
+    Json(DispatchStatus {
+        totals: fifocore::dispatch::dispatch_stats(),
+        sessions,
+        writes: fifocore::write_batch::write_batch_stats(),
+    })
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SessionReport {
+    session: u32,
+    label: Option<String>,
+    #[serde(flatten)]
+    pause: PauseStatus,
+}
+
+/// `/buses/{bus}/sessions`
+async fn bus_sessions_list(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+) -> Result<Json<Vec<SessionReport>>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let mut sessions = Vec::new();
+    for session in state.fifocore.sessions(bus_id) {
+        // the session may close between listing and lookup
+        let (Ok(label), Ok(pause)) = (
+            state.fifocore.session_label(session),
+            state.fifocore.session_pause(session),
+        ) else {
+            continue;
+        };
+        sessions.push(SessionReport {
+            session: session.ses_id(),
+            label: label.as_deref().map(str::to_owned),
+            pause,
+        });
+    }
+    sessions.sort_by_key(|ses| ses.session);
+    Ok(Json(sessions))
+}
+
+/// `/buses/{bus}/sessions/{session}/pause?cap=<messages>`
+///
+/// Holds the session's messages, up to `cap` of them, until it's resumed; see
+/// [`fifocore::pause`].
+async fn bus_session_pause(
+    State(state): State<AppState>,
+    Path((bus, session)): Path<(BusRef, u32)>,
+    Query(params): Query<FxHashMap<String, u32>>,
+) -> Result<Json<PauseStatus>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let ses = ReduxFIFOSession::from_parts(session, bus_id);
+    let cap = params.get("cap").copied().unwrap_or(DEFAULT_PAUSE_CAP);
+    state
+        .fifocore
+        .pause_session(ses, cap)
+        .and_then(|_| state.fifocore.session_pause(ses))
+        .map(Json)
+        .map_err(|e| ApiError::fifocore(e, format!("Session {session} on bus {bus}")))
+}
+
+/// `/buses/{bus}/sessions/{session}/resume`
+async fn bus_session_resume(
+    State(state): State<AppState>,
+    Path((bus, session)): Path<(BusRef, u32)>,
+) -> Result<Json<PauseStatus>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let ses = ReduxFIFOSession::from_parts(session, bus_id);
+    state
+        .fifocore
+        .resume_session(ses)
+        .and_then(|_| state.fifocore.session_pause(ses))
+        .map(Json)
+        .map_err(|e| ApiError::fifocore(e, format!("Session {session} on bus {bus}")))
+}
+
+/// `/locks`
+async fn device_locks(State(state): State<AppState>) -> Json<Vec<DeviceLockReport>> {
+    Json(state.device_locks.report())
+}
+
+/// `/confirm/policy`
+async fn confirm_policy(State(state): State<AppState>) -> Json<ConfirmPolicy> {
+    Json(state.confirmations.policy())
+}
+
+/// `POST /confirm/policy?confirm=<operator token>` with a [`ConfirmPolicy`]
+async fn confirm_policy_set(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+    Json(policy): Json<ConfirmPolicy>,
+) -> Result<Json<()>, ApiError> {
+    let token = params.get("confirm").map(String::as_str);
+    state.bench.check_policy(&policy)?;
+    state.confirmations.set_policy(policy, token)?;
+    Ok(Json(()))
+}
+
+/// `/bench`: whether bench mode is on, and what its guards are doing
+async fn bench_status(State(state): State<AppState>) -> Json<BenchStatus> {
+    Json(state.bench.status(state.confirmations.policy()))
+}
+
+/// `POST /bench/heartbeat?buses=0,drive`: sends the bench heartbeat on the given buses, or on every
+/// open bus, for another auto-stop time
+async fn bench_heartbeat_arm(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<HeartbeatStatus>, ApiError> {
+    let bus_ids = resolve_buses(&state, &params)?;
+    Ok(Json(
+        state.bench.arm_heartbeat(&state.fifocore, &bus_ids).await?,
+    ))
+}
+
+/// `DELETE /bench/heartbeat`
+async fn bench_heartbeat_stop(State(state): State<AppState>) -> Json<HeartbeatStatus> {
+    Json(state.bench.stop_heartbeat())
+}
+
+/// `/diagnostics/console`: whether tokio-console is running, and where
+async fn console_status() -> Json<ConsoleStatus> {
+    Json(crate::diagnostics::console_status())
+}
+
+/// `POST /diagnostics/console?addr=127.0.0.1:6669&token=<diagnostics token>`
+async fn console_start(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<ConsoleStatus>, ApiError> {
+    let addr = match params.get("addr") {
+        Some(addr) => addr
+            .parse()
+            .map_err(|_| ApiError::invalid_param("addr", addr))?,
+        None => DEFAULT_CONSOLE_ADDR,
+    };
+    let token = params.get("token").map(String::as_str);
+    Ok(Json(state.diagnostics.start_console(
+        &state.fifocore.runtime(),
+        addr,
+        token,
+    )?))
+}
+
+/// `DELETE /diagnostics/console?token=<diagnostics token>`
+async fn console_stop(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<ConsoleStatus>, ApiError> {
+    let token = params.get("token").map(String::as_str);
+    Ok(Json(state.diagnostics.stop_console(token)?))
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(untagged)]
+enum GlobalDisableResult {
+    Sent(GlobalDisableReport),
+    NotSent { bus_id: u16, error: &'static str },
+}
+
+/// `POST /estop?buses=0,drive&window=250`: FRC global disable on each bus, or on every open bus if
+/// `buses` isn't given, confirmed by watching the buses for `window` ms afterwards
+async fn global_disable(
+    State(state): State<AppState>,
+    Query(params): Query<FxHashMap<String, String>>,
+) -> Result<Json<Vec<GlobalDisableResult>>, ApiError> {
+    let bus_ids = resolve_buses(&state, &params)?;
+    let window = if params.contains_key("window") {
+        Duration::from_millis(pull_key(&params, "window", |v| v.parse().ok())?)
+    } else {
+        estop::DEFAULT_CONFIRM_WINDOW
+    };
+
+    let reports = state.fifocore.global_disable(&bus_ids, window).await;
+    Ok(Json(
+        bus_ids
+            .into_iter()
+            .zip(reports)
+            .map(|(bus_id, report)| match report {
+                Ok(report) => GlobalDisableResult::Sent(report),
+                Err(e) => {
+                    log_error!("Global disable on bus {bus_id} failed: {e}");
+                    GlobalDisableResult::NotSent {
+                        bus_id,
+                        error: e.name(),
+                    }
+                }
+            })
+            .collect(),
+    ))
+}
+
+async fn collect_inventory(
+    state: &AppState,
+    params: &FxHashMap<String, String>,
+) -> Result<InventoryReport, ApiError> {
+    let wait = if params.contains_key("wait") {
+        pull_key(params, "wait", |v| v.parse::<u64>().ok())?
+    } else {
+        500
+    };
+    Ok(InventoryReport::collect(
+        &state.fifocore,
+        &state.bus_sessions,
+        &state.profiles,
+        Duration::from_millis(wait),
+    )
+    .await)
+}
+
+fn session_hex(device_id_hex: &str) -> Result<u32, ApiError> {
     u32::from_str_radix(&device_id_hex, 16).map_err(|_| {
         log_error!("Invalid session id {device_id_hex}");
-        StatusCode::BAD_REQUEST
+        ApiError::invalid_param("device_id", device_id_hex)
     })
 }
 
-fn pull_key<T: core::fmt::Debug, R, F: FnOnce(&T) -> Option<R>>(
-    params: &FxHashMap<String, T>,
+fn pull_key<'p, T: core::fmt::Debug, R, F: FnOnce(&'p T) -> Option<R>>(
+    params: &'p FxHashMap<String, T>,
     key: &str,
     mapper: F,
-) -> Result<R, StatusCode> {
-    mapper(params.get(key).ok_or_else(|| {
+) -> Result<R, ApiError> {
+    let value = params.get(key).ok_or_else(|| {
         log_error!("Missing param key {key}");
-        StatusCode::BAD_REQUEST
-    })?)
-    .ok_or_else(|| {
-        log_error!("Param key {key}: invalid value {:?}", params.get(key));
-        StatusCode::BAD_REQUEST
+        ApiError::missing_param(key)
+    })?;
+    mapper(value).ok_or_else(|| {
+        log_error!("Param key {key}: invalid value {value:?}");
+        ApiError::invalid_param(key, value)
     })
 }
 
+/// Resolves a `{bus}` path segment, which may be an id, an alias or bus params.
+pub(crate) fn resolve_bus(state: &AppState, bus: &BusRef) -> Result<u16, ApiError> {
+    state
+        .fifocore
+        .resolve_bus(bus)
+        .map_err(|e| ApiError::fifocore(e, format!("Bus {bus}")))
+}
+
+/// Holds the device for `operation` until the guard drops; see [`bus::device_lock`].
+async fn lock_device(
+    state: &AppState,
+    bus_id: u16,
+    device_id: u32,
+    operation: &'static str,
+) -> Result<DeviceGuard, ApiError> {
+    Ok(state
+        .device_locks
+        .acquire(bus_id, FRCCanId(device_id).into(), operation)
+        .await?)
+}
+
+/// Holds `operation` on the device back until it's confirmed, if the policy wants it confirmed;
+/// see [`crate::confirm`].
+pub(crate) async fn confirm_device(
+    state: &AppState,
+    operation: Operation,
+    bus_id: u16,
+    device_id: u32,
+    params: &FxHashMap<String, String>,
+) -> Result<(), ApiError> {
+    let target = format!("bus {bus_id} device {device_id:#010x}");
+    let token = params.get("confirm").map(String::as_str);
+    Ok(state
+        .confirmations
+        .check(&state.fifocore, operation, Some(bus_id), &target, token)
+        .await?)
+}
+
+/// Buses listed by id or alias in `?buses=`, or every open bus if it isn't given.
+fn resolve_buses(
+    state: &AppState,
+    params: &FxHashMap<String, String>,
+) -> Result<Vec<u16>, ApiError> {
+    match params.get("buses") {
+        Some(buses) => buses
+            .split(',')
+            .map(|bus| {
+                let bus = bus
+                    .parse()
+                    .map_or_else(|_| BusRef::Name(bus.to_string()), BusRef::Id);
+                resolve_bus(state, &bus)
+            })
+            .collect(),
+        None => Ok(state.fifocore.buses()),
+    }
+}
+
 fn bus_state<'a>(
     bus_sessions: &'a mut parking_lot::MutexGuard<'_, FxHashMap<u16, BusState>>,
     bus_id: u16,
-) -> Result<&'a mut BusState, StatusCode> {
+) -> Result<&'a mut BusState, ApiError> {
     bus_sessions.get_mut(&bus_id).ok_or_else(|| {
         log_error!("Bus {bus_id} not opened!");
-        StatusCode::BAD_REQUEST
+        ApiError::bus_session_not_open(bus_id)
     })
 }
 
@@ -356,11 +1710,60 @@ fn bus_state<'a>(
 //    (StatusCode::OK, "")
 //}
 
-pub async fn run_web_server(mut shutdown_pipe: watch::Receiver<bool>, fifocore: FIFOCore) {
+/// Address the REST server listens on.
+pub const DEFAULT_ADDR: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 7244);
+
+/// State the REST server shares with the rest of the process. Anything left at its default is the
+/// server's alone.
+#[derive(Default)]
+pub struct Services {
+    pub mirrors: Mirrors,
+    pub profiles: Profiles,
+    pub firmware_metadata: FirmwareMetadata,
+    pub bench: Bench,
+    pub diagnostics: Diagnostics,
+    pub bus_sessions: Arc<Mutex<FxHashMap<u16, BusState>>>,
+}
+
+pub async fn run_web_server(
+    shutdown_pipe: watch::Receiver<bool>,
+    fifocore: FIFOCore,
+    services: Services,
+) {
+    run_web_server_on(DEFAULT_ADDR, shutdown_pipe, fifocore, services).await
+}
+
+/// [`run_web_server`], listening on `addr` instead of [`DEFAULT_ADDR`].
+pub async fn run_web_server_on(
+    addr: SocketAddr,
+    mut shutdown_pipe: watch::Receiver<bool>,
+    fifocore: FIFOCore,
+    services: Services,
+) {
+    let Services {
+        mirrors,
+        profiles,
+        firmware_metadata,
+        bench,
+        diagnostics,
+        bus_sessions,
+    } = services;
     let state = AppState {
         fifocore,
         ota_clients: Default::default(),
-        bus_sessions: Default::default(),
+        usb_ota_clients: Default::default(),
+        bus_sessions,
+        device_locks: Default::default(),
+        confirmations: Confirmations::new(bench.confirm_policy()),
+        bench,
+        diagnostics,
+        calibrations: Default::default(),
+        mirrors,
+        profiles,
+        firmware_metadata,
+        #[cfg(feature = "simulation")]
+        simulator: Default::default(),
     };
 
     // CORS configuration
@@ -378,20 +1781,41 @@ pub async fn run_web_server(mut shutdown_pipe: watch::Receiver<bool>, fifocore:
             "Sec-Fetch-Site".parse().unwrap(),
             "Sec-Fetch-Dest".parse().unwrap(),
             "Accept".parse().unwrap(),
+            "If-None-Match".parse().unwrap(),
         ])
+        // so browser clients can revalidate /bulk/state
+        .expose_headers([header::ETAG])
         .allow_methods([
             axum::http::Method::GET,
             axum::http::Method::POST,
             axum::http::Method::OPTIONS,
         ]);
 
-    let mut app = Router::new()
+    let app = Router::new()
         .route("/version", get(version_handler))
         .route("/banner", get(banner_handler))
         .route("/", get(configurator_handler))
         .route("/ws/{bus}", axum::routing::any(websocket_handler))
+        .route("/console/{serial}", axum::routing::any(console_handler))
+        .route("/usb/devices", get(usb_devices_handler))
         .route("/buses", get(list_bus_handler))
         .route("/buses/open", get(open_bus_handler))
+        // What hardware a bus is on: USB adapter and firmware, serial port, network interface
+        .route("/buses/{bus}/info", get(bus_info_handler))
+        // Sessions open on a bus, and holding one's messages while it's paused
+        .route("/buses/{bus}/sessions", get(bus_sessions_list))
+        .route(
+            "/buses/{bus}/sessions/{session}/pause",
+            get(bus_session_pause),
+        )
+        .route(
+            "/buses/{bus}/sessions/{session}/resume",
+            get(bus_session_resume),
+        )
+        // Human-readable names usable wherever a bus id or params are
+        .route("/buses/aliases", get(list_aliases_handler))
+        .route("/buses/aliases/{alias}/set", get(set_alias_handler))
+        .route("/buses/aliases/{alias}/remove", get(remove_alias_handler))
         // Open a bus for session monitoring. You need to explicitly open one to do anything else.
         .route("/sessions/open/{bus}", get(session_open_bus))
         // Close a session monitoring session
@@ -402,6 +1826,14 @@ pub async fn run_web_server(mut shutdown_pipe: watch::Receiver<bool>, fifocore:
         .route("/sessions/{bus}/devices/list", get(session_list_devices))
         // Clear the currently detected devices list
         .route("/sessions/{bus}/devices/clear", get(session_clear_devices))
+        // Bus voltage from the PDP/PDH, and brownouts seen
+        .route("/sessions/{bus}/power", get(session_power))
+        // Settings devices changed on their own, like a Canandmag zeroed with its button
+        .route(
+            "/sessions/{bus}/setting_changes",
+            get(session_setting_changes),
+        )
+        .route("/setting_changes/events", get(setting_change_events))
         .route(
             "/sessions/{bus}/devices/{device_id}/arbitrate",
             get(session_arb_device),
@@ -422,35 +1854,222 @@ pub async fn run_web_server(mut shutdown_pipe: watch::Receiver<bool>, fifocore:
             "/sessions/{bus}/devices/{device_id}/set_name",
             get(session_set_name),
         )
+        .route(
+            "/sessions/{bus}/devices/{device_id}/composite/{name}",
+            get(session_fetch_composite),
+        )
+        .route(
+            "/sessions/{bus}/devices/{device_id}/composite/{name}/set",
+            get(session_set_composite),
+        )
+        // Periodic frame rates, by message index
+        .route(
+            "/sessions/{bus}/devices/{device_id}/frame_periods",
+            get(session_frame_periods),
+        )
+        .route(
+            "/sessions/{bus}/devices/{device_id}/frame_periods/{message_index}/set",
+            get(session_set_frame_period),
+        )
         .route(
             "/sessions/{bus}/devices/{device_id}/reboot",
             get(session_reboot),
         )
+        .route(
+            "/sessions/{bus}/devices/{device_id}/factory_reset",
+            post(session_factory_reset),
+        )
+        // Canandgyro calibration with a stillness check, and how it went
+        .route(
+            "/sessions/{bus}/devices/{device_id}/calibrate",
+            post(session_calibrate).delete(session_calibration_cancel),
+        )
+        .route(
+            "/sessions/{bus}/devices/{device_id}/calibrate/status",
+            get(session_calibration_status),
+        )
+        .route(
+            "/sessions/{bus}/devices/{device_id}/info",
+            get(session_device_info),
+        )
+        // Every setting of one device, and applying them back
+        .route(
+            "/sessions/{bus}/devices/{device_id}/snapshot",
+            get(session_device_snapshot),
+        )
+        .route(
+            "/sessions/{bus}/devices/{device_id}/restore",
+            post(session_device_restore),
+        )
+        // Decoded signals of one device, filtered and differentiated on the host
+        .route(
+            "/sessions/{bus}/devices/{device_id}/telemetry",
+            get(session_device_telemetry),
+        )
+        .route(
+            "/sessions/{bus}/settings/fetch",
+            post(session_fetch_all_settings),
+        )
+        .route(
+            "/sessions/{bus}/settings/fetch/{job}",
+            get(session_fetch_all_status).delete(session_fetch_all_cancel),
+        )
+        .route(
+            "/sessions/{bus}/control/budget",
+            get(session_control_budget),
+        )
         /*
         /sessions/{bus}/devices/{device_id}
          */
+        // Devices across every open session, merged by serial numer
+        .route("/devices", get(fleet_devices))
+        .route("/devices/{serial}/blink", get(fleet_blink))
+        .route("/devices/{serial}/reboot", get(fleet_reboot))
+        .route("/devices/{serial}/fetch_setting", get(fleet_fetch_setting))
+        // Mirror bus traffic to UDP multicast
+        .route("/mirror", get(mirror_list))
+        .route("/mirror/{bus}/start", get(mirror_start))
+        .route("/mirror/{bus}/stop", get(mirror_stop))
+        // Default settings for factory-fresh devices
+        .route("/profiles", get(profile_list).post(profile_set))
+        .route("/profiles/audit", get(profile_audit))
+        .route("/profiles/{product}/remove", get(profile_remove))
+        // Settings files in Alchemist's format, with devices matched by serial numer
+        .route("/settings/export", get(settings_export))
+        .route("/settings/import", post(settings_import))
+        .route(
+            "/settings/migrations",
+            get(settings_migrations).post(settings_migrations_set),
+        )
+        // Firmware inventory of every device on every bus
+        .route("/inventory", get(inventory_json))
+        .route("/inventory/table", get(inventory_table))
+        .route("/inventory/firmware", get(inventory_firmware))
+        .route("/snapshot", get(snapshot_json))
+        // Everything a client needs on connect, with an ETag for cheap reconnects
+        .route("/bulk/state", get(bulk_state))
+        // Memory limits and what has been dropped to stay within them
+        .route("/memory", get(memory_status))
+        // How long session readers leave received messages waiting
+        .route("/dispatch", get(dispatch_status))
+        // Histograms of receive-to-application latency, recorded while started
+        .route("/latency", get(latency_report))
+        .route("/latency/table", get(latency_table))
+        .route("/latency/start", get(latency_start))
+        .route("/latency/stop", get(latency_stop))
+        .route("/latency/reset", get(latency_reset))
+        // Which devices are held by a control operation, and how often clients have collided
+        .route("/locks", get(device_locks))
+        // Emergency stop: disable every actuator on the given buses and check that it stuck
+        .route("/estop", post(global_disable))
+        // Which destructive operations need an operator to confirm them
+        .route("/confirm/policy", get(confirm_policy).post(confirm_policy_set))
+        // Bench mode's guards, and the heartbeat it sends while clients keep arming it
+        .route("/bench", get(bench_status))
+        .route(
+            "/bench/heartbeat",
+            post(bench_heartbeat_arm).delete(bench_heartbeat_stop),
+        )
+        // tokio-console, for looking into the server's tasks without a special build
+        .route(
+            "/diagnostics/console",
+            get(console_status).post(console_start).delete(console_stop),
+        )
+        .route("/firmware/notes", get(firmware_notes_lookup))
+        .route(
+            "/firmware/index",
+            get(firmware_index).post(firmware_index_set),
+        )
         .route("/ota/{bus}/{id}/start", post(crate::ota::ota_start_handler))
         .route(
             "/ota/{bus}/{id}/status",
             get(crate::ota::ota_status_handler),
         )
+        .route(
+            "/ota/{bus}/{id}/progress",
+            get(crate::ota::ota_progress_handler),
+        )
         .route("/ota/{bus}/{id}/abort", get(crate::ota::ota_abort_handler))
-        .with_state(state.clone());
+        .route("/ota/{bus}/{id}", delete(crate::ota::ota_abort_handler))
+        // Flashing devices in DFU mode over USB, by USB serial number
+        .route("/ota/usb/{serial}/start", post(crate::ota::usb_ota_start_handler))
+        .route("/ota/usb/{serial}/status", get(crate::ota::usb_ota_status_handler))
+        .route(
+            "/ota/usb/{serial}/progress",
+            get(crate::ota::usb_ota_progress_handler),
+        )
+        .route("/ota/usb/{serial}/abort", get(crate::ota::usb_ota_abort_handler))
+        .route("/ota/usb/{serial}", delete(crate::ota::usb_ota_abort_handler));
+
+    // Virtual devices on sim: buses
+    #[cfg(feature = "simulation")]
+    let app = crate::sim::routes(app);
+
+    let mut app = app.with_state(state.clone());
     //.route("/*_", options(options_handler))
 
     app = app.layer(cors);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:7244")
-        .await
-        .expect("Failed to bind to address");
+    // local clients on Windows can also connect over a named pipe
+    #[cfg(windows)]
+    let pipe_server = tokio::spawn(crate::named_pipe::serve(app.clone(), shutdown_pipe.clone()));
 
-    log_info!("Starting CANLink server on 0.0.0.0:7244");
+    let watchdog = tokio::spawn(crate::watchdog::run_watchdog(
+        state.fifocore.clone(),
+        state.bus_sessions.clone(),
+    ));
 
-    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
-        shutdown_pipe.wait_for(|f| *f).await.ok();
-    });
+    // the server only exits on its own if something went wrong; bring it back up on the same
+    // state so open bus sessions, OTA jobs, and mirrors carry over.
+    let mut backoff = SERVER_RESTART_MIN_BACKOFF;
+    loop {
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log_error!("Failed to bind to {addr}: {e}; retrying in {backoff:?}");
+                if wait_or_shutdown(&mut shutdown_pipe, backoff).await {
+                    break;
+                }
+                backoff = (backoff * 2).min(SERVER_RESTART_MAX_BACKOFF);
+                continue;
+            }
+        };
 
-    if let Err(e) = server.await {
-        log_error!("Server error: {}", e);
+        log_info!("Starting CANLink server on {addr}");
+
+        let mut server_shutdown = shutdown_pipe.clone();
+        let server = axum::serve(listener, app.clone()).with_graceful_shutdown(async move {
+            server_shutdown.wait_for(|f| *f).await.ok();
+        });
+
+        let result = server.await;
+        if *shutdown_pipe.borrow() {
+            if let Err(e) = result {
+                log_error!("Server error: {}", e);
+            }
+            break;
+        }
+        match result {
+            Err(e) => log_error!("Server error: {e}; restarting in {backoff:?}"),
+            Ok(()) => log_error!("Server exited unexpectedly; restarting in {backoff:?}"),
+        }
+        if wait_or_shutdown(&mut shutdown_pipe, backoff).await {
+            break;
+        }
+        backoff = (backoff * 2).min(SERVER_RESTART_MAX_BACKOFF);
     }
+
+    watchdog.abort();
+    #[cfg(windows)]
+    let _ = pipe_server.await;
+}
+
+const SERVER_RESTART_MIN_BACKOFF: std::time::Duration = std::time::Duration::from_millis(500);
+const SERVER_RESTART_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Sleeps for `delay`, returning early with `true` if shutdown gets requested in the meantime.
+async fn wait_or_shutdown(shutdown_pipe: &mut watch::Receiver<bool>, delay: std::time::Duration) -> bool {
+    tokio::time::timeout(delay, shutdown_pipe.wait_for(|f| *f))
+        .await
+        .is_ok()
 }
@@ -0,0 +1,544 @@
+//! Periodic actions and alerts for benches that run unattended.
+//!
+//! A schedule is loaded from a JSON file:
+//!
+//! ```json
+//! {
+//!   "jobs": [
+//!     { "cron": "0 0 * * *", "action": "rotate_logs", "dir": "/var/log/reduxfifo" },
+//!     { "cron": "30 2 * * *", "action": "inventory", "dir": "/var/lib/reduxfifo/inventory" }
+//!   ],
+//!   "device_lost_webhooks": ["http://10.0.0.5:8080/alerts"]
+//! }
+//! ```
+//!
+//! `cron` takes the usual five fields (minute, hour, day of month, month, day of week) in local
+//! time. Each field is `*`, a number, a range `a-b`, a step `*/n` or `a-b/n`, or a comma-separated
+//! list of those. As in cron, a job whose day of month and day of week are both restricted runs
+//! when either matches.
+//!
+//! `rotate_logs` starts a new log file in `dir` for each bus in `buses`, or for every open bus if
+//! that's left out. Buses are given by id or alias, e.g. `"buses": [0, "drive"]`. A `filter`
+//! narrows down what the new logs keep, e.g. only Redux frames from the bus, one in ten of each id:
+//! `"filter": {"redux_only": true, "direction": "rx", "decimate": 10}` (see [`LogFilter`]). Without
+//! one, each bus keeps the filter its log already had. `"format": "mcap"` writes the new logs as
+//! MCAP for Foxglove or PlotJuggler instead of `.rdxlog`; likewise each bus keeps its format if
+//! that's left out. `inventory` writes a timestamped [`InventoryReport`] into `dir`.
+//!
+//! Each webhook gets a POST with the [`PresenceEvent`] as its JSON body whenever a device drops off
+//! a bus. To get emails, point it at a mail gateway. Only plain `http://` URLs are supported. While
+//! webhooks are configured, every open bus gets a device session so that losses are noticed.
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use chrono::{Datelike, Local, Timelike};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::{broadcast::error::RecvError, watch},
+};
+
+use crate::{
+    bus::{
+        self, BusState,
+        presence::{self, PresenceChange, PresenceEvent},
+    },
+    inventory::InventoryReport,
+    log::{log_error, log_info, log_warn},
+    profile::Profiles,
+};
+use fifocore::{
+    FIFOCore,
+    bus_alias::BusRef,
+    logger::{LogFilter, LogFormat},
+};
+
+/// How long a webhook may take to accept an event before it's given up on.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long inventory jobs wait on enumerate and firmware version responses.
+const INVENTORY_SETTLE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Schedule {
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+    #[serde(default)]
+    pub device_lost_webhooks: Vec<Webhook>,
+}
+
+impl Schedule {
+    pub fn load_file(path: &std::path::Path) -> Result<Self, ScheduleError> {
+        let data = std::fs::read(path).map_err(ScheduleError::Io)?;
+        serde_json::from_slice(&data).map_err(ScheduleError::Json)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty() && self.device_lost_webhooks.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Job {
+    pub cron: Cron,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    RotateLogs {
+        dir: PathBuf,
+        /// Buses to rotate; all open buses if empty
+        #[serde(default)]
+        buses: Vec<BusRef>,
+        /// Filter for the new logs; each bus keeps its current one if left out
+        #[serde(default)]
+        filter: Option<LogFilter>,
+        /// Format of the new logs; each bus keeps its current one if left out
+        #[serde(default)]
+        format: Option<LogFormat>,
+    },
+    Inventory {
+        dir: PathBuf,
+    },
+}
+
+/// A five-field cron expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(spec: &str) -> Result<Self, ScheduleError> {
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(ScheduleError::Cron(spec.to_string(), "expected 5 fields"));
+        };
+        let field = |field, min, max| {
+            parse_field(field, min, max).map_err(|e| ScheduleError::Cron(spec.to_string(), e))
+        };
+        let (days, any_day) = field(day, 1, 31)?;
+        let (mut weekdays, any_weekday) = field(weekday, 0, 7)?;
+        // 7 is another way to write Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: field(minute, 0, 59)?.0,
+            hours: field(hour, 0, 23)?.0,
+            days,
+            months: field(month, 1, 12)?.0,
+            weekdays,
+            any_day,
+            any_weekday,
+        })
+    }
+
+    pub fn matches(&self, t: &(impl Datelike + Timelike)) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = bit(self.days, t.day());
+        let weekday = bit(self.weekdays, t.weekday().num_days_from_sunday());
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        };
+        day_matches
+            && bit(self.minutes, t.minute())
+            && bit(self.hours, t.hour())
+            && bit(self.months, t.month())
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = ScheduleError;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        Self::parse(&spec)
+    }
+}
+
+/// Parses one cron field into a bitmask of allowed values, and whether it was a bare `*`.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<(u64, bool), &'static str> {
+    let number = |s: &str| match s.parse::<u32>() {
+        Ok(v) if (min..=max).contains(&v) => Ok(v),
+        _ => Err("value out of range"),
+    };
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err("invalid step"),
+            },
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `a/n` runs from a to the end of the range
+                None if step > 1 => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if start > end {
+            return Err("range runs backwards");
+        }
+        for v in (start..=end).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok((mask, field == "*"))
+}
+
+/// Where to POST events, from an `http://host[:port]/path` URL.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Webhook {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Webhook {
+    pub fn parse(url: &str) -> Result<Self, ScheduleError> {
+        let invalid = |reason| ScheduleError::Webhook(url.to_string(), reason);
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("only http:// URLs are supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid("invalid port"))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// POSTs `body` as JSON and returns the response status code.
+    async fn post(&self, body: &[u8]) -> std::io::Result<u16> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            self.port,
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body).await?;
+
+        // "HTTP/1.1 200"
+        let mut status_line = [0u8; 12];
+        stream.read_exact(&mut status_line).await?;
+        core::str::from_utf8(&status_line[9..])
+            .ok()
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| std::io::Error::other("malformed HTTP response"))
+    }
+}
+
+impl TryFrom<String> for Webhook {
+    type Error = ScheduleError;
+
+    fn try_from(url: String) -> Result<Self, Self::Error> {
+        Self::parse(&url)
+    }
+}
+
+impl core::fmt::Display for Webhook {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+#[derive(Debug)]
+pub enum ScheduleError {
+    Cron(String, &'static str),
+    Webhook(String, &'static str),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl core::fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ScheduleError::Cron(spec, reason) => write!(f, "invalid cron {spec:?}: {reason}"),
+            ScheduleError::Webhook(url, reason) => write!(f, "invalid webhook {url:?}: {reason}"),
+            ScheduleError::Io(e) => write!(f, "couldn't read schedule: {e}"),
+            ScheduleError::Json(e) => write!(f, "invalid schedule file: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for ScheduleError {}
+
+/// Runs `schedule` until `shutdown` flips.
+///
+/// Device sessions needed for inventories and loss alerts are opened in `bus_sessions`, so share
+/// it with the REST server to avoid a second session per bus.
+pub async fn run_schedule(
+    schedule: Schedule,
+    fifocore: FIFOCore,
+    bus_sessions: Arc<Mutex<FxHashMap<u16, BusState>>>,
+    profiles: Profiles,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut events = presence::subscribe();
+    let watch_buses = !schedule.device_lost_webhooks.is_empty();
+    let mut last_minute = None;
+    loop {
+        if watch_buses {
+            open_missing_buses(&fifocore, &bus_sessions, &profiles);
+        }
+
+        let now = Local::now();
+        let into_minute = Duration::new(now.second() as u64, now.nanosecond() % 1_000_000_000);
+        let next_minute = Duration::from_secs(60).saturating_sub(into_minute);
+        tokio::select! {
+            _ = shutdown.changed() => return,
+            _ = tokio::time::sleep(next_minute) => {
+                let now = Local::now();
+                // a wakeup a hair early would otherwise run the same minute twice
+                let minute = now.timestamp().div_euclid(60);
+                if last_minute == Some(minute) {
+                    continue;
+                }
+                last_minute = Some(minute);
+                for job in schedule.jobs.iter().filter(|job| job.cron.matches(&now)) {
+                    run_action(&job.action, &fifocore, &bus_sessions, &profiles).await;
+                }
+            }
+            event = events.recv() => match event {
+                Ok(event) if event.change == PresenceChange::Lost => {
+                    for webhook in schedule.device_lost_webhooks.iter() {
+                        tokio::spawn(notify(webhook.clone(), event.clone()));
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => {
+                    log_warn!("[schedule] Missed {n} presence events");
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+fn open_missing_buses(
+    fifocore: &FIFOCore,
+    bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+    profiles: &Profiles,
+) {
+    for bus_id in fifocore.buses() {
+        let guard = bus_sessions.lock();
+        if guard.contains_key(&bus_id) {
+            continue;
+        }
+        if let Err(e) = bus::open_bus_state(guard, bus_sessions, fifocore, bus_id, profiles) {
+            log_warn!("[schedule] Couldn't watch bus {bus_id}: {e}");
+        }
+    }
+}
+
+async fn run_action(
+    action: &Action,
+    fifocore: &FIFOCore,
+    bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+    profiles: &Profiles,
+) {
+    match action {
+        Action::RotateLogs {
+            dir,
+            buses,
+            filter,
+            format,
+        } => {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                log_error!(
+                    "[schedule] Couldn't create log folder {}: {e}",
+                    dir.display()
+                );
+                return;
+            }
+            let buses = if buses.is_empty() {
+                fifocore.buses()
+            } else {
+                buses
+                    .iter()
+                    .filter_map(|bus| match fifocore.resolve_bus(bus) {
+                        Ok(bus_id) => Some(bus_id),
+                        Err(e) => {
+                            log_error!("[schedule] Couldn't rotate log of bus {bus}: {e}");
+                            None
+                        }
+                    })
+                    .collect()
+            };
+            for bus_id in buses {
+                let filter = filter
+                    .clone()
+                    .unwrap_or_else(|| fifocore.log_filter(bus_id).unwrap_or_default());
+                let format = format
+                    .or_else(|| fifocore.log_format(bus_id))
+                    .unwrap_or_default();
+                let opened = fifocore.open_log_as(dir.clone(), bus_id, filter, format);
+                match opened {
+                    Ok(()) => log_info!("[schedule] Rotated log of bus {bus_id}"),
+                    Err(e) => log_error!("[schedule] Couldn't rotate log of bus {bus_id}: {e}"),
+                }
+            }
+        }
+        Action::Inventory { dir } => {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                log_error!(
+                    "[schedule] Couldn't create inventory folder {}: {e}",
+                    dir.display()
+                );
+                return;
+            }
+            let report =
+                InventoryReport::collect(fifocore, bus_sessions, profiles, INVENTORY_SETTLE).await;
+            let stem = dir.join(format!("inventory-{}", Local::now().format("%Y%m%d-%H%M")));
+            match report.write_files(&stem) {
+                Ok(()) => log_info!(
+                    "[schedule] Wrote inventory of {} device(s) to {}",
+                    report.devices.len(),
+                    stem.display()
+                ),
+                Err(e) => log_error!("[schedule] Couldn't write inventory: {e}"),
+            }
+        }
+    }
+}
+
+async fn notify(webhook: Webhook, event: PresenceEvent) {
+    let body = serde_json::to_vec(&event).unwrap_or_default();
+    match tokio::time::timeout(WEBHOOK_TIMEOUT, webhook.post(&body)).await {
+        Ok(Ok(status)) if (200..300).contains(&status) => {}
+        Ok(Ok(status)) => log_warn!("[schedule] Webhook {webhook} answered {status}"),
+        Ok(Err(e)) => log_warn!("[schedule] Webhook {webhook} failed: {e}"),
+        Err(_) => log_warn!("[schedule] Webhook {webhook} timed out"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cron() {
+        let nightly = Cron::parse("30 2 * * *").unwrap();
+        assert!(nightly.matches(&at(2026, 3, 14, 2, 30)));
+        assert!(!nightly.matches(&at(2026, 3, 14, 3, 30)));
+
+        // 2026-03-15 is a Sunday
+        let weekend = Cron::parse("*/15 8-17 * * 6,7").unwrap();
+        assert!(weekend.matches(&at(2026, 3, 15, 8, 45)));
+        assert!(!weekend.matches(&at(2026, 3, 16, 8, 45)));
+        assert!(!weekend.matches(&at(2026, 3, 15, 8, 50)));
+
+        // day of month or day of week
+        let first_or_monday = Cron::parse("0 0 1 * 1").unwrap();
+        assert!(first_or_monday.matches(&at(2026, 3, 1, 0, 0)));
+        assert!(first_or_monday.matches(&at(2026, 3, 16, 0, 0)));
+        assert!(!first_or_monday.matches(&at(2026, 3, 17, 0, 0)));
+
+        assert!(Cron::parse("0 0 * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("5-1 * * * *").is_err());
+    }
+
+    #[test]
+    fn test_rotate_buses() {
+        let action: Action = serde_json::from_str(
+            r#"{"action": "rotate_logs", "dir": "logs", "buses": [0, "drive"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            action,
+            Action::RotateLogs {
+                dir: "logs".into(),
+                buses: vec![BusRef::Id(0), BusRef::Name("drive".into())],
+                filter: None,
+                format: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_rotate_filter() {
+        let action: Action = serde_json::from_str(
+            r#"{"action": "rotate_logs", "dir": "logs",
+                "filter": {"ids": [{"id": 2, "mask": 65535}], "direction": "rx", "decimate": 10}}"#,
+        )
+        .unwrap();
+        let Action::RotateLogs {
+            filter: Some(filter),
+            ..
+        } = action
+        else {
+            panic!("no filter in {action:?}");
+        };
+        assert_eq!(filter.ids.len(), 1);
+        assert!(filter.ids[0].matches(0x0e0002));
+        assert!(!filter.ids[0].matches(0x0e0003));
+        assert!(!filter.redux_only);
+        assert_eq!(filter.direction, fifocore::logger::LogDirection::Rx);
+        assert_eq!(filter.decimate, 10);
+    }
+
+    #[test]
+    fn test_rotate_format() {
+        let action: Action =
+            serde_json::from_str(r#"{"action": "rotate_logs", "dir": "logs", "format": "mcap"}"#)
+                .unwrap();
+        let Action::RotateLogs { format, .. } = action else {
+            panic!("not a rotation: {action:?}");
+        };
+        assert_eq!(format, Some(LogFormat::Mcap));
+    }
+
+    #[test]
+    fn test_webhook_url() {
+        let hook = Webhook::parse("http://10.0.0.5:8080/alerts").unwrap();
+        assert_eq!(hook.to_string(), "http://10.0.0.5:8080/alerts");
+        assert_eq!(
+            Webhook::parse("http://bench").unwrap().to_string(),
+            "http://bench:80/"
+        );
+        assert!(Webhook::parse("https://bench/alerts").is_err());
+    }
+}
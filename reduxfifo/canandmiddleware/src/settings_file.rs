@@ -0,0 +1,590 @@
+//! Settings files: the configuration of every Redux device on a robot in one JSON document.
+//!
+//! A file has a `schema`/`version` header and one section per product, each listing devices by
+//! serial numer with their setting values keyed by the same CamelCase names Alchemist uses for its
+//! device settings (`VelocityFramePeriod`), so a file written here loads in Alchemist and the
+//! other way around. Values are raw 48-bit setting values, as they go over the bus.
+//!
+//! Importing sends each device's settings to whichever bus the device with that serial numer is
+//! reachable on, so a file exported on one robot can be loaded back after the wiring changed. CAN
+//! ids are left out of the file: they describe the robot, not the device.
+//!
+//! Devices that have been updated since the file was exported get their settings carried forward
+//! through the [migration table](crate::migration) first.
+
+use std::{collections::BTreeMap, path::Path, sync::Arc, time::Duration};
+
+use canandmessage::cananddevice;
+use fifocore::FIFOCore;
+use frc_can_id::FRCCanId;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use serial_numer::SerialNumer;
+
+use crate::{
+    bus::{
+        self, BusState,
+        control::FetchState,
+        device::{DeviceKey, firmware_str, serial_str},
+    },
+    firmware_notes::Version,
+    fleet,
+    log::{log_info, log_warn},
+    migration::{AppliedMigration, MigrationError, MigrationTable},
+    profile::{ProfileProduct, Profiles, normalize_name},
+};
+
+/// Value of `schema` in every settings file.
+pub const SCHEMA: &str = "redux-settings";
+/// Newest file version this build reads, and the one it writes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How often an export checks whether its setting fetches have finished.
+const EXPORT_POLL: Duration = Duration::from_millis(50);
+
+/// The settings of one device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceSettings {
+    /// Serial numer of the device, which import matches devices by
+    pub serial: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Firmware version at export time, which migrations start from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    /// Setting name to raw setting value, e.g. `{"VelocityFramePeriod": 10}`
+    pub settings: BTreeMap<String, u64>,
+}
+
+/// A versioned settings file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsFile {
+    pub schema: String,
+    pub version: u32,
+    /// Unix time the file was exported, in microseconds
+    #[serde(default)]
+    pub exported_us: i64,
+    /// Program and version that wrote the file, e.g. `reduxfifo 2025.0.0`
+    #[serde(default)]
+    pub generator: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub canandmag: Vec<DeviceSettings>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub canandgyro: Vec<DeviceSettings>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub canandcolor: Vec<DeviceSettings>,
+    /// Buses and devices that were left out of the export, and why. Ignored on import.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl Default for SettingsFile {
+    fn default() -> Self {
+        Self {
+            schema: SCHEMA.to_string(),
+            version: SCHEMA_VERSION,
+            exported_us: 0,
+            generator: concat!("reduxfifo ", env!("CARGO_PKG_VERSION")).to_string(),
+            canandmag: Vec::new(),
+            canandgyro: Vec::new(),
+            canandcolor: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// Whether a setting belongs in a settings file: it has to be writable, and the CAN id and name
+/// are kept out of the setting map (the name has its own field).
+fn exported(index: u8, writable: bool) -> bool {
+    let name_parts = bus::composite_setting("name").map_or(&[][..], |name| name.parts);
+    writable && index != cananddevice::types::Setting::CanId as u8 && !name_parts.contains(&index)
+}
+
+impl SettingsFile {
+    fn section(&self, product: ProfileProduct) -> &[DeviceSettings] {
+        match product {
+            ProfileProduct::Canandmag => &self.canandmag,
+            ProfileProduct::Canandgyro => &self.canandgyro,
+            ProfileProduct::Canandcolor => &self.canandcolor,
+        }
+    }
+
+    fn section_mut(&mut self, product: ProfileProduct) -> &mut Vec<DeviceSettings> {
+        match product {
+            ProfileProduct::Canandmag => &mut self.canandmag,
+            ProfileProduct::Canandgyro => &mut self.canandgyro,
+            ProfileProduct::Canandcolor => &mut self.canandcolor,
+        }
+    }
+
+    /// Every device in the file, with its product.
+    pub fn devices(&self) -> impl Iterator<Item = (ProfileProduct, &DeviceSettings)> {
+        [
+            ProfileProduct::Canandmag,
+            ProfileProduct::Canandgyro,
+            ProfileProduct::Canandcolor,
+        ]
+        .into_iter()
+        .flat_map(|product| self.section(product).iter().map(move |dev| (product, dev)))
+    }
+
+    /// Reads back the settings of every Redux device on every bus.
+    ///
+    /// Buses without a device session get one opened, as for an inventory, and get `settle` to
+    /// answer the enumerate. `timeout` bounds the whole export; devices that haven't reported all
+    /// their settings by then are left out and listed in `errors`.
+    pub async fn export(
+        fifocore: &FIFOCore,
+        bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+        profiles: &Profiles,
+        settle: Duration,
+        timeout: Duration,
+    ) -> Self {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut bus_ids = fifocore.buses();
+        bus_ids.sort_unstable();
+        let mut file = Self {
+            errors: bus::enumerate_buses(bus_sessions, fifocore, &bus_ids, profiles),
+            ..Default::default()
+        };
+        tokio::time::sleep(settle).await;
+
+        let mut jobs = Vec::new();
+        for &bus_id in bus_ids.iter() {
+            let mut guard = bus_sessions.lock();
+            let Some(state) = guard.get_mut(&bus_id) else {
+                continue;
+            };
+            let keys: Vec<DeviceKey> = state
+                .devices
+                .iter()
+                .filter(|(key, dev)| {
+                    ProfileProduct::for_device(key).is_some()
+                        && !dev.bootloader()
+                        && dev.serial_numer().is_some()
+                })
+                .map(|(key, _)| *key)
+                .collect();
+            if !keys.is_empty() {
+                jobs.push((bus_id, state.control.start_fetch(keys)));
+            }
+        }
+
+        loop {
+            let done = {
+                let guard = bus_sessions.lock();
+                jobs.iter().all(|&(bus_id, job)| {
+                    guard
+                        .get(&bus_id)
+                        .and_then(|state| state.control.job(job))
+                        .is_none_or(|job| job.done)
+                })
+            };
+            if done || tokio::time::Instant::now() >= deadline {
+                break;
+            }
+            tokio::time::sleep(EXPORT_POLL).await;
+        }
+
+        let guard = bus_sessions.lock();
+        for &(bus_id, job) in jobs.iter() {
+            let Some(state) = guard.get(&bus_id) else {
+                file.errors
+                    .push(format!("bus {bus_id}: session closed during export"));
+                continue;
+            };
+            let Some(job) = state.control.job(job) else {
+                continue;
+            };
+            for fetch in job.devices.iter() {
+                let key = DeviceKey::from(FRCCanId::new(fetch.can_id));
+                if fetch.state != FetchState::Done {
+                    let why = fetch
+                        .error
+                        .as_deref()
+                        .unwrap_or("timed out reading settings");
+                    file.errors
+                        .push(format!("bus {bus_id} {}: {why}", fetch.device));
+                    continue;
+                }
+                let (Some(product), Some(dev)) =
+                    (ProfileProduct::for_device(&key), state.devices.get(&key))
+                else {
+                    continue;
+                };
+                let settings = product
+                    .settings()
+                    .into_iter()
+                    .filter(|(index, _, writable, _)| exported(*index, *writable))
+                    .filter_map(|(index, name, _, _)| {
+                        let value = fetch.settings.get(&index)?;
+                        let mut raw = [0u8; 8];
+                        raw[..6].copy_from_slice(value);
+                        Some((name, u64::from_le_bytes(raw)))
+                    })
+                    .collect();
+                let name = bus::composite_setting("name").and_then(|name| {
+                    let value = name
+                        .join(|index| fetch.settings.get(&index).copied())
+                        .ok()?;
+                    value.as_str().map(str::to_string)
+                });
+                file.section_mut(product).push(DeviceSettings {
+                    serial: serial_str(dev.serial_numer()),
+                    name,
+                    firmware: dev.firmware_version().map(|fw| firmware_str(Some(fw))),
+                    settings,
+                });
+            }
+        }
+        drop(guard);
+
+        for product in [
+            ProfileProduct::Canandmag,
+            ProfileProduct::Canandgyro,
+            ProfileProduct::Canandcolor,
+        ] {
+            let section = file.section_mut(product);
+            section.sort_by(|a, b| a.serial.cmp(&b.serial));
+            // a device on several buses is exported once
+            section.dedup_by(|a, b| a.serial == b.serial);
+        }
+        file.exported_us = fifocore::timebase::now_us();
+        file
+    }
+
+    /// Checks the header, migrates each device's settings to the firmware `running` says it has,
+    /// and resolves every setting name, without touching any device.
+    fn resolve(
+        &self,
+        migrations: &MigrationTable,
+        running: impl Fn(SerialNumer) -> Option<Version>,
+    ) -> Result<Vec<ResolvedDevice>, SettingsFileError> {
+        if self.schema != SCHEMA {
+            return Err(SettingsFileError::WrongSchema(self.schema.clone()));
+        }
+        if self.version == 0 || self.version > SCHEMA_VERSION {
+            return Err(SettingsFileError::UnsupportedVersion(self.version));
+        }
+        self.devices()
+            .map(|(product, dev)| ResolvedDevice::resolve(product, dev, migrations, &running))
+            .collect()
+    }
+
+    /// Writes the settings in the file to the devices with matching serial numers.
+    ///
+    /// The whole file is checked before anything is written, so a typo in one setting name
+    /// doesn't leave half the robot configured. With `dry_run`, devices are looked up and
+    /// migrated but nothing is sent.
+    pub fn import(
+        &self,
+        bus_sessions: &mut FxHashMap<u16, BusState>,
+        migrations: &MigrationTable,
+        dry_run: bool,
+    ) -> Result<Vec<ImportResult>, SettingsFileError> {
+        let devices = self.resolve(migrations, |serial| running_firmware(bus_sessions, serial))?;
+        Ok(devices
+            .into_iter()
+            .map(|dev| {
+                let outcome = dev.apply(bus_sessions, dry_run);
+                match &outcome {
+                    ImportOutcome::Applied {
+                        settings,
+                        migrations,
+                        ..
+                    } => log_info!(
+                        "[settings] wrote {settings} setting(s) to {:?} {}{}",
+                        dev.product,
+                        dev.serial_str,
+                        match migrations.last() {
+                            Some(last) => format!(", migrated to {}", last.to),
+                            None => String::new(),
+                        }
+                    ),
+                    ImportOutcome::DryRun { .. } => {}
+                    outcome => {
+                        log_warn!("[settings] didn't import {}: {outcome:?}", dev.serial_str)
+                    }
+                }
+                ImportResult {
+                    product: dev.product,
+                    serial: dev.serial_str,
+                    outcome,
+                }
+            })
+            .collect())
+    }
+
+    pub fn load_file(path: &Path) -> Result<Self, SettingsFileError> {
+        let text = std::fs::read_to_string(path).map_err(SettingsFileError::Io)?;
+        serde_json::from_str(&text).map_err(SettingsFileError::Json)
+    }
+
+    pub fn write_file(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+}
+
+#[derive(Debug)]
+struct ResolvedDevice {
+    product: ProfileProduct,
+    serial: SerialNumer,
+    serial_str: String,
+    name: Option<String>,
+    /// (index, value) of each setting
+    settings: Vec<(u8, [u8; 6])>,
+    migrations: Vec<AppliedMigration>,
+}
+
+/// Firmware the device with `serial` last reported, on whichever bus it's found first.
+fn running_firmware(
+    bus_sessions: &FxHashMap<u16, BusState>,
+    serial: SerialNumer,
+) -> Option<Version> {
+    let address = fleet::addresses(bus_sessions, serial).into_iter().next()?;
+    let key = DeviceKey::from(FRCCanId::new(address.can_id));
+    let dev = bus_sessions.get(&address.bus_id)?.devices.get(&key)?;
+    dev.firmware_version().map(Version::from)
+}
+
+impl ResolvedDevice {
+    fn resolve(
+        product: ProfileProduct,
+        dev: &DeviceSettings,
+        migrations: &MigrationTable,
+        running: impl Fn(SerialNumer) -> Option<Version>,
+    ) -> Result<Self, SettingsFileError> {
+        let serial = SerialNumer::from_readable_str(&dev.serial, true)
+            .ok_or_else(|| SettingsFileError::InvalidSerial(dev.serial.clone()))?;
+        let mut saved = dev.settings.clone();
+        let saved_on = dev.firmware.as_deref().and_then(|fw| fw.parse().ok());
+        let applied = match saved_on.zip(running(serial)) {
+            Some((saved_on, running)) => migrations
+                .migrate(product, saved_on, running, &mut saved)
+                .map_err(|e| SettingsFileError::Migration(dev.serial.clone(), e))?,
+            None => Vec::new(),
+        };
+
+        let known = product.settings();
+        let mut settings = Vec::with_capacity(saved.len());
+        for (name, value) in saved.iter() {
+            let wanted = normalize_name(name);
+            let Some((index, _, writable, _)) = known
+                .iter()
+                .find(|(_, spec_name, _, _)| normalize_name(spec_name) == wanted)
+            else {
+                return Err(SettingsFileError::UnknownSetting(
+                    dev.serial.clone(),
+                    name.clone(),
+                ));
+            };
+            if !exported(*index, *writable) {
+                return Err(SettingsFileError::NotImportable(
+                    dev.serial.clone(),
+                    name.clone(),
+                ));
+            }
+            if *value >= 1 << 48 {
+                return Err(SettingsFileError::ValueTooLarge(
+                    dev.serial.clone(),
+                    name.clone(),
+                    *value,
+                ));
+            }
+            settings.push((*index, value.to_le_bytes()[..6].try_into().unwrap()));
+        }
+        Ok(Self {
+            product,
+            serial,
+            serial_str: dev.serial.clone(),
+            name: dev.name.clone(),
+            settings,
+            migrations: applied,
+        })
+    }
+
+    fn apply(&self, bus_sessions: &mut FxHashMap<u16, BusState>, dry_run: bool) -> ImportOutcome {
+        let Some(address) = fleet::addresses(bus_sessions, self.serial)
+            .into_iter()
+            .next()
+        else {
+            return ImportOutcome::NotFound;
+        };
+        let found = ProfileProduct::for_device(&DeviceKey::from(FRCCanId::new(address.can_id)));
+        if found != Some(self.product) {
+            return ImportOutcome::WrongProduct {
+                device: address.device,
+            };
+        }
+        if dry_run {
+            return ImportOutcome::DryRun {
+                bus_id: address.bus_id,
+                can_id: address.can_id,
+                settings: self.settings.len(),
+                migrations: self.migrations.clone(),
+            };
+        }
+        let name = self.name.as_ref().zip(bus::composite_setting("name"));
+        let routed = fleet::route(bus_sessions, self.serial, |state, can_id| {
+            for &(index, value) in self.settings.iter() {
+                state.send_set_setting(can_id, index, value)?;
+            }
+            if let Some((name, composite)) = name {
+                state.send_set_composite(can_id, composite, name.as_bytes())?;
+            }
+            Ok(())
+        });
+        match routed {
+            Ok(routed) => ImportOutcome::Applied {
+                bus_id: routed.bus_id,
+                can_id: routed.can_id,
+                settings: self.settings.len(),
+                migrations: self.migrations.clone(),
+            },
+            Err(e) => ImportOutcome::Failed {
+                error: e.to_string(),
+            },
+        }
+    }
+}
+
+/// What importing a settings file did to one device.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    pub product: ProfileProduct,
+    pub serial: String,
+    #[serde(flatten)]
+    pub outcome: ImportOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ImportOutcome {
+    /// Every setting in the file was sent to the device
+    Applied {
+        bus_id: u16,
+        can_id: u32,
+        settings: usize,
+        /// Migrations the settings went through first, oldest first
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        migrations: Vec<AppliedMigration>,
+    },
+    /// The device was found, and nothing was sent to it
+    DryRun {
+        bus_id: u16,
+        can_id: u32,
+        settings: usize,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        migrations: Vec<AppliedMigration>,
+    },
+    /// No bus session has seen a device with this serial numer
+    NotFound,
+    /// The serial numer belongs to a different product than the file section it's in
+    WrongProduct {
+        device: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum SettingsFileError {
+    WrongSchema(String),
+    UnsupportedVersion(u32),
+    InvalidSerial(String),
+    /// (serial, setting name)
+    UnknownSetting(String, String),
+    /// (serial, setting name)
+    NotImportable(String, String),
+    /// (serial, setting name, value)
+    ValueTooLarge(String, String, u64),
+    /// (serial, why its settings couldn't be carried to its firmware)
+    Migration(String, MigrationError),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl core::fmt::Display for SettingsFileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SettingsFileError::WrongSchema(schema) => {
+                write!(
+                    f,
+                    "not a settings file: schema is {schema:?}, not {SCHEMA:?}"
+                )
+            }
+            SettingsFileError::UnsupportedVersion(version) => write!(
+                f,
+                "settings file version {version} isn't supported (newest is {SCHEMA_VERSION})"
+            ),
+            SettingsFileError::InvalidSerial(serial) => {
+                write!(f, "{serial:?} is not a serial numer")
+            }
+            SettingsFileError::UnknownSetting(serial, name) => {
+                write!(f, "{serial}: no setting named {name:?}")
+            }
+            SettingsFileError::NotImportable(serial, name) => {
+                write!(f, "{serial}: setting {name:?} can't be imported")
+            }
+            SettingsFileError::ValueTooLarge(serial, name, value) => {
+                write!(
+                    f,
+                    "{serial}: value {value} for {name:?} doesn't fit in 48 bits"
+                )
+            }
+            SettingsFileError::Migration(serial, e) => write!(f, "{serial}: {e}"),
+            SettingsFileError::Io(e) => write!(f, "couldn't read settings file: {e}"),
+            SettingsFileError::Json(e) => write!(f, "invalid settings file: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for SettingsFileError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn file(settings: &str) -> SettingsFile {
+        serde_json::from_str(&format!(
+            r#"{{"schema": "redux-settings", "version": 1, "canandmag": [
+                {{"serial": "07-1-0001-001-0-0", "settings": {settings}}}
+            ]}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let devices = file(r#"{"VelocityFramePeriod": 10, "position_frame_period": 20}"#)
+            .resolve(&MigrationTable::default(), |_| None)
+            .unwrap();
+        assert_eq!(devices[0].product, ProfileProduct::Canandmag);
+        assert_eq!(devices[0].settings.len(), 2);
+
+        assert!(matches!(
+            file(r#"{"CanId": 3}"#).resolve(&MigrationTable::default(), |_| None),
+            Err(SettingsFileError::NotImportable(..))
+        ));
+        assert!(matches!(
+            file(r#"{"NoSuchSetting": 3}"#).resolve(&MigrationTable::default(), |_| None),
+            Err(SettingsFileError::UnknownSetting(..))
+        ));
+        let newer = SettingsFile {
+            version: SCHEMA_VERSION + 1,
+            ..file("{}")
+        };
+        assert!(matches!(
+            newer.resolve(&MigrationTable::default(), |_| None),
+            Err(SettingsFileError::UnsupportedVersion(_))
+        ));
+
+        let results = file(r#"{"VelocityFramePeriod": 10}"#)
+            .import(&mut FxHashMap::default(), &MigrationTable::default(), false)
+            .unwrap();
+        assert_eq!(results[0].outcome, ImportOutcome::NotFound);
+    }
+}
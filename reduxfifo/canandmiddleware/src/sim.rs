@@ -0,0 +1,805 @@
+//! Simulated devices for `sim:` buses.
+//!
+//! Virtual devices answer enumerates, setting reads and writes and send their periodic frames
+//! onto a `sim:` bus, where the regular bus session code picks them up like any real device. Their
+//! readings are driven through the REST server, so integration tests can script scenarios without
+//! hardware.
+//!
+//! Several devices can be created on the same id with distinct serials to stage a CAN id conflict.
+//! Like real firmware, conflicting devices go quiet apart from periodic `CAN_ID_ERROR` reports until
+//! the host arbitrates one of them, which then answers normally and can be moved to a free id.
+
+use std::{
+    collections::hash_map::Entry,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    response::Json,
+    routing::{get, post},
+};
+use canandmessage::{
+    CanandMessageWrapper, canandcolor, cananddevice, canandgyro, canandmag,
+    simulation::{SimCanandcolor, SimCanandgyro, SimCanandmag, SimSignalError},
+    traits::CanandDeviceMessage,
+};
+use fifocore::{FIFOCore, ReduxFIFOMessage, ReduxFIFOSessionConfig, Session, bus_alias::BusRef};
+use frc_can_id::FRCCanId;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serial_numer::{LifecycleFlag, ProductId, SerialNumer};
+use tokio::task::JoinHandle;
+
+use crate::{
+    log::{log_error, log_info},
+    problem::ApiError,
+    rest_server::{AppState, resolve_bus},
+};
+
+/// How often simulated devices are stepped.
+const SIM_PERIOD: Duration = Duration::from_millis(1);
+/// Status frame rate new devices start with, matching the real default.
+const DEFAULT_STATUS_RATE: Duration = Duration::from_millis(100);
+/// How often a device in an unarbitrated id conflict reports its serial.
+const CONFLICT_REPORT_PERIOD: Duration = Duration::from_millis(500);
+
+/// Products that can be simulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SimProduct {
+    Canandmag,
+    Canandcolor,
+    Canandgyro,
+}
+
+impl SimProduct {
+    fn dev_type(self) -> u8 {
+        match self {
+            SimProduct::Canandmag => canandmag::DEV_TYPE,
+            SimProduct::Canandcolor => canandcolor::DEV_TYPE,
+            SimProduct::Canandgyro => canandgyro::DEV_TYPE,
+        }
+    }
+
+    fn from_dev_type(dev_type: u8) -> Option<Self> {
+        [
+            SimProduct::Canandmag,
+            SimProduct::Canandcolor,
+            SimProduct::Canandgyro,
+        ]
+        .into_iter()
+        .find(|product| product.dev_type() == dev_type)
+    }
+
+    fn product_id(self) -> ProductId {
+        match self {
+            SimProduct::Canandmag => ProductId::Encoder,
+            SimProduct::Canandcolor => ProductId::Sandworm,
+            SimProduct::Canandgyro => ProductId::Gyro,
+        }
+    }
+}
+
+impl core::str::FromStr for SimProduct {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "canandmag" => Ok(SimProduct::Canandmag),
+            "canandcolor" => Ok(SimProduct::Canandcolor),
+            "canandgyro" => Ok(SimProduct::Canandgyro),
+            _ => Err(()),
+        }
+    }
+}
+
+enum SimModel {
+    Canandmag(Box<SimCanandmag>),
+    Canandcolor(Box<SimCanandcolor>),
+    Canandgyro(Box<SimCanandgyro>),
+}
+
+macro_rules! with_model {
+    ($model:expr, $sim:ident => $body:expr) => {
+        match $model {
+            SimModel::Canandmag($sim) => $body,
+            SimModel::Canandcolor($sim) => $body,
+            SimModel::Canandgyro($sim) => $body,
+        }
+    };
+}
+
+impl SimModel {
+    fn new(product: SimProduct, serial: SerialNumer, dev_id: u8) -> Self {
+        let serial: [u8; 6] = serial.into();
+        let mut model = match product {
+            SimProduct::Canandmag => SimModel::Canandmag(Default::default()),
+            SimProduct::Canandcolor => SimModel::Canandcolor(Default::default()),
+            SimProduct::Canandgyro => SimModel::Canandgyro(Default::default()),
+        };
+        with_model!(&mut model, sim => {
+            sim.settings.SerialNumber = serial;
+            sim.settings.CanId = dev_id;
+        });
+        let _ = model.set_rate("status", DEFAULT_STATUS_RATE);
+        model
+    }
+
+    fn set_signal(&mut self, name: &str, value: serde_json::Value) -> Result<(), SimSignalError> {
+        with_model!(self, sim => sim.set_signal(name, value))
+    }
+
+    fn set_rate(&mut self, message: &str, rate: Duration) -> Result<(), SimSignalError> {
+        with_model!(self, sim => sim.set_rate(message, rate))
+    }
+
+    /// The CAN id setting, which the host may have changed.
+    fn can_id_setting(&self) -> u8 {
+        with_model!(self, sim => sim.settings.CanId)
+    }
+
+    fn signals(&self) -> serde_json::Map<String, serde_json::Value> {
+        with_model!(self, sim => sim
+            .signals()
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect())
+    }
+
+    /// Feeds a frame the host sent to this device.
+    fn handle(&mut self, msg: &ReduxFIFOMessage) {
+        let frame = CanandMessageWrapper(*msg);
+        match self {
+            SimModel::Canandmag(sim) => {
+                if let Ok(msg) = TryInto::<canandmag::Message>::try_into(frame) {
+                    sim.handle_message(msg);
+                }
+            }
+            SimModel::Canandcolor(sim) => {
+                if let Ok(msg) = TryInto::<canandcolor::Message>::try_into(frame) {
+                    sim.handle_message(msg);
+                }
+            }
+            SimModel::Canandgyro(sim) => {
+                if let Ok(msg) = TryInto::<canandgyro::Message>::try_into(frame) {
+                    sim.handle_message(msg);
+                }
+            }
+        }
+    }
+
+    /// Steps the device, returning the frames it sends.
+    fn periodic(&mut self, can_id: u32) -> Vec<ReduxFIFOMessage> {
+        with_model!(self, sim => sim
+            .sim_periodic()
+            .into_iter()
+            .filter_map(|msg| wrap(&msg, can_id))
+            .collect())
+    }
+}
+
+fn wrap<M: CanandDeviceMessage>(msg: &M, can_id: u32) -> Option<ReduxFIFOMessage> {
+    match msg.try_into_wrapper::<ReduxFIFOMessage>(can_id) {
+        Ok(wrapper) => Some(wrapper.0),
+        Err(e) => {
+            log_error!("[sim] Could not serialize {msg:?}: {e}");
+            None
+        }
+    }
+}
+
+/// Fault to inject into a simulated device.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "fault", rename_all = "snake_case")]
+pub enum SimFault {
+    /// Stop sending anything, as if the device was unplugged
+    Offline,
+    /// Report these active and sticky fault flags in the status frame
+    Flags { faults: serde_json::Value },
+    /// Come back online and report the faults the device was created with
+    Clear,
+}
+
+struct SimDevice {
+    product: SimProduct,
+    dev_id: u8,
+    serial: SerialNumer,
+    model: SimModel,
+    offline: bool,
+    /// Picked out by the host in an id conflict, so it answers as if it were alone
+    arbitrated: bool,
+    last_conflict_report: Option<Instant>,
+    /// Fault signals as they were at creation, restored by [`SimFault::Clear`]
+    nominal_faults: Vec<(String, serde_json::Value)>,
+}
+
+impl SimDevice {
+    fn address(&self) -> (SimProduct, u8) {
+        (self.product, self.dev_id)
+    }
+
+    fn can_id(&self) -> u32 {
+        frc_can_id::build_frc_can_id(
+            self.product.dev_type(),
+            frc_can_id::REDUX_VENDOR_ID,
+            0,
+            self.dev_id,
+        )
+    }
+
+    fn info(&self, bus_id: u16) -> SimDeviceInfo {
+        let mut serial = [0u8; 17];
+        SimDeviceInfo {
+            bus_id,
+            product: self.product,
+            dev_id: self.dev_id,
+            serial: self.serial.to_readable_str(&mut serial).to_string(),
+            offline: self.offline,
+            arbitrated: self.arbitrated,
+            signals: self.model.signals(),
+        }
+    }
+
+    /// Whether the device talks normally, rather than only reporting an id conflict.
+    fn listening(&self, occupancy: &FxHashMap<(SimProduct, u8), usize>) -> bool {
+        !self.offline && (self.arbitrated || occupancy.get(&self.address()).is_none_or(|&n| n <= 1))
+    }
+
+    /// Feeds a frame the host sent to this device, following it to a new id if it was given one.
+    fn handle(&mut self, msg: &ReduxFIFOMessage) {
+        self.model.handle(msg);
+        let dev_id = self.model.can_id_setting();
+        if dev_id != self.dev_id && dev_id <= 0x3f {
+            log_info!(
+                "[sim] {:?}:{} moved to id {dev_id}",
+                self.product,
+                self.dev_id
+            );
+            self.dev_id = dev_id;
+            self.arbitrated = false;
+        }
+    }
+
+    fn conflict_report_due(&mut self, now: Instant) -> bool {
+        if self
+            .last_conflict_report
+            .is_some_and(|at| now - at < CONFLICT_REPORT_PERIOD)
+        {
+            return false;
+        }
+        self.last_conflict_report = Some(now);
+        true
+    }
+}
+
+/// A simulated device as reported by the REST server.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SimDeviceInfo {
+    pub bus_id: u16,
+    pub product: SimProduct,
+    pub dev_id: u8,
+    pub serial: String,
+    pub offline: bool,
+    /// Picked out by the host while sharing its id with other devices
+    pub arbitrated: bool,
+    pub signals: serde_json::Map<String, serde_json::Value>,
+}
+
+struct SimBus {
+    /// In creation order, which decides which device an address refers to when several share it
+    devices: Vec<SimDevice>,
+    task: JoinHandle<()>,
+}
+
+impl SimBus {
+    /// How many online devices are on each address.
+    fn occupancy(&self) -> FxHashMap<(SimProduct, u8), usize> {
+        let mut occupancy = FxHashMap::default();
+        for dev in self.devices.iter().filter(|dev| !dev.offline) {
+            *occupancy.entry(dev.address()).or_default() += 1;
+        }
+        occupancy
+    }
+
+    fn device_mut(&mut self, product: SimProduct, dev_id: u8) -> Option<&mut SimDevice> {
+        self.devices
+            .iter_mut()
+            .find(|dev| dev.address() == (product, dev_id))
+    }
+}
+
+impl Drop for SimBus {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Simulated devices on every `sim:` bus, shared between the REST server and its handlers.
+#[derive(Clone, Default)]
+pub struct Simulator {
+    buses: Arc<Mutex<FxHashMap<u16, SimBus>>>,
+}
+
+impl core::fmt::Debug for Simulator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Simulator")
+            .field("buses", &self.buses.lock().keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Simulator {
+    /// Adds a simulated device to a `sim:` bus.
+    ///
+    /// If `serial` isn't given, one is made up from the product and device id. With `conflict`, the
+    /// device may join others already on the same id, as long as its serial differs.
+    pub fn create(
+        &self,
+        fifocore: &FIFOCore,
+        bus_id: u16,
+        product: SimProduct,
+        dev_id: u8,
+        serial: Option<SerialNumer>,
+        conflict: bool,
+    ) -> Result<SimDeviceInfo, SimError> {
+        if dev_id > 0x3f {
+            return Err(SimError::InvalidDeviceId(dev_id));
+        }
+        let is_sim_bus = fifocore.with_buses(|buses| {
+            buses
+                .get(&bus_id)
+                .map(|bus| bus.params().starts_with("sim:"))
+        });
+        match is_sim_bus {
+            None => return Err(SimError::FIFOCore(fifocore::error::Error::InvalidBus)),
+            Some(false) => return Err(SimError::NotSimBus(bus_id)),
+            Some(true) => {}
+        }
+
+        let mut buses = self.buses.lock();
+        let bus = match buses.entry(bus_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut config = ReduxFIFOSessionConfig::new(0x0e0000, 0xff0000);
+                config.echo_tx = true;
+                let session = fifocore
+                    .open_managed_session(bus_id, 256, config)
+                    .map_err(SimError::FIFOCore)?;
+                let task = fifocore.runtime().spawn(sim_bus_task(
+                    session,
+                    fifocore.clone(),
+                    self.buses.clone(),
+                ));
+                entry.insert(SimBus {
+                    devices: Vec::new(),
+                    task,
+                })
+            }
+        };
+        let sharing: Vec<SerialNumer> = bus
+            .devices
+            .iter()
+            .filter(|dev| dev.address() == (product, dev_id))
+            .map(|dev| dev.serial)
+            .collect();
+        if !sharing.is_empty() && !conflict {
+            return Err(SimError::AlreadyExists(product, dev_id));
+        }
+
+        let serial = serial.unwrap_or_else(|| {
+            SerialNumer::build(
+                product.product_id(),
+                0,
+                bus_id,
+                ((sharing.len() as u16) << 6) | dev_id as u16,
+                LifecycleFlag::Mule,
+            )
+        });
+        if sharing.contains(&serial) {
+            return Err(SimError::SerialInUse(product, dev_id));
+        }
+        let model = SimModel::new(product, serial, dev_id);
+        let signals = model.signals();
+        let device = SimDevice {
+            product,
+            dev_id,
+            serial,
+            model,
+            offline: false,
+            arbitrated: false,
+            last_conflict_report: None,
+            nominal_faults: ["status.faults", "status.sticky_faults"]
+                .into_iter()
+                .filter_map(|name| Some((name.to_string(), signals.get(name)?.clone())))
+                .collect(),
+        };
+        let info = device.info(bus_id);
+        bus.devices.push(device);
+        if sharing.is_empty() {
+            log_info!("[sim] Created {product:?}:{dev_id} on bus {bus_id}");
+        } else {
+            log_info!(
+                "[sim] Created {product:?}:{dev_id} on bus {bus_id}, conflicting with {} other(s)",
+                sharing.len()
+            );
+        }
+        Ok(info)
+    }
+
+    /// Removes a simulated device, the oldest one if several share the id. Returns false if there
+    /// wasn't one.
+    pub fn destroy(&self, bus_id: u16, product: SimProduct, dev_id: u8) -> bool {
+        let mut buses = self.buses.lock();
+        let Some(bus) = buses.get_mut(&bus_id) else {
+            return false;
+        };
+        let removed = match bus
+            .devices
+            .iter()
+            .position(|dev| dev.address() == (product, dev_id))
+        {
+            Some(index) => {
+                bus.devices.remove(index);
+                true
+            }
+            None => false,
+        };
+        if bus.devices.is_empty() {
+            buses.remove(&bus_id);
+        }
+        removed
+    }
+
+    /// Every simulated device on a bus.
+    pub fn list(&self, bus_id: u16) -> Vec<SimDeviceInfo> {
+        let buses = self.buses.lock();
+        let mut devices: Vec<SimDeviceInfo> = buses
+            .get(&bus_id)
+            .map(|bus| bus.devices.iter().map(|dev| dev.info(bus_id)).collect())
+            .unwrap_or_default();
+        devices.sort_by_key(|dev| (dev.dev_id, dev.product as u8));
+        devices
+    }
+
+    /// Sets simulated signals, keyed by `message_name.signal_name`.
+    pub fn set_signals(
+        &self,
+        bus_id: u16,
+        product: SimProduct,
+        dev_id: u8,
+        signals: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<SimDeviceInfo, SimError> {
+        self.with_device(bus_id, product, dev_id, |dev| {
+            for (name, value) in signals {
+                dev.model
+                    .set_signal(&name, value)
+                    .map_err(|e| SimError::Signal(name.clone(), e))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Sets how often messages are sent, in milliseconds. 0 stops a message.
+    pub fn set_rates(
+        &self,
+        bus_id: u16,
+        product: SimProduct,
+        dev_id: u8,
+        rates: FxHashMap<String, u64>,
+    ) -> Result<SimDeviceInfo, SimError> {
+        self.with_device(bus_id, product, dev_id, |dev| {
+            for (message, rate_ms) in rates {
+                dev.model
+                    .set_rate(&message, Duration::from_millis(rate_ms))
+                    .map_err(|e| SimError::Signal(message.clone(), e))?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn inject_fault(
+        &self,
+        bus_id: u16,
+        product: SimProduct,
+        dev_id: u8,
+        fault: SimFault,
+    ) -> Result<SimDeviceInfo, SimError> {
+        self.with_device(bus_id, product, dev_id, |dev| {
+            match fault {
+                SimFault::Offline => dev.offline = true,
+                SimFault::Flags { faults } => {
+                    for (name, _) in dev.nominal_faults.iter() {
+                        dev.model
+                            .set_signal(name, faults.clone())
+                            .map_err(|e| SimError::Signal(name.clone(), e))?;
+                    }
+                }
+                SimFault::Clear => {
+                    dev.offline = false;
+                    for (name, value) in dev.nominal_faults.iter() {
+                        dev.model
+                            .set_signal(name, value.clone())
+                            .map_err(|e| SimError::Signal(name.clone(), e))?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Runs `f` on a device, the oldest one if several share the id.
+    fn with_device(
+        &self,
+        bus_id: u16,
+        product: SimProduct,
+        dev_id: u8,
+        f: impl FnOnce(&mut SimDevice) -> Result<(), SimError>,
+    ) -> Result<SimDeviceInfo, SimError> {
+        let mut buses = self.buses.lock();
+        let dev = buses
+            .get_mut(&bus_id)
+            .and_then(|bus| bus.device_mut(product, dev_id))
+            .ok_or(SimError::NoSuchDevice(product, dev_id))?;
+        f(dev)?;
+        Ok(dev.info(bus_id))
+    }
+}
+
+#[derive(Debug)]
+pub enum SimError {
+    NotSimBus(u16),
+    InvalidDeviceId(u8),
+    AlreadyExists(SimProduct, u8),
+    SerialInUse(SimProduct, u8),
+    NoSuchDevice(SimProduct, u8),
+    Signal(String, SimSignalError),
+    FIFOCore(fifocore::error::Error),
+}
+
+impl core::fmt::Display for SimError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SimError::NotSimBus(bus_id) => write!(f, "bus {bus_id} is not a sim: bus"),
+            SimError::InvalidDeviceId(dev_id) => write!(f, "device id {dev_id} is out of range"),
+            SimError::AlreadyExists(product, dev_id) => {
+                write!(f, "{product:?}:{dev_id} already exists")
+            }
+            SimError::SerialInUse(product, dev_id) => {
+                write!(f, "another {product:?}:{dev_id} already has that serial")
+            }
+            SimError::NoSuchDevice(product, dev_id) => {
+                write!(f, "no simulated {product:?}:{dev_id}")
+            }
+            SimError::Signal(name, e) => write!(f, "{name}: {e}"),
+            SimError::FIFOCore(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl core::error::Error for SimError {}
+
+async fn sim_bus_task(
+    session: Session,
+    fifocore: FIFOCore,
+    buses: Arc<Mutex<FxHashMap<u16, SimBus>>>,
+) {
+    let bus_id = session.session().bus_id();
+    let mut read_buf = session.read_buffer(256);
+    let mut interval = tokio::time::interval(SIM_PERIOD);
+    loop {
+        interval.tick().await;
+        if let Err(e) = session.read_barrier(&mut read_buf) {
+            log_error!("[sim] Read session on bus {bus_id} failed: {e}");
+            return;
+        }
+
+        let mut outbound = Vec::new();
+        {
+            let mut buses = buses.lock();
+            let Some(bus) = buses.get_mut(&bus_id) else {
+                return;
+            };
+            for msg in read_buf.iter().filter(|msg| msg.tx()) {
+                let occupancy = bus.occupancy();
+                if msg.id() == frc_can_id::REDUX_BROADCAST_ENUMERATE {
+                    for dev in bus.devices.iter().filter(|dev| dev.listening(&occupancy)) {
+                        outbound.extend(wrap(
+                            &cananddevice::Message::Enumerate {
+                                serial: dev.serial.into(),
+                                is_bootloader: false,
+                                reserved: 0,
+                            },
+                            dev.can_id(),
+                        ));
+                    }
+                    continue;
+                }
+                let id = FRCCanId::new(msg.id());
+                let Some(product) = SimProduct::from_dev_type(id.device_type_code()) else {
+                    continue;
+                };
+                let address = (product, id.device_number());
+                if let Ok(cananddevice::Message::CanIdArbitrate { addr_value }) =
+                    TryInto::<cananddevice::Message>::try_into(CanandMessageWrapper(*msg))
+                {
+                    for dev in bus
+                        .devices
+                        .iter_mut()
+                        .filter(|dev| dev.address() == address)
+                    {
+                        dev.arbitrated = dev.serial.into_msg_padded() == addr_value;
+                    }
+                    continue;
+                }
+                for dev in bus
+                    .devices
+                    .iter_mut()
+                    .filter(|dev| dev.address() == address && dev.listening(&occupancy))
+                {
+                    dev.handle(msg);
+                }
+            }
+
+            let now = Instant::now();
+            let occupancy = bus.occupancy();
+            for dev in bus.devices.iter_mut() {
+                let frames = dev.model.periodic(dev.can_id());
+                if occupancy.get(&dev.address()).is_none_or(|&n| n <= 1) {
+                    // nothing left to arbitrate against
+                    dev.arbitrated = false;
+                }
+                if dev.listening(&occupancy) {
+                    outbound.extend(frames);
+                } else if !dev.offline && dev.conflict_report_due(now) {
+                    outbound.extend(wrap(
+                        &cananddevice::Message::CanIdError {
+                            addr_value: dev.serial.into_msg_padded(),
+                        },
+                        dev.can_id(),
+                    ));
+                }
+            }
+        }
+
+        for mut msg in outbound {
+            msg.bus_id = bus_id;
+            msg.flags |= ReduxFIFOMessage::FLAG_SIM;
+            if let Err(e) = fifocore.write_single(&msg) {
+                log_error!("[sim] Write to bus {bus_id} failed: {e}");
+            }
+        }
+    }
+}
+
+// REST handlers
+
+pub(crate) fn routes(router: Router<AppState>) -> Router<AppState> {
+    router
+        .route(
+            "/sim/{bus}/devices",
+            get(sim_list_handler).post(sim_create_handler),
+        )
+        .route(
+            "/sim/{bus}/devices/{product}/{id}/destroy",
+            get(sim_destroy_handler),
+        )
+        .route(
+            "/sim/{bus}/devices/{product}/{id}/signals",
+            post(sim_signals_handler),
+        )
+        .route(
+            "/sim/{bus}/devices/{product}/{id}/rates",
+            post(sim_rates_handler),
+        )
+        .route(
+            "/sim/{bus}/devices/{product}/{id}/fault",
+            post(sim_fault_handler),
+        )
+}
+
+/// Body of `POST /sim/{bus}/devices`
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct CreateDevice {
+    product: SimProduct,
+    id: u8,
+    /// Readable serial, e.g. `0x1-0-0-0-0-0`. Made up if left out.
+    serial: Option<String>,
+    /// Put the device on an id that's already taken, to stage a CAN id conflict
+    #[serde(default)]
+    conflict: bool,
+}
+
+fn parse_product(product: &str) -> Result<SimProduct, ApiError> {
+    product
+        .parse()
+        .map_err(|_| ApiError::invalid_param("product", product))
+}
+
+/// `GET /sim/{bus}/devices`
+pub(crate) async fn sim_list_handler(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+) -> Result<Json<Vec<SimDeviceInfo>>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    Ok(Json(state.simulator.list(bus_id)))
+}
+
+/// `POST /sim/{bus}/devices`
+pub(crate) async fn sim_create_handler(
+    State(state): State<AppState>,
+    Path(bus): Path<BusRef>,
+    Json(body): Json<CreateDevice>,
+) -> Result<Json<SimDeviceInfo>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let serial = body
+        .serial
+        .map(|s| {
+            SerialNumer::from_readable_str(&s, false)
+                .ok_or_else(|| ApiError::invalid_param("serial", s))
+        })
+        .transpose()?;
+    Ok(Json(state.simulator.create(
+        &state.fifocore,
+        bus_id,
+        body.product,
+        body.id,
+        serial,
+        body.conflict,
+    )?))
+}
+
+/// `GET /sim/{bus}/devices/{product}/{id}/destroy`
+pub(crate) async fn sim_destroy_handler(
+    State(state): State<AppState>,
+    Path((bus, product, dev_id)): Path<(BusRef, String, u8)>,
+) -> Result<Json<bool>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let product = parse_product(&product)?;
+    Ok(Json(state.simulator.destroy(bus_id, product, dev_id)))
+}
+
+/// `POST /sim/{bus}/devices/{product}/{id}/signals` with `{"status.faults": 0, ...}`
+pub(crate) async fn sim_signals_handler(
+    State(state): State<AppState>,
+    Path((bus, product, dev_id)): Path<(BusRef, String, u8)>,
+    Json(signals): Json<serde_json::Map<String, serde_json::Value>>,
+) -> Result<Json<SimDeviceInfo>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let product = parse_product(&product)?;
+    Ok(Json(
+        state
+            .simulator
+            .set_signals(bus_id, product, dev_id, signals)?,
+    ))
+}
+
+/// `POST /sim/{bus}/devices/{product}/{id}/rates` with `{"status": 100, ...}` in milliseconds
+pub(crate) async fn sim_rates_handler(
+    State(state): State<AppState>,
+    Path((bus, product, dev_id)): Path<(BusRef, String, u8)>,
+    Json(rates): Json<FxHashMap<String, u64>>,
+) -> Result<Json<SimDeviceInfo>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let product = parse_product(&product)?;
+    Ok(Json(
+        state.simulator.set_rates(bus_id, product, dev_id, rates)?,
+    ))
+}
+
+/// `POST /sim/{bus}/devices/{product}/{id}/fault` with `{"fault": "offline"}`
+pub(crate) async fn sim_fault_handler(
+    State(state): State<AppState>,
+    Path((bus, product, dev_id)): Path<(BusRef, String, u8)>,
+    Json(fault): Json<SimFault>,
+) -> Result<Json<SimDeviceInfo>, ApiError> {
+    let bus_id = resolve_bus(&state, &bus)?;
+    let product = parse_product(&product)?;
+    Ok(Json(
+        state
+            .simulator
+            .inject_fault(bus_id, product, dev_id, fault)?,
+    ))
+}
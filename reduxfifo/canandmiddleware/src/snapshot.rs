@@ -0,0 +1,151 @@
+//! Point-in-time snapshots of every device session.
+//!
+//! The per-bus REST endpoints each take the session lock on their own, so stitching several of them
+//! together under load can mix up state from before and after a bus task poll. A snapshot copies
+//! everything while holding the lock once, and only serializes after it's been released.
+
+use std::{collections::BTreeMap, sync::Arc, time::Instant};
+
+use fifocore::FIFOCore;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::bus::{
+    BusState,
+    device::{DeviceType, firmware_str, serial_str},
+};
+
+/// One device's cached state.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceSnapshot {
+    /// Device type and CAN id, e.g. `Encoder:3`
+    pub device: String,
+    pub can_id: u32,
+    pub device_type: DeviceType,
+    pub serial: Option<String>,
+    pub firmware: Option<String>,
+    pub bootloader: bool,
+    /// Rebooted by us and not back yet
+    pub rebooting: bool,
+    /// Milliseconds since the device last sent a frame
+    pub last_active_ms: Option<u64>,
+    /// Times the device was announced present/lost this session
+    pub arrivals: u32,
+    pub departures: u32,
+    /// Fault bits from the most recent status frame
+    pub faults: Option<u8>,
+    pub sticky_faults: Option<u8>,
+    /// Cached setting values by setting index
+    pub settings: BTreeMap<u8, [u8; 6]>,
+}
+
+/// Statistics about a bus session.
+#[derive(Debug, Clone, Serialize)]
+pub struct BusStats {
+    pub device_count: usize,
+    pub devices_in_conflict: usize,
+    pub session_age_ms: u64,
+    pub task_running: bool,
+    /// Milliseconds since the bus task last serviced the bus
+    pub heartbeat_age_ms: u64,
+}
+
+/// One bus session's state.
+#[derive(Debug, Clone, Serialize)]
+pub struct BusSnapshot {
+    pub bus_id: u16,
+    /// Bus open params, e.g. `halcan` or `rdxusb:0`
+    pub bus: String,
+    pub devices: Vec<DeviceSnapshot>,
+    pub stats: BusStats,
+}
+
+/// Every device session, as of a single instant.
+#[derive(Debug, Clone, Serialize)]
+pub struct MiddlewareSnapshot {
+    /// When the snapshot was taken, in microseconds on the same timebase as message timestamps
+    pub taken_us: i64,
+    pub buses: Vec<BusSnapshot>,
+}
+
+impl MiddlewareSnapshot {
+    /// Copies the state of all of `bus_sessions` in one pass over its lock.
+    pub fn capture(
+        fifocore: &FIFOCore,
+        bus_sessions: &Arc<Mutex<FxHashMap<u16, BusState>>>,
+    ) -> Self {
+        // bus params don't change while a bus is open, so these needn't be under the same lock
+        let params: FxHashMap<u16, String> = fifocore.with_buses(|buses| {
+            buses
+                .iter()
+                .map(|(&id, ent)| (id, ent.params().to_string()))
+                .collect()
+        });
+
+        let guard = bus_sessions.lock();
+        let now = Instant::now();
+        let taken_us = fifocore::timebase::now_us();
+        let mut buses: Vec<BusSnapshot> = guard
+            .iter()
+            .map(|(&bus_id, state)| BusSnapshot::capture(state, params.get(&bus_id), now))
+            .collect();
+        drop(guard);
+
+        buses.sort_by_key(|bus| bus.bus_id);
+        Self { taken_us, buses }
+    }
+}
+
+impl BusSnapshot {
+    fn capture(state: &BusState, params: Option<&String>, now: Instant) -> Self {
+        let mut devices: Vec<DeviceSnapshot> = state
+            .devices
+            .iter()
+            .map(|(key, dev)| {
+                let (arrivals, departures) = state.presence.counts(key);
+                DeviceSnapshot {
+                    device: key.pretty_str(),
+                    can_id: key.can_id(),
+                    device_type: dev.dev_type(now),
+                    serial: dev.serial_numer().map(|s| serial_str(Some(s))),
+                    firmware: dev.firmware_version().map(|fw| firmware_str(Some(fw))),
+                    bootloader: dev.bootloader(),
+                    rebooting: dev.rebooting(),
+                    last_active_ms: dev
+                        .last_active()
+                        .map(|ts| now.saturating_duration_since(ts).as_millis() as u64),
+                    arrivals,
+                    departures,
+                    faults: dev.faults().map(|(active, _)| active),
+                    sticky_faults: dev.faults().map(|(_, sticky)| sticky),
+                    settings: dev
+                        .setting_cache()
+                        .iter()
+                        .map(|(&index, value)| (index, *value))
+                        .collect(),
+                }
+            })
+            .collect();
+        devices.sort_by_key(|dev| dev.can_id);
+
+        Self {
+            bus_id: state.bus_id,
+            bus: params.cloned().unwrap_or_default(),
+            stats: BusStats {
+                device_count: devices.len(),
+                devices_in_conflict: state
+                    .devices
+                    .values()
+                    .filter(|dev| dev.in_conflict())
+                    .count(),
+                session_age_ms: now
+                    .saturating_duration_since(state.presence.opened())
+                    .as_millis() as u64,
+                task_running: !state.task.is_finished(),
+                heartbeat_age_ms: state.heartbeat.age().as_millis() as u64,
+            },
+            devices,
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! A device's telemetry, decoded and processed on the host.
+//!
+//! Frames from the device are decoded into the same state Alchemist keeps for it, whose numeric
+//! fields are the signals a client can ask for, named `Message_signal` like
+//! `VelocityOutput_velocity`. Values are raw, as the device sends them. Each chosen signal goes
+//! through its own [`SignalProcessor`], so every dashboard gets the same smoothing, rate of change
+//! and windowed extremes instead of each implementing its own.
+
+use canandmessage::{
+    CanandMessageWrapper, alchemist, canandcolor, canandgyro, canandmag, traits::MessageIndexId,
+};
+use fifocore::ReduxFIFOMessage;
+use frc_can_id::FRCCanId;
+use rdxsignal::{ProcessedSample, SignalConfig, SignalProcessor};
+use serde::Serialize;
+
+use crate::bus::device::{DeviceKey, ReduxDeviceType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TelemetryError {
+    /// The device isn't a product we decode telemetry of
+    UnsupportedDevice(String),
+    NoSignals,
+    /// The device has no numeric signal by that name
+    UnknownSignal(String),
+}
+
+impl core::fmt::Display for TelemetryError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TelemetryError::UnsupportedDevice(device) => {
+                write!(f, "No telemetry is decoded for {device}")
+            }
+            TelemetryError::NoSignals => write!(f, "No signals were asked for"),
+            TelemetryError::UnknownSignal(signal) => write!(f, "No signal named {signal}"),
+        }
+    }
+}
+
+impl std::error::Error for TelemetryError {}
+
+/// What a device last reported, as Alchemist keeps it.
+enum DeviceState {
+    Canandmag(Box<alchemist::Canandmag>),
+    Canandgyro(Box<alchemist::Canandgyro>),
+    Canandcolor(Box<alchemist::Canandcolor>),
+}
+
+impl DeviceState {
+    fn for_device(key: DeviceKey) -> Option<Self> {
+        match key.dev_type {
+            ReduxDeviceType::Encoder => Some(Self::Canandmag(Default::default())),
+            ReduxDeviceType::Gyroscope => Some(Self::Canandgyro(Default::default())),
+            ReduxDeviceType::ColorDistanceSensor => Some(Self::Canandcolor(Default::default())),
+            _ => None,
+        }
+    }
+
+    /// Decodes `msg` into the state, returning the name of its message.
+    fn process(&mut self, msg: &ReduxFIFOMessage) -> Option<String> {
+        let id = FRCCanId(msg.id());
+        let frame = CanandMessageWrapper(*msg);
+        match self {
+            DeviceState::Canandmag(dev) => {
+                let (index, _) = canandmag::MessageIndex::from_frc_can_id(&id)?;
+                dev.process(frame.try_into().ok()?);
+                Some(format!("{index:?}"))
+            }
+            DeviceState::Canandgyro(dev) => {
+                let (index, _) = canandgyro::MessageIndex::from_frc_can_id(&id)?;
+                dev.process(frame.try_into().ok()?);
+                Some(format!("{index:?}"))
+            }
+            DeviceState::Canandcolor(dev) => {
+                let (index, _) = canandcolor::MessageIndex::from_frc_can_id(&id)?;
+                dev.process(frame.try_into().ok()?);
+                Some(format!("{index:?}"))
+            }
+        }
+    }
+
+    fn signals(&self) -> serde_json::Value {
+        match self {
+            DeviceState::Canandmag(dev) => serde_json::to_value(dev),
+            DeviceState::Canandgyro(dev) => serde_json::to_value(dev),
+            DeviceState::Canandcolor(dev) => serde_json::to_value(dev),
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// One processed sample of a signal.
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetrySample {
+    pub signal: String,
+    #[serde(flatten)]
+    pub sample: ProcessedSample,
+}
+
+struct Signal {
+    name: String,
+    /// The message carrying it, i.e. its name up to the `_`
+    message: String,
+    processor: SignalProcessor,
+}
+
+/// Chosen signals of one device, decoded from its frames.
+pub struct Telemetry {
+    device: DeviceState,
+    signals: Vec<Signal>,
+}
+
+impl Telemetry {
+    /// Processes each of `signals` of the device at `key` with `config`.
+    pub fn new(
+        key: DeviceKey,
+        signals: &[&str],
+        config: SignalConfig,
+    ) -> Result<Self, TelemetryError> {
+        let device = DeviceState::for_device(key)
+            .ok_or_else(|| TelemetryError::UnsupportedDevice(key.pretty_str()))?;
+        if signals.is_empty() {
+            return Err(TelemetryError::NoSignals);
+        }
+        let known = device.signals();
+        let signals = signals
+            .iter()
+            .map(|&name| match (known.get(name), name.split_once('_')) {
+                (Some(value), Some((message, _))) if value.is_number() => Ok(Signal {
+                    name: name.to_string(),
+                    message: message.to_string(),
+                    processor: SignalProcessor::new(config),
+                }),
+                _ => Err(TelemetryError::UnknownSignal(name.to_string())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { device, signals })
+    }
+
+    /// Takes in a frame from the device, returning a sample of each chosen signal it carried.
+    pub fn ingest(&mut self, msg: &ReduxFIFOMessage) -> Vec<TelemetrySample> {
+        let Some(message) = self.device.process(msg) else {
+            return Vec::new();
+        };
+        let t = msg.timestamp as f64 / 1e6;
+        let values = self.device.signals();
+        self.signals
+            .iter_mut()
+            .filter(|signal| signal.message == message)
+            .filter_map(|signal| {
+                let value = values.get(&signal.name)?.as_f64()?;
+                Some(TelemetrySample {
+                    signal: signal.name.clone(),
+                    sample: signal.processor.push(t, value),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_velocity() {
+        let key = DeviceKey {
+            dev_type: ReduxDeviceType::Encoder,
+            dev_id: 3,
+        };
+        assert_eq!(
+            Telemetry::new(key, &["VelocityOutput_nope"], Default::default()).err(),
+            Some(TelemetryError::UnknownSignal("VelocityOutput_nope".into()))
+        );
+        let mut telemetry = Telemetry::new(
+            key,
+            &["VelocityOutput_velocity"],
+            SignalConfig {
+                derivative: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let velocity = |ticks: i32, timestamp: u64| {
+            ReduxFIFOMessage::builder()
+                .id(canandmag::MessageIndex::VelocityOutput.frc_can_id(3).0)
+                .data(&ticks.to_le_bytes()[..3])
+                .timestamp(timestamp)
+                .build()
+        };
+
+        let samples = telemetry.ingest(&velocity(100, 1_000_000));
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].sample.value, 100.0);
+        let samples = telemetry.ingest(&velocity(200, 1_500_000));
+        assert_eq!(samples[0].sample.derivative, Some(200.0));
+        // other messages from the device carry nothing asked for
+        let msg = ReduxFIFOMessage::builder()
+            .id(canandmag::MessageIndex::Status.frc_can_id(3).0)
+            .data(&[0; 8])
+            .build();
+        assert!(telemetry.ingest(&msg).is_empty());
+    }
+}
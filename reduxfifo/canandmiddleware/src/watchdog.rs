@@ -0,0 +1,133 @@
+//! Supervision of the middleware's long-running tasks.
+//!
+//! Bus session tasks beat a [`Heartbeat`] every time they get through a read. The watchdog
+//! checks those every second; a task that exited or stopped beating gets logged and replaced by a
+//! fresh one that picks up the same [`BusState`], so the device list keeps updating instead of
+//! freezing on whatever it last saw. Bus logs whose writer died are reopened as well.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fifocore::FIFOCore;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    bus::{self, BusState},
+    log::{log_error, log_warn},
+};
+
+/// How often the watchdog looks at its tasks.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// A bus task that hasn't beaten in this long is considered stalled. They normally beat every 5 ms.
+const STALL_TIMEOUT: Duration = Duration::from_secs(3);
+/// Minimum time between two restarts of the same bus task, so a bus that can't be read at all
+/// doesn't get restarted in a tight loop.
+const RESTART_HOLDOFF: Duration = Duration::from_secs(5);
+/// How often dead bus loggers are looked for.
+const LOGGER_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Progress marker a supervised task updates whenever it gets work done.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    last: Arc<Mutex<Instant>>,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self {
+            last: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn beat(&self) {
+        *self.last.lock() = Instant::now();
+    }
+
+    /// Time since the last beat.
+    pub fn age(&self) -> Duration {
+        self.last.lock().elapsed()
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Default)]
+struct RestartRecord {
+    count: u32,
+    last: Option<Instant>,
+}
+
+/// Watches the bus session tasks in `bus_sessions` and the bus loggers until aborted.
+pub async fn run_watchdog(
+    fifocore: FIFOCore,
+    bus_sessions: Arc<Mutex<FxHashMap<u16, BusState>>>,
+) {
+    let mut restarts: FxHashMap<u16, RestartRecord> = FxHashMap::default();
+    let mut last_logger_check = Instant::now();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = Instant::now();
+
+        if now.duration_since(last_logger_check) >= LOGGER_CHECK_INTERVAL {
+            last_logger_check = now;
+            fifocore.restart_dead_loggers();
+        }
+
+        // a bus task stuck while holding the lock would stall us too, so don't wait forever on it
+        let Some(mut guard) = bus_sessions.try_lock_for(STALL_TIMEOUT) else {
+            log_error!(
+                "[watchdog] Bus session table has been locked for over {STALL_TIMEOUT:?}; a task is likely deadlocked"
+            );
+            continue;
+        };
+
+        let open_buses = fifocore.buses();
+        let mut closed = Vec::new();
+        for (&bus_id, state) in guard.iter_mut() {
+            if !open_buses.contains(&bus_id) {
+                closed.push(bus_id);
+                continue;
+            }
+
+            let dead = state.task.is_finished();
+            let age = state.heartbeat.age();
+            if !dead && age < STALL_TIMEOUT {
+                continue;
+            }
+
+            let record = restarts.entry(bus_id).or_default();
+            if record
+                .last
+                .is_some_and(|last| now.duration_since(last) < RESTART_HOLDOFF)
+            {
+                continue;
+            }
+            record.count += 1;
+            record.last = Some(now);
+            log_warn!(
+                "[watchdog] Bus {bus_id} session task {} (last heartbeat {:.1}s ago, {} known devices, restart #{}), restarting",
+                if dead { "exited" } else { "stalled" },
+                age.as_secs_f32(),
+                state.devices.len(),
+                record.count
+            );
+            if let Err(e) = bus::restart_bus_task(state, &bus_sessions) {
+                log_error!("[watchdog] Couldn't restart bus {bus_id} session task: {e}");
+            }
+        }
+
+        for bus_id in closed {
+            log_warn!("[watchdog] Bus {bus_id} was closed; dropping its device session");
+            guard.remove(&bus_id);
+            restarts.remove(&bus_id);
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use std::time::Duration;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use axum::extract::ws::{Message, WebSocket};
 use futures::{
@@ -6,72 +9,260 @@ use futures::{
     stream::{SplitSink, SplitStream},
 };
 
-use crate::log::log_error;
-use fifocore::{FIFOCore, ReduxFIFOMessage, ReduxFIFOSessionConfig};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tokio::sync::mpsc;
+
+use crate::log::{log_error, log_warn};
+use fifocore::{
+    FIFOCore, ReduxFIFOMessage, ReduxFIFOSessionConfig, backends::rdxusb::Console,
+    limits::DropKind, trace_tag::TraceTag,
+};
+
+/// What to do when a client reads slower than its bus delivers.
+///
+/// Negotiated with `?overflow=drop|decimate&queue=N` on the websocket URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Drop messages that don't fit in the send queue
+    #[default]
+    Drop,
+    /// Thin out each message id while the queue is backed up, dropping only if that isn't enough
+    Decimate,
+}
+
+impl core::str::FromStr for OverflowPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(Self::Drop),
+            "decimate" => Ok(Self::Decimate),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Send queue settings for one websocket client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackpressureConfig {
+    pub policy: OverflowPolicy,
+    /// Messages that may wait to be sent before they start getting dropped
+    pub queue_len: usize,
+}
+
+impl BackpressureConfig {
+    pub const MAX_QUEUE_LEN: usize = 65536;
+}
+
+impl Default for BackpressureConfig {
+    fn default() -> Self {
+        Self {
+            policy: OverflowPolicy::Drop,
+            queue_len: 4096,
+        }
+    }
+}
 
-pub async fn handle_socket(socket: WebSocket, fifocore: FIFOCore, bus_id: u16) {
+/// Largest decimation factor [`OverflowPolicy::Decimate`] will go to.
+const MAX_DECIMATION: u16 = 64;
+/// Overflow notices go out at most this often.
+const NOTICE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Counts shared between the bus reader and the socket writer of one client.
+#[derive(Debug)]
+struct Backlog {
+    dropped: u32,
+    skipped: u32,
+    decimation: u16,
+}
+
+pub async fn handle_socket(
+    socket: WebSocket,
+    fifocore: FIFOCore,
+    bus_id: u16,
+    backpressure: BackpressureConfig,
+    echo_tx: bool,
+    tag: TraceTag,
+) {
     let (sender, receiver) = socket.split();
 
-    let config = ReduxFIFOSessionConfig::new(0x0e0000, 0xff0000);
+    let mut config = ReduxFIFOSessionConfig::new(0x0e0000, 0xff0000);
+    config.echo_tx = echo_tx;
 
-    let rx = tokio::task::spawn(websocket_tx(sender, fifocore.clone(), bus_id, config));
-    let tx = tokio::task::spawn(websocket_rx(receiver, fifocore.clone(), bus_id));
+    let rx = tokio::task::spawn(websocket_tx(
+        sender,
+        fifocore.clone(),
+        bus_id,
+        config,
+        backpressure,
+        tag,
+    ));
+    let tx = tokio::task::spawn(websocket_rx(receiver, fifocore.clone(), bus_id, tag));
 
     let _ = futures::future::join(rx, tx).await;
 }
 
 pub async fn websocket_tx(
-    mut ws_tx: SplitSink<WebSocket, Message>,
+    ws_tx: SplitSink<WebSocket, Message>,
     fifocore: FIFOCore,
     bus_id: u16,
     config: ReduxFIFOSessionConfig,
+    mut backpressure: BackpressureConfig,
+    tag: TraceTag,
 ) {
     let session = match fifocore.open_managed_session(bus_id, 256, config) {
         Ok(session) => session,
         Err(e) => {
             log_error!("[ReduxCore] Failed to open websocket session: {e}");
+            let mut ws_tx = ws_tx;
             let _ = ws_tx.close().await.ok();
             return;
         }
     };
-    let mut read_buf = session.read_buffer(256);
+    // so the bus listing shows who's connected; the writes themselves are tagged in websocket_rx
+    if let Some(label) = tag.label() {
+        let _ = session.set_label(&label);
+    }
 
+    // The bus is read on its own so a slow socket can't stall reads and let the session buffer
+    // silently overwrite itself; anything lost is lost here, where it gets counted.
+    // the send queue is per-session memory too
+    let limits = fifocore::limits::memory_limits();
+    if limits.max_session_messages != 0 {
+        backpressure.queue_len = backpressure
+            .queue_len
+            .min(limits.max_session_messages as usize);
+    }
+    let (queue_tx, queue_rx) = mpsc::channel(backpressure.queue_len);
+    let backlog = Arc::new(Mutex::new(Backlog {
+        dropped: 0,
+        skipped: 0,
+        decimation: 1,
+    }));
+    let writer = tokio::task::spawn(websocket_send(ws_tx, queue_rx, backlog.clone(), bus_id));
+
+    let mut read_buf = session.read_buffer(256);
+    let mut per_id_counts: FxHashMap<u32, u16> = FxHashMap::default();
     let mut interval = tokio::time::interval(Duration::from_millis(5));
     loop {
         interval.tick().await;
+        if writer.is_finished() {
+            // session gets dropped on close
+            return;
+        }
         if let Err(e) = session.read_barrier(&mut read_buf) {
             log_error!("[ReduxCore] Read session failed: {e}");
-            let _ = ws_tx.close().await;
+            writer.abort();
             return;
         }
-        let mut errored = None;
+
+        let mut backlog = backlog.lock();
+        if backpressure.policy == OverflowPolicy::Decimate {
+            let queued = backpressure.queue_len - queue_tx.capacity();
+            if queued > backpressure.queue_len * 3 / 4 {
+                backlog.decimation = (backlog.decimation * 2).min(MAX_DECIMATION);
+            } else if queued < backpressure.queue_len / 4 {
+                backlog.decimation = (backlog.decimation / 2).max(1);
+            }
+        }
 
         for msg in read_buf.iter() {
-            let rx_msg = rdxcanlink_protocol::CANLinkRxMessage {
-                message_id: msg.message_id,
-                bus_id: msg.bus_id,
-                flags: msg.flags as u16,
-                timestamp: msg.timestamp,
-                data: msg.data,
-                data_size: msg.data_size as usize,
-            };
-            let outbound = Message::binary::<Vec<u8>>(rx_msg.into());
-            if let Err(e) = ws_tx.feed(outbound).await {
-                errored = Some(e);
-                break;
+            if backlog.decimation > 1 {
+                let count = per_id_counts.entry(msg.message_id).or_default();
+                *count = count.wrapping_add(1);
+                if !count.is_multiple_of(backlog.decimation) {
+                    backlog.skipped = backlog.skipped.saturating_add(1);
+                    continue;
+                }
             }
+            match queue_tx.try_send(*msg) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    backlog.dropped = backlog.dropped.saturating_add(1);
+                    fifocore::limits::record_drop(DropKind::ClientQueueFull, 1);
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => return,
+            }
+        }
+    }
+}
+
+/// Drains a client's send queue into its socket, reporting anything lost along the way.
+async fn websocket_send(
+    mut ws_tx: SplitSink<WebSocket, Message>,
+    mut queue_rx: mpsc::Receiver<ReduxFIFOMessage>,
+    backlog: Arc<Mutex<Backlog>>,
+    bus_id: u16,
+) {
+    let mut last_notice: Option<Instant> = None;
+    while let Some(first) = queue_rx.recv().await {
+        let notice = {
+            let mut backlog = backlog.lock();
+            let lossy = backlog.dropped > 0 || backlog.skipped > 0;
+            let due = last_notice.is_none_or(|at| at.elapsed() >= NOTICE_INTERVAL);
+            if lossy && due {
+                let notice = rdxcanlink_protocol::CANLinkOverflowNotice {
+                    bus_id,
+                    timestamp: fifocore::timebase::now_us() as u64,
+                    dropped: core::mem::take(&mut backlog.dropped),
+                    skipped: core::mem::take(&mut backlog.skipped),
+                    queued: queue_rx.len() as u32,
+                    decimation: backlog.decimation,
+                };
+                log_warn!(
+                    "[ReduxCore] Websocket client on bus {bus_id} is falling behind: {} dropped, {} skipped",
+                    notice.dropped,
+                    notice.skipped
+                );
+                Some(notice)
+            } else {
+                None
+            }
+        };
+
+        let mut result = Ok(());
+        if let Some(notice) = notice {
+            last_notice = Some(Instant::now());
+            let frame: rdxcanlink_protocol::CANLinkRxMessage = notice.into();
+            result = ws_tx.feed(Message::binary::<Vec<u8>>(frame.into())).await;
+        }
+
+        // send whatever else is already waiting in one flush
+        let mut next = Some(first);
+        while result.is_ok()
+            && let Some(msg) = next
+        {
+            result = ws_tx.feed(rx_frame(&msg)).await;
+            next = queue_rx.try_recv().ok();
         }
 
-        if let Some(e) = errored.or(ws_tx.flush().await.err()) {
+        if let Some(e) = result.err().or(ws_tx.flush().await.err()) {
             log_error!("[ReduxCore] Websocket TX closed: {e}");
             let _ = ws_tx.close().await;
-            // session gets dropped on close
             return;
         }
     }
 }
 
-pub async fn websocket_rx(mut ws_rx: SplitStream<WebSocket>, fifocore: FIFOCore, bus_id: u16) {
+fn rx_frame(msg: &ReduxFIFOMessage) -> Message {
+    let rx_msg = rdxcanlink_protocol::CANLinkRxMessage {
+        message_id: msg.message_id,
+        bus_id: msg.bus_id,
+        flags: msg.flags as u16,
+        timestamp: msg.timestamp,
+        data: msg.data,
+        data_size: msg.data_size as usize,
+    };
+    Message::binary::<Vec<u8>>(rx_msg.into())
+}
+
+pub async fn websocket_rx(
+    mut ws_rx: SplitStream<WebSocket>,
+    fifocore: FIFOCore,
+    bus_id: u16,
+    tag: TraceTag,
+) {
     loop {
         match ws_rx.next().await {
             Some(Ok(Message::Binary(msg))) => {
@@ -87,7 +278,7 @@ pub async fn websocket_rx(mut ws_rx: SplitStream<WebSocket>, fifocore: FIFOCore,
                     data.data_size as u8,
                     data.flags as u8,
                 );
-                let _ = fifocore.write_single(&msg);
+                let _ = fifocore.write_single_tagged(&msg, tag);
             }
             Some(Err(e)) => {
                 log_error!("[ReduxCore] Websocket RX closed: {e}");
@@ -102,3 +293,38 @@ pub async fn websocket_rx(mut ws_rx: SplitStream<WebSocket>, fifocore: FIFOCore,
         }
     }
 }
+
+/// Bridges a websocket to an RdxUSB device console.
+///
+/// Console output goes out as binary messages; text or binary messages coming in are console input.
+pub async fn handle_console(socket: WebSocket, mut console: Console) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    loop {
+        tokio::select! {
+            output = console.recv() => {
+                let Ok(bytes) = output else {
+                    let _ = ws_tx.send(Message::Close(None)).await;
+                    return;
+                };
+                if ws_tx.send(Message::binary(bytes)).await.is_err() {
+                    return;
+                }
+            }
+            input = ws_rx.next() => {
+                let bytes = match input {
+                    Some(Ok(Message::Binary(bytes))) => bytes.to_vec(),
+                    Some(Ok(Message::Text(text))) => text.as_bytes().to_vec(),
+                    Some(Ok(Message::Close(..))) | None => return,
+                    Some(Err(e)) => {
+                        log_error!("[ReduxCore] Console websocket closed: {e}");
+                        return;
+                    }
+                    Some(Ok(..)) => continue,
+                };
+                if let Err(e) = console.write(&bytes) {
+                    log_warn!("[ReduxCore] Dropped console input for {:?}: {e}", console.device_id());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! End to end: simulated devices on a sim: bus, driven entirely through the REST server.
+//!
+//! Run with `cargo test -p canandmiddleware --features simulation --test e2e`.
+#![cfg(feature = "simulation")]
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    time::{Duration, Instant},
+};
+
+use canandmiddleware::rest_server::{self, Services};
+use fifocore::{FIFOCore, ReduxFIFOSessionConfig};
+use serde_json::{Value, json};
+
+/// Blocking HTTP/1.1 client; the server answers with a fixed content length and closes.
+struct Client {
+    addr: SocketAddr,
+}
+
+impl Client {
+    fn request(&self, method: &str, path: &str, body: Option<&[u8]>) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(self.addr).expect("connect to REST server");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let body = body.unwrap_or_default();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            self.addr,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .expect("response has no header terminator");
+        let head = std::str::from_utf8(&response[..split]).unwrap();
+        let status = head
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .expect("response has no status code");
+        (status, response[split + 4..].to_vec())
+    }
+
+    /// Requests `path`, asserting success, and parses the reply as JSON.
+    fn json(&self, method: &str, path: &str, body: Option<Value>) -> Value {
+        let body = body.map(|b| serde_json::to_vec(&b).unwrap());
+        let (status, reply) = self.request(method, path, body.as_deref());
+        let text = String::from_utf8_lossy(&reply);
+        assert_eq!(status, 200, "{method} {path}: {text}");
+        serde_json::from_slice(&reply).unwrap_or_else(|e| panic!("{method} {path}: {e}: {text}"))
+    }
+
+    fn get(&self, path: &str) -> Value {
+        self.json("GET", path, None)
+    }
+
+    fn post(&self, path: &str, body: Value) -> Value {
+        self.json("POST", path, Some(body))
+    }
+}
+
+/// Polls `f` until it returns something or `timeout` runs out.
+fn wait_for<T>(timeout: Duration, mut f: impl FnMut() -> Option<T>) -> Option<T> {
+    let start = Instant::now();
+    loop {
+        if let Some(v) = f() {
+            return Some(v);
+        }
+        if start.elapsed() > timeout {
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn free_addr() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+#[test]
+fn test_sim_bus_over_rest() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let fifocore = FIFOCore::new(rt.handle().clone());
+    let bus_id = fifocore.open_or_get_bus("sim:e2e").unwrap();
+
+    let addr = free_addr();
+    let (shutdown_send, shutdown_recv) = tokio::sync::watch::channel(false);
+    let server = rt.spawn(rest_server::run_web_server_on(
+        addr,
+        shutdown_recv,
+        fifocore.clone(),
+        Services::default(),
+    ));
+    let client = Client { addr };
+    wait_for(Duration::from_secs(5), || TcpStream::connect(addr).ok())
+        .expect("REST server never came up");
+
+    let info = client.get(&format!("/buses/{bus_id}/info"));
+    assert_eq!(info["params"], "sim:e2e");
+    assert_eq!(info["backend"], json!({"kind": "sim", "name": "e2e"}));
+
+    // two emulated devices
+    let gyro_serial = client.post(
+        &format!("/sim/{bus_id}/devices"),
+        json!({"product": "canandgyro", "id": 2}),
+    )["serial"]
+        .clone();
+    client.post(
+        &format!("/sim/{bus_id}/devices"),
+        json!({"product": "canandmag", "id": 3}),
+    );
+    assert_eq!(
+        client
+            .get(&format!("/sim/{bus_id}/devices"))
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    // enumeration
+    client.get(&format!("/sessions/open/{bus_id}"));
+    client.get(&format!("/sessions/{bus_id}/enumerate"));
+    wait_for(Duration::from_secs(5), || {
+        let listed = client.get(&format!("/sessions/{bus_id}/devices/list"));
+        (listed.as_object()?.len() == 2).then_some(())
+    })
+    .expect("both sim devices should enumerate");
+    let fleet = wait_for(Duration::from_secs(5), || {
+        let fleet = client.get("/devices");
+        let devices = fleet.as_array()?;
+        devices
+            .iter()
+            .all(|dev| dev["serial"].is_string())
+            .then(|| devices.clone())
+    })
+    .expect("sim devices should report their serials");
+    assert_eq!(fleet.len(), 2);
+    let gyro = fleet
+        .iter()
+        .find(|dev| dev["serial"] == gyro_serial)
+        .expect("gyro in fleet listing");
+    let gyro_id = format!("{:x}", gyro["addresses"][0]["can_id"].as_u64().unwrap());
+
+    // setting change, read back from the device
+    client.get(&format!(
+        "/sessions/{bus_id}/devices/{gyro_id}/set_name?name=e2e-gyro"
+    ));
+    let name = wait_for(Duration::from_secs(5), || {
+        let report = client.get(&format!(
+            "/sessions/{bus_id}/devices/{gyro_id}/composite/name"
+        ));
+        (report["text"] == "e2e-gyro").then_some(report)
+    });
+    assert!(name.is_some(), "name setting didn't stick");
+
+    // blink, directly and by serial
+    client.get(&format!("/sessions/{bus_id}/devices/{gyro_id}/blink?r=1"));
+    let routed = client.get(&format!(
+        "/devices/{}/blink?r=0",
+        gyro_serial.as_str().unwrap()
+    ));
+    assert_eq!(routed["bus_id"], bus_id);
+    assert_eq!(routed["failovers"], 0);
+
+    // OTA dry run: the sim devices have no bootloader, so the job is started and aborted. Flashing
+    // has to be confirmed first.
+    let start = format!("/ota/{bus_id:x}/{gyro_id}/start");
+    let (status, reply) = client.request("POST", &start, Some(&[0_u8; 256]));
+    assert_eq!(status, 428, "OTA should need confirming");
+    let problem: Value = serde_json::from_slice(&reply).unwrap();
+    assert_eq!(problem["confirmation"]["operation"], "firmware_flash");
+    let token = problem["confirmation"]["token"].as_str().unwrap();
+    let confirmed = format!("{start}?confirm={token}");
+    let (status, _) = client.request("POST", &confirmed, Some(&[0_u8; 256]));
+    assert_eq!(status, 200);
+    // tokens are good for one go
+    let (status, _) = client.request("POST", &confirmed, Some(&[0_u8; 256]));
+    assert_eq!(status, 428);
+    let state = client.get(&format!("/ota/{bus_id:x}/{gyro_id}/status"))["state"].clone();
+    assert_ne!(state, "None", "OTA job should have been started");
+    assert_ne!(state, "Finished", "sim devices can't accept firmware");
+    let (status, reply) = client.request("GET", &format!("/ota/{bus_id:x}/{gyro_id}/abort"), None);
+    assert_eq!(
+        (status, reply.as_slice()),
+        (200, b">w<".as_slice()),
+        "job should be aborted"
+    );
+    // aborting drops the job
+    assert_eq!(
+        client.get(&format!("/ota/{bus_id:x}/{gyro_id}/status"))["state"],
+        "None"
+    );
+    // and so does cancelling it through DELETE on the job, here started under a standing operator
+    // token
+    client.post("/confirm/policy", json!({ "operator_token": "pit" }));
+    let (status, _) = client.request("POST", &format!("{start}?confirm=pit"), Some(&[0_u8; 256]));
+    assert_eq!(status, 200);
+    let (status, _) = client.request("POST", "/confirm/policy", Some(b"{}"));
+    assert_eq!(status, 403, "changing the policy should take the operator token");
+    // progress streams from a second connection until the job stops
+    let addr = client.addr;
+    let progress = format!("/ota/{bus_id:x}/{gyro_id}/progress");
+    let watch = progress.clone();
+    let watcher = std::thread::spawn(move || Client { addr }.request("GET", &watch, None));
+    std::thread::sleep(Duration::from_millis(200));
+    let (status, reply) = client.request("DELETE", &format!("/ota/{bus_id:x}/{gyro_id}"), None);
+    assert_eq!((status, reply.as_slice()), (200, b">w<".as_slice()));
+    let (status, events) = watcher.join().unwrap();
+    let events = String::from_utf8_lossy(&events);
+    assert_eq!(status, 200);
+    assert!(events.contains("event: progress"), "{events}");
+    assert!(events.contains(r#""state":"Abort""#), "{events}");
+    let (status, _) = client.request("GET", &progress, None);
+    assert_eq!(status, 404, "no job left to follow");
+
+    // a calibration waiting a minute for the gyro to hold still is cancelled right away and lets
+    // go of the gyro
+    let calibrate = format!("/sessions/{bus_id}/devices/{gyro_id}/calibrate");
+    let started = client.json("POST", &format!("{calibrate}?still_ms=60000"), None);
+    assert_eq!(started["state"], "precheck");
+    let cancel_start = Instant::now();
+    let cancelled = client.json("DELETE", &calibrate, None);
+    assert_eq!(cancelled["state"], "cancelled");
+    assert!(cancel_start.elapsed() < Duration::from_secs(1));
+    let locks = client.get("/locks");
+    assert!(
+        locks
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|lock| lock["holder"].is_null()),
+        "cancelled calibration still holds the gyro: {locks}"
+    );
+
+    // a paused session holds the frames it would have read, and gets them back on resuming
+    let view = fifocore
+        .open_managed_session(bus_id, 64, ReduxFIFOSessionConfig::new(0, 0))
+        .unwrap();
+    view.set_label("e2e-view").unwrap();
+    let sessions = client.get(&format!("/buses/{bus_id}/sessions"));
+    let listed = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|ses| ses["label"] == "e2e-view")
+        .expect("labeled session in listing");
+    let ses_id = listed["session"].as_u64().unwrap();
+    assert_eq!(listed["paused"], false);
+    let paused = client.get(&format!("/buses/{bus_id}/sessions/{ses_id}/pause?cap=64"));
+    assert_eq!(paused["paused"], true);
+    assert_eq!(paused["cap"], 64);
+    let mut read_buf = view.read_buffer(64);
+    view.read_barrier(&mut read_buf).unwrap();
+    let held = wait_for(Duration::from_secs(5), || {
+        let sessions = client.get(&format!("/buses/{bus_id}/sessions"));
+        let status = sessions
+            .as_array()?
+            .iter()
+            .find(|ses| ses["session"] == ses_id)?;
+        let held = status["held"].as_u64()?;
+        (held > 0).then_some(held)
+    })
+    .expect("paused session should hold the sim devices' frames");
+    view.read_barrier(&mut read_buf).unwrap();
+    assert!(
+        read_buf.unordered_valid_messages().is_empty(),
+        "paused session was delivered to"
+    );
+    let resumed = client.get(&format!("/buses/{bus_id}/sessions/{ses_id}/resume"));
+    assert_eq!(resumed["paused"], false);
+    view.read_barrier(&mut read_buf).unwrap();
+    assert!(
+        read_buf.unordered_valid_messages().len() as u64 >= held,
+        "held frames were lost"
+    );
+    drop(view);
+
+    shutdown_send.send_replace(true);
+    rt.block_on(server).unwrap();
+}
+
+#[test]
+fn test_sim_id_conflict() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let fifocore = FIFOCore::new(rt.handle().clone());
+    let bus_id = fifocore.open_or_get_bus("sim:conflict").unwrap();
+
+    let addr = free_addr();
+    let (shutdown_send, shutdown_recv) = tokio::sync::watch::channel(false);
+    let server = rt.spawn(rest_server::run_web_server_on(
+        addr,
+        shutdown_recv,
+        fifocore.clone(),
+        Services::default(),
+    ));
+    let client = Client { addr };
+    wait_for(Duration::from_secs(5), || TcpStream::connect(addr).ok())
+        .expect("REST server never came up");
+
+    // two canandmags on id 5, which only goes through when asked for
+    let devices = format!("/sim/{bus_id}/devices");
+    client.post(&devices, json!({"product": "canandmag", "id": 5}));
+    let body = serde_json::to_vec(&json!({"product": "canandmag", "id": 5})).unwrap();
+    let (status, _) = client.request("POST", &devices, Some(&body));
+    assert_eq!(status, 409, "a second device should need \"conflict\"");
+    let second = client.post(
+        &devices,
+        json!({"product": "canandmag", "id": 5, "conflict": true}),
+    )["serial"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // the middleware sees both serials fighting over the id
+    client.get(&format!("/sessions/open/{bus_id}"));
+    let conflict = wait_for(Duration::from_secs(5), || {
+        let listed = client.get(&format!("/sessions/{bus_id}/devices/list"));
+        let detected = listed["Encoder:5"]["InConflict"]["devices_detected"].as_array()?;
+        (detected.len() == 2).then(|| listed["Encoder:5"].clone())
+    });
+    assert!(conflict.is_some(), "id 5 should be reported in conflict");
+
+    // arbitrate the second device and move it out of the way
+    let can_id = frc_can_id::build_frc_can_id(
+        canandmessage::canandmag::DEV_TYPE,
+        frc_can_id::REDUX_VENDOR_ID,
+        0,
+        5,
+    );
+    client.get(&format!(
+        "/sessions/{bus_id}/devices/{can_id:x}/arbitrate?serial={second}"
+    ));
+    let listed = client.get(&format!("/sessions/{bus_id}/devices/list"));
+    assert_eq!(
+        listed["Encoder:5"]["InConflict"]["authorized"]
+            .as_array()
+            .map(|serial| serial.len()),
+        Some(6),
+        "arbitrated serial should be recorded: {listed}"
+    );
+    wait_for(Duration::from_secs(5), || {
+        let sim = client.get(&devices);
+        sim.as_array()?
+            .iter()
+            .any(|dev| dev["serial"] == second && dev["arbitrated"] == true)
+            .then_some(())
+    })
+    .expect("second device should answer to arbitration");
+    client.get(&format!(
+        "/sessions/{bus_id}/devices/{can_id:x}/set_id?id=6"
+    ));
+
+    let sim = wait_for(Duration::from_secs(5), || {
+        let sim = client.get(&devices);
+        sim.as_array()?
+            .iter()
+            .any(|dev| dev["serial"] == second && dev["dev_id"] == 6)
+            .then_some(sim)
+    })
+    .expect("arbitrated device should move to id 6");
+    assert!(
+        sim.as_array()
+            .unwrap()
+            .iter()
+            .all(|dev| dev["arbitrated"] == false),
+        "nothing is left to arbitrate: {sim}"
+    );
+
+    // once the conflict reports age out, both ids hold an ordinary canandmag
+    let resolved = wait_for(Duration::from_secs(10), || {
+        let listed = client.get(&format!("/sessions/{bus_id}/devices/list"));
+        (listed["Encoder:5"].get("Canandmag").is_some()
+            && listed["Encoder:6"].get("Canandmag").is_some())
+        .then_some(listed)
+    });
+    assert!(resolved.is_some(), "conflict on id 5 should resolve");
+
+    shutdown_send.send_replace(true);
+    rt.block_on(server).unwrap();
+}
@@ -4,7 +4,10 @@ pub mod halcan;
 #[cfg(target_os = "linux")]
 pub mod socketcan;
 
+pub mod gsusb;
 pub mod rdxusb;
+pub mod replay;
+pub mod sim;
 pub mod slcan;
 pub mod usb;
 pub mod websocket;
@@ -17,9 +20,120 @@ use tokio::sync::watch;
 
 use crate::{
     ReadBuffer, ReduxFIFOMessage, ReduxFIFOSession, ReduxFIFOSessionConfig, WriteBuffer,
-    error::Error, logger::LoggerTx,
+    bus_info::{BackendInfo, BusInfo},
+    bus_options::BusOptions,
+    bus_status::{BusStatus, BusStatusTracker},
+    dispatch::SessionWait,
+    error::Error,
+    limits::{DropKind, Reservation},
+    logger::{LogEntry, LoggerTx},
+    pause::{PauseQueue, PauseStatus},
+    retain::RetainedHistory,
+    shm::ShmRing,
+    timestamp::{TimestampSource, TimestampStatus, Timestamper},
+    trace_tag::TraceTag,
+    tx_confirm::{TxStatus, TxTracker},
+    tx_queue::{QueuedTx, TxPriority, TxQueue, Written},
+    tx_rate::TxRateBucket,
+    utilization::{
+        DEFAULT_BITRATE, DEFAULT_DATA_BITRATE, DEFAULT_WARN_PERCENT, UtilizationStatus,
+        UtilizationTracker,
+    },
 };
 
+/// The bus backends ReduxFIFO knows how to open.
+///
+/// Each kind doubles as a bit in the mask returned by [`capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[repr(u32)]
+pub enum BackendKind {
+    HalCan = 1 << 0,
+    SocketCan = 1 << 1,
+    RdxUsb = 1 << 2,
+    WebSocketLegacy = 1 << 3,
+    WebSocket = 1 << 4,
+    Slcan = 1 << 5,
+    Sim = 1 << 6,
+    Replay = 1 << 7,
+    GsUsb = 1 << 8,
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 9] = [
+        BackendKind::HalCan,
+        BackendKind::SocketCan,
+        BackendKind::RdxUsb,
+        BackendKind::WebSocketLegacy,
+        BackendKind::WebSocket,
+        BackendKind::Slcan,
+        BackendKind::Sim,
+        BackendKind::Replay,
+        BackendKind::GsUsb,
+    ];
+
+    /// Bus params prefix that selects this backend.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            BackendKind::HalCan => "halcan",
+            BackendKind::SocketCan => "socketcan",
+            BackendKind::RdxUsb => "rdxusb",
+            BackendKind::WebSocketLegacy => "websocket:",
+            BackendKind::WebSocket => "ws:",
+            BackendKind::Slcan => "slcan:",
+            BackendKind::Sim => "sim:",
+            BackendKind::Replay => "replay:",
+            BackendKind::GsUsb => "gsusb:",
+        }
+    }
+
+    /// What this backend's params look like, for error messages.
+    pub fn usage(self) -> &'static str {
+        match self {
+            BackendKind::HalCan => "halcan",
+            BackendKind::SocketCan => "socketcan[.fd]:<interface>",
+            BackendKind::RdxUsb => "rdxusb:<channel>.<vid hex>.<pid hex>.<serial>",
+            BackendKind::WebSocketLegacy => "websocket:ws[s]://<host>[:port][/path]",
+            BackendKind::WebSocket => "ws://<host>[:port][/path]",
+            BackendKind::Slcan => "slcan:<baud|auto>:<serial port>",
+            BackendKind::Sim => "sim:<name>",
+            BackendKind::Replay => "replay:<log file>",
+            BackendKind::GsUsb => "gsusb:<serial>[:<channel>]",
+        }
+    }
+
+    /// Picks the backend for a bus params string.
+    pub fn from_params(params: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| params.starts_with(kind.prefix()))
+    }
+
+    /// Whether this build can open this backend on this machine.
+    ///
+    /// For halcan this also requires the WPILib HAL to actually be present at runtime.
+    pub fn supported(self) -> bool {
+        match self {
+            BackendKind::HalCan => cfg!(feature = "wpihal-rio") && crate::hal::available(),
+            BackendKind::SocketCan => cfg!(target_os = "linux"),
+            BackendKind::RdxUsb
+            | BackendKind::WebSocketLegacy
+            | BackendKind::WebSocket
+            | BackendKind::Slcan
+            | BackendKind::Sim
+            | BackendKind::Replay
+            | BackendKind::GsUsb => true,
+        }
+    }
+}
+
+/// Bitmask of the [`BackendKind`]s that can be opened.
+pub fn capabilities() -> u32 {
+    BackendKind::ALL
+        .into_iter()
+        .filter(|kind| kind.supported())
+        .fold(0, |mask, kind| mask | kind as u32)
+}
+
 pub trait MessageBackend: Send + core::fmt::Debug {
     /// Open a new [`ReduxFIFOSession`] with this backend.
     fn open_session(
@@ -34,6 +148,15 @@ pub trait MessageBackend: Send + core::fmt::Debug {
     ///
     /// The bumpvec of pointers is handed to the backend. Control of the previously used [`ReduxFIFOBuffer`]s is handed back to the API caller.
     fn read_barrier(&mut self, data: &mut [ReadBuffer]);
+    /// Takes up to `max` of a session's messages timestamped from `t0` to `t1`; see
+    /// [`SessionState::take_window`].
+    fn read_window(
+        &mut self,
+        ses: ReduxFIFOSession,
+        t0: u64,
+        t1: u64,
+        max: usize,
+    ) -> Result<Vec<ReduxFIFOMessage>, Error>;
     /// Executes a write barrier.
     /// This executes synchronously.
     ///
@@ -44,13 +167,55 @@ pub trait MessageBackend: Send + core::fmt::Debug {
     /// Get an RX size notifier for a session.
     fn rx_notifier(&mut self, ses: ReduxFIFOSession) -> Result<watch::Receiver<u32>, Error>;
 
-    fn write_single(&mut self, msg: &ReduxFIFOMessage) -> Result<(), Error>;
+    /// Writes one message on behalf of `tag`, returning whether it was sent or queued and its
+    /// sequence number if the bus confirms writes.
+    fn write_single(
+        &mut self,
+        msg: &ReduxFIFOMessage,
+        tag: TraceTag,
+        priority: TxPriority,
+    ) -> Result<Written, Error>;
+    /// Sends what queued writes the backend has room for, returning how many are still queued.
+    fn drain_tx_queue(&mut self) -> usize;
+    /// How many writes of `priority`, or of any priority, are waiting in the transmit queue.
+    fn tx_queued(&self, priority: Option<TxPriority>) -> usize;
+    /// Looks up whether a write made it onto the wire.
+    fn tx_status(&self, tx_seq: u32) -> TxStatus;
+    /// Get a notifier that changes whenever a write is confirmed on the wire.
+    fn tx_notifier(&self) -> watch::Receiver<u32>;
 
     fn sessions(&self) -> Vec<ReduxFIFOSession>;
+    /// How long a session's reader has been leaving messages waiting.
+    fn session_wait(&self, ses: ReduxFIFOSession) -> Result<SessionWait, Error>;
+    /// The label a session's writes are logged with.
+    fn session_tag(&self, ses: ReduxFIFOSession) -> Result<TraceTag, Error>;
+    fn set_session_tag(&mut self, ses: ReduxFIFOSession, tag: TraceTag) -> Result<(), Error>;
+    /// Delivers a session's messages to `ring` instead of its read buffer, or back to the read
+    /// buffer if `None`.
+    fn set_session_ring(
+        &mut self,
+        ses: ReduxFIFOSession,
+        ring: Option<Arc<ShmRing>>,
+    ) -> Result<(), Error>;
+    /// Holds a session's messages instead of delivering them, up to `cap`; see [`crate::pause`].
+    fn pause_session(&mut self, ses: ReduxFIFOSession, cap: u32) -> Result<(), Error>;
+    /// Goes back to delivering a paused session's messages, starting with the held ones.
+    fn resume_session(&mut self, ses: ReduxFIFOSession) -> Result<(), Error>;
+    fn session_pause(&self, ses: ReduxFIFOSession) -> Result<PauseStatus, Error>;
     fn bus_id(&self) -> u16;
     fn params<'a>(&'a self) -> &'a str;
     fn id_cache(&self) -> IdCache;
     fn max_packet_size(&self) -> usize;
+    /// Where this bus's timestamps come from, and how often they needed fixing up.
+    fn timestamps(&self) -> TimestampStatus;
+    /// How busy the bus has been; see [`crate::utilization`].
+    fn utilization(&self) -> UtilizationStatus;
+    /// The bus's error state and overruns; see [`crate::bus_status`].
+    fn status(&self) -> BusStatus;
+    /// Get a notifier that changes along with [`MessageBackend::status`].
+    fn status_notifier(&self) -> watch::Receiver<BusStatus>;
+    /// The hardware behind the bus; see [`crate::bus_info`].
+    fn info(&self) -> BusInfo;
 
     fn set_logger(&mut self, logger: LoggerTx);
 }
@@ -58,6 +223,8 @@ pub trait MessageBackend: Send + core::fmt::Debug {
 /// this is what `backends/*.rs` actually implements
 pub trait Backend: core::fmt::Debug + Send {
     type State: 'static;
+    /// What the timestamps this backend puts on received messages are based on.
+    const TIMESTAMPS: TimestampSource = TimestampSource::HostArrival;
     /// Start the session.
     /// Enclosed is also an arc/mutex state map, that the backend is responsible for
     /// inserting a [`SessionState`] into
@@ -94,6 +261,16 @@ pub trait Backend: core::fmt::Debug + Send {
     fn params_match(&self, params: &str) -> bool;
     /// The maximum packet size for this message backend.
     fn max_packet_size(&self) -> usize;
+    /// What this backend is talking to; see [`crate::bus_info`].
+    fn info(&self) -> BackendInfo;
+    /// Whether this backend reports written messages going out on the wire to [`SessionTable::tx`].
+    fn confirms_tx(&self) -> bool {
+        false
+    }
+    /// Whether this backend plays back a log, and so takes the `speed` and `loop` options.
+    fn replays(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -129,20 +306,87 @@ pub struct SessionTable<S> {
     pub id_cache: IdCache,
     pub bus_id: u16,
     pub logger: LoggerTx,
+    /// Writes waiting to be seen on the wire; only enabled with [`BusOptions::confirm_tx`]
+    pub tx: TxTracker,
+    /// Final say on received message timestamps
+    pub timestamps: Timestamper,
+    /// Messages kept for closed sessions that asked for them
+    pub retained: RetainedHistory,
+    pub utilization: UtilizationTracker,
+    pub status: BusStatusTracker,
+    /// Options the bus was opened with, for backends that take options of their own
+    pub options: BusOptions,
 }
 impl<S: 'static> SessionTable<S> {
-    pub fn ingest_message(&mut self, msg: ReduxFIFOMessage) {
-        self.id_cache.update(msg.message_id, msg.timestamp);
+    pub fn ingest_message(&mut self, mut msg: ReduxFIFOMessage) {
+        self.ingest_messages(core::slice::from_mut(&mut msg));
+    }
+
+    /// Stamps received messages per the bus's [`Timestamper`] and delivers them to every session
+    /// they match, in one pass; see [`crate::dispatch`].
+    ///
+    /// Callers hold the table lock throughout, so keep `msgs` to
+    /// [`MAX_DISPATCH_BATCH`](crate::dispatch::MAX_DISPATCH_BATCH) or fewer.
+    pub fn ingest_messages(&mut self, msgs: &mut [ReduxFIFOMessage]) {
+        self.stamp_messages(msgs);
+        self.dispatch_messages(msgs);
+    }
+
+    /// Gives received messages their final timestamps.
+    pub fn stamp_messages(&mut self, msgs: &mut [ReduxFIFOMessage]) {
+        self.timestamps
+            .stamp(msgs, crate::timebase::now_us() as u64);
+    }
+
+    /// Delivers already stamped messages to every session they match, in one pass.
+    pub fn dispatch_messages(&mut self, msgs: &[ReduxFIFOMessage]) {
+        if msgs.is_empty() {
+            return;
+        }
+        let mut deliveries = 0;
+        let now = crate::timebase::now_us() as u64;
+        for msg in msgs.iter() {
+            self.id_cache.update(msg.message_id, msg.timestamp);
+            self.retained.record(msg, now);
+            self.utilization.record(msg, now);
+            for ses in self
+                .sessions
+                .values_mut()
+                .filter(|ses| ses.config.message_matches(msg))
+            {
+                ses.add_message(*msg);
+                ses.rx_pending = true;
+                deliveries += 1;
+            }
+            if let Some(logger) = &self.logger {
+                crate::limits::send_to_log(logger, *msg);
+            }
+        }
+        self.notify_pending();
+        crate::dispatch::record_batch(msgs.len(), deliveries);
+    }
+
+    /// Delivers a message this host sent to every matching session that asked for `echo_tx`.
+    pub fn echo_message(&mut self, msg: ReduxFIFOMessage) {
         for ses in self
             .sessions
             .values_mut()
-            .filter(|ses| ses.config.message_matches(&msg))
+            .filter(|ses| ses.config.echo_tx && ses.config.message_matches(&msg))
         {
-            ses.read_buf.add_message(msg);
-            ses.update_rx_notifier();
+            ses.add_message(msg);
+            ses.rx_pending = true;
         }
-        if let Some(logger) = &mut self.logger {
-            logger.try_send(msg).ok();
+        let now = crate::timebase::now_us() as u64;
+        self.retained.record_tx(&msg, now);
+        self.utilization.record(&msg, now);
+        self.notify_pending();
+    }
+
+    /// Wakes the readers of sessions that got messages since they were last notified.
+    fn notify_pending(&mut self) {
+        let now = crate::timebase::now_us() as u64;
+        for ses in self.sessions.values_mut().filter(|ses| ses.rx_pending) {
+            ses.notify_dispatched(now);
         }
     }
 
@@ -160,6 +404,17 @@ impl<S: 'static> SessionTable<S> {
             id_cache: Default::default(),
             bus_id,
             logger: None,
+            tx: TxTracker::new(),
+            timestamps: Timestamper::new(TimestampSource::HostArrival),
+            retained: Default::default(),
+            utilization: UtilizationTracker::new(
+                bus_id,
+                DEFAULT_BITRATE,
+                DEFAULT_DATA_BITRATE,
+                DEFAULT_WARN_PERCENT,
+            ),
+            status: BusStatusTracker::new(bus_id),
+            options: BusOptions::default(),
         }
     }
 }
@@ -180,19 +435,104 @@ pub struct SessionState<S> {
     pub read_buf: ReadBuffer,
     pub rx_notifier: watch::Sender<u32>,
     pub backend_state: S,
+    /// Share of the buffer budget held by `read_buf`
+    pub reservation: Reservation,
+    /// Got messages that its reader hasn't been notified of yet
+    pub rx_pending: bool,
+    /// Starvation metric for this session's reader
+    pub wait: SessionWait,
+    /// Label for messages written through this session
+    pub tag: TraceTag,
+    /// Ring the session's messages go to instead of `read_buf`, if one is attached
+    pub ring: Option<Arc<ShmRing>>,
+    /// Messages held while the session is paused
+    pub pause: PauseQueue,
 }
 
 impl<S> SessionState<S> {
+    /// Adds a message to the read buffer, counting the overwritten one if it was full.
+    pub fn add_message(&mut self, msg: ReduxFIFOMessage) {
+        if self.pause.holding() {
+            self.pause.hold(msg);
+            return;
+        }
+        if let Some(ring) = &self.ring {
+            ring.push(&msg);
+            return;
+        }
+        let meta = &self.read_buf.meta;
+        if meta.max_length > 0 && meta.valid_length == meta.max_length {
+            crate::limits::record_drop(DropKind::SessionOverwrite, 1);
+        }
+        self.read_buf.add_message(msg);
+    }
+
     /// Notifies listeners if the rx threshold is reached
     pub fn update_rx_notifier(&self) {
-        self.rx_notifier
-            .send_replace(self.read_buf.meta.valid_length);
+        let waiting = match &self.ring {
+            Some(ring) => ring.len(),
+            None => self.read_buf.meta.valid_length,
+        };
+        self.rx_notifier.send_replace(waiting);
+    }
+
+    /// Notifies the reader of messages dispatched at `now`.
+    pub fn notify_dispatched(&mut self, now: u64) {
+        self.rx_pending = false;
+        if self.pause.status().paused {
+            // nothing reached the reader
+            return;
+        }
+        self.wait.dispatched(now);
+        self.update_rx_notifier();
     }
 
     pub fn swap_buffers(&mut self, swap_buf: &mut ReadBuffer) {
         core::mem::swap(&mut self.read_buf, swap_buf);
+        let now = crate::timebase::now_us() as u64;
+        self.wait.read(now);
+        if self.pause.refill(&mut self.read_buf) {
+            self.wait.dispatched(now);
+        }
         self.update_rx_notifier();
     }
+
+    /// Hands over the messages held since a pause, as many as there's room for; the rest follow
+    /// on later read barriers.
+    fn deliver_held(&mut self, now: u64) {
+        let delivered = match &self.ring {
+            Some(ring) => {
+                let mut delivered = false;
+                self.pause.flush(|msg| {
+                    ring.push(msg);
+                    delivered = true;
+                });
+                delivered
+            }
+            None => self.pause.refill(&mut self.read_buf),
+        };
+        if delivered {
+            self.notify_dispatched(now);
+        }
+    }
+
+    /// Takes the waiting messages timestamped from `t0` to `t1` inclusive, oldest first. Older
+    /// ones are discarded and newer ones stay waiting, as do any in the window past the first `max`.
+    pub fn take_window(&mut self, t0: u64, t1: u64, max: usize) -> Vec<ReduxFIFOMessage> {
+        let waiting: Vec<ReduxFIFOMessage> = self.read_buf.iter().copied().collect();
+        self.read_buf.ready_for_read();
+        let mut window = Vec::new();
+        for msg in waiting {
+            if msg.timestamp > t1 || (msg.timestamp >= t0 && window.len() == max) {
+                self.read_buf.add_message(msg);
+            } else if msg.timestamp >= t0 {
+                window.push(msg);
+            }
+        }
+        self.wait.read(crate::timebase::now_us() as u64);
+        self.update_rx_notifier();
+        window
+    }
 }
 
 /// Session controller for a Bus.
@@ -203,23 +543,35 @@ pub(crate) struct BusController<B: Backend + core::fmt::Debug> {
     params: String,
     backend: B,
     ses_table: Arc<parking_lot::Mutex<SessionTable<B::State>>>,
-    logger: Option<tokio::sync::mpsc::Sender<ReduxFIFOMessage>>,
+    logger: LoggerTx,
+    /// Writes waiting for room in the backend; only used with [`BusOptions::tx_queue`]
+    tx_queue: TxQueue,
+    /// Only used while a [`crate::tx_rate`] limit is set
+    tx_rate: TxRateBucket,
 }
 impl<B: BackendOpen> BusController<B>
 where
     <B as Backend>::State: core::fmt::Debug + Send,
 {
     #[allow(unused)]
-    pub fn new(bus_id: u16, params: &str, runtime: tokio::runtime::Handle) -> Result<Self, Error> {
-        let ses_table = Arc::new(parking_lot::Mutex::new(SessionTable::new(bus_id)));
-        Ok(Self {
+    pub fn new(
+        bus_id: u16,
+        params: &str,
+        options: BusOptions,
+        runtime: tokio::runtime::Handle,
+    ) -> Result<Self, Error> {
+        let ses_table = new_ses_table(bus_id, options, B::TIMESTAMPS);
+        Self {
             bus_id,
             next_session_id: 0,
             params: params.to_string(),
             backend: B::open(bus_id, params, runtime, ses_table.clone())?,
             ses_table: ses_table,
             logger: None,
-        })
+            tx_queue: TxQueue::default(),
+            tx_rate: TxRateBucket::default(),
+        }
+        .check_options(options)
     }
 }
 
@@ -227,12 +579,16 @@ impl BusController<crate::backends::rdxusb::RdxUsbBackend> {
     pub fn new(
         bus_id: u16,
         params: &str,
+        options: BusOptions,
         runtime: tokio::runtime::Handle,
         usb_event_loop: Arc<parking_lot::Mutex<usb::UsbEventLoop>>,
     ) -> Result<Self, Error> {
-        let ses_table: Arc<parking_lot::Mutex<SessionTable<usb::UsbSessionState>>> =
-            Arc::new(parking_lot::Mutex::new(SessionTable::new(bus_id)));
-        Ok(Self {
+        let ses_table = new_ses_table(
+            bus_id,
+            options,
+            crate::backends::rdxusb::RdxUsbBackend::TIMESTAMPS,
+        );
+        Self {
             bus_id,
             next_session_id: 0,
             params: params.to_string(),
@@ -243,9 +599,210 @@ impl BusController<crate::backends::rdxusb::RdxUsbBackend> {
                 ses_table.clone(),
                 usb_event_loop,
             )?,
-            ses_table: ses_table,
+            ses_table,
             logger: None,
-        })
+            tx_queue: TxQueue::default(),
+            tx_rate: TxRateBucket::default(),
+        }
+        .check_options(options)
+    }
+}
+
+impl BusController<crate::backends::gsusb::GsUsbBackend> {
+    pub fn new(
+        bus_id: u16,
+        params: &str,
+        options: BusOptions,
+        runtime: tokio::runtime::Handle,
+        usb_event_loop: Arc<parking_lot::Mutex<usb::UsbEventLoop>>,
+    ) -> Result<Self, Error> {
+        let ses_table = new_ses_table(
+            bus_id,
+            options,
+            crate::backends::gsusb::GsUsbBackend::TIMESTAMPS,
+        );
+        Self {
+            bus_id,
+            next_session_id: 0,
+            params: params.to_string(),
+            backend: crate::backends::gsusb::GsUsbBackend::open(
+                bus_id,
+                params,
+                runtime,
+                ses_table.clone(),
+                usb_event_loop,
+            )?,
+            ses_table,
+            logger: None,
+            tx_queue: TxQueue::default(),
+            tx_rate: TxRateBucket::default(),
+        }
+        .check_options(options)
+    }
+}
+
+/// The table is set up before the backend opens so the backend can see which options apply.
+fn new_ses_table<S: 'static>(
+    bus_id: u16,
+    options: BusOptions,
+    timestamps: TimestampSource,
+) -> Arc<parking_lot::Mutex<SessionTable<S>>> {
+    let mut ses_table = SessionTable::new(bus_id);
+    ses_table.tx.set_enabled(options.confirm_tx);
+    ses_table.options = options;
+    ses_table.timestamps = Timestamper::new(timestamps);
+    ses_table.utilization = UtilizationTracker::new(
+        bus_id,
+        options.bitrate.unwrap_or(DEFAULT_BITRATE),
+        options.data_bitrate.unwrap_or(DEFAULT_DATA_BITRATE),
+        options.util_warn.unwrap_or(DEFAULT_WARN_PERCENT),
+    );
+    Arc::new(parking_lot::Mutex::new(ses_table))
+}
+
+impl<B: Backend> BusController<B> {
+    /// Refuses options the opened backend can't honor.
+    fn check_options(mut self, options: BusOptions) -> Result<Self, Error> {
+        if options.confirm_tx && !self.backend.confirms_tx() {
+            crate::log_error!("{}: backend can't confirm writes on the wire", self.params);
+            return Err(Error::BusNotSupported);
+        }
+        if (options.speed.is_some() || options.loop_replay) && !self.backend.replays() {
+            crate::log_error!("{}: only replayed logs take `speed` and `loop`", self.params);
+            return Err(Error::InvalidBus);
+        }
+        self.ses_table
+            .lock()
+            .utilization
+            .set_fd(self.backend.max_packet_size() > 8);
+        self.tx_queue = TxQueue::new(options.tx_queue.unwrap_or(0));
+        Ok(self)
+    }
+
+    /// Writes one message, queueing it if the backend is full and the bus has a queue.
+    fn write_queued(
+        &mut self,
+        msg: &ReduxFIFOMessage,
+        tag: TraceTag,
+        priority: TxPriority,
+    ) -> Result<Written, Error> {
+        self.drain_tx();
+        if priority != TxPriority::Urgent && self.over_rate_limit() {
+            self.ses_table.lock().status.tx_overrun(1);
+            return Err(Error::BusBufferFull);
+        }
+        let tx_seq = self.track_tx(core::slice::from_ref(msg));
+        // anything still queued is more important or was written first
+        let result = match self.tx_queue.is_empty() {
+            true => self.backend.write_single(msg),
+            false => Err(Error::BusBufferFull),
+        };
+        match result {
+            Ok(()) => {
+                self.record_tx(core::slice::from_ref(msg), tag);
+                Ok(Written::Sent(tx_seq))
+            }
+            Err(Error::BusBufferFull) if self.tx_queue.enabled() => {
+                let tx = QueuedTx {
+                    msg: *msg,
+                    tag,
+                    tx_seq,
+                };
+                self.queue_tx(tx, priority)?;
+                self.drain_tx();
+                match self.tx_queue.last_waiting() {
+                    true => Ok(Written::Queued(tx_seq)),
+                    false => Ok(Written::Sent(tx_seq)),
+                }
+            }
+            Err(e) => {
+                let mut ses_table = self.ses_table.lock();
+                if let Some(tx_seq) = tx_seq {
+                    ses_table.tx.untrack(tx_seq, 1);
+                }
+                if e == Error::BusBufferFull {
+                    ses_table.status.tx_overrun(1);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether a write now would go over the [`crate::tx_rate`] limit, if one is set.
+    fn over_rate_limit(&mut self) -> bool {
+        let Some(limit) = crate::tx_rate::tx_rate_limit() else {
+            return false;
+        };
+        let now = crate::timebase::now_us() as u64;
+        !self.tx_rate.admit(limit, now)
+    }
+
+    /// Queues a write, failing with [`Error::BusBufferFull`] if it was the one a full queue
+    /// pushed out.
+    fn queue_tx(&mut self, tx: QueuedTx, priority: TxPriority) -> Result<(), Error> {
+        let Some(pushed_out) = self.tx_queue.push(tx, priority) else {
+            return Ok(());
+        };
+        crate::limits::record_drop(DropKind::TxQueueFull, 1);
+        let mut ses_table = self.ses_table.lock();
+        ses_table.status.tx_overrun(1);
+        if let Some(tx_seq) = pushed_out.tx_seq {
+            ses_table.tx.untrack(tx_seq, 1);
+        }
+        match pushed_out == tx {
+            true => Err(Error::BusBufferFull),
+            false => Ok(()),
+        }
+    }
+
+    /// Sends what queued writes the backend has room for.
+    fn drain_tx(&mut self) {
+        if self.tx_queue.is_empty() {
+            return;
+        }
+        let backend = &mut self.backend;
+        let drained = self.tx_queue.drain(|msg| backend.write_single(msg));
+        for tx in &drained.sent {
+            self.record_tx(core::slice::from_ref(&tx.msg), tx.tag);
+        }
+        let mut ses_table = self.ses_table.lock();
+        for (tx, e) in &drained.failed {
+            let id = tx.msg.message_id;
+            crate::log_error!("{}: dropped queued write of {id:#x}: {e}", self.params);
+            if let Some(tx_seq) = tx.tx_seq {
+                ses_table.tx.untrack(tx_seq, 1);
+            }
+        }
+    }
+
+    /// Starts tracking messages about to be written, returning the first sequence number.
+    fn track_tx(&mut self, msgs: &[ReduxFIFOMessage]) -> Option<u32> {
+        let now = crate::timebase::now_us() as u64;
+        let mut ses_table = self.ses_table.lock();
+        let mut msgs = msgs.iter();
+        let first = ses_table.tx.track(msgs.next()?, now)?;
+        for msg in msgs {
+            ses_table.tx.track(msg, now);
+        }
+        Some(first)
+    }
+
+    /// Passes messages that were just written to the logger and to `echo_tx` sessions, flagged
+    /// with [`ReduxFIFOMessage::FLAG_TX`] and stamped with the time they went out.
+    ///
+    /// Messages from simulated devices are skipped; the sim backend delivers those as received.
+    fn record_tx(&mut self, msgs: &[ReduxFIFOMessage], tag: TraceTag) {
+        let timestamp = crate::timebase::now_us() as u64;
+        let mut ses_table = self.ses_table.lock();
+        for msg in msgs.iter().filter(|msg| !msg.sim()) {
+            let mut tx_msg = *msg;
+            tx_msg.flags |= ReduxFIFOMessage::FLAG_TX;
+            tx_msg.timestamp = timestamp;
+            if let Some(logger) = &self.logger {
+                crate::limits::send_to_log(logger, LogEntry { msg: tx_msg, tag });
+            }
+            ses_table.echo_message(tx_msg);
+        }
     }
 }
 
@@ -269,17 +826,31 @@ where
         if ses_table.sessions.contains_key(&session) {
             return Err(Error::SessionAlreadyOpened);
         }
+        let reservation = Reservation::reserve(msg_count)?;
+        let msg_count = reservation.size();
         let state = self.backend.start_session(msg_count, &config)?;
-        ses_table.sessions.insert(
+        let mut state = SessionState {
             session,
-            SessionState {
-                session,
-                config,
-                read_buf: ReadBuffer::new(session, msg_count),
-                backend_state: state,
-                rx_notifier: watch::channel(0).0,
-            },
-        );
+            config,
+            read_buf: ReadBuffer::new(session, msg_count),
+            backend_state: state,
+            rx_notifier: watch::channel(0).0,
+            reservation,
+            rx_pending: false,
+            wait: SessionWait::default(),
+            tag: TraceTag::NONE,
+            ring: None,
+            pause: PauseQueue::default(),
+        };
+        let now = crate::timebase::now_us() as u64;
+        let history = ses_table.retained.take(&config, now);
+        if !history.is_empty() {
+            for msg in history {
+                state.add_message(msg);
+            }
+            state.notify_dispatched(now);
+        }
+        ses_table.sessions.insert(session, state);
 
         self.next_session_id += 1;
         Ok(session)
@@ -288,15 +859,22 @@ where
     /// This also releases control of the associated memory.
     fn close_session(&mut self, ses: ReduxFIFOSession) -> Result<ReadBuffer, Error> {
         let mut ses_table = self.ses_table.lock();
-        Ok(ses_table
+        let state = ses_table
             .sessions
             .remove(&ses)
-            .ok_or(Error::InvalidSessionID)?
-            .read_buf)
+            .ok_or(Error::InvalidSessionID)?;
+        ses_table
+            .retained
+            .session_closed(state.config, state.reservation);
+        if let Some(ring) = &state.ring {
+            ring.close();
+        }
+        Ok(state.read_buf)
     }
 
     /// Executes a read barrier.
     fn read_barrier(&mut self, data: &mut [ReadBuffer]) {
+        self.drain_tx();
         let mut ses_table = self.ses_table.lock();
         for entry in data {
             let session = entry.session();
@@ -308,21 +886,69 @@ where
             }
         }
     }
+    fn read_window(
+        &mut self,
+        ses: ReduxFIFOSession,
+        t0: u64,
+        t1: u64,
+        max: usize,
+    ) -> Result<Vec<ReduxFIFOMessage>, Error> {
+        let mut ses_table = self.ses_table.lock();
+        let state = ses_table
+            .sessions
+            .get_mut(&ses)
+            .ok_or(Error::InvalidSessionID)?;
+        Ok(state.take_window(t0, t1, max))
+    }
+
     /// Executes a write barrier.
     /// This executes synchronously.
     ///
     /// The backend does not own the underlying buffers.
     fn write_barrier(&mut self, data: &mut WriteBuffer) {
         data.ready_for_write();
+        // one at a time, so the rate limit can stop partway through
+        if self.tx_queue.enabled() || crate::tx_rate::tx_rate_limit().is_some() {
+            let tag = data.tag;
+            let mut written = 0;
+            let mut status = Ok(());
+            for msg in data.msgs.iter() {
+                match self.write_queued(msg, tag, TxPriority::Arbitration) {
+                    Ok(accepted) => {
+                        if written == 0 {
+                            data.meta.tx_seq = accepted.tx_seq().unwrap_or(0);
+                        }
+                        written += 1;
+                    }
+                    Err(e) => {
+                        status = Err(e);
+                        break;
+                    }
+                }
+            }
+            data.meta.messages_written = written;
+            data.set_status(status);
+            return;
+        }
+        let tx_seq = self.track_tx(data.messages());
         self.backend.write_messages(data);
-        if let Some(logger) = &mut self.logger {
-            let written = data.messages_written();
-            for msg in data.messages().iter().take(written) {
-                let mut tx_msg = msg.clone();
-                tx_msg.flags |= ReduxFIFOMessage::FLAG_TX;
-                logger.try_send(tx_msg).ok();
+        let written = data.messages_written();
+        if data.status() == Err(Error::BusBufferFull) {
+            let unwritten = data.msgs.len() - written;
+            self.ses_table.lock().status.tx_overrun(unwritten as u64);
+        }
+        if let Some(tx_seq) = tx_seq {
+            let unwritten = data.msgs.len() - written;
+            self.ses_table
+                .lock()
+                .tx
+                .untrack(tx_seq.wrapping_add(written as u32), unwritten as u32);
+            if written > 0 {
+                data.meta.tx_seq = tx_seq;
             }
         }
+        let tag = data.tag;
+        self.record_tx(&data.messages()[..written], tag);
     }
     /// Checks if the bus address parameters match this message backend.
     fn params_match(&self, params: &str) -> bool {
@@ -338,6 +964,31 @@ where
         ses_table.id_cache.clone()
     }
 
+    fn timestamps(&self) -> TimestampStatus {
+        self.ses_table.lock().timestamps.status()
+    }
+
+    fn utilization(&self) -> UtilizationStatus {
+        let now = crate::timebase::now_us() as u64;
+        self.ses_table.lock().utilization.status(now)
+    }
+
+    fn status(&self) -> BusStatus {
+        self.ses_table.lock().status.status()
+    }
+
+    fn status_notifier(&self) -> watch::Receiver<BusStatus> {
+        self.ses_table.lock().status.subscribe()
+    }
+
+    fn info(&self) -> BusInfo {
+        BusInfo {
+            bus_id: self.bus_id,
+            params: self.params.clone(),
+            backend: self.backend.info(),
+        }
+    }
+
     /// Get an RX size notifier for a session.
     fn rx_notifier(&mut self, ses: ReduxFIFOSession) -> Result<watch::Receiver<u32>, Error> {
         let ses_table = self.ses_table.lock();
@@ -353,18 +1004,119 @@ where
         ses_table.sessions.keys().cloned().collect()
     }
 
+    fn session_wait(&self, ses: ReduxFIFOSession) -> Result<SessionWait, Error> {
+        let ses_table = self.ses_table.lock();
+        ses_table
+            .sessions
+            .get(&ses)
+            .map(|entry| entry.wait)
+            .ok_or(Error::InvalidSessionID)
+    }
+
+    fn session_tag(&self, ses: ReduxFIFOSession) -> Result<TraceTag, Error> {
+        let ses_table = self.ses_table.lock();
+        ses_table
+            .sessions
+            .get(&ses)
+            .map(|entry| entry.tag)
+            .ok_or(Error::InvalidSessionID)
+    }
+
+    fn set_session_tag(&mut self, ses: ReduxFIFOSession, tag: TraceTag) -> Result<(), Error> {
+        let mut ses_table = self.ses_table.lock();
+        let entry = ses_table
+            .sessions
+            .get_mut(&ses)
+            .ok_or(Error::InvalidSessionID)?;
+        entry.tag = tag;
+        Ok(())
+    }
+
+    fn set_session_ring(
+        &mut self,
+        ses: ReduxFIFOSession,
+        ring: Option<Arc<ShmRing>>,
+    ) -> Result<(), Error> {
+        let mut ses_table = self.ses_table.lock();
+        let entry = ses_table
+            .sessions
+            .get_mut(&ses)
+            .ok_or(Error::InvalidSessionID)?;
+        if let Some(old) = &entry.ring {
+            old.close();
+        }
+        if let Some(ring) = &ring {
+            // what was waiting in the read buffer goes to the ring first
+            for msg in entry.read_buf.iter() {
+                ring.push(msg);
+            }
+            entry.read_buf.ready_for_read();
+        }
+        entry.ring = ring;
+        entry.update_rx_notifier();
+        Ok(())
+    }
+
+    fn pause_session(&mut self, ses: ReduxFIFOSession, cap: u32) -> Result<(), Error> {
+        let mut ses_table = self.ses_table.lock();
+        let entry = ses_table
+            .sessions
+            .get_mut(&ses)
+            .ok_or(Error::InvalidSessionID)?;
+        entry.pause.pause(cap)
+    }
+
+    fn resume_session(&mut self, ses: ReduxFIFOSession) -> Result<(), Error> {
+        let mut ses_table = self.ses_table.lock();
+        let entry = ses_table
+            .sessions
+            .get_mut(&ses)
+            .ok_or(Error::InvalidSessionID)?;
+        entry.pause.resume();
+        entry.deliver_held(crate::timebase::now_us() as u64);
+        Ok(())
+    }
+
+    fn session_pause(&self, ses: ReduxFIFOSession) -> Result<PauseStatus, Error> {
+        let ses_table = self.ses_table.lock();
+        ses_table
+            .sessions
+            .get(&ses)
+            .map(|entry| entry.pause.status())
+            .ok_or(Error::InvalidSessionID)
+    }
+
     fn bus_id(&self) -> u16 {
         self.bus_id
     }
 
-    fn write_single(&mut self, msg: &ReduxFIFOMessage) -> Result<(), Error> {
-        if let Some(logger) = &mut self.logger {
-            let mut tx_msg = msg.clone();
-            tx_msg.flags |= ReduxFIFOMessage::FLAG_TX;
-            logger.try_send(tx_msg).ok();
+    fn write_single(
+        &mut self,
+        msg: &ReduxFIFOMessage,
+        tag: TraceTag,
+        priority: TxPriority,
+    ) -> Result<Written, Error> {
+        self.write_queued(msg, tag, priority)
+    }
+
+    fn drain_tx_queue(&mut self) -> usize {
+        self.drain_tx();
+        self.tx_queue.len()
+    }
+
+    fn tx_queued(&self, priority: Option<TxPriority>) -> usize {
+        match priority {
+            Some(priority) => self.tx_queue.waiting(priority),
+            None => self.tx_queue.len(),
         }
+    }
+
+    fn tx_status(&self, tx_seq: u32) -> TxStatus {
+        self.ses_table.lock().tx.status(tx_seq)
+    }
 
-        self.backend.write_single(&msg)
+    fn tx_notifier(&self) -> watch::Receiver<u32> {
+        self.ses_table.lock().tx.subscribe()
     }
 
     fn max_packet_size(&self) -> usize {
@@ -377,3 +1129,137 @@ where
         self.logger = logger;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session_state(size: u32) -> SessionState<()> {
+        let session = ReduxFIFOSession::from_parts(0, 0);
+        SessionState {
+            session,
+            config: ReduxFIFOSessionConfig::default(),
+            read_buf: ReadBuffer::new(session, size),
+            rx_notifier: watch::channel(0).0,
+            backend_state: (),
+            // reserves nothing, so it doesn't race the limits tests
+            reservation: Reservation::reserve(0).unwrap(),
+            rx_pending: false,
+            wait: SessionWait::default(),
+            tag: TraceTag::NONE,
+            ring: None,
+            pause: PauseQueue::default(),
+        }
+    }
+
+    /// A backend with room for `room` more writes.
+    #[derive(Debug)]
+    struct FullBackend {
+        room: usize,
+    }
+
+    impl Backend for FullBackend {
+        type State = ();
+
+        fn start_session(
+            &mut self,
+            _msg_count: u32,
+            _config: &ReduxFIFOSessionConfig,
+        ) -> Result<Self::State, Error> {
+            Ok(())
+        }
+
+        fn write_single(&mut self, _msg: &ReduxFIFOMessage) -> Result<(), Error> {
+            self.room = self.room.checked_sub(1).ok_or(Error::BusBufferFull)?;
+            Ok(())
+        }
+
+        fn params_match(&self, _params: &str) -> bool {
+            false
+        }
+
+        fn max_packet_size(&self) -> usize {
+            8
+        }
+
+        fn info(&self) -> BackendInfo {
+            BackendInfo::Sim {
+                name: "full".to_string(),
+            }
+        }
+    }
+
+    fn timestamps<'a>(msgs: impl IntoIterator<Item = &'a ReduxFIFOMessage>) -> Vec<u64> {
+        msgs.into_iter().map(|msg| msg.timestamp).collect()
+    }
+
+    #[test]
+    fn test_take_window() {
+        let mut state = session_state(16);
+        for ts in [10, 20, 30, 40, 50] {
+            state.add_message(ReduxFIFOMessage::builder().timestamp(ts).build());
+        }
+
+        // both ends are inclusive, older messages are discarded and newer ones stay waiting
+        assert_eq!(timestamps(&state.take_window(20, 30, usize::MAX)), [20, 30]);
+        assert_eq!(timestamps(state.read_buf.iter()), [40, 50]);
+        assert_eq!(*state.rx_notifier.borrow(), 2);
+
+        // so the next window sees each of the newer ones once
+        assert_eq!(timestamps(&state.take_window(0, 40, usize::MAX)), [40]);
+        assert!(state.take_window(0, 40, usize::MAX).is_empty());
+
+        // past `max`, the rest of the window waits for the next take
+        for ts in [60, 70] {
+            state.add_message(ReduxFIFOMessage::builder().timestamp(ts).build());
+        }
+        assert_eq!(timestamps(&state.take_window(0, 70, 2)), [50, 60]);
+        assert_eq!(timestamps(&state.take_window(0, 70, 2)), [70]);
+        assert_eq!(*state.rx_notifier.borrow(), 0);
+    }
+
+    #[test]
+    fn test_write_queued() {
+        let mut bus = BusController {
+            bus_id: 0,
+            next_session_id: 0,
+            params: "full".to_string(),
+            backend: FullBackend { room: 1 },
+            ses_table: new_ses_table(0, BusOptions::default(), TimestampSource::HostArrival),
+            logger: None,
+            tx_queue: TxQueue::new(4),
+            tx_rate: TxRateBucket::default(),
+        };
+        let msg = |id| ReduxFIFOMessage::builder().id(id).build();
+        let mut write = |bus: &mut BusController<_>, id, priority| {
+            bus.write_single(&msg(id), TraceTag::NONE, priority)
+                .unwrap()
+        };
+
+        assert_eq!(
+            write(&mut bus, 1, TxPriority::Arbitration),
+            Written::Sent(None)
+        );
+        assert_eq!(
+            write(&mut bus, 2, TxPriority::Arbitration),
+            Written::Queued(None)
+        );
+        assert_eq!(
+            write(&mut bus, 3, TxPriority::Urgent),
+            Written::Queued(None)
+        );
+        assert_eq!(bus.tx_queued(Some(TxPriority::Urgent)), 1);
+        assert_eq!(bus.tx_queued(None), 2);
+
+        // the urgent write takes the first room the backend makes
+        bus.backend.room = 1;
+        assert_eq!(bus.drain_tx_queue(), 1);
+        assert_eq!(bus.tx_queued(Some(TxPriority::Urgent)), 0);
+        bus.backend.room = 2;
+        assert_eq!(bus.drain_tx_queue(), 0);
+        assert_eq!(
+            write(&mut bus, 4, TxPriority::Arbitration),
+            Written::Sent(None)
+        );
+    }
+}
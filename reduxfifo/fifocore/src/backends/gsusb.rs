@@ -0,0 +1,673 @@
+//! gs_usb backend, for candleLight, CANable and other adapters speaking the protocol of the Linux
+//! `gs_usb` driver.
+//!
+//! Params are `gsusb:<usb serial>`, or `gsusb:<usb serial>:<channel>` for adapters with more than
+//! one channel. The adapter has to be plugged in when the bus is opened, since its vendor and
+//! product id are looked up by serial; after that it is followed across reconnects like RdxUSB
+//! devices are.
+//!
+//! Channels are started at the bus's `bitrate` option (1 Mbit/s by default) when the adapter
+//! connects, with hardware timestamps if the adapter has them. Those count microseconds on the
+//! adapter's own 32-bit clock and are mapped onto the [`crate::timebase`] here; adapters without
+//! them fall back to host arrival times. Only classic CAN is supported.
+//!
+//! Every frame written is echoed back by the adapter once it's on the wire, which is how
+//! [`BusOptions::confirm_tx`](crate::bus_options::BusOptions::confirm_tx) is honored.
+
+use std::{sync::Arc, time::Duration};
+
+use nusb::{
+    DeviceInfo, MaybeFuture,
+    transfer::{ControlIn, ControlOut, ControlType, Recipient},
+};
+use parking_lot::Mutex;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    sync::mpsc::error::TryRecvError,
+};
+
+use crate::{
+    MessageIdBuilder, ReduxFIFOMessage,
+    backends::{
+        Backend, SessionTable,
+        usb::{
+            BulkIn, BulkOut, Sessions, UsbDevice, UsbDeviceId, UsbError, UsbEventLoop, UsbSession,
+            UsbSessionState,
+        },
+    },
+    bus_info::BackendInfo,
+    error::Error,
+    log_debug, log_error, log_trace,
+    timestamp::TimestampSource,
+    utilization::DEFAULT_BITRATE,
+};
+
+/// Vendor and product ids of adapters known to speak gs_usb, as listed by the Linux driver.
+const GS_USB_IDS: [(u16, u16); 4] = [
+    // candleLight, CANable and most other open hardware
+    (0x1d50, 0x606f),
+    (0x1209, 0x2323),
+    // CES CANext FD
+    (0x1cd2, 0x606f),
+    // ABE CANdebugger FD
+    (0x16d0, 0x10b8),
+];
+
+/// Control requests, from the Linux driver's `enum gs_usb_breq`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy)]
+enum Breq {
+    HostFormat = 0,
+    BitTiming = 1,
+    Mode = 2,
+    BtConst = 4,
+    DeviceConfig = 5,
+}
+
+/// Tells the adapter we're little-endian.
+const HOST_FORMAT: u32 = 0x0000_beef;
+const MODE_RESET: u32 = 0;
+const MODE_START: u32 = 1;
+/// Same bit in [`BtConst::feature`] and [`DeviceMode::flags`]
+const FEATURE_HW_TIMESTAMP: u32 = 1 << 4;
+
+/// `can_id` flags, which are Linux's
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+/// Host frame flag for frames the adapter had to drop before this one
+const FRAME_FLAG_OVERFLOW: u8 = 1 << 0;
+/// Echo id of received frames; anything else is one of ours coming back
+const ECHO_ID_RX: u32 = 0xffff_ffff;
+
+/// Host frame header: echo id, CAN id, DLC, channel, flags and a reserved byte
+const FRAME_HEADER_LEN: usize = 12;
+/// Host frames without a timestamp, which is also how they are sent
+const FRAME_LEN: usize = FRAME_HEADER_LEN + 8;
+const FRAME_LEN_TIMESTAMPED: usize = FRAME_LEN + 4;
+
+/// Adapter clock readings later than the lowest latency seen by this much, in microseconds, are
+/// taken as the adapter's clock having drifted slow and resync it.
+const CLOCK_SLIP_US: i64 = 100_000;
+
+/// `struct gs_device_config`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DeviceConfig {
+    reserved: [u8; 3],
+    /// Number of channels, less one
+    icount: u8,
+    sw_version: u32,
+    hw_version: u32,
+}
+
+/// `struct gs_device_bt_const`: what bit timings a channel can do.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BtConst {
+    feature: u32,
+    /// CAN clock in Hz
+    fclk_can: u32,
+    tseg1_min: u32,
+    tseg1_max: u32,
+    tseg2_min: u32,
+    tseg2_max: u32,
+    sjw_max: u32,
+    brp_min: u32,
+    brp_max: u32,
+    brp_inc: u32,
+}
+
+/// `struct gs_device_bittiming`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+struct BitTiming {
+    prop_seg: u32,
+    phase_seg1: u32,
+    phase_seg2: u32,
+    sjw: u32,
+    brp: u32,
+}
+
+/// `struct gs_device_mode`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DeviceMode {
+    mode: u32,
+    flags: u32,
+}
+
+impl BtConst {
+    /// Bit timing for `bitrate` with the sample point as close to 87.5% as the channel allows, or
+    /// `None` if the CAN clock can't be divided down to it exactly.
+    fn bit_timing(&self, bitrate: u32) -> Option<BitTiming> {
+        let brp_inc = self.brp_inc.max(1);
+        let mut brp = self.brp_min.max(1);
+        while brp <= self.brp_max {
+            let divisor = brp.checked_mul(bitrate)?;
+            if let Some(tq) = self.fclk_can.checked_div(divisor)
+                && tq * divisor == self.fclk_can
+                && let Some(timing) = self.segments(tq, brp)
+            {
+                return Some(timing);
+            }
+            brp += brp_inc;
+        }
+        None
+    }
+
+    /// Splits a bit of `tq` time quanta into segments, if the channel's limits allow it.
+    fn segments(&self, tq: u32, brp: u32) -> Option<BitTiming> {
+        // one quantum goes to the sync segment
+        let tseg2 = (tq / 8).clamp(self.tseg2_min, self.tseg2_max);
+        let tseg1 = tq.checked_sub(1 + tseg2)?;
+        if !(self.tseg1_min..=self.tseg1_max).contains(&tseg1) {
+            return None;
+        }
+        Some(BitTiming {
+            prop_seg: 0,
+            phase_seg1: tseg1,
+            phase_seg2: tseg2,
+            sjw: tseg2.min(self.sjw_max).max(1),
+            brp,
+        })
+    }
+}
+
+/// `struct gs_host_frame` for classic CAN, the unit of bulk transfers in both directions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HostFrame {
+    echo_id: u32,
+    can_id: u32,
+    dlc: u8,
+    channel: u8,
+    flags: u8,
+    data: [u8; 8],
+    /// Adapter clock in microseconds, on adapters started with hardware timestamps
+    timestamp_us: Option<u32>,
+}
+
+impl HostFrame {
+    fn from_message(msg: &ReduxFIFOMessage, channel: u8, echo_id: u32) -> Self {
+        let mut can_id = msg.id();
+        if !msg.short_id() {
+            can_id |= CAN_EFF_FLAG;
+        }
+        if msg.rtr() {
+            can_id |= CAN_RTR_FLAG;
+        }
+        let dlc = msg.data_size.min(8);
+        let mut data = [0u8; 8];
+        data[..dlc as usize].copy_from_slice(&msg.data[..dlc as usize]);
+        Self {
+            echo_id,
+            can_id,
+            dlc,
+            channel,
+            flags: 0,
+            data,
+            timestamp_us: None,
+        }
+    }
+
+    /// Decodes a frame of [`FRAME_LEN`] or [`FRAME_LEN_TIMESTAMPED`] bytes.
+    fn decode(buf: &[u8]) -> Self {
+        let word = |at: usize| u32::from_le_bytes(buf[at..at + 4].try_into().unwrap());
+        Self {
+            echo_id: word(0),
+            can_id: word(4),
+            dlc: buf[8],
+            channel: buf[9],
+            flags: buf[10],
+            data: buf[FRAME_HEADER_LEN..FRAME_LEN].try_into().unwrap(),
+            timestamp_us: (buf.len() >= FRAME_LEN_TIMESTAMPED).then(|| word(FRAME_LEN)),
+        }
+    }
+
+    /// Appends the frame as sent to the adapter, which is without a timestamp.
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.echo_id.to_le_bytes());
+        out.extend_from_slice(&self.can_id.to_le_bytes());
+        out.extend_from_slice(&[self.dlc, self.channel, self.flags, 0]);
+        out.extend_from_slice(&self.data);
+    }
+
+    /// The frame as a message, with the adapter's timestamp if it has one already mapped to the
+    /// host timebase as `timestamp`, or 0.
+    fn to_message(self, timestamp: u64) -> ReduxFIFOMessage {
+        let dlc = self.dlc.min(8);
+        let mut data = [0u8; 64];
+        data[..8].copy_from_slice(&self.data);
+        ReduxFIFOMessage {
+            message_id: MessageIdBuilder::new(self.can_id)
+                .err(self.can_id & CAN_ERR_FLAG != 0)
+                .rtr(self.can_id & CAN_RTR_FLAG != 0)
+                .short_id(self.can_id & CAN_EFF_FLAG == 0)
+                .build(),
+            bus_id: self.channel as u16,
+            flags: ReduxFIFOMessage::FLAG_NO_FD,
+            data_size: dlc,
+            timestamp,
+            data,
+        }
+    }
+}
+
+/// Maps an adapter's free-running 32-bit microsecond clock onto the host timebase.
+///
+/// The lowest delay seen between the adapter's clock and frames arriving here is taken as the USB
+/// latency, which also keeps up with an adapter clock that runs fast. One that runs slow is
+/// resynced once it has slipped by [`CLOCK_SLIP_US`].
+#[derive(Debug, Default)]
+struct AdapterClock {
+    last_raw: Option<u32>,
+    wraps: u64,
+    offset: Option<i64>,
+}
+
+impl AdapterClock {
+    fn host_time(&mut self, raw: u32, now: u64) -> u64 {
+        if self.last_raw.is_some_and(|last| raw < last) {
+            self.wraps += 1;
+        }
+        self.last_raw = Some(raw);
+        let adapter = ((self.wraps << 32) | raw as u64) as i64;
+        let delay = now as i64 - adapter;
+        let offset = match self.offset {
+            Some(offset) if delay >= offset && delay - offset <= CLOCK_SLIP_US => offset,
+            _ => delay,
+        };
+        self.offset = Some(offset);
+        (adapter + offset).max(0) as u64
+    }
+
+    /// Starts over, for when the adapter reconnects with its clock reset.
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Whether a USB device is a gs_usb adapter, going by its vendor and product id.
+pub fn is_gs_usb(device_info: &DeviceInfo) -> bool {
+    GS_USB_IDS.contains(&(device_info.vendor_id(), device_info.product_id()))
+}
+
+/// Finds the connected gs_usb adapter with a USB serial number of `serial`.
+fn find_adapter(serial: &str) -> Result<UsbDeviceId, Error> {
+    nusb::list_devices()
+        .wait()
+        .map_err(|_| Error::UsbEnumerateFail)?
+        .find(|info| info.serial_number() == Some(serial) && is_gs_usb(info))
+        .map(|info| UsbDeviceId::new(info.vendor_id(), info.product_id(), serial.to_string()))
+        .ok_or(Error::UsbDeviceNotFound)
+}
+
+/// An adapter opened and started, ready for bulk transfers.
+struct Adapter {
+    tx_ep: BulkOut,
+    rx_ep: BulkIn,
+    hw_timestamps: bool,
+}
+
+async fn gsusb_loop(
+    mut usb_ses: UsbDevice,
+    mut tx_msgs: tokio::sync::mpsc::Receiver<(ReduxFIFOMessage, u16)>,
+    sessions: Sessions,
+) {
+    log_trace!("gsusb: start new eventloop for {:?}", usb_ses.device_id);
+    let mut clock = AdapterClock::default();
+    loop {
+        let Ok(device_info) = usb_ses.devinfo().await else {
+            return;
+        };
+        let adapter = match run_device(device_info.clone(), &sessions).await {
+            Ok(adapter) => adapter,
+            Err(e) => {
+                log_error!(
+                    "gsusb: Device open failed for {:?}: {e:?}",
+                    usb_ses.device_id
+                );
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        if !adapter.hw_timestamps {
+            log_debug!("gsusb: {:?} has no hardware timestamps", usb_ses.device_id);
+        }
+        clock.reset();
+        usb_ses.set_opened(&device_info);
+
+        let frame_len = match adapter.hw_timestamps {
+            true => FRAME_LEN_TIMESTAMPED,
+            false => FRAME_LEN,
+        };
+        let tx_fut = run_tx(adapter.tx_ep, &mut tx_msgs);
+        let rx_fut = run_rx(adapter.rx_ep, frame_len, sessions.clone(), &mut clock);
+        tokio::select! {
+            Err(e) = tx_fut => { log_error!("gsusb: TX closed: {e:?}"); }
+            Err(e) = rx_fut => { log_error!("gsusb: RX closed: {e:?}"); }
+        }
+        usb_ses.set_closed();
+    }
+}
+
+async fn control_out<T: bytemuck::Pod>(
+    iface: &nusb::Interface,
+    request: Breq,
+    value: u16,
+    data: &T,
+) -> Result<(), UsbError> {
+    iface
+        .control_out(
+            ControlOut {
+                control_type: ControlType::Vendor,
+                recipient: Recipient::Interface,
+                request: request as u8,
+                value,
+                index: iface.interface_number() as u16,
+                data: bytemuck::bytes_of(data),
+            },
+            Duration::from_secs(1),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn control_in<T: bytemuck::Pod>(
+    iface: &nusb::Interface,
+    request: Breq,
+    value: u16,
+) -> Result<T, UsbError> {
+    let res = iface
+        .control_in(
+            ControlIn {
+                control_type: ControlType::Vendor,
+                recipient: Recipient::Interface,
+                request: request as u8,
+                value,
+                index: iface.interface_number() as u16,
+                length: core::mem::size_of::<T>() as u16,
+            },
+            Duration::from_secs(1),
+        )
+        .await?;
+    bytemuck::try_pod_read_unaligned(&res).map_err(|_| UsbError::InvalidDevInfo)
+}
+
+/// Opens the adapter and starts every channel on it, at the bitrate of the bus open on that
+/// channel if there is one.
+async fn run_device(device_info: DeviceInfo, sessions: &Sessions) -> Result<Adapter, UsbError> {
+    // gs_usb adapters have the one vendor-specific interface
+    let Some(iface_idx) = device_info
+        .interfaces()
+        .find(|iface| iface.class() == 0xff)
+        .map(|iface| iface.interface_number())
+    else {
+        return Err(UsbError::InterfaceMissing);
+    };
+    let handle = device_info.open().await?;
+    // not all platforms will do this successfully, so this is a best-faith effort.
+    handle.detach_kernel_driver(iface_idx).ok();
+    let iface = handle.claim_interface(iface_idx).await?;
+    let Some(iface_desc) = handle
+        .active_configuration()
+        .map_err(|_| UsbError::InterfaceMissing)?
+        .interface_alt_settings()
+        .find(|iface| iface.interface_number() == iface_idx)
+    else {
+        return Err(UsbError::InterfaceMissing);
+    };
+    let mut ep_num_out = None;
+    let mut ep_num_in = None;
+    for ep_desc in iface_desc.endpoints() {
+        match ep_desc.direction() {
+            nusb::transfer::Direction::Out => ep_num_out.get_or_insert(ep_desc.address()),
+            nusb::transfer::Direction::In => ep_num_in.get_or_insert(ep_desc.address()),
+        };
+    }
+    let (Some(ep_num_out), Some(ep_num_in)) = (ep_num_out, ep_num_in) else {
+        return Err(UsbError::InterfaceMissing);
+    };
+
+    // newer firmware ignores this, but older firmware wants it before anything else
+    control_out(&iface, Breq::HostFormat, 1, &HOST_FORMAT)
+        .await
+        .ok();
+    let config: DeviceConfig = control_in(&iface, Breq::DeviceConfig, 1).await?;
+    log_debug!(
+        "gsusb: {} channel(s), firmware {}, hardware {}",
+        config.icount as u16 + 1,
+        config.sw_version,
+        config.hw_version
+    );
+
+    let mut hw_timestamps = true;
+    for channel in 0..=config.icount as u16 {
+        let bt_const: BtConst = control_in(&iface, Breq::BtConst, channel).await?;
+        let bitrate = sessions
+            .lock()
+            .get(&channel)
+            .and_then(|ses_table| ses_table.lock().options.bitrate)
+            .unwrap_or(DEFAULT_BITRATE);
+        let Some(timing) = bt_const.bit_timing(bitrate) else {
+            log_error!(
+                "gsusb: channel {channel} can't run at {bitrate} bit/s off a {} Hz clock",
+                bt_const.fclk_can
+            );
+            return Err(UsbError::Other);
+        };
+        hw_timestamps &= bt_const.feature & FEATURE_HW_TIMESTAMP != 0;
+
+        let reset = DeviceMode {
+            mode: MODE_RESET,
+            flags: 0,
+        };
+        control_out(&iface, Breq::Mode, channel, &reset).await?;
+        control_out(&iface, Breq::BitTiming, channel, &timing).await?;
+        let start = DeviceMode {
+            mode: MODE_START,
+            flags: bt_const.feature & FEATURE_HW_TIMESTAMP,
+        };
+        control_out(&iface, Breq::Mode, channel, &start).await?;
+    }
+
+    Ok(Adapter {
+        tx_ep: iface.endpoint(ep_num_out)?,
+        rx_ep: iface.endpoint(ep_num_in)?,
+        hw_timestamps,
+    })
+}
+
+async fn run_tx(
+    tx_ep: BulkOut,
+    msgs: &mut tokio::sync::mpsc::Receiver<(ReduxFIFOMessage, u16)>,
+) -> Result<(), UsbError> {
+    // one frame per transfer, as the adapter expects
+    let mut writer = tx_ep.writer(FRAME_LEN).with_num_transfers(2);
+    let mut out_queue = Vec::new();
+    let mut echo_id = 0_u32;
+
+    loop {
+        let Some(pair) = msgs.recv().await else {
+            return Ok(());
+        };
+        let mut pair = Some(pair);
+        while let Some((msg, chn)) = pair {
+            HostFrame::from_message(&msg, chn as u8, echo_id).encode(&mut out_queue);
+            // never the id of received frames
+            echo_id = echo_id.wrapping_add(1) & 0x7fff_ffff;
+
+            pair = match msgs.try_recv() {
+                Ok(p) => Some(p),
+                Err(TryRecvError::Disconnected) => {
+                    return Ok(());
+                }
+                Err(TryRecvError::Empty) => None,
+            }
+        }
+        writer.write_all(&out_queue).await?;
+        writer.flush().await?;
+        out_queue.clear();
+    }
+}
+
+async fn run_rx(
+    rx_ep: BulkIn,
+    frame_len: usize,
+    sessions: Sessions,
+    clock: &mut AdapterClock,
+) -> Result<(), UsbError> {
+    let reader = rx_ep.reader(64).with_num_transfers(2);
+    let mut buf_reader = tokio::io::BufReader::new(reader);
+    let mut buf = [0_u8; FRAME_LEN_TIMESTAMPED];
+
+    loop {
+        buf_reader.read_exact(&mut buf[..frame_len]).await?;
+        let frame = HostFrame::decode(&buf[..frame_len]);
+        let now = crate::timebase::now_us() as u64;
+        let timestamp = frame
+            .timestamp_us
+            .map_or(0, |raw| clock.host_time(raw, now));
+        let mut msg = frame.to_message(timestamp);
+        let channel_id = msg.bus_id;
+
+        let meta_ses = sessions.lock();
+        let Some(bus) = meta_ses.get(&channel_id) else {
+            continue;
+        };
+
+        let mut ses_lock = bus.lock();
+        // we need to reassign the bus id here to the actually reduxfifo-mapped bus id
+        msg.bus_id = ses_lock.bus_id;
+        if frame.flags & FRAME_FLAG_OVERFLOW != 0 {
+            ses_lock.status.rx_overrun(1);
+        }
+        if ses_lock.status.error_frame(&msg) {
+            continue;
+        }
+        if frame.echo_id != ECHO_ID_RX {
+            // our own write made it onto the wire
+            if msg.timestamp == 0 {
+                msg.timestamp = now;
+            }
+            ses_lock.tx.confirm(&msg);
+            continue;
+        }
+        ses_lock.ingest_message(msg);
+    }
+}
+
+#[derive(Debug)]
+pub struct GsUsbBackend {
+    params: Params,
+    handle: Arc<UsbSession>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Params {
+    serial: String,
+    channel: u16,
+}
+
+impl GsUsbBackend {
+    fn parse_params(s: &str) -> Result<Params, Error> {
+        // gsusb:[serial][:channel]
+        let args = s.strip_prefix("gsusb:").ok_or(Error::BusNotSupported)?;
+        let (serial, channel) = match args.split_once(':') {
+            Some((serial, channel)) => (
+                serial,
+                channel.parse::<u16>().map_err(|_| Error::InvalidBus)?,
+            ),
+            None => (args, 0),
+        };
+        if serial.is_empty() {
+            return Err(Error::InvalidBus);
+        }
+        Ok(Params {
+            serial: serial.to_string(),
+            channel,
+        })
+    }
+
+    pub fn open(
+        bus_id: u16,
+        params: &str,
+        runtime: tokio::runtime::Handle,
+        ses_table: Arc<Mutex<SessionTable<<Self as Backend>::State>>>,
+        usb_event_loop: Arc<Mutex<UsbEventLoop>>,
+    ) -> Result<Self, Error> {
+        log_debug!("open gsusb: {bus_id}");
+        let params = match Self::parse_params(params) {
+            Ok(p) => p,
+            Err(e) => {
+                log_error!("Invalid gs_usb bus string {params}");
+                log_error!("Bus strings are expected for the form `gsusb:[usb serial][:channel]`");
+                return Err(e);
+            }
+        };
+        let usb_device_id = find_adapter(&params.serial).inspect_err(|_| {
+            log_error!("gsusb: no gs_usb adapter with serial {}", params.serial);
+        })?;
+
+        let handle = {
+            log_trace!("gsusb: request open device");
+            let mut eloop = usb_event_loop.lock();
+            eloop.open(
+                usb_device_id,
+                params.channel,
+                runtime,
+                ses_table,
+                "gsusb",
+                gsusb_loop,
+            )
+        };
+
+        // USB device is already claimed by some other backend
+        if handle.tag() != "gsusb" {
+            return Err(Error::BusDeviceBusy);
+        }
+
+        Ok(Self { params, handle })
+    }
+}
+
+impl Backend for GsUsbBackend {
+    type State = UsbSessionState;
+    const TIMESTAMPS: TimestampSource = TimestampSource::Hardware;
+
+    fn start_session(
+        &mut self,
+        _msg_count: u32,
+        _config: &crate::ReduxFIFOSessionConfig,
+    ) -> Result<Self::State, Error> {
+        Ok(UsbSessionState {
+            channel: self.params.channel,
+        })
+    }
+
+    fn write_single(&mut self, msg: &ReduxFIFOMessage) -> Result<(), Error> {
+        if msg.data_size as usize > self.max_packet_size() {
+            return Err(Error::DataTooLong);
+        }
+        self.handle
+            .msg_tx()
+            .try_send((*msg, self.params.channel))
+            .map_err(|_| Error::BusBufferFull)
+    }
+
+    fn params_match(&self, params: &str) -> bool {
+        Self::parse_params(params).is_ok_and(|params| params == self.params)
+    }
+
+    fn max_packet_size(&self) -> usize {
+        8
+    }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo::GsUsb(self.handle.info(self.params.channel))
+    }
+
+    fn confirms_tx(&self) -> bool {
+        true
+    }
+}
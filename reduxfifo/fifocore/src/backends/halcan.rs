@@ -1,7 +1,9 @@
 use std::{sync::Arc, time::Duration};
 
 use crate::backends::{Backend, BackendOpen, SessionTable};
+use crate::bus_info::BackendInfo;
 use crate::error::Error;
+use crate::timestamp::TimestampSource;
 use crate::timebase::monotonic_us;
 use crate::{ReduxFIFOMessage, ReduxFIFOSessionConfig, log_debug, log_error, log_trace, timebase};
 use parking_lot::Mutex;
@@ -44,6 +46,7 @@ async fn halcan_read_loop(bus_id: u16, sessions: Arc<Mutex<SessionTable<HALFIFOS
     loop {
         {
             let mut logged_messages = false;
+            let mut overruns = 0;
             let mut ses_lock = sessions.lock();
 
             ses_lock.iter_sessions_halcan_use_only(|ses, id_cache, logger| {
@@ -56,8 +59,9 @@ async fn halcan_read_loop(bus_id: u16, sessions: Arc<Mutex<SessionTable<HALFIFOS
                 let should_log = !logged_messages && ses.config.filter_id == 0x0e_0000;
 
                 // translate the messages and add them to the buffer
-                for ent in &ses.backend_state.hal_buf[..count.min(ses.backend_state.hal_buf.len())]
-                {
+                // (indexed, since adding a message borrows the whole session)
+                for idx in 0..count.min(ses.backend_state.hal_buf.len()) {
+                    let ent = ses.backend_state.hal_buf[idx];
                     let mut data = [0u8; 64];
                     data[..8].copy_from_slice(&ent.data);
                     let message_id = ent.messageID;
@@ -77,27 +81,30 @@ async fn halcan_read_loop(bus_id: u16, sessions: Arc<Mutex<SessionTable<HALFIFOS
                         data,
                     };
 
-                    ses.read_buf.add_message(msg);
+                    ses.add_message(msg);
 
                     // update the id cache
                     id_cache.update(message_id, timestamp);
                     if should_log {
                         if let Some(logger) = logger.as_ref() {
-                            logger.try_send(msg).ok();
+                            crate::limits::send_to_log(logger, msg);
                         }
                     }
                 }
 
                 if count > 0 {
-                    ses.update_rx_notifier();
+                    ses.notify_dispatched(timebase::now_us() as u64);
                 }
-                if let Some(e) = maybe_err {
-                    log_error!("Got HALError: {e}, {}", e.0);
+                match maybe_err {
+                    Some(HALError(wpihal_rio::can::ERR_CAN_BUFFER_OVERRUN)) => overruns += 1,
+                    Some(e) => log_error!("Got HALError: {e}, {}", e.0),
+                    None => {}
                 }
                 if should_log {
                     logged_messages = true;
                 }
             });
+            ses_lock.status.rx_overrun(overruns);
 
             drop(ses_lock);
         }
@@ -107,6 +114,8 @@ async fn halcan_read_loop(bus_id: u16, sessions: Arc<Mutex<SessionTable<HALFIFOS
 
 impl Backend for HalCanBackend {
     type State = HALFIFOSession;
+    // HAL sessions are filled by the read loop directly, already stamped by the driver
+    const TIMESTAMPS: TimestampSource = TimestampSource::Driver;
     fn params_match(&self, params: &str) -> bool {
         params.starts_with("halcan")
     }
@@ -149,6 +158,10 @@ impl Backend for HalCanBackend {
     fn max_packet_size(&self) -> usize {
         8
     }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo::HalCan
+    }
 }
 
 impl Drop for HalCanBackend {
@@ -2,14 +2,14 @@ use std::{sync::Arc, time::Duration};
 
 use nusb::{
     DeviceInfo,
-    transfer::{ControlIn, ControlType, Recipient},
+    transfer::{ControlIn, ControlOut, ControlType, Recipient},
 };
 use parking_lot::Mutex;
-use rdxusb_protocol::{RdxUsbCtrl, RdxUsbDeviceInfo, RdxUsbPacket};
+use rdxusb_protocol::{CONSOLE_CHANNEL, RdxUsbCtrl, RdxUsbDeviceInfo, RdxUsbPacket};
 use rustc_hash::FxHashMap;
 use tokio::{
     io::{AsyncReadExt as _, AsyncWriteExt},
-    sync::mpsc::error::TryRecvError,
+    sync::broadcast,
 };
 
 use crate::{
@@ -17,12 +17,14 @@ use crate::{
     backends::{
         Backend, SessionTable,
         usb::{
-            BulkIn, BulkOut, UsbDevice, UsbDeviceId, UsbError, UsbEventLoop, UsbSession,
-            UsbSessionState,
+            BulkIn, BulkOut, ConsoleSender, UsbDevice, UsbDeviceId, UsbError, UsbEventLoop,
+            UsbSession, UsbSessionState, firmware_version,
         },
     },
+    bus_info::BackendInfo,
     error::Error,
     log_debug, log_error, log_trace,
+    write_batch::{self, MAX_WRITE_BATCH},
 };
 
 impl From<ReduxFIFOMessage> for RdxUsbPacket {
@@ -43,7 +45,11 @@ impl From<ReduxFIFOMessage> for RdxUsbPacket {
         Self {
             message_id,
             channel: 0,
-            reserved: 0,
+            flags: if value.tx() {
+                rdxusb_protocol::PACKET_FLAG_TX_ACK
+            } else {
+                0
+            },
             data_size: value.data_size,
             timestamp_ns: 0,
             data: value.data,
@@ -77,7 +83,7 @@ async fn rdxusb_loop(
         let Ok(device_info) = usb_ses.devinfo().await else {
             return;
         };
-        let (tx_ep, rx_ep) = match run_device(device_info).await {
+        let (tx_ep, rx_ep, has_console, has_tx_ack) = match run_device(device_info.clone()).await {
             Ok(d) => d,
             Err(e) => {
                 log_error!(
@@ -92,24 +98,48 @@ async fn rdxusb_loop(
             "rdxusb: device opened successfully: {:?}",
             usb_ses.device_id
         );
+        if !has_console {
+            log_debug!("rdxusb: {:?} has no console", usb_ses.device_id);
+        }
+        if !has_tx_ack {
+            log_debug!(
+                "rdxusb: {:?} does not acknowledge transmits",
+                usb_ses.device_id
+            );
+        }
+
+        usb_ses.set_opened(&device_info);
 
         let tx_fut = run_tx(tx_ep, &mut tx_msgs);
-        let rx_fut = run_rx(rx_ep, sessions.clone());
+        let rx_fut = run_rx(rx_ep, sessions.clone(), &usb_ses.console);
         tokio::select! {
             Err(e) = tx_fut => { log_error!("rdxusb: TX closed: {e:?}"); }
             Err(e) = rx_fut => { log_error!("rdxusb: RX closed: {e:?}"); }
         }
+        usb_ses.set_closed();
     }
 }
 
-async fn run_device(device_info: DeviceInfo) -> Result<(BulkOut, BulkIn), UsbError> {
-    let Some(iface) = device_info
+/// Whether a USB device exposes an RdxUSB interface.
+fn has_rdxusb_interface(device_info: &DeviceInfo) -> bool {
+    rdxusb_interface(device_info).is_some()
+}
+
+fn rdxusb_interface(device_info: &DeviceInfo) -> Option<u8> {
+    device_info
         .interfaces()
         .find(|iface| iface.class() == 0xff && iface.subclass() == 0x0 && iface.protocol() == 0x0)
-    else {
+        .map(|iface| iface.interface_number())
+}
+
+/// Opens the device, returning its endpoints and whether it has a console and acknowledges
+/// transmits.
+async fn run_device(
+    device_info: DeviceInfo,
+) -> Result<(BulkOut, BulkIn, bool, bool), UsbError> {
+    let Some(iface_idx) = rdxusb_interface(&device_info) else {
         return Err(UsbError::InterfaceMissing);
     };
-    let iface_idx = iface.interface_number();
 
     let mut handle = Err(UsbError::Other);
     for _ in 0..3 {
@@ -164,53 +194,67 @@ async fn run_device(device_info: DeviceInfo) -> Result<(BulkOut, BulkIn), UsbErr
         .await?;
     let rdxusb_info = bytemuck::try_from_bytes::<RdxUsbDeviceInfo>(&res.as_slice())
         .map_err(|_| UsbError::InvalidDevInfo)?;
-    if (
-        rdxusb_info.protocol_version_major,
-        rdxusb_info.protocol_version_minor,
-    ) != (2, 0)
-    {
-        return Err(UsbError::WrongProtocolVersion(2, 0));
+    // minor versions only add to the protocol
+    if rdxusb_info.protocol_version_major != rdxusb_protocol::PROTOCOL_VERSION_MAJOR_FS {
+        return Err(UsbError::WrongProtocolVersion(
+            rdxusb_protocol::PROTOCOL_VERSION_MAJOR_FS,
+            0,
+        ));
     }
+    let has_console = rdxusb_info.protocol_version_minor
+        >= rdxusb_protocol::PROTOCOL_VERSION_MINOR_CONSOLE
+        && rdxusb_info.has_console();
+    let has_tx_ack = rdxusb_info.protocol_version_minor
+        >= rdxusb_protocol::PROTOCOL_VERSION_MINOR_TX_ACK
+        && rdxusb_info.has_tx_ack();
 
     let tx_ep = iface.endpoint(ep_num_out.unwrap())?;
     let rx_ep = iface.endpoint(ep_num_in.unwrap())?;
 
-    Ok((tx_ep, rx_ep))
+    Ok((tx_ep, rx_ep, has_console, has_tx_ack))
 }
 
+/// Bytes per bulk OUT transfer: a full batch of the largest packets, in whole USB packets.
+const TX_TRANSFER_SIZE: usize = (MAX_WRITE_BATCH * RdxUsbPacket::SIZE).next_multiple_of(64);
+
 async fn run_tx(
     tx_ep: BulkOut,
     msgs: &mut tokio::sync::mpsc::Receiver<(ReduxFIFOMessage, u16)>,
 ) -> Result<(), UsbError> {
-    let mut writer = tx_ep.writer(64).with_num_transfers(2);
-    let mut out_queue = Vec::new();
+    let mut writer = tx_ep.writer(TX_TRANSFER_SIZE).with_num_transfers(2);
+    let mut out_queue = Vec::with_capacity(TX_TRANSFER_SIZE);
+    let mut batch = Vec::with_capacity(MAX_WRITE_BATCH);
 
     loop {
         let Some(pair) = msgs.recv().await else {
             return Ok(());
         };
-        let mut pair = Some(pair);
-        while let Some((msg, chn)) = pair {
+        // everything else already queued goes out in the same transfer; whatever is queued
+        // while it's in flight makes up the next one
+        let open = write_batch::drain_queue(msgs, pair, &mut batch);
+        let frames = batch.len();
+        for (msg, chn) in batch.drain(..) {
             let mut data: RdxUsbPacket = msg.into();
             data.channel = chn;
-            out_queue.extend_from_slice(&bytemuck::bytes_of(&data)[..data.wire_length()]);
-
-            pair = match msgs.try_recv() {
-                Ok(p) => Some(p),
-                Err(TryRecvError::Disconnected) => {
-                    return Ok(());
-                }
-                Err(TryRecvError::Empty) => None,
+            if chn == CONSOLE_CHANNEL {
+                data.message_id = 0;
             }
+            out_queue.extend_from_slice(&bytemuck::bytes_of(&data)[..data.wire_length()]);
         }
         writer.write_all(&out_queue).await?;
+        writer.flush().await?;
+        write_batch::record_write(frames, out_queue.len());
         out_queue.clear();
+        if !open {
+            return Ok(());
+        }
     }
 }
 
 async fn run_rx(
     rx_ep: BulkIn,
     sessions: Arc<Mutex<FxHashMap<u16, Arc<Mutex<SessionTable<UsbSessionState>>>>>>,
+    console: &ConsoleSender,
 ) -> Result<(), UsbError> {
     let reader = rx_ep.reader(64).with_num_transfers(2);
     let mut buf_reader = tokio::io::BufReader::new(reader);
@@ -225,8 +269,14 @@ async fn run_rx(
             .read_exact(&mut packet[16..16 + data_length])
             .await?;
 
-        let mut msg: ReduxFIFOMessage = (*RdxUsbPacket::from_buf(&packet)).into();
-        msg.timestamp = crate::timebase::now_us() as u64;
+        let packet = RdxUsbPacket::from_buf(&packet);
+        if packet.channel == CONSOLE_CHANNEL {
+            // nobody listening is fine; console output just goes nowhere
+            let _ = console.send(packet.data[..data_length].to_vec());
+            continue;
+        }
+
+        let mut msg: ReduxFIFOMessage = (*packet).into();
         let channel_id = msg.bus_id;
 
         let meta_ses = sessions.lock();
@@ -237,10 +287,277 @@ async fn run_rx(
         let mut ses_lock = bus.lock();
         // we need to reassign the bus id here to the actually reduxfifo-mapped bus id
         msg.bus_id = ses_lock.bus_id;
+        if packet.tx_ack() {
+            // the device's clock isn't ours, so like received frames this goes by arrival time
+            msg.timestamp = crate::timebase::now_us() as u64;
+            ses_lock.tx.confirm(&msg);
+            continue;
+        }
         ses_lock.ingest_message(msg);
     }
 }
 
+/// Finds the connected RdxUSB device with a USB serial number of `serial`.
+pub async fn find_device(serial: &str) -> Result<UsbDeviceId, Error> {
+    nusb::list_devices()
+        .await
+        .map_err(|_| Error::UsbDeviceNotFound)?
+        .find(|info| info.serial_number() == Some(serial) && has_rdxusb_interface(info))
+        .map(|info| UsbDeviceId::new(info.vendor_id(), info.product_id(), serial.to_string()))
+        .ok_or(Error::UsbDeviceNotFound)
+}
+
+/// Where a USB device stands with respect to DFU, going by its DFU interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DfuState {
+    /// No DFU interface
+    None,
+    /// Running firmware that can be switched into DFU mode
+    Runtime,
+    /// Sitting in the bootloader waiting to be flashed
+    Dfu,
+}
+
+fn dfu_state(device_info: &DeviceInfo) -> DfuState {
+    device_info
+        .interfaces()
+        .filter(|iface| {
+            iface.class() == rdxusb_protocol::DFU_INTERFACE_CLASS
+                && iface.subclass() == rdxusb_protocol::DFU_INTERFACE_SUBCLASS
+        })
+        .map(|iface| match iface.protocol() {
+            rdxusb_protocol::DFU_PROTOCOL_DFU_MODE => DfuState::Dfu,
+            _ => DfuState::Runtime,
+        })
+        .max_by_key(|state| *state == DfuState::Dfu)
+        .unwrap_or(DfuState::None)
+}
+
+/// A Redux device attached over USB, as seen without opening it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct UsbDeviceListing {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_numer: Option<String>,
+    pub product: Option<String>,
+    pub manufacturer: Option<String>,
+    /// Firmware version from the device descriptor's bcdDevice
+    pub firmware: String,
+    pub dfu: DfuState,
+    /// Whether the device can be opened as a bus, i.e. it has an RdxUSB interface
+    pub can_adapter: bool,
+    /// Bus params to open the device with, for CAN adapters
+    pub params: Option<String>,
+    /// Whether this process already has the device open as a bus
+    pub open: bool,
+}
+
+impl UsbDeviceListing {
+    fn new(info: &DeviceInfo) -> Self {
+        let can_adapter = has_rdxusb_interface(info);
+        let serial_numer = info.serial_number().map(str::to_string);
+        Self {
+            vid: info.vendor_id(),
+            pid: info.product_id(),
+            params: serial_numer.as_ref().filter(|_| can_adapter).map(|serial| {
+                format!(
+                    "rdxusb:0.{:04x}.{:04x}.{serial}",
+                    info.vendor_id(),
+                    info.product_id()
+                )
+            }),
+            serial_numer,
+            product: info.product_string().map(str::to_string),
+            manufacturer: info.manufacturer_string().map(str::to_string),
+            firmware: firmware_version(info),
+            dfu: dfu_state(info),
+            can_adapter,
+            open: false,
+        }
+    }
+
+    /// The device's id for matching against open USB sessions, if it has a serial number.
+    pub fn device_id(&self) -> Option<UsbDeviceId> {
+        self.serial_numer
+            .clone()
+            .map(|serial| UsbDeviceId::new(self.vid, self.pid, serial))
+    }
+}
+
+/// Lists the Redux devices attached over USB without opening or claiming any of them.
+///
+/// This includes devices that can't be opened as a bus, such as ones sitting in their bootloader in
+/// DFU mode. A device counts as Redux if it has an RdxUSB interface or its manufacturer string
+/// says so.
+pub async fn list_devices() -> Result<Vec<UsbDeviceListing>, Error> {
+    Ok(nusb::list_devices()
+        .await
+        .map_err(|_| Error::UsbEnumerateFail)?
+        .filter(|info| {
+            has_rdxusb_interface(info)
+                || info
+                    .manufacturer_string()
+                    .is_some_and(|m| m.starts_with("Redux"))
+        })
+        .map(|info| UsbDeviceListing::new(&info))
+        .collect())
+}
+
+/// Byte stream to and from an RdxUSB device's debug console.
+///
+/// Output is only delivered from when the console was opened; there is no scrollback.
+/// The console stays usable across reconnects of the device.
+#[derive(Debug)]
+pub struct Console {
+    session: Arc<UsbSession>,
+    rx: broadcast::Receiver<Vec<u8>>,
+}
+
+impl Console {
+    pub(crate) fn new(session: Arc<UsbSession>) -> Self {
+        Self {
+            rx: session.subscribe_console(),
+            session,
+        }
+    }
+
+    pub fn device_id(&self) -> &UsbDeviceId {
+        self.session.device_id()
+    }
+
+    /// Waits for the next chunk of console output.
+    ///
+    /// If this falls too far behind the device, the oldest output is skipped.
+    pub async fn recv(&mut self) -> Result<Vec<u8>, Error> {
+        loop {
+            match self.rx.recv().await {
+                Ok(bytes) => return Ok(bytes),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    log_debug!(
+                        "rdxusb: console reader for {:?} fell behind, skipped {n} chunks",
+                        self.session.device_id()
+                    );
+                }
+                Err(broadcast::error::RecvError::Closed) => return Err(Error::UsbClosed),
+            }
+        }
+    }
+
+    /// Sends console input to the device, split into packets as needed.
+    pub fn write(&self, bytes: &[u8]) -> Result<(), Error> {
+        for chunk in bytes.chunks(64) {
+            let msg = ReduxFIFOMessage::builder().data(chunk).build();
+            self.session
+                .msg_tx()
+                .try_send((msg, CONSOLE_CHANNEL))
+                .map_err(|_| Error::BusBufferFull)?;
+        }
+        Ok(())
+    }
+}
+
+/// RdxOTA link to a bootloader sitting in DFU mode, for flashing a device over USB alone.
+///
+/// Packets go over [`RdxUsbCtrl::OtaOut`] and [`RdxUsbCtrl::OtaIn`] control requests on the
+/// bootloader's DFU interface, which is claimed for as long as the link is open.
+#[derive(Debug)]
+pub struct DfuOtaLink {
+    device_id: UsbDeviceId,
+    iface: nusb::Interface,
+}
+
+impl DfuOtaLink {
+    /// Opens the bootloader in DFU mode with a USB serial number of `serial`.
+    pub async fn open(serial: &str) -> Result<Self, Error> {
+        let info = nusb::list_devices()
+            .await
+            .map_err(|_| Error::UsbEnumerateFail)?
+            .find(|info| {
+                info.serial_number() == Some(serial) && dfu_state(info) == DfuState::Dfu
+            })
+            .ok_or(Error::UsbDeviceNotFound)?;
+        let Some(iface_idx) = info
+            .interfaces()
+            .find(|iface| {
+                iface.class() == rdxusb_protocol::DFU_INTERFACE_CLASS
+                    && iface.subclass() == rdxusb_protocol::DFU_INTERFACE_SUBCLASS
+                    && iface.protocol() == rdxusb_protocol::DFU_PROTOCOL_DFU_MODE
+            })
+            .map(|iface| iface.interface_number())
+        else {
+            return Err(Error::UsbDeviceNotFound);
+        };
+        let device_id = UsbDeviceId::new(info.vendor_id(), info.product_id(), serial.to_string());
+        let handle = info.open().await.map_err(|e| {
+            log_error!("rdxusb: Could not open {device_id:?} for DFU: {e}");
+            Error::BusDeviceBusy
+        })?;
+        // same as for RdxUSB interfaces, this may not work everywhere
+        handle.detach_kernel_driver(iface_idx).ok();
+        let iface = handle.claim_interface(iface_idx).await.map_err(|e| {
+            log_error!("rdxusb: Could not claim DFU interface of {device_id:?}: {e}");
+            Error::BusDeviceBusy
+        })?;
+        Ok(Self { device_id, iface })
+    }
+
+    pub fn device_id(&self) -> &UsbDeviceId {
+        &self.device_id
+    }
+
+    /// Sends an RdxOTA message with arbitration id `id` to the bootloader.
+    pub async fn send(&self, id: u32, data: &[u8], timeout: Duration) -> Result<(), Error> {
+        if data.len() > 64 {
+            return Err(Error::DataTooLong);
+        }
+        let mut buf = [0u8; 64];
+        buf[..data.len()].copy_from_slice(data);
+        // the bootloader is the only thing on the other end, so address it directly
+        let packet = RdxUsbPacket::new(
+            id | rdxusb_protocol::MESSAGE_ARB_ID_EXT | rdxusb_protocol::MESSAGE_ARB_ID_DEVICE,
+            0,
+            buf,
+            data.len() as u8,
+            0,
+        );
+        self.iface
+            .control_out(
+                ControlOut {
+                    control_type: ControlType::Vendor,
+                    recipient: Recipient::Interface,
+                    request: RdxUsbCtrl::OtaOut as u8,
+                    value: 0,
+                    index: self.iface.interface_number() as u16,
+                    data: &packet.encode()[..packet.wire_length()],
+                },
+                timeout,
+            )
+            .await
+            .map_err(|_| Error::UsbClosed)
+    }
+
+    /// Takes the next RdxOTA message the bootloader has for the host, if it has one.
+    pub async fn poll(&self, timeout: Duration) -> Result<Option<RdxUsbPacket>, Error> {
+        let res = self
+            .iface
+            .control_in(
+                ControlIn {
+                    control_type: ControlType::Vendor,
+                    recipient: Recipient::Interface,
+                    request: RdxUsbCtrl::OtaIn as u8,
+                    value: 0,
+                    index: self.iface.interface_number() as u16,
+                    length: RdxUsbPacket::SIZE as u16,
+                },
+                timeout,
+            )
+            .await
+            .map_err(|_| Error::UsbClosed)?;
+        Ok(RdxUsbPacket::from_slice(&res).map(|(packet, _)| packet))
+    }
+}
+
 fn split_once<'a>(s: &'a str, d: &str) -> Result<(&'a str, &'a str), Error> {
     s.split_once(d).ok_or(Error::InvalidBus)
 }
@@ -249,6 +566,8 @@ fn split_once<'a>(s: &'a str, d: &str) -> Result<(&'a str, &'a str), Error> {
 pub struct RdxUsbBackend {
     params: Params,
     handle: Arc<UsbSession>,
+    /// Ask the device to acknowledge each write
+    confirm_tx: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -302,6 +621,7 @@ impl RdxUsbBackend {
         };
 
         let usb_device_id = UsbDeviceId::new(params.vid, params.pid, params.serial.clone());
+        let confirm_tx = ses_table.lock().tx.enabled();
 
         // ok let's open the device, if we need to.
         let handle = {
@@ -322,7 +642,11 @@ impl RdxUsbBackend {
             return Err(Error::BusDeviceBusy);
         }
 
-        Ok(Self { params, handle })
+        Ok(Self {
+            params,
+            handle,
+            confirm_tx,
+        })
     }
 }
 
@@ -340,9 +664,16 @@ impl Backend for RdxUsbBackend {
     }
 
     fn write_single(&mut self, msg: &crate::ReduxFIFOMessage) -> Result<(), Error> {
+        let mut msg = *msg;
+        // on the way out, FLAG_TX turns into an ack request
+        if self.confirm_tx {
+            msg.flags |= ReduxFIFOMessage::FLAG_TX;
+        } else {
+            msg.flags &= !ReduxFIFOMessage::FLAG_TX;
+        }
         self.handle
             .msg_tx()
-            .try_send((*msg, self.params.channel))
+            .try_send((msg, self.params.channel))
             .map_err(|_| Error::BusBufferFull)
     }
 
@@ -357,4 +688,12 @@ impl Backend for RdxUsbBackend {
     fn max_packet_size(&self) -> usize {
         64
     }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo::RdxUsb(self.handle.info(self.params.channel))
+    }
+
+    fn confirms_tx(&self) -> bool {
+        true
+    }
 }
@@ -0,0 +1,214 @@
+//! Replay backend
+//!
+//! ## Data model
+//! `replay:<path>` plays back a log written by [`crate::logger`] as if it were a live bus, so that
+//! code reading the bus can be tested against recorded traffic. Messages come out with the spacing
+//! they were recorded with, stamped with when they're played, and reach sessions the same way
+//! received frames do. Messages the recording host wrote itself are only delivered to `echo_tx`
+//! sessions, flagged with [`ReduxFIFOMessage::FLAG_TX`], like on the bus they were recorded on.
+//!
+//! The `;speed=N` option plays the log N times faster (or slower, below 1), and `;loop` starts it
+//! over once it ends. Otherwise the bus goes quiet at the end of the log and stays open.
+//!
+//! Writes to the bus are accepted and go nowhere but the echo.
+use std::{
+    io::Read as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use tokio::io::{AsyncReadExt as _, BufReader};
+
+use crate::{
+    ReduxFIFOMessage, ReduxFIFOSessionConfig,
+    backends::{Backend, BackendOpen, SessionTable},
+    bus_info::BackendInfo,
+    dispatch::MAX_DISPATCH_BATCH,
+    error::Error,
+    log_debug, log_error, log_info,
+    logger::{LOG_MAGIC, LogHeader},
+    timebase,
+    timestamp::TimestampSource,
+};
+
+#[derive(Debug)]
+pub struct ReplayBackend {
+    path: String,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ReplayBackend {
+    fn parse_params(s: &str) -> Result<&str, Error> {
+        s.strip_prefix("replay:")
+            .filter(|path| !path.is_empty())
+            .ok_or(Error::InvalidBus)
+    }
+}
+
+/// Reads the next message in a log, or [`None`] at its end.
+///
+/// Label records are skipped, since replayed messages go out without the labels they were
+/// written with.
+async fn read_message(
+    reader: &mut BufReader<tokio::fs::File>,
+    bus_id: u16,
+) -> std::io::Result<Option<ReduxFIFOMessage>> {
+    let mut header = [0_u8; size_of::<LogHeader>()];
+    let mut data = [0_u8; 64];
+    let (header, data_size) = loop {
+        match reader.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let header: LogHeader = bytemuck::pod_read_unaligned(&header);
+        let data_size = header.data_size.min(64);
+        reader.read_exact(&mut data[..data_size as usize]).await?;
+        if !header.is_label() {
+            break (header, data_size);
+        }
+    };
+    Ok(Some(ReduxFIFOMessage {
+        message_id: header.message_id,
+        // logs are per bus, so whatever bus recorded it is this one now
+        bus_id,
+        flags: header.flags & !ReduxFIFOMessage::FLAG_HISTORICAL,
+        data_size,
+        timestamp: header.timestamp,
+        data,
+    }))
+}
+
+/// Plays a log through once, returning how many messages it had.
+async fn replay_once(
+    path: &Path,
+    bus_id: u16,
+    speed: f64,
+    ses_table: &Mutex<SessionTable<()>>,
+) -> std::io::Result<u64> {
+    let mut reader = BufReader::new(tokio::fs::File::open(path).await?);
+    let mut magic = [0_u8; LOG_MAGIC.len()];
+    reader.read_exact(&mut magic).await?;
+
+    let mut batch = Vec::with_capacity(MAX_DISPATCH_BATCH);
+    // the first message's log time, and when it was played
+    let mut start = None;
+    let mut count = 0;
+    while let Some(mut msg) = read_message(&mut reader, bus_id).await? {
+        let now = timebase::now_us() as u64;
+        let (log_start, play_start) = *start.get_or_insert((msg.timestamp, now));
+        let due = play_start + (msg.timestamp.saturating_sub(log_start) as f64 / speed) as u64;
+        // messages that are already due go out together
+        if due > now || batch.len() == MAX_DISPATCH_BATCH || msg.tx() {
+            ses_table.lock().ingest_messages(&mut batch);
+            batch.clear();
+        }
+        if due > now {
+            tokio::time::sleep(Duration::from_micros(due - now)).await;
+        }
+        msg.timestamp = due;
+        if msg.tx() {
+            ses_table.lock().echo_message(msg);
+        } else {
+            batch.push(msg);
+        }
+        count += 1;
+    }
+    ses_table.lock().ingest_messages(&mut batch);
+    Ok(count)
+}
+
+async fn replay_task(path: PathBuf, bus_id: u16, ses_table: Arc<Mutex<SessionTable<()>>>) {
+    let options = ses_table.lock().options;
+    let speed = options.speed.unwrap_or(1.0) as f64;
+    loop {
+        match replay_once(&path, bus_id, speed, &ses_table).await {
+            Ok(count) => log_info!("replay {}: played {count} messages", path.display()),
+            Err(e) => {
+                log_error!("replay {}: {e}", path.display());
+                return;
+            }
+        }
+        if !options.loop_replay {
+            return;
+        }
+    }
+}
+
+impl Backend for ReplayBackend {
+    type State = ();
+    const TIMESTAMPS: TimestampSource = TimestampSource::Replay;
+
+    fn start_session(
+        &mut self,
+        _msg_count: u32,
+        _config: &ReduxFIFOSessionConfig,
+    ) -> Result<Self::State, Error> {
+        Ok(())
+    }
+
+    fn write_single(&mut self, msg: &ReduxFIFOMessage) -> Result<(), Error> {
+        if msg.data_size as usize > self.max_packet_size() {
+            return Err(Error::DataTooLong);
+        }
+        Ok(())
+    }
+
+    fn params_match(&self, params: &str) -> bool {
+        Self::parse_params(params).is_ok_and(|path| path == self.path)
+    }
+
+    fn max_packet_size(&self) -> usize {
+        64
+    }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo::Replay {
+            path: self.path.clone(),
+        }
+    }
+
+    fn replays(&self) -> bool {
+        true
+    }
+}
+
+impl BackendOpen for ReplayBackend {
+    fn open(
+        bus_id: u16,
+        params: &str,
+        runtime: tokio::runtime::Handle,
+        ses_table: Arc<Mutex<SessionTable<Self::State>>>,
+    ) -> Result<Self, Error> {
+        let path = Self::parse_params(params)?.to_string();
+        log_debug!("open replay of {path} as bus {bus_id}");
+
+        // check it's a log up front, so a wrong file fails the open rather than the replay
+        let mut magic = [0_u8; LOG_MAGIC.len()];
+        let is_log = std::fs::File::open(&path)
+            .and_then(|mut file| file.read_exact(&mut magic))
+            .map(|_| &magic == LOG_MAGIC);
+        match is_log {
+            Ok(true) => {}
+            Ok(false) => {
+                log_error!("replay {path}: not a ReduxFIFO log");
+                return Err(Error::FailedToOpenBus);
+            }
+            Err(e) => {
+                log_error!("replay {path}: {e}");
+                return Err(Error::FailedToOpenBus);
+            }
+        }
+
+        let task = runtime.spawn(replay_task(PathBuf::from(&path), bus_id, ses_table));
+        Ok(Self { path, task })
+    }
+}
+
+impl Drop for ReplayBackend {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
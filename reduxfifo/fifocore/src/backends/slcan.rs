@@ -1,3 +1,20 @@
+//! slcan backend, for serial-line CAN adapters like the CANable.
+//!
+//! Params are `slcan:<baud>:<serial port>`, where the baud is that of the serial port. The bus is
+//! opened at the `bitrate` option, or 1 Mbit/s.
+//!
+//! `slcan:auto:<serial port>` instead finds the bitrate by listening: the adapter is opened
+//! listen-only at each of [`AUTO_BITRATES`] in turn until it hears a valid frame, and only then
+//! opened read-write, so that a wrong guess never puts error frames on the bus. A quiet bus is
+//! probed until it isn't; writes wait in the queue until then, and are refused as
+//! [`Error::BusBufferFull`] once it fills. The serial port is opened at [`AUTO_SERIAL_BAUD`], which
+//! USB adapters ignore anyway.
+//!
+//! Adapters with the slcan-fd extensions (`Y` to set the data bitrate, and `d`, `D`, `b`, `B`
+//! frames) are found by whether they accept a `Y` command, and run as CAN FD at the
+//! `data_bitrate` option, or 5 Mbit/s. Messages without [`ReduxFIFOMessage::FLAG_NO_FD`] go out on
+//! them as FD frames, like on SocketCAN FD buses.
+
 use std::{collections::VecDeque, sync::Arc, time::Duration};
 
 use parking_lot::Mutex;
@@ -6,21 +23,90 @@ use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
 use crate::{
     MessageIdBuilder, ReduxFIFOMessage,
     backends::{Backend, BackendOpen, SessionTable},
+    bus_info::BackendInfo,
+    dispatch::MAX_DISPATCH_BATCH,
     error::Error,
-    log_debug, log_error, log_trace,
+    log_debug, log_error, log_info, log_trace,
+    utilization::{DEFAULT_BITRATE, DEFAULT_DATA_BITRATE},
+    write_batch::{self, MAX_WRITE_BATCH},
 };
 
+/// Bitrates `slcan:auto:` tries, in order: the ones FRC robots run at.
+pub const AUTO_BITRATES: [u32; 2] = [1_000_000, 500_000];
+/// Serial baud `slcan:auto:` opens the port at.
+pub const AUTO_SERIAL_BAUD: u32 = 115_200;
+/// How long each bitrate is listened to for a valid frame. Robots send several in this time.
+const PROBE_WINDOW: Duration = Duration::from_millis(250);
+/// How long the adapter has to answer a command.
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(100);
+/// What adapters answer commands they don't know with.
+const BELL: u8 = 0x07;
+
+/// FD payload lengths by DLC, past the 8 that classic CAN counts directly.
+const FD_LENGTHS: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
 #[derive(Debug)]
 pub struct SlcanBackend {
     params: Params,
     tx_queue: tokio::sync::mpsc::Sender<ReduxFIFOMessage>,
     run_task: tokio::task::JoinHandle<()>,
+    link: Arc<Mutex<Link>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Params {
     path: String,
-    baud: u32,
+    /// Serial baud, or `None` to find the bitrate by listening
+    baud: Option<u32>,
+}
+
+impl Params {
+    fn serial_baud(&self) -> u32 {
+        self.baud.unwrap_or(AUTO_SERIAL_BAUD)
+    }
+}
+
+/// What the adapter was opened with, once it's open.
+#[derive(Debug, Clone, Copy, Default)]
+struct Link {
+    bitrate: Option<u32>,
+    fd: bool,
+}
+
+/// What to open the adapter with, from the bus options.
+#[derive(Debug, Clone, Copy)]
+struct LinkConfig {
+    /// `None` to probe
+    bitrate: Option<u32>,
+    data_bitrate: u32,
+}
+
+/// The `S` command for a nominal bitrate.
+fn bitrate_command(bitrate: u32) -> Option<u8> {
+    Some(match bitrate {
+        10_000 => b'0',
+        20_000 => b'1',
+        50_000 => b'2',
+        100_000 => b'3',
+        125_000 => b'4',
+        250_000 => b'5',
+        500_000 => b'6',
+        800_000 => b'7',
+        1_000_000 => b'8',
+        _ => return None,
+    })
+}
+
+/// The slcan-fd `Y` command for a data bitrate.
+fn data_bitrate_command(data_bitrate: u32) -> Option<u8> {
+    Some(match data_bitrate {
+        1_000_000 => b'1',
+        2_000_000 => b'2',
+        4_000_000 => b'4',
+        5_000_000 => b'5',
+        8_000_000 => b'8',
+        _ => return None,
+    })
 }
 
 fn split_once<'a>(s: &'a str, d: &str) -> Result<(&'a str, &'a str), Error> {
@@ -34,7 +120,10 @@ impl SlcanBackend {
             return Err(Error::BusNotSupported);
         }
         let (baud_str, path) = split_once(backend_args, ":")?;
-        let baud = baud_str.parse::<u32>().map_err(|_| Error::InvalidBus)?;
+        let baud = match baud_str {
+            "auto" => None,
+            baud => Some(baud.parse::<u32>().map_err(|_| Error::InvalidBus)?),
+        };
 
         Ok(Params {
             path: path.to_string(),
@@ -55,6 +144,9 @@ impl Backend for SlcanBackend {
     }
 
     fn write_single(&mut self, msg: &crate::ReduxFIFOMessage) -> Result<(), crate::error::Error> {
+        if msg.data_size as usize > self.max_packet_size() {
+            return Err(Error::DataTooLong);
+        }
         self.tx_queue
             .try_send(*msg)
             .map_err(|_| Error::BusBufferFull)
@@ -69,7 +161,17 @@ impl Backend for SlcanBackend {
     }
 
     fn max_packet_size(&self) -> usize {
-        8
+        if self.link.lock().fd { 64 } else { 8 }
+    }
+
+    fn info(&self) -> BackendInfo {
+        let link = *self.link.lock();
+        BackendInfo::Slcan {
+            port: self.params.path.clone(),
+            baud: self.params.serial_baud(),
+            bitrate: link.bitrate,
+            fd: link.fd,
+        }
     }
 }
 
@@ -84,29 +186,56 @@ impl BackendOpen for SlcanBackend {
         let params = Self::parse_params(params)?;
         log_debug!("Params parsed: {params:?}");
 
-        let stream =
-            tokio_serial::SerialStream::open(&tokio_serial::new(&params.path, params.baud))
-                .map_err(|e| {
-                    log_error!(
-                        "Failed to open slcan bus {} @ {} baud: {e}",
-                        params.path,
-                        params.baud
-                    );
-                    Error::FailedToOpenBus
-                })?;
+        let options = ses_table.lock().options;
+        let config = LinkConfig {
+            bitrate: params
+                .baud
+                .map(|_| options.bitrate.unwrap_or(DEFAULT_BITRATE)),
+            data_bitrate: options.data_bitrate.unwrap_or(DEFAULT_DATA_BITRATE),
+        };
+        if let Some(bitrate) = config.bitrate
+            && bitrate_command(bitrate).is_none()
+        {
+            log_error!("slcan adapters can't run at {bitrate} bit/s");
+            return Err(Error::InvalidBus);
+        }
+        if data_bitrate_command(config.data_bitrate).is_none() {
+            log_error!(
+                "slcan adapters can't run a data phase at {} bit/s",
+                config.data_bitrate
+            );
+            return Err(Error::InvalidBus);
+        }
+
+        let stream = tokio_serial::SerialStream::open(&tokio_serial::new(
+            &params.path,
+            params.serial_baud(),
+        ))
+        .map_err(|e| {
+            log_error!(
+                "Failed to open slcan bus {} @ {} baud: {e}",
+                params.path,
+                params.serial_baud()
+            );
+            Error::FailedToOpenBus
+        })?;
 
         let (tx_queue_send, tx_queue_recv) = tokio::sync::mpsc::channel(128);
+        let link = Arc::new(Mutex::new(Link::default()));
 
         Ok(Self {
             params: params.clone(),
             tx_queue: tx_queue_send,
             run_task: runtime.spawn(run_backend_wrapper(
                 params,
+                config,
                 stream,
                 tx_queue_recv,
                 bus_id,
                 ses_table,
+                link.clone(),
             )),
+            link,
         })
     }
 }
@@ -124,36 +253,128 @@ enum NextOperation {
 
 async fn run_backend_wrapper(
     params: Params,
+    config: LinkConfig,
     stream: tokio_serial::SerialStream,
     tx_queue: tokio::sync::mpsc::Receiver<ReduxFIFOMessage>,
     bus_id: u16,
     sessions: Arc<Mutex<SessionTable<()>>>,
+    link: Arc<Mutex<Link>>,
 ) {
-    if let Err(e) = run_backend(stream, tx_queue, bus_id, sessions).await {
+    if let Err(e) = run_backend(config, stream, tx_queue, bus_id, sessions, link).await {
         log_error!(
             "slcan backend {bus_id}: {} @ {} died: {e}",
             params.path,
-            params.baud
+            params.serial_baud()
         );
     }
 }
 
+/// Sends a command, waiting for the adapter to take it (`\r`) or refuse it (bell).
+async fn command(stream: &mut tokio_serial::SerialStream, cmd: &[u8]) -> anyhow::Result<bool> {
+    stream.write_all(cmd).await?;
+    let deadline = tokio::time::Instant::now() + COMMAND_TIMEOUT;
+    let mut reply = [0_u8; 64];
+    loop {
+        let Ok(read) = tokio::time::timeout_at(deadline, stream.read(&mut reply)).await else {
+            return Ok(false);
+        };
+        for byte in &reply[..read?] {
+            match *byte {
+                b'\r' => return Ok(true),
+                BELL => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Listens at each of [`AUTO_BITRATES`] until one of them carries a valid frame, leaving the
+/// adapter closed.
+async fn probe_bitrate(
+    stream: &mut tokio_serial::SerialStream,
+    bus_id: u16,
+) -> anyhow::Result<u32> {
+    let mut buf = [0_u8; 256];
+    let mut logged = false;
+    loop {
+        for bitrate in AUTO_BITRATES {
+            let code = bitrate_command(bitrate).unwrap_or(b'8');
+            command(stream, &[b'S', code, b'\r']).await?;
+            if !command(stream, b"L\r").await? {
+                log_error!(
+                    "slcan backend {bus_id}: adapter can't listen without transmitting, so the \
+                     bitrate can't be probed; using {DEFAULT_BITRATE} bit/s"
+                );
+                return Ok(DEFAULT_BITRATE);
+            }
+            let mut state = RxStateMachine::new(bus_id);
+            let heard = tokio::time::timeout(PROBE_WINDOW, async {
+                loop {
+                    let read = stream.read(&mut buf).await?;
+                    state.ingest(&buf[..read]);
+                    if state.drain().is_some() {
+                        return anyhow::Ok(());
+                    }
+                }
+            })
+            .await;
+            command(stream, b"C\r").await?;
+            if let Ok(heard) = heard {
+                heard?;
+                log_info!("slcan backend {bus_id}: heard traffic at {bitrate} bit/s");
+                return Ok(bitrate);
+            }
+        }
+        if !logged {
+            log_info!("slcan backend {bus_id}: no traffic yet at any of {AUTO_BITRATES:?} bit/s");
+            logged = true;
+        }
+    }
+}
+
 async fn run_backend(
+    config: LinkConfig,
     mut stream: tokio_serial::SerialStream,
     mut tx_queue: tokio::sync::mpsc::Receiver<ReduxFIFOMessage>,
     bus_id: u16,
     sessions: Arc<Mutex<SessionTable<()>>>,
+    link: Arc<Mutex<Link>>,
 ) -> Result<(), anyhow::Error> {
     log_trace!("slcan: start backend for {bus_id}");
     let mut buf = bytes::BytesMut::with_capacity(1024);
-    let mut state = RxStateMachine::new(bus_id);
-    let mut tx_buf: Vec<u8> = Vec::with_capacity(32);
+    let mut tx_buf: Vec<u8> = Vec::with_capacity(32 * MAX_WRITE_BATCH);
+    let mut tx_batch = Vec::with_capacity(MAX_WRITE_BATCH);
+    let mut batch = Vec::with_capacity(MAX_DISPATCH_BATCH);
     stream.write_all(b"\r\r\rC\r\r\r").await?;
     tokio::time::sleep(Duration::from_millis(20)).await;
     stream.try_read(&mut buf).ok();
     buf.clear();
 
-    stream.write_all(b"S8\r").await?;
+    // classic adapters refuse the data bitrate command
+    let data_code = data_bitrate_command(config.data_bitrate).unwrap_or(b'5');
+    let fd = command(&mut stream, &[b'Y', data_code, b'\r']).await?;
+    let bitrate = match config.bitrate {
+        Some(bitrate) => bitrate,
+        None => probe_bitrate(&mut stream, bus_id).await?,
+    };
+    log_debug!(
+        "slcan backend {bus_id}: opening at {bitrate} bit/s{}",
+        if fd { " with CAN FD" } else { "" }
+    );
+    *link.lock() = Link {
+        bitrate: Some(bitrate),
+        fd,
+    };
+    {
+        let mut sessions = sessions.lock();
+        sessions.utilization.set_fd(fd);
+        sessions.utilization.set_bitrate(bitrate);
+    }
+
+    let mut state = RxStateMachine::new(bus_id);
+    state.fd = fd;
+    let code = bitrate_command(bitrate).unwrap_or(b'8');
+    stream.write_all(&[b'S', code, b'\r']).await?;
     stream.write_all(b"O\r").await?;
 
     loop {
@@ -170,27 +391,72 @@ async fn run_backend(
         match next_op {
             NextOperation::RxData(read_len) => {
                 state.ingest(&buf[..read_len]);
-                let mut ses_lock = sessions.lock();
-                while let Some(mut msg) = state.drain() {
-                    msg.timestamp = crate::timebase::now_us() as u64;
-                    ses_lock.ingest_message(msg);
+                while let Some(msg) = state.drain() {
+                    batch.push(msg);
+                    if batch.len() == MAX_DISPATCH_BATCH {
+                        sessions.lock().ingest_messages(&mut batch);
+                        batch.clear();
+                    }
                 }
-                drop(ses_lock);
+                sessions.lock().ingest_messages(&mut batch);
+                batch.clear();
             }
             NextOperation::TxMessage(msg) => {
-                serialize_into(&mut tx_buf, &msg)?;
+                // everything else already queued goes out in the same write
+                let open = write_batch::drain_queue(&mut tx_queue, msg, &mut tx_batch);
+                tx_buf.clear();
+                for msg in tx_batch.iter() {
+                    serialize_into(&mut tx_buf, msg, fd)?;
+                }
                 stream.write_all(&tx_buf).await?;
+                write_batch::record_write(tx_batch.len(), tx_buf.len());
+                tx_batch.clear();
+                if !open {
+                    return Ok(());
+                }
             }
         }
     }
 }
 
-fn serialize_into(tx_buf: &mut Vec<u8>, msg: &crate::ReduxFIFOMessage) -> anyhow::Result<()> {
-    let len = msg.data_slice().len().min(8);
-    tx_buf.clear();
-    tx_buf.extend_from_slice(format!("T{:08X}{len}", msg.message_id).as_bytes());
-    for byte in &msg.data_slice()[..len] {
-        tx_buf.extend_from_slice(format!("{byte:02X}").as_bytes());
+/// Appends a message to `tx_buf` as an slcan frame command, as an FD frame if `fd` and it isn't
+/// classic.
+fn serialize_into(
+    tx_buf: &mut Vec<u8>,
+    msg: &crate::ReduxFIFOMessage,
+    fd: bool,
+) -> anyhow::Result<()> {
+    let fd = fd && !msg.no_fd() && !msg.rtr();
+    let (dlc, len) = if fd {
+        let dlc = FD_LENGTHS
+            .iter()
+            .position(|len| *len >= msg.data_size)
+            .unwrap_or(FD_LENGTHS.len() - 1);
+        (dlc, FD_LENGTHS[dlc] as usize)
+    } else {
+        let len = msg.data_slice().len().min(8);
+        (len, len)
+    };
+    let cmd = match (fd, msg.rtr(), msg.no_brs()) {
+        (true, _, true) => b'd',
+        (true, _, false) => b'b',
+        (false, true, _) => b'r',
+        (false, false, _) => b't',
+    };
+    if msg.short_id() {
+        tx_buf.push(cmd);
+        tx_buf.extend_from_slice(format!("{:03X}{dlc:X}", msg.id() & 0x7ff).as_bytes());
+    } else {
+        tx_buf.push(cmd.to_ascii_uppercase());
+        tx_buf.extend_from_slice(format!("{:08X}{dlc:X}", msg.id()).as_bytes());
+    }
+    if !msg.rtr() {
+        // FD lengths between DLCs are padded out with zeros
+        let data = msg.data_slice();
+        for i in 0..len {
+            let byte = data.get(i).copied().unwrap_or(0);
+            tx_buf.extend_from_slice(format!("{byte:02X}").as_bytes());
+        }
     }
     tx_buf.push(b'\r');
     Ok(())
@@ -199,6 +465,8 @@ fn serialize_into(tx_buf: &mut Vec<u8>, msg: &crate::ReduxFIFOMessage) -> anyhow
 struct RxStateMachine {
     in_buf: VecDeque<u8>,
     bus_id: u16,
+    /// The adapter runs CAN FD, so classic frames are flagged as such
+    fd: bool,
 }
 
 const STD_HEADER: usize = 5;
@@ -209,6 +477,7 @@ impl RxStateMachine {
         Self {
             in_buf: VecDeque::new(),
             bus_id,
+            fd: false,
         }
     }
     pub fn ingest(&mut self, data: &[u8]) {
@@ -224,7 +493,7 @@ impl RxStateMachine {
             // read the first character.
             let first = *self.in_buf.front()?;
             match first {
-                b't' | b'r' => {
+                b't' | b'r' | b'd' | b'b' => {
                     // 11-bit id
                     let is_remote = first == b'r';
                     if self.in_buf.len() < STD_HEADER {
@@ -239,15 +508,10 @@ impl RxStateMachine {
                         .map(|b| from_bcx(*b).unwrap_or(0))
                         .fold(0_u32, |prev, next| (prev << 4) | (next as u32))
                         | MessageIdBuilder::ID_FLAG_11BIT;
-                    let len = self
-                        .in_buf
-                        .get(4)
-                        .unwrap_or(&b'0')
-                        .saturating_sub(b'0')
-                        .min(8);
-                    return self.conjure_message(id, len, is_remote, STD_HEADER);
+                    let (len, flags) = self.length(first, self.in_buf.get(4).copied());
+                    return self.conjure_message(id, len, flags, is_remote, STD_HEADER);
                 }
-                b'T' | b'R' => {
+                b'T' | b'R' | b'D' | b'B' => {
                     // 29-bit id
                     let is_remote = first == b'R';
                     if self.in_buf.len() < EXT_HEADER {
@@ -261,13 +525,8 @@ impl RxStateMachine {
                         .take(8)
                         .map(|b| from_bcx(*b).unwrap_or(0))
                         .fold(0_u32, |prev, next| (prev << 4) | (next as u32));
-                    let len = self
-                        .in_buf
-                        .get(9)
-                        .unwrap_or(&b'0')
-                        .saturating_sub(b'0')
-                        .min(8);
-                    return self.conjure_message(id, len, is_remote, EXT_HEADER);
+                    let (len, flags) = self.length(first, self.in_buf.get(9).copied());
+                    return self.conjure_message(id, len, flags, is_remote, EXT_HEADER);
                 }
                 _ => {
                     // irrelevant garbage
@@ -278,10 +537,22 @@ impl RxStateMachine {
         }
     }
 
+    /// Data length and message flags of a frame, from its command and DLC characters.
+    fn length(&self, cmd: u8, dlc: Option<u8>) -> (u8, u8) {
+        let dlc = dlc.and_then(from_bcx).unwrap_or(0);
+        match cmd.to_ascii_lowercase() {
+            b'd' => (FD_LENGTHS[dlc as usize], ReduxFIFOMessage::FLAG_NO_BRS),
+            b'b' => (FD_LENGTHS[dlc as usize], 0),
+            _ if self.fd => (dlc.min(8), ReduxFIFOMessage::FLAG_NO_FD),
+            _ => (dlc.min(8), 0),
+        }
+    }
+
     fn conjure_message(
         &mut self,
         id: u32,
         len: u8,
+        flags: u8,
         is_remote: bool,
         header_size: usize,
     ) -> Option<ReduxFIFOMessage> {
@@ -292,7 +563,7 @@ impl RxStateMachine {
                 id | MessageIdBuilder::ID_FLAG_RTR,
                 [0_u8; _],
                 len,
-                0,
+                flags,
             );
             drop(self.in_buf.drain(..header_size));
             return Some(msg);
@@ -309,7 +580,7 @@ impl RxStateMachine {
                 data[i] = (msb << 4) | lsb;
             }
 
-            let msg = ReduxFIFOMessage::id_data(self.bus_id, id, data, len, 0);
+            let msg = ReduxFIFOMessage::id_data(self.bus_id, id, data, len, flags);
             drop(self.in_buf.drain(..serialized_len));
             return Some(msg);
         }
@@ -1,7 +1,11 @@
 //! Socketcan backend
 //!
 //! ## Data model
-//! This matches on two types of buses: socketcan:{bus-name} and socketcanfd:{bus-name}
+//! This matches on two types of buses: socketcan:{bus-name} and socketcan.fd:{bus-name}. The
+//! latter opens the interface for CAN FD frames, which it must be configured for (`ip link set can0
+//! type can bitrate 1000000 dbitrate 5000000 fd on`).
+//! A bare interface name with no scheme, like `can0`, is opened as `socketcan:can0` if the machine
+//! has a CAN interface by that name; see [`crate::bus_uri::BusUri::expand_interface`].
 //!
 //! ## Opening a bus
 //! When the bus is opened, a single TX write task for the corresponding bus is created.
@@ -9,22 +13,39 @@
 //!
 //! Sessions can be opened from this bus
 //!
+//! ## Write confirmation
+//! With the `confirm_tx` bus option, loopback and own-message reception are turned on, so the
+//! kernel hands back each of our frames once the controller has sent it. Those are matched to
+//! writes instead of being delivered as received traffic. Frames from other sockets on this host
+//! are then received as well.
+//!
+//! ## Bus status
+//! Controller error frames are turned on and go to the bus's [`crate::bus_status`] instead of to
+//! sessions. The controller's state and error counters are also read over netlink whenever the
+//! interface is (re)opened, since error frames only come when they change.
+//!
 use std::{
     sync::Arc,
     time::{Duration, SystemTime},
 };
 
+use futures::FutureExt as _;
 use parking_lot::Mutex;
 use socketcan::{
     Frame, Socket as _, SocketOptions,
     id::{FdFlags, id_to_canid_t},
+    nl::CanState,
 };
 
 use crate::{
     MessageIdBuilder, ReduxFIFOMessage, ReduxFIFOSessionConfig, WriteBuffer,
     backends::{Backend, BackendOpen, SessionTable},
+    bus_info::BackendInfo,
+    bus_status::{BusErrorState, BusStatusTracker},
+    dispatch::MAX_DISPATCH_BATCH,
     error::Error,
     log_debug, log_error, log_trace, timebase,
+    timestamp::TimestampSource,
 };
 use embedded_can::Frame as _;
 
@@ -87,6 +108,7 @@ impl TryFrom<&ReduxFIFOMessage> for socketcan::CanAnyFrame {
             let mut flags = FdFlags::empty();
             flags.set(FdFlags::FDF, !value.no_fd());
             flags.set(FdFlags::BRS, !value.no_brs());
+            flags.set(FdFlags::ESI, value.esi());
 
             Self::Fd(socketcan::CanFdFrame::with_flags(id, data, flags).ok_or(Error::DataTooLong)?)
         })
@@ -102,7 +124,7 @@ enum CanBus {
 }
 
 impl CanBus {
-    pub fn open(bus: &str, fd: bool) -> Result<CanBus, Error> {
+    pub fn open(bus: &str, fd: bool, confirm_tx: bool) -> Result<CanBus, Error> {
         let open_fail = |e| {
             log_trace!("Failed to open socketcan iface `{bus}`: {e}");
             Error::FailedToOpenBus
@@ -118,7 +140,9 @@ impl CanBus {
                 socketcan::socket::TimestampingMode::Hardware,
             )
             .map_err(open_fail)?;
-            let _ = bus.set_loopback(false);
+            let _ = bus.set_loopback(confirm_tx);
+            let _ = bus.set_recv_own_msgs(confirm_tx);
+            let _ = bus.set_error_filter(ERROR_FILTER);
             Ok(Self::CanFd(bus))
         } else {
             let bus = socketcan::tokio::CanSocketTimestamp::open_with_timestamping_mode(
@@ -126,7 +150,9 @@ impl CanBus {
                 socketcan::socket::TimestampingMode::Hardware,
             )
             .map_err(open_fail)?;
-            let _ = bus.set_loopback(false);
+            let _ = bus.set_loopback(confirm_tx);
+            let _ = bus.set_recv_own_msgs(confirm_tx);
+            let _ = bus.set_error_filter(ERROR_FILTER);
             Ok(Self::Can2(bus))
         }
     }
@@ -182,7 +208,8 @@ impl CanBus {
                     .unwrap()
                     .as_micros() as i64,
             ),
-            None => timebase::now_us() as u64,
+            // the bus's timestamper falls back to the arrival time
+            None => 0,
         };
 
         let mut flags = 0;
@@ -190,10 +217,13 @@ impl CanBus {
             if matches!(frame, socketcan::CanAnyFrame::Normal(..)) {
                 flags |= ReduxFIFOMessage::FLAG_NO_FD;
             }
-            if let socketcan::CanAnyFrame::Fd(fd_frame) = frame
-                && !fd_frame.is_brs()
-            {
-                flags |= ReduxFIFOMessage::FLAG_NO_BRS;
+            if let socketcan::CanAnyFrame::Fd(fd_frame) = frame {
+                if !fd_frame.is_brs() {
+                    flags |= ReduxFIFOMessage::FLAG_NO_BRS;
+                }
+                if fd_frame.is_esi() {
+                    flags |= ReduxFIFOMessage::FLAG_ESI;
+                }
             }
         }
 
@@ -237,7 +267,7 @@ impl CanBus {
     async fn reopen_bus(state: &SocketCanBackendState) -> Self {
         loop {
             log_debug!("Attempting to open SocketCAN bus `{}`", state.bus_str);
-            let Ok(new_bus) = Self::open(&state.bus_str, state.fd) else {
+            let Ok(new_bus) = Self::open(&state.bus_str, state.fd, state.confirm_tx) else {
                 tokio::time::sleep(Duration::from_millis(50)).await;
                 continue;
             };
@@ -247,11 +277,35 @@ impl CanBus {
     }
 }
 
+/// Error frame classes that carry the controller's state: controller problems, bus off, and
+/// restarts after bus off.
+const ERROR_FILTER: u32 = 0x0004 | 0x0040 | 0x0100;
+
+/// Reads the controller's state from netlink, for when error frames haven't said anything yet.
+fn read_controller_state(iface: &str, status: &mut BusStatusTracker) {
+    let Ok(iface) = socketcan::CanInterface::open(iface) else {
+        return;
+    };
+    let state = match iface.state() {
+        Ok(Some(CanState::ErrorActive)) => BusErrorState::ErrorActive,
+        Ok(Some(CanState::ErrorWarning)) => BusErrorState::ErrorWarning,
+        Ok(Some(CanState::ErrorPassive)) => BusErrorState::ErrorPassive,
+        Ok(Some(CanState::BusOff)) => BusErrorState::BusOff,
+        // virtual interfaces and stopped controllers
+        _ => BusErrorState::Unknown,
+    };
+    status.set_state(state);
+    if let Ok(Some(counters)) = iface.berr_counter() {
+        status.set_error_counters(counters.txerr, counters.rxerr);
+    }
+}
+
 #[derive(Debug, Clone)]
 struct SocketCanBackendState {
     bus_str: String,
     bus_id: u16,
     fd: bool,
+    confirm_tx: bool,
 }
 
 async fn socketcan_read_loop(
@@ -269,7 +323,9 @@ async fn socketcan_read_loop(
             new_bus
         }
     };
+    read_controller_state(&state.bus_str, &mut ses_table.lock().status);
 
+    let mut batch = Vec::with_capacity(MAX_DISPATCH_BATCH);
     loop {
         let msg = match bus.recv_msg(&state).await {
             Ok(msg) => msg,
@@ -281,13 +337,27 @@ async fn socketcan_read_loop(
                 write_bus.lock().take();
                 bus = Arc::new(CanBus::reopen_bus(&state).await);
                 write_bus.lock().replace(bus.clone());
+                read_controller_state(&state.bus_str, &mut ses_table.lock().status);
                 continue;
             }
         };
 
+        // take whatever else is already queued so a burst is dispatched in one pass. A read error
+        // here just ends the batch; the next blocking read will hit it again.
+        batch.push(msg);
+        while batch.len() < MAX_DISPATCH_BATCH
+            && let Some(Ok(msg)) = bus.recv_msg(&state).now_or_never()
+        {
+            batch.push(msg);
+        }
+
         let mut ses_lock = ses_table.lock();
-        ses_lock.ingest_message(msg);
+        batch.retain(|msg| !ses_lock.status.error_frame(msg));
+        ses_lock.stamp_messages(&mut batch);
+        batch.retain(|msg| !ses_lock.tx.confirm(msg));
+        ses_lock.dispatch_messages(&batch);
         drop(ses_lock);
+        batch.clear();
     }
 }
 
@@ -309,16 +379,19 @@ impl BackendOpen for SocketCanBackend {
         ses_table: Arc<Mutex<SessionTable<()>>>,
     ) -> Result<Self, Error> {
         log_debug!("open socketcan: {bus_number}");
+        let confirm_tx = ses_table.lock().tx.enabled();
         let state = match params.split_once(":") {
             Some(("socketcan", bus)) => SocketCanBackendState {
                 bus_str: bus.to_string(),
                 bus_id: bus_number,
                 fd: false,
+                confirm_tx,
             },
             Some(("socketcan.fd", bus)) => SocketCanBackendState {
                 bus_str: bus.to_string(),
                 bus_id: bus_number,
                 fd: true,
+                confirm_tx,
             },
             Some((invalid_0, invalid_1)) => {
                 log_error!("Invalid SocketCAN bus string {invalid_0}:{invalid_1}.");
@@ -332,11 +405,11 @@ impl BackendOpen for SocketCanBackend {
 
         let write_bus = if tokio::runtime::Handle::try_current().is_ok() {
             // if we're in a tokio runtime, open it directly to avoid double-block
-            CanBus::open(&state.bus_str, state.fd).ok().map(Arc::new)
+            CanBus::open(&state.bus_str, state.fd, state.confirm_tx).ok().map(Arc::new)
         } else {
             // if we're not, have the tokio runtime do it
             runtime
-                .block_on((async || CanBus::open(&state.bus_str, state.fd))())
+                .block_on(async { CanBus::open(&state.bus_str, state.fd, state.confirm_tx) })
                 .ok()
                 .map(Arc::new)
         };
@@ -358,6 +431,7 @@ impl BackendOpen for SocketCanBackend {
 
 impl Backend for SocketCanBackend {
     type State = ();
+    const TIMESTAMPS: TimestampSource = TimestampSource::Driver;
     fn params_match(&self, params: &str) -> bool {
         match params.split_once(":") {
             Some(("socketcan", bus)) if bus == self.state.bus_str && !self.state.fd => true,
@@ -409,6 +483,17 @@ impl Backend for SocketCanBackend {
     fn max_packet_size(&self) -> usize {
         if self.state.fd { 64 } else { 8 }
     }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo::SocketCan {
+            interface: self.state.bus_str.clone(),
+            fd: self.state.fd,
+        }
+    }
+
+    fn confirms_tx(&self) -> bool {
+        true
+    }
 }
 
 impl Drop for SocketCanBackend {
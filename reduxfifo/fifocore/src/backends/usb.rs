@@ -7,9 +7,12 @@ use futures::StreamExt;
 use nusb::{DeviceInfo, Endpoint, hotplug::HotplugEvent};
 use parking_lot::Mutex;
 use rustc_hash::FxHashMap;
-use tokio::{sync::watch, task::JoinHandle};
+use tokio::{
+    sync::{broadcast, watch},
+    task::JoinHandle,
+};
 
-use crate::{ReduxFIFOMessage, backends::SessionTable, error::Error, log_trace};
+use crate::{ReduxFIFOMessage, backends::SessionTable, bus_info::UsbInfo, error::Error, log_trace};
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct UsbDeviceId {
@@ -42,6 +45,9 @@ impl UsbDeviceId {
 pub struct UsbDevice {
     pub device_id: UsbDeviceId,
     pub devinfo_watch: watch::Receiver<Option<DeviceInfo>>,
+    /// Where the device's console output goes, for backends that have one
+    pub console: ConsoleSender,
+    connection: Arc<Mutex<UsbConnection>>,
 }
 
 impl UsbDevice {
@@ -72,6 +78,18 @@ impl UsbDevice {
         }
     }
 
+    /// Records the device as opened, for [`UsbSession::info`].
+    pub fn set_opened(&self, info: &DeviceInfo) {
+        let mut connection = self.connection.lock();
+        connection.last_opened = Some(info.clone());
+        connection.connected = true;
+    }
+
+    /// Records the device as gone until it's opened again.
+    pub fn set_closed(&self) {
+        self.connection.lock().connected = false;
+    }
+
     async fn rescan(&self) -> Option<DeviceInfo> {
         log::trace!(target: "reduxfifo::usb", "Scan devices triggered");
         if let Ok(device_iter) = nusb::list_devices().await {
@@ -91,6 +109,23 @@ pub(crate) type BulkIn = Endpoint<nusb::transfer::Bulk, nusb::transfer::In>;
 pub(crate) type Sessions = Arc<Mutex<FxHashMap<u16, Arc<Mutex<SessionTable<UsbSessionState>>>>>>;
 type TxSender = tokio::sync::mpsc::Sender<(ReduxFIFOMessage, u16)>;
 type TxReceiver = tokio::sync::mpsc::Receiver<(ReduxFIFOMessage, u16)>;
+pub(crate) type ConsoleSender = broadcast::Sender<Vec<u8>>;
+
+/// What a backend's event loop last saw of its device.
+#[derive(Debug, Default)]
+struct UsbConnection {
+    last_opened: Option<DeviceInfo>,
+    connected: bool,
+}
+
+/// A device's firmware version, from its device descriptor's bcdDevice.
+pub fn firmware_version(info: &DeviceInfo) -> String {
+    let version = info.device_version();
+    format!("{:x}.{:02x}", version >> 8, version & 0xff)
+}
+
+/// Console chunks that can pile up for a slow reader before it starts missing output.
+const CONSOLE_BACKLOG: usize = 256;
 
 /// This is always gonna live in an Arc of some sort.
 #[derive(Debug)]
@@ -101,6 +136,8 @@ pub struct UsbSession {
     task_handle: JoinHandle<()>,
     tag: String,
     meta_sessions: Sessions,
+    console: ConsoleSender,
+    connection: Arc<Mutex<UsbConnection>>,
 }
 
 impl UsbSession {
@@ -115,6 +152,31 @@ impl UsbSession {
     pub fn tag(&self) -> &str {
         &self.tag
     }
+
+    pub fn device_id(&self) -> &UsbDeviceId {
+        &self.device_id
+    }
+
+    /// The device and whether it's connected, as seen by a bus on `channel`.
+    pub fn info(&self, channel: u16) -> UsbInfo {
+        let connection = self.connection.lock();
+        let last = connection.last_opened.as_ref();
+        UsbInfo {
+            vid: self.device_id.vid,
+            pid: self.device_id.pid,
+            serial_numer: self.device_id.serial_numer.clone(),
+            channel,
+            connected: connection.connected,
+            product: last.and_then(|info| info.product_string().map(str::to_string)),
+            manufacturer: last.and_then(|info| info.manufacturer_string().map(str::to_string)),
+            firmware: last.map(firmware_version),
+        }
+    }
+
+    /// Listens to the device's console output from now on.
+    pub fn subscribe_console(&self) -> broadcast::Receiver<Vec<u8>> {
+        self.console.subscribe()
+    }
 }
 
 impl Drop for UsbSession {
@@ -137,6 +199,14 @@ impl UsbEventLoop {
         }
     }
 
+    /// The live session for a device, if some backend has opened it.
+    pub fn find(&self, device_id: &UsbDeviceId) -> Option<Arc<UsbSession>> {
+        self.devices
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|ses| ses.device_id_matches(device_id))
+    }
+
     /// Indicates that a USB device is to be watched for new or existing connections.
     pub fn open<
         R: Future<Output = ()> + Send + 'static,
@@ -170,9 +240,13 @@ impl UsbEventLoop {
 
         log_trace!("rdxusb: create new session for {device_id:?}");
         let (send, recv) = watch::channel(None);
+        let (console, _) = broadcast::channel(CONSOLE_BACKLOG);
+        let connection = Arc::new(Mutex::new(UsbConnection::default()));
         let device = UsbDevice {
             device_id: device_id.clone(),
             devinfo_watch: recv,
+            console: console.clone(),
+            connection: connection.clone(),
         };
         let (tx_send, tx_recv) = tokio::sync::mpsc::channel(128);
 
@@ -187,6 +261,8 @@ impl UsbEventLoop {
             msg_tx: tx_send,
             tag: tag.to_string(),
             meta_sessions,
+            console,
+            connection,
         });
         self.devices.push(Arc::downgrade(&ses));
         ses
@@ -1,8 +1,10 @@
 use std::{sync::Arc, time::Duration};
 
 use crate::backends::{Backend, BackendOpen, SessionTable};
+use crate::bus_info::BackendInfo;
 use crate::error::Error;
-use crate::{ReduxFIFOMessage, ReduxFIFOSessionConfig, log_debug, log_error, log_trace, timebase};
+use crate::timestamp::TimestampSource;
+use crate::{ReduxFIFOMessage, ReduxFIFOSessionConfig, log_debug, log_error, log_trace, log_warn};
 use futures::{SinkExt, StreamExt};
 use parking_lot::Mutex;
 use tokio::sync::mpsc;
@@ -168,8 +170,20 @@ impl WebSocketBackend {
             let Ok(rx_msg) = rdxcanlink_protocol::CANLinkRxMessage::try_from(&*data) else {
                 continue;
             };
+            if rx_msg.flags & rdxcanlink_protocol::FLAG_NOTICE != 0 {
+                if let Ok(notice) = rdxcanlink_protocol::CANLinkOverflowNotice::try_from(&rx_msg) {
+                    log_warn!(
+                        "websocket: server dropped {} and skipped {} messages (decimation {})",
+                        notice.dropped,
+                        notice.skipped,
+                        notice.decimation
+                    );
+                    ses_table.lock().status.rx_overrun(notice.dropped as u64);
+                }
+                continue;
+            }
 
-            let mut redux_msg = ReduxFIFOMessage {
+            let redux_msg = ReduxFIFOMessage {
                 message_id: rx_msg.message_id,
                 bus_id: bus_id, // Use our bus_id, not the one from the message
                 flags: rx_msg.flags as u8,
@@ -178,11 +192,6 @@ impl WebSocketBackend {
                 data: rx_msg.data,
             };
 
-            // Update timestamp if not provided
-            if redux_msg.timestamp == 0 {
-                redux_msg.timestamp = timebase::now_us() as u64;
-            }
-
             let mut ses_lock = ses_table.lock();
             ses_lock.ingest_message(redux_msg);
             drop(ses_lock);
@@ -192,6 +201,7 @@ impl WebSocketBackend {
 
 impl Backend for WebSocketBackend {
     type State = WebSocketSessionState;
+    const TIMESTAMPS: TimestampSource = TimestampSource::Remote;
 
     fn start_session(
         &mut self,
@@ -218,6 +228,12 @@ impl Backend for WebSocketBackend {
     fn max_packet_size(&self) -> usize {
         64
     }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo::WebSocket {
+            url: self.url.clone(),
+        }
+    }
 }
 
 impl BackendOpen for WebSocketBackend {
@@ -1,8 +1,10 @@
 use std::{sync::Arc, time::Duration};
 
 use crate::backends::{Backend, BackendOpen, SessionTable};
+use crate::bus_info::BackendInfo;
 use crate::error::Error;
-use crate::{ReduxFIFOMessage, ReduxFIFOSessionConfig, log_debug, log_error, log_trace, timebase};
+use crate::timestamp::TimestampSource;
+use crate::{ReduxFIFOMessage, ReduxFIFOSessionConfig, log_debug, log_error, log_trace};
 use futures::{SinkExt, StreamExt};
 use parking_lot::Mutex;
 use tokio::sync::mpsc;
@@ -166,7 +168,7 @@ impl WebSocketBackend {
             let bytes_to_copy = data.len().min(array.len());
             array[..bytes_to_copy].copy_from_slice(&data[..bytes_to_copy]);
 
-            let mut redux_msg = ReduxFIFOMessage {
+            let redux_msg = ReduxFIFOMessage {
                 message_id: message_id,
                 bus_id: bus_id, // Use our bus_id, not the one from the message
                 flags: 0,
@@ -175,11 +177,6 @@ impl WebSocketBackend {
                 data: array,
             };
 
-            // Update timestamp if not provided
-            if redux_msg.timestamp == 0 {
-                redux_msg.timestamp = timebase::now_us() as u64;
-            }
-
             let mut ses_lock = ses_table.lock();
             ses_lock.ingest_message(redux_msg);
             drop(ses_lock);
@@ -189,6 +186,8 @@ impl WebSocketBackend {
 
 impl Backend for WebSocketBackend {
     type State = ();
+    // the legacy protocol's 32-bit remote timestamps wrap every ~71 minutes
+    const TIMESTAMPS: TimestampSource = TimestampSource::HostArrival;
 
     fn start_session(
         &mut self,
@@ -215,6 +214,12 @@ impl Backend for WebSocketBackend {
     fn max_packet_size(&self) -> usize {
         64
     }
+
+    fn info(&self) -> BackendInfo {
+        BackendInfo::WebSocket {
+            url: self.base_url.clone(),
+        }
+    }
 }
 
 impl BackendOpen for WebSocketBackend {
@@ -0,0 +1,107 @@
+//! Human-readable names for buses.
+//!
+//! An alias such as `drive` stands for bus params (`socketcan:can0`), not a bus id, so it keeps
+//! pointing at the same hardware when the bus is closed and reopened under a different id. Anywhere
+//! params are accepted an alias can be given instead, and a [`BusRef`] resolves to the id of the
+//! open bus it names.
+
+use rustc_hash::FxHashMap;
+
+use crate::{backends::BackendKind, bus_options::BusOptions, bus_uri::BusUri, error::Error};
+
+/// Longest alias accepted.
+pub const MAX_ALIAS_LEN: usize = 32;
+
+/// A bus given by id, alias, or params.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum BusRef {
+    Id(u16),
+    Name(String),
+}
+
+impl From<u16> for BusRef {
+    fn from(value: u16) -> Self {
+        Self::Id(value)
+    }
+}
+
+impl core::fmt::Display for BusRef {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BusRef::Id(id) => write!(f, "{id}"),
+            BusRef::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Registry of aliases and the bus params they stand for.
+#[derive(Debug, Default, Clone)]
+pub struct BusAliases(FxHashMap<String, String>);
+
+impl BusAliases {
+    /// Aliases are ASCII letters, digits, `-` and `_`, can't be all digits (those are bus ids),
+    /// and can't start like bus params. This also keeps them safe to put in file names.
+    pub fn valid_alias(alias: &str) -> bool {
+        !alias.is_empty()
+            && alias.len() <= MAX_ALIAS_LEN
+            && alias
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+            && !alias.bytes().all(|b| b.is_ascii_digit())
+            && BackendKind::from_params(alias).is_none()
+    }
+
+    /// Points `alias` at `params`, replacing what it pointed at before.
+    pub fn insert(&mut self, alias: &str, params: &str) -> Result<Option<String>, Error> {
+        if !Self::valid_alias(alias) {
+            crate::log_error!("Invalid bus alias {alias:?}");
+            return Err(Error::InvalidBus);
+        }
+        let (bare_params, _) = BusOptions::split(params)?;
+        // only the params' shape; the bus needn't be openable until it's used
+        if let Err(e) = bare_params.parse::<BusUri>() {
+            crate::log_error!("Bus alias {alias}: {e}");
+            return Err(e.into());
+        }
+        Ok(self.0.insert(alias.to_string(), params.to_string()))
+    }
+
+    pub fn remove(&mut self, alias: &str) -> Option<String> {
+        self.0.remove(alias)
+    }
+
+    /// The params `alias` stands for.
+    pub fn get(&self, alias: &str) -> Option<&str> {
+        self.0.get(alias).map(String::as_str)
+    }
+
+    /// Expands `params` if it is an alias, otherwise returns it unchanged.
+    pub fn expand<'a>(&'a self, params: &'a str) -> &'a str {
+        self.get(params).unwrap_or(params)
+    }
+
+    /// The alias of a bus opened with `params`, if it has one.
+    ///
+    /// If several aliases name the same bus, the alphabetically first one wins.
+    pub fn alias_for(&self, params: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .filter(|(_, aliased)| {
+                BusOptions::split(aliased).map_or(aliased.as_str(), |(bare, _)| bare) == params
+            })
+            .map(|(alias, _)| alias.as_str())
+            .min()
+    }
+
+    /// `(alias, params)` pairs, sorted by alias.
+    pub fn list(&self) -> Vec<(String, String)> {
+        let mut list: Vec<_> = self
+            .0
+            .iter()
+            .map(|(alias, params)| (alias.clone(), params.clone()))
+            .collect();
+        list.sort();
+        list
+    }
+}
@@ -0,0 +1,123 @@
+//! What hardware a bus is actually talking to.
+//!
+//! Bus params say what was asked for; [`BusInfo`] says what the backend found: which USB adapter
+//! with which firmware, which serial port at what baud, which network interface. It's there so
+//! that a bug report can be tied to the hardware path it came from.
+
+use core::fmt;
+
+/// A bus and the hardware behind it, from [`FIFOCore::bus_info`](crate::FIFOCore::bus_info).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BusInfo {
+    pub bus_id: u16,
+    pub params: String,
+    pub backend: BackendInfo,
+}
+
+/// Backend-specific details of a bus.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum BackendInfo {
+    HalCan,
+    SocketCan {
+        interface: String,
+        fd: bool,
+    },
+    RdxUsb(UsbInfo),
+    GsUsb(UsbInfo),
+    Slcan {
+        port: String,
+        /// Serial baud rate the port was opened at
+        baud: u32,
+        /// CAN bitrate the adapter was opened at; `None` while `slcan:auto:` is still listening
+        bitrate: Option<u32>,
+        /// Whether the adapter speaks the slcan-fd extensions
+        fd: bool,
+    },
+    WebSocket {
+        url: String,
+    },
+    Sim {
+        name: String,
+    },
+    Replay {
+        path: String,
+    },
+}
+
+/// A USB adapter behind a bus.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct UsbInfo {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_numer: String,
+    /// Channel of the adapter the bus is on
+    pub channel: u16,
+    /// Whether the adapter is connected and opened right now
+    pub connected: bool,
+    /// From the adapter's descriptors when it was last opened, like the rest below; `None` if it
+    /// hasn't been yet
+    pub product: Option<String>,
+    pub manufacturer: Option<String>,
+    /// Firmware version from the device descriptor's bcdDevice
+    pub firmware: Option<String>,
+}
+
+impl fmt::Display for UsbInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:04x} {} channel {}",
+            self.vid, self.pid, self.serial_numer, self.channel
+        )?;
+        if let Some(product) = &self.product {
+            write!(f, " ({product})")?;
+        }
+        if let Some(firmware) = &self.firmware {
+            write!(f, " fw {firmware}")?;
+        }
+        if !self.connected {
+            f.write_str(", not connected")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BackendInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendInfo::HalCan => f.write_str("roboRIO CAN through the WPILib HAL"),
+            BackendInfo::SocketCan { interface, fd } => {
+                let fd = if *fd { " (CAN FD)" } else { "" };
+                write!(f, "SocketCAN interface {interface}{fd}")
+            }
+            BackendInfo::RdxUsb(usb) => write!(f, "RdxUSB {usb}"),
+            BackendInfo::GsUsb(usb) => write!(f, "gs_usb {usb}"),
+            BackendInfo::Slcan {
+                port,
+                baud,
+                bitrate,
+                fd,
+            } => {
+                write!(f, "slcan on {port} at {baud} baud")?;
+                match bitrate {
+                    Some(bitrate) => write!(f, ", CAN at {bitrate} bit/s")?,
+                    None => f.write_str(", probing CAN bitrate")?,
+                }
+                if *fd {
+                    f.write_str(" (CAN FD)")?;
+                }
+                Ok(())
+            }
+            BackendInfo::WebSocket { url } => write!(f, "relayed from {url}"),
+            BackendInfo::Sim { name } => write!(f, "simulated bus {name}"),
+            BackendInfo::Replay { path } => write!(f, "replay of {path}"),
+        }
+    }
+}
+
+impl fmt::Display for BusInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bus {} ({}): {}", self.bus_id, self.params, self.backend)
+    }
+}
@@ -0,0 +1,133 @@
+//! Options appended to bus params, and the dedicated threads isolated buses run on.
+//!
+//! Options follow the backend's params, each after a `;`: `slcan:115200:/dev/ttyACM0;isolated`.
+//! Some take a value: `socketcan:can0;bitrate=500000`.
+
+use crate::error::Error;
+
+/// Options that apply to any backend.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BusOptions {
+    /// Run the bus's backend tasks, and with them session dispatch, on a dedicated thread instead
+    /// of the shared runtime. A backend that stalls (e.g. an slcan adapter that's slow to drain its
+    /// serial port) then can't add jitter to the other buses.
+    pub isolated: bool,
+    /// Track when written messages actually go out on the wire; see [`crate::tx_confirm`].
+    /// Only backends that can observe this accept it.
+    pub confirm_tx: bool,
+    /// Nominal bitrate in bit/s, for estimating utilization; see [`crate::utilization`]
+    pub bitrate: Option<u32>,
+    /// CAN FD data phase bitrate in bit/s
+    pub data_bitrate: Option<u32>,
+    /// Utilization percentage past which a warning is logged; 0 disables it
+    pub util_warn: Option<u8>,
+    /// How many times faster than recorded a log is replayed; see [`crate::backends::replay`]
+    pub speed: Option<f32>,
+    /// Start a replayed log over once it ends
+    pub loop_replay: bool,
+    /// Writes to hold while the backend's transmit buffer is full; see [`crate::tx_queue`]
+    pub tx_queue: Option<u32>,
+}
+
+impl BusOptions {
+    pub const SEPARATOR: char = ';';
+
+    /// Splits bus params into the part the backend understands and the options after it.
+    pub fn split(params: &str) -> Result<(&str, Self), Error> {
+        let Some((params, opts)) = params.split_once(Self::SEPARATOR) else {
+            return Ok((params, Self::default()));
+        };
+        let mut options = Self::default();
+        for opt in opts.split(Self::SEPARATOR).map(str::trim) {
+            match opt {
+                "isolated" => options.isolated = true,
+                "confirm_tx" => options.confirm_tx = true,
+                "loop" => options.loop_replay = true,
+                "" => {}
+                other if other.contains('=') => {
+                    let (key, value) = other.split_once('=').unwrap_or_default();
+                    let parsed = match key {
+                        "bitrate" => value.parse().ok().map(|v| options.bitrate = Some(v)),
+                        "data_bitrate" => {
+                            value.parse().ok().map(|v| options.data_bitrate = Some(v))
+                        }
+                        "util_warn" => value
+                            .parse()
+                            .ok()
+                            .filter(|v| *v <= 100)
+                            .map(|v| options.util_warn = Some(v)),
+                        "speed" => value
+                            .parse()
+                            .ok()
+                            .filter(|v: &f32| v.is_finite() && *v > 0.0)
+                            .map(|v| options.speed = Some(v)),
+                        "tx_queue" => value
+                            .parse()
+                            .ok()
+                            .filter(|v| *v <= crate::tx_queue::MAX_TX_QUEUE)
+                            .map(|v| options.tx_queue = Some(v)),
+                        _ => None,
+                    };
+                    if parsed.is_none() {
+                        crate::log_error!("{params}: invalid bus option {other:?}");
+                        return Err(Error::InvalidBus);
+                    }
+                }
+                other => {
+                    crate::log_error!("{params}: unknown bus option {other:?}");
+                    return Err(Error::InvalidBus);
+                }
+            }
+        }
+        Ok((params, options))
+    }
+}
+
+/// A current-thread tokio runtime driven by its own OS thread.
+///
+/// Dropping it stops the runtime, which cancels whatever tasks are still on it.
+#[derive(Debug)]
+pub struct DedicatedRuntime {
+    handle: tokio::runtime::Handle,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl DedicatedRuntime {
+    pub fn new(thread_name: String) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| {
+                crate::log_error!("Could not start runtime for {thread_name}: {e}");
+                Error::FailedToOpenBus
+            })?;
+        let handle = runtime.handle().clone();
+        let (shutdown, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        std::thread::Builder::new()
+            .name(thread_name.clone())
+            .spawn(move || {
+                let _ = runtime.block_on(shutdown_rx);
+            })
+            .map_err(|e| {
+                crate::log_error!("Could not spawn thread {thread_name}: {e}");
+                Error::FailedToOpenBus
+            })?;
+        Ok(Self {
+            handle,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn handle(&self) -> tokio::runtime::Handle {
+        self.handle.clone()
+    }
+}
+
+impl Drop for DedicatedRuntime {
+    fn drop(&mut self) {
+        // not joined: a backend blocked in a syscall shouldn't hang whoever closes the bus
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Bus health: the controller's error state, and frames lost to full buffers.
+//!
+//! A CAN controller that keeps seeing errors (a loose wire, a missing terminator) counts them, and
+//! past certain counts goes error passive and then bus off, where it stops sending altogether.
+//! Frames are also lost without any error on the wire when a buffer fills up. Each bus keeps a
+//! [`BusStatus`] with both, from what its backend can see:
+//!
+//! - SocketCAN: the controller's state and error counters, from the error frames the driver sends
+//!   (and netlink when the bus opens), and controller buffer overflows.
+//! - HAL CAN: receive overruns of the HAL's stream sessions.
+//! - WebSocket: messages the server dropped because this client fell behind.
+//!
+//! On every bus, writes refused because the transmit queue was full count as transmit overruns.
+//! Backends that can't see the controller leave the state [`BusErrorState::Unknown`].
+//!
+//! Going error passive or bus off logs a warning. Every change is published to the bus's
+//! [`watch`] channel.
+
+use tokio::sync::watch;
+
+use crate::ReduxFIFOMessage;
+
+/// Linux CAN error frame classes, in the id; see `linux/can/error.h`.
+const CAN_ERR_CRTL: u32 = 0x0004;
+const CAN_ERR_BUSOFF: u32 = 0x0040;
+const CAN_ERR_RESTARTED: u32 = 0x0100;
+const CAN_ERR_CNT: u32 = 0x0200;
+
+/// Controller problems, in `data[1]` of a [`CAN_ERR_CRTL`] error frame.
+const CAN_ERR_CRTL_RX_OVERFLOW: u8 = 0x01;
+const CAN_ERR_CRTL_TX_OVERFLOW: u8 = 0x02;
+const CAN_ERR_CRTL_WARNING: u8 = 0x04 | 0x08;
+const CAN_ERR_CRTL_PASSIVE: u8 = 0x10 | 0x20;
+const CAN_ERR_CRTL_ACTIVE: u8 = 0x40;
+
+/// Fault confinement state of a bus's CAN controller.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum BusErrorState {
+    /// The backend can't see the controller
+    #[default]
+    Unknown = 0,
+    /// Normal operation
+    ErrorActive = 1,
+    /// An error counter passed 96; the bus is seeing errors but still works normally
+    ErrorWarning = 2,
+    /// An error counter passed 127; the controller can no longer flag errors it sees, and waits
+    /// longer between its own frames
+    ErrorPassive = 3,
+    /// The transmit error counter passed 255 and the controller left the bus
+    BusOff = 4,
+}
+
+/// How healthy a bus is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BusStatus {
+    pub state: BusErrorState,
+    /// Transmit error counter, if the controller reports it
+    pub tx_errors: Option<u16>,
+    /// Receive error counter, if the controller reports it
+    pub rx_errors: Option<u16>,
+    /// Times the bus went error passive
+    pub error_passive_count: u32,
+    /// Times the bus went bus off
+    pub bus_off_count: u32,
+    /// Frames that couldn't be sent because a transmit buffer was full, or overflows the
+    /// controller reported without a count
+    pub tx_overruns: u64,
+    /// Frames lost because a receive buffer was full, or overflows reported without a count
+    pub rx_overruns: u64,
+    /// When `state` last changed
+    pub state_since_us: u64,
+}
+
+/// Keeps a bus's [`BusStatus`] and publishes changes to it.
+#[derive(Debug)]
+pub struct BusStatusTracker {
+    bus_id: u16,
+    status: watch::Sender<BusStatus>,
+}
+
+impl BusStatusTracker {
+    pub fn new(bus_id: u16) -> Self {
+        Self {
+            bus_id,
+            status: watch::channel(BusStatus::default()).0,
+        }
+    }
+
+    pub fn status(&self) -> BusStatus {
+        *self.status.borrow()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<BusStatus> {
+        self.status.subscribe()
+    }
+
+    /// Moves the bus to `state`, counting and logging the transitions that matter.
+    pub fn set_state(&mut self, state: BusErrorState) {
+        let bus_id = self.bus_id;
+        self.status.send_if_modified(|status| {
+            let was = status.state;
+            if state == was {
+                return false;
+            }
+            status.state = state;
+            status.state_since_us = crate::timebase::now_us() as u64;
+            match state {
+                BusErrorState::ErrorPassive => {
+                    status.error_passive_count += 1;
+                    crate::log_warn!(
+                        "bus {bus_id}: CAN controller is error passive; check the bus's wiring \
+                         and termination"
+                    );
+                }
+                BusErrorState::BusOff => {
+                    status.bus_off_count += 1;
+                    crate::log_warn!(
+                        "bus {bus_id}: CAN controller is bus off and can't send; check the bus's \
+                         wiring and termination"
+                    );
+                }
+                BusErrorState::ErrorActive
+                    if matches!(was, BusErrorState::ErrorPassive | BusErrorState::BusOff) =>
+                {
+                    crate::log_info!("bus {bus_id}: CAN controller recovered");
+                }
+                _ => {}
+            }
+            true
+        });
+    }
+
+    pub fn set_error_counters(&mut self, tx_errors: u16, rx_errors: u16) {
+        self.status.send_if_modified(|status| {
+            let changed =
+                status.tx_errors != Some(tx_errors) || status.rx_errors != Some(rx_errors);
+            status.tx_errors = Some(tx_errors);
+            status.rx_errors = Some(rx_errors);
+            changed
+        });
+    }
+
+    pub fn tx_overrun(&mut self, frames: u64) {
+        if frames > 0 {
+            self.status
+                .send_modify(|status| status.tx_overruns += frames);
+        }
+    }
+
+    pub fn rx_overrun(&mut self, frames: u64) {
+        if frames > 0 {
+            self.status
+                .send_modify(|status| status.rx_overruns += frames);
+        }
+    }
+
+    /// Takes in a Linux CAN error frame, returning false if `msg` isn't one.
+    pub fn error_frame(&mut self, msg: &ReduxFIFOMessage) -> bool {
+        if !msg.err() {
+            return false;
+        }
+        let class = msg.message_id & 0x1fff_ffff;
+        let data = msg.data;
+        if class & CAN_ERR_BUSOFF != 0 {
+            self.set_state(BusErrorState::BusOff);
+        } else if class & CAN_ERR_CRTL != 0 {
+            let problem = data[1];
+            if problem & CAN_ERR_CRTL_PASSIVE != 0 {
+                self.set_state(BusErrorState::ErrorPassive);
+            } else if problem & CAN_ERR_CRTL_WARNING != 0 {
+                self.set_state(BusErrorState::ErrorWarning);
+            } else if problem & CAN_ERR_CRTL_ACTIVE != 0 {
+                self.set_state(BusErrorState::ErrorActive);
+            }
+            if problem & CAN_ERR_CRTL_RX_OVERFLOW != 0 {
+                self.rx_overrun(1);
+            }
+            if problem & CAN_ERR_CRTL_TX_OVERFLOW != 0 {
+                self.tx_overrun(1);
+            }
+        } else if class & CAN_ERR_RESTARTED != 0 {
+            self.set_state(BusErrorState::ErrorActive);
+        }
+        if class & CAN_ERR_CNT != 0 {
+            self.set_error_counters(data[6] as u16, data[7] as u16);
+        }
+        true
+    }
+}
@@ -0,0 +1,638 @@
+//! Parsed bus params.
+//!
+//! Bus params arrive as free-form strings from every frontend: the FFI and JNI, the REST server,
+//! the standalone command line and aliases. [`BusUri`] is what they mean, so that a typo is caught
+//! before a backend is started and reported with what was expected, rather than as whatever the
+//! backend happened to trip over. Options after a `;` are [`BusOptions`]' business and are split
+//! off first.
+//!
+//! Parsing only looks at the string. [`BusUri::check`] then looks at the machine, for things like
+//! a serial port or log file that isn't there.
+
+use core::{fmt, ops::RangeInclusive, str::FromStr};
+use std::borrow::Cow;
+
+use crate::{backends::BackendKind, bus_options::BusOptions, error::Error};
+
+/// Serial baud rates slcan adapters are opened at.
+pub const SLCAN_BAUD_RANGE: RangeInclusive<u32> = 1200..=4_000_000;
+
+/// Longest Linux network interface name.
+const MAX_INTERFACE_LEN: usize = 15;
+
+/// `ARPHRD_CAN`, the link type sysfs reports for CAN interfaces.
+const CAN_LINK_TYPE: &str = "280";
+
+/// Bus params, one variant per backend.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BusUri {
+    /// `halcan`
+    HalCan,
+    /// `socketcan:can0`, or `socketcan.fd:can0` for CAN FD
+    SocketCan { interface: String, fd: bool },
+    /// `rdxusb:0.16d0.1234.SERIAL`, with the vendor and product ids in hex
+    RdxUsb {
+        channel: u16,
+        vid: u16,
+        pid: u16,
+        serial: String,
+    },
+    /// `websocket:ws://host:port/path`
+    WebSocketLegacy { url: String },
+    /// `ws://host:port/path`
+    WebSocket { url: String },
+    /// `slcan:115200:/dev/ttyACM0`, or `slcan:auto:/dev/ttyACM0` to probe the CAN bitrate, with
+    /// `baud` as `None`
+    Slcan { baud: Option<u32>, path: String },
+    /// `sim:name`
+    Sim { name: String },
+    /// `replay:/path/to/log`
+    Replay { path: String },
+    /// `gsusb:SERIAL`, or `gsusb:SERIAL:1` for a channel other than the first
+    GsUsb { serial: String, channel: u16 },
+}
+
+impl BusUri {
+    /// Parses bus params that may have options after them, ignoring the options.
+    pub fn from_params(params: &str) -> Result<Self, BusUriError> {
+        params
+            .split_once(BusOptions::SEPARATOR)
+            .map_or(params, |(params, _)| params)
+            .parse()
+    }
+
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            BusUri::HalCan => BackendKind::HalCan,
+            BusUri::SocketCan { .. } => BackendKind::SocketCan,
+            BusUri::RdxUsb { .. } => BackendKind::RdxUsb,
+            BusUri::WebSocketLegacy { .. } => BackendKind::WebSocketLegacy,
+            BusUri::WebSocket { .. } => BackendKind::WebSocket,
+            BusUri::Slcan { .. } => BackendKind::Slcan,
+            BusUri::Sim { .. } => BackendKind::Sim,
+            BusUri::Replay { .. } => BackendKind::Replay,
+            BusUri::GsUsb { .. } => BackendKind::GsUsb,
+        }
+    }
+
+    /// Checks that the bus can be opened on this machine, as far as can be told without opening
+    /// it.
+    pub fn check(&self) -> Result<(), BusUriError> {
+        if !self.kind().supported() {
+            return Err(BusUriError::NotSupported(self.kind()));
+        }
+        match self {
+            // COM ports aren't files, so there is nothing to look for on Windows
+            BusUri::Slcan { path, .. } if cfg!(unix) && !std::path::Path::new(path).exists() => {
+                Err(BusUriError::PathNotFound(path.clone()))
+            }
+            BusUri::Replay { path } if !std::path::Path::new(path).is_file() => {
+                Err(BusUriError::PathNotFound(path.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Rewrites a bare CAN network interface name, like `can0`, as that interface over socketcan,
+    /// keeping any options after it. Anything else comes back unchanged.
+    pub fn expand_interface(params: &str) -> Cow<'_, str> {
+        let (bare, options) = params
+            .split_once(BusOptions::SEPARATOR)
+            .map_or((params, None), |(bare, options)| (bare, Some(options)));
+        if BackendKind::from_params(bare).is_some() || !is_can_interface(bare) {
+            return Cow::Borrowed(params);
+        }
+        let uri = BusUri::SocketCan {
+            interface: bare.to_string(),
+            fd: false,
+        };
+        match options {
+            Some(options) => Cow::Owned(format!("{uri}{}{options}", BusOptions::SEPARATOR)),
+            None => Cow::Owned(uri.to_string()),
+        }
+    }
+}
+
+fn valid_interface(interface: &str) -> bool {
+    !interface.is_empty()
+        && interface.len() <= MAX_INTERFACE_LEN
+        && !interface.contains(|c: char| c == '/' || c == ':' || c.is_whitespace())
+}
+
+/// Whether this machine has a CAN network interface called `name`.
+fn is_can_interface(name: &str) -> bool {
+    cfg!(target_os = "linux")
+        && valid_interface(name)
+        && std::fs::read_to_string(format!("/sys/class/net/{name}/type"))
+            .is_ok_and(|link_type| link_type.trim() == CAN_LINK_TYPE)
+}
+
+fn malformed(kind: BackendKind, input: &str) -> BusUriError {
+    BusUriError::Malformed {
+        kind,
+        input: input.to_string(),
+    }
+}
+
+fn invalid(
+    kind: BackendKind,
+    field: &'static str,
+    value: &str,
+    expected: &'static str,
+) -> BusUriError {
+    BusUriError::InvalidField {
+        kind,
+        field,
+        value: value.to_string(),
+        expected,
+    }
+}
+
+fn parse_url(kind: BackendKind, input: &str, url: &str) -> Result<String, BusUriError> {
+    let host = url
+        .strip_prefix("ws://")
+        .or_else(|| url.strip_prefix("wss://"))
+        .ok_or_else(|| malformed(kind, input))?;
+    if host.is_empty() || host.starts_with(['/', ':']) || host.contains(char::is_whitespace) {
+        return Err(invalid(kind, "host", host, "a host name or address"));
+    }
+    Ok(url.to_string())
+}
+
+impl FromStr for BusUri {
+    type Err = BusUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(kind) = BackendKind::from_params(s) else {
+            return Err(BusUriError::UnknownScheme(s.to_string()));
+        };
+        match kind {
+            BackendKind::HalCan if s == "halcan" => Ok(BusUri::HalCan),
+            BackendKind::HalCan => Err(malformed(kind, s)),
+            BackendKind::SocketCan => {
+                let (fd, interface) = match s.split_once(':') {
+                    Some(("socketcan", interface)) => (false, interface),
+                    Some(("socketcan.fd", interface)) => (true, interface),
+                    _ => return Err(malformed(kind, s)),
+                };
+                if !valid_interface(interface) {
+                    return Err(invalid(
+                        kind,
+                        "interface",
+                        interface,
+                        "a network interface name of up to 15 characters",
+                    ));
+                }
+                Ok(BusUri::SocketCan {
+                    interface: interface.to_string(),
+                    fd,
+                })
+            }
+            BackendKind::RdxUsb => {
+                let args = s
+                    .strip_prefix("rdxusb:")
+                    .ok_or_else(|| malformed(kind, s))?;
+                let mut parts = args.splitn(4, '.');
+                let (Some(channel), Some(vid), Some(pid), Some(serial)) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(malformed(kind, s));
+                };
+                let channel = channel
+                    .parse()
+                    .map_err(|_| invalid(kind, "channel", channel, "a decimal number"))?;
+                let vid = u16::from_str_radix(vid, 16)
+                    .map_err(|_| invalid(kind, "vendor id", vid, "up to 4 hex digits"))?;
+                let pid = u16::from_str_radix(pid, 16)
+                    .map_err(|_| invalid(kind, "product id", pid, "up to 4 hex digits"))?;
+                if serial.is_empty() {
+                    return Err(invalid(kind, "serial", serial, "a serial number"));
+                }
+                Ok(BusUri::RdxUsb {
+                    channel,
+                    vid,
+                    pid,
+                    serial: serial.to_string(),
+                })
+            }
+            BackendKind::WebSocketLegacy => {
+                let url = s.strip_prefix("websocket:").unwrap_or_default();
+                Ok(BusUri::WebSocketLegacy {
+                    url: parse_url(kind, s, url)?,
+                })
+            }
+            BackendKind::WebSocket => Ok(BusUri::WebSocket {
+                url: parse_url(kind, s, s)?,
+            }),
+            BackendKind::Slcan => {
+                let (baud, path) = s
+                    .strip_prefix("slcan:")
+                    .and_then(|args| args.split_once(':'))
+                    .ok_or_else(|| malformed(kind, s))?;
+                let baud = match baud {
+                    "auto" => None,
+                    baud => {
+                        let baud = baud.parse().map_err(|_| {
+                            invalid(kind, "baud rate", baud, "a decimal number or auto")
+                        })?;
+                        if !SLCAN_BAUD_RANGE.contains(&baud) {
+                            return Err(BusUriError::BaudOutOfRange(baud));
+                        }
+                        Some(baud)
+                    }
+                };
+                if path.is_empty() {
+                    return Err(invalid(kind, "serial port", path, "a serial port path"));
+                }
+                Ok(BusUri::Slcan {
+                    baud,
+                    path: path.to_string(),
+                })
+            }
+            BackendKind::Sim => Ok(BusUri::Sim {
+                name: s.strip_prefix("sim:").unwrap_or_default().to_string(),
+            }),
+            BackendKind::Replay => {
+                let path = s.strip_prefix("replay:").unwrap_or_default();
+                if path.is_empty() {
+                    return Err(invalid(kind, "log file", path, "a log file path"));
+                }
+                Ok(BusUri::Replay {
+                    path: path.to_string(),
+                })
+            }
+            BackendKind::GsUsb => {
+                let args = s.strip_prefix("gsusb:").unwrap_or_default();
+                let (serial, channel) = match args.split_once(':') {
+                    Some((serial, channel)) => (
+                        serial,
+                        channel
+                            .parse()
+                            .map_err(|_| invalid(kind, "channel", channel, "a decimal number"))?,
+                    ),
+                    None => (args, 0),
+                };
+                if serial.is_empty() {
+                    return Err(invalid(kind, "serial", serial, "a serial number"));
+                }
+                Ok(BusUri::GsUsb {
+                    serial: serial.to_string(),
+                    channel,
+                })
+            }
+        }
+    }
+}
+
+/// The canonical form of the params, which parses back to the same [`BusUri`].
+impl fmt::Display for BusUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusUri::HalCan => f.write_str("halcan"),
+            BusUri::SocketCan { interface, fd } => {
+                let fd = if *fd { ".fd" } else { "" };
+                write!(f, "socketcan{fd}:{interface}")
+            }
+            BusUri::RdxUsb {
+                channel,
+                vid,
+                pid,
+                serial,
+            } => write!(f, "rdxusb:{channel}.{vid:04x}.{pid:04x}.{serial}"),
+            BusUri::WebSocketLegacy { url } => write!(f, "websocket:{url}"),
+            BusUri::WebSocket { url } => f.write_str(url),
+            BusUri::Slcan {
+                baud: Some(baud),
+                path,
+            } => write!(f, "slcan:{baud}:{path}"),
+            BusUri::Slcan { baud: None, path } => write!(f, "slcan:auto:{path}"),
+            BusUri::Sim { name } => write!(f, "sim:{name}"),
+            BusUri::Replay { path } => write!(f, "replay:{path}"),
+            BusUri::GsUsb { serial, channel: 0 } => write!(f, "gsusb:{serial}"),
+            BusUri::GsUsb { serial, channel } => write!(f, "gsusb:{serial}:{channel}"),
+        }
+    }
+}
+
+/// Why bus params couldn't be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BusUriError {
+    /// Params that don't start like any backend's
+    UnknownScheme(String),
+    /// Params that start like `kind`'s but don't have its shape
+    Malformed {
+        kind: BackendKind,
+        input: String,
+    },
+    /// One part of the params has the wrong value
+    InvalidField {
+        kind: BackendKind,
+        field: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    BaudOutOfRange(u32),
+    /// Serial port or log file that doesn't exist
+    PathNotFound(String),
+    NotSupported(BackendKind),
+}
+
+impl BusUriError {
+    /// Every backend's params format, for listing in messages.
+    pub fn supported_schemes() -> String {
+        BackendKind::ALL
+            .into_iter()
+            .map(BackendKind::usage)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl fmt::Display for BusUriError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusUriError::UnknownScheme(input) => write!(
+                f,
+                "unknown bus {input:?}; expected one of {}",
+                Self::supported_schemes()
+            ),
+            BusUriError::Malformed { kind, input } => {
+                write!(
+                    f,
+                    "invalid {kind:?} bus {input:?}; expected {}",
+                    kind.usage()
+                )
+            }
+            BusUriError::InvalidField {
+                kind,
+                field,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid {field} {value:?} for {kind:?} bus; expected {expected} in {}",
+                kind.usage()
+            ),
+            BusUriError::BaudOutOfRange(baud) => write!(
+                f,
+                "slcan baud rate {baud} out of range; expected {} to {}",
+                SLCAN_BAUD_RANGE.start(),
+                SLCAN_BAUD_RANGE.end()
+            ),
+            BusUriError::PathNotFound(path) => write!(f, "{path} does not exist"),
+            BusUriError::NotSupported(kind) => {
+                write!(f, "{kind:?} backend not supported on this platform")
+            }
+        }
+    }
+}
+
+impl core::error::Error for BusUriError {}
+
+impl From<BusUriError> for Error {
+    fn from(value: BusUriError) -> Self {
+        match value {
+            BusUriError::PathNotFound(_) => Error::FailedToOpenBus,
+            BusUriError::NotSupported(_) => Error::BusNotSupported,
+            _ => Error::InvalidBus,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Parses `params`, checking that it prints back the same.
+    fn parse(params: &str) -> BusUri {
+        let uri: BusUri = params.parse().unwrap();
+        assert_eq!(uri.to_string(), params);
+        uri
+    }
+
+    #[test]
+    fn test_schemes() {
+        assert_eq!(parse("halcan"), BusUri::HalCan);
+        assert_eq!(
+            parse("socketcan:can0"),
+            BusUri::SocketCan {
+                interface: "can0".to_string(),
+                fd: false,
+            }
+        );
+        assert_eq!(
+            parse("socketcan.fd:can1"),
+            BusUri::SocketCan {
+                interface: "can1".to_string(),
+                fd: true,
+            }
+        );
+        assert_eq!(
+            parse("rdxusb:1.16d0.12ab.SERIAL.1"),
+            BusUri::RdxUsb {
+                channel: 1,
+                vid: 0x16d0,
+                pid: 0x12ab,
+                serial: "SERIAL.1".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("websocket:wss://10.0.0.2:7244/ws/0"),
+            BusUri::WebSocketLegacy {
+                url: "wss://10.0.0.2:7244/ws/0".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("ws://localhost:7244/ws/can0"),
+            BusUri::WebSocket {
+                url: "ws://localhost:7244/ws/can0".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("slcan:115200:/dev/ttyACM0"),
+            BusUri::Slcan {
+                baud: Some(115200),
+                path: "/dev/ttyACM0".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("slcan:auto:COM3"),
+            BusUri::Slcan {
+                baud: None,
+                path: "COM3".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("sim:bench"),
+            BusUri::Sim {
+                name: "bench".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("replay:/tmp/rdxlog_bus0.rdxlog"),
+            BusUri::Replay {
+                path: "/tmp/rdxlog_bus0.rdxlog".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("gsusb:0123ABCD"),
+            BusUri::GsUsb {
+                serial: "0123ABCD".to_string(),
+                channel: 0,
+            }
+        );
+        assert_eq!(
+            parse("gsusb:0123ABCD:1"),
+            BusUri::GsUsb {
+                serial: "0123ABCD".to_string(),
+                channel: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        // hex ids print zero-padded and lowercase
+        let uri: BusUri = "rdxusb:0.16D0.AB.x".parse().unwrap();
+        assert_eq!(uri.to_string(), "rdxusb:0.16d0.00ab.x");
+        // and the first gs_usb channel prints without its number
+        let uri: BusUri = "gsusb:x:0".parse().unwrap();
+        assert_eq!(uri.to_string(), "gsusb:x");
+    }
+
+    #[test]
+    fn test_options() {
+        assert_eq!(
+            BusUri::from_params("sim:bench;isolated").unwrap(),
+            BusUri::Sim {
+                name: "bench".to_string(),
+            }
+        );
+        assert_eq!(
+            BusUri::from_params("halcan;nonsense").unwrap(),
+            BusUri::HalCan
+        );
+        // parsing alone leaves options in
+        assert_eq!(
+            "sim:bench;isolated".parse::<BusUri>().unwrap(),
+            BusUri::Sim {
+                name: "bench;isolated".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        fn err(params: &str) -> BusUriError {
+            params.parse::<BusUri>().unwrap_err()
+        }
+        let malformed = |kind, input: &str| BusUriError::Malformed {
+            kind,
+            input: input.to_string(),
+        };
+        let field = |params, field| match err(params) {
+            BusUriError::InvalidField { field: bad, .. } => assert_eq!(bad, field, "{params}"),
+            e => panic!("{params}: {e:?}"),
+        };
+
+        assert_eq!(err(""), BusUriError::UnknownScheme(String::new()));
+        assert_eq!(err("can0"), BusUriError::UnknownScheme("can0".to_string()));
+        assert_eq!(
+            err("wss://host"),
+            BusUriError::UnknownScheme("wss://host".to_string())
+        );
+        assert_eq!(err("halcan:0"), malformed(BackendKind::HalCan, "halcan:0"));
+
+        assert_eq!(
+            err("socketcan"),
+            malformed(BackendKind::SocketCan, "socketcan")
+        );
+        assert_eq!(
+            err("socketcanfd:can0"),
+            malformed(BackendKind::SocketCan, "socketcanfd:can0")
+        );
+        field("socketcan:", "interface");
+        field("socketcan:can 0", "interface");
+        field("socketcan:../can0", "interface");
+        field("socketcan:a_very_long_name0", "interface");
+
+        assert_eq!(
+            err("rdxusb:0.16d0"),
+            malformed(BackendKind::RdxUsb, "rdxusb:0.16d0")
+        );
+        field("rdxusb:x.16d0.1234.s", "channel");
+        field("rdxusb:0.16g0.1234.s", "vendor id");
+        field("rdxusb:0.16d0.12345.s", "product id");
+        field("rdxusb:0.16d0.1234.", "serial");
+
+        assert_eq!(
+            err("websocket:http://host"),
+            malformed(BackendKind::WebSocketLegacy, "websocket:http://host")
+        );
+        field("websocket:ws://", "host");
+        field("ws://:7244", "host");
+        field("ws:///ws/0", "host");
+
+        assert_eq!(
+            err("slcan:115200"),
+            malformed(BackendKind::Slcan, "slcan:115200")
+        );
+        field("slcan:fast:/dev/ttyACM0", "baud rate");
+        field("slcan:115200:", "serial port");
+        assert_eq!(
+            err("slcan:300:/dev/ttyACM0"),
+            BusUriError::BaudOutOfRange(300)
+        );
+        assert_eq!(
+            err("slcan:8000000:/dev/ttyACM0"),
+            BusUriError::BaudOutOfRange(8_000_000)
+        );
+
+        field("replay:", "log file");
+        field("gsusb:", "serial");
+        field("gsusb:x:first", "channel");
+    }
+
+    #[test]
+    fn test_errors_list_usage() {
+        let e = "slcan:fast:/dev/ttyACM0".parse::<BusUri>().unwrap_err();
+        assert!(e.to_string().contains(BackendKind::Slcan.usage()), "{e}");
+        let e = "nonsense".parse::<BusUri>().unwrap_err().to_string();
+        for kind in BackendKind::ALL {
+            assert!(e.contains(kind.usage()), "{e}");
+        }
+        assert_eq!(
+            Error::from(BusUriError::BaudOutOfRange(1)),
+            Error::InvalidBus
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let missing = BusUri::Replay {
+            path: "/nonexistent/rdxlog_bus0.rdxlog".to_string(),
+        };
+        assert_eq!(
+            missing.check(),
+            Err(BusUriError::PathNotFound(
+                "/nonexistent/rdxlog_bus0.rdxlog".to_string()
+            ))
+        );
+        let sim = BusUri::Sim {
+            name: String::new(),
+        };
+        assert_eq!(sim.check(), Ok(()));
+    }
+
+    #[test]
+    fn test_expand_interface() {
+        // only CAN interfaces are taken as socketcan shorthand
+        assert_eq!(BusUri::expand_interface("lo"), "lo");
+        assert_eq!(BusUri::expand_interface("lo;confirm_tx"), "lo;confirm_tx");
+        assert_eq!(BusUri::expand_interface("nonexistent0"), "nonexistent0");
+        assert_eq!(BusUri::expand_interface("../lo"), "../lo");
+        // and params with a scheme are left alone
+        assert_eq!(BusUri::expand_interface("halcan"), "halcan");
+        assert_eq!(BusUri::expand_interface("socketcan:can0"), "socketcan:can0");
+    }
+}
@@ -0,0 +1,70 @@
+//! A token for cancelling long-running operations from somewhere else.
+//!
+//! Anything that can run for more than a moment — OTA updates, calibration, waits on a session —
+//! takes or hands out a [`CancelToken`]. Cancelling it makes the operation stop at its next await
+//! and return [`Error::Cancelled`](crate::error::Error::Cancelled); whatever the operation held,
+//! such as a device lock or a session, is released as its future is dropped, so the device is free
+//! again by the time the operation reports it was cancelled.
+//!
+//! Waits scoped to a single request, like the REST API's `?wait=` parameters, don't need a token:
+//! they're dropped, and so cancelled, when the client goes away.
+
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use tokio::sync::Notify;
+
+use crate::error::Error;
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+/// Cancels an operation when [cancelled](Self::cancel). Clones share the same cancellation.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<Inner>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every operation using this token, now and from now on.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Release);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Waits until the token is cancelled, returning right away if it already is.
+    pub async fn cancelled(&self) {
+        let notified = self.0.notify.notified();
+        tokio::pin!(notified);
+        // registers the waiter before checking, so a cancel in between isn't missed
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Runs `fut` until it finishes or the token is cancelled, in which case `fut` is dropped
+    /// without being polled again.
+    pub async fn run<T>(&self, fut: impl Future<Output = T>) -> Result<T, Error> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err(Error::Cancelled),
+            out = fut => Ok(out),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Which Cargo features a ReduxFIFO crate was built with, and which combinations it refuses.
+//!
+//! Each crate describes its features once with [`feature_matrix!`], which generates both a
+//! capability struct that can be asked at runtime (e.g. [`Capabilities::BUILT`]) and a
+//! `compile_error!` for every feature enabled without one it needs, or with one it can't be built
+//! alongside. Cargo already turns on what a feature implies, so the checks catch builds that get
+//! around that, like `--cfg 'feature="ffi"'` or a downstream crate's feature list, with an error
+//! that names the features instead of a missing symbol three crates away.
+
+/// Declares a crate's feature matrix.
+///
+/// ```ignore
+/// fifocore::feature_matrix! {
+///     /// Features this crate was built with.
+///     pub struct Capabilities {
+///         /// Session-less global instance
+///         singleton = "singleton",
+///         ffi = "ffi" requires("singleton"),
+///         wpihal_rio = "wpihal-rio" conflicts("wpihal-mrc"),
+///     }
+/// }
+/// ```
+///
+/// The features are checked where the macro is invoked, so each crate invokes it for its own.
+#[macro_export]
+macro_rules! feature_matrix {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                $field:ident = $feature:literal
+                    $(requires($($required:literal),+ $(,)?))?
+                    $(conflicts($($conflict:literal),+ $(,)?))?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        $vis struct $name {
+            $($(#[doc = $doc])* pub $field: bool,)*
+        }
+
+        impl $name {
+            /// What this crate was built with.
+            pub const BUILT: Self = Self {
+                $($field: cfg!(feature = $feature),)*
+            };
+
+            /// Every feature in the matrix, by its Cargo name, and whether it's enabled.
+            pub fn features(&self) -> Vec<(&'static str, bool)> {
+                vec![$(($feature, self.$field)),*]
+            }
+        }
+
+        $($($(
+            #[cfg(all(feature = $feature, not(feature = $required)))]
+            compile_error!(concat!(
+                "feature `", $feature, "` needs feature `", $required,
+                "` as well; enable it, or use one of the prebaked feature sets"
+            ));
+        )+)?)*
+        $($($(
+            #[cfg(all(feature = $feature, feature = $conflict))]
+            compile_error!(concat!(
+                "features `", $feature, "` and `", $conflict, "` can't be enabled together"
+            ));
+        )+)?)*
+    };
+}
+
+crate::feature_matrix! {
+    /// Features fifocore was built with.
+    pub struct Capabilities {
+        /// roboRIO HAL, for `halcan` buses and the FPGA timebase
+        wpihal_rio = "wpihal-rio" conflicts("wpihal-mrc"),
+        /// SystemCore HAL, for the FPGA timebase
+        wpihal_mrc = "wpihal-mrc",
+        athena = "athena" requires("wpihal-rio"),
+        /// Opens the SystemCore's CAN buses on startup
+        systemcore = "systemcore" requires("wpihal-mrc"),
+        systemcore_sim = "systemcore-sim" requires("wpihal-mrc"),
+        /// Conversions between messages and canandmessage's types
+        canandmessage = "canandmessage",
+    }
+}
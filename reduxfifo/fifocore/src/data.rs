@@ -88,6 +88,21 @@ impl ReduxFIFOMessage {
     pub const FLAG_DEV: u8 = 0x4;
     /// Set in the flags field if the message is sent from ReduxFIFO.
     pub const FLAG_TX: u8 = 0x8;
+    /// Set in the flags field by simulated devices writing to a `sim:` bus, so the message is
+    /// delivered as received traffic instead of as an echo of what ReduxFIFO sent.
+    pub const FLAG_SIM: u8 = 0x10;
+    /// Set in the flags field of messages that arrived while no session was open to read them,
+    /// delivered when a session asking for them re-opened; see [`crate::retain`].
+    pub const FLAG_HISTORICAL: u8 = 0x20;
+    /// Set in the flags field of CAN-FD messages with the error state indicator set, which the
+    /// sending node does while it is error passive. Written messages only keep it on backends that
+    /// let the host set it.
+    pub const FLAG_ESI: u8 = 0x40;
+
+    /// Starts building a message; see [`ReduxFIFOMessageBuilder`].
+    pub const fn builder() -> ReduxFIFOMessageBuilder {
+        ReduxFIFOMessageBuilder(Self::id_data(0, 0, [0u8; 64], 0, 0))
+    }
 
     /// Construct a new message from the component bits.
     pub const fn id_data(bus_id: u16, message_id: u32, data: [u8; 64], dlc: u8, flags: u8) -> Self {
@@ -134,10 +149,144 @@ impl ReduxFIFOMessage {
         self.flags & Self::FLAG_TX != 0
     }
 
+    pub const fn sim(&self) -> bool {
+        self.flags & Self::FLAG_SIM != 0
+    }
+
+    pub const fn historical(&self) -> bool {
+        self.flags & Self::FLAG_HISTORICAL != 0
+    }
+
+    pub const fn esi(&self) -> bool {
+        self.flags & Self::FLAG_ESI != 0
+    }
+
     pub fn data_slice(&self) -> &[u8] {
         let data_size = (self.data_size as usize).min(64);
         &self.data[..data_size]
     }
+
+    /// Replaces the data, keeping `data_size` in step. Anything past 64 bytes is dropped.
+    pub fn set_data(&mut self, data: &[u8]) {
+        let len = data.len().min(64);
+        self.data = [0u8; 64];
+        self.data[..len].copy_from_slice(&data[..len]);
+        self.data_size = len as u8;
+    }
+
+    pub const fn set_no_brs(&mut self, no_brs: bool) {
+        self.flags = with_flag(self.flags, Self::FLAG_NO_BRS, no_brs);
+    }
+
+    pub const fn set_no_fd(&mut self, no_fd: bool) {
+        self.flags = with_flag(self.flags, Self::FLAG_NO_FD, no_fd);
+    }
+
+    pub const fn set_device(&mut self, device: bool) {
+        self.flags = with_flag(self.flags, Self::FLAG_DEV, device);
+    }
+
+    pub const fn set_tx(&mut self, tx: bool) {
+        self.flags = with_flag(self.flags, Self::FLAG_TX, tx);
+    }
+
+    pub const fn set_sim(&mut self, sim: bool) {
+        self.flags = with_flag(self.flags, Self::FLAG_SIM, sim);
+    }
+
+    pub const fn set_esi(&mut self, esi: bool) {
+        self.flags = with_flag(self.flags, Self::FLAG_ESI, esi);
+    }
+}
+
+const fn with_flag(flags: u8, flag: u8, set: bool) -> u8 {
+    if set { flags | flag } else { flags & !flag }
+}
+
+/// Builds a [`ReduxFIFOMessage`] without touching flag bits or `data_size` by hand.
+///
+/// ```
+/// # use fifocore::ReduxFIFOMessage;
+/// let msg = ReduxFIFOMessage::builder()
+///     .bus(1)
+///     .id(0x0e0c_0002)
+///     .data(&[1, 2, 3])
+///     .fd(false)
+///     .build();
+/// assert_eq!(msg.data_slice(), &[1, 2, 3]);
+/// assert!(msg.no_fd());
+/// ```
+///
+/// Messages start out on bus 0 with id 0, no data, and no flags set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct ReduxFIFOMessageBuilder(ReduxFIFOMessage);
+impl ReduxFIFOMessageBuilder {
+    pub const fn bus(mut self, bus_id: u16) -> Self {
+        self.0.bus_id = bus_id;
+        self
+    }
+
+    /// Message id, including any of the [`MessageIdBuilder`] flag bits.
+    pub const fn id(mut self, message_id: u32) -> Self {
+        self.0.message_id = message_id;
+        self
+    }
+
+    /// Message data; `data_size` follows its length. Anything past 64 bytes is dropped.
+    pub fn data(mut self, data: &[u8]) -> Self {
+        self.0.set_data(data);
+        self
+    }
+
+    /// Whether the message goes out as CAN-FD on buses that support it (the default).
+    pub const fn fd(mut self, fd: bool) -> Self {
+        self.0.set_no_fd(!fd);
+        self
+    }
+
+    /// Whether CAN-FD messages switch bit rate for the data phase (the default).
+    pub const fn brs(mut self, brs: bool) -> Self {
+        self.0.set_no_brs(!brs);
+        self
+    }
+
+    /// Whether a CAN-FD message carries the error state indicator.
+    pub const fn esi(mut self, esi: bool) -> Self {
+        self.0.set_esi(esi);
+        self
+    }
+
+    /// Whether the message is addressed to the device itself. Only applicable on RdxUsb devices.
+    pub const fn device(mut self, device: bool) -> Self {
+        self.0.set_device(device);
+        self
+    }
+
+    pub const fn tx(mut self, tx: bool) -> Self {
+        self.0.set_tx(tx);
+        self
+    }
+
+    pub const fn sim(mut self, sim: bool) -> Self {
+        self.0.set_sim(sim);
+        self
+    }
+
+    pub const fn timestamp(mut self, timestamp: u64) -> Self {
+        self.0.timestamp = timestamp;
+        self
+    }
+
+    pub const fn build(self) -> ReduxFIFOMessage {
+        self.0
+    }
+}
+
+impl From<ReduxFIFOMessageBuilder> for ReduxFIFOMessage {
+    fn from(value: ReduxFIFOMessageBuilder) -> Self {
+        value.0
+    }
 }
 
 #[cfg(feature = "canandmessage")]
@@ -155,9 +304,7 @@ impl canandmessage::CanandMessage<ReduxFIFOMessage> for ReduxFIFOMessage {
         if data.len() > 64 {
             return Err(canandmessage::CanandMessageError::DataTooLarge(data.len()));
         }
-        let mut new_data = [0u8; 64];
-        new_data[..data.len()].copy_from_slice(data);
-        Ok(Self::id_data(0, id, new_data, data.len() as u8, 0))
+        Ok(Self::builder().id(id).data(data).build())
     }
 }
 
@@ -247,6 +394,9 @@ pub struct ReduxFIFOWriteBuffer {
     pub messages_written: u32,
     /// The number of messages in this buffer.
     pub length: u32,
+    /// Sequence number of the first message written, for looking up when it went out on the wire;
+    /// the rest follow consecutively. Zero if the bus doesn't confirm writes (output)
+    pub tx_seq: u32,
 }
 
 /// This is a metadata struct for a buffer that ReduxFIFO acts on.
@@ -275,7 +425,13 @@ pub struct ReduxFIFOReadBuffer {
 pub struct ReduxFIFOSessionConfig {
     pub filter_id: u32,
     pub filter_mask: u32,
+    /// Also receive messages this host writes to the bus, flagged with [`ReduxFIFOMessage::FLAG_TX`]
+    /// and timestamped when they were sent.
     pub echo_tx: bool,
+    /// Once the session closes, keep collecting what it would have received for this many
+    /// milliseconds and deliver it to the next session opened with the same filter, flagged with
+    /// [`ReduxFIFOMessage::FLAG_HISTORICAL`]. 0 retains nothing.
+    pub retain_ms: u32,
 }
 
 impl ReduxFIFOSessionConfig {
@@ -284,6 +440,7 @@ impl ReduxFIFOSessionConfig {
             filter_id,
             filter_mask,
             echo_tx: false,
+            retain_ms: 0,
         }
     }
 
@@ -298,6 +455,7 @@ impl Default for ReduxFIFOSessionConfig {
             filter_id: 0x0e0000,
             filter_mask: 0xff0000,
             echo_tx: false,
+            retain_ms: 0,
         }
     }
 }
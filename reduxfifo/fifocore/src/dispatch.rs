@@ -0,0 +1,122 @@
+//! Fan-out of received messages to sessions, and how long sessions wait on it.
+//!
+//! Backends hand received messages to [`SessionTable::ingest_messages`] in batches of at most
+//! [`MAX_DISPATCH_BATCH`]. Each batch is a single pass over its messages that copies every one into
+//! the read buffer of each session it matches, and only once the pass is done is each session that
+//! got something notified, once. A capture-all session on a busy bus therefore costs one wakeup per
+//! batch rather than one per message, and the filtered sessions behind it aren't left waiting on
+//! the bus lock while its reader churns.
+//!
+//! How well that works is measured per session by [`SessionWait`]: the time from a message being
+//! dispatched into an empty read buffer to the reader collecting it with a read barrier. Reads that
+//! waited longer than [`STARVED_AFTER_US`] count as starved.
+//!
+//! [`SessionTable::ingest_messages`]: crate::backends::SessionTable::ingest_messages
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Most messages a backend dispatches under one hold of the session table lock.
+pub const MAX_DISPATCH_BATCH: usize = 64;
+/// Waits longer than this between dispatch and read count as starved, in microseconds.
+pub const STARVED_AFTER_US: u64 = 20_000;
+
+/// How long one session's reader has been leaving dispatched messages unread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct SessionWait {
+    /// When the oldest unread message was dispatched
+    #[serde(skip)]
+    waiting_since: Option<u64>,
+    /// Read barriers that picked up at least one message
+    pub reads: u64,
+    /// Reads that waited longer than [`STARVED_AFTER_US`]
+    pub starved_reads: u64,
+    /// Longest wait seen, in microseconds
+    pub max_wait_us: u64,
+    /// Wait of the most recent read, in microseconds
+    pub last_wait_us: u64,
+}
+
+impl SessionWait {
+    /// Notes that messages were dispatched to the session at `now`.
+    pub(crate) fn dispatched(&mut self, now: u64) {
+        self.waiting_since.get_or_insert(now);
+    }
+
+    /// Notes a read barrier at `now`.
+    pub(crate) fn read(&mut self, now: u64) {
+        let Some(since) = self.waiting_since.take() else {
+            return;
+        };
+        let wait = now.saturating_sub(since);
+        self.reads += 1;
+        self.last_wait_us = wait;
+        self.max_wait_us = self.max_wait_us.max(wait);
+        let starved = wait > STARVED_AFTER_US;
+        if starved {
+            self.starved_reads += 1;
+        }
+        record_read(wait, starved);
+    }
+}
+
+/// Dispatch totals across every bus since startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct DispatchStats {
+    /// Batches dispatched
+    pub batches: u64,
+    /// Messages dispatched
+    pub messages: u64,
+    /// Copies of messages into session read buffers
+    pub deliveries: u64,
+    /// Read barriers that picked up at least one message
+    pub reads: u64,
+    /// Reads that waited longer than [`STARVED_AFTER_US`]
+    pub starved_reads: u64,
+    /// Longest wait between dispatch and read, in microseconds
+    pub max_wait_us: u64,
+}
+
+struct DispatchCounters {
+    batches: AtomicU64,
+    messages: AtomicU64,
+    deliveries: AtomicU64,
+    reads: AtomicU64,
+    starved_reads: AtomicU64,
+    max_wait_us: AtomicU64,
+}
+
+static COUNTERS: DispatchCounters = DispatchCounters {
+    batches: AtomicU64::new(0),
+    messages: AtomicU64::new(0),
+    deliveries: AtomicU64::new(0),
+    reads: AtomicU64::new(0),
+    starved_reads: AtomicU64::new(0),
+    max_wait_us: AtomicU64::new(0),
+};
+
+pub(crate) fn record_batch(messages: usize, deliveries: u64) {
+    COUNTERS.batches.fetch_add(1, Ordering::Relaxed);
+    COUNTERS
+        .messages
+        .fetch_add(messages as u64, Ordering::Relaxed);
+    COUNTERS.deliveries.fetch_add(deliveries, Ordering::Relaxed);
+}
+
+fn record_read(wait: u64, starved: bool) {
+    COUNTERS.reads.fetch_add(1, Ordering::Relaxed);
+    if starved {
+        COUNTERS.starved_reads.fetch_add(1, Ordering::Relaxed);
+    }
+    COUNTERS.max_wait_us.fetch_max(wait, Ordering::Relaxed);
+}
+
+pub fn dispatch_stats() -> DispatchStats {
+    DispatchStats {
+        batches: COUNTERS.batches.load(Ordering::Relaxed),
+        messages: COUNTERS.messages.load(Ordering::Relaxed),
+        deliveries: COUNTERS.deliveries.load(Ordering::Relaxed),
+        reads: COUNTERS.reads.load(Ordering::Relaxed),
+        starved_reads: COUNTERS.starved_reads.load(Ordering::Relaxed),
+        max_wait_us: COUNTERS.max_wait_us.load(Ordering::Relaxed),
+    }
+}
@@ -17,6 +17,15 @@ macro_rules! defn_error {
         }
 
         impl Error {
+            /// Stable name of the error variant, suitable for machine-readable error codes.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$name => stringify!($name),
+                    )+
+                }
+            }
+
             pub fn message(&self) -> &'static str {
                 match self {
                     $(
@@ -43,6 +52,7 @@ defn_error!(
     (NotInitialized,        REDUXFIFO_NOT_INITIALIZED,         -2, "ReduxFIFO not initialized"),
     (NullArgument,          REDUXFIFO_NULL_POINTER_ARGUMENT,   -3, "Null pointer passed as argument"),
     (JavaInvalidByteBuffer, REDUXFIFO_JAVA_INVALID_BYTEBUFFER, -4, "Invalid ByteBuffer passed"),
+    (NotAcknowledged,       REDUXFIFO_NOT_ACKNOWLEDGED,        -5, "Operation that enables actuators was not acknowledged"),
 
     (InvalidBus,       REDUXFIFO_INVALID_BUS,        -100, "Invalid bus param string or index"),
     (BusAlreadyOpened, REDUXFIFO_BUS_ALREADY_OPENED, -101, "Bus has already been opened"),
@@ -54,15 +64,23 @@ defn_error!(
     (BusWriteFail,     REDUXFIFO_BUS_WRITE_FAIL,     -107, "Failed to write message to bus"),
     (BusBufferFull,    REDUXFIFO_BUS_BUFFER_FULL,    -108, "Bus write buffer is full; retry later"),
     (BusDeviceBusy,    REDUXFIFO_BUS_DEVICE_BUSY,    -109, "Bus device is claimed by another backend (e.g. another USB backend)."),
+    (TxPending,        REDUXFIFO_TX_PENDING,         -110, "Written message has not been confirmed on the wire yet"),
+    (TxUnconfirmed,    REDUXFIFO_TX_UNCONFIRMED,     -111, "Written message has no on-wire confirmation (not tracked, lost, or expired)"),
+    (RoboRioPresent,   REDUXFIFO_ROBORIO_PRESENT,    -112, "A roboRIO heartbeat was heard on the bus"),
 
     (InvalidSessionID,       REDUXFIFO_INVALID_SESSION_ID,        -200, "Invalid session ID"),
     (SessionAlreadyOpened,   REDUXFIFO_SESSION_ALREADY_OPENED,    -201, "Session ID already opened"),
     (MaxSessionsOpened,      REDUXFIFO_MAX_SESSIONS_OPENED,       -202, "Maximum number of sessions opened"),
     (SessionClosed,          REDUXFIFO_SESSION_CLOSED,            -203, "Session closed duriong operation"),
     (MessageReceiveTimeout,  REDUXFIFO_MESSAGE_RECEIVE_TIMEOUT,   -204, "Message receive timeout"),
+    (MemoryLimitReached,     REDUXFIFO_MEMORY_LIMIT_REACHED,      -205, "Session buffer memory limit reached"),
+    (InvalidLabel,           REDUXFIFO_INVALID_LABEL,             -206, "Session label is empty, too long, or not printable ASCII"),
+    (Cancelled,              REDUXFIFO_CANCELLED,                 -207, "Operation cancelled"),
 
     (HalCanOpenSessionFail,  REDUXFIFO_HAL_CAN_OPEN_SESSION_FAIL, -301, "HAL_CAN_OpenStreamSession() failed"),
     (UsbClosed,              REDUXFIFO_USB_CLOSED,                -302, "USB transport has closed"),
+    (UsbDeviceNotFound,      REDUXFIFO_USB_DEVICE_NOT_FOUND,      -303, "No RdxUSB device with that serial number is connected"),
+    (UsbEnumerateFail,       REDUXFIFO_USB_ENUMERATE_FAIL,        -304, "Could not list the connected USB devices"),
 
     (DataTooLong,            REDUXFIFO_DATA_TOO_LONG,             -400, "Data length too long for this transport backend"),
 );
@@ -0,0 +1,161 @@
+//! FRC global disable, for stopping bench setups that have no driver station e-stop.
+//!
+//! Every FRC actuator drops its output when it sees the global disable frame, but only until the
+//! next frame that enables it again: a roboRIO heartbeat with the system watchdog set, or the
+//! non-roboRIO heartbeat a SPARK accepts from desktop tools. So after sending the disable, the bus
+//! is watched for a while. If something on it is still enabling actuators, the disable didn't
+//! stick and the report says so, along with which actuators were still talking.
+//!
+//! Actuator output itself isn't in any frame we can decode across vendors, so an actuator being
+//! heard from doesn't mean it's still driving; it's listed so the report says what was on the bus.
+
+use std::time::Duration;
+
+use frc_can_id::{FRCCanDeviceType, FRCCanHeartbeat, FRCCanId, FRCCanVendor, HEARTBEAT_ID};
+use serde::Serialize;
+
+use crate::{
+    FIFOCore, ReduxFIFOMessage, ReduxFIFOSessionConfig, error::Error, tx_queue::TxPriority,
+};
+
+/// How long the bus is watched after the disable by default. Actuators give up on a heartbeat
+/// after 100 ms, so this sees at least two of whatever is keeping them enabled.
+pub const DEFAULT_CONFIRM_WINDOW: Duration = Duration::from_millis(250);
+
+/// API id of the heartbeat SPARKs accept in place of the roboRIO's
+const REV_NON_RIO_HEARTBEAT_API: u16 = 0x0b2;
+
+/// An actuator heard from while the disable was being confirmed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActuatorActivity {
+    /// FRC CAN id of the device with the API bits cleared
+    pub device_id: u32,
+    /// `REV SPARK #3` and the like
+    pub name: String,
+    /// Frames it sent during the confirmation window
+    pub frames: u32,
+}
+
+/// What happened on one bus after the global disable was sent.
+#[derive(Debug, Clone, Serialize)]
+pub struct GlobalDisableReport {
+    pub bus_id: u16,
+    /// When the disable went out on the wire, on buses opened with `confirm_tx`
+    pub tx_timestamp: Option<u64>,
+    /// Frames seen during the window that re-enable actuators
+    pub enabling_frames: u32,
+    pub actuators: Vec<ActuatorActivity>,
+    /// The disable had to wait in the bus's transmit queue for room in its transmit buffer
+    pub queued: bool,
+    /// The disable was still waiting when the window closed, so it may never have been sent
+    pub still_queued: bool,
+    /// The disable was sent and nothing re-enabled the actuators during the window
+    pub confirmed: bool,
+}
+
+impl GlobalDisableReport {
+    fn observe(&mut self, msg: &ReduxFIFOMessage) {
+        let id = msg.id();
+        if enables_actuators(id, msg.data_slice()) {
+            self.enabling_frames += 1;
+        }
+        let can_id = FRCCanId::new(id);
+        if !is_actuator(can_id.device_type()) {
+            return;
+        }
+        let device_id = id & frc_can_id::DEVICE_FILTER;
+        match self.actuators.iter_mut().find(|a| a.device_id == device_id) {
+            Some(actuator) => actuator.frames += 1,
+            None => {
+                let label = frc_can_id::known::label(id);
+                let kind = match label.product {
+                    Some(product) => product.to_string(),
+                    None => format!("{}", label.device_type),
+                };
+                self.actuators.push(ActuatorActivity {
+                    device_id,
+                    name: format!("{} {kind} #{}", label.vendor, label.device_number),
+                    frames: 1,
+                });
+            }
+        }
+    }
+}
+
+fn is_actuator(device_type: FRCCanDeviceType) -> bool {
+    matches!(
+        device_type,
+        FRCCanDeviceType::MotorController
+            | FRCCanDeviceType::RelayController
+            | FRCCanDeviceType::PneumaticsController
+    )
+}
+
+/// Whether a frame lets actuators run again after a global disable.
+fn enables_actuators(id: u32, data: &[u8]) -> bool {
+    if id == HEARTBEAT_ID {
+        return data
+            .first_chunk::<8>()
+            .is_some_and(|data| FRCCanHeartbeat::new(*data).system_watchdog());
+    }
+    let can_id = FRCCanId::new(id);
+    can_id.manufacturer() == FRCCanVendor::Rev
+        && can_id.device_type() == FRCCanDeviceType::MotorController
+        && can_id.api_index() == REV_NON_RIO_HEARTBEAT_API
+}
+
+/// Sends the global disable frame on `bus_id` and watches the bus for `window`.
+pub async fn global_disable(
+    fifocore: &FIFOCore,
+    bus_id: u16,
+    window: Duration,
+) -> Result<GlobalDisableReport, Error> {
+    // open the session first so nothing sent right after the disable is missed
+    let session = fifocore.open_managed_session(bus_id, 256, ReduxFIFOSessionConfig::new(0, 0))?;
+    let mut notifier = session.rx_notifier()?;
+    let mut buffer = session.read_buffer(256);
+
+    let disable = ReduxFIFOMessage::builder()
+        .bus(bus_id)
+        .id(frc_can_id::GLOBAL_DISABLE)
+        .fd(false)
+        .build();
+    // ahead of anything waiting in the bus's transmit queue
+    let written = fifocore.write_single_with_priority(&disable, TxPriority::Urgent)?;
+
+    let mut report = GlobalDisableReport {
+        bus_id,
+        tx_timestamp: None,
+        enabling_frames: 0,
+        actuators: Vec::new(),
+        queued: written.queued(),
+        still_queued: false,
+        confirmed: false,
+    };
+    let deadline = tokio::time::Instant::now() + window;
+    loop {
+        match tokio::time::timeout_at(deadline, notifier.wait_for(|size| *size > 0)).await {
+            // holding the borrow blocks the bus from delivering more messages
+            Ok(Ok(size)) => drop(size),
+            Ok(Err(_)) => return Err(Error::SessionClosed),
+            Err(_) => break,
+        }
+        session.read_barrier(&mut buffer)?;
+        for msg in buffer.iter() {
+            report.observe(msg);
+        }
+    }
+
+    // urgent writes leave the queue first, so none waiting means the disable is out
+    report.still_queued =
+        written.queued() && fifocore.tx_queued(bus_id, Some(TxPriority::Urgent))? > 0;
+    if let Some(tx_seq) = written.tx_seq() {
+        report.tx_timestamp = fifocore
+            .tx_status(bus_id, tx_seq)
+            .and_then(|status| status.into_result())
+            .ok();
+    }
+    report.actuators.sort_by_key(|actuator| actuator.device_id);
+    report.confirmed = !report.still_queued && report.enabling_frames == 0;
+    Ok(report)
+}
@@ -1,12 +1,29 @@
-use std::sync::{Arc, atomic::AtomicU32};
+use std::{
+    sync::{Arc, atomic::AtomicU32},
+    time::Duration,
+};
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use tokio::{sync::watch, task::JoinHandle};
 
 use crate::{
     ReadBuffer, ReduxFIFOMessage, ReduxFIFOSession, ReduxFIFOSessionConfig, Session, WriteBuffer,
-    backends::{self, MessageBackend},
+    backends::{self, BackendKind, MessageBackend},
+    bus_alias::{BusAliases, BusRef},
+    bus_info::BusInfo,
+    bus_options::{BusOptions, DedicatedRuntime},
+    bus_status::BusStatus,
+    bus_uri::{BusUri, BusUriError},
+    dispatch::SessionWait,
     error::Error,
+    estop::{self, GlobalDisableReport},
+    pause::PauseStatus,
+    shm::ShmRing,
+    timestamp::TimestampStatus,
+    trace_tag::TraceTag,
+    tx_confirm::{self, TxStatus},
+    tx_queue::{self, TxPriority, Written},
+    utilization::UtilizationStatus,
 };
 
 #[allow(unused)]
@@ -33,6 +50,12 @@ pub struct FIFOCore {
     #[allow(unused)]
     usb_hotplug: DropAbortHandle,
     loggers: Arc<parking_lot::Mutex<FxHashMap<u16, crate::logger::Logger>>>,
+    /// Runtimes of buses opened with [`BusOptions::isolated`]; declared after `buses` so a bus is
+    /// always dropped before its runtime
+    dedicated: Arc<parking_lot::Mutex<FxHashMap<u16, DedicatedRuntime>>>,
+    aliases: Arc<parking_lot::Mutex<BusAliases>>,
+    /// Buses with a task sending their queued writes; locked before `buses`
+    tx_draining: Arc<parking_lot::Mutex<FxHashSet<u16>>>,
 }
 
 impl PartialEq for FIFOCore {
@@ -61,10 +84,16 @@ impl FIFOCore {
             usb_evloop,
             usb_hotplug,
             loggers: Default::default(),
+            dedicated: Default::default(),
+            aliases: Default::default(),
+            tx_draining: Default::default(),
         };
-        #[cfg(feature = "wpihal-rio")]
-        inst.open_or_get_bus("halcan")
-            .expect("Could not open wpihalcan");
+        // desktop processes that load a HAL-enabled build may not have the HAL at all
+        if BackendKind::HalCan.supported()
+            && let Err(e) = inst.open_or_get_bus("halcan")
+        {
+            crate::log_error!("Could not open wpihalcan: {e}");
+        }
 
         #[cfg(feature = "systemcore")]
         for bus in ["can_s0", "can_s1", "can_s2", "can_s3", "can_s4"] {
@@ -79,8 +108,13 @@ impl FIFOCore {
         self.runtime.clone()
     }
 
-    /// Searches for a bus matching the parameters.
+    /// Searches for a bus matching the parameters, which may also be an alias or a bare CAN
+    /// interface name (see [`BusUri::expand_interface`]).
+    ///
+    /// Bus options are ignored, so an already open bus is found whatever options it was opened with.
     pub fn bus_matching_params(&self, params: &str) -> Option<u16> {
+        let params = self.expand_alias(params);
+        let params = BusOptions::split(&params).map_or(params.as_str(), |(params, _)| params);
         let buses = self.buses.lock();
 
         for ent in buses.values() {
@@ -91,12 +125,75 @@ impl FIFOCore {
         None
     }
 
-    /// Opens a new bus with the given parameters or returns an error..
+    /// Opens a new bus with the given parameters, alias or bare CAN interface name, or returns an
+    /// error.
     pub fn open_or_get_bus(&self, params: &str) -> Result<u16, Error> {
-        if let Some(id) = self.bus_matching_params(params) {
+        let params = self.expand_alias(params);
+        if let Some(id) = self.bus_matching_params(&params) {
             return Ok(id);
         }
-        self.open_bus(params)
+        self.open_bus(&params)
+    }
+
+    /// Names the bus opened with `params` as `alias`, replacing any previous meaning of `alias`.
+    ///
+    /// The bus doesn't have to be open yet.
+    pub fn set_bus_alias(&self, alias: &str, params: &str) -> Result<(), Error> {
+        let previous = self.aliases.lock().insert(alias, params)?;
+        match previous {
+            Some(previous) if previous != params => {
+                crate::log_info!("Bus alias {alias} now {params} (was {previous})")
+            }
+            Some(_) => {}
+            None => crate::log_info!("Bus alias {alias} is {params}"),
+        }
+        Ok(())
+    }
+
+    /// Removes an alias, returning whether it existed. Buses it named stay open.
+    pub fn remove_bus_alias(&self, alias: &str) -> bool {
+        self.aliases.lock().remove(alias).is_some()
+    }
+
+    /// A snapshot of the alias registry.
+    pub fn bus_aliases(&self) -> BusAliases {
+        self.aliases.lock().clone()
+    }
+
+    /// The alias of an open bus, if it has one.
+    pub fn bus_alias(&self, bus_id: u16) -> Option<String> {
+        let params = {
+            let buses = self.buses.lock();
+            buses.get(&bus_id)?.params().to_string()
+        };
+        self.aliases.lock().alias_for(&params).map(str::to_string)
+    }
+
+    /// Resolves a bus given by id, alias or params to the id of the open bus.
+    ///
+    /// Ids are passed through without checking that the bus is open, like everywhere else ids are
+    /// accepted; names must refer to an open bus.
+    pub fn resolve_bus(&self, bus: &BusRef) -> Result<u16, Error> {
+        match bus {
+            BusRef::Id(id) => Ok(*id),
+            BusRef::Name(name) => match name.parse::<u16>() {
+                Ok(id) => Ok(id),
+                Err(_) => self.bus_matching_params(name).ok_or(Error::InvalidBus),
+            },
+        }
+    }
+
+    /// Parses and checks bus params or an alias without opening the bus, for frontends to report
+    /// what's wrong with them in more detail than an [`Error`] can.
+    pub fn validate_bus(&self, params: &str) -> Result<BusUri, BusUriError> {
+        let uri = BusUri::from_params(&self.expand_alias(params))?;
+        uri.check()?;
+        Ok(uri)
+    }
+
+    fn expand_alias(&self, params: &str) -> String {
+        let params = self.aliases.lock().expand(params).to_string();
+        BusUri::expand_interface(&params).into_owned()
     }
 
     /// Underlying open bus machinery.
@@ -107,68 +204,90 @@ impl FIFOCore {
         }
         let next_id = buses.keys().max().map_or(0, |v| *v + 1); //buses.len() as u16;
 
-        let backend: Result<Box<dyn MessageBackend>, Error> = if params.starts_with("halcan") {
+        let (params, options) = BusOptions::split(params)?;
+
+        let uri = params
+            .parse::<BusUri>()
+            .and_then(|uri| uri.check().map(|_| uri))
+            .map_err(|e| {
+                crate::log_error!("Couldn't open bus {params}: {e}");
+                Error::from(e)
+            })?;
+        let kind = uri.kind();
+
+        let dedicated = if options.isolated {
+            Some(DedicatedRuntime::new(format!("ReduxFIFO bus {next_id}"))?)
+        } else {
+            None
+        };
+        let runtime = dedicated
+            .as_ref()
+            .map_or_else(|| self.runtime.clone(), DedicatedRuntime::handle);
+
+        let backend: Box<dyn MessageBackend> = match kind {
             #[cfg(feature = "wpihal-rio")]
-            {
-                Ok(Box::new(backends::BusController::<
-                    backends::halcan::HalCanBackend,
-                >::new(
-                    next_id, params, self.runtime.clone()
-                )?))
-            }
-            #[cfg(not(feature = "wpihal-rio"))]
-            {
-                crate::log_error!(
-                    "halcan backend not supported without WPILib support compiled in"
-                );
-                Err(Error::BusNotSupported)
-            }
-        } else if params.starts_with("socketcan") {
+            BackendKind::HalCan => Box::new(backends::BusController::<
+                backends::halcan::HalCanBackend,
+            >::new(
+                next_id, params, options, runtime.clone()
+            )?),
             #[cfg(target_os = "linux")]
-            {
-                Ok(Box::new(backends::BusController::<
-                    backends::socketcan::SocketCanBackend,
-                >::new(
-                    next_id, params, self.runtime.clone()
-                )?))
-            }
-            #[cfg(not(target_os = "linux"))]
-            {
-                crate::log_error!("socketcan backend not supported on non-linux");
-                Err(Error::BusNotSupported)
-            }
-        } else if params.starts_with("rdxusb") {
-            Ok(Box::new(backends::BusController::<
+            BackendKind::SocketCan => Box::new(backends::BusController::<
+                backends::socketcan::SocketCanBackend,
+            >::new(
+                next_id, params, options, runtime.clone()
+            )?),
+            BackendKind::RdxUsb => Box::new(backends::BusController::<
                 backends::rdxusb::RdxUsbBackend,
             >::new(
                 next_id,
                 params,
-                self.runtime.clone(),
+                options,
+                runtime.clone(),
+                self.usb_evloop.clone(),
+            )?),
+            BackendKind::GsUsb => Box::new(backends::BusController::<
+                backends::gsusb::GsUsbBackend,
+            >::new(
+                next_id,
+                params,
+                options,
+                runtime.clone(),
                 self.usb_evloop.clone(),
-            )?))
-        } else if params.starts_with("websocket:") {
-            Ok(Box::new(backends::BusController::<
+            )?),
+            BackendKind::WebSocketLegacy => Box::new(backends::BusController::<
                 backends::websocket_legacy::WebSocketBackend,
             >::new(
-                next_id, params, self.runtime.clone()
-            )?))
-        } else if params.starts_with("ws:") {
-            Ok(Box::new(backends::BusController::<
+                next_id, params, options, runtime.clone()
+            )?),
+            BackendKind::WebSocket => Box::new(backends::BusController::<
                 backends::websocket::WebSocketBackend,
             >::new(
-                next_id, params, self.runtime.clone()
-            )?))
-        } else if params.starts_with("slcan:") {
-            Ok(Box::new(backends::BusController::<
+                next_id, params, options, runtime.clone()
+            )?),
+            BackendKind::Slcan => Box::new(backends::BusController::<
                 backends::slcan::SlcanBackend,
             >::new(
-                next_id, params, self.runtime.clone()
-            )?))
-        } else {
-            crate::log_error!("Unknown bus backend {params}");
-            Err(Error::InvalidBus)
+                next_id, params, options, runtime.clone()
+            )?),
+            BackendKind::Sim => Box::new(backends::BusController::<
+                backends::sim::SimBackend,
+            >::new(
+                next_id, params, options, runtime.clone()
+            )?),
+            BackendKind::Replay => Box::new(backends::BusController::<
+                backends::replay::ReplayBackend,
+            >::new(
+                next_id, params, options, runtime.clone()
+            )?),
+            // compiled out; `supported()` already turned these away
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::BusNotSupported),
         };
-        buses.insert(next_id, backend?);
+        buses.insert(next_id, backend);
+        if let Some(dedicated) = dedicated {
+            self.dedicated.lock().insert(next_id, dedicated);
+        }
         Ok(next_id)
     }
 
@@ -177,6 +296,8 @@ impl FIFOCore {
     pub fn close_bus(&self, bus_id: u16) -> Result<(), Error> {
         let mut buses = self.buses.lock();
         buses.remove(&bus_id).ok_or(Error::BusClosed)?;
+        drop(buses);
+        self.dedicated.lock().remove(&bus_id);
         Ok(())
     }
 
@@ -201,6 +322,51 @@ impl FIFOCore {
             .map(|b| b.max_packet_size())
     }
 
+    /// Where received message timestamps on `bus_id` come from; see [`crate::timestamp`].
+    pub fn bus_timestamps(&self, bus_id: u16) -> Result<TimestampStatus, Error> {
+        let buses = self.buses.lock();
+        buses
+            .get(&bus_id)
+            .ok_or(Error::InvalidBus)
+            .map(|b| b.timestamps())
+    }
+
+    /// How busy `bus_id` has been; see [`crate::utilization`].
+    pub fn bus_utilization(&self, bus_id: u16) -> Result<UtilizationStatus, Error> {
+        let buses = self.buses.lock();
+        buses
+            .get(&bus_id)
+            .ok_or(Error::InvalidBus)
+            .map(|b| b.utilization())
+    }
+
+    /// Error state and overruns of `bus_id`; see [`crate::bus_status`].
+    pub fn bus_status(&self, bus_id: u16) -> Result<BusStatus, Error> {
+        let buses = self.buses.lock();
+        buses
+            .get(&bus_id)
+            .ok_or(Error::InvalidBus)
+            .map(|b| b.status())
+    }
+
+    /// Return a [`watch::Receiver`] that changes whenever [`FIFOCore::bus_status`] does.
+    pub fn bus_status_watch(&self, bus_id: u16) -> Result<watch::Receiver<BusStatus>, Error> {
+        let buses = self.buses.lock();
+        buses
+            .get(&bus_id)
+            .ok_or(Error::InvalidBus)
+            .map(|b| b.status_notifier())
+    }
+
+    /// The hardware behind `bus_id`; see [`crate::bus_info`].
+    pub fn bus_info(&self, bus_id: u16) -> Result<BusInfo, Error> {
+        let buses = self.buses.lock();
+        buses
+            .get(&bus_id)
+            .ok_or(Error::InvalidBus)
+            .map(|b| b.info())
+    }
+
     pub fn sessions(&self, bus_id: u16) -> Vec<ReduxFIFOSession> {
         let buses = self.buses.lock();
         buses
@@ -209,6 +375,87 @@ impl FIFOCore {
             .map_or(Vec::new(), |b| b.sessions())
     }
 
+    /// How long `ses`'s reader has been leaving received messages unread; see [`crate::dispatch`].
+    pub fn session_wait(&self, ses: ReduxFIFOSession) -> Result<SessionWait, Error> {
+        let buses = self.buses.lock();
+        buses
+            .get(&ses.bus_id())
+            .ok_or(Error::InvalidBus)?
+            .session_wait(ses)
+    }
+
+    /// Labels `ses`, so that messages written with [`FIFOCore::write_single_as`] are attributed to
+    /// it in bus logs; see [`crate::trace_tag`].
+    pub fn set_session_label(&self, ses: ReduxFIFOSession, label: &str) -> Result<(), Error> {
+        let tag = TraceTag::intern(label)?;
+        let mut buses = self.buses.lock();
+        buses
+            .get_mut(&ses.bus_id())
+            .ok_or(Error::InvalidBus)?
+            .set_session_tag(ses, tag)
+    }
+
+    /// Maps a shared-memory ring of `capacity` messages and delivers `ses`'s messages to it from
+    /// now on, instead of to read barriers; see [`crate::shm`]. Messages already waiting move to
+    /// the ring. A ring already attached to the session is closed.
+    pub fn attach_ring(&self, ses: ReduxFIFOSession, capacity: u32) -> Result<Arc<ShmRing>, Error> {
+        let ring = Arc::new(ShmRing::new(ses, capacity)?);
+        let mut buses = self.buses.lock();
+        buses
+            .get_mut(&ses.bus_id())
+            .ok_or(Error::InvalidBus)?
+            .set_session_ring(ses, Some(ring.clone()))?;
+        Ok(ring)
+    }
+
+    /// Closes `ses`'s ring, if it has one, and goes back to delivering its messages to read
+    /// barriers.
+    pub fn detach_ring(&self, ses: ReduxFIFOSession) -> Result<(), Error> {
+        let mut buses = self.buses.lock();
+        buses
+            .get_mut(&ses.bus_id())
+            .ok_or(Error::InvalidBus)?
+            .set_session_ring(ses, None)
+    }
+
+    /// The label of `ses`, if it has one.
+    pub fn session_label(&self, ses: ReduxFIFOSession) -> Result<Option<Arc<str>>, Error> {
+        let buses = self.buses.lock();
+        buses
+            .get(&ses.bus_id())
+            .ok_or(Error::InvalidBus)?
+            .session_tag(ses)
+            .map(TraceTag::label)
+    }
+
+    /// Pauses `ses`: its messages are held, up to `cap` of them, instead of going to its read
+    /// barriers until it's resumed; see [`crate::pause`].
+    pub fn pause_session(&self, ses: ReduxFIFOSession, cap: u32) -> Result<(), Error> {
+        let mut buses = self.buses.lock();
+        buses
+            .get_mut(&ses.bus_id())
+            .ok_or(Error::InvalidBus)?
+            .pause_session(ses, cap)
+    }
+
+    /// Resumes a paused `ses`, delivering what it held before anything newer.
+    pub fn resume_session(&self, ses: ReduxFIFOSession) -> Result<(), Error> {
+        let mut buses = self.buses.lock();
+        buses
+            .get_mut(&ses.bus_id())
+            .ok_or(Error::InvalidBus)?
+            .resume_session(ses)
+    }
+
+    /// Whether `ses` is paused, and what it's holding.
+    pub fn session_pause(&self, ses: ReduxFIFOSession) -> Result<PauseStatus, Error> {
+        let buses = self.buses.lock();
+        buses
+            .get(&ses.bus_id())
+            .ok_or(Error::InvalidBus)?
+            .session_pause(ses)
+    }
+
     /// Opens a new session with the given initial read buffer.
     pub fn open_session(
         &self,
@@ -263,6 +510,53 @@ impl FIFOCore {
         Ok(())
     }
 
+    /// Takes `ses`'s messages timestamped from `t0_us` to `t1_us` inclusive, oldest first, in the
+    /// [`crate::timebase`] (FPGA time on a robot). Messages from before the window are discarded
+    /// and later ones stay waiting, so reading consecutive windows sees each message once. An empty
+    /// window (`t0_us > t1_us`) takes nothing.
+    ///
+    /// Sessions with a [ring](Self::attach_ring) attached have nothing waiting to take.
+    pub fn read_window(
+        &self,
+        ses: ReduxFIFOSession,
+        t0_us: u64,
+        t1_us: u64,
+    ) -> Result<Vec<ReduxFIFOMessage>, Error> {
+        self.read_window_up_to(ses, t0_us, t1_us, usize::MAX)
+    }
+
+    /// [`Self::read_window`] that takes at most `max` messages, leaving the rest of the window
+    /// waiting for the next read.
+    pub fn read_window_up_to(
+        &self,
+        ses: ReduxFIFOSession,
+        t0_us: u64,
+        t1_us: u64,
+        max: usize,
+    ) -> Result<Vec<ReduxFIFOMessage>, Error> {
+        if t0_us > t1_us {
+            return Ok(Vec::new());
+        }
+        let mut buses = self.buses.lock();
+        let bus = buses.get_mut(&ses.bus_id()).ok_or(Error::InvalidBus)?;
+        bus.read_window(ses, t0_us, t1_us, max)
+    }
+
+    /// [`Self::read_window`] with the window in host monotonic time
+    /// ([`crate::timebase::monotonic_us`]), for callers that don't have the FPGA clock.
+    pub fn read_window_monotonic(
+        &self,
+        ses: ReduxFIFOSession,
+        t0_us: i64,
+        t1_us: i64,
+    ) -> Result<Vec<ReduxFIFOMessage>, Error> {
+        self.read_window(
+            ses,
+            crate::timebase::retimestamp_from_monotonic(t0_us),
+            crate::timebase::retimestamp_from_monotonic(t1_us),
+        )
+    }
+
     /// Executes a multi-bus read barrier.
     /// For each slice in the read buffer, the bus ID is determined from the first entry.
     pub fn read_barrier_multibus<'a>(
@@ -283,22 +577,167 @@ impl FIFOCore {
     }
 
     pub fn write_barrier(&self, data: &mut [WriteBuffer]) {
-        let mut buses = self.buses.lock();
-        for buffer in data {
-            let bus_id = buffer.meta.bus_id as u16;
-            buffer.ready_for_write();
-            let Some(bus) = buses.get_mut(&bus_id) else {
-                buffer.set_status(Err(Error::InvalidBus));
-                return;
-            };
-            bus.write_barrier(buffer);
+        let mut queued = Vec::new();
+        {
+            let mut buses = self.buses.lock();
+            for buffer in data {
+                let bus_id = buffer.meta.bus_id as u16;
+                buffer.ready_for_write();
+                let Some(bus) = buses.get_mut(&bus_id) else {
+                    buffer.set_status(Err(Error::InvalidBus));
+                    break;
+                };
+                bus.write_barrier(buffer);
+                if bus.tx_queued(None) > 0 {
+                    queued.push(bus_id);
+                }
+            }
+        }
+        for bus_id in queued {
+            self.drain_tx_in_background(bus_id);
         }
     }
 
     pub fn write_single(&self, msg: &ReduxFIFOMessage) -> Result<(), Error> {
-        let mut buses = self.buses.lock();
-        let bus = buses.get_mut(&msg.bus_id).ok_or(Error::InvalidBus)?;
-        bus.write_single(msg)
+        self.write_single_tracked(msg).map(|_| ())
+    }
+
+    /// Writes a message, returning the sequence number to look up its on-wire timestamp with
+    /// if the bus was opened with [`BusOptions::confirm_tx`].
+    pub fn write_single_tracked(&self, msg: &ReduxFIFOMessage) -> Result<Option<u32>, Error> {
+        self.write_single_tagged(msg, TraceTag::NONE)
+    }
+
+    /// Writes a message on behalf of `ses`, which must be on the message's bus, logging it with
+    /// the session's label.
+    pub fn write_single_as(
+        &self,
+        ses: ReduxFIFOSession,
+        msg: &ReduxFIFOMessage,
+    ) -> Result<(), Error> {
+        let written = {
+            let mut buses = self.buses.lock();
+            let bus = buses.get_mut(&msg.bus_id).ok_or(Error::InvalidBus)?;
+            let tag = bus.session_tag(ses)?;
+            bus.write_single(msg, tag, TxPriority::Arbitration)?
+        };
+        self.drain_if_queued(msg.bus_id, written);
+        Ok(())
+    }
+
+    /// Writes a message, logging it as written by `tag`.
+    pub fn write_single_tagged(
+        &self,
+        msg: &ReduxFIFOMessage,
+        tag: TraceTag,
+    ) -> Result<Option<u32>, Error> {
+        let written = {
+            let mut buses = self.buses.lock();
+            let bus = buses.get_mut(&msg.bus_id).ok_or(Error::InvalidBus)?;
+            bus.write_single(msg, tag, TxPriority::Arbitration)?
+        };
+        Ok(self.drain_if_queued(msg.bus_id, written).tx_seq())
+    }
+
+    /// Writes a message placed by `priority` if it has to wait in the bus's
+    /// [transmit queue](crate::tx_queue), returning whether it did.
+    pub fn write_single_with_priority(
+        &self,
+        msg: &ReduxFIFOMessage,
+        priority: TxPriority,
+    ) -> Result<Written, Error> {
+        let written = {
+            let mut buses = self.buses.lock();
+            let bus = buses.get_mut(&msg.bus_id).ok_or(Error::InvalidBus)?;
+            bus.write_single(msg, TraceTag::NONE, priority)?
+        };
+        Ok(self.drain_if_queued(msg.bus_id, written))
+    }
+
+    /// How many writes of `priority`, or of any priority, are waiting in a bus's
+    /// [transmit queue](crate::tx_queue).
+    pub fn tx_queued(&self, bus_id: u16, priority: Option<TxPriority>) -> Result<usize, Error> {
+        let buses = self.buses.lock();
+        let bus = buses.get(&bus_id).ok_or(Error::InvalidBus)?;
+        Ok(bus.tx_queued(priority))
+    }
+
+    fn drain_if_queued(&self, bus_id: u16, written: Written) -> Written {
+        if written.queued() {
+            self.drain_tx_in_background(bus_id);
+        }
+        written
+    }
+
+    /// Keeps sending a bus's queued writes as the backend makes room for them, until the queue is
+    /// empty or the bus is closed. Barriers drain the queue too, but a bus nobody touches again
+    /// would otherwise hold its writes forever.
+    fn drain_tx_in_background(&self, bus_id: u16) {
+        if !self.tx_draining.lock().insert(bus_id) {
+            return;
+        }
+        let buses = self.buses.clone();
+        let draining = self.tx_draining.clone();
+        self.runtime.spawn(async move {
+            loop {
+                tokio::time::sleep(tx_queue::DRAIN_INTERVAL).await;
+                // holding `draining` across the check means a write queued after it starts a
+                // new task instead of being left behind
+                let mut draining = draining.lock();
+                let queued = buses
+                    .lock()
+                    .get_mut(&bus_id)
+                    .map_or(0, |bus| bus.drain_tx_queue());
+                if queued == 0 {
+                    draining.remove(&bus_id);
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Looks up whether a write made it onto the wire, without waiting.
+    pub fn tx_status(&self, bus_id: u16, tx_seq: u32) -> Result<TxStatus, Error> {
+        let buses = self.buses.lock();
+        let bus = buses.get(&bus_id).ok_or(Error::InvalidBus)?;
+        Ok(bus.tx_status(tx_seq))
+    }
+
+    /// Waits for a write to be confirmed on the wire and returns when it went out.
+    ///
+    /// Resolves to [`Error::TxUnconfirmed`] once the write is given up on, which takes at most
+    /// [`tx_confirm::CONFIRM_TIMEOUT_US`] after it was written.
+    pub async fn tx_confirmed(&self, bus_id: u16, tx_seq: u32) -> Result<u64, Error> {
+        let mut notifier = {
+            let buses = self.buses.lock();
+            buses.get(&bus_id).ok_or(Error::InvalidBus)?.tx_notifier()
+        };
+        loop {
+            match self.tx_status(bus_id, tx_seq)? {
+                TxStatus::Pending => {}
+                status => return status.into_result(),
+            }
+            // writes age out without anything being sent on the notifier, so recheck now and then
+            let recheck = Duration::from_micros(tx_confirm::CONFIRM_TIMEOUT_US / 4);
+            if let Ok(Err(_)) = tokio::time::timeout(recheck, notifier.changed()).await {
+                return Err(Error::BusClosed);
+            }
+        }
+    }
+
+    /// Sends the FRC global disable frame on each of `bus_ids` at once, then watches each bus for
+    /// `window` to see whether the disable stuck. See [`estop`].
+    pub async fn global_disable(
+        &self,
+        bus_ids: &[u16],
+        window: Duration,
+    ) -> Vec<Result<GlobalDisableReport, Error>> {
+        futures::future::join_all(
+            bus_ids
+                .iter()
+                .map(|&bus_id| estop::global_disable(self, bus_id, window)),
+        )
+        .await
     }
 
     /// Returns an RX buffer size listener.
@@ -310,12 +749,80 @@ impl FIFOCore {
         bus.rx_notifier(ses)
     }
 
+    /// Opens the debug console of the RdxUSB device with USB serial number `serial`.
+    ///
+    /// The console shares the device's connection with its CAN channels,
+    /// so this opens channel 0 as a bus too if no channel of the device is open yet.
+    /// Lists the Redux devices attached over USB without claiming them, noting which ones are
+    /// already open as buses.
+    pub async fn usb_devices(&self) -> Result<Vec<backends::rdxusb::UsbDeviceListing>, Error> {
+        let mut devices = backends::rdxusb::list_devices().await?;
+        let usb_evloop = self.usb_evloop.lock();
+        for device in devices.iter_mut() {
+            device.open = device
+                .device_id()
+                .is_some_and(|id| usb_evloop.find(&id).is_some());
+        }
+        Ok(devices)
+    }
+
+    pub async fn open_console(&self, serial: &str) -> Result<backends::rdxusb::Console, Error> {
+        let device_id = backends::rdxusb::find_device(serial).await?;
+        let session = self.usb_evloop.lock().find(&device_id);
+        let session = match session {
+            Some(session) => session,
+            None => {
+                self.open_or_get_bus(&format!(
+                    "rdxusb:0.{:04x}.{:04x}.{serial}",
+                    device_id.vid, device_id.pid
+                ))?;
+                self.usb_evloop
+                    .lock()
+                    .find(&device_id)
+                    .ok_or(Error::UsbClosed)?
+            }
+        };
+        if session.tag() != "rdxusb" {
+            return Err(Error::BusDeviceBusy);
+        }
+        Ok(backends::rdxusb::Console::new(session))
+    }
+
+    /// Starts a new log of `bus`, keeping the filter of the log it replaces, if any.
+    pub fn open_log(&self, log_path: std::path::PathBuf, bus: u16) -> Result<(), Error> {
+        let filter = self.log_filter(bus).unwrap_or_default();
+        self.open_log_filtered(log_path, bus, filter)
+    }
+
+    /// Starts a new log of `bus` keeping the messages `filter` does.
+    ///
+    /// The log is written in the format its file extension names, or if `log_path` is a folder or
+    /// has another extension, the format of the log it replaces, defaulting to `.rdxlog`.
+    pub fn open_log_filtered(
+        &self,
+        log_path: std::path::PathBuf,
+        bus: u16,
+        filter: crate::logger::LogFilter,
+    ) -> Result<(), Error> {
+        let format = crate::logger::LogFormat::from_path(&log_path)
+            .filter(|_| !log_path.is_dir())
+            .or_else(|| self.log_format(bus))
+            .unwrap_or_default();
+        self.open_log_as(log_path, bus, filter, format)
+    }
+
     /// TODO: this is terrible.
     ///
     /// Needs:
     /// * auto-renaming
     /// * ability to hook multiple buses into one logger
-    pub fn open_log(&self, log_path: std::path::PathBuf, bus: u16) -> Result<(), Error> {
+    pub fn open_log_as(
+        &self,
+        log_path: std::path::PathBuf,
+        bus: u16,
+        filter: crate::logger::LogFilter,
+        format: crate::logger::LogFormat,
+    ) -> Result<(), Error> {
         let time_sec = crate::timebase::now_us() as f64 / 1_000_000.0_f64;
         let actual_log_path = if log_path.is_dir() {
             if !log_path.exists() {
@@ -325,13 +832,19 @@ impl FIFOCore {
             let dt: chrono::DateTime<chrono::Utc> = std::time::SystemTime::now().into();
 
             let dt_fmt = dt.format("%Y_%M_%dT%H_%M_%S");
-            log_path.join(format!("rdxlog_bus{bus}_{dt_fmt}_{time_sec:.06}.rdxlog"))
+            // aliases are restricted to characters that are safe in file names
+            let label = self.bus_alias(bus).unwrap_or_else(|| format!("bus{bus}"));
+            log_path.join(format!(
+                "rdxlog_{label}_{dt_fmt}_{time_sec:.06}.{}",
+                format.extension()
+            ))
         } else {
             log_path
         };
         let mut buses = self.buses.lock();
         let bus_inst = buses.get_mut(&bus).ok_or(Error::InvalidBus)?;
-        let logger = crate::logger::Logger::new(actual_log_path, self.runtime().clone());
+        let logger =
+            crate::logger::Logger::new(actual_log_path, filter, format, self.runtime().clone());
         bus_inst.set_logger(logger.sender());
         drop(buses);
         let mut loggers = self.loggers.lock();
@@ -340,6 +853,51 @@ impl FIFOCore {
         Ok(())
     }
 
+    /// Reopens the log of every bus whose logger died, appending to the same file.
+    ///
+    /// Returns the ids of the buses whose logs were restarted.
+    pub fn restart_dead_loggers(&self) -> Vec<u16> {
+        let mut loggers = self.loggers.lock();
+        let mut restarted = Vec::new();
+        for (bus_id, logger) in loggers.iter_mut() {
+            if !logger.is_dead() {
+                continue;
+            }
+            crate::log_warn!(
+                "Logger for bus {bus_id} died, reopening {}",
+                logger.path().display()
+            );
+            let new_logger = crate::logger::Logger::new(
+                logger.path().to_path_buf(),
+                logger.filter().clone(),
+                logger.format(),
+                self.runtime().clone(),
+            );
+            if let Some(bus_inst) = self.buses.lock().get_mut(bus_id) {
+                bus_inst.set_logger(new_logger.sender());
+            }
+            *logger = new_logger;
+            restarted.push(*bus_id);
+        }
+        restarted
+    }
+
+    /// Filter of the log currently open on `bus_id`.
+    pub fn log_filter(&self, bus_id: u16) -> Option<crate::logger::LogFilter> {
+        self.loggers
+            .lock()
+            .get(&bus_id)
+            .map(|logger| logger.filter().clone())
+    }
+
+    /// Format of the log currently open on `bus_id`.
+    pub fn log_format(&self, bus_id: u16) -> Option<crate::logger::LogFormat> {
+        self.loggers
+            .lock()
+            .get(&bus_id)
+            .map(|logger| logger.format())
+    }
+
     pub fn close_log(&self, bus_id: u16) -> Result<(), Error> {
         let mut loggers = self.loggers.lock();
         loggers.remove(&bus_id);
@@ -351,3 +909,60 @@ impl FIFOCore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ids(msgs: Vec<ReduxFIFOMessage>) -> Vec<u32> {
+        msgs.iter().map(|msg| msg.message_id).collect()
+    }
+
+    #[test]
+    fn test_read_window() {
+        let _serial = crate::limits::test::SERIAL.lock();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let fifocore = FIFOCore::new(runtime.handle().clone());
+        let bus_id = fifocore.open_or_get_bus("sim:read_window").unwrap();
+        let ses = fifocore
+            .open_session(bus_id, 16, ReduxFIFOSessionConfig::new(0, 0))
+            .unwrap();
+
+        // marks[i] falls between the (i + 1)th message and the next one
+        let mut marks = Vec::new();
+        for id in 1..=4 {
+            let msg = ReduxFIFOMessage::builder().bus(bus_id).id(id).sim(true);
+            fifocore.write_single(&msg.build()).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+            marks.push(crate::timebase::now_us() as u64);
+        }
+
+        // an empty window takes nothing, and discards nothing either
+        assert!(
+            fifocore
+                .read_window(ses, marks[1], marks[0])
+                .unwrap()
+                .is_empty()
+        );
+        // 1 is older than the window and discarded, 3 and 4 are newer and stay waiting
+        assert_eq!(
+            ids(fifocore.read_window(ses, marks[0], marks[1]).unwrap()),
+            [2]
+        );
+        assert!(fifocore.read_window(ses, 0, marks[1]).unwrap().is_empty());
+        assert_eq!(
+            ids(fifocore.read_window_up_to(ses, 0, marks[3], 1).unwrap()),
+            [3]
+        );
+        assert_eq!(
+            ids(fifocore
+                .read_window_monotonic(ses, marks[1] as i64, marks[3] as i64)
+                .unwrap()),
+            [4]
+        );
+        assert_eq!(
+            fifocore.read_window(ReduxFIFOSession::from_parts(0, bus_id + 1), 0, 1),
+            Err(Error::InvalidBus)
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! Runtime detection of the WPILib HAL.
+//!
+//! A build with wpihal support compiled in may still end up loaded into a process that doesn't
+//! have the HAL, e.g. a desktop tool that picked up the same shared library the vendordep ships.
+//! Nothing here calls into the HAL; it only checks whether the symbols we need can be resolved,
+//! so everything else can fall back to not using it instead of crashing on the first HAL call.
+
+use std::sync::LazyLock;
+
+/// HAL functions the halcan backend and the FPGA timebase call into.
+#[cfg(all(unix, any(feature = "wpihal-rio", feature = "wpihal-mrc")))]
+const REQUIRED_SYMBOLS: &[&core::ffi::CStr] = &[
+    c"HAL_Initialize",
+    c"HAL_GetFPGATime",
+    c"HAL_CAN_SendMessage",
+    c"HAL_CAN_OpenStreamSession",
+    c"HAL_CAN_ReadStreamSession",
+    c"HAL_CAN_CloseStreamSession",
+];
+
+static AVAILABLE: LazyLock<bool> = LazyLock::new(|| {
+    let available = probe();
+    if !available && cfg!(any(feature = "wpihal-rio", feature = "wpihal-mrc")) {
+        crate::log_info!("WPILib HAL not found in this process; HAL-backed features are disabled");
+    }
+    available
+});
+
+/// Returns true if wpihal support is compiled in and the HAL is present in this process.
+///
+/// The result is probed once and cached.
+pub fn available() -> bool {
+    *AVAILABLE
+}
+
+#[cfg(all(unix, any(feature = "wpihal-rio", feature = "wpihal-mrc")))]
+fn probe() -> bool {
+    REQUIRED_SYMBOLS.iter().all(|sym| {
+        // SAFETY: RTLD_DEFAULT searches the global scope and the name is null-terminated
+        let addr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, sym.as_ptr()) };
+        if addr.is_null() {
+            crate::log_debug!("HAL symbol {sym:?} not found");
+        }
+        !addr.is_null()
+    })
+}
+
+/// Windows resolves the HAL at load time, so if we got this far it's there.
+#[cfg(all(not(unix), any(feature = "wpihal-rio", feature = "wpihal-mrc")))]
+fn probe() -> bool {
+    true
+}
+
+#[cfg(not(any(feature = "wpihal-rio", feature = "wpihal-mrc")))]
+fn probe() -> bool {
+    false
+}
@@ -0,0 +1,305 @@
+//! The slice of [`FIFOCore`] that session users need, as a trait.
+//!
+//! Code that only reads and writes messages can take a [`FIFOInterface`] instead of a [`FIFOCore`],
+//! and be handed a [`FakeFIFO`] in tests. The fake keeps everything in memory and never touches a
+//! backend or a tokio runtime: messages written to it are recorded for inspection, and messages
+//! [injected](FakeFIFO::inject) into it show up in matching sessions on the next read barrier.
+
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+use tokio::sync::watch;
+
+use crate::{
+    FIFOCore, ReadBuffer, ReduxFIFOMessage, ReduxFIFOSession, ReduxFIFOSessionConfig, Session,
+    error::Error, trace_tag::TraceTag,
+};
+
+/// Session-level operations on a set of buses.
+pub trait FIFOInterface: core::fmt::Debug + Send + Sync {
+    /// Ids of the open buses.
+    fn buses(&self) -> Vec<u16>;
+
+    fn max_packet_size(&self, bus_id: u16) -> Result<usize, Error>;
+
+    /// Writes a message to the bus in its `bus_id`.
+    fn write_single(&self, msg: &ReduxFIFOMessage) -> Result<(), Error>;
+
+    /// Writes a message on behalf of `ses`; see [`crate::trace_tag`]. Without logs to attribute
+    /// it in, this is just a write.
+    fn write_single_as(
+        &self,
+        _ses: ReduxFIFOSession,
+        msg: &ReduxFIFOMessage,
+    ) -> Result<(), Error> {
+        self.write_single(msg)
+    }
+
+    /// Labels `ses` for the messages written through it.
+    fn set_session_label(&self, _ses: ReduxFIFOSession, label: &str) -> Result<(), Error> {
+        TraceTag::intern(label).map(|_| ())
+    }
+
+    /// Opens a new session with a read buffer of `msg_count` messages.
+    fn open_session(
+        &self,
+        bus_id: u16,
+        msg_count: u32,
+        config: ReduxFIFOSessionConfig,
+    ) -> Result<ReduxFIFOSession, Error>;
+
+    fn close_session(&self, ses: ReduxFIFOSession) -> Result<ReadBuffer, Error>;
+
+    /// Swaps each of `data` with the read buffer of its session.
+    fn read_barrier(&self, bus_id: u16, data: &mut [ReadBuffer]) -> Result<(), Error>;
+
+    /// Listener for the number of messages waiting in a session.
+    fn rx_notifier(&self, ses: ReduxFIFOSession) -> Result<watch::Receiver<u32>, Error>;
+
+    /// Shareable handle to the same buses, for sessions to close themselves with.
+    fn handle(&self) -> Arc<dyn FIFOInterface>;
+
+    /// Opens a session that closes when dropped.
+    fn open_managed_session(
+        &self,
+        bus_id: u16,
+        msg_count: u32,
+        config: ReduxFIFOSessionConfig,
+    ) -> Result<Session, Error> {
+        let session = self.open_session(bus_id, msg_count, config)?;
+        Ok(Session::from_interface(self.handle(), session))
+    }
+}
+
+impl FIFOInterface for FIFOCore {
+    fn buses(&self) -> Vec<u16> {
+        FIFOCore::buses(self)
+    }
+
+    fn max_packet_size(&self, bus_id: u16) -> Result<usize, Error> {
+        FIFOCore::max_packet_size(self, bus_id)
+    }
+
+    fn write_single(&self, msg: &ReduxFIFOMessage) -> Result<(), Error> {
+        FIFOCore::write_single(self, msg)
+    }
+
+    fn write_single_as(&self, ses: ReduxFIFOSession, msg: &ReduxFIFOMessage) -> Result<(), Error> {
+        FIFOCore::write_single_as(self, ses, msg)
+    }
+
+    fn set_session_label(&self, ses: ReduxFIFOSession, label: &str) -> Result<(), Error> {
+        FIFOCore::set_session_label(self, ses, label)
+    }
+
+    fn open_session(
+        &self,
+        bus_id: u16,
+        msg_count: u32,
+        config: ReduxFIFOSessionConfig,
+    ) -> Result<ReduxFIFOSession, Error> {
+        FIFOCore::open_session(self, bus_id, msg_count, config)
+    }
+
+    fn close_session(&self, ses: ReduxFIFOSession) -> Result<ReadBuffer, Error> {
+        FIFOCore::close_session(self, ses)
+    }
+
+    fn read_barrier(&self, bus_id: u16, data: &mut [ReadBuffer]) -> Result<(), Error> {
+        FIFOCore::read_barrier(self, bus_id, data)
+    }
+
+    fn rx_notifier(&self, ses: ReduxFIFOSession) -> Result<watch::Receiver<u32>, Error> {
+        FIFOCore::rx_notifier(self, ses)
+    }
+
+    fn handle(&self) -> Arc<dyn FIFOInterface> {
+        Arc::new(self.clone())
+    }
+}
+
+#[derive(Debug)]
+struct FakeSession {
+    config: ReduxFIFOSessionConfig,
+    read_buf: ReadBuffer,
+    rx_notifier: watch::Sender<u32>,
+}
+
+impl FakeSession {
+    fn add_message(&mut self, msg: ReduxFIFOMessage) {
+        self.read_buf.add_message(msg);
+        self.rx_notifier
+            .send_replace(self.read_buf.meta.valid_length);
+    }
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    buses: FxHashMap<u16, usize>,
+    sessions: FxHashMap<ReduxFIFOSession, FakeSession>,
+    next_session_id: u32,
+    written: Vec<ReduxFIFOMessage>,
+}
+
+/// In-memory stand-in for [`FIFOCore`]. Clones share the same buses.
+#[derive(Debug, Clone, Default)]
+pub struct FakeFIFO {
+    state: Arc<parking_lot::Mutex<FakeState>>,
+}
+
+impl FakeFIFO {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens a CAN-FD sized bus with id `bus_id`.
+    pub fn add_bus(&self, bus_id: u16) -> &Self {
+        self.state.lock().buses.insert(bus_id, 64);
+        self
+    }
+
+    /// Closes a bus along with its sessions.
+    pub fn remove_bus(&self, bus_id: u16) {
+        let mut state = self.state.lock();
+        state.buses.remove(&bus_id);
+        state.sessions.retain(|ses, _| ses.bus_id() != bus_id);
+    }
+
+    /// Delivers `msg` to every session on its bus that it matches, as if it came off the bus.
+    pub fn inject(&self, msg: ReduxFIFOMessage) {
+        let mut state = self.state.lock();
+        for ses in state
+            .sessions
+            .iter_mut()
+            .filter(|(id, ses)| id.bus_id() == msg.bus_id && ses.config.message_matches(&msg))
+            .map(|(_, ses)| ses)
+        {
+            ses.add_message(msg);
+        }
+    }
+
+    /// Everything written so far, oldest first.
+    pub fn written(&self) -> Vec<ReduxFIFOMessage> {
+        self.state.lock().written.clone()
+    }
+
+    /// Everything written so far, clearing the record.
+    pub fn take_written(&self) -> Vec<ReduxFIFOMessage> {
+        core::mem::take(&mut self.state.lock().written)
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.state.lock().sessions.len()
+    }
+}
+
+impl FIFOInterface for FakeFIFO {
+    fn buses(&self) -> Vec<u16> {
+        self.state.lock().buses.keys().cloned().collect()
+    }
+
+    fn max_packet_size(&self, bus_id: u16) -> Result<usize, Error> {
+        self.state
+            .lock()
+            .buses
+            .get(&bus_id)
+            .copied()
+            .ok_or(Error::InvalidBus)
+    }
+
+    fn write_single(&self, msg: &ReduxFIFOMessage) -> Result<(), Error> {
+        let mut state = self.state.lock();
+        let max_len = *state.buses.get(&msg.bus_id).ok_or(Error::InvalidBus)?;
+        if msg.data_size as usize > max_len {
+            return Err(Error::DataTooLong);
+        }
+        state.written.push(*msg);
+
+        let mut echo = *msg;
+        echo.flags |= ReduxFIFOMessage::FLAG_TX;
+        for ses in state
+            .sessions
+            .iter_mut()
+            .filter(|(id, ses)| {
+                id.bus_id() == msg.bus_id && ses.config.echo_tx && ses.config.message_matches(msg)
+            })
+            .map(|(_, ses)| ses)
+        {
+            ses.add_message(echo);
+        }
+        Ok(())
+    }
+
+    fn open_session(
+        &self,
+        bus_id: u16,
+        msg_count: u32,
+        config: ReduxFIFOSessionConfig,
+    ) -> Result<ReduxFIFOSession, Error> {
+        let mut state = self.state.lock();
+        if !state.buses.contains_key(&bus_id) {
+            return Err(Error::InvalidBus);
+        }
+        let session_id = state.next_session_id;
+        if session_id == u32::MAX {
+            return Err(Error::MaxSessionsOpened);
+        }
+        state.next_session_id += 1;
+        let session = ReduxFIFOSession::from_parts(session_id, bus_id);
+        state.sessions.insert(
+            session,
+            FakeSession {
+                config,
+                read_buf: ReadBuffer::new(session, msg_count),
+                rx_notifier: watch::channel(0).0,
+            },
+        );
+        Ok(session)
+    }
+
+    fn close_session(&self, ses: ReduxFIFOSession) -> Result<ReadBuffer, Error> {
+        let mut state = self.state.lock();
+        if !state.buses.contains_key(&ses.bus_id()) {
+            return Err(Error::InvalidBus);
+        }
+        state
+            .sessions
+            .remove(&ses)
+            .map(|ses| ses.read_buf)
+            .ok_or(Error::InvalidSessionID)
+    }
+
+    fn read_barrier(&self, bus_id: u16, data: &mut [ReadBuffer]) -> Result<(), Error> {
+        let mut state = self.state.lock();
+        if !state.buses.contains_key(&bus_id) {
+            return Err(Error::InvalidBus);
+        }
+        for entry in data {
+            entry.ready_for_read();
+            match state.sessions.get_mut(&entry.session()) {
+                Some(ses) => {
+                    core::mem::swap(&mut ses.read_buf, entry);
+                    ses.rx_notifier.send_replace(ses.read_buf.meta.valid_length);
+                }
+                None => entry.set_status(Err(Error::InvalidSessionID)),
+            }
+        }
+        Ok(())
+    }
+
+    fn rx_notifier(&self, ses: ReduxFIFOSession) -> Result<watch::Receiver<u32>, Error> {
+        let state = self.state.lock();
+        if !state.buses.contains_key(&ses.bus_id()) {
+            return Err(Error::InvalidBus);
+        }
+        state
+            .sessions
+            .get(&ses)
+            .map(|ses| ses.rx_notifier.subscribe())
+            .ok_or(Error::InvalidSessionID)
+    }
+
+    fn handle(&self) -> Arc<dyn FIFOInterface> {
+        Arc::new(self.clone())
+    }
+}
@@ -0,0 +1,179 @@
+//! Latency from a frame's receive timestamp to the application being handed it.
+//!
+//! Off by default, since it takes a lock per read. When on, the FFI read barriers and the legacy
+//! ReduxCore receive calls that the Java vendordep goes through record the age of every message
+//! they hand over: the time from its timestamp to the handoff, in the same time base. Ages go into
+//! an HDR histogram per label, which is the frame's id without its device number, so one label
+//! covers a frame across every device that sends it.
+//!
+//! On buses the driver timestamps, the age includes the kernel's share; on WebSocket buses it's
+//! against the remote server's timestamps and so also carries the link's delay. Messages replayed
+//! to a re-opened session ([`ReduxFIFOMessage::historical`]) aren't recorded.
+
+use core::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::collections::BTreeMap;
+
+use hdrhistogram::Histogram;
+
+use crate::{ReduxFIFOMessage, timebase};
+
+/// Ages past this are recorded as this, in microseconds.
+pub const MAX_RECORDED_US: u64 = 10_000_000;
+/// Significant figures the histograms keep.
+const SIGFIGS: u8 = 3;
+/// Bits of an FRC CAN id that hold the device number.
+const DEVICE_NUMBER_MASK: u32 = 0x3f;
+/// Set on the labels of 11-bit ids, which aren't FRC ids and are kept whole.
+const SHORT_ID_LABEL: u32 = 1 << 31;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// When the histograms were last cleared
+static SINCE_US: AtomicI64 = AtomicI64::new(0);
+static HISTOGRAMS: parking_lot::Mutex<BTreeMap<u32, Histogram<u64>>> =
+    parking_lot::const_mutex(BTreeMap::new());
+
+/// Turns recording on or off. What was recorded is kept until [`reset`].
+pub fn set_enabled(enabled: bool) {
+    let was_enabled = ENABLED.swap(enabled, Ordering::Relaxed);
+    if enabled && !was_enabled && HISTOGRAMS.lock().is_empty() {
+        SINCE_US.store(timebase::now_us(), Ordering::Relaxed);
+    }
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn reset() {
+    HISTOGRAMS.lock().clear();
+    SINCE_US.store(timebase::now_us(), Ordering::Relaxed);
+}
+
+fn label_of(msg: &ReduxFIFOMessage) -> u32 {
+    if msg.short_id() {
+        msg.message_id & 0x7ff | SHORT_ID_LABEL
+    } else {
+        msg.message_id & 0x1fff_ffff & !DEVICE_NUMBER_MASK
+    }
+}
+
+/// Records the age of messages being handed to the application, if recording is on.
+pub fn record<'a>(messages: impl IntoIterator<Item = &'a ReduxFIFOMessage>) {
+    if !enabled() {
+        return;
+    }
+    let now = timebase::now_us();
+    let mut histograms = HISTOGRAMS.lock();
+    for msg in messages {
+        if msg.historical() || msg.timestamp == 0 {
+            continue;
+        }
+        let age = now.saturating_sub(msg.timestamp as i64).max(0) as u64;
+        histograms
+            .entry(label_of(msg))
+            .or_insert_with(|| {
+                Histogram::new_with_bounds(1, MAX_RECORDED_US, SIGFIGS)
+                    .expect("latency histogram bounds are valid")
+            })
+            .saturating_record(age);
+    }
+}
+
+/// Latency of one label's messages, in microseconds.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LabelLatency {
+    /// The frame id with the device number cleared, or the 11-bit id
+    pub id: u32,
+    pub short_id: bool,
+    /// Who sends the frame and what it is, as far as is known
+    pub label: String,
+    pub count: u64,
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub p999_us: u64,
+    pub max_us: u64,
+}
+
+/// Every label's latency since the histograms were last cleared.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LatencyReport {
+    pub enabled: bool,
+    /// When recording started or the histograms were last reset
+    pub since_us: i64,
+    pub labels: Vec<LabelLatency>,
+}
+
+fn describe(label: u32) -> String {
+    if label & SHORT_ID_LABEL != 0 {
+        return format!("11-bit id 0x{:03x}", label & 0x7ff);
+    }
+    let frame = frc_can_id::known::label(label);
+    let mut text = format!("{} ", frame.vendor);
+    match frame.product {
+        Some(product) => text.push_str(product),
+        None => text.push_str(&frame.device_type.to_string()),
+    }
+    text.push_str(&format!(
+        " api 0x{:02x}:{}",
+        frame.api_class, frame.api_index
+    ));
+    if let Some(name) = frame.frame {
+        text.push_str(&format!(" ({name})"));
+    }
+    text
+}
+
+pub fn report() -> LatencyReport {
+    let histograms = HISTOGRAMS.lock();
+    LatencyReport {
+        enabled: enabled(),
+        since_us: SINCE_US.load(Ordering::Relaxed),
+        labels: histograms
+            .iter()
+            .map(|(&label, histogram)| LabelLatency {
+                id: label & !SHORT_ID_LABEL,
+                short_id: label & SHORT_ID_LABEL != 0,
+                label: describe(label),
+                count: histogram.len(),
+                mean_us: histogram.mean(),
+                p50_us: histogram.value_at_quantile(0.5),
+                p90_us: histogram.value_at_quantile(0.9),
+                p99_us: histogram.value_at_quantile(0.99),
+                p999_us: histogram.value_at_quantile(0.999),
+                max_us: histogram.max(),
+            })
+            .collect(),
+    }
+}
+
+/// A table with a line per label, for logs and terminals.
+impl core::fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "{:<8}  {:<48} {:>9} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "id", "label", "count", "p50 us", "p90 us", "p99 us", "p99.9 us", "max us"
+        )?;
+        for label in &self.labels {
+            writeln!(
+                f,
+                "{:08x}  {:<48} {:>9} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                label.id,
+                label.label,
+                label.count,
+                label.p50_us,
+                label.p90_us,
+                label.p99_us,
+                label.p999_us,
+                label.max_us
+            )?;
+        }
+        if self.labels.is_empty() {
+            let state = if self.enabled { "on" } else { "off" };
+            writeln!(f, "(nothing recorded; recording is {state})")?;
+        }
+        Ok(())
+    }
+}
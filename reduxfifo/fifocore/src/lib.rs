@@ -1,4 +1,4 @@
-use std::mem::ManuallyDrop;
+use std::{mem::ManuallyDrop, sync::Arc};
 
 /// Contains definitions of the error type.
 pub mod error;
@@ -9,6 +9,54 @@ pub mod fifocore;
 /// Backends to the FIFO event loop
 pub mod backends;
 
+/// Human-readable bus aliases
+pub mod bus_alias;
+
+/// Parsing and validation of bus params
+pub mod bus_uri;
+
+/// Backend-independent bus options and dedicated bus threads
+pub mod bus_options;
+
+/// Backend-specific details of the hardware behind a bus
+pub mod bus_info;
+
+/// On-wire confirmation of written messages
+pub mod tx_confirm;
+
+/// Priority-ordered queueing of writes to full buses
+pub mod tx_queue;
+
+/// Coalescing of queued writes into one transfer
+pub mod write_batch;
+
+/// Process-wide cap on how fast each bus is written to
+pub mod tx_rate;
+
+/// Global disable of every actuator on a bus
+pub mod estop;
+
+/// Fan-out of received messages to sessions
+pub mod dispatch;
+
+/// Traffic kept for closed sessions until they re-open
+pub mod retain;
+
+/// Holding a session's messages while it's paused
+pub mod pause;
+
+/// Estimated bus utilization
+pub mod utilization;
+
+/// Bus error states and overruns
+pub mod bus_status;
+
+/// Receive-to-application latency histograms
+pub mod latency;
+
+/// Labels attributing written frames to the software that wrote them
+pub mod trace_tag;
+
 /// Data structures shared between this and FFI
 pub mod data;
 pub use data::*;
@@ -16,11 +64,36 @@ pub use data::*;
 /// Timing
 pub mod timebase;
 
+/// Per-backend policies for received message timestamps
+pub mod timestamp;
+
+/// WPILib HAL detection
+pub mod hal;
+
 /// Loggers
 pub mod logger;
 
+/// Conversion between bus logs and candump text logs
+pub mod logconvert;
+
+/// Memory limits and drop accounting
+pub mod limits;
+
+/// Cancellation of long-running operations
+pub mod cancel;
+
+/// Shared-memory rings sessions can be read through without read barriers
+pub mod shm;
+
+/// Trait over FIFOCore's session operations, and an in-memory fake of it
+pub mod interface;
+
+/// Cargo features this was built with, and checks against unsupported combinations
+pub mod capabilities;
+
 mod log;
 pub use crate::fifocore::FIFOCore;
+pub use crate::interface::FIFOInterface;
 pub(crate) use crate::log::*;
 
 /// Struct representing data that ReduxFIFO will write onto bus.
@@ -28,6 +101,8 @@ pub(crate) use crate::log::*;
 pub struct WriteBuffer {
     pub(crate) meta: Box<ReduxFIFOWriteBuffer>,
     pub(crate) msgs: Vec<ReduxFIFOMessage>,
+    /// Who the messages are logged as written by
+    pub(crate) tag: trace_tag::TraceTag,
 }
 
 impl WriteBuffer {
@@ -39,13 +114,23 @@ impl WriteBuffer {
                 status: 0,
                 messages_written: 0,
                 length: messages.len() as u32,
+                tx_seq: 0,
             }),
             msgs: messages,
+            tag: trace_tag::TraceTag::NONE,
         }
     }
+
+    /// Attributes the messages to `tag` in bus logs; see [`trace_tag`].
+    pub fn with_tag(mut self, tag: trace_tag::TraceTag) -> Self {
+        self.tag = tag;
+        self
+    }
+
     pub(crate) fn ready_for_write(&mut self) {
         self.meta.messages_written = 0;
         self.meta.status = 0;
+        self.meta.tx_seq = 0;
     }
     pub(crate) fn set_status(&mut self, status: Result<(), error::Error>) {
         self.meta.status = match status {
@@ -77,6 +162,7 @@ impl WriteBuffer {
             Self {
                 meta: metadata,
                 msgs: messages,
+                tag: trace_tag::TraceTag::NONE,
             }
         }
     }
@@ -115,6 +201,11 @@ impl WriteBuffer {
     pub fn status(&self) -> Result<(), error::Error> {
         error::Error::from_code(self.meta.status)
     }
+
+    /// Sequence number of the first written message, if the bus confirms writes.
+    pub fn tx_seq(&self) -> Option<u32> {
+        (self.meta.tx_seq != 0).then_some(self.meta.tx_seq)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -255,11 +346,16 @@ impl<'a> Iterator for ValidMessages<'a> {
 /// Managed session handle.
 /// When dropped, it will be closed.
 pub struct Session {
-    fifocore: FIFOCore,
+    fifocore: Arc<dyn FIFOInterface>,
     session: ReduxFIFOSession,
 }
 impl Session {
     pub unsafe fn wrap(fifocore: FIFOCore, session: ReduxFIFOSession) -> Self {
+        Self::from_interface(Arc::new(fifocore), session)
+    }
+
+    /// Takes ownership of `session`, opened on `fifocore`.
+    pub fn from_interface(fifocore: Arc<dyn FIFOInterface>, session: ReduxFIFOSession) -> Self {
         Self { fifocore, session }
     }
 
@@ -279,6 +375,16 @@ impl Session {
     pub fn session(&self) -> ReduxFIFOSession {
         self.session
     }
+
+    /// Labels the session, for messages written through it; see [`trace_tag`].
+    pub fn set_label(&self, label: &str) -> Result<(), error::Error> {
+        self.fifocore.set_session_label(self.session, label)
+    }
+
+    /// Writes a message, attributed to this session's label.
+    pub fn write_single(&self, msg: &ReduxFIFOMessage) -> Result<(), error::Error> {
+        self.fifocore.write_single_as(self.session, msg)
+    }
 }
 
 impl Drop for Session {
@@ -0,0 +1,321 @@
+//! Process-wide caps on how many messages ReduxFIFO keeps in memory.
+//!
+//! By default nothing is capped and sessions get the buffer sizes they ask for. Coprocessors with
+//! little RAM can set [`MemoryLimits::coprocessor`] (or their own limits) before opening anything,
+//! after which every buffer has a fixed ceiling and each overflow is resolved the same way every time:
+//!
+//! * session read buffers are rings, so a full one overwrites its oldest message
+//! * bus log queues drop the newest message when the log writer can't keep up
+//! * session sizes over [`MemoryLimits::max_session_messages`] are clamped down to it
+//! * sessions that would take the total over [`MemoryLimits::max_buffered_messages`] get whatever is
+//!   left, and are refused with [`Error::MemoryLimitReached`] if nothing is
+//!
+//! Every one of those is counted in [`drop_stats`].
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::Error;
+
+/// Memory limits. Zero means unlimited for the message counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MemoryLimits {
+    /// Largest read buffer a single session may have, in messages
+    pub max_session_messages: u32,
+    /// Total messages held across the read buffers of all sessions
+    pub max_buffered_messages: u64,
+    /// Messages that may wait for each bus log writer
+    pub log_queue_len: usize,
+}
+
+impl MemoryLimits {
+    /// No caps; the historic behavior.
+    pub const fn unbounded() -> Self {
+        Self {
+            max_session_messages: 0,
+            max_buffered_messages: 0,
+            log_queue_len: 128,
+        }
+    }
+
+    /// Limits sized for a coprocessor with 512 MB of RAM: messages are 80 bytes, so session buffers
+    /// top out at 80 MiB total and 5 MiB each.
+    pub const fn coprocessor() -> Self {
+        Self {
+            max_session_messages: 65536,
+            max_buffered_messages: 1 << 20,
+            log_queue_len: 4096,
+        }
+    }
+
+    pub const fn is_bounded(&self) -> bool {
+        self.max_session_messages != 0 || self.max_buffered_messages != 0
+    }
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self::unbounded()
+    }
+}
+
+/// Ways a message or buffer can be lost to the limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropKind {
+    /// A session read buffer was full and its oldest message got overwritten
+    SessionOverwrite,
+    /// A bus log queue was full and the newest message was not logged
+    LogQueueFull,
+    /// A session got a smaller read buffer than it asked for
+    SessionClamped,
+    /// A session could not be opened because the buffer budget was used up
+    SessionRejected,
+    /// A middleware client send queue was full and the newest message was not queued
+    ClientQueueFull,
+    /// A session's shared-memory ring was full and the newest message was not delivered
+    RingFull,
+    /// A bus's transmit queue was full and its least important write was not sent
+    TxQueueFull,
+    /// A paused session's hold queue was full and the newest message was not held
+    PauseFull,
+}
+
+/// Totals of each [`DropKind`] since startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct DropStats {
+    pub session_overwrite: u64,
+    pub log_queue_full: u64,
+    pub session_clamped: u64,
+    pub session_rejected: u64,
+    pub client_queue_full: u64,
+    pub ring_full: u64,
+    pub tx_queue_full: u64,
+    pub pause_full: u64,
+    /// Messages currently reserved by open session read buffers
+    pub buffered_messages: u64,
+}
+
+struct DropCounters {
+    session_overwrite: AtomicU64,
+    log_queue_full: AtomicU64,
+    session_clamped: AtomicU64,
+    session_rejected: AtomicU64,
+    client_queue_full: AtomicU64,
+    ring_full: AtomicU64,
+    tx_queue_full: AtomicU64,
+    pause_full: AtomicU64,
+}
+
+static LIMITS: parking_lot::RwLock<MemoryLimits> =
+    parking_lot::const_rwlock(MemoryLimits::unbounded());
+static RESERVED: AtomicU64 = AtomicU64::new(0);
+static DROPS: DropCounters = DropCounters {
+    session_overwrite: AtomicU64::new(0),
+    log_queue_full: AtomicU64::new(0),
+    session_clamped: AtomicU64::new(0),
+    session_rejected: AtomicU64::new(0),
+    client_queue_full: AtomicU64::new(0),
+    ring_full: AtomicU64::new(0),
+    tx_queue_full: AtomicU64::new(0),
+    pause_full: AtomicU64::new(0),
+};
+
+/// Replaces the limits. Sessions and loggers already open keep the sizes they were given.
+pub fn set_memory_limits(limits: MemoryLimits) {
+    *LIMITS.write() = limits;
+}
+
+pub fn memory_limits() -> MemoryLimits {
+    *LIMITS.read()
+}
+
+pub fn record_drop(kind: DropKind, count: u64) {
+    let counter = match kind {
+        DropKind::SessionOverwrite => &DROPS.session_overwrite,
+        DropKind::LogQueueFull => &DROPS.log_queue_full,
+        DropKind::SessionClamped => &DROPS.session_clamped,
+        DropKind::SessionRejected => &DROPS.session_rejected,
+        DropKind::ClientQueueFull => &DROPS.client_queue_full,
+        DropKind::RingFull => &DROPS.ring_full,
+        DropKind::TxQueueFull => &DROPS.tx_queue_full,
+        DropKind::PauseFull => &DROPS.pause_full,
+    };
+    counter.fetch_add(count, Ordering::Relaxed);
+}
+
+pub fn drop_stats() -> DropStats {
+    DropStats {
+        session_overwrite: DROPS.session_overwrite.load(Ordering::Relaxed),
+        log_queue_full: DROPS.log_queue_full.load(Ordering::Relaxed),
+        session_clamped: DROPS.session_clamped.load(Ordering::Relaxed),
+        session_rejected: DROPS.session_rejected.load(Ordering::Relaxed),
+        client_queue_full: DROPS.client_queue_full.load(Ordering::Relaxed),
+        ring_full: DROPS.ring_full.load(Ordering::Relaxed),
+        tx_queue_full: DROPS.tx_queue_full.load(Ordering::Relaxed),
+        pause_full: DROPS.pause_full.load(Ordering::Relaxed),
+        buffered_messages: RESERVED.load(Ordering::Relaxed),
+    }
+}
+
+/// Read buffer space held by a session, given back when dropped.
+#[derive(Debug)]
+pub struct Reservation(u32);
+
+impl Reservation {
+    /// Reserves a read buffer of up to `requested` messages under the current limits.
+    pub fn reserve(requested: u32) -> Result<Self, Error> {
+        let limits = memory_limits();
+        let mut size = requested;
+        if limits.max_session_messages != 0 {
+            size = size.min(limits.max_session_messages);
+        }
+
+        let reserved = RESERVED
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |reserved| {
+                if limits.max_buffered_messages == 0 {
+                    return Some(reserved + size as u64);
+                }
+                let left = limits.max_buffered_messages.saturating_sub(reserved);
+                let granted = (size as u64).min(left);
+                (granted > 0 || size == 0).then_some(reserved + granted)
+            })
+            .map_err(|_| {
+                record_drop(DropKind::SessionRejected, 1);
+                Error::MemoryLimitReached
+            })?;
+        if limits.max_buffered_messages != 0 {
+            let left = limits.max_buffered_messages.saturating_sub(reserved);
+            size = (size as u64).min(left) as u32;
+        }
+
+        if size < requested {
+            record_drop(DropKind::SessionClamped, 1);
+            crate::log_warn!(
+                "Session buffer of {requested} messages clamped to {size} by memory limits"
+            );
+        }
+        Ok(Self(size))
+    }
+
+    /// Messages reserved.
+    pub fn size(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        RESERVED.fetch_sub(self.0 as u64, Ordering::AcqRel);
+    }
+}
+
+/// Sends a message to a log queue, dropping it if the queue is full.
+pub(crate) fn send_to_log(
+    logger: &tokio::sync::mpsc::Sender<crate::logger::LogEntry>,
+    entry: impl Into<crate::logger::LogEntry>,
+) {
+    if let Err(tokio::sync::mpsc::error::TrySendError::Full(_)) = logger.try_send(entry.into()) {
+        record_drop(DropKind::LogQueueFull, 1);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// The limits and counters are process-wide, so these tests, and any others that reserve
+    /// buffers, take turns.
+    pub(crate) static SERIAL: parking_lot::Mutex<()> = parking_lot::const_mutex(());
+
+    fn with_limits(limits: MemoryLimits, f: impl FnOnce()) {
+        let _serial = SERIAL.lock();
+        set_memory_limits(limits);
+        f();
+        set_memory_limits(MemoryLimits::unbounded());
+    }
+
+    #[test]
+    fn test_reserve_release() {
+        with_limits(MemoryLimits::unbounded(), || {
+            let before = drop_stats();
+            let a = Reservation::reserve(100).unwrap();
+            let b = Reservation::reserve(0).unwrap();
+            assert_eq!((a.size(), b.size()), (100, 0));
+            assert_eq!(
+                drop_stats().buffered_messages,
+                before.buffered_messages + 100
+            );
+            drop(a);
+            drop(b);
+            let after = drop_stats();
+            assert_eq!(after.buffered_messages, before.buffered_messages);
+            assert_eq!(after.session_clamped, before.session_clamped);
+        });
+    }
+
+    #[test]
+    fn test_session_clamped() {
+        let limits = MemoryLimits {
+            max_session_messages: 10,
+            ..MemoryLimits::unbounded()
+        };
+        with_limits(limits, || {
+            let before = drop_stats();
+            let res = Reservation::reserve(50).unwrap();
+            assert_eq!(res.size(), 10);
+            let exact = Reservation::reserve(10).unwrap();
+            assert_eq!(exact.size(), 10);
+            let after = drop_stats();
+            assert_eq!(after.session_clamped, before.session_clamped + 1);
+            assert_eq!(after.buffered_messages, before.buffered_messages + 20);
+        });
+    }
+
+    #[test]
+    fn test_over_limit_rejected() {
+        with_limits(MemoryLimits::unbounded(), || {
+            let base = drop_stats().buffered_messages;
+            set_memory_limits(MemoryLimits {
+                max_buffered_messages: base + 100,
+                ..MemoryLimits::unbounded()
+            });
+            let before = drop_stats();
+            let first = Reservation::reserve(60).unwrap();
+            // gets whatever is left of the budget
+            let second = Reservation::reserve(60).unwrap();
+            assert_eq!((first.size(), second.size()), (60, 40));
+            assert!(matches!(
+                Reservation::reserve(1),
+                Err(Error::MemoryLimitReached)
+            ));
+            let after = drop_stats();
+            assert_eq!(after.session_clamped, before.session_clamped + 1);
+            assert_eq!(after.session_rejected, before.session_rejected + 1);
+            assert_eq!(after.buffered_messages, base + 100);
+
+            // releasing a reservation frees its share of the budget
+            drop(second);
+            assert_eq!(Reservation::reserve(40).unwrap().size(), 40);
+        });
+    }
+
+    #[test]
+    fn test_drop_counts() {
+        with_limits(MemoryLimits::unbounded(), || {
+            let before = drop_stats();
+            record_drop(DropKind::SessionOverwrite, 3);
+            record_drop(DropKind::LogQueueFull, 1);
+            record_drop(DropKind::ClientQueueFull, 2);
+            record_drop(DropKind::RingFull, 4);
+            record_drop(DropKind::TxQueueFull, 5);
+            record_drop(DropKind::PauseFull, 6);
+            let after = drop_stats();
+            assert_eq!(after.session_overwrite, before.session_overwrite + 3);
+            assert_eq!(after.log_queue_full, before.log_queue_full + 1);
+            assert_eq!(after.client_queue_full, before.client_queue_full + 2);
+            assert_eq!(after.ring_full, before.ring_full + 4);
+            assert_eq!(after.tx_queue_full, before.tx_queue_full + 5);
+            assert_eq!(after.pause_full, before.pause_full + 6);
+            assert_eq!(after.session_rejected, before.session_rejected);
+        });
+    }
+}
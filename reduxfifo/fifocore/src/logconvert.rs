@@ -0,0 +1,457 @@
+//! Conversion between bus logs and `candump -L` text logs.
+//!
+//! `candump -L` (or `-l`) writes one frame per line:
+//!
+//! ```text
+//! (1436509052.249713) can0 0E0A1234#0102030405060708
+//! (1436509052.250113) can0 123##1DEADBEEF
+//! (1436509052.250513) can1 7FF#R T
+//! ```
+//!
+//! An id of 3 hex digits is 11-bit and of 8 is 29-bit. `##` starts a CAN FD frame, followed by a
+//! flags nibble with BRS as bit 0 and ESI as bit 1, and `#R` a remote frame. A trailing `T` or `R`,
+//! as `candump -x` adds, says whether the host transmitted or received the frame.
+//!
+//! Interfaces are mapped to and from bus ids with a [`BusMap`]. Message timestamps are written as
+//! they are, so a log recorded against FPGA time won't line up with one taken by socketcan.
+//!
+//! Messages written as candump lose their session labels, and frames from buses that don't say
+//! whether they're FD are written as classic frames where they fit.
+
+use std::{
+    fmt,
+    io::{self, BufRead, Read, Write},
+};
+
+use crate::{
+    MessageIdBuilder, ReduxFIFOMessage,
+    logger::{LOG_MAGIC, LogHeader},
+};
+
+/// Why a log couldn't be converted.
+#[derive(Debug)]
+pub enum ConvertError {
+    Io(io::Error),
+    /// Input that doesn't start with [`LOG_MAGIC`]
+    NotAnRdxlog,
+    /// A candump line that couldn't be parsed
+    Malformed {
+        line: usize,
+        text: String,
+    },
+    /// A candump interface with no bus to go to
+    UnmappedInterface(String),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::Io(e) => write!(f, "{e}"),
+            ConvertError::NotAnRdxlog => write!(f, "not a ReduxFIFO log"),
+            ConvertError::Malformed { line, text } => {
+                write!(f, "malformed candump line {line}: {text:?}")
+            }
+            ConvertError::UnmappedInterface(iface) => {
+                write!(f, "no bus id for interface {iface:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+impl From<io::Error> for ConvertError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Which candump interface each bus is.
+///
+/// Interfaces named `can<N>` are bus `N` unless mapped otherwise, and the other way around when
+/// writing; any other interface has to be mapped with [`BusMap::map`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BusMap {
+    interfaces: Vec<(String, u16)>,
+}
+
+impl BusMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps `interface` to `bus_id`, replacing what either was mapped to before.
+    pub fn map(mut self, interface: impl Into<String>, bus_id: u16) -> Self {
+        let interface = interface.into();
+        self.interfaces
+            .retain(|(iface, bus)| *iface != interface && *bus != bus_id);
+        self.interfaces.push((interface, bus_id));
+        self
+    }
+
+    pub fn bus_id(&self, interface: &str) -> Option<u16> {
+        match self.interfaces.iter().find(|(iface, _)| iface == interface) {
+            Some((_, bus_id)) => Some(*bus_id),
+            None => interface.strip_prefix("can")?.parse().ok(),
+        }
+    }
+
+    pub fn interface(&self, bus_id: u16) -> String {
+        match self.interfaces.iter().find(|(_, bus)| *bus == bus_id) {
+            Some((iface, _)) => iface.clone(),
+            None => format!("can{bus_id}"),
+        }
+    }
+}
+
+/// Parses one `candump -L` line, returning [`None`] for blank lines and `#` comments.
+///
+/// `lineno` is only used in errors.
+pub fn parse_candump_line(
+    line: &str,
+    lineno: usize,
+    buses: &BusMap,
+) -> Result<Option<ReduxFIFOMessage>, ConvertError> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let malformed = || ConvertError::Malformed {
+        line: lineno,
+        text: line.to_string(),
+    };
+    let mut tokens = line.split_whitespace();
+    let timestamp = tokens
+        .next()
+        .and_then(|ts| ts.strip_prefix('(')?.strip_suffix(')'))
+        .and_then(parse_timestamp)
+        .ok_or_else(malformed)?;
+    let interface = tokens.next().ok_or_else(malformed)?;
+    let bus_id = buses
+        .bus_id(interface)
+        .ok_or_else(|| ConvertError::UnmappedInterface(interface.to_string()))?;
+    let (id, data) = tokens
+        .next()
+        .and_then(|frame| frame.split_once('#'))
+        .ok_or_else(malformed)?;
+    let tx = match tokens.next() {
+        None | Some("R") => false,
+        Some("T") => true,
+        Some(_) => return Err(malformed()),
+    };
+
+    let extended = match id.len() {
+        3 => false,
+        8 => true,
+        _ => return Err(malformed()),
+    };
+    let id_word = u32::from_str_radix(id, 16).map_err(|_| malformed())?;
+    let mut flags = if tx { ReduxFIFOMessage::FLAG_TX } else { 0 };
+    let (data, rtr) = if let Some(fd) = data.strip_prefix('#') {
+        let fd_flags = fd
+            .get(..1)
+            .and_then(|f| u8::from_str_radix(f, 16).ok())
+            .ok_or_else(malformed)?;
+        if fd_flags & 0x1 == 0 {
+            flags |= ReduxFIFOMessage::FLAG_NO_BRS;
+        }
+        if fd_flags & 0x2 != 0 {
+            flags |= ReduxFIFOMessage::FLAG_ESI;
+        }
+        (&fd[1..], false)
+    } else {
+        flags |= ReduxFIFOMessage::FLAG_NO_FD;
+        match data.strip_prefix('R') {
+            // remote frames may give the length they ask for, which we don't keep
+            Some(_) => ("", true),
+            None => (data, false),
+        }
+    };
+
+    // canplayer allows dots between bytes
+    let data = data.replace('.', "");
+    if data.len() % 2 != 0 || data.len() > 128 {
+        return Err(malformed());
+    }
+    let mut msg = ReduxFIFOMessage {
+        message_id: MessageIdBuilder::new(id_word)
+            .err(extended && id_word & MessageIdBuilder::ID_FLAG_ERR != 0)
+            .rtr(rtr)
+            .short_id(!extended)
+            .build(),
+        bus_id,
+        flags,
+        timestamp,
+        ..Default::default()
+    };
+    for (i, byte) in msg.data.iter_mut().take(data.len() / 2).enumerate() {
+        *byte = u8::from_str_radix(&data[i * 2..i * 2 + 2], 16).map_err(|_| malformed())?;
+    }
+    msg.data_size = (data.len() / 2) as u8;
+    Ok(Some(msg))
+}
+
+/// `sec.usec` to microseconds, without going through a float.
+fn parse_timestamp(ts: &str) -> Option<u64> {
+    let (sec, frac) = ts.split_once('.').unwrap_or((ts, ""));
+    if frac.len() > 6 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let usec = format!("{frac:0<6}").parse::<u64>().ok()?;
+    sec.parse::<u64>()
+        .ok()?
+        .checked_mul(1_000_000)?
+        .checked_add(usec)
+}
+
+/// Reads a whole `candump -L` log.
+pub fn read_candump(
+    reader: impl BufRead,
+    buses: &BusMap,
+) -> Result<Vec<ReduxFIFOMessage>, ConvertError> {
+    let mut messages = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        if let Some(msg) = parse_candump_line(&line?, i + 1, buses)? {
+            messages.push(msg);
+        }
+    }
+    Ok(messages)
+}
+
+/// Formats a message as a `candump -L` line, without a newline.
+pub fn format_candump(msg: &ReduxFIFOMessage, buses: &BusMap) -> String {
+    use fmt::Write as _;
+
+    let mut line = format!(
+        "({}.{:06}) {} ",
+        msg.timestamp / 1_000_000,
+        msg.timestamp % 1_000_000,
+        buses.interface(msg.bus_id)
+    );
+    let id = msg.id()
+        | if msg.err() {
+            MessageIdBuilder::ID_FLAG_ERR
+        } else {
+            0
+        };
+    if msg.short_id() {
+        write!(line, "{:03X}#", id & 0x7ff).ok();
+    } else {
+        write!(line, "{id:08X}#").ok();
+    }
+    let fd = !msg.no_fd() && (msg.data_size > 8 || msg.no_brs() || msg.esi());
+    if fd {
+        let fd_flags = u8::from(!msg.no_brs()) | u8::from(msg.esi()) << 1;
+        write!(line, "#{fd_flags:X}").ok();
+    }
+    if msg.rtr() && !fd {
+        line.push('R');
+    } else {
+        for byte in msg.data_slice() {
+            write!(line, "{byte:02X}").ok();
+        }
+    }
+    line.push_str(if msg.tx() { " T" } else { " R" });
+    line
+}
+
+/// Writes messages as a `candump -L` log.
+pub fn write_candump<'a>(
+    mut writer: impl Write,
+    messages: impl IntoIterator<Item = &'a ReduxFIFOMessage>,
+    buses: &BusMap,
+) -> io::Result<()> {
+    for msg in messages {
+        writeln!(writer, "{}", format_candump(msg, buses))?;
+    }
+    writer.flush()
+}
+
+/// Reads the messages out of a log written by [`crate::logger`], skipping label records.
+pub fn read_rdxlog(mut reader: impl Read) -> Result<Vec<ReduxFIFOMessage>, ConvertError> {
+    let mut magic = [0_u8; LOG_MAGIC.len()];
+    reader.read_exact(&mut magic).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => ConvertError::NotAnRdxlog,
+        _ => e.into(),
+    })?;
+    if &magic != LOG_MAGIC {
+        return Err(ConvertError::NotAnRdxlog);
+    }
+
+    let mut messages = Vec::new();
+    let mut header = [0_u8; size_of::<LogHeader>()];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(messages),
+            Err(e) => return Err(e.into()),
+        }
+        let header: LogHeader = bytemuck::pod_read_unaligned(&header);
+        let mut msg = ReduxFIFOMessage {
+            message_id: header.message_id,
+            bus_id: header.bus_id,
+            flags: header.flags,
+            data_size: header.data_size.min(64),
+            timestamp: header.timestamp,
+            ..Default::default()
+        };
+        reader.read_exact(&mut msg.data[..msg.data_size as usize])?;
+        if !header.is_label() {
+            messages.push(msg);
+        }
+    }
+}
+
+/// Writes messages as a log in [`crate::logger`]'s format, without session labels.
+pub fn write_rdxlog<'a>(
+    mut writer: impl Write,
+    messages: impl IntoIterator<Item = &'a ReduxFIFOMessage>,
+) -> io::Result<()> {
+    writer.write_all(LOG_MAGIC)?;
+    for msg in messages {
+        writer.write_all(bytemuck::bytes_of(&LogHeader::from(*msg)))?;
+        writer.write_all(msg.data_slice())?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Every kind of frame candump writes: classic 11- and 29-bit, FD with each flag, remote and
+    /// error frames, transmitted and received.
+    const CANDUMP: &str = "\
+(1436509052.249713) can0 0E0A1234#0102030405060708 R
+(1436509052.250113) can0 123##1DEADBEEF0102030405060708 R
+(1436509052.250513) can1 7FF#R T
+(1436509052.250600) can0 0000ABCD##00102030405060708090A0B0C0D0E0F10 T
+(1436509052.250700) can0 456##2 R
+(1436509052.250800) can1 20000004#0000080000000000 R
+(1436509052.250900) can0 000# T
+";
+
+    #[test]
+    fn test_candump_round_trip() {
+        let buses = BusMap::new();
+        let messages = read_candump(CANDUMP.as_bytes(), &buses).unwrap();
+        assert_eq!(messages.len(), 7);
+
+        let fd = &messages[3];
+        assert!(!fd.no_fd() && fd.no_brs() && fd.tx());
+        assert_eq!(fd.data_size, 16);
+        let remote = &messages[2];
+        assert!(remote.rtr() && remote.short_id() && remote.no_fd());
+        assert_eq!((remote.bus_id, remote.id()), (1, 0x7ff));
+        assert!(messages[4].esi() && messages[4].data_size == 0);
+        assert!(messages[5].err());
+        assert_eq!(messages[0].timestamp, 1_436_509_052_249_713);
+
+        let mut written = Vec::new();
+        write_candump(&mut written, &messages, &buses).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), CANDUMP);
+
+        // an FD frame that fits a classic one and has nothing else FD about it can't be told from
+        // a classic frame on a bus that doesn't say, so it's written as one
+        let msg = parse_candump_line("(1.0) can0 123##1DEADBEEF", 1, &buses)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            format_candump(&msg, &buses),
+            "(1.000000) can0 123#DEADBEEF R"
+        );
+    }
+
+    #[test]
+    fn test_rdxlog_round_trip() {
+        let messages = read_candump(CANDUMP.as_bytes(), &BusMap::new()).unwrap();
+        let mut written = Vec::new();
+        write_rdxlog(&mut written, &messages).unwrap();
+        assert!(written.starts_with(LOG_MAGIC));
+        assert_eq!(read_rdxlog(written.as_slice()).unwrap(), messages);
+    }
+
+    #[test]
+    fn test_rdxlog_labels() {
+        let messages = read_candump(CANDUMP.as_bytes(), &BusMap::new()).unwrap();
+        let mut written = Vec::new();
+        write_rdxlog(&mut written, &messages[..1]).unwrap();
+        // as the logger writes ahead of a labeled session's messages
+        let label = b"drive";
+        let header = LogHeader {
+            message_id: 0,
+            bus_id: 0,
+            flags: LogHeader::FLAG_LABEL,
+            data_size: label.len() as u8,
+            timestamp: messages[1].timestamp,
+        };
+        written.extend_from_slice(bytemuck::bytes_of(&header));
+        written.extend_from_slice(label);
+        let header = LogHeader::from(messages[1]);
+        written.extend_from_slice(bytemuck::bytes_of(&header));
+        written.extend_from_slice(messages[1].data_slice());
+
+        assert_eq!(read_rdxlog(written.as_slice()).unwrap(), messages[..2]);
+    }
+
+    #[test]
+    fn test_bus_map() {
+        let buses = BusMap::new().map("vcan0", 2).map("drive", 0);
+        let line = "(1.5) vcan0 123#01 R";
+        let msg = parse_candump_line(line, 1, &buses).unwrap().unwrap();
+        assert_eq!((msg.bus_id, msg.timestamp), (2, 1_500_000));
+        assert_eq!(format_candump(&msg, &buses), "(1.500000) vcan0 123#01 R");
+        assert_eq!(buses.interface(0), "drive");
+        assert_eq!(buses.interface(1), "can1");
+        // mapping an interface takes its bus away from whatever had it
+        assert_eq!(buses.clone().map("other", 2).bus_id("vcan0"), None);
+        assert!(matches!(
+            parse_candump_line("(1.0) eth0 123#01", 7, &buses),
+            Err(ConvertError::UnmappedInterface(iface)) if iface == "eth0"
+        ));
+    }
+
+    #[test]
+    fn test_candump_malformed() {
+        let buses = BusMap::new();
+        assert!(matches!(parse_candump_line("  ", 1, &buses), Ok(None)));
+        assert!(matches!(
+            parse_candump_line("# comment", 1, &buses),
+            Ok(None)
+        ));
+        for line in [
+            "1.0 can0 123#01",
+            "(1.0000001) can0 123#01",
+            "(1.0) can0",
+            "(1.0) can0 123",
+            "(1.0) can0 1234#01",
+            "(1.0) can0 12G#01",
+            "(1.0) can0 123#012",
+            "(1.0) can0 123##",
+            "(1.0) can0 123#01 X",
+        ] {
+            match parse_candump_line(line, 3, &buses) {
+                Err(ConvertError::Malformed { line: 3, text }) => assert_eq!(text, line),
+                other => panic!("{line}: {other:?}"),
+            }
+        }
+        // the line number of the bad line is reported
+        let log = "(1.0) can0 123#01\n\n(1.1) can0 123#0\n";
+        assert!(matches!(
+            read_candump(log.as_bytes(), &buses),
+            Err(ConvertError::Malformed { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_not_an_rdxlog() {
+        assert!(matches!(
+            read_rdxlog(&b"(1.0) can0 123#01"[..]),
+            Err(ConvertError::NotAnRdxlog)
+        ));
+        assert!(matches!(
+            read_rdxlog(&b""[..]),
+            Err(ConvertError::NotAnRdxlog)
+        ));
+    }
+}
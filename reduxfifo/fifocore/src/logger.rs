@@ -1,7 +1,39 @@
-use crate::ReduxFIFOMessage;
-use tokio::{fs::OpenOptions, io::AsyncWriteExt, runtime::Handle, task::JoinHandle};
+use crate::{ReduxFIFOMessage, trace_tag::TraceTag};
+use rustc_hash::FxHashMap;
+use tokio::{
+    fs::OpenOptions,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    runtime::Handle,
+    task::JoinHandle,
+};
 
-pub type LoggerTx = Option<tokio::sync::mpsc::Sender<ReduxFIFOMessage>>;
+pub mod mcap;
+
+pub type LoggerTx = Option<tokio::sync::mpsc::Sender<LogEntry>>;
+
+/// What every log file starts with. A [`LogHeader`] and the message's data follow for each message.
+///
+/// Records flagged with [`LogHeader::FLAG_LABEL`] aren't messages: their data is the
+/// [session label](crate::trace_tag) of the transmitted messages after them, up to the next such
+/// record. An empty label means those weren't written through a labeled session.
+pub const LOG_MAGIC: &[u8; 16] = b"ReduxFIFOLogFile";
+
+/// A message on its way to the logger.
+#[derive(Debug, Clone, Copy)]
+pub struct LogEntry {
+    pub msg: ReduxFIFOMessage,
+    /// Label of the session that wrote the message, for ones this host sent
+    pub tag: TraceTag,
+}
+
+impl From<ReduxFIFOMessage> for LogEntry {
+    fn from(msg: ReduxFIFOMessage) -> Self {
+        Self {
+            msg,
+            tag: TraceTag::NONE,
+        }
+    }
+}
 
 #[derive(Clone, Copy, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
@@ -24,6 +56,16 @@ pub struct LogHeader {
     pub timestamp: u64,
 }
 
+impl LogHeader {
+    /// Set in the flags of label records, which no message flags overlap; see [`LOG_MAGIC`].
+    pub const FLAG_LABEL: u8 = 0x80;
+
+    /// Whether this is a label record rather than a message.
+    pub const fn is_label(&self) -> bool {
+        self.flags & Self::FLAG_LABEL != 0
+    }
+}
+
 impl From<ReduxFIFOMessage> for LogHeader {
     fn from(value: ReduxFIFOMessage) -> Self {
         Self {
@@ -36,6 +78,123 @@ impl From<ReduxFIFOMessage> for LogHeader {
     }
 }
 
+/// What a bus log is written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// ReduxFIFO's own format; see [`LOG_MAGIC`]. Replay backends read these.
+    #[default]
+    Rdxlog,
+    /// [MCAP](mcap), which Foxglove and PlotJuggler open directly
+    Mcap,
+}
+
+impl LogFormat {
+    /// File extension of logs in this format, without the dot.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            LogFormat::Rdxlog => "rdxlog",
+            LogFormat::Mcap => "mcap",
+        }
+    }
+
+    /// The format a log file's extension names, if it names one.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rdxlog" => Some(LogFormat::Rdxlog),
+            "mcap" => Some(LogFormat::Mcap),
+            _ => None,
+        }
+    }
+}
+
+/// Which way a message went over the bus, for [`LogFilter::direction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogDirection {
+    #[default]
+    Both,
+    /// Only messages received from the bus
+    Rx,
+    /// Only messages this host wrote
+    Tx,
+}
+
+/// Matches message ids where `id & mask == id_match & mask`, like a session filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LogIdFilter {
+    #[serde(rename = "id")]
+    pub id_match: u32,
+    pub mask: u32,
+}
+
+impl LogIdFilter {
+    pub const fn matches(&self, message_id: u32) -> bool {
+        (message_id ^ self.id_match) & self.mask == 0
+    }
+}
+
+/// Which messages a bus log keeps. The default keeps everything.
+///
+/// Filters are applied by the logger as it writes, so a filtered log still takes a slot in the log
+/// queue for every message on the bus.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LogFilter {
+    /// Keep messages matching any of these; all messages if empty
+    pub ids: Vec<LogIdFilter>,
+    /// Keep only messages to and from Redux devices
+    pub redux_only: bool,
+    pub direction: LogDirection,
+    /// Keep one in every `decimate` messages of each message id; 0 and 1 keep them all
+    pub decimate: u32,
+}
+
+impl LogFilter {
+    /// Whether the filter looks at a message at all, ignoring decimation.
+    fn passes(&self, msg: &ReduxFIFOMessage) -> bool {
+        let message_id = msg.id();
+        let direction = match self.direction {
+            LogDirection::Both => true,
+            LogDirection::Rx => !msg.tx(),
+            LogDirection::Tx => msg.tx(),
+        };
+        direction
+            && (!self.redux_only
+                || frc_can_id::FRCCanId::new(message_id).manufacturer_code()
+                    == frc_can_id::REDUX_VENDOR_ID)
+            && (self.ids.is_empty() || self.ids.iter().any(|f| f.matches(message_id)))
+    }
+}
+
+/// A [`LogFilter`] and the per-id counts it decimates with.
+struct FilterState {
+    filter: LogFilter,
+    seen: FxHashMap<u32, u32>,
+}
+
+impl FilterState {
+    fn new(filter: LogFilter) -> Self {
+        Self {
+            filter,
+            seen: FxHashMap::default(),
+        }
+    }
+
+    fn keep(&mut self, msg: &ReduxFIFOMessage) -> bool {
+        if !self.filter.passes(msg) {
+            return false;
+        }
+        if self.filter.decimate <= 1 {
+            return true;
+        }
+        let seen = self.seen.entry(msg.id()).or_default();
+        let keep = *seen == 0;
+        *seen = (*seen + 1) % self.filter.decimate;
+        keep
+    }
+}
+
 macro_rules! log_err_and_bail {
     ($e:expr, $fname:expr) => {{
         match $e {
@@ -48,17 +207,33 @@ macro_rules! log_err_and_bail {
     }};
 }
 
+/// A log being written by a task on the runtime.
+///
+/// Dropping the logger doesn't stop the task: it writes out what's queued and closes the file once
+/// the bus lets go of its [`sender`](Logger::sender) too.
 #[derive(Debug)]
 pub struct Logger {
+    fname: std::path::PathBuf,
+    filter: LogFilter,
+    format: LogFormat,
     task: JoinHandle<()>,
-    tx: tokio::sync::mpsc::Sender<ReduxFIFOMessage>,
+    tx: tokio::sync::mpsc::Sender<LogEntry>,
 }
 
 impl Logger {
-    pub fn new(fname: std::path::PathBuf, runtime: Handle) -> Self {
-        let (sender, receiver) = tokio::sync::mpsc::channel(128);
+    pub fn new(
+        fname: std::path::PathBuf,
+        filter: LogFilter,
+        format: LogFormat,
+        runtime: Handle,
+    ) -> Self {
+        let (sender, receiver) =
+            tokio::sync::mpsc::channel(crate::limits::memory_limits().log_queue_len.max(1));
         Self {
-            task: runtime.spawn(logger_task(fname, receiver)),
+            task: runtime.spawn(logger_task(fname.clone(), filter.clone(), format, receiver)),
+            fname,
+            filter,
+            format,
             tx: sender,
         }
     }
@@ -66,43 +241,117 @@ impl Logger {
     pub fn sender(&self) -> LoggerTx {
         Some(self.tx.clone())
     }
-}
 
-impl Drop for Logger {
-    fn drop(&mut self) {
-        self.task.abort();
+    pub fn path(&self) -> &std::path::Path {
+        &self.fname
+    }
+
+    pub fn filter(&self) -> &LogFilter {
+        &self.filter
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    /// Returns true if the logger task has exited, e.g. after a failed write.
+    pub fn is_dead(&self) -> bool {
+        self.task.is_finished()
     }
 }
 
 async fn logger_task(
     fname: std::path::PathBuf,
-    mut rx: tokio::sync::mpsc::Receiver<ReduxFIFOMessage>,
+    filter: LogFilter,
+    format: LogFormat,
+    mut rx: tokio::sync::mpsc::Receiver<LogEntry>,
 ) {
     crate::log_info!("Opening log file {}", fname.display());
     let mut file = log_err_and_bail!(
         OpenOptions::new()
+            .read(true)
             .append(true)
             .create(true)
             .open(&fname)
             .await,
         fname
     );
-    log_err_and_bail!(file.write_all(b"ReduxFIFOLogFile").await, fname);
+    // a restarted logger appends to the file it was already writing
+    let existing_len = log_err_and_bail!(file.metadata().await, fname).len();
     let mut buffer = Vec::with_capacity(80);
+    let mut mcap = mcap::McapWriter::default();
+    match format {
+        LogFormat::Rdxlog if existing_len == 0 => buffer.extend_from_slice(LOG_MAGIC),
+        LogFormat::Rdxlog => {}
+        LogFormat::Mcap => {
+            if existing_len >= mcap::MAGIC.len() as u64 {
+                let mut tail = [0u8; mcap::MAGIC.len()];
+                let tail_at = std::io::SeekFrom::End(-(tail.len() as i64));
+                log_err_and_bail!(file.seek(tail_at).await, fname);
+                log_err_and_bail!(file.read_exact(&mut tail).await, fname);
+                if existing_len > mcap::MAGIC.len() as u64 && tail == *mcap::MAGIC {
+                    crate::log_error!(
+                        "Log file {} is a finished MCAP file and can't be appended to",
+                        fname.display()
+                    );
+                    return;
+                }
+            }
+            mcap.start(&mut buffer, existing_len == 0);
+        }
+    }
+    log_err_and_bail!(file.write_all(&buffer).await, fname);
+    let mut filter = FilterState::new(filter);
+    // unknown at first, since the file may have been appended to with another label in effect
+    let mut current_tag = None;
+    let mut failed = false;
 
-    while let Some(msg) = rx.recv().await {
+    while let Some(entry) = rx.recv().await {
+        let LogEntry { msg, tag } = entry;
+        if !filter.keep(&msg) {
+            continue;
+        }
         buffer.clear();
-        let header = LogHeader::from(msg);
-        buffer.extend_from_slice(bytemuck::bytes_of(&header));
-        buffer.extend_from_slice(msg.data_slice());
+        match format {
+            LogFormat::Rdxlog => {
+                if msg.tx() && current_tag != Some(tag) {
+                    current_tag = Some(tag);
+                    let label = tag.label();
+                    let label = label.as_deref().unwrap_or_default().as_bytes();
+                    let header = LogHeader {
+                        message_id: 0,
+                        bus_id: msg.bus_id,
+                        flags: LogHeader::FLAG_LABEL,
+                        data_size: label.len() as u8,
+                        timestamp: msg.timestamp,
+                    };
+                    buffer.extend_from_slice(bytemuck::bytes_of(&header));
+                    buffer.extend_from_slice(label);
+                }
+                let header = LogHeader::from(msg);
+                buffer.extend_from_slice(bytemuck::bytes_of(&header));
+                buffer.extend_from_slice(msg.data_slice());
+            }
+            LogFormat::Mcap => mcap.message(&mut buffer, &entry),
+        }
         if let Err(e) = file.write_all(&buffer).await {
             crate::log_error!("Failed write to {}: {e}", fname.display());
+            failed = true;
             break;
         }
     }
 
     rx.close();
 
+    // a logger that died mid-file leaves it open for its replacement to append to
+    if format == LogFormat::Mcap && !failed {
+        buffer.clear();
+        mcap.finish(&mut buffer);
+        if let Err(e) = file.write_all(&buffer).await {
+            crate::log_error!("Failed write to {}: {e}", fname.display());
+        }
+    }
+
     crate::log_info!("Closing log file {}", fname.display());
     file.shutdown().await.ok();
 }
@@ -0,0 +1,156 @@
+//! Bus logs in the [MCAP](https://mcap.dev/spec) container, for opening captures directly in
+//! Foxglove or PlotJuggler.
+//!
+//! Each log has one channel, [`TOPIC`], whose messages are JSON objects described by [`SCHEMA`]:
+//!
+//! ```json
+//! { "bus_id": 0, "id": 118359747, "extended": true, "fd": false, "tx": true, "label": "ota",
+//!   "data": [0, 16, 0, 0] }
+//! ```
+//!
+//! `label` is only present on messages written through a [labeled session](crate::trace_tag).
+//! Files are written unindexed and uncompressed, as the logger streams them; the summary section
+//! is left out, which readers handle by scanning the file.
+
+use crate::logger::LogEntry;
+
+/// Starts and ends every MCAP file.
+pub const MAGIC: &[u8; 8] = b"\x89MCAP0\r\n";
+
+/// Topic of the log's one channel.
+pub const TOPIC: &str = "/can";
+
+pub const SCHEMA_NAME: &str = "reduxfifo.CanFrame";
+
+/// JSON schema of the messages on [`TOPIC`].
+pub const SCHEMA: &str = r#"{
+  "type": "object",
+  "properties": {
+    "bus_id": { "type": "integer", "description": "ReduxFIFO bus the frame was on" },
+    "id": { "type": "integer", "description": "Message id, 11 or 29 bits" },
+    "extended": { "type": "boolean", "description": "Whether the id is 29-bit" },
+    "fd": { "type": "boolean", "description": "Whether the frame is CAN FD" },
+    "tx": { "type": "boolean", "description": "Whether this host wrote the frame" },
+    "label": { "type": "string", "description": "Label of the session that wrote the frame" },
+    "data": { "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } }
+  },
+  "required": ["bus_id", "id", "extended", "fd", "tx", "data"]
+}"#;
+
+const OP_HEADER: u8 = 0x01;
+const OP_FOOTER: u8 = 0x02;
+const OP_SCHEMA: u8 = 0x03;
+const OP_CHANNEL: u8 = 0x04;
+const OP_MESSAGE: u8 = 0x05;
+const OP_DATA_END: u8 = 0x0f;
+
+const SCHEMA_ID: u16 = 1;
+const CHANNEL_ID: u16 = 1;
+
+/// Encodes log entries as MCAP records.
+#[derive(Debug, Default)]
+pub struct McapWriter {
+    sequence: u32,
+}
+
+impl McapWriter {
+    /// Writes what comes before the first message: the file's magic and header if it's a new file,
+    /// then the schema and channel, which are repeated when a restarted logger appends to a file.
+    pub fn start(&mut self, buffer: &mut Vec<u8>, new_file: bool) {
+        if new_file {
+            buffer.extend_from_slice(MAGIC);
+            record(buffer, OP_HEADER, |buf| {
+                string(buf, "");
+                string(buf, concat!("reduxfifo ", env!("CARGO_PKG_VERSION")));
+            });
+        }
+        record(buffer, OP_SCHEMA, |buf| {
+            buf.extend_from_slice(&SCHEMA_ID.to_le_bytes());
+            string(buf, SCHEMA_NAME);
+            string(buf, "jsonschema");
+            string(buf, SCHEMA);
+        });
+        record(buffer, OP_CHANNEL, |buf| {
+            buf.extend_from_slice(&CHANNEL_ID.to_le_bytes());
+            buf.extend_from_slice(&SCHEMA_ID.to_le_bytes());
+            string(buf, TOPIC);
+            string(buf, "json");
+            // no metadata
+            buf.extend_from_slice(&0_u32.to_le_bytes());
+        });
+    }
+
+    pub fn message(&mut self, buffer: &mut Vec<u8>, entry: &LogEntry) {
+        let LogEntry { msg, tag } = entry;
+        // microseconds to nanoseconds
+        let time = msg.timestamp.saturating_mul(1000);
+        self.sequence = self.sequence.wrapping_add(1);
+        record(buffer, OP_MESSAGE, |buf| {
+            buf.extend_from_slice(&CHANNEL_ID.to_le_bytes());
+            buf.extend_from_slice(&self.sequence.to_le_bytes());
+            buf.extend_from_slice(&time.to_le_bytes());
+            buf.extend_from_slice(&time.to_le_bytes());
+
+            use std::io::Write;
+            write!(
+                buf,
+                r#"{{"bus_id":{},"id":{},"extended":{},"fd":{},"tx":{}"#,
+                msg.bus_id,
+                msg.id(),
+                !msg.short_id(),
+                !msg.no_fd(),
+                msg.tx()
+            )
+            .ok();
+            if let Some(label) = tag.label() {
+                // labels are printable ascii, so quotes and backslashes are all that need escaping
+                buf.extend_from_slice(br#","label":""#);
+                for c in label.bytes() {
+                    if c == b'"' || c == b'\\' {
+                        buf.push(b'\\');
+                    }
+                    buf.push(c);
+                }
+                buf.push(b'"');
+            }
+            buf.extend_from_slice(br#","data":["#);
+            for (i, byte) in msg.data_slice().iter().enumerate() {
+                if i > 0 {
+                    buf.push(b',');
+                }
+                write!(buf, "{byte}").ok();
+            }
+            buf.extend_from_slice(b"]}");
+        });
+    }
+
+    /// Writes the end of the file, after which nothing more can be appended.
+    pub fn finish(&mut self, buffer: &mut Vec<u8>) {
+        // crcs of 0 mean they weren't computed
+        record(buffer, OP_DATA_END, |buf| {
+            buf.extend_from_slice(&0_u32.to_le_bytes())
+        });
+        record(buffer, OP_FOOTER, |buf| {
+            // no summary section
+            buf.extend_from_slice(&0_u64.to_le_bytes());
+            buf.extend_from_slice(&0_u64.to_le_bytes());
+            buf.extend_from_slice(&0_u32.to_le_bytes());
+        });
+        buffer.extend_from_slice(MAGIC);
+    }
+}
+
+/// Appends a record with opcode `op` and the content `f` writes.
+fn record(buffer: &mut Vec<u8>, op: u8, f: impl FnOnce(&mut Vec<u8>)) {
+    buffer.push(op);
+    let len_at = buffer.len();
+    buffer.extend_from_slice(&0_u64.to_le_bytes());
+    f(buffer);
+    let len = (buffer.len() - len_at - 8) as u64;
+    buffer[len_at..len_at + 8].copy_from_slice(&len.to_le_bytes());
+}
+
+fn string(buffer: &mut Vec<u8>, s: &str) {
+    buffer.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(s.as_bytes());
+}
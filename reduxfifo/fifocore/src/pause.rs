@@ -0,0 +1,136 @@
+//! Pausing a session's view of a bus without losing what it would have seen.
+//!
+//! A paused session stops getting messages: its read barriers come back with nothing new and its
+//! rx notifier stays put. What it would have got is held in a queue of up to the cap it was paused
+//! with, which counts toward the [memory limits](crate::limits) like a read buffer does. Past the
+//! cap the newest message isn't held, and is counted in [`PauseStatus::dropped`] and the
+//! `pause_full` drop total.
+//!
+//! Resuming hands the held messages over in the order they arrived, as many as the read buffer
+//! has room for at a time, topping it up after each read barrier until the queue is empty.
+//! Messages that arrive before then go behind the held ones, so a reader that keeps up sees
+//! exactly the frames it would have seen unpaused, just late.
+
+use std::collections::VecDeque;
+
+use crate::{
+    ReadBuffer, ReduxFIFOMessage,
+    error::Error,
+    limits::{DropKind, Reservation},
+};
+
+/// Messages held for a paused session when no cap is given.
+pub const DEFAULT_PAUSE_CAP: u32 = 16384;
+/// Most messages a paused session can hold.
+pub const MAX_PAUSE_CAP: u32 = 1 << 20;
+
+/// Where a session's pause stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct PauseStatus {
+    pub paused: bool,
+    /// Messages waiting to be delivered: held while paused, or not yet handed over since resuming
+    pub held: u32,
+    /// Most messages that can be held
+    pub cap: u32,
+    /// Messages not held because the queue was full, since the session was last paused
+    pub dropped: u64,
+}
+
+/// Hold queue of one session.
+#[derive(Debug, Default)]
+pub struct PauseQueue {
+    paused: bool,
+    held: VecDeque<ReduxFIFOMessage>,
+    /// Memory the queue may take, held from pausing until the queue drains
+    reservation: Option<Reservation>,
+    dropped: u64,
+}
+
+impl PauseQueue {
+    /// Starts holding messages, up to `cap` of them. Pausing a paused session changes its cap,
+    /// keeping what it holds.
+    pub fn pause(&mut self, cap: u32) -> Result<(), Error> {
+        let cap = cap.clamp(1, MAX_PAUSE_CAP).max(self.held.len() as u32);
+        let previous = self.reservation.take();
+        match Reservation::reserve(cap) {
+            Ok(reservation) => self.reservation = Some(reservation),
+            Err(e) => {
+                self.reservation = previous;
+                return Err(e);
+            }
+        }
+        if !self.paused {
+            self.dropped = 0;
+        }
+        self.paused = true;
+        Ok(())
+    }
+
+    /// Stops holding new messages; what's held is handed over by [`PauseQueue::refill`].
+    pub fn resume(&mut self) {
+        self.paused = false;
+        if self.held.is_empty() {
+            self.reservation = None;
+        }
+    }
+
+    /// Whether messages go to the queue instead of the session: while paused, and until what was
+    /// held has been handed over.
+    pub fn holding(&self) -> bool {
+        self.paused || !self.held.is_empty()
+    }
+
+    /// Holds a message, or drops it if the queue is full.
+    pub fn hold(&mut self, msg: ReduxFIFOMessage) {
+        if self.held.len() >= self.cap() as usize {
+            self.dropped += 1;
+            crate::limits::record_drop(DropKind::PauseFull, 1);
+            return;
+        }
+        self.held.push_back(msg);
+    }
+
+    /// Moves held messages into `read_buf`, oldest first, until it's full. Returns whether any
+    /// were moved.
+    pub fn refill(&mut self, read_buf: &mut ReadBuffer) -> bool {
+        if self.paused {
+            return false;
+        }
+        let room = read_buf
+            .meta
+            .max_length
+            .saturating_sub(read_buf.meta.valid_length);
+        let count = (room as usize).min(self.held.len());
+        for msg in self.held.drain(..count) {
+            read_buf.add_message(msg);
+        }
+        if self.held.is_empty() {
+            self.reservation = None;
+        }
+        count > 0
+    }
+
+    /// Hands every held message to `deliver`, for sessions read through a ring.
+    pub fn flush(&mut self, mut deliver: impl FnMut(&ReduxFIFOMessage)) {
+        if self.paused {
+            return;
+        }
+        for msg in self.held.drain(..) {
+            deliver(&msg);
+        }
+        self.reservation = None;
+    }
+
+    pub fn status(&self) -> PauseStatus {
+        PauseStatus {
+            paused: self.paused,
+            held: self.held.len() as u32,
+            cap: self.cap(),
+            dropped: self.dropped,
+        }
+    }
+
+    fn cap(&self) -> u32 {
+        self.reservation.as_ref().map_or(0, Reservation::size)
+    }
+}
@@ -0,0 +1,133 @@
+//! Traffic kept for sessions that close and are opened again, such as across robot code restarts.
+//!
+//! A session opened with [`ReduxFIFOSessionConfig::retain_ms`] set leaves its filter registered
+//! when it closes. The bus keeps collecting matching messages for it from then on, holding only
+//! the last `retain_ms` of them and no more than the closed session's buffer did. The next session
+//! opened on the bus with the same filter, `echo_tx` setting and a nonzero `retain_ms` gets those
+//! messages first, flagged with [`ReduxFIFOMessage::FLAG_HISTORICAL`], so nothing received in
+//! the gap is lost.
+//!
+//! The retained messages keep the closed session's share of the buffer budget until they are
+//! delivered or the bus closes.
+
+use std::collections::VecDeque;
+
+use rustc_hash::FxHashMap;
+
+use crate::{ReduxFIFOMessage, ReduxFIFOSessionConfig, limits::Reservation};
+
+/// What a retained filter is matched by on re-open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct RetainKey {
+    filter_id: u32,
+    filter_mask: u32,
+    echo_tx: bool,
+}
+
+impl RetainKey {
+    fn new(config: &ReduxFIFOSessionConfig) -> Self {
+        Self {
+            filter_id: config.filter_id,
+            filter_mask: config.filter_mask,
+            echo_tx: config.echo_tx,
+        }
+    }
+}
+
+/// Messages kept for one closed session's filter.
+#[derive(Debug)]
+struct Retained {
+    config: ReduxFIFOSessionConfig,
+    /// How long messages are kept, in microseconds
+    window_us: u64,
+    /// Messages by the host time they arrived, oldest first
+    messages: VecDeque<(u64, ReduxFIFOMessage)>,
+    reservation: Reservation,
+}
+
+impl Retained {
+    fn record(&mut self, msg: ReduxFIFOMessage, now: u64) {
+        if self.messages.len() >= self.reservation.size() as usize {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((now, msg));
+        self.expire(now);
+    }
+
+    fn expire(&mut self, now: u64) {
+        while let Some(&(arrived, _)) = self.messages.front() {
+            if now.saturating_sub(arrived) <= self.window_us {
+                break;
+            }
+            self.messages.pop_front();
+        }
+    }
+}
+
+/// The filters of a bus's closed sessions that asked for their traffic to be retained.
+#[derive(Debug, Default)]
+pub struct RetainedHistory {
+    filters: FxHashMap<RetainKey, Retained>,
+}
+
+impl RetainedHistory {
+    /// Starts retaining for a session that just closed, if it asked for it. A filter that is
+    /// already being retained keeps what it has and takes the longer of the two windows.
+    pub fn session_closed(&mut self, config: ReduxFIFOSessionConfig, reservation: Reservation) {
+        if config.retain_ms == 0 || reservation.size() == 0 {
+            return;
+        }
+        let window_us = config.retain_ms as u64 * 1000;
+        self.filters
+            .entry(RetainKey::new(&config))
+            .and_modify(|retained| retained.window_us = retained.window_us.max(window_us))
+            .or_insert_with(|| Retained {
+                config,
+                window_us,
+                messages: VecDeque::new(),
+                reservation,
+            });
+    }
+
+    /// Takes what was retained for a session being opened with `config`, oldest first and flagged
+    /// as historical.
+    pub fn take(&mut self, config: &ReduxFIFOSessionConfig, now: u64) -> Vec<ReduxFIFOMessage> {
+        if config.retain_ms == 0 {
+            return Vec::new();
+        }
+        let Some(mut retained) = self.filters.remove(&RetainKey::new(config)) else {
+            return Vec::new();
+        };
+        retained.expire(now);
+        retained
+            .messages
+            .drain(..)
+            .map(|(_, mut msg)| {
+                msg.flags |= ReduxFIFOMessage::FLAG_HISTORICAL;
+                msg
+            })
+            .collect()
+    }
+
+    /// Keeps a received message for every retained filter it matches.
+    pub fn record(&mut self, msg: &ReduxFIFOMessage, now: u64) {
+        for retained in self
+            .filters
+            .values_mut()
+            .filter(|retained| retained.config.message_matches(msg))
+        {
+            retained.record(*msg, now);
+        }
+    }
+
+    /// Keeps a message this host sent for every retained filter that echoes them.
+    pub fn record_tx(&mut self, msg: &ReduxFIFOMessage, now: u64) {
+        for retained in self
+            .filters
+            .values_mut()
+            .filter(|retained| retained.config.echo_tx && retained.config.message_matches(msg))
+        {
+            retained.record(*msg, now);
+        }
+    }
+}
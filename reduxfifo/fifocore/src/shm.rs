@@ -0,0 +1,229 @@
+//! Shared-memory ring buffers that a session's received messages can be delivered into.
+//!
+//! Reading a session through read barriers copies its messages into a [`ReadBuffer`] the caller
+//! handed over, under the bus lock. A session with a [`ShmRing`] attached instead has its messages
+//! written straight into a memory-mapped ring that the reader maps and reads in place, with no
+//! call into ReduxFIFO per read: the bus is the only writer and the session's reader the only
+//! reader, so the ring needs nothing more than its two counters.
+//!
+//! The mapping starts with a [`ShmRingHeader`], followed at `header_size` by `capacity` message
+//! slots of `slot_size` bytes each. `head` counts the messages written and `tail` the messages
+//! read, so `head - tail` are waiting, in the slots at `tail % capacity` up to `head % capacity`.
+//! The reader loads `head` with acquire ordering, reads the slots, and then stores the new `tail`
+//! with release ordering. A full ring drops new messages, counting them in `dropped`, rather than
+//! overwriting ones the reader may be in the middle of.
+
+use std::{
+    ptr::NonNull,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+};
+
+use crate::{
+    ReduxFIFOMessage, ReduxFIFOSession,
+    error::Error,
+    limits::{DropKind, Reservation},
+};
+
+/// `RDXR`, at the start of every ring.
+pub const RING_MAGIC: u32 = u32::from_le_bytes(*b"RDXR");
+pub const RING_VERSION: u16 = 1;
+/// Largest ring that can be attached, in messages.
+pub const MAX_RING_CAPACITY: u32 = 1 << 16;
+
+/// Start of a ring's mapping, shared with its reader.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ShmRingHeader {
+    /// [`RING_MAGIC`]
+    pub magic: u32,
+    /// [`RING_VERSION`]
+    pub version: u16,
+    /// Offset of the first message slot from the start of the header
+    pub header_size: u16,
+    /// Number of message slots, a power of two
+    pub capacity: u32,
+    /// Size of each message slot
+    pub slot_size: u32,
+    /// Session the ring delivers
+    pub session: u64,
+    /// Messages written by ReduxFIFO
+    pub head: AtomicU64,
+    /// Messages read by the reader, which is the only one that stores it
+    pub tail: AtomicU64,
+    /// Messages dropped because the ring was full
+    pub dropped: AtomicU64,
+    /// Nonzero once the ring is detached from its session, after which nothing more is written
+    pub closed: AtomicU32,
+    reserved: u32,
+}
+
+/// Bytes before the first message slot; a cache line, so slots don't share one with the counters.
+const HEADER_SIZE: usize = 64;
+const _: () = assert!(size_of::<ShmRingHeader>() <= HEADER_SIZE);
+
+/// A memory-mapped ring of received messages; see the [module docs](self).
+#[derive(Debug)]
+pub struct ShmRing {
+    map: NonNull<u8>,
+    len: usize,
+    capacity: u32,
+    /// Share of the buffer budget the slots count against
+    _reservation: Reservation,
+}
+
+// The header is only touched through atomics once shared, and slots are handed between the one
+// writer and the one reader by `head` and `tail`.
+unsafe impl Send for ShmRing {}
+unsafe impl Sync for ShmRing {}
+
+impl ShmRing {
+    /// Maps a ring for `session` holding at least `capacity` messages, rounded up to a power of
+    /// two, counting against the [memory limits](crate::limits).
+    pub fn new(session: ReduxFIFOSession, capacity: u32) -> Result<Self, Error> {
+        if capacity == 0 || capacity > MAX_RING_CAPACITY {
+            return Err(Error::MemoryLimitReached);
+        }
+        let capacity = capacity.next_power_of_two();
+        let reservation = Reservation::reserve(capacity)?;
+        if reservation.size() < capacity {
+            // a ring can't be any smaller than the reader was told it would be
+            return Err(Error::MemoryLimitReached);
+        }
+        let len = HEADER_SIZE + capacity as usize * size_of::<ReduxFIFOMessage>();
+        let map = map(len).ok_or(Error::MemoryLimitReached)?;
+        let ring = Self {
+            map,
+            len,
+            capacity,
+            _reservation: reservation,
+        };
+        // the mapping starts zeroed, so only the constants need writing
+        unsafe {
+            let header = map.as_ptr() as *mut ShmRingHeader;
+            (*header).magic = RING_MAGIC;
+            (*header).version = RING_VERSION;
+            (*header).header_size = HEADER_SIZE as u16;
+            (*header).capacity = capacity;
+            (*header).slot_size = size_of::<ReduxFIFOMessage>() as u32;
+            (*header).session = session.0;
+        }
+        Ok(ring)
+    }
+
+    pub fn header(&self) -> &ShmRingHeader {
+        unsafe { &*(self.map.as_ptr() as *const ShmRingHeader) }
+    }
+
+    /// Start of the mapping, for handing to the reader.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.map.as_ptr()
+    }
+
+    /// Size of the mapping in bytes.
+    pub fn map_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Messages waiting to be read.
+    pub fn len(&self) -> u32 {
+        let header = self.header();
+        let tail = header.tail.load(Ordering::Acquire);
+        header.head.load(Ordering::Acquire).wrapping_sub(tail) as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(&self, index: u64) -> *mut ReduxFIFOMessage {
+        let offset = (index & (self.capacity as u64 - 1)) as usize;
+        unsafe { (self.map.as_ptr().add(HEADER_SIZE) as *mut ReduxFIFOMessage).add(offset) }
+    }
+
+    /// Writes a message into the ring, returning `false` if it was full or closed.
+    ///
+    /// Only the bus the ring's session is on may push.
+    pub(crate) fn push(&self, msg: &ReduxFIFOMessage) -> bool {
+        let header = self.header();
+        if header.closed.load(Ordering::Relaxed) != 0 {
+            return false;
+        }
+        let head = header.head.load(Ordering::Relaxed);
+        if head.wrapping_sub(header.tail.load(Ordering::Acquire)) >= self.capacity as u64 {
+            header.dropped.fetch_add(1, Ordering::Relaxed);
+            crate::limits::record_drop(DropKind::RingFull, 1);
+            return false;
+        }
+        unsafe { self.slot(head).write(*msg) };
+        header.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Reads every waiting message in place, oldest first, then frees their slots. Returns how
+    /// many were read.
+    ///
+    /// This is the reader's side; it must not run while anything else reads the ring.
+    pub fn read(&self, mut f: impl FnMut(&ReduxFIFOMessage)) -> usize {
+        let header = self.header();
+        let tail = header.tail.load(Ordering::Relaxed);
+        let head = header.head.load(Ordering::Acquire);
+        let mut index = tail;
+        while index != head {
+            f(unsafe { &*self.slot(index) });
+            index = index.wrapping_add(1);
+        }
+        header.tail.store(head, Ordering::Release);
+        head.wrapping_sub(tail) as usize
+    }
+
+    /// Stops messages being written into the ring and tells its reader so.
+    pub(crate) fn close(&self) {
+        self.header().closed.store(1, Ordering::Release);
+    }
+}
+
+impl Drop for ShmRing {
+    fn drop(&mut self) {
+        unmap(self.map, self.len);
+    }
+}
+
+#[cfg(unix)]
+fn map(len: usize) -> Option<NonNull<u8>> {
+    let ptr = unsafe {
+        libc::mmap(
+            core::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return None;
+    }
+    NonNull::new(ptr as *mut u8)
+}
+
+#[cfg(unix)]
+fn unmap(map: NonNull<u8>, len: usize) {
+    unsafe { libc::munmap(map.as_ptr() as *mut libc::c_void, len) };
+}
+
+// elsewhere the ring is plain zeroed memory; it's still only shared within the process
+#[cfg(not(unix))]
+fn map(len: usize) -> Option<NonNull<u8>> {
+    let layout = std::alloc::Layout::from_size_align(len, HEADER_SIZE).ok()?;
+    NonNull::new(unsafe { std::alloc::alloc_zeroed(layout) })
+}
+
+#[cfg(not(unix))]
+fn unmap(map: NonNull<u8>, len: usize) {
+    let layout = std::alloc::Layout::from_size_align(len, HEADER_SIZE).unwrap();
+    unsafe { std::alloc::dealloc(map.as_ptr(), layout) };
+}
@@ -1,14 +1,20 @@
 /// The current monotonic time.
-/// This is the FPGA time if wpihal support is compiled in, otherwise just [`monotonic_us`]
+/// This is the FPGA time if wpihal support is compiled in and the HAL is present, otherwise just [`monotonic_us`]
 #[cfg(feature = "wpihal-rio")]
 pub fn now_us() -> i64 {
+    if !crate::hal::available() {
+        return monotonic_us();
+    }
     wpihal_rio::get_fpga_time().unwrap_or(0) as i64
 }
 
 /// The current monotonic time.
-/// This is the FPGA time if wpihal support is compiled in, otherwise just [`monotonic_us`]
+/// This is the FPGA time if wpihal support is compiled in and the HAL is present, otherwise just [`monotonic_us`]
 #[cfg(feature = "wpihal-mrc")]
 pub fn now_us() -> i64 {
+    if !crate::hal::available() {
+        return monotonic_us();
+    }
     wpihal_mrc::get_fpga_time().unwrap_or(0) as i64
 }
 
@@ -34,6 +40,9 @@ pub fn retimestamp(ts_us: i64, other_time_us: i64) -> u64 {
 pub fn retimestamp_from_monotonic(ts_us: i64) -> u64 {
     #[cfg(feature = "wpihal-rio")]
     {
+        if !crate::hal::available() {
+            return ts_us as u64;
+        }
         retimestamp(ts_us, monotonic_us())
     }
     #[cfg(not(feature = "wpihal-rio"))]
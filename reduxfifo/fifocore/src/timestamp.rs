@@ -0,0 +1,125 @@
+//! Where the timestamps of received messages come from.
+//!
+//! Backends differ in what they know about when a frame was on the wire: SocketCAN gets a kernel
+//! receive time from the driver, a remote CANLink server sends its own, and serial or USB adapters
+//! give us nothing better than when the bytes reached this host. Each backend declares its
+//! [`TimestampSource`], and the bus's [`Timestamper`] turns whatever the backend had into the final
+//! timestamp, so that every bus produces increasing timestamps in the [`crate::timebase`].
+//!
+//! For host-arrival buses, frames that arrive in the same read are spread back from the read time
+//! by [`HOST_ARRIVAL_SPACING_US`] each, since they went over the wire one after another rather than
+//! all at once. Sources that can fail to stamp a frame fall back to the host-arrival time for it.
+//! If a source's clock jumps back by more than [`RESYNC_US`] (say, a remote server restarted), its
+//! later timestamps are shifted to carry on from where the bus left off.
+
+use crate::ReduxFIFOMessage;
+
+/// Spacing of frames that arrive together on a host-arrival bus, in microseconds. This is about the
+/// shortest a classic CAN frame can take at 1 Mbit/s.
+pub const HOST_ARRIVAL_SPACING_US: u64 = 50;
+
+/// Backwards jumps in a source's timestamps past this are treated as its clock restarting, in
+/// microseconds. Smaller ones are clamped.
+pub const RESYNC_US: u64 = 1_000_000;
+
+/// What a bus's timestamps are based on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampSource {
+    /// Taken by the CAN controller as the frame came off the wire
+    Hardware,
+    /// Taken by the OS driver on receipt
+    Driver,
+    /// Taken by the remote host that relayed the frame
+    Remote,
+    /// Taken when the frame reached this host
+    HostArrival,
+    /// Recorded in a replayed log, moved to when the replay plays the frame
+    Replay,
+}
+
+/// How a bus stamps received messages, and how often it had to step in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TimestampStatus {
+    pub source: TimestampSource,
+    /// Messages the source didn't stamp, which got the host-arrival time instead
+    pub fallbacks: u64,
+    /// Messages moved later to keep timestamps increasing
+    pub clamped: u64,
+    /// Times the source's clock jumped back and was shifted to carry on
+    pub resyncs: u64,
+}
+
+/// Applies a bus's [`TimestampSource`] to the messages it receives.
+#[derive(Debug, Clone)]
+pub struct Timestamper {
+    status: TimestampStatus,
+    last: u64,
+    /// Added to source timestamps since the last resync
+    offset: u64,
+}
+
+impl Timestamper {
+    pub fn new(source: TimestampSource) -> Self {
+        Self {
+            status: TimestampStatus {
+                source,
+                fallbacks: 0,
+                clamped: 0,
+                resyncs: 0,
+            },
+            last: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn source(&self) -> TimestampSource {
+        self.status.source
+    }
+
+    pub fn status(&self) -> TimestampStatus {
+        self.status
+    }
+
+    /// Stamps a batch of messages that were received together, by `now`.
+    ///
+    /// The backend leaves its own timestamp on each message, or 0 if it had none.
+    pub fn stamp(&mut self, msgs: &mut [ReduxFIFOMessage], now: u64) {
+        let last_idx = msgs.len().saturating_sub(1);
+        for (i, msg) in msgs.iter_mut().enumerate() {
+            let ts = match self.status.source {
+                TimestampSource::HostArrival => {
+                    now.saturating_sub((last_idx - i) as u64 * HOST_ARRIVAL_SPACING_US)
+                }
+                _ if msg.timestamp == 0 => {
+                    self.status.fallbacks += 1;
+                    now
+                }
+                _ => self.rebase(msg.timestamp),
+            };
+            msg.timestamp = self.increasing(ts);
+        }
+    }
+
+    /// Applies the resync offset to a source timestamp, resyncing if the source jumped back.
+    fn rebase(&mut self, ts: u64) -> u64 {
+        let ts = ts.saturating_add(self.offset);
+        if ts.saturating_add(RESYNC_US) < self.last {
+            self.offset += self.last + 1 - ts;
+            self.status.resyncs += 1;
+            return self.last + 1;
+        }
+        ts
+    }
+
+    fn increasing(&mut self, ts: u64) -> u64 {
+        let ts = if ts <= self.last {
+            self.status.clamped += 1;
+            self.last + 1
+        } else {
+            ts
+        };
+        self.last = ts;
+        ts
+    }
+}
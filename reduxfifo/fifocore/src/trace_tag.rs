@@ -0,0 +1,61 @@
+//! Labels naming the software on this host that wrote a frame.
+//!
+//! When several programs share a bus through one ReduxFIFO (the vendordep, Alchemist, a firmware
+//! update), the frames they write all look alike on the wire. A session can be given a label such
+//! as `"vendordep"` or `"ota"` with [`FIFOCore::set_session_label`](crate::FIFOCore), and messages
+//! written through it with [`FIFOCore::write_single_as`](crate::FIFOCore) carry the label into
+//! the bus log, where [`crate::logger`] records it alongside them.
+//!
+//! Labels are interned process-wide into a [`TraceTag`], so passing one around costs a byte.
+
+use std::sync::Arc;
+
+use crate::error::Error;
+
+/// Longest label a session can have, in bytes.
+pub const MAX_LABEL_LEN: usize = 32;
+
+/// Interned labels; [`TraceTag`] `n` is `LABELS[n - 1]`.
+static LABELS: parking_lot::Mutex<Vec<Arc<str>>> = parking_lot::const_mutex(Vec::new());
+
+/// An interned session label. [`TraceTag::NONE`] marks writes that didn't come from a labeled
+/// session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TraceTag(u8);
+
+impl TraceTag {
+    pub const NONE: Self = Self(0);
+
+    /// Finds or adds the tag for `label`.
+    ///
+    /// Labels are 1 to [`MAX_LABEL_LEN`] bytes of printable ASCII without spaces. Up to 255
+    /// different labels can be used over the life of the process.
+    pub fn intern(label: &str) -> Result<Self, Error> {
+        if label.is_empty()
+            || label.len() > MAX_LABEL_LEN
+            || !label.bytes().all(|b| b.is_ascii_graphic())
+        {
+            return Err(Error::InvalidLabel);
+        }
+        let mut labels = LABELS.lock();
+        if let Some(index) = labels.iter().position(|l| &**l == label) {
+            return Ok(Self(index as u8 + 1));
+        }
+        if labels.len() == u8::MAX as usize {
+            crate::log_error!("ran out of trace tags for session label {label}");
+            return Err(Error::InvalidLabel);
+        }
+        labels.push(label.into());
+        Ok(Self(labels.len() as u8))
+    }
+
+    /// The label this tag was interned from, or [`None`] for [`TraceTag::NONE`].
+    pub fn label(self) -> Option<Arc<str>> {
+        let index = (self.0 as usize).checked_sub(1)?;
+        LABELS.lock().get(index).cloned()
+    }
+
+    pub fn is_none(self) -> bool {
+        self == Self::NONE
+    }
+}
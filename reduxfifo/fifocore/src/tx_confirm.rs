@@ -0,0 +1,173 @@
+//! On-wire confirmation of transmitted messages.
+//!
+//! Writing a message only means it was queued. Some backends can also tell when it actually went
+//! out on the bus: SocketCAN loops our own frames back once the controller has sent them, and
+//! RdxUSB devices with [`rdxusb_protocol::DEVICE_CAP_TX_ACK`] echo host frames once transmitted.
+//! On buses opened with the `;confirm_tx` option, every write gets a sequence number that can be
+//! looked up here for that on-wire timestamp.
+
+use std::collections::VecDeque;
+
+use tokio::sync::watch;
+
+use crate::{ReduxFIFOMessage, error::Error};
+
+/// Writes waiting for confirmation; past this the oldest is given up on.
+const MAX_PENDING: usize = 256;
+/// Confirmed writes whose timestamps can still be looked up.
+const MAX_CONFIRMED: usize = 1024;
+/// How long a write may wait for confirmation before it counts as unconfirmed, in microseconds.
+pub const CONFIRM_TIMEOUT_US: u64 = 1_000_000;
+
+/// Where a tracked write is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    /// Written but not yet seen on the wire
+    Pending,
+    /// Went out on the wire at this timestamp, in the same time base as received messages
+    OnWire(u64),
+    /// No confirmation: the bus doesn't track them, the frame never made it out, or the result
+    /// is too old to still be kept
+    Unconfirmed,
+}
+
+impl TxStatus {
+    pub fn into_result(self) -> Result<u64, Error> {
+        match self {
+            TxStatus::Pending => Err(Error::TxPending),
+            TxStatus::OnWire(timestamp) => Ok(timestamp),
+            TxStatus::Unconfirmed => Err(Error::TxUnconfirmed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingTx {
+    seq: u32,
+    written_at: u64,
+    message_id: u32,
+    data_size: u8,
+    data: [u8; 64],
+}
+
+impl PendingTx {
+    fn matches(&self, msg: &ReduxFIFOMessage) -> bool {
+        self.message_id == msg.message_id
+            && self.data_size == msg.data_size
+            && self.data[..self.data_size as usize] == *msg.data_slice()
+    }
+
+    fn expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.written_at) > CONFIRM_TIMEOUT_US
+    }
+}
+
+/// Matches frames seen on the wire back to the writes that produced them.
+#[derive(Debug)]
+pub struct TxTracker {
+    enabled: bool,
+    next_seq: u32,
+    pending: VecDeque<PendingTx>,
+    confirmed: VecDeque<(u32, u64)>,
+    /// Bumped whenever a write is confirmed
+    notifier: watch::Sender<u32>,
+}
+
+impl TxTracker {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            next_seq: 0,
+            pending: VecDeque::new(),
+            confirmed: VecDeque::new(),
+            notifier: watch::channel(0).0,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Starts tracking a message that's about to be written and returns its sequence number,
+    /// or `None` if this bus doesn't track confirmations. Sequence numbers are never zero.
+    pub fn track(&mut self, msg: &ReduxFIFOMessage, now: u64) -> Option<u32> {
+        if !self.enabled {
+            return None;
+        }
+        while self
+            .pending
+            .front()
+            .is_some_and(|p| p.expired(now) || self.pending.len() >= MAX_PENDING)
+        {
+            self.pending.pop_front();
+        }
+        self.next_seq = self.next_seq.wrapping_add(1).max(1);
+        self.pending.push_back(PendingTx {
+            seq: self.next_seq,
+            written_at: now,
+            message_id: msg.message_id,
+            data_size: msg.data_size,
+            data: msg.data,
+        });
+        Some(self.next_seq)
+    }
+
+    /// Forgets `count` writes starting at `first`, after the backend failed to send them.
+    pub fn untrack(&mut self, first: u32, count: u32) {
+        self.pending.retain(|p| p.seq.wrapping_sub(first) >= count);
+    }
+
+    /// Checks a frame from the wire against the tracked writes. If it matches one, that write is
+    /// confirmed with the frame's timestamp and this returns true: the frame is our own and
+    /// shouldn't be delivered as received traffic.
+    pub fn confirm(&mut self, msg: &ReduxFIFOMessage) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let Some(idx) = self.pending.iter().position(|p| p.matches(msg)) else {
+            return false;
+        };
+        let Some(tx) = self.pending.remove(idx) else {
+            return false;
+        };
+        if self.confirmed.len() >= MAX_CONFIRMED {
+            self.confirmed.pop_front();
+        }
+        self.confirmed.push_back((tx.seq, msg.timestamp));
+        self.notifier.send_modify(|n| *n = n.wrapping_add(1));
+        true
+    }
+
+    pub fn status(&self, seq: u32) -> TxStatus {
+        if !self.enabled || seq == 0 {
+            return TxStatus::Unconfirmed;
+        }
+        if let Some(tx) = self.pending.iter().find(|p| p.seq == seq) {
+            return if tx.expired(crate::timebase::now_us() as u64) {
+                TxStatus::Unconfirmed
+            } else {
+                TxStatus::Pending
+            };
+        }
+        self.confirmed
+            .iter()
+            .rev()
+            .find(|(s, _)| *s == seq)
+            .map_or(TxStatus::Unconfirmed, |(_, ts)| TxStatus::OnWire(*ts))
+    }
+
+    /// Changes whenever a write is confirmed.
+    pub fn subscribe(&self) -> watch::Receiver<u32> {
+        self.notifier.subscribe()
+    }
+}
+
+impl Default for TxTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,263 @@
+//! Writes held back while a bus's transmit buffer is full, sent in priority order as it drains.
+//!
+//! Without a queue, a write that finds the backend's buffer full fails with
+//! [`Error::BusBufferFull`] and it's up to the writer to try again, so under saturation whichever
+//! writer retries first wins. Buses opened with the `tx_queue=<n>` [option](crate::bus_options)
+//! instead keep up to `n` such writes and send them ahead of anything newer, most important first,
+//! as the backend makes room: on the bus's next write or read barrier, and every
+//! [`DRAIN_INTERVAL`] until the queue is empty.
+//!
+//! Importance follows CAN arbitration: the frame that would win the bus goes first, so the FRC
+//! heartbeat and other low ids get ahead of device telemetry. Writes made with
+//! [`TxPriority::Urgent`], like the global disable, go ahead of everything. Writes of the same
+//! priority keep the order they were made in. A full queue pushes out its least important write.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{ReduxFIFOMessage, data::MessageIdBuilder, error::Error, trace_tag::TraceTag};
+
+/// Longest queue a bus can be opened with.
+pub const MAX_TX_QUEUE: u32 = 4096;
+
+/// How often a bus with queued writes retries them when nothing else reads or writes it.
+pub const DRAIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Where a write goes in a bus's [`TxQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum TxPriority {
+    /// Ahead of every other write
+    Urgent,
+    /// By CAN arbitration id, as the bus itself would order them
+    #[default]
+    Arbitration,
+}
+
+/// Ranks an id the way CAN arbitration does, lowest first: by the 11 base id bits, then standard
+/// frames ahead of extended ones, then the 18 extended bits, then data frames ahead of remote
+/// frames.
+pub const fn arbitration_rank(message_id: u32) -> u64 {
+    let rtr = (message_id & MessageIdBuilder::ID_FLAG_RTR != 0) as u64;
+    if message_id & MessageIdBuilder::ID_FLAG_11BIT != 0 {
+        ((message_id & 0x7ff) as u64) << 20 | rtr
+    } else {
+        let id = message_id & 0x1fff_ffff;
+        ((id >> 18) as u64) << 20 | 1 << 19 | ((id & 0x3ffff) as u64) << 1 | rtr
+    }
+}
+
+/// A write waiting in a [`TxQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuedTx {
+    pub msg: ReduxFIFOMessage,
+    /// Who it's logged as written by
+    pub tag: TraceTag,
+    /// Its on-wire confirmation sequence number, if tracked
+    pub tx_seq: Option<u32>,
+}
+
+/// A write a bus accepted, with its on-wire confirmation sequence number if tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Written {
+    /// Handed to the backend
+    Sent(Option<u32>),
+    /// Waiting in the bus's [`TxQueue`] for the backend to make room
+    Queued(Option<u32>),
+}
+
+impl Written {
+    pub fn tx_seq(self) -> Option<u32> {
+        match self {
+            Self::Sent(tx_seq) | Self::Queued(tx_seq) => tx_seq,
+        }
+    }
+
+    pub fn queued(self) -> bool {
+        matches!(self, Self::Queued(_))
+    }
+}
+
+/// Writes queued by [`TxQueue::drain`], split by how they went.
+#[derive(Debug, Default)]
+pub struct Drained {
+    /// Handed to the backend
+    pub sent: Vec<QueuedTx>,
+    /// Refused by the backend for something other than a full buffer, and given up on
+    pub failed: Vec<(QueuedTx, Error)>,
+}
+
+/// Priority-ordered writes waiting for room in a bus's transmit buffer; see the
+/// [module docs](self).
+#[derive(Debug, Default)]
+pub struct TxQueue {
+    capacity: usize,
+    next_seq: u64,
+    queued: BTreeMap<(TxPriority, u64, u64), QueuedTx>,
+}
+
+impl TxQueue {
+    /// A queue of up to `capacity` writes. A capacity of 0 queues nothing.
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity: capacity.min(MAX_TX_QUEUE) as usize,
+            next_seq: 0,
+            queued: BTreeMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    /// How many writes of `priority` are waiting.
+    pub fn waiting(&self, priority: TxPriority) -> usize {
+        self.queued
+            .range((priority, 0, 0)..=(priority, u64::MAX, u64::MAX))
+            .count()
+    }
+
+    /// Whether the last write pushed is still waiting.
+    pub fn last_waiting(&self) -> bool {
+        self.queued
+            .keys()
+            .any(|&(_, _, seq)| seq.wrapping_add(1) == self.next_seq)
+    }
+
+    /// Queues a write. If the queue was full, the least important write is pushed out and
+    /// returned, which may be this one.
+    pub fn push(&mut self, tx: QueuedTx, priority: TxPriority) -> Option<QueuedTx> {
+        if !self.enabled() {
+            return Some(tx);
+        }
+        let key = (priority, arbitration_rank(tx.msg.message_id), self.next_seq);
+        self.next_seq += 1;
+        self.queued.insert(key, tx);
+        if self.queued.len() > self.capacity {
+            return self.queued.pop_last().map(|(_, tx)| tx);
+        }
+        None
+    }
+
+    /// Hands queued writes to `send` in priority order until it reports a full buffer or the queue
+    /// is empty.
+    pub fn drain(
+        &mut self,
+        mut send: impl FnMut(&ReduxFIFOMessage) -> Result<(), Error>,
+    ) -> Drained {
+        let mut drained = Drained::default();
+        while let Some(entry) = self.queued.first_entry() {
+            match send(&entry.get().msg) {
+                Err(Error::BusBufferFull) => break,
+                Ok(()) => drained.sent.push(entry.remove()),
+                Err(e) => drained.failed.push((entry.remove(), e)),
+            }
+        }
+        drained
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tx(message_id: u32) -> QueuedTx {
+        QueuedTx {
+            msg: ReduxFIFOMessage::id_data(0, message_id, [0; 64], 0, 0),
+            tag: TraceTag::NONE,
+            tx_seq: None,
+        }
+    }
+
+    fn ids(txs: &[QueuedTx]) -> Vec<u32> {
+        txs.iter().map(|tx| tx.msg.message_id).collect()
+    }
+
+    #[test]
+    fn test_arbitration_rank() {
+        let short = |id| MessageIdBuilder::new(id).short_id(true).build();
+        // a standard frame beats an extended one with the same base id, and loses to a lower one
+        assert!(arbitration_rank(short(0x100)) < arbitration_rank(0x100 << 18));
+        assert!(arbitration_rank(0x0ff << 18 | 0x3ffff) < arbitration_rank(short(0x100)));
+        assert!(arbitration_rank(0x0101_1840) < arbitration_rank(0x070e_07c1));
+        let rtr = MessageIdBuilder::new(0x1234).rtr(true).build();
+        assert!(arbitration_rank(0x1234) < arbitration_rank(rtr));
+        assert!(arbitration_rank(rtr) < arbitration_rank(0x1235));
+    }
+
+    #[test]
+    fn test_drain_order_under_saturation() {
+        let mut queue = TxQueue::new(8);
+        for id in [
+            0x070e_07c1,
+            0x070e_0781,
+            0x0101_1840,
+            0x070e_07c1,
+            0x0001_0000,
+        ] {
+            assert_eq!(queue.push(tx(id), TxPriority::Arbitration), None);
+        }
+        assert_eq!(queue.push(tx(0x1fff_ffff), TxPriority::Urgent), None);
+
+        // room for two frames at a time
+        let mut room = 2;
+        let mut send = |_: &ReduxFIFOMessage| match room {
+            0 => Err(Error::BusBufferFull),
+            _ => {
+                room -= 1;
+                Ok(())
+            }
+        };
+        assert_eq!(queue.waiting(TxPriority::Urgent), 1);
+        let drained = queue.drain(&mut send);
+        assert_eq!(ids(&drained.sent), [0x1fff_ffff, 0x0001_0000]);
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.waiting(TxPriority::Urgent), 0);
+        assert_eq!(queue.waiting(TxPriority::Arbitration), 4);
+
+        // a more important write made while the rest wait still goes first
+        queue.push(tx(0x0101_1840), TxPriority::Arbitration);
+        assert!(queue.last_waiting());
+        let drained = queue.drain(|_| Ok(()));
+        assert_eq!(
+            ids(&drained.sent),
+            [
+                0x0101_1840,
+                0x0101_1840,
+                0x070e_0781,
+                0x070e_07c1,
+                0x070e_07c1
+            ]
+        );
+        assert!(queue.is_empty());
+        assert!(!queue.last_waiting());
+    }
+
+    #[test]
+    fn test_full_queue_pushes_out_least_important() {
+        let mut queue = TxQueue::new(2);
+        queue.push(tx(0x0200_0000), TxPriority::Arbitration);
+        queue.push(tx(0x0300_0000), TxPriority::Arbitration);
+        let pushed_out = queue.push(tx(0x0100_0000), TxPriority::Arbitration);
+        assert_eq!(pushed_out.map(|tx| tx.msg.message_id), Some(0x0300_0000));
+        let pushed_out = queue.push(tx(0x0400_0000), TxPriority::Arbitration);
+        assert_eq!(pushed_out.map(|tx| tx.msg.message_id), Some(0x0400_0000));
+        // urgent writes push out ordinary ones however low their id
+        let pushed_out = queue.push(tx(0x1fff_ffff), TxPriority::Urgent);
+        assert_eq!(pushed_out.map(|tx| tx.msg.message_id), Some(0x0200_0000));
+
+        let drained = queue.drain(|msg| match msg.message_id {
+            0x1fff_ffff => Err(Error::DataTooLong),
+            _ => Ok(()),
+        });
+        assert_eq!(ids(&drained.sent), [0x0100_0000]);
+        assert_eq!(drained.failed.len(), 1);
+        assert!(TxQueue::new(0).push(tx(0), TxPriority::Urgent).is_some());
+    }
+}
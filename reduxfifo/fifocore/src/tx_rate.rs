@@ -0,0 +1,113 @@
+//! Process-wide cap on how fast each bus may be written to.
+//!
+//! By default writes go out as fast as the backend takes them. With a [`TxRateLimit`] set, each
+//! bus gets a token bucket: `burst` frames can go out back to back, after which the bus is held to
+//! `frames_per_sec`. A write over the cap is refused with [`Error::BusBufferFull`] like one that
+//! found the backend full, so writers that already retry a full bus slow down without knowing why.
+//! Refused writes aren't queued, even on buses with a [transmit queue](crate::tx_queue).
+//!
+//! Writes made with [`TxPriority::Urgent`](crate::tx_queue::TxPriority::Urgent), like the global
+//! disable, are never held back, and don't use up the bucket.
+//!
+//! [`Error::BusBufferFull`]: crate::error::Error::BusBufferFull
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Bucket credit one frame costs. Credit accrues at `frames_per_sec` per microsecond, so a frame
+/// costs a million of them.
+const FRAME_CREDIT: u64 = 1_000_000;
+
+/// How fast each bus may be written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TxRateLimit {
+    /// Sustained frames per second
+    pub frames_per_sec: u32,
+    /// Frames that can go out back to back after the bus has been quiet
+    pub burst: u32,
+}
+
+/// Writes refused for going over the limit, across every bus since startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct TxRateStats {
+    pub limit: Option<TxRateLimit>,
+    pub limited: u64,
+}
+
+static LIMIT: parking_lot::RwLock<Option<TxRateLimit>> = parking_lot::const_rwlock(None);
+static LIMITED: AtomicU64 = AtomicU64::new(0);
+
+/// Replaces the limit, or lifts it with `None`. Takes effect on the next write to each bus.
+pub fn set_tx_rate_limit(limit: Option<TxRateLimit>) {
+    match limit {
+        Some(limit) => crate::log_info!(
+            "Bus writes capped at {} frames/s, bursts of {}",
+            limit.frames_per_sec,
+            limit.burst
+        ),
+        None => crate::log_info!("Bus write cap lifted"),
+    }
+    *LIMIT.write() = limit;
+}
+
+pub fn tx_rate_limit() -> Option<TxRateLimit> {
+    *LIMIT.read()
+}
+
+pub fn tx_rate_stats() -> TxRateStats {
+    TxRateStats {
+        limit: tx_rate_limit(),
+        limited: LIMITED.load(Ordering::Relaxed),
+    }
+}
+
+/// Token bucket of one bus.
+#[derive(Debug, Default)]
+pub(crate) struct TxRateBucket {
+    credit: u64,
+    last_us: u64,
+}
+
+impl TxRateBucket {
+    /// Takes one frame's worth of credit for a write at `now_us`. Returns false, and counts the
+    /// write as limited, if the bus is over `limit`.
+    pub(crate) fn admit(&mut self, limit: TxRateLimit, now_us: u64) -> bool {
+        let cap = limit.burst.max(1) as u64 * FRAME_CREDIT;
+        let elapsed = now_us.saturating_sub(self.last_us);
+        self.last_us = self.last_us.max(now_us);
+        self.credit = elapsed
+            .saturating_mul(limit.frames_per_sec as u64)
+            .saturating_add(self.credit)
+            .min(cap);
+        if self.credit < FRAME_CREDIT {
+            LIMITED.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        self.credit -= FRAME_CREDIT;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let limit = TxRateLimit {
+            frames_per_sec: 100,
+            burst: 3,
+        };
+        let mut bucket = TxRateBucket::default();
+        let t0 = 1_000_000;
+        assert!((0..3).all(|_| bucket.admit(limit, t0)));
+        assert!(!bucket.admit(limit, t0));
+        // a frame's worth accrues every 10 ms
+        assert!(!bucket.admit(limit, t0 + 9_999));
+        assert!(bucket.admit(limit, t0 + 10_000));
+        assert!(!bucket.admit(limit, t0 + 10_000));
+        // and no more than the burst after a long quiet
+        let later = t0 + 60_000_000;
+        assert!((0..3).all(|_| bucket.admit(limit, later)));
+        assert!(!bucket.admit(limit, later));
+    }
+}
@@ -0,0 +1,201 @@
+//! Estimated bus utilization.
+//!
+//! Nothing on the bus reports how busy it is, so it's worked out from the frames seen on it, both
+//! received and sent by this host: each frame's length in bits comes from its id and payload size
+//! plus the worst case of bit stuffing, and takes time on the wire according to the bus's bitrate.
+//! CAN FD frames that switch bitrate send their data phase at the faster data bitrate. Frames from
+//! other nodes that no backend passes up (error frames on most adapters, say) aren't counted, so
+//! this is a lower bound on a busy bus and a close one on a healthy bus.
+//!
+//! Bitrates default to the FRC standard of 1 Mbit/s, with a 5 Mbit/s data phase on FD buses, and
+//! can be set per bus with the `;bitrate=` and `;data_bitrate=` options. Utilization over the last
+//! second going above `;util_warn=` percent (default [`DEFAULT_WARN_PERCENT`], 0 to disable) logs
+//! a warning.
+
+use crate::ReduxFIFOMessage;
+
+pub const DEFAULT_BITRATE: u32 = 1_000_000;
+pub const DEFAULT_DATA_BITRATE: u32 = 5_000_000;
+pub const DEFAULT_WARN_PERCENT: u8 = 90;
+
+/// Width of one bucket of the rolling window, in microseconds.
+const BUCKET_US: u64 = 100_000;
+/// Buckets in the rolling window, which makes it one second.
+const BUCKETS: usize = 10;
+/// Once warned, utilization has to drop this far below the threshold to warn again.
+const WARN_HYSTERESIS_PERCENT: f32 = 5.0;
+
+/// Valid CAN FD payload sizes; others are padded up to the next one on the wire.
+const FD_SIZES: [u32; 7] = [12, 16, 20, 24, 32, 48, 64];
+
+/// Bits a frame takes on the wire, as (at the nominal bitrate, at the data bitrate).
+pub fn frame_bits(msg: &ReduxFIFOMessage, fd_bus: bool) -> (u32, u32) {
+    let extended = !msg.short_id();
+    let len = msg.data_size.min(64) as u32;
+    if !fd_bus || (msg.no_fd() && len <= 8) {
+        // Tindell's worst case: g header bits that can be stuffed, 13 that can't
+        let data = if msg.rtr() { 0 } else { len.min(8) * 8 };
+        let g = if extended { 54 } else { 34 };
+        return (g + 13 + data + (g + data - 1) / 4, 0);
+    }
+
+    let len = match len {
+        0..=8 => len,
+        _ => FD_SIZES.into_iter().find(|size| *size >= len).unwrap_or(64),
+    };
+    // SOF, id, control bits up to and including BRS
+    let arbitration = if extended { 36 } else { 17 };
+    // ESI, DLC, data, then the stuff count and the CRC with their fixed stuff bits
+    let crc = if len <= 16 { 17 } else { 21 };
+    let payload = 1 + 4 + len * 8;
+    let data_phase = payload + payload / 4 + 4 + crc + (4 + crc).div_ceil(4);
+    // CRC delimiter, ACK, EOF and intermission
+    let nominal = arbitration + arbitration / 4 + 13;
+    if msg.no_brs() {
+        (nominal + data_phase, 0)
+    } else {
+        (nominal, data_phase)
+    }
+}
+
+/// How busy a bus has been.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct UtilizationStatus {
+    pub bitrate: u32,
+    /// Data phase bitrate, on FD buses
+    pub data_bitrate: Option<u32>,
+    /// Estimated share of the last second the bus was busy
+    pub percent: f32,
+    /// Highest `percent` seen since the bus was opened
+    pub peak_percent: f32,
+    /// Frames seen over the last second
+    pub frames_per_sec: u32,
+    /// Utilization that logs a warning; 0 if disabled
+    pub warn_percent: u8,
+    /// Times utilization went above `warn_percent`
+    pub warnings: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    busy_ns: u64,
+    frames: u32,
+}
+
+/// Rolling utilization of one bus.
+#[derive(Debug, Clone)]
+pub struct UtilizationTracker {
+    bus_id: u16,
+    bitrate: u32,
+    data_bitrate: u32,
+    fd: bool,
+    warn_percent: u8,
+    buckets: [Bucket; BUCKETS],
+    /// Index of the bucket `now` falls in, counted from the timebase's zero
+    current: u64,
+    /// When the first frame was seen
+    started: Option<u64>,
+    peak_percent: f32,
+    warned: bool,
+    warnings: u64,
+}
+
+impl UtilizationTracker {
+    pub fn new(bus_id: u16, bitrate: u32, data_bitrate: u32, warn_percent: u8) -> Self {
+        Self {
+            bus_id,
+            bitrate: bitrate.max(1),
+            data_bitrate: data_bitrate.max(1),
+            fd: false,
+            warn_percent,
+            buckets: [Bucket::default(); BUCKETS],
+            current: 0,
+            started: None,
+            peak_percent: 0.0,
+            warned: false,
+            warnings: 0,
+        }
+    }
+
+    /// Whether the bus carries FD frames, which is only known once the backend is open.
+    pub fn set_fd(&mut self, fd: bool) {
+        self.fd = fd;
+    }
+
+    /// Nominal bitrate of the bus, for backends that only find it once open.
+    pub fn set_bitrate(&mut self, bitrate: u32) {
+        self.bitrate = bitrate.max(1);
+    }
+
+    /// Counts a frame seen on the bus at `now`.
+    pub fn record(&mut self, msg: &ReduxFIFOMessage, now: u64) {
+        self.advance(now);
+        self.started.get_or_insert(now);
+        let (nominal, data) = frame_bits(msg, self.fd);
+        let busy_ns = nominal as u64 * 1_000_000_000 / self.bitrate as u64
+            + data as u64 * 1_000_000_000 / self.data_bitrate as u64;
+        let bucket = &mut self.buckets[(self.current % BUCKETS as u64) as usize];
+        bucket.busy_ns += busy_ns;
+        bucket.frames += 1;
+    }
+
+    /// Moves the window up to `now`, checking the warning threshold each time a bucket fills.
+    fn advance(&mut self, now: u64) {
+        let index = now / BUCKET_US;
+        if index <= self.current {
+            return;
+        }
+        let stale = (index - self.current).min(BUCKETS as u64);
+        for i in 1..=stale {
+            self.buckets[((self.current + i) % BUCKETS as u64) as usize] = Bucket::default();
+        }
+        self.current = index;
+        if self.started.is_none() {
+            return;
+        }
+
+        let percent = self.percent(index * BUCKET_US);
+        self.peak_percent = self.peak_percent.max(percent);
+        if self.warn_percent == 0 {
+            return;
+        }
+        let warn = self.warn_percent as f32;
+        if !self.warned && percent >= warn {
+            self.warned = true;
+            self.warnings += 1;
+            crate::log_warn!(
+                "bus {}: CAN bus utilization at {percent:.0}% (warning at {}%); \
+                 devices may miss frames or see delayed ones",
+                self.bus_id,
+                self.warn_percent
+            );
+        } else if self.warned && percent < warn - WARN_HYSTERESIS_PERCENT {
+            self.warned = false;
+        }
+    }
+
+    /// Busy share of the window ending at `now`, which is shorter until a full window was seen.
+    fn percent(&self, now: u64) -> f32 {
+        let Some(started) = self.started else {
+            return 0.0;
+        };
+        let span_us = ((BUCKETS as u64 - 1) * BUCKET_US + now % BUCKET_US)
+            .min(now.saturating_sub(started))
+            .max(1);
+        let busy_ns: u64 = self.buckets.iter().map(|b| b.busy_ns).sum();
+        (busy_ns as f32 / (span_us as f32 * 1000.0) * 100.0).min(100.0)
+    }
+
+    pub fn status(&mut self, now: u64) -> UtilizationStatus {
+        self.advance(now);
+        UtilizationStatus {
+            bitrate: self.bitrate,
+            data_bitrate: self.fd.then_some(self.data_bitrate),
+            percent: self.percent(now),
+            peak_percent: self.peak_percent,
+            frames_per_sec: self.buckets.iter().map(|b| b.frames).sum(),
+            warn_percent: self.warn_percent,
+            warnings: self.warnings,
+        }
+    }
+}
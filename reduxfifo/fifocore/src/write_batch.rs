@@ -0,0 +1,105 @@
+//! Coalescing of queued writes into one transfer.
+//!
+//! Backends that talk to a serial port or a USB endpoint pay for every write they make: a syscall
+//! for slcan, a submitted transfer for RdxUSB. Sending one frame per write, that overhead dominates
+//! at high command rates, so those backends take every frame waiting in their transmit queue when
+//! they wake up, up to [`MAX_WRITE_BATCH`], serialize them back to back and send them in one
+//! write. Frames still go out in the order they were written.
+//!
+//! [`write_batch_stats`] counts the writes made against the frames they carried, so
+//! `frames / writes` is how many per-frame writes coalescing saved.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::sync::mpsc::{Receiver, error::TryRecvError};
+
+/// Most frames a backend sends in one write.
+pub const MAX_WRITE_BATCH: usize = 32;
+
+/// Coalesced write totals across every bus since startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct WriteBatchStats {
+    /// Writes made, i.e. syscalls or USB transfers
+    pub writes: u64,
+    /// Frames those writes carried
+    pub frames: u64,
+    /// Bytes those writes carried, as serialized for the adapter
+    pub bytes: u64,
+    /// Most frames carried by one write
+    pub max_frames: u64,
+}
+
+struct WriteBatchCounters {
+    writes: AtomicU64,
+    frames: AtomicU64,
+    bytes: AtomicU64,
+    max_frames: AtomicU64,
+}
+
+static COUNTERS: WriteBatchCounters = WriteBatchCounters {
+    writes: AtomicU64::new(0),
+    frames: AtomicU64::new(0),
+    bytes: AtomicU64::new(0),
+    max_frames: AtomicU64::new(0),
+};
+
+/// Notes one write carrying `frames` frames in `bytes` bytes.
+pub(crate) fn record_write(frames: usize, bytes: usize) {
+    COUNTERS.writes.fetch_add(1, Ordering::Relaxed);
+    COUNTERS.frames.fetch_add(frames as u64, Ordering::Relaxed);
+    COUNTERS.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    COUNTERS
+        .max_frames
+        .fetch_max(frames as u64, Ordering::Relaxed);
+}
+
+pub fn write_batch_stats() -> WriteBatchStats {
+    WriteBatchStats {
+        writes: COUNTERS.writes.load(Ordering::Relaxed),
+        frames: COUNTERS.frames.load(Ordering::Relaxed),
+        bytes: COUNTERS.bytes.load(Ordering::Relaxed),
+        max_frames: COUNTERS.max_frames.load(Ordering::Relaxed),
+    }
+}
+
+/// Collects `first` and whatever else is already queued behind it into `batch`, up to
+/// [`MAX_WRITE_BATCH`], without waiting. Returns false if the queue has closed, after which
+/// `batch` should still be sent.
+pub(crate) fn drain_queue<T>(queue: &mut Receiver<T>, first: T, batch: &mut Vec<T>) -> bool {
+    batch.push(first);
+    while batch.len() < MAX_WRITE_BATCH {
+        match queue.try_recv() {
+            Ok(next) => batch.push(next),
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drain_queue() {
+        let (send, mut recv) = tokio::sync::mpsc::channel(128);
+        for i in 0..MAX_WRITE_BATCH + 3 {
+            send.try_send(i).unwrap();
+        }
+        let mut batch = Vec::new();
+        let first = recv.try_recv().unwrap();
+        assert!(drain_queue(&mut recv, first, &mut batch));
+        assert_eq!(batch, (0..MAX_WRITE_BATCH).collect::<Vec<_>>());
+
+        // the rest go in the next batch, and a closed queue still gives up what it had
+        batch.clear();
+        drop(send);
+        let first = recv.try_recv().unwrap();
+        assert!(!drain_queue(&mut recv, first, &mut batch));
+        assert_eq!(
+            batch,
+            [MAX_WRITE_BATCH, MAX_WRITE_BATCH + 1, MAX_WRITE_BATCH + 2]
+        );
+    }
+}
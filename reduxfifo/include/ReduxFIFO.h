@@ -51,7 +51,7 @@ struct __attribute__((packed, aligned(4))) ReduxFIFO_Message
 {
     uint32_t message_id; // full 32-bit message id
     uint16_t bus_id; // index of the message bus the message is pulled from.
-    uint8_t pad; // pad byte (reserved)
+    uint8_t pad; // message flags (REDUXFIFO_FLAG_*)
     uint8_t data_size; // length of the data (0-64)
     uint64_t timestamp; // 64-bit timestamp relative to the FPGA clock (microseconds)
     uint8_t data[64]; // CAN packet data
@@ -60,8 +60,23 @@ struct __attribute__((packed, aligned(4))) ReduxFIFO_Message
 #pragma pack(pop)
 #endif
 
+/** Message should not use / was received without bit rate switching */
+#define REDUXFIFO_FLAG_NO_BRS 0x01
+/** Message should not be sent / was not received as a CAN-FD frame */
+#define REDUXFIFO_FLAG_NO_FD  0x02
+/** Message is directly addressed to an RdxUSB device */
+#define REDUXFIFO_FLAG_DEV    0x04
+/** Message was transmitted by this host; only seen in sessions opened with echo_tx */
+#define REDUXFIFO_FLAG_TX     0x08
+/** Message arrived while no session was open and was retained for this one; see retain_ms */
+#define REDUXFIFO_FLAG_HISTORICAL 0x20
+/** CAN-FD message has the error state indicator set (the sender is error passive) */
+#define REDUXFIFO_FLAG_ESI    0x40
+
 typedef uint64_t ReduxFIFO_Session;
 typedef int32_t ReduxFIFO_Status;
+/** Opaque token for cancelling a wait from another thread */
+typedef struct ReduxFIFO_CancelToken ReduxFIFO_CancelToken;
 
 /**
  * This represents a FIFO buffer, specifically the metadata half.
@@ -143,6 +158,13 @@ struct __attribute__((packed, aligned(4))) ReduxFIFO_WriteBufferMeta
      * Write barriers use this to determine how many messages to write.
      */
     uint32_t length;
+    /**
+     * [output] On buses opened with the `;confirm_tx` option, the sequence number of the first message written.
+     * The rest follow consecutively. Pass these to ReduxFIFO_GetTxTimestamp.
+     * 
+     * Zero if the bus doesn't track on-wire confirmations.
+     */
+    uint32_t tx_seq;
 };
 #ifdef _MSC_VER
 #pragma pack(pop)
@@ -160,18 +182,63 @@ struct __attribute__((packed, aligned(4))) ReduxFIFO_SessionConfig
     uint32_t filter_id;
     /** The filter mask to AND incoming messages with */
     uint32_t filter_mask;
-
+    /** Nonzero to also receive messages this host transmits, flagged with REDUXFIFO_FLAG_TX */
+    uint8_t echo_tx;
+    uint8_t reserved[3];
+    /**
+     * Nonzero to keep collecting this session's messages for this many milliseconds after it closes.
+     * The next session opened on the bus with the same filter, echo_tx and a nonzero retain_ms
+     * receives them first, flagged with REDUXFIFO_FLAG_HISTORICAL.
+     */
+    uint32_t retain_ms;
 };
 #ifdef _MSC_VER
 #pragma pack(pop)
 #endif
 
 
+/**
+ * Start of a shared-memory ring from ReduxFIFO_AttachRing. The ring's `capacity` message slots of
+ * `slot_size` bytes each (a ReduxFIFO_Message) follow at `header_size` bytes from the start.
+ *
+ * ReduxFIFO writes messages at `head` and the reader frees them by advancing `tail`; both only
+ * ever count up. Messages `tail` up to `head` are waiting, in slot `index & (capacity - 1)`. To read:
+ * load `head` with acquire ordering (`__atomic_load_n(&ring->head, __ATOMIC_ACQUIRE)`), read the
+ * waiting slots, then store `head` to `tail` with release ordering. Only one thread may read a
+ * ring. When the ring is full new messages are dropped and counted in `dropped`.
+ */
+struct ReduxFIFO_RingHeader
+{
+    /** "RDXR" */
+    uint32_t magic;
+    /** 1 */
+    uint16_t version;
+    /** Offset of the first slot from the start of the header */
+    uint16_t header_size;
+    /** Number of slots, a power of two */
+    uint32_t capacity;
+    /** Size of each slot */
+    uint32_t slot_size;
+    /** The session delivered to the ring */
+    ReduxFIFO_Session session;
+    /** [atomic] Messages written by ReduxFIFO */
+    uint64_t head;
+    /** [atomic] Messages read; only the reader stores this */
+    uint64_t tail;
+    /** [atomic] Messages dropped because the ring was full */
+    uint64_t dropped;
+    /** [atomic] Nonzero once the ring is detached or its session closed; nothing more is written */
+    uint32_t closed;
+    uint32_t reserved;
+};
+
+
 #define REDUXFIFO_OK                            0
 #define REDUXFIFO_ERR_UNKNOWN                  -1
 #define REDUXFIFO_ERR_NOT_INITIALIZED          -2
 #define REDUXFIFO_ERR_NULL_POINTER_ARGUMENT    -3
 #define REDUXFIFO_ERR_JAVA_INVALID_BYTEBUFFER  -4
+#define REDUXFIFO_ERR_NOT_ACKNOWLEDGED         -5
 
 #define REDUXFIFO_ERR_INVALID_BUS              -100
 #define REDUXFIFO_ERR_BUS_ALREADY_OPENED       -101
@@ -182,14 +249,23 @@ struct __attribute__((packed, aligned(4))) ReduxFIFO_SessionConfig
 #define REDUXFIFO_ERR_BUS_READ_FAIL            -106
 #define REDUXFIFO_ERR_BUS_WRITE_FAIL           -107
 #define REDUXFIFO_ERR_BUS_BUFFER_FULL          -108
+#define REDUXFIFO_ERR_TX_PENDING               -110
+#define REDUXFIFO_ERR_TX_UNCONFIRMED           -111
+#define REDUXFIFO_ERR_ROBORIO_PRESENT          -112
 
 #define REDUXFIFO_ERR_INVALID_SESSION_ID         -200
 #define REDUXFIFO_ERR_SESSION_ALREADY_OPENED     -201
 #define REDUXFIFO_ERR_MAX_SESSIONS_OPENED        -202
 #define REDUXFIFO_ERR_SESSION_CLSOED             -203
 #define REDUXFIFO_ERR_MESSAGE_RECEIVE_TIMEOUT    -204
+#define REDUXFIFO_ERR_MEMORY_LIMIT_REACHED       -205
+#define REDUXFIFO_ERR_INVALID_LABEL              -206
+#define REDUXFIFO_ERR_CANCELLED                  -207
 
 #define REDUXFIFO_ERR_HAL_CAN_OPEN_SESSION_FAIL  -301
+#define REDUXFIFO_ERR_USB_CLOSED                -302
+#define REDUXFIFO_ERR_USB_DEVICE_NOT_FOUND      -303
+#define REDUXFIFO_ERR_USB_ENUMERATE_FAIL        -304
 
 
 /**
@@ -204,6 +280,7 @@ const char* ReduxFIFO_ErrorMessage(const ReduxFIFO_Status status);
  * Starts the ReduxFIFO driver. 
  * 
  * This is idempotent and will do nothing if called multiple times.
+ * Builds without the legacy driver have no server and return REDUXFIFO_NOT_INITIALIZED.
  * @return status
  */
 ReduxFIFO_Status ReduxFIFO_StartServer();
@@ -220,11 +297,39 @@ ReduxFIFO_Status ReduxFIFO_StopServer();
  */
 uint32_t ReduxFIFO_GetVersion();
 
+#define REDUXFIFO_CAP_HALCAN           (1 << 0)
+#define REDUXFIFO_CAP_SOCKETCAN        (1 << 1)
+#define REDUXFIFO_CAP_RDXUSB           (1 << 2)
+#define REDUXFIFO_CAP_WEBSOCKET_LEGACY (1 << 3)
+#define REDUXFIFO_CAP_WEBSOCKET        (1 << 4)
+#define REDUXFIFO_CAP_SLCAN            (1 << 5)
+#define REDUXFIFO_CAP_SIM              (1 << 6)
+#define REDUXFIFO_CAP_REPLAY           (1 << 7)
+
+/**
+ * Returns which bus backends can be opened in this process.
+ *
+ * halcan is only reported if the WPILib HAL is actually loaded, so desktop tools using the same
+ * library as robot code can check this instead of trying to open "halcan" and failing.
+ * Opening a backend whose bit is clear returns REDUXFIFO_ERR_BUS_NOT_SUPPORTED.
+ *
+ * @return bitmask of REDUXFIFO_CAP_* values
+ */
+uint32_t ReduxFIFO_GetCapabilities();
+
 /**
  * Opens a bus or returns a bus ID if a matching "bus address" already exists.
  *
  * bus address (e.g. "halcan" or "socketcan[.fd]:can0" or "gs_usb:16d0.1277/[serial numer]" or "slcan:/dev/ttyUSB0")
+ * "sim:[name]" opens a virtual bus that loops every written message back to all of its sessions
+ * "replay:[path]" plays back a log recorded with ReduxCore_OpenLog as a bus, with its original timing;
+ * ";speed=N" after it plays N times as fast and ";loop" starts over at the end
  * multiple bus addresses may be passed in with commas delimiting them
+ * ";isolated" after an address runs that bus on a dedicated thread instead of the shared worker pool
+ * ";confirm_tx" after an address tracks when writes go out on the wire (see ReduxFIFO_GetTxTimestamp)
+ * an alias registered with ReduxFIFO_SetBusAlias may be passed in place of an address
+ * a malformed address returns REDUXFIFO_ERR_INVALID_BUS and logs the format that was expected;
+ * an slcan serial port or replayed log that doesn't exist returns REDUXFIFO_ERR_FAILED_TO_OPEN_BUS
  *
  * other backends may be added depending on how we feel that day
  * 
@@ -236,6 +341,27 @@ uint32_t ReduxFIFO_GetVersion();
  */
 ReduxFIFO_Status ReduxFIFO_OpenBus(const char* bus_address, uint16_t* bus_id);
 
+/**
+ * Registers a human-readable alias (e.g. "drive") for a bus address.
+ * 
+ * The alias can then be used wherever a bus address is accepted, including the REST API's bus paths.
+ * It names the address rather than a bus ID, so it survives the bus being closed and reopened.
+ * Registering an existing alias again repoints it.
+ * 
+ * @param[in] alias letters, digits, '-' and '_'; not all digits. This MUST be valid UTF-8.
+ * @param[in] bus_address the bus address it stands for, as passed to ReduxFIFO_OpenBus.
+ * @return status: REDUXFIFO_ERR_INVALID_BUS if the alias or address is malformed.
+ */
+ReduxFIFO_Status ReduxFIFO_SetBusAlias(const char* alias, const char* bus_address);
+
+/**
+ * Removes a bus alias. Buses opened through it stay open.
+ * 
+ * @param[in] alias alias to remove
+ * @return status: REDUXFIFO_ERR_INVALID_BUS if there was no such alias.
+ */
+ReduxFIFO_Status ReduxFIFO_RemoveBusAlias(const char* alias);
+
 /**
  * Close a bus.
  * 
@@ -272,6 +398,16 @@ ReduxFIFO_Status ReduxFIFO_OpenSession(
  */
 ReduxFIFO_Status ReduxFIFO_CloseSession(ReduxFIFO_Session ses);
 
+/**
+ * Labels a session with the software it belongs to (e.g. "vendordep"), so that messages written with
+ * ReduxFIFO_WriteSingleAs are attributed to it in bus logs. Labeling a session again replaces its label.
+ * 
+ * @param[in] ses the session handle
+ * @param[in] label 1 to 32 printable ASCII characters, without spaces.
+ * @return status: REDUXFIFO_ERR_INVALID_LABEL if the label is malformed.
+ */
+ReduxFIFO_Status ReduxFIFO_SetSessionLabel(ReduxFIFO_Session ses, const char* label);
+
 /**
  * Read buffer pointer struct.
  */
@@ -346,6 +482,61 @@ ReduxFIFO_Status ReduxFIFO_ReadBarrierMultiBus(
     size_t buffer_count
 );
 
+/**
+ * Takes a session's messages timestamped from t0_us to t1_us inclusive, oldest first, in FPGA time
+ * on a robot and monotonic time elsewhere. Messages from before the window are discarded and later
+ * ones stay waiting, so reading consecutive windows sees each message once. If the window holds
+ * more than capacity messages, the newest of them also stay waiting for the next call.
+ *
+ * This reads from the session's current buffer directly; don't mix it with read barriers on the
+ * same session.
+ *
+ * @param[in] ses session handle
+ * @param[in] t0_us start of the window
+ * @param[in] t1_us end of the window; a window ending before it starts takes nothing
+ * @param[out] messages array of capacity messages to take them into
+ * @param[in] capacity the length of messages
+ * @param[out] count how many messages were taken. may be set to NULL.
+ * @return status
+ */
+ReduxFIFO_Status ReduxFIFO_ReadWindow(
+    ReduxFIFO_Session ses,
+    uint64_t t0_us,
+    uint64_t t1_us,
+    struct ReduxFIFO_Message* messages,
+    uint32_t capacity,
+    uint32_t* count
+);
+
+/**
+ * Delivers a session's messages to a shared-memory ring instead of to read barriers, so they can be
+ * read in place without calling into ReduxFIFO; see ReduxFIFO_RingHeader. Messages already waiting
+ * for the session move to the ring, and a ring already attached to it is closed.
+ *
+ * ReduxFIFO_WaitForThreshold still works on the session, counting the messages in the ring as of
+ * the last one delivered.
+ *
+ * Only available in builds with the `shm-ring` feature.
+ *
+ * @param[in] session the session
+ * @param[in] capacity slots in the ring, rounded up to a power of two; at most 65536
+ * @param[out] ring the ring, mapped until ReduxFIFO_DetachRing
+ * @return status
+ */
+ReduxFIFO_Status ReduxFIFO_AttachRing(ReduxFIFO_Session session, uint32_t capacity, struct ReduxFIFO_RingHeader** ring);
+
+/**
+ * Closes and unmaps a session's ring. The ring must not be touched afterwards. The session, if still
+ * open, goes back to delivering to read barriers. Rings stay mapped after their session closes
+ * until this is called.
+ *
+ * Only available in builds with the `shm-ring` feature.
+ *
+ * @param[in] session the session
+ * @return status
+ */
+ReduxFIFO_Status ReduxFIFO_DetachRing(ReduxFIFO_Session session);
+
 /**
  * Serves as a write barrier; this queues messages to be sent out and immidiately returns.
  * 
@@ -368,6 +559,90 @@ ReduxFIFO_Status ReduxFIFO_WriteBarrier(
  */
 ReduxFIFO_Status ReduxFIFO_WriteSingle(ReduxFIFO_Message* msg);
 
+/**
+ * Writes a single message like ReduxFIFO_WriteSingle, attributed in bus logs to the label of a session on the
+ * message's bus.
+ * 
+ * @param[in] ses the session writing the message; see ReduxFIFO_SetSessionLabel
+ * @param[in] msg the message to write
+ * @return status: REDUXFIFO_ERR_INVALID_SESSION_ID if the session isn't open on the message's bus.
+ */
+ReduxFIFO_Status ReduxFIFO_WriteSingleAs(ReduxFIFO_Session ses, ReduxFIFO_Message* msg);
+
+/**
+ * Writes a single message like ReduxFIFO_WriteSingle, and returns the sequence number to look its on-wire timestamp
+ * up with.
+ * 
+ * @param[in] msg the message to write
+ * @param[out] tx_seq sequence number of the write; zero if the bus doesn't track on-wire confirmations.
+ * @return status
+ */
+ReduxFIFO_Status ReduxFIFO_WriteSingleTracked(ReduxFIFO_Message* msg, uint32_t* tx_seq);
+
+/**
+ * Gets when a write actually went out on the wire, on buses opened with the `;confirm_tx` option.
+ * 
+ * Waits up to timeout_ms for the confirmation. Writes that aren't confirmed within a second are given up on.
+ * 
+ * @param[in] bus_id the bus written to
+ * @param[in] tx_seq sequence number from ReduxFIFO_WriteSingleTracked or a write buffer's tx_seq
+ * @param[in] timeout_ms how long to wait. 0 returns immediately.
+ * @param[out] timestamp when the message went out, in the same time base as received messages.
+ * @return status: REDUXFIFO_ERR_TX_PENDING if not confirmed yet, REDUXFIFO_ERR_TX_UNCONFIRMED if it never will be.
+ */
+ReduxFIFO_Status ReduxFIFO_GetTxTimestamp(uint16_t bus_id, uint32_t tx_seq, uint64_t timeout_ms, uint64_t* timestamp);
+
+#define REDUXFIFO_BUS_STATE_UNKNOWN       0
+#define REDUXFIFO_BUS_STATE_ERROR_ACTIVE  1
+#define REDUXFIFO_BUS_STATE_ERROR_WARNING 2
+#define REDUXFIFO_BUS_STATE_ERROR_PASSIVE 3
+#define REDUXFIFO_BUS_STATE_BUS_OFF       4
+
+/**
+ * Health of a bus, from ReduxFIFO_GetBusStatus.
+ */
+#ifdef _MSC_VER
+#pragma pack(push, 4)
+struct ReduxFIFO_BusStatus
+#else
+struct __attribute__((packed, aligned(4))) ReduxFIFO_BusStatus
+#endif
+{
+    /** One of REDUXFIFO_BUS_STATE_*; unknown if the backend can't see the CAN controller */
+    uint8_t state;
+    uint8_t reserved;
+    /** Controller transmit error counter; 0xFFFF if not reported */
+    uint16_t tx_errors;
+    /** Controller receive error counter; 0xFFFF if not reported */
+    uint16_t rx_errors;
+    uint16_t reserved2;
+    /** Times the bus went error passive */
+    uint32_t error_passive_count;
+    /** Times the bus went bus off */
+    uint32_t bus_off_count;
+    /** Frames that couldn't be sent because a transmit buffer was full */
+    uint64_t tx_overruns;
+    /** Frames lost because a receive buffer was full */
+    uint64_t rx_overruns;
+    /** When state last changed, in the same time base as received messages */
+    uint64_t state_since;
+};
+#ifdef _MSC_VER
+#pragma pack(pop)
+#endif
+
+/**
+ * Gets a bus's CAN controller error state and how many frames it lost to full buffers, to warn
+ * about failing wiring. What's reported depends on the backend; see reduxfifo.md.
+ * 
+ * @param[in] bus_id the bus
+ * @param[in] timeout_ms if nonzero, first wait up to this long for the state, error counters or
+ *            overrun counts to differ from what's in status. 0 returns immediately.
+ * @param[in,out] status the bus's status
+ * @return status
+ */
+ReduxFIFO_Status ReduxFIFO_GetBusStatus(uint16_t bus_id, uint64_t timeout_ms, struct ReduxFIFO_BusStatus* status);
+
 /**
  * 
  * @param[in] session handle
@@ -378,6 +653,172 @@ ReduxFIFO_Status ReduxFIFO_WriteSingle(ReduxFIFO_Message* msg);
  */
 ReduxFIFO_Status ReduxFIFO_WaitForThreshold(ReduxFIFO_Session session, uint32_t threshold, uint64_t timeout_ms, uint32_t* messages);
 
+/**
+ * Makes a token for cancelling ReduxFIFO_WaitForThresholdCancellable from another thread.
+ *
+ * @return the token, to be freed with ReduxFIFO_FreeCancelToken
+ */
+ReduxFIFO_CancelToken* ReduxFIFO_NewCancelToken(void);
+
+/**
+ * Cancels every wait using the token, now and from now on. Waits return promptly with
+ * REDUXFIFO_ERR_CANCELLED.
+ *
+ * @param[in] token the token
+ * @return status
+ */
+ReduxFIFO_Status ReduxFIFO_Cancel(const ReduxFIFO_CancelToken* token);
+
+/**
+ * Frees a token. No wait may still be using it.
+ *
+ * @param[in] token the token; may be NULL
+ */
+void ReduxFIFO_FreeCancelToken(ReduxFIFO_CancelToken* token);
+
+/**
+ * ReduxFIFO_WaitForThreshold that can be cancelled from another thread with ReduxFIFO_Cancel.
+ *
+ * @param[in] session handle
+ * @param[in] threshold message count threshold
+ * @param[in] timeout_ms timeout in ms. 0 returns immediately.
+ * @param[in] token the token to cancel the wait with
+ * @param[in] messages the number of messages when threshold reached. may be set to NULL. only valid if the return status is 0.
+ * @return status: REDUXFIFO_ERR_CANCELLED if the token was cancelled first.
+ */
+ReduxFIFO_Status ReduxFIFO_WaitForThresholdCancellable(ReduxFIFO_Session session, uint32_t threshold, uint64_t timeout_ms, const ReduxFIFO_CancelToken* token, uint32_t* messages);
+
+/**
+ * Outcome of ReduxFIFO_GlobalDisable on one bus.
+ */
+#ifdef _MSC_VER
+#pragma pack(push, 4)
+struct ReduxFIFO_GlobalDisableResult
+#else
+struct __attribute__((packed, aligned(4))) ReduxFIFO_GlobalDisableResult
+#endif
+{
+    uint16_t bus_id;
+    /** Nonzero if the disable left the transmit queue and nothing re-enabled the actuators while
+     * the bus was watched */
+    uint8_t confirmed;
+    uint8_t reserved;
+    /** Whether the disable could be sent on this bus */
+    ReduxFIFO_Status status;
+    /** Enabled roboRIO heartbeats and SPARK non-roboRIO heartbeats seen after the disable */
+    uint32_t enabling_frames;
+    /** Motor, relay, and pneumatics controllers heard from after the disable */
+    uint32_t actuators_heard;
+    /** When the disable went out on the wire; 0 unless the bus was opened with ";confirm_tx" */
+    uint64_t tx_timestamp;
+};
+#ifdef _MSC_VER
+#pragma pack(pop)
+#endif
+
+/**
+ * Emergency stop for bench setups: sends the FRC global disable frame on each bus, then watches
+ * the buses to check that nothing (an enabled roboRIO, a desktop tool driving SPARKs) turns the
+ * actuators back on. Actuator output itself can't be observed, so this only confirms the disable
+ * wasn't overridden. Blocks for the whole window.
+ * 
+ * @param[in] bus_ids buses to disable
+ * @param[in] bus_count number of entries in bus_ids
+ * @param[in] window_ms how long to watch the buses; 0 uses the default of 250 ms
+ * @param[out] results bus_count results, one per bus. may be set to NULL.
+ * @return status of the first bus the disable couldn't be sent on, or REDUXFIFO_OK
+ */
+ReduxFIFO_Status ReduxFIFO_GlobalDisable(
+    const uint16_t* bus_ids,
+    size_t bus_count,
+    uint32_t window_ms,
+    struct ReduxFIFO_GlobalDisableResult* results
+);
+
+/**
+ * Turns latency histograms on or off. They're off by default.
+ *
+ * While on, ReduxFIFO_ReadBarrier, ReduxFIFO_ReadBarrierMultiBus and the ReduxCore receive calls
+ * record how long each message they hand over took from its receive timestamp, per frame id with
+ * the device number cleared. Turning them off keeps what was recorded.
+ *
+ * @param[in] enabled nonzero to record
+ */
+void ReduxFIFO_SetLatencyHistograms(uint8_t enabled);
+
+/**
+ * Logs a table of the latency histograms: count, p50, p90, p99, p99.9 and max per frame id.
+ * The REST API's /latency returns the same as JSON.
+ */
+void ReduxFIFO_DumpLatencyHistograms();
+
+/**
+ * Clears the latency histograms.
+ */
+void ReduxFIFO_ResetLatencyHistograms();
+
+/** acknowledge value for ReduxFIFO_StartBenchHeartbeat, "BNCH" in ASCII */
+#define REDUXFIFO_BENCH_HEARTBEAT_ACK 0x424E4348
+
+/**
+ * Starts sending a stand-in roboRIO heartbeat on a bus, for running devices on a bench with no
+ * roboRIO. ENABLING IT ENABLES EVERY ACTUATOR ON THE BUS. It starts out disabled; enable it with
+ * ReduxFIFO_SetBenchHeartbeatEnabled and keep it enabled with ReduxFIFO_FeedBenchHeartbeat, or it
+ * disables itself once watchdog_ms passes without either. Blocks for 100 ms while listening for a
+ * roboRIO first, and stops on its own if one shows up later. Starting it again restarts it disabled.
+ *
+ * @param[in] bus_id bus to send it on
+ * @param[in] watchdog_ms how long enabling lasts without being fed
+ * @param[in] acknowledge must be REDUXFIFO_BENCH_HEARTBEAT_ACK
+ * @return status: REDUXFIFO_ERR_NOT_ACKNOWLEDGED if acknowledge is wrong,
+ *         REDUXFIFO_ERR_ROBORIO_PRESENT if a roboRIO heartbeat was heard
+ */
+ReduxFIFO_Status ReduxFIFO_StartBenchHeartbeat(uint16_t bus_id, uint32_t watchdog_ms, uint32_t acknowledge);
+
+/**
+ * Enables or disables actuators on a bus with a bench heartbeat. A disable is sent right away.
+ *
+ * @param[in] bus_id bus the heartbeat was started on
+ * @param[in] enabled nonzero to enable for the next watchdog timeout
+ * @return status: REDUXFIFO_ERR_INVALID_BUS if no heartbeat was started on the bus, or why it
+ *         stopped on its own (REDUXFIFO_ERR_ROBORIO_PRESENT or REDUXFIFO_ERR_BUS_CLOSED)
+ */
+ReduxFIFO_Status ReduxFIFO_SetBenchHeartbeatEnabled(uint16_t bus_id, uint8_t enabled);
+
+/**
+ * Keeps actuators on a bus with a bench heartbeat enabled for another watchdog timeout. Does
+ * nothing once the watchdog has run out; that takes enabling it again.
+ *
+ * @return status, as for ReduxFIFO_SetBenchHeartbeatEnabled
+ */
+ReduxFIFO_Status ReduxFIFO_FeedBenchHeartbeat(uint16_t bus_id);
+
+/**
+ * Stops the bench heartbeat on a bus. Devices disable themselves 100 ms later.
+ *
+ * @return status: REDUXFIFO_ERR_INVALID_BUS if no heartbeat was started on the bus
+ */
+ReduxFIFO_Status ReduxFIFO_StopBenchHeartbeat(uint16_t bus_id);
+
+/**
+ * Starts a tokio-console endpoint, for looking into ReduxFIFO's async tasks, moving it if it's
+ * already running. It only sees tasks spawned after it starts. The endpoint has no
+ * authentication, so keep it on loopback unless everyone who can reach it should see every task.
+ * Needs a build with the tokio-console feature.
+ *
+ * @param[in] addr address to listen on, e.g. "127.0.0.1:6669", or NULL for that
+ * @return 0 on success, -1 if this build can't run tokio-console, -2 on an invalid address, -3 if
+ *         the address couldn't be listened on
+ */
+int32_t ReduxFIFO_StartTokioConsole(const char* addr);
+
+/**
+ * Stops the tokio-console endpoint.
+ *
+ * @return 0 on success, -1 if it wasn't running
+ */
+int32_t ReduxFIFO_StopTokioConsole();
+
 #ifdef __cplusplus
 }  // extern "C"
 #endif
\ No newline at end of file
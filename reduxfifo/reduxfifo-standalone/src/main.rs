@@ -1,5 +1,22 @@
+use std::{future::Future, sync::Arc};
+
+use anyhow::Context;
+use canandmiddleware::{
+    bench::{Bench, BenchConfig},
+    diagnostics::{Diagnostics, DiagnosticsPolicy},
+    firmware_notes::FirmwareMetadata,
+    inventory::InventoryReport,
+    mirror::{MirrorConfig, Mirrors},
+    profile::Profiles,
+    rest_server::Services,
+    schedule::Schedule,
+    settings_file::SettingsFile,
+};
 use clap::Parser as _;
-use fifocore::FIFOCore;
+use fifocore::{FIFOCore, bus_uri::BusUri};
+
+#[cfg(windows)]
+mod service;
 
 #[derive(clap::Parser)]
 #[command(version, about, long_about = None)]
@@ -10,41 +27,369 @@ struct Cli {
         help = "args to pass through to Cargo"
     )]
     buses_to_open: Vec<String>,
+
+    #[arg(
+        long = "alias",
+        value_name = "NAME=BUS",
+        help = "name a bus so it can be referred to as NAME anywhere, e.g. drive=socketcan:can0"
+    )]
+    aliases: Vec<String>,
+
+    #[arg(
+        long = "mirror",
+        value_name = "BUS=GROUP:PORT",
+        help = "mirror a bus (opening it if needed) to a UDP multicast group, e.g. rdxusb:0=239.0.0.1:7245"
+    )]
+    mirrors: Vec<String>,
+
+    #[arg(
+        long = "inventory",
+        value_name = "PATH",
+        help = "write a firmware inventory of every device on the opened buses to PATH.json and PATH.txt"
+    )]
+    inventory: Option<std::path::PathBuf>,
+
+    #[arg(
+        long = "export-settings",
+        value_name = "PATH",
+        help = "write the settings of every device on the opened buses to a settings file at PATH"
+    )]
+    export_settings: Option<std::path::PathBuf>,
+
+    #[arg(
+        long = "import-settings",
+        value_name = "PATH",
+        help = "write the settings in a settings file to the devices with matching serial numers"
+    )]
+    import_settings: Option<std::path::PathBuf>,
+
+    #[arg(
+        long = "profiles",
+        value_name = "PATH",
+        help = "JSON list of default settings profiles to apply to factory-fresh devices"
+    )]
+    profiles: Option<std::path::PathBuf>,
+
+    #[arg(
+        long = "firmware-notes",
+        value_name = "PATH",
+        help = "JSON bundle of firmware release notes and known issues to show alongside device info"
+    )]
+    firmware_notes: Option<std::path::PathBuf>,
+
+    #[arg(
+        long = "firmware-index",
+        value_name = "PATH",
+        help = "JSON index of bundled firmware files, for reporting which devices are outdated"
+    )]
+    firmware_index: Option<std::path::PathBuf>,
+
+    #[arg(
+        long = "settings-migrations",
+        value_name = "PATH",
+        help = "JSON table of settings migrations for importing settings onto newer firmware"
+    )]
+    settings_migrations: Option<std::path::PathBuf>,
+
+    #[arg(
+        long = "schedule",
+        value_name = "PATH",
+        help = "JSON schedule of periodic log rotations and inventories, and webhooks to call when devices are lost"
+    )]
+    schedule: Option<std::path::PathBuf>,
+
+    #[arg(
+        long = "bounded-memory",
+        help = "cap session, client and log buffers for hosts with little RAM (e.g. 512 MB coprocessors)"
+    )]
+    bounded_memory: bool,
+
+    #[arg(
+        long = "bench",
+        help = "bench mode: a heartbeat clients can arm to enable actuators without a roboRIO, \
+                confirmations for every destructive operation, and capped bus writes"
+    )]
+    bench: bool,
+
+    #[arg(
+        long = "bench-auto-stop",
+        value_name = "MS",
+        requires = "bench",
+        help = "how long the bench heartbeat keeps going after it was last armed [default: 1000]"
+    )]
+    bench_auto_stop: Option<u64>,
+
+    #[arg(
+        long = "bench-tx-rate",
+        value_name = "FRAMES_PER_SEC",
+        requires = "bench",
+        help = "cap on writes to each bus in bench mode [default: 2000]"
+    )]
+    bench_tx_rate: Option<u32>,
+
+    #[arg(
+        long = "tokio-console",
+        value_name = "ADDR",
+        num_args = 0..=1,
+        default_missing_value = "127.0.0.1:6669",
+        help = "start a tokio-console endpoint on ADDR; needs the tokio-console feature [default: 127.0.0.1:6669]"
+    )]
+    tokio_console: Option<std::net::SocketAddr>,
+
+    #[arg(
+        long = "diagnostics-token",
+        value_name = "TOKEN",
+        help = "token REST clients have to pass to start or stop tokio-console, which also lets them \
+                bind it off loopback"
+    )]
+    diagnostics_token: Option<String>,
+
+    #[cfg(windows)]
+    #[arg(
+        long = "service",
+        help = "run as a Windows service, logging to the event log; only for the service control manager to pass"
+    )]
+    service: bool,
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::try_parse()?;
+    #[cfg(windows)]
+    if cli.service {
+        return service::run_service(cli);
+    }
     env_logger::init_from_env(
         env_logger::Env::new().default_filter_or("debug,jni=off,hyper=debug"),
     );
+    run(cli, || {}, async { wait_for_term().await.unwrap() })
+}
 
+/// Runs the server until `stop` completes, calling `started` once the buses are open and the
+/// command line's one-off jobs are done.
+fn run(cli: Cli, started: impl FnOnce(), stop: impl Future<Output = ()>) -> anyhow::Result<()> {
+    canandmiddleware::diagnostics::install();
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .thread_name("ReduxFIFO")
         .build()
         .expect("could not start ReduxFIFO");
 
+    if cli.bounded_memory {
+        fifocore::limits::set_memory_limits(fifocore::limits::MemoryLimits::coprocessor());
+    }
     let fifocore = FIFOCore::new(rt.handle().clone());
-    rt.block_on(async_main(fifocore, cli))
+    rt.block_on(async_main(fifocore, cli, started, stop))
 }
 
-async fn async_main(fifocore: FIFOCore, cli: Cli) -> anyhow::Result<()> {
+async fn async_main(
+    fifocore: FIFOCore,
+    cli: Cli,
+    started: impl FnOnce(),
+    stop: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
     let (shutdown_send, shutdown_recv) = tokio::sync::watch::channel(false);
+    let mirrors = Mirrors::default();
+    let profiles = Profiles::default();
+    if let Some(path) = &cli.profiles {
+        let count = profiles
+            .load_file(path)
+            .with_context(|| format!("could not load profiles from {}", path.display()))?;
+        log::info!("loaded {count} settings profile(s) from {}", path.display());
+    }
+    let firmware_metadata = FirmwareMetadata::default();
+    if let Some(path) = &cli.firmware_notes {
+        let count = firmware_metadata
+            .load_file(path)
+            .with_context(|| format!("could not load firmware notes from {}", path.display()))?;
+        log::info!("loaded firmware notes for {count} product(s) from {}", path.display());
+    }
+    if let Some(path) = &cli.firmware_index {
+        let count = firmware_metadata
+            .bundled()
+            .load_file(path)
+            .with_context(|| format!("could not load firmware index from {}", path.display()))?;
+        log::info!("loaded {count} bundled firmware file(s) from {}", path.display());
+    }
+    if let Some(path) = &cli.settings_migrations {
+        let count = firmware_metadata
+            .migrations()
+            .load_file(path)
+            .with_context(|| {
+                format!("could not load settings migrations from {}", path.display())
+            })?;
+        log::info!("loaded {count} settings migration(s) from {}", path.display());
+    }
+    let migrations = firmware_metadata.migrations().table();
+    let import_settings = match &cli.import_settings {
+        Some(path) => Some(
+            SettingsFile::load_file(path)
+                .with_context(|| format!("could not load settings from {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let schedule = match &cli.schedule {
+        Some(path) => Schedule::load_file(path)
+            .with_context(|| format!("could not load schedule from {}", path.display()))?,
+        None => Schedule::default(),
+    };
+    for alias in &cli.aliases {
+        let (name, bus) = alias
+            .split_once('=')
+            .with_context(|| format!("alias {alias:?} should be NAME=BUS"))?;
+        BusUri::from_params(bus).with_context(|| format!("invalid alias {alias:?}"))?;
+        fifocore
+            .set_bus_alias(name, bus)
+            .with_context(|| format!("invalid alias {alias:?}"))?;
+    }
+    for bus in &cli.buses_to_open {
+        fifocore
+            .validate_bus(bus)
+            .with_context(|| format!("could not open bus {bus}"))?;
+    }
+    let bench = match cli.bench {
+        true => {
+            let mut config = BenchConfig::default();
+            if let Some(auto_stop) = cli.bench_auto_stop {
+                config.auto_stop_ms = auto_stop;
+            }
+            if let Some(frames_per_sec) = cli.bench_tx_rate {
+                config.tx_rate.frames_per_sec = frames_per_sec;
+            }
+            Bench::enable(config)
+        }
+        false => Bench::default(),
+    };
+    if let Some(addr) = cli.tokio_console {
+        canandmiddleware::diagnostics::start_console(&fifocore.runtime(), addr)
+            .context("could not start tokio-console")?;
+    }
+    let diagnostics = Diagnostics::new(DiagnosticsPolicy {
+        token: cli.diagnostics_token,
+    });
+    let bus_sessions: Arc<_> = Default::default();
     let web_task = fifocore
         .runtime()
         .spawn(canandmiddleware::rest_server::run_web_server(
             shutdown_recv,
             fifocore.clone(),
+            Services {
+                mirrors: mirrors.clone(),
+                profiles: profiles.clone(),
+                firmware_metadata,
+                bench,
+                diagnostics,
+                bus_sessions: Arc::clone(&bus_sessions),
+            },
         ));
     for bus in cli.buses_to_open {
         log::info!("attempt open bus {bus}");
-        let id = fifocore.open_or_get_bus(&bus).unwrap();
+        let id = fifocore
+            .open_or_get_bus(&bus)
+            .with_context(|| format!("could not open bus {bus}"))?;
         log::info!("opened bus {bus} on id {id}");
+        if let Ok(info) = fifocore.bus_info(id) {
+            log::info!("{info}");
+        }
+    }
+    for mirror in cli.mirrors {
+        let (bus, group) = mirror
+            .rsplit_once('=')
+            .with_context(|| format!("mirror {mirror:?} should be BUS=GROUP:PORT"))?;
+        let group = group
+            .parse()
+            .with_context(|| format!("invalid mirror address {group:?}"))?;
+        let id = fifocore
+            .open_or_get_bus(bus)
+            .with_context(|| format!("could not open bus {bus}"))?;
+        mirrors.start(&fifocore, MirrorConfig::new(id, group))?;
+    }
+    let schedule_task = (!schedule.is_empty()).then(|| {
+        log::info!(
+            "running {} scheduled job(s), {} device-lost webhook(s)",
+            schedule.jobs.len(),
+            schedule.device_lost_webhooks.len()
+        );
+        fifocore
+            .runtime()
+            .spawn(canandmiddleware::schedule::run_schedule(
+                schedule,
+                fifocore.clone(),
+                Arc::clone(&bus_sessions),
+                profiles.clone(),
+                shutdown_send.subscribe(),
+            ))
+    });
+    if let Some(path) = cli.inventory {
+        let bus_sessions = Default::default();
+        let report = InventoryReport::collect(
+            &fifocore,
+            &bus_sessions,
+            &profiles,
+            std::time::Duration::from_secs(1),
+        )
+        .await;
+        // the bus tasks exit once their state is gone
+        bus_sessions.lock().clear();
+        report
+            .write_files(&path)
+            .with_context(|| format!("could not write inventory to {}", path.display()))?;
+        log::info!(
+            "wrote inventory of {} device(s) to {}",
+            report.devices.len(),
+            path.display()
+        );
+    }
+    if let Some(path) = cli.export_settings {
+        let bus_sessions = Default::default();
+        let file = SettingsFile::export(
+            &fifocore,
+            &bus_sessions,
+            &profiles,
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(10),
+        )
+        .await;
+        bus_sessions.lock().clear();
+        file.write_file(&path)
+            .with_context(|| format!("could not write settings to {}", path.display()))?;
+        log::info!(
+            "wrote settings of {} device(s) to {}",
+            file.devices().count(),
+            path.display()
+        );
+        for err in file.errors.iter() {
+            log::warn!("left out of settings export: {err}");
+        }
+    }
+    if let Some(file) = import_settings {
+        let bus_sessions = Default::default();
+        let mut bus_ids = fifocore.buses();
+        bus_ids.sort_unstable();
+        canandmiddleware::bus::enumerate_buses(&bus_sessions, &fifocore, &bus_ids, &profiles);
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let results = file
+            .import(&mut bus_sessions.lock(), &migrations, false)
+            .context("could not import settings")?;
+        bus_sessions.lock().clear();
+        let applied = results
+            .iter()
+            .filter(|result| {
+                matches!(
+                    result.outcome,
+                    canandmiddleware::settings_file::ImportOutcome::Applied { .. }
+                )
+            })
+            .count();
+        log::info!("imported settings to {applied} of {} device(s)", results.len());
     }
 
-    wait_for_term().await.unwrap();
+    started();
+    stop.await;
     let _ = shutdown_send.send(true);
     web_task.await?;
+    if let Some(task) = schedule_task {
+        task.await?;
+    }
     Ok(())
 }
 
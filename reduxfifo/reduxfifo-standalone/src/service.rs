@@ -0,0 +1,260 @@
+//! Running as a Windows service, so a driver station can keep the server up without a console
+//! window.
+//!
+//! The service control manager starts the process with `--service`; we hand the main thread to its
+//! dispatcher, which calls [`service_main`] to run the server until the service is stopped or
+//! Windows shuts down. Logs go to the Application event log under [`SERVICE_NAME`] instead of the
+//! console. The event source isn't registered, so nothing is written to the registry beyond what
+//! `sc.exe create` does; Event Viewer notes that the source's message file is missing but still
+//! shows each message.
+
+use std::{
+    ffi::c_void,
+    ptr,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use tokio::sync::watch;
+
+use crate::Cli;
+
+/// Name the service is installed under and the event log source.
+pub const SERVICE_NAME: &str = "ReduxFIFO";
+
+const SERVICE_WIN32_OWN_PROCESS: u32 = 0x10;
+
+const SERVICE_STOPPED: u32 = 1;
+const SERVICE_START_PENDING: u32 = 2;
+const SERVICE_STOP_PENDING: u32 = 3;
+const SERVICE_RUNNING: u32 = 4;
+
+const SERVICE_ACCEPT_STOP: u32 = 0x1;
+const SERVICE_ACCEPT_SHUTDOWN: u32 = 0x4;
+
+const SERVICE_CONTROL_STOP: u32 = 1;
+const SERVICE_CONTROL_INTERROGATE: u32 = 4;
+const SERVICE_CONTROL_SHUTDOWN: u32 = 5;
+
+const NO_ERROR: u32 = 0;
+const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+
+const EVENTLOG_ERROR_TYPE: u16 = 0x1;
+const EVENTLOG_WARNING_TYPE: u16 = 0x2;
+const EVENTLOG_INFORMATION_TYPE: u16 = 0x4;
+
+#[repr(C)]
+struct ServiceStatus {
+    service_type: u32,
+    current_state: u32,
+    controls_accepted: u32,
+    win32_exit_code: u32,
+    service_specific_exit_code: u32,
+    check_point: u32,
+    wait_hint: u32,
+}
+
+#[repr(C)]
+struct ServiceTableEntry {
+    service_name: *mut u16,
+    service_proc: Option<unsafe extern "system" fn(u32, *mut *mut u16)>,
+}
+
+type HandlerEx = unsafe extern "system" fn(u32, u32, *mut c_void, *mut c_void) -> u32;
+
+#[link(name = "advapi32")]
+unsafe extern "system" {
+    fn StartServiceCtrlDispatcherW(service_table: *const ServiceTableEntry) -> i32;
+    fn RegisterServiceCtrlHandlerExW(
+        service_name: *const u16,
+        handler: HandlerEx,
+        context: *mut c_void,
+    ) -> *mut c_void;
+    fn SetServiceStatus(status_handle: *mut c_void, status: *const ServiceStatus) -> i32;
+    fn RegisterEventSourceW(server_name: *const u16, source_name: *const u16) -> *mut c_void;
+    fn ReportEventW(
+        event_log: *mut c_void,
+        event_type: u16,
+        category: u16,
+        event_id: u32,
+        user_sid: *mut c_void,
+        num_strings: u16,
+        data_size: u32,
+        strings: *const *const u16,
+        raw_data: *mut c_void,
+    ) -> i32;
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain([0]).collect()
+}
+
+/// Command line the service was started with, for [`service_main`] to pick up.
+static CLI: Mutex<Option<Cli>> = Mutex::new(None);
+/// Handle [`SetServiceStatus`] reports through, once registered.
+static STATUS_HANDLE: AtomicUsize = AtomicUsize::new(0);
+/// Set when the service control manager asks us to stop.
+static STOP: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::channel(false).0);
+
+fn set_status(state: u32, exit_code: u32) {
+    let handle = STATUS_HANDLE.load(Ordering::Acquire);
+    if handle == 0 {
+        return;
+    }
+    let status = ServiceStatus {
+        service_type: SERVICE_WIN32_OWN_PROCESS,
+        current_state: state,
+        controls_accepted: match state {
+            SERVICE_RUNNING => SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN,
+            _ => 0,
+        },
+        win32_exit_code: match exit_code {
+            0 => NO_ERROR,
+            _ => ERROR_SERVICE_SPECIFIC_ERROR,
+        },
+        service_specific_exit_code: exit_code,
+        check_point: 0,
+        // enough for the server to close its buses and clients
+        wait_hint: 10_000,
+    };
+    unsafe { SetServiceStatus(handle as *mut c_void, &status) };
+}
+
+unsafe extern "system" fn control_handler(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut c_void,
+    _context: *mut c_void,
+) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            set_status(SERVICE_STOP_PENDING, 0);
+            STOP.send_replace(true);
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+    let name = wide(SERVICE_NAME);
+    let handle =
+        unsafe { RegisterServiceCtrlHandlerExW(name.as_ptr(), control_handler, ptr::null_mut()) };
+    if handle.is_null() {
+        log::error!(
+            "could not register the service control handler: {}",
+            std::io::Error::last_os_error()
+        );
+        return;
+    }
+    STATUS_HANDLE.store(handle as usize, Ordering::Release);
+    set_status(SERVICE_START_PENDING, 0);
+
+    let Some(cli) = CLI.lock().unwrap().take() else {
+        set_status(SERVICE_STOPPED, 1);
+        return;
+    };
+    let stopped = async {
+        let _ = STOP.subscribe().wait_for(|stop| *stop).await;
+    };
+    let result = crate::run(cli, || set_status(SERVICE_RUNNING, 0), stopped);
+    match result {
+        Ok(()) => set_status(SERVICE_STOPPED, 0),
+        Err(e) => {
+            log::error!("stopped: {e:#}");
+            set_status(SERVICE_STOPPED, 1);
+        }
+    }
+}
+
+/// Runs the server as the service the process was started as, returning once it's stopped.
+pub fn run_service(cli: Cli) -> anyhow::Result<()> {
+    log::set_boxed_logger(Box::new(EventLog::open()?))?;
+    log::set_max_level(log::LevelFilter::Info);
+    *CLI.lock().unwrap() = Some(cli);
+
+    let mut name = wide(SERVICE_NAME);
+    let table = [
+        ServiceTableEntry {
+            service_name: name.as_mut_ptr(),
+            service_proc: Some(service_main),
+        },
+        ServiceTableEntry {
+            service_name: ptr::null_mut(),
+            service_proc: None,
+        },
+    ];
+    // blocks until the service has stopped
+    if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+        anyhow::bail!(
+            "could not connect to the service control manager (was this started as a service?): \
+             {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok(())
+}
+
+/// Logs to the Application event log.
+struct EventLog {
+    /// Event log handle, kept open for the life of the process
+    handle: usize,
+}
+
+impl EventLog {
+    fn open() -> anyhow::Result<Self> {
+        let name = wide(SERVICE_NAME);
+        let handle = unsafe { RegisterEventSourceW(ptr::null(), name.as_ptr()) };
+        if handle.is_null() {
+            anyhow::bail!(
+                "could not open the event log: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(Self {
+            handle: handle as usize,
+        })
+    }
+}
+
+impl log::Log for EventLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // the event log isn't the place for per-frame chatter from dependencies
+        metadata.level() <= log::Level::Warn
+            || metadata.target().starts_with("reduxfifo")
+            || metadata.target().starts_with("canandmiddleware")
+            || metadata.target().starts_with("fifocore")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let event_type = match record.level() {
+            log::Level::Error => EVENTLOG_ERROR_TYPE,
+            log::Level::Warn => EVENTLOG_WARNING_TYPE,
+            _ => EVENTLOG_INFORMATION_TYPE,
+        };
+        let message = wide(&format!("[{}] {}", record.target(), record.args()));
+        let strings = [message.as_ptr()];
+        unsafe {
+            ReportEventW(
+                self.handle as *mut c_void,
+                event_type,
+                0,
+                0,
+                ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                ptr::null_mut(),
+            )
+        };
+    }
+
+    fn flush(&self) {}
+}
@@ -1,10 +1,76 @@
 #![allow(unused)]
-use fifocore::{FIFOCore, ReduxFIFOSessionConfig};
+use clap::{Parser, Subcommand};
+use fifocore::{FIFOCore, ReduxFIFOMessage, ReduxFIFOSessionConfig};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+#[derive(Parser)]
+#[command(version, about = "ReduxFIFO debugging utilities")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Open a bus and sit on a session
+    Listen {
+        /// Bus params
+        #[arg(default_value = "slcan:115200:/dev/cu.usbmodem101")]
+        params: String,
+    },
+    /// Attach to the debug console of an RdxUSB device; stdin lines are sent as console input
+    Console {
+        /// USB serial number of the device
+        serial: String,
+    },
+    /// List Redux devices attached over USB, including ones in DFU mode, without opening them
+    UsbDevices,
+    /// Open a bus and print the hardware behind it: USB adapter, serial port or interface
+    BusInfo {
+        /// Bus params
+        params: String,
+    },
+    /// Send the FRC global disable on each bus and check that nothing re-enables the actuators
+    GlobalDisable {
+        /// Bus params of every bus to disable
+        #[arg(required = true)]
+        params: Vec<String>,
+        /// How long to watch the buses after the disable
+        #[arg(long, default_value_t = 250)]
+        window_ms: u64,
+    },
+    /// Print a bus log, naming the session label each frame this host wrote came from
+    Dump {
+        /// Log file, as written by a bus logger
+        path: std::path::PathBuf,
+    },
+    /// Convert a bus log to a `candump -L` text log
+    ExportCandump {
+        /// Log file, as written by a bus logger
+        path: std::path::PathBuf,
+        /// Where to write the candump log; stdout if left out
+        out: Option<std::path::PathBuf>,
+        /// Interface to name a bus as, as IFACE=BUS; bus N is canN otherwise
+        #[arg(long = "map")]
+        maps: Vec<String>,
+    },
+    /// Convert a `candump -L` text log to a bus log, which can be played back as `replay:<out>`
+    ImportCandump {
+        /// candump log file
+        path: std::path::PathBuf,
+        /// Bus log to write
+        out: std::path::PathBuf,
+        /// Bus to put an interface's frames on, as IFACE=BUS; canN goes to bus N otherwise
+        #[arg(long = "map")]
+        maps: Vec<String>,
+    },
+}
 
 fn main() -> anyhow::Result<()> {
     env_logger::init_from_env(
         env_logger::Env::new().default_filter_or("debug,jni=off,warp=info,hyper=info"),
     );
+    let cli = Cli::parse();
 
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -13,15 +79,26 @@ fn main() -> anyhow::Result<()> {
         .expect("could not start ReduxFIFO");
 
     let fifocore = FIFOCore::new(rt.handle().clone());
-    rt.block_on(async_main(fifocore))
+    match cli.command {
+        Command::Listen { params } => rt.block_on(listen(fifocore, &params)),
+        Command::Console { serial } => rt.block_on(console(fifocore, &serial)),
+        Command::UsbDevices => rt.block_on(usb_devices(fifocore)),
+        Command::BusInfo { params } => rt.block_on(bus_info(fifocore, &params)),
+        Command::GlobalDisable { params, window_ms } => {
+            rt.block_on(global_disable(fifocore, &params, window_ms))
+        }
+        Command::Dump { path } => dump(&path),
+        Command::ExportCandump { path, out, maps } => export_candump(&path, out, &maps),
+        Command::ImportCandump { path, out, maps } => import_candump(&path, &out, &maps),
+    }
 }
 
-async fn async_main(fifocore: FIFOCore) -> anyhow::Result<()> {
+async fn listen(fifocore: FIFOCore, params: &str) -> anyhow::Result<()> {
     // 4 ok, 6 fail?
     let can_device_id = 0;
     println!("Connect to websocket...");
     //let bus_id = fifocore.open_or_get_bus("ws://10.43.22.2:7244/ws/0")?;
-    let bus_id = fifocore.open_or_get_bus("slcan:115200:/dev/cu.usbmodem101")?;
+    let bus_id = fifocore.open_or_get_bus(params)?;
     let session = fifocore.open_managed_session(
         bus_id,
         256,
@@ -35,3 +112,195 @@ async fn async_main(fifocore: FIFOCore) -> anyhow::Result<()> {
 
     loop {}
 }
+
+async fn usb_devices(fifocore: FIFOCore) -> anyhow::Result<()> {
+    let devices = fifocore.usb_devices().await?;
+    if devices.is_empty() {
+        println!("No Redux USB devices found");
+    }
+    for dev in devices {
+        println!(
+            "{:04x}:{:04x} {} {} fw {} dfu {:?}{}{}",
+            dev.vid,
+            dev.pid,
+            dev.serial_numer.as_deref().unwrap_or("<no serial>"),
+            dev.product.as_deref().unwrap_or("<unknown product>"),
+            dev.firmware,
+            dev.dfu,
+            dev.params.map(|p| format!(" bus {p}")).unwrap_or_default(),
+            if dev.open { " (open)" } else { "" },
+        );
+    }
+    Ok(())
+}
+
+async fn bus_info(fifocore: FIFOCore, params: &str) -> anyhow::Result<()> {
+    let bus_id = fifocore.open_or_get_bus(params)?;
+    // USB adapters are opened in the background, so give them a moment to connect
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    println!("{}", fifocore.bus_info(bus_id)?);
+    Ok(())
+}
+
+async fn global_disable(
+    fifocore: FIFOCore,
+    params: &[String],
+    window_ms: u64,
+) -> anyhow::Result<()> {
+    let bus_ids = params
+        .iter()
+        .map(|params| fifocore.open_or_get_bus(params))
+        .collect::<Result<Vec<u16>, _>>()?;
+    let reports = fifocore
+        .global_disable(&bus_ids, std::time::Duration::from_millis(window_ms))
+        .await;
+
+    let mut unconfirmed = 0;
+    for (params, report) in params.iter().zip(reports) {
+        let report = match report {
+            Ok(report) => report,
+            Err(e) => {
+                println!("{params}: disable not sent: {e}");
+                unconfirmed += 1;
+                continue;
+            }
+        };
+        if report.confirmed {
+            println!("{params}: disabled");
+        } else {
+            println!(
+                "{params}: NOT disabled, {} frames re-enabled actuators within {window_ms} ms",
+                report.enabling_frames
+            );
+            unconfirmed += 1;
+        }
+        for actuator in report.actuators {
+            println!("    heard {} ({} frames)", actuator.name, actuator.frames);
+        }
+    }
+    if unconfirmed > 0 {
+        anyhow::bail!("{unconfirmed} of {} buses not confirmed disabled", params.len());
+    }
+    Ok(())
+}
+
+async fn console(fifocore: FIFOCore, serial: &str) -> anyhow::Result<()> {
+    let mut console = fifocore.open_console(serial).await?;
+    log::info!("Attached to console of {:?}", console.device_id());
+
+    let mut stdin = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    loop {
+        tokio::select! {
+            output = console.recv() => {
+                stdout.write_all(&output?).await?;
+                stdout.flush().await?;
+            }
+            line = stdin.next_line() => {
+                let Some(mut line) = line? else {
+                    return Ok(());
+                };
+                line.push('\n');
+                console.write(line.as_bytes())?;
+            }
+        }
+    }
+}
+
+fn dump(path: &std::path::Path) -> anyhow::Result<()> {
+    use fifocore::logger::{LOG_MAGIC, LogHeader};
+    use std::io::Read;
+
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut magic = [0_u8; LOG_MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    anyhow::ensure!(
+        &magic == LOG_MAGIC,
+        "{} is not a ReduxFIFO log",
+        path.display()
+    );
+
+    let mut label = String::new();
+    let mut header = [0_u8; size_of::<LogHeader>()];
+    let mut data = [0_u8; 64];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        let header: LogHeader = bytemuck::pod_read_unaligned(&header);
+        let data = &mut data[..(header.data_size as usize).min(64)];
+        reader.read_exact(data)?;
+        if header.is_label() {
+            label = String::from_utf8_lossy(data).into_owned();
+            continue;
+        }
+        let tx = header.flags & ReduxFIFOMessage::FLAG_TX != 0;
+        let hex: Vec<String> = data.iter().map(|b| format!("{b:02x}")).collect();
+        println!(
+            "{:>14} bus {} {:08x} {} [{}] {}{}",
+            header.timestamp,
+            header.bus_id,
+            header.message_id,
+            if tx { "tx" } else { "rx" },
+            data.len(),
+            hex.join(" "),
+            match (tx, label.is_empty()) {
+                (true, false) => format!("  ({label})"),
+                _ => String::new(),
+            },
+        );
+    }
+}
+
+fn bus_map(maps: &[String]) -> anyhow::Result<fifocore::logconvert::BusMap> {
+    use anyhow::Context;
+
+    maps.iter()
+        .try_fold(fifocore::logconvert::BusMap::new(), |buses, map| {
+            let (iface, bus) = map
+                .split_once('=')
+                .with_context(|| format!("map {map:?} should be IFACE=BUS"))?;
+            let bus = bus
+                .parse()
+                .with_context(|| format!("map {map:?} should be IFACE=BUS"))?;
+            Ok(buses.map(iface, bus))
+        })
+}
+
+fn export_candump(
+    path: &std::path::Path,
+    out: Option<std::path::PathBuf>,
+    maps: &[String],
+) -> anyhow::Result<()> {
+    use fifocore::logconvert;
+
+    let buses = bus_map(maps)?;
+    let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let messages = logconvert::read_rdxlog(reader)?;
+    match out {
+        Some(out) => {
+            let writer = std::io::BufWriter::new(std::fs::File::create(out)?);
+            logconvert::write_candump(writer, &messages, &buses)?;
+        }
+        None => logconvert::write_candump(std::io::stdout().lock(), &messages, &buses)?,
+    }
+    Ok(())
+}
+
+fn import_candump(
+    path: &std::path::Path,
+    out: &std::path::Path,
+    maps: &[String],
+) -> anyhow::Result<()> {
+    use fifocore::logconvert;
+
+    let buses = bus_map(maps)?;
+    let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let messages = logconvert::read_candump(reader, &buses)?;
+    let writer = std::io::BufWriter::new(std::fs::File::create(out)?);
+    logconvert::write_rdxlog(writer, &messages)?;
+    log::info!("Wrote {} messages to {}", messages.len(), out.display());
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! What this build of ReduxFIFO can do, across its crates.
+//!
+//! Unsupported feature combinations are refused at compile time by each crate's
+//! [`feature_matrix!`](fifocore::feature_matrix); [`capabilities`] reports the supported ones at
+//! runtime, so that a downstream crate can check for e.g. the FFI before relying on it.
+
+fifocore::feature_matrix! {
+    /// Features reduxfifo itself was built with.
+    pub struct Features {
+        /// The process-wide [`INSTANCE`](crate::INSTANCE) and its runtime
+        singleton = "singleton",
+        jni = "jni",
+        /// The extern C API
+        ffi = "ffi" requires("singleton"),
+        /// ReduxCore and its Java bindings, for ReduxLib
+        legacy_driver = "legacy-driver" requires("jni", "singleton", "ffi"),
+        /// Sessions read through shared-memory rings
+        shm_ring = "shm-ring" requires("ffi"),
+        /// A tokio-console endpoint that can be started at runtime
+        tokio_console = "tokio-console",
+        wpihal_rio = "wpihal-rio" conflicts("wpihal-mrc"),
+        wpihal_mrc = "wpihal-mrc",
+        athena = "athena" requires("legacy-driver"),
+        systemcore = "systemcore" requires("legacy-driver"),
+        systemcore_sim = "systemcore-sim" requires("legacy-driver"),
+    }
+}
+
+/// Features of every ReduxFIFO crate in this build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub reduxfifo: Features,
+    pub fifocore: fifocore::capabilities::Capabilities,
+    pub canandmiddleware: canandmiddleware::Capabilities,
+}
+
+impl Capabilities {
+    /// Every feature, as `crate/feature`, and whether it's enabled.
+    pub fn features(&self) -> Vec<(String, bool)> {
+        let crates = [
+            ("reduxfifo", self.reduxfifo.features()),
+            ("fifocore", self.fifocore.features()),
+            ("canandmiddleware", self.canandmiddleware.features()),
+        ];
+        crates
+            .into_iter()
+            .flat_map(|(name, features)| {
+                features
+                    .into_iter()
+                    .map(move |(feature, enabled)| (format!("{name}/{feature}"), enabled))
+            })
+            .collect()
+    }
+
+    /// Names of the enabled features, as `crate/feature`.
+    pub fn enabled(&self) -> Vec<String> {
+        self.features()
+            .into_iter()
+            .filter_map(|(feature, enabled)| enabled.then_some(feature))
+            .collect()
+    }
+}
+
+/// What this build of ReduxFIFO was compiled with.
+pub const fn capabilities() -> Capabilities {
+    Capabilities {
+        reduxfifo: Features::BUILT,
+        fifocore: fifocore::capabilities::Capabilities::BUILT,
+        canandmiddleware: canandmiddleware::Capabilities::BUILT,
+    }
+}
@@ -0,0 +1,351 @@
+//! Typed async access to Redux devices, for Rust robot programs and coprocessor daemons.
+//!
+//! A [`Device`] is one device on a [`Bus`], talked to through its own session: its messages come
+//! out of [`Device::messages`] already decoded into the device's generated canandmessage enum, and
+//! its settings are read and written with [`Device::fetch_setting`] and [`Device::set_setting`],
+//! which resolve once the device reports the value back.
+//!
+//! ```no_run
+//! # async fn run(fifocore: fifocore::FIFOCore) -> Result<(), reduxfifo::client::ClientError> {
+//! use futures::StreamExt as _;
+//! use reduxfifo::client::{Bus, Canandmag, Device};
+//!
+//! let bus = Bus::open(&fifocore, "socketcan:can0")?;
+//! let mag = Device::<Canandmag>::connect(&bus, 3).await?;
+//! let firmware = mag
+//!     .fetch_setting(canandmessage::canandmag::types::Setting::FirmwareVersion)
+//!     .await?;
+//! println!("{firmware:?}");
+//! let mut messages = mag.messages();
+//! while let Some(received) = messages.next().await {
+//!     println!("{}: {:?}", received.timestamp, received.message);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Everything here runs on the tokio runtime [`Device::connect`] is called from.
+
+use core::{fmt, marker::PhantomData, time::Duration};
+use std::sync::Arc;
+
+use canandmessage::{
+    CanandMessageError, CanandMessageWrapper,
+    generic::{ReportSetting, SetSetting, WrapperSerializable},
+    traits::{CanandDevice, CanandDeviceMessage, CanandDeviceSetting, MessageIndexId},
+};
+use fifocore::{
+    FIFOCore, FIFOInterface, ReduxFIFOMessage, ReduxFIFOSessionConfig, Session, error::Error,
+};
+use frc_can_id::{REDUX_VENDOR_ID, build_frc_can_id};
+use futures::{StreamExt as _, stream::BoxStream};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+};
+
+/// The Canandmag, for [`Device::<Canandmag>`](Device).
+pub type Canandmag = canandmessage::canandmag::Device;
+/// The Canandgyro, for [`Device::<Canandgyro>`](Device).
+pub type Canandgyro = canandmessage::canandgyro::Device;
+/// The Canandcolor, for [`Device::<Canandcolor>`](Device).
+pub type Canandcolor = canandmessage::canandcolor::Device;
+
+/// How long a device has to report a setting back.
+pub const SETTING_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Size of a device session's read buffer.
+const READ_BUFFER_SIZE: u32 = 256;
+/// Frames held for each message stream and setting future before the slowest one starts missing
+/// them.
+const FRAME_BACKLOG: usize = 256;
+/// Bits of a CAN id that pick out one device: its type, vendor and number.
+const DEVICE_MASK: u32 = build_frc_can_id(0x1f, 0xff, 0, 0x3f);
+
+/// Error from talking to a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientError {
+    /// The bus or session failed
+    Fifo(Error),
+    /// The message couldn't be encoded
+    Encode(CanandMessageError),
+    /// The device didn't report the setting back within [`SETTING_TIMEOUT`]
+    Timeout,
+    /// The device reported the setting back, but refused it
+    Rejected(u8),
+    /// The device reported a value that doesn't decode as the setting
+    InvalidSetting(u8),
+    /// The device's session was closed, e.g. because its bus was
+    Closed,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Fifo(e) => write!(f, "{e}"),
+            ClientError::Encode(e) => write!(f, "could not encode message: {e}"),
+            ClientError::Timeout => f.write_str("device did not report the setting back"),
+            ClientError::Rejected(index) => write!(f, "device rejected setting {index:#x}"),
+            ClientError::InvalidSetting(index) => {
+                write!(f, "device reported an invalid value for setting {index:#x}")
+            }
+            ClientError::Closed => f.write_str("device session closed"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<Error> for ClientError {
+    fn from(value: Error) -> Self {
+        ClientError::Fifo(value)
+    }
+}
+
+impl From<CanandMessageError> for ClientError {
+    fn from(value: CanandMessageError) -> Self {
+        ClientError::Encode(value)
+    }
+}
+
+/// An open bus that devices can be connected on.
+#[derive(Debug, Clone)]
+pub struct Bus {
+    fifocore: Arc<dyn FIFOInterface>,
+    bus_id: u16,
+}
+
+impl Bus {
+    /// Bus `bus_id`, already opened on `fifocore`.
+    pub fn new(fifocore: &dyn FIFOInterface, bus_id: u16) -> Self {
+        Self {
+            fifocore: fifocore.handle(),
+            bus_id,
+        }
+    }
+
+    /// Opens the bus with `params`, or gets it if it's already open.
+    pub fn open(fifocore: &FIFOCore, params: &str) -> Result<Self, Error> {
+        let bus_id = fifocore.open_or_get_bus(params)?;
+        Ok(Self::new(fifocore, bus_id))
+    }
+
+    pub fn bus_id(&self) -> u16 {
+        self.bus_id
+    }
+}
+
+/// A message from a device, and when it arrived.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Received<M> {
+    pub message: M,
+    /// Microseconds, on the bus's timebase
+    pub timestamp: u64,
+}
+
+/// One device of type `D`, like [`Canandmag`], on a bus.
+///
+/// Dropping it closes its session.
+pub struct Device<D: CanandDevice> {
+    bus: Bus,
+    device_number: u8,
+    session: Arc<Session>,
+    /// Everything the session reads, for message streams and setting futures to pick from
+    frames: broadcast::Sender<ReduxFIFOMessage>,
+    reader: JoinHandle<()>,
+    device: PhantomData<fn() -> D>,
+}
+
+impl<D: CanandDevice> fmt::Debug for Device<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Device")
+            .field("device", &D::DEV_NAME)
+            .field("bus_id", &self.bus.bus_id)
+            .field("device_number", &self.device_number)
+            .finish()
+    }
+}
+
+impl<D: CanandDevice> Device<D> {
+    /// Connects to device number `device_number` (0-63) on `bus`.
+    ///
+    /// This doesn't wait to hear from the device, so it succeeds whether or not the device is on
+    /// the bus; use [`Device::fetch_setting`] to check.
+    pub async fn connect(bus: &Bus, device_number: u8) -> Result<Self, ClientError> {
+        let device_number = device_number & 0x3f;
+        let filter_id = build_frc_can_id(D::DEV_TYPE, REDUX_VENDOR_ID, 0, device_number);
+        let session = bus.fifocore.open_managed_session(
+            bus.bus_id,
+            READ_BUFFER_SIZE,
+            ReduxFIFOSessionConfig::new(filter_id, DEVICE_MASK),
+        )?;
+        let _ = session.set_label(&format!("{} {device_number}", D::DEV_NAME));
+        let session = Arc::new(session);
+        let (frames, _) = broadcast::channel(FRAME_BACKLOG);
+        let reader = tokio::spawn(read_frames(session.clone(), frames.clone()));
+        Ok(Self {
+            bus: bus.clone(),
+            device_number,
+            session,
+            frames,
+            reader,
+            device: PhantomData,
+        })
+    }
+
+    pub fn device_number(&self) -> u8 {
+        self.device_number
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    /// Every message the device sends from now on, decoded. Frames that don't decode as one of
+    /// the device's messages are skipped.
+    ///
+    /// Each stream gets every message; one that falls more than a few hundred messages behind
+    /// skips ahead to the newest.
+    pub fn messages(&self) -> BoxStream<'static, Received<D::Message>>
+    where
+        D: 'static,
+        D::Message: Send + 'static,
+    {
+        futures::stream::unfold(self.frames.subscribe(), |mut frames| async move {
+            loop {
+                let msg = match frames.recv().await {
+                    Ok(msg) => msg,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                };
+                if let Ok(message) = D::Message::try_from_wrapper(&CanandMessageWrapper(msg)) {
+                    let received = Received {
+                        message,
+                        timestamp: msg.timestamp,
+                    };
+                    return Some((received, frames));
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Sends a message to the device.
+    pub fn send(&self, message: &D::Message) -> Result<(), ClientError> {
+        let mut msg: CanandMessageWrapper<ReduxFIFOMessage> =
+            message.try_into_wrapper(self.device_number as u32)?;
+        msg.0.bus_id = self.bus.bus_id;
+        Ok(self.session.write_single(&msg)?)
+    }
+
+    /// Reads a setting from the device.
+    pub async fn fetch_setting(
+        &self,
+        index: <D::Setting as CanandDeviceSetting>::Index,
+    ) -> Result<D::Setting, ClientError> {
+        let index: u8 = index.into();
+        // subscribed before sending, so that a quick report isn't missed
+        let reports = self.frames.subscribe();
+        self.send_raw(
+            canandmessage::cananddevice::MessageIndex::SettingCommand,
+            &[
+                canandmessage::cananddevice::types::SettingCommand::FetchSettingValue as u8,
+                index,
+            ],
+        )?;
+        report_for::<D>(reports, index).await
+    }
+
+    /// Writes a setting to the device, resolving to the value it reports back, which it may have
+    /// clamped.
+    pub async fn set_setting(&self, setting: D::Setting) -> Result<D::Setting, ClientError> {
+        let index = setting.raw_index();
+        let body: [u8; 8] = SetSetting::new(
+            index,
+            setting.into(),
+            canandmessage::cananddevice::types::SettingFlags {
+                ephemeral: false,
+                synch_hold: false,
+                synch_msg_count: 0,
+            },
+        )
+        .into();
+        let reports = self.frames.subscribe();
+        self.send_raw(canandmessage::cananddevice::MessageIndex::SetSetting, &body)?;
+        report_for::<D>(reports, index).await
+    }
+
+    /// Sends one of the messages every device shares.
+    fn send_raw(
+        &self,
+        index: canandmessage::cananddevice::MessageIndex,
+        data: &[u8],
+    ) -> Result<(), ClientError> {
+        let id = index.frc_can_id_as(D::DEV_TYPE, self.device_number).0;
+        let msg = ReduxFIFOMessage::builder()
+            .bus(self.bus.bus_id)
+            .id(id)
+            .data(data)
+            .build();
+        Ok(self.session.write_single(&msg)?)
+    }
+}
+
+impl<D: CanandDevice> Drop for Device<D> {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Waits for the device to report setting `index`.
+async fn report_for<D: CanandDevice>(
+    mut frames: broadcast::Receiver<ReduxFIFOMessage>,
+    index: u8,
+) -> Result<D::Setting, ClientError> {
+    let report = tokio::time::timeout(SETTING_TIMEOUT, async {
+        loop {
+            let msg = match frames.recv().await {
+                Ok(msg) => msg,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return Err(ClientError::Closed),
+            };
+            if let Ok(report) = ReportSetting::try_from_wrapper(&CanandMessageWrapper(msg))
+                && report.index == index
+            {
+                return Ok(report);
+            }
+        }
+    })
+    .await
+    .map_err(|_| ClientError::Timeout)??;
+
+    if !report.flags.set_success() {
+        return Err(ClientError::Rejected(index));
+    }
+    let address = <D::Setting as CanandDeviceSetting>::Index::try_from(index)
+        .map_err(|_| ClientError::InvalidSetting(index))?;
+    D::Setting::from_address_data(address, &report.value)
+        .map_err(|_| ClientError::InvalidSetting(index))
+}
+
+/// Hands everything `session` reads to `frames`, until the session closes.
+async fn read_frames(session: Arc<Session>, frames: broadcast::Sender<ReduxFIFOMessage>) {
+    let Ok(mut notifier) = session.rx_notifier() else {
+        return;
+    };
+    let mut buffer = session.read_buffer(READ_BUFFER_SIZE);
+    loop {
+        match notifier.wait_for(|size| *size > 0).await {
+            // holding the borrow blocks the bus from delivering more messages
+            Ok(size) => drop(size),
+            Err(_) => return,
+        }
+        if session.read_barrier(&mut buffer).is_err() {
+            return;
+        }
+        for msg in buffer.iter() {
+            // nobody listening is fine
+            let _ = frames.send(*msg);
+        }
+    }
+}
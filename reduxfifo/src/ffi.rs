@@ -3,12 +3,17 @@ use std::{ffi::CStr, time::Duration};
 
 use crate::INSTANCE;
 use crate::log_debug;
+use crate::subsystems::bench_heartbeat::{BenchHeartbeat, NotOnARobot};
 
 use fifocore::{
     ReadBuffer, ReduxFIFOMessage, ReduxFIFOReadBuffer, ReduxFIFOSession, ReduxFIFOSessionConfig,
-    ReduxFIFOStatus, ReduxFIFOVersion, ReduxFIFOWriteBuffer, WriteBuffer, error::Error,
+    ReduxFIFOStatus, ReduxFIFOVersion, ReduxFIFOWriteBuffer, WriteBuffer, cancel::CancelToken,
+    error::Error,
 };
 
+#[cfg(feature = "shm-ring")]
+use fifocore::shm::{ShmRing, ShmRingHeader};
+
 #[repr(C)]
 struct ReduxFIFOReadBufferFFI {
     meta: *mut ReduxFIFOReadBuffer,
@@ -21,6 +26,34 @@ struct ReduxFIFOWriteBufferFFI {
     data: *mut ReduxFIFOMessage,
 }
 
+/// Outcome of [`ReduxFIFO_GlobalDisable`] on one bus.
+#[repr(C)]
+struct ReduxFIFOGlobalDisableResultFFI {
+    bus_id: u16,
+    confirmed: u8,
+    reserved: u8,
+    status: ReduxFIFOStatus,
+    enabling_frames: u32,
+    actuators_heard: u32,
+    tx_timestamp: u64,
+}
+
+/// A bus's [`fifocore::bus_status::BusStatus`], for [`ReduxFIFO_GetBusStatus`].
+#[repr(C)]
+struct ReduxFIFOBusStatusFFI {
+    state: u8,
+    reserved: u8,
+    /// `u16::MAX` if the controller doesn't report it
+    tx_errors: u16,
+    rx_errors: u16,
+    reserved2: u16,
+    error_passive_count: u32,
+    bus_off_count: u32,
+    tx_overruns: u64,
+    rx_overruns: u64,
+    state_since: u64,
+}
+
 /// Returns the version number. This number is unique per version.
 ///
 /// Minor version is bits 0-7
@@ -31,6 +64,15 @@ extern "C" fn ReduxFIFO_GetVersion() -> u32 {
     ReduxFIFOVersion::version().serialized()
 }
 
+/// Returns a bitmask of the bus backends that can be opened in this process.
+///
+/// Bits are the `REDUXFIFO_CAP_*` values from `ReduxFIFO.h`; a backend whose bit is clear returns
+/// `REDUXFIFO_ERR_BUS_NOT_SUPPORTED` from [`ReduxFIFO_OpenBus`].
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_GetCapabilities() -> u32 {
+    fifocore::backends::capabilities()
+}
+
 /// Returns a null-terminated UTF-8 error message string.
 #[unsafe(no_mangle)]
 extern "C" fn ReduxFIFO_ErrorMessage(status: i32) -> *const libc::c_char {
@@ -45,20 +87,27 @@ extern "C" fn ReduxFIFO_ErrorMessage(status: i32) -> *const libc::c_char {
 /// This is generally called by the CanandEventLoop in either C++ or Java and doesn't need to be directly called.
 /// This function is idempotent and will do nothing if called multiple times.
 ///
-/// Return 0 on success, -1 on already started
+/// Return 0 on success, -1 on already started, and `REDUXFIFO_NOT_INITIALIZED` in builds without
+/// the `legacy-driver` feature, which have no server to start.
 #[unsafe(no_mangle)]
 extern "C" fn ReduxFIFO_StartServer() -> i32 {
-    crate::legacy::ReduxCore_InitServer()
+    #[cfg(feature = "legacy-driver")]
+    return crate::legacy::ReduxCore_InitServer();
+    #[cfg(not(feature = "legacy-driver"))]
+    return Error::NotInitialized.into();
 }
 
 /// Stops the Redux CANLink server.
 /// This is called by CanandEventLoop to stop CANLink.
 ///
-/// Return 0 on success, -1 on already stopped
-///
+/// Return 0 on success, -1 on already stopped, and `REDUXFIFO_NOT_INITIALIZED` in builds without
+/// the `legacy-driver` feature.
 #[unsafe(no_mangle)]
 extern "C" fn ReduxFIFO_StopServer() -> i32 {
-    crate::legacy::ReduxCore_StopServer()
+    #[cfg(feature = "legacy-driver")]
+    return crate::legacy::ReduxCore_StopServer();
+    #[cfg(not(feature = "legacy-driver"))]
+    return Error::NotInitialized.into();
 }
 
 /// C ABI open bus
@@ -83,6 +132,41 @@ extern "C" fn ReduxFIFO_OpenBus(
         .into()
 }
 
+/// C ABI bus alias registration
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_SetBusAlias(
+    alias: *const libc::c_char,
+    bus_address: *const libc::c_char,
+) -> ReduxFIFOStatus {
+    if alias.is_null() || bus_address.is_null() {
+        return Err(Error::NullArgument).into();
+    }
+    let (Ok(alias), Ok(params)) = (
+        unsafe { CStr::from_ptr(alias) }.to_str(),
+        unsafe { CStr::from_ptr(bus_address) }.to_str(),
+    ) else {
+        return Err(Error::InvalidBus).into();
+    };
+    log_debug!("FFI set bus alias: {alias} = {params}");
+    INSTANCE.set_bus_alias(alias, params).into()
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_RemoveBusAlias(alias: *const libc::c_char) -> ReduxFIFOStatus {
+    if alias.is_null() {
+        return Err(Error::NullArgument).into();
+    }
+    let Ok(alias) = unsafe { CStr::from_ptr(alias) }.to_str() else {
+        return Err(Error::InvalidBus).into();
+    };
+    if INSTANCE.remove_bus_alias(alias) {
+        Ok(())
+    } else {
+        Err(Error::InvalidBus)
+    }
+    .into()
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn ReduxFIFO_CloseBus(bus_id: u16) -> ReduxFIFOStatus {
     log_debug!("FFI close bus: {bus_id}");
@@ -113,6 +197,21 @@ extern "C" fn ReduxFIFO_CloseSession(session: ReduxFIFOSession) -> ReduxFIFOStat
     INSTANCE.close_session(session).map(|_| ()).into()
 }
 
+/// C ABI session labeling
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_SetSessionLabel(
+    session: ReduxFIFOSession,
+    label: *const libc::c_char,
+) -> ReduxFIFOStatus {
+    if label.is_null() {
+        return Err(Error::NullArgument).into();
+    }
+    let Ok(label) = unsafe { CStr::from_ptr(label) }.to_str() else {
+        return Err(Error::InvalidLabel).into();
+    };
+    INSTANCE.set_session_label(session, label).into()
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn ReduxFIFO_AllocateReadBuffer(
     session: ReduxFIFOSession,
@@ -166,7 +265,9 @@ extern "C" fn ReduxFIFO_ReadBarrier(
         .map(|m| unsafe { ReadBuffer::from_parts(m.meta, m.data) })
         .collect();
 
-    INSTANCE.read_barrier(bus_id, &mut data).into()
+    let status = INSTANCE.read_barrier(bus_id, &mut data);
+    fifocore::latency::record(data.iter().flat_map(ReadBuffer::iter));
+    status.into()
 }
 
 #[unsafe(no_mangle)]
@@ -193,9 +294,58 @@ extern "C" fn ReduxFIFO_ReadBarrierMultiBus(
         })
         .collect();
 
-    INSTANCE
-        .read_barrier_multibus(data.iter_mut().map(|m| m.as_mut_slice()))
-        .into()
+    let status = INSTANCE.read_barrier_multibus(data.iter_mut().map(|m| m.as_mut_slice()));
+    fifocore::latency::record(data.iter().flatten().flat_map(ReadBuffer::iter));
+    status.into()
+}
+
+/// Turns latency histograms on (nonzero) or off.
+///
+/// While on, read barriers and the ReduxCore receive calls record how long each message took from
+/// its receive timestamp to being handed over, per frame id without the device number.
+/// Takes up to `capacity` of `session`'s messages in a window into `messages`; see
+/// [`fifocore::FIFOCore::read_window_up_to`].
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_ReadWindow(
+    session: ReduxFIFOSession,
+    t0_us: u64,
+    t1_us: u64,
+    messages: *mut ReduxFIFOMessage,
+    capacity: u32,
+    count: *mut u32,
+) -> ReduxFIFOStatus {
+    if messages.is_null() {
+        return Err(Error::NullArgument).into();
+    }
+    let window = match INSTANCE.read_window_up_to(session, t0_us, t1_us, capacity as usize) {
+        Ok(window) => window,
+        Err(e) => return Err(e).into(),
+    };
+    unsafe { core::slice::from_raw_parts_mut(messages, window.len()) }.copy_from_slice(&window);
+    fifocore::latency::record(window.iter());
+    if !count.is_null() {
+        unsafe {
+            *count = window.len() as u32;
+        }
+    }
+    Ok(()).into()
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_SetLatencyHistograms(enabled: u8) {
+    fifocore::latency::set_enabled(enabled != 0);
+}
+
+/// Logs a table of the latency histograms recorded so far.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_DumpLatencyHistograms() {
+    crate::log_info!("latency histograms:\n{}", fifocore::latency::report());
+}
+
+/// Clears the latency histograms.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_ResetLatencyHistograms() {
+    fifocore::latency::reset();
 }
 
 #[unsafe(no_mangle)]
@@ -223,12 +373,242 @@ extern "C" fn ReduxFIFO_WriteSingle(msg: *const ReduxFIFOMessage) -> ReduxFIFOSt
         .into()
 }
 
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_WriteSingleAs(
+    session: ReduxFIFOSession,
+    msg: *const ReduxFIFOMessage,
+) -> ReduxFIFOStatus {
+    unsafe { msg.as_ref() }
+        .map_or(Err(Error::NullArgument), |msg| {
+            INSTANCE.write_single_as(session, msg)
+        })
+        .into()
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_WriteSingleTracked(
+    msg: *const ReduxFIFOMessage,
+    tx_seq: *mut u32,
+) -> ReduxFIFOStatus {
+    let Some(msg) = (unsafe { msg.as_ref() }) else {
+        return Err(Error::NullArgument).into();
+    };
+    INSTANCE
+        .write_single_tracked(msg)
+        .map(|seq| {
+            if let Some(tx_seq) = unsafe { tx_seq.as_mut() } {
+                *tx_seq = seq.unwrap_or(0);
+            }
+        })
+        .into()
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_GetTxTimestamp(
+    bus_id: u16,
+    tx_seq: u32,
+    timeout_ms: u64,
+    timestamp: *mut u64,
+) -> ReduxFIFOStatus {
+    let Some(timestamp) = (unsafe { timestamp.as_mut() }) else {
+        return Err(Error::NullArgument).into();
+    };
+    let result = if timeout_ms == 0 {
+        INSTANCE
+            .tx_status(bus_id, tx_seq)
+            .and_then(|status| status.into_result())
+    } else {
+        INSTANCE.runtime().block_on(async {
+            tokio::time::timeout(
+                Duration::from_millis(timeout_ms),
+                INSTANCE.tx_confirmed(bus_id, tx_seq),
+            )
+            .await
+            .unwrap_or(Err(Error::TxPending))
+        })
+    };
+    result
+        .map(|ts| {
+            *timestamp = ts;
+        })
+        .into()
+}
+
+/// Gets the error state and overrun counts of a bus.
+///
+/// With a nonzero `timeout_ms`, first waits up to that long for them to change from `status`'s
+/// contents, for polling on a thread.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_GetBusStatus(
+    bus_id: u16,
+    timeout_ms: u64,
+    status: *mut ReduxFIFOBusStatusFFI,
+) -> ReduxFIFOStatus {
+    let Some(status) = (unsafe { status.as_mut() }) else {
+        return Err(Error::NullArgument).into();
+    };
+    let mut notifier = match INSTANCE.bus_status_watch(bus_id) {
+        Ok(n) => n,
+        Err(e) => {
+            return Err(e).into();
+        }
+    };
+    let last = (status.state, status.tx_overruns, status.rx_overruns);
+    let changed = |current: &fifocore::bus_status::BusStatus| {
+        (
+            current.state as u8,
+            current.tx_overruns,
+            current.rx_overruns,
+        ) != last
+            || current.tx_errors.unwrap_or(u16::MAX) != status.tx_errors
+            || current.rx_errors.unwrap_or(u16::MAX) != status.rx_errors
+    };
+    let changed_to = if timeout_ms == 0 {
+        None
+    } else {
+        INSTANCE.runtime().block_on(async {
+            match tokio::time::timeout(
+                Duration::from_millis(timeout_ms),
+                notifier.wait_for(changed),
+            )
+            .await
+            {
+                Ok(Ok(current)) => Some(*current),
+                _ => None,
+            }
+        })
+    };
+    // a timeout or a closed bus reports what there is
+    let current = changed_to.unwrap_or_else(|| *notifier.borrow());
+    *status = ReduxFIFOBusStatusFFI {
+        state: current.state as u8,
+        reserved: 0,
+        tx_errors: current.tx_errors.unwrap_or(u16::MAX),
+        rx_errors: current.rx_errors.unwrap_or(u16::MAX),
+        reserved2: 0,
+        error_passive_count: current.error_passive_count,
+        bus_off_count: current.bus_off_count,
+        tx_overruns: current.tx_overruns,
+        rx_overruns: current.rx_overruns,
+        state_since: current.state_since_us,
+    };
+    Ok(()).into()
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn ReduxFIFO_WaitForThreshold(
     session: ReduxFIFOSession,
     threshold: u32,
     timeout_ms: u64,
     msg_count: *mut u32,
+) -> ReduxFIFOStatus {
+    wait_for_threshold(session, threshold, timeout_ms, None, msg_count)
+}
+
+/// Makes a token for cancelling [`ReduxFIFO_WaitForThresholdCancellable`] from another thread.
+///
+/// Free it with [`ReduxFIFO_FreeCancelToken`].
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_NewCancelToken() -> *mut CancelToken {
+    Box::into_raw(Box::new(CancelToken::new()))
+}
+
+/// Cancels every wait using `token`, now and from now on.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_Cancel(token: *const CancelToken) -> ReduxFIFOStatus {
+    match unsafe { token.as_ref() } {
+        Some(token) => {
+            token.cancel();
+            Ok(()).into()
+        }
+        None => Err(Error::NullArgument).into(),
+    }
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_FreeCancelToken(token: *mut CancelToken) {
+    if !token.is_null() {
+        drop(unsafe { Box::from_raw(token) });
+    }
+}
+
+/// [`ReduxFIFO_WaitForThreshold`], returning early with [`Error::Cancelled`] if `token` is
+/// cancelled.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_WaitForThresholdCancellable(
+    session: ReduxFIFOSession,
+    threshold: u32,
+    timeout_ms: u64,
+    token: *const CancelToken,
+    msg_count: *mut u32,
+) -> ReduxFIFOStatus {
+    match unsafe { token.as_ref() } {
+        Some(token) => wait_for_threshold(session, threshold, timeout_ms, Some(token), msg_count),
+        None => Err(Error::NullArgument).into(),
+    }
+}
+
+/// Rings handed out by [`ReduxFIFO_AttachRing`], kept mapped until [`ReduxFIFO_DetachRing`] even
+/// if their session closes first, so the reader never loses its mapping underneath it.
+#[cfg(feature = "shm-ring")]
+static RINGS: std::sync::LazyLock<
+    parking_lot::Mutex<std::collections::HashMap<u64, std::sync::Arc<ShmRing>>>,
+> = std::sync::LazyLock::new(Default::default);
+
+#[cfg(feature = "shm-ring")]
+pub(crate) fn attach_ring(
+    session: ReduxFIFOSession,
+    capacity: u32,
+) -> Result<std::sync::Arc<ShmRing>, Error> {
+    let ring = INSTANCE.attach_ring(session, capacity)?;
+    RINGS.lock().insert(session.0, ring.clone());
+    Ok(ring)
+}
+
+#[cfg(feature = "shm-ring")]
+pub(crate) fn detach_ring(session: ReduxFIFOSession) -> Result<(), Error> {
+    match INSTANCE.detach_ring(session) {
+        // the session closing already closed the ring
+        Ok(()) | Err(Error::InvalidSessionID | Error::InvalidBus) => {}
+        Err(e) => return Err(e),
+    }
+    RINGS
+        .lock()
+        .remove(&session.0)
+        .map(drop)
+        .ok_or(Error::InvalidSessionID)
+}
+
+/// Delivers `session`'s messages to a shared-memory ring of at least `capacity` messages instead
+/// of to read barriers, writing the start of its mapping to `ring`.
+#[cfg(feature = "shm-ring")]
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_AttachRing(
+    session: ReduxFIFOSession,
+    capacity: u32,
+    ring: *mut *mut ShmRingHeader,
+) -> ReduxFIFOStatus {
+    let Some(ring) = (unsafe { ring.as_mut() }) else {
+        return Err(Error::NullArgument).into();
+    };
+    attach_ring(session, capacity)
+        .map(|attached| *ring = attached.as_ptr() as *mut ShmRingHeader)
+        .into()
+}
+
+/// Closes and unmaps `session`'s ring. The session, if still open, goes back to read barriers.
+#[cfg(feature = "shm-ring")]
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_DetachRing(session: ReduxFIFOSession) -> ReduxFIFOStatus {
+    detach_ring(session).into()
+}
+
+fn wait_for_threshold(
+    session: ReduxFIFOSession,
+    threshold: u32,
+    timeout_ms: u64,
+    token: Option<&CancelToken>,
+    msg_count: *mut u32,
 ) -> ReduxFIFOStatus {
     let msg_count = unsafe { msg_count.as_mut() };
     let mut notifier = match INSTANCE.rx_notifier(session) {
@@ -237,25 +617,196 @@ extern "C" fn ReduxFIFO_WaitForThreshold(
             return Err(e).into();
         }
     };
+    let token = token.cloned().unwrap_or_default();
 
     INSTANCE
         .runtime()
-        .block_on((async move || match tokio::time::timeout(
-            Duration::from_millis(timeout_ms.into()),
-            notifier.wait_for(|size| *size > threshold),
-        )
-        .await
-        {
-            Ok(Ok(p)) => {
-                msg_count.map(|r| {
-                    *r = *p;
-                });
-                drop(p);
-
-                Ok(())
+        .block_on(async move {
+            match token
+                .run(tokio::time::timeout(
+                    Duration::from_millis(timeout_ms),
+                    notifier.wait_for(|size| *size > threshold),
+                ))
+                .await?
+            {
+                Ok(Ok(p)) => {
+                    if let Some(r) = msg_count {
+                        *r = *p;
+                    }
+                    drop(p);
+
+                    Ok(())
+                }
+                Ok(Err(_)) => Err(Error::InvalidSessionID),
+                Err(_) => Err(Error::MessageReceiveTimeout),
             }
-            Ok(Err(_)) => Err(Error::InvalidSessionID),
-            Err(_) => Err(Error::MessageReceiveTimeout),
-        })())
+        })
         .into()
 }
+
+/// Sends the FRC global disable frame on every bus in `bus_ids`, then watches them for
+/// `window_ms` (250 if 0) to check that nothing re-enabled the actuators.
+///
+/// `results`, if not null, gets one entry per bus. Returns the first bus's error, if any.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_GlobalDisable(
+    bus_ids: *const u16,
+    bus_count: libc::size_t,
+    window_ms: u32,
+    results: *mut ReduxFIFOGlobalDisableResultFFI,
+) -> ReduxFIFOStatus {
+    if bus_ids.is_null() {
+        return Err(Error::NullArgument).into();
+    }
+    let bus_ids = unsafe { core::slice::from_raw_parts(bus_ids, bus_count) };
+    let window = match window_ms {
+        0 => fifocore::estop::DEFAULT_CONFIRM_WINDOW,
+        ms => Duration::from_millis(ms.into()),
+    };
+    let reports = INSTANCE
+        .runtime()
+        .block_on(INSTANCE.global_disable(bus_ids, window));
+
+    if !results.is_null() {
+        let results = unsafe { core::slice::from_raw_parts_mut(results, bus_count) };
+        for ((result, report), &bus_id) in results.iter_mut().zip(reports.iter()).zip(bus_ids) {
+            *result = match report {
+                Ok(report) => ReduxFIFOGlobalDisableResultFFI {
+                    bus_id,
+                    confirmed: report.confirmed as u8,
+                    reserved: 0,
+                    status: Ok(()).into(),
+                    enabling_frames: report.enabling_frames,
+                    actuators_heard: report.actuators.len() as u32,
+                    tx_timestamp: report.tx_timestamp.unwrap_or(0),
+                },
+                Err(e) => ReduxFIFOGlobalDisableResultFFI {
+                    bus_id,
+                    confirmed: 0,
+                    reserved: 0,
+                    status: Err(*e).into(),
+                    enabling_frames: 0,
+                    actuators_heard: 0,
+                    tx_timestamp: 0,
+                },
+            };
+        }
+    }
+    reports
+        .into_iter()
+        .find_map(Result::err)
+        .map_or(Ok(()), Err)
+        .into()
+}
+
+/// `acknowledge` for [`ReduxFIFO_StartBenchHeartbeat`], "BNCH" in ASCII.
+const BENCH_HEARTBEAT_ACK: u32 = 0x424e_4348;
+
+/// Stand-in heartbeats started with [`ReduxFIFO_StartBenchHeartbeat`], by bus.
+static BENCH_HEARTBEATS: std::sync::LazyLock<
+    parking_lot::Mutex<std::collections::HashMap<u16, BenchHeartbeat>>,
+> = std::sync::LazyLock::new(Default::default);
+
+/// Starts a stand-in roboRIO heartbeat on `bus_id`, disabled until
+/// [`ReduxFIFO_SetBenchHeartbeatEnabled`]; see [`BenchHeartbeat`]. `acknowledge` has to be
+/// [`BENCH_HEARTBEAT_ACK`]. Blocks for a heartbeat timeout while listening for a roboRIO.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_StartBenchHeartbeat(
+    bus_id: u16,
+    watchdog_ms: u32,
+    acknowledge: u32,
+) -> ReduxFIFOStatus {
+    if acknowledge != BENCH_HEARTBEAT_ACK {
+        return Err(Error::NotAcknowledged).into();
+    }
+    // the old heartbeat would be heard as a roboRIO
+    BENCH_HEARTBEATS.lock().remove(&bus_id);
+    INSTANCE
+        .runtime()
+        .block_on(BenchHeartbeat::start(
+            INSTANCE.clone(),
+            bus_id,
+            Duration::from_millis(watchdog_ms.into()),
+            NotOnARobot::i_understand_this_enables_actuators(),
+        ))
+        .map(|heartbeat| {
+            BENCH_HEARTBEATS.lock().insert(bus_id, heartbeat);
+        })
+        .into()
+}
+
+/// Enables (nonzero) or disables actuators on a bus with a bench heartbeat. Enabling lasts for the
+/// watchdog timeout, unless fed with [`ReduxFIFO_FeedBenchHeartbeat`].
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_SetBenchHeartbeatEnabled(bus_id: u16, enabled: u8) -> ReduxFIFOStatus {
+    with_bench_heartbeat(bus_id, |heartbeat| match enabled {
+        0 => heartbeat.disable(),
+        _ => heartbeat.enable(),
+    })
+}
+
+/// Keeps actuators on a bus with a bench heartbeat enabled for another watchdog timeout.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_FeedBenchHeartbeat(bus_id: u16) -> ReduxFIFOStatus {
+    with_bench_heartbeat(bus_id, BenchHeartbeat::feed)
+}
+
+/// Stops the bench heartbeat on `bus_id`.
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_StopBenchHeartbeat(bus_id: u16) -> ReduxFIFOStatus {
+    match BENCH_HEARTBEATS.lock().remove(&bus_id) {
+        Some(_) => Ok(()),
+        None => Err(Error::InvalidBus),
+    }
+    .into()
+}
+
+fn with_bench_heartbeat(bus_id: u16, f: impl FnOnce(&BenchHeartbeat)) -> ReduxFIFOStatus {
+    let mut heartbeats = BENCH_HEARTBEATS.lock();
+    let Some(heartbeat) = heartbeats.get(&bus_id) else {
+        return Err(Error::InvalidBus).into();
+    };
+    let status = heartbeat.status().map(|()| f(heartbeat));
+    if status.is_err() {
+        heartbeats.remove(&bus_id);
+    }
+    status.into()
+}
+
+/// Starts a tokio-console endpoint on `addr` (`"127.0.0.1:6669"` if null), moving it if it's
+/// already running; see [`canandmiddleware::diagnostics`]. Nothing stops a non-loopback address
+/// here, so be sure it's wanted: the endpoint has no authentication.
+///
+/// Return 0 on success, -1 if this build can't run tokio-console, -2 on an invalid address, -3 if
+/// the address couldn't be listened on
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_StartTokioConsole(addr: *const libc::c_char) -> i32 {
+    use canandmiddleware::diagnostics::{DEFAULT_CONSOLE_ADDR, DiagnosticsError, start_console};
+    let addr = match addr.is_null() {
+        true => DEFAULT_CONSOLE_ADDR,
+        false => match unsafe { CStr::from_ptr(addr) }.to_str().map(str::parse) {
+            Ok(Ok(addr)) => addr,
+            _ => return -2,
+        },
+    };
+    match start_console(&INSTANCE.runtime(), addr) {
+        Ok(_) => 0,
+        Err(DiagnosticsError::Bind(..)) => -3,
+        Err(_) => -1,
+    }
+}
+
+/// Stops the tokio-console endpoint.
+///
+/// Return 0 on success, -1 if it wasn't running
+#[unsafe(no_mangle)]
+extern "C" fn ReduxFIFO_StopTokioConsole() -> i32 {
+    use canandmiddleware::diagnostics::{console_status, stop_console};
+    match console_status().running {
+        true => {
+            stop_console();
+            0
+        }
+        false => -1,
+    }
+}
@@ -4,7 +4,7 @@
 /// this file is somehow even harder to look at than the c++ version
 use jni::{
     objects::{JByteBuffer, JClass, JObjectArray, JString},
-    sys::{jint, jlong, jsize},
+    sys::{jboolean, jint, jlong, jsize, JNI_FALSE, JNI_TRUE},
     JNIEnv,
 };
 
@@ -84,6 +84,65 @@ pub extern "system" fn Java_com_reduxrobotics_canand_ReduxFIFOJNI_openBus<'local
     }
 }
 
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_reduxrobotics_canand_ReduxFIFOJNI_setBusAlias<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    alias: JString<'local>,
+    bus_address: JString<'local>,
+) -> jint {
+    let strings = env.get_string(&alias).map(String::from).and_then(|alias| {
+        env.get_string(&bus_address)
+            .map(|params| (alias, String::from(params)))
+    });
+    let (alias, bus_string) = match strings {
+        Ok(strings) => strings,
+        Err(e) => {
+            env.throw_new(
+                "java/lang/IllegalArgumentException",
+                format!("Could not read bus alias: {e}"),
+            )
+            .ok();
+            return -1;
+        }
+    };
+    match INSTANCE.set_bus_alias(&alias, &bus_string) {
+        Ok(()) => 0,
+        Err(err) => {
+            env.throw_new(
+                REDUXFIFO_EXCEPTION,
+                format!("Failed to alias {alias} to {bus_string}: {err}"),
+            )
+            .ok();
+            err as jint
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_reduxrobotics_canand_ReduxFIFOJNI_removeBusAlias<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    alias: JString<'local>,
+) -> jboolean {
+    let alias: String = match env.get_string(&alias) {
+        Ok(js) => js.into(),
+        Err(e) => {
+            env.throw_new(
+                "java/lang/IllegalArgumentException",
+                format!("Could not read bus alias: {e}"),
+            )
+            .ok();
+            return JNI_FALSE;
+        }
+    };
+    if INSTANCE.remove_bus_alias(&alias) {
+        JNI_TRUE
+    } else {
+        JNI_FALSE
+    }
+}
+
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_reduxrobotics_canand_ReduxFIFOJNI_closeBus<'local>(
     mut env: JNIEnv<'local>,
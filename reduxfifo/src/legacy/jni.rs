@@ -12,6 +12,11 @@ use crate::{
     subsystems::repeater::Repeater,
 };
 use fifocore::ReduxFIFOVersion;
+#[cfg(feature = "shm-ring")]
+use fifocore::{
+    ReduxFIFOSession, ReduxFIFOSessionConfig,
+    shm::{RING_MAGIC, ShmRingHeader},
+};
 use jni::{
     JNIEnv,
     objects::{JByteArray, JByteBuffer, JClass, JString},
@@ -379,6 +384,74 @@ pub extern "system" fn Java_com_reduxrobotics_canand_ReduxJNI_closeLog<'local>(
     unsafe { ReduxCore_CloseLog(bus_id as u16) }
 }
 
+/// Opens a session on `bus_id` delivering to a shared-memory ring of at least `capacity` messages,
+/// returned as a direct buffer over the whole mapping. See `ReduxFIFO_RingHeader` in ReduxFIFO.h
+/// for its layout; close it with `closeRing`.
+#[cfg(feature = "shm-ring")]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_reduxrobotics_canand_ReduxJNI_openRing<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    bus_id: jint,
+    capacity: jint,
+    filter_id: jint,
+    filter_mask: jint,
+) -> JByteBuffer<'local> {
+    let config = ReduxFIFOSessionConfig::new(filter_id as u32, filter_mask as u32);
+    let Ok(session) = joever(&mut env, |_| {
+        crate::INSTANCE.open_session(bus_id as u16, 1, config)
+    }) else {
+        return JByteBuffer::default();
+    };
+    let Ok(ring) = joever(&mut env, |_| {
+        crate::ffi::attach_ring(session, capacity.max(0) as u32)
+    }) else {
+        let _ = crate::INSTANCE.close_session(session);
+        return JByteBuffer::default();
+    };
+    // SAFETY: the ring stays mapped until closeRing
+    match unsafe { env.new_direct_byte_buffer(ring.as_ptr(), ring.map_len()) } {
+        Ok(bbuf) => bbuf,
+        Err(e) => {
+            let _ = env.throw_new(REDUXJNI_EXCEPTION, format!("ReduxFIFO Error: {e}"));
+            let _ = crate::ffi::detach_ring(session);
+            let _ = crate::INSTANCE.close_session(session);
+            JByteBuffer::default()
+        }
+    }
+}
+
+/// Closes a ring from `openRing` and its session. The buffer must not be touched afterwards.
+#[cfg(feature = "shm-ring")]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_reduxrobotics_canand_ReduxJNI_closeRing<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    buffer: JByteBuffer<'local>,
+) {
+    let Ok(capacity) = joever(&mut env, |env| env.get_direct_buffer_capacity(&buffer)) else {
+        return;
+    };
+    if capacity < core::mem::size_of::<ShmRingHeader>() {
+        let _ = env.throw_new(REDUXJNI_EXCEPTION, "This buffer isn't a ring");
+        return;
+    }
+    let Ok(buffer_addr) = joever(&mut env, |env| env.get_direct_buffer_address(&buffer)) else {
+        return;
+    };
+    let header = unsafe { &*(buffer_addr as *const ShmRingHeader) };
+    if header.magic != RING_MAGIC {
+        let _ = env.throw_new(REDUXJNI_EXCEPTION, "This buffer isn't a ring");
+        return;
+    }
+    let session = ReduxFIFOSession(header.session);
+    // unmaps the buffer, so nothing may read the header after this
+    if joever(&mut env, |_| crate::ffi::detach_ring(session)).is_err() {
+        return;
+    }
+    let _ = crate::INSTANCE.close_session(session);
+}
+
 const fn right_pad<V: Copy, const S: usize, const T: usize>(a: [V; S], pad: V) -> [V; T] {
     assert!(S <= T);
     let mut value = [pad; T];
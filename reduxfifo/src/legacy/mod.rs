@@ -1,12 +1,12 @@
 //! Legacy Redux driver polyfill
 
-use std::{ffi::CStr, time::Duration};
+use std::{ffi::CStr, sync::LazyLock, time::Duration};
 
 use parking_lot::{Condvar, Mutex};
 
 use crate::subsystems::repeater::Repeater;
 use crate::{INSTANCE, log_debug};
-use fifocore::{ReduxFIFOMessage, ReduxFIFOVersion, WriteBuffer};
+use fifocore::{ReduxFIFOMessage, ReduxFIFOVersion, WriteBuffer, trace_tag::TraceTag};
 use tokio::{
     sync::{
         mpsc::{self, Receiver as TokioMPSCReceiver},
@@ -36,6 +36,10 @@ pub(crate) struct ReduxCoreSession {
 
 pub(crate) static REDUXCORE: Mutex<Option<ReduxCoreSession>> = Mutex::new(None);
 
+/// What the vendordep's writes are attributed to in bus logs.
+static VENDORDEP_TAG: LazyLock<TraceTag> =
+    LazyLock::new(|| TraceTag::intern("vendordep").unwrap_or_default());
+
 const REDUXCORE_OK: i32 = 0;
 const REDUXCORE_FAIL: i32 = -1;
 
@@ -76,6 +80,7 @@ pub extern "C" fn ReduxCore_InitServer() -> i32 {
             .spawn(canandmiddleware::rest_server::run_web_server(
                 sd_recv,
                 INSTANCE.clone(),
+                Default::default(),
             ));
         *canlink_handle = Some(ReduxCoreSession {
             bus_task,
@@ -116,14 +121,14 @@ pub extern "C" fn ReduxCore_EnqueueCANMessage(
     data_size: u8,
 ) -> i32 {
     let data_slice = unsafe { core::slice::from_raw_parts(data, data_size as usize) };
-    let size = (data_size as usize).min(64);
-    let mut data_64 = [0_u8; 64];
-    data_64[..size].copy_from_slice(&data_slice[..size]);
-
-    let msg = ReduxFIFOMessage::id_data(can_bus_id, message_id, data_64, size as u8, 0);
+    let msg = ReduxFIFOMessage::builder()
+        .bus(can_bus_id)
+        .id(message_id)
+        .data(data_slice)
+        .build();
     let mut ctr = 10;
     loop {
-        let result = INSTANCE.write_single(&msg);
+        let result = INSTANCE.write_single_tagged(&msg, *VENDORDEP_TAG);
         let Err(e) = result else {
             return fifocore::error::REDUXFIFO_OK;
         };
@@ -163,7 +168,7 @@ pub extern "C" fn ReduxCore_BatchEnqueueCANMessages(
         return 0;
     };
     let bus_id = msg0.bus_id;
-    let mut write_buffer = WriteBuffer::new(bus_id, Vec::from(msg_slice));
+    let mut write_buffer = WriteBuffer::new(bus_id, Vec::from(msg_slice)).with_tag(*VENDORDEP_TAG);
     INSTANCE.write_barrier(core::array::from_mut(&mut write_buffer));
 
     unsafe {
@@ -206,6 +211,7 @@ pub extern "C" fn ReduxCore_BatchWaitForCANMessages(
         core::slice::from_raw_parts_mut(messages, message_count)
     };
     messages_slice[..read_count].copy_from_slice(&msg_buf[..read_count]);
+    fifocore::latency::record(&msg_buf[..read_count]);
 
     if read_count == 0 {
         REDUXCORE_FAIL // the pipe has been closed.
@@ -236,6 +242,7 @@ pub extern "C" fn ReduxCore_WaitForCANMessage(msg_buf: *mut ReduxFIFOMessage) ->
             unsafe {
                 *msg_buf = msg;
             }
+            fifocore::latency::record([&msg]);
             REDUXCORE_OK
         }
         None => REDUXCORE_FAIL,
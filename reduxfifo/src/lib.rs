@@ -13,6 +13,13 @@ mod ffi;
 /// Native-acceleration for specific vendordep-facing tasks
 pub mod subsystems;
 
+/// Typed async API to Redux devices, for Rust programs
+pub mod client;
+
+/// Cargo features this build has, and checks against unsupported combinations
+pub mod capabilities;
+pub use capabilities::capabilities;
+
 #[cfg(feature = "legacy-driver")]
 pub mod legacy;
 
@@ -24,11 +31,8 @@ use fifocore::FIFOCore;
 
 #[cfg(feature = "singleton")]
 static RUNTIME: std::sync::LazyLock<tokio::runtime::Runtime> = std::sync::LazyLock::new(|| {
-    #[cfg(feature = "tokio-console")]
-    console_subscriber::ConsoleLayer::builder()
-        .with_default_env()
-        .server_addr((std::net::Ipv4Addr::UNSPECIFIED, 6669))
-        .init();
+    // the console itself stays off until started over REST or FFI
+    canandmiddleware::diagnostics::install();
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .thread_name("ReduxFIFO")
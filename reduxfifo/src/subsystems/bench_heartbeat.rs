@@ -0,0 +1,231 @@
+//! Stand-in roboRIO heartbeat, for running devices on a bench.
+//!
+//! Off a robot nothing sends the roboRIO heartbeat, so devices that drive or sit next to actuators
+//! stay disabled. [`BenchHeartbeat`] sends one in its place on a single bus. Since enabling it
+//! enables every actuator on the bus, it takes some convincing:
+//!
+//! * starting it takes a [`NotOnARobot`] acknowledgement, and is logged as a warning
+//! * it won't start on a bus where a roboRIO is heard, and stops for good if one shows up later
+//! * it sends a disabled heartbeat until [`BenchHeartbeat::enable`], and goes back to disabled on
+//!   [`BenchHeartbeat::disable`] or when it isn't [fed](BenchHeartbeat::feed) within its watchdog
+//!   timeout, after which only another `enable` turns it back on
+//!
+//! Dropping it stops the heartbeat, after which devices disable themselves within a heartbeat
+//! timeout.
+
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use fifocore::{FIFOCore, ReduxFIFOMessage, ReduxFIFOSessionConfig, Session, error::Error};
+use frc_can_id::{FRCCanHeartbeat, HEARTBEAT_ID, HEARTBEAT_TIMEOUT_US};
+use tokio::{sync::watch, task::JoinHandle, time::Instant};
+
+use crate::{log_info, log_warn};
+
+/// How often the heartbeat is sent, as often as a roboRIO sends its own.
+pub const HEARTBEAT_PERIOD: Duration = Duration::from_millis(20);
+
+/// Bits of the heartbeat that enable actuators: enabled, and the system watchdog.
+const ENABLED_HEARTBEAT: u64 = 1 << 25 | 1 << 28;
+
+/// Acknowledges that a stand-in heartbeat enables every actuator on its bus.
+#[derive(Debug, Clone, Copy)]
+pub struct NotOnARobot(());
+
+impl NotOnARobot {
+    /// The bus has no roboRIO on it, and everything on it is safe to enable.
+    pub const fn i_understand_this_enables_actuators() -> Self {
+        Self(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Control {
+    enabled: bool,
+    /// When enabling lapses unless fed again
+    deadline: Instant,
+}
+
+impl Control {
+    fn live(&self, now: Instant) -> bool {
+        self.enabled && now < self.deadline
+    }
+}
+
+/// A stand-in roboRIO heartbeat on one bus; see the module docs.
+pub struct BenchHeartbeat {
+    bus_id: u16,
+    watchdog: Duration,
+    control: watch::Sender<Control>,
+    /// Why the task stopped on its own
+    stopped: Arc<OnceLock<Error>>,
+    handle: JoinHandle<()>,
+}
+
+impl Drop for BenchHeartbeat {
+    fn drop(&mut self) {
+        self.handle.abort();
+        log_info!("Bench heartbeat stopped on bus {}", self.bus_id);
+    }
+}
+
+impl BenchHeartbeat {
+    /// Starts sending a disabled heartbeat on `bus_id`, after listening for a roboRIO for a
+    /// heartbeat timeout. Once enabled, it stays enabled for `watchdog` after each feed.
+    ///
+    /// Fails with [`Error::RoboRioPresent`] if a roboRIO heartbeat was heard.
+    pub async fn start(
+        fifocore: FIFOCore,
+        bus_id: u16,
+        watchdog: Duration,
+        _ack: NotOnARobot,
+    ) -> Result<Self, Error> {
+        let config = ReduxFIFOSessionConfig::new(HEARTBEAT_ID, 0x1fff_ffff);
+        let session = fifocore.open_managed_session(bus_id, 8, config)?;
+        let _ = session.set_label("bench heartbeat");
+        // our own heartbeat isn't echoed back to the session, so only a real roboRIO shows up
+        let mut notifier = session.rx_notifier()?;
+        let wait = Duration::from_micros(HEARTBEAT_TIMEOUT_US);
+        match tokio::time::timeout(wait, notifier.wait_for(|size| *size > 0)).await {
+            Ok(Ok(_)) => {
+                log_warn!("Bench heartbeat not started on bus {bus_id}: a roboRIO is on it");
+                return Err(Error::RoboRioPresent);
+            }
+            Ok(Err(_)) => return Err(Error::BusClosed),
+            Err(_) => {}
+        }
+
+        log_warn!(
+            "BENCH HEARTBEAT: sending a stand-in roboRIO heartbeat on bus {bus_id}; enabling it \
+             enables every actuator on the bus"
+        );
+        let (control, watcher) = watch::channel(Control {
+            enabled: false,
+            deadline: Instant::now(),
+        });
+        let stopped = Arc::new(OnceLock::new());
+        let handle = fifocore.runtime().spawn(run_heartbeat(
+            session,
+            notifier,
+            watcher,
+            Arc::clone(&stopped),
+        ));
+        Ok(Self {
+            bus_id,
+            watchdog,
+            control,
+            stopped,
+            handle,
+        })
+    }
+
+    pub fn bus_id(&self) -> u16 {
+        self.bus_id
+    }
+
+    /// Enables actuators for the next watchdog timeout.
+    pub fn enable(&self) {
+        log_warn!("BENCH HEARTBEAT: actuators on bus {} enabled", self.bus_id);
+        self.control.send_replace(Control {
+            enabled: true,
+            deadline: Instant::now() + self.watchdog,
+        });
+    }
+
+    /// Disables actuators, with a disabled heartbeat sent right away.
+    pub fn disable(&self) {
+        self.control.send_if_modified(|control| {
+            let was_enabled = control.enabled;
+            control.enabled = false;
+            was_enabled
+        });
+    }
+
+    /// Keeps actuators enabled for another watchdog timeout. Does nothing once the watchdog has
+    /// run out; that takes [`Self::enable`].
+    pub fn feed(&self) {
+        let now = Instant::now();
+        let deadline = now + self.watchdog;
+        // the task checks the deadline every beat, so it needn't be woken for this
+        self.control.send_if_modified(|control| {
+            if control.live(now) {
+                control.deadline = deadline;
+            }
+            false
+        });
+    }
+
+    /// Whether actuators are enabled: enabled, fed in time, and still running.
+    pub fn enabled(&self) -> bool {
+        self.status().is_ok() && self.control.borrow().live(Instant::now())
+    }
+
+    /// Ok while the heartbeat is being sent. It stops on its own with [`Error::RoboRioPresent`] if
+    /// a roboRIO shows up, or [`Error::BusClosed`] if the bus closes.
+    pub fn status(&self) -> Result<(), Error> {
+        match self.stopped.get() {
+            Some(e) => Err(*e),
+            None => Ok(()),
+        }
+    }
+}
+
+async fn run_heartbeat(
+    session: Session,
+    mut notifier: watch::Receiver<u32>,
+    mut watcher: watch::Receiver<Control>,
+    stopped: Arc<OnceLock<Error>>,
+) {
+    let bus_id = session.session().bus_id();
+    let mut interval = tokio::time::interval(HEARTBEAT_PERIOD);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut was_live = false;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            changed = watcher.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                // a disable goes out right away, anything else with the next beat
+                let enabled = watcher.borrow_and_update().enabled;
+                if enabled || !was_live {
+                    continue;
+                }
+            }
+            heard = notifier.wait_for(|size| *size > 0) => {
+                let reason = match heard.is_ok() {
+                    true => {
+                        log_warn!("Bench heartbeat stopped on bus {bus_id}: a roboRIO showed up");
+                        Error::RoboRioPresent
+                    }
+                    false => {
+                        log_info!("Bench heartbeat stopped on bus {bus_id}: the bus closed");
+                        Error::BusClosed
+                    }
+                };
+                let _ = stopped.set(reason);
+                return;
+            }
+        }
+
+        let control = *watcher.borrow();
+        let live = control.live(Instant::now());
+        if was_live && !live && control.enabled {
+            log_warn!("Bench heartbeat watchdog ran out; actuators on bus {bus_id} disabled");
+        }
+        was_live = live;
+
+        let bits = if live { ENABLED_HEARTBEAT } else { 0 };
+        let msg = ReduxFIFOMessage::builder()
+            .bus(bus_id)
+            .id(HEARTBEAT_ID)
+            .data(&FRCCanHeartbeat::new(bits.to_be_bytes()).data())
+            .fd(false)
+            .build();
+        // a missed beat is ridden out by the devices
+        let _ = session.write_single(&msg);
+    }
+}
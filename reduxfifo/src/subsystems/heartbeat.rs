@@ -0,0 +1,312 @@
+//! Robot state from the roboRIO heartbeat.
+//!
+//! [`HeartbeatMonitor`] listens to the heartbeat on one bus and keeps the latest [`RobotState`]:
+//! whether actuators are enabled, autonomous or test mode, and the match time. Heartbeats are
+//! judged by a [`HeartbeatTracker`], the same as devices judge them, so a roboRIO that goes quiet
+//! reads as disabled within [`HEARTBEAT_TIMEOUT_US`] even if its last heartbeat said enabled.
+//!
+//! Consumers that care about enabling and disabling, rather than every heartbeat, can wait on
+//! [`HeartbeatMonitor::enabled_watch`] or register a callback with
+//! [`HeartbeatMonitor::on_transition`].
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use fifocore::{FIFOCore, ReduxFIFOSessionConfig, Session, error::Error};
+use frc_can_id::{FRCCanHeartbeat, HEARTBEAT_ID, HEARTBEAT_TIMEOUT_US, HeartbeatTracker};
+use parking_lot::Mutex;
+use tokio::{sync::watch, task::JoinHandle, time::Instant};
+
+use crate::log_info;
+
+/// What the latest heartbeat says about the robot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RobotState {
+    /// Latest heartbeat, unless it's timed out
+    pub heartbeat: Option<FRCCanHeartbeat>,
+    /// A heartbeat was heard and hasn't been followed by another within the timeout
+    pub timed_out: bool,
+}
+
+impl RobotState {
+    /// Actuators may be energized: a current heartbeat with the system watchdog set.
+    pub fn enabled(&self) -> bool {
+        self.heartbeat.is_some_and(|hb| hb.system_watchdog())
+    }
+
+    pub fn autonomous(&self) -> bool {
+        self.heartbeat.is_some_and(|hb| hb.autonomous())
+    }
+
+    pub fn test_mode(&self) -> bool {
+        self.heartbeat.is_some_and(|hb| hb.test_mode())
+    }
+
+    /// Match time in seconds, as the driver station reports it.
+    pub fn match_time_seconds(&self) -> Option<u8> {
+        self.heartbeat.map(|hb| hb.match_time_seconds())
+    }
+}
+
+/// An enable or disable edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Enabled,
+    /// `timed_out` if the heartbeat stopped rather than saying disabled
+    Disabled {
+        timed_out: bool,
+    },
+}
+
+/// Identifies a callback registered with [`HeartbeatMonitor::on_transition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CallbackId(u64);
+
+type Callbacks = Arc<Mutex<Vec<(CallbackId, Box<dyn FnMut(Transition) + Send>)>>>;
+
+/// Monitors the roboRIO heartbeat on one bus; see the module docs.
+pub struct HeartbeatMonitor {
+    bus_id: u16,
+    state: watch::Receiver<RobotState>,
+    enabled: watch::Receiver<bool>,
+    callbacks: Callbacks,
+    next_callback: AtomicU64,
+    handle: JoinHandle<()>,
+}
+
+impl Drop for HeartbeatMonitor {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl HeartbeatMonitor {
+    /// Starts listening for heartbeats on `bus_id`. Until one arrives the robot reads as
+    /// disabled.
+    pub fn start(fifocore: &FIFOCore, bus_id: u16) -> Result<Self, Error> {
+        let config = ReduxFIFOSessionConfig::new(HEARTBEAT_ID, 0x1fff_ffff);
+        let session = fifocore.open_managed_session(bus_id, 8, config)?;
+        let _ = session.set_label("heartbeat monitor");
+        let notifier = session.rx_notifier()?;
+
+        let (state_send, state) = watch::channel(RobotState::default());
+        let (enabled_send, enabled) = watch::channel(false);
+        let callbacks = Callbacks::default();
+        let handle = fifocore.runtime().spawn(run_monitor(
+            session,
+            notifier,
+            state_send,
+            enabled_send,
+            Arc::clone(&callbacks),
+        ));
+        Ok(Self {
+            bus_id,
+            state,
+            enabled,
+            callbacks,
+            next_callback: AtomicU64::new(0),
+            handle,
+        })
+    }
+
+    pub fn bus_id(&self) -> u16 {
+        self.bus_id
+    }
+
+    pub fn state(&self) -> RobotState {
+        *self.state.borrow()
+    }
+
+    pub fn enabled(&self) -> bool {
+        *self.enabled.borrow()
+    }
+
+    /// Every change in state, one per heartbeat at most. Closes when the bus does.
+    pub fn subscribe(&self) -> watch::Receiver<RobotState> {
+        self.state.clone()
+    }
+
+    /// Whether actuators are enabled, changing only on enable and disable edges. Closes when the
+    /// bus does.
+    pub fn enabled_watch(&self) -> watch::Receiver<bool> {
+        self.enabled.clone()
+    }
+
+    /// Calls `callback` on every enable and disable edge, from the monitor's task, so it should
+    /// return quickly.
+    pub fn on_transition(&self, callback: impl FnMut(Transition) + Send + 'static) -> CallbackId {
+        let id = CallbackId(self.next_callback.fetch_add(1, Ordering::Relaxed));
+        self.callbacks.lock().push((id, Box::new(callback)));
+        id
+    }
+
+    /// Stops calling a callback. Returns false if it wasn't registered.
+    pub fn remove_callback(&self, id: CallbackId) -> bool {
+        let mut callbacks = self.callbacks.lock();
+        let len = callbacks.len();
+        callbacks.retain(|(cb_id, _)| *cb_id != id);
+        callbacks.len() != len
+    }
+}
+
+/// Heartbeat state between frames, apart from the bus so its edges can be checked directly.
+#[derive(Debug, Default)]
+struct Edges {
+    tracker: HeartbeatTracker,
+    enabled: bool,
+}
+
+impl Edges {
+    /// Records a heartbeat frame heard at `now_us`. Returns false if it wasn't one.
+    fn ingest(&mut self, id: u32, data: &[u8], now_us: u64) -> bool {
+        self.tracker.ingest(id, data, now_us)
+    }
+
+    /// The robot's state at `now_us`, and the edge it crossed since the last poll, if any.
+    fn poll(&mut self, now_us: u64) -> (RobotState, Option<Transition>) {
+        let heartbeat = self.tracker.current(now_us);
+        let state = RobotState {
+            heartbeat,
+            timed_out: heartbeat.is_none() && self.tracker.last_heartbeat().is_some(),
+        };
+        let now_enabled = state.enabled();
+        let was_enabled = std::mem::replace(&mut self.enabled, now_enabled);
+        let transition = match (was_enabled, now_enabled) {
+            (false, true) => Some(Transition::Enabled),
+            (true, false) => Some(Transition::Disabled {
+                timed_out: state.timed_out,
+            }),
+            _ => None,
+        };
+        (state, transition)
+    }
+}
+
+async fn run_monitor(
+    session: Session,
+    mut notifier: watch::Receiver<u32>,
+    state: watch::Sender<RobotState>,
+    enabled: watch::Sender<bool>,
+    callbacks: Callbacks,
+) {
+    let bus_id = session.session().bus_id();
+    let mut buffer = session.read_buffer(8);
+    let mut edges = Edges::default();
+    // timed against our own clock, which bus timestamps needn't be on
+    let epoch = Instant::now();
+    let now_us = || epoch.elapsed().as_micros() as u64;
+    let mut expires: Option<Instant> = None;
+    loop {
+        let changed = tokio::select! {
+            ready = notifier.wait_for(|size| *size > 0) => {
+                match ready {
+                    // holding the borrow blocks the bus from delivering more messages
+                    Ok(size) => drop(size),
+                    Err(_) => break,
+                }
+                if session.read_barrier(&mut buffer).is_err() {
+                    break;
+                }
+                let now = now_us();
+                let heard = buffer
+                    .iter()
+                    .fold(false, |heard, msg| edges.ingest(msg.id(), msg.data_slice(), now) | heard);
+                if heard {
+                    expires = Some(epoch + Duration::from_micros(now + HEARTBEAT_TIMEOUT_US));
+                }
+                heard
+            }
+            _ = sleep_until(expires) => {
+                expires = None;
+                true
+            }
+        };
+        if !changed {
+            continue;
+        }
+
+        let (next, transition) = edges.poll(now_us());
+        state.send_replace(next);
+        if let Some(transition) = transition {
+            enabled.send_replace(next.enabled());
+            log_info!("Robot on bus {bus_id}: {transition:?}");
+            for (_, callback) in callbacks.lock().iter_mut() {
+                callback(transition);
+            }
+        }
+    }
+
+    // a closed bus can't be enabling anything
+    state.send_replace(RobotState {
+        heartbeat: None,
+        timed_out: true,
+    });
+    if enabled.send_replace(false) {
+        let transition = Transition::Disabled { timed_out: true };
+        for (_, callback) in callbacks.lock().iter_mut() {
+            callback(transition);
+        }
+    }
+    log_info!("Heartbeat monitor on bus {bus_id} stopped: the bus closed");
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ENABLED: [u8; 8] = [0x39, 0xc7, 0x0e, 0x7d, 0x13, 0x00, 0x00, 0xff];
+    const DISABLED: [u8; 8] = [0xb8, 0x4e, 0x0e, 0xbc, 0x00, 0x00, 0x00, 0xff];
+
+    #[test]
+    fn test_edges() {
+        let mut edges = Edges::default();
+        assert_eq!(edges.poll(0), (RobotState::default(), None));
+        assert!(!edges.ingest(HEARTBEAT_ID + 1, &ENABLED, 0));
+
+        // enabling is one edge, however many enabled heartbeats follow
+        assert!(edges.ingest(HEARTBEAT_ID, &ENABLED, 1_000));
+        let (state, transition) = edges.poll(1_000);
+        assert!(state.enabled() && !state.timed_out);
+        assert_eq!(transition, Some(Transition::Enabled));
+        edges.ingest(HEARTBEAT_ID, &ENABLED, 2_000);
+        assert_eq!(edges.poll(2_000).1, None);
+
+        // a disabled heartbeat disables without timing out
+        edges.ingest(HEARTBEAT_ID, &DISABLED, 3_000);
+        let (state, transition) = edges.poll(3_000);
+        assert!(!state.enabled() && state.heartbeat.is_some());
+        assert_eq!(transition, Some(Transition::Disabled { timed_out: false }));
+        assert_eq!(edges.poll(3_000).1, None);
+
+        // an enabled heartbeat counts until just before the timeout
+        edges.ingest(HEARTBEAT_ID, &ENABLED, 4_000);
+        assert_eq!(edges.poll(4_000).1, Some(Transition::Enabled));
+        let (state, transition) = edges.poll(4_000 + HEARTBEAT_TIMEOUT_US - 1);
+        assert!(state.enabled());
+        assert_eq!(transition, None);
+
+        // and then the robot reads as disabled, once
+        let (state, transition) = edges.poll(4_000 + HEARTBEAT_TIMEOUT_US);
+        assert_eq!(
+            state,
+            RobotState {
+                heartbeat: None,
+                timed_out: true,
+            }
+        );
+        assert_eq!(transition, Some(Transition::Disabled { timed_out: true }));
+        assert_eq!(edges.poll(4_000 + HEARTBEAT_TIMEOUT_US).1, None);
+    }
+}
@@ -1,2 +1,8 @@
 /// Message repeater
 pub mod repeater;
+/// Multi-turn position from a Canandmag's absolute position
+pub mod multiturn;
+/// Stand-in roboRIO heartbeat for bench setups
+pub mod bench_heartbeat;
+/// Robot state from the roboRIO heartbeat, with enable and disable edges
+pub mod heartbeat;
@@ -0,0 +1,274 @@
+//! Continuous multi-turn position from a Canandmag's wrapped absolute position.
+//!
+//! The absolute position only covers one rotation, so every time the shaft passes the zero point
+//! the reading jumps by a full turn. [`MultiTurn`] undoes the jumps by predicting where the shaft
+//! should be from the last position, the velocity and the time since, then picking the whole
+//! number of turns that lands the reading closest to the prediction. Predicting from the frame
+//! timestamps rather than assuming one frame period per reading keeps the count right across
+//! dropped frames, as long as the shaft moved less than half a turn from where it was heading.
+
+use canandmessage::{CanandMessageWrapper, canandmag, traits::MessageIndexId};
+use fifocore::ReduxFIFOMessage;
+use frc_can_id::FRCCanId;
+
+/// Absolute position counts in one rotation.
+pub const COUNTS_PER_ROTATION: i64 = 16384;
+
+/// Default position frame period, in microseconds.
+pub const DEFAULT_FRAME_PERIOD_US: u64 = 20_000;
+
+/// Gaps longer than this many frame periods are too long to trust the velocity across; readings
+/// after one are unwrapped from the last position alone and counted in
+/// [`MultiTurn::uncertain_unwraps`].
+const MAX_PREDICT_PERIODS: u64 = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    timestamp_us: u64,
+    /// Unwrapped position, in counts
+    counts: i64,
+}
+
+/// Reconstructs a continuous position from a stream of wrapped absolute positions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiTurn {
+    frame_period_us: u64,
+    last: Option<Sample>,
+    /// Counts per microsecond over the last interval
+    velocity: f64,
+    /// Added to the unwrapped counts, for [`MultiTurn::set_position`]
+    offset: i64,
+    missed_frames: u64,
+    uncertain_unwraps: u64,
+}
+
+impl Default for MultiTurn {
+    fn default() -> Self {
+        Self::new(DEFAULT_FRAME_PERIOD_US)
+    }
+}
+
+impl MultiTurn {
+    /// Unwraps a stream sent every `frame_period_us`, which should match the device's position
+    /// frame period setting.
+    pub fn new(frame_period_us: u64) -> Self {
+        Self {
+            frame_period_us: frame_period_us.max(1),
+            last: None,
+            velocity: 0.0,
+            offset: 0,
+            missed_frames: 0,
+            uncertain_unwraps: 0,
+        }
+    }
+
+    /// Takes an absolute position reading, in counts of [`COUNTS_PER_ROTATION`], stamped at
+    /// `timestamp_us`. Returns the new position in rotations, or [`None`] if the reading was older
+    /// than the last one and was ignored. The first reading starts within the first rotation.
+    pub fn update(&mut self, absolute: u16, timestamp_us: u64) -> Option<f64> {
+        let raw = absolute as i64 & (COUNTS_PER_ROTATION - 1);
+        let Some(last) = self.last else {
+            self.last = Some(Sample {
+                timestamp_us,
+                counts: raw,
+            });
+            return Some(self.position());
+        };
+        if timestamp_us < last.timestamp_us {
+            return None;
+        }
+        let dt = timestamp_us - last.timestamp_us;
+        let periods = (dt + self.frame_period_us / 2) / self.frame_period_us;
+        self.missed_frames += periods.saturating_sub(1);
+
+        let predicted = if periods > MAX_PREDICT_PERIODS {
+            self.uncertain_unwraps += 1;
+            last.counts as f64
+        } else {
+            last.counts as f64 + self.velocity * dt as f64
+        };
+        let turns = ((predicted - raw as f64) / COUNTS_PER_ROTATION as f64).round() as i64;
+        let counts = raw + turns * COUNTS_PER_ROTATION;
+
+        // repeated frames (same timestamp) keep the last velocity
+        if dt > 0 {
+            self.velocity = (counts - last.counts) as f64 / dt as f64;
+        }
+        self.last = Some(Sample {
+            timestamp_us,
+            counts,
+        });
+        Some(self.position())
+    }
+
+    /// [`MultiTurn::update`] from a Canandmag position frame, using its timestamp. Returns [`None`]
+    /// for any other message. Frames from every Canandmag are taken, so feed it from a session
+    /// filtered to one device.
+    pub fn update_message(&mut self, msg: &ReduxFIFOMessage) -> Option<f64> {
+        let (canandmag::MessageIndex::PositionOutput, _) =
+            canandmag::MessageIndex::from_frc_can_id(&FRCCanId(msg.id()))?
+        else {
+            return None;
+        };
+        let canandmag::Message::PositionOutput {
+            absolute_position, ..
+        } = CanandMessageWrapper(*msg).try_into().ok()?
+        else {
+            return None;
+        };
+        self.update(absolute_position, msg.timestamp)
+    }
+
+    /// Unwrapped position in rotations, 0 before the first reading.
+    pub fn position(&self) -> f64 {
+        let counts = self.last.map_or(0, |last| last.counts) + self.offset;
+        counts as f64 / COUNTS_PER_ROTATION as f64
+    }
+
+    /// Velocity over the last interval, in rotations per second.
+    pub fn velocity(&self) -> f64 {
+        self.velocity * 1_000_000.0 / COUNTS_PER_ROTATION as f64
+    }
+
+    /// Shifts the position so it reads `rotations` now. Keeps the unwrap going.
+    pub fn set_position(&mut self, rotations: f64) {
+        let counts = self.last.map_or(0, |last| last.counts);
+        self.offset = (rotations * COUNTS_PER_ROTATION as f64).round() as i64 - counts;
+    }
+
+    /// Frames that should have arrived between readings but didn't.
+    pub fn missed_frames(&self) -> u64 {
+        self.missed_frames
+    }
+
+    /// Readings after a gap too long to predict across, whose turn count may be off.
+    pub fn uncertain_unwraps(&self) -> u64 {
+        self.uncertain_unwraps
+    }
+
+    /// Forgets everything, so the next reading starts over within the first rotation.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.frame_period_us);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PERIOD: u64 = DEFAULT_FRAME_PERIOD_US;
+
+    /// Feeds `counts` (unwrapped) one frame period apart, returning the last position.
+    fn feed(multiturn: &mut MultiTurn, counts: impl IntoIterator<Item = i64>, t0: u64) -> f64 {
+        let mut position = 0.0;
+        for (i, counts) in counts.into_iter().enumerate() {
+            let absolute = counts.rem_euclid(COUNTS_PER_ROTATION) as u16;
+            position = multiturn.update(absolute, t0 + i as u64 * PERIOD).unwrap();
+        }
+        position
+    }
+
+    fn rotations(counts: i64) -> f64 {
+        counts as f64 / COUNTS_PER_ROTATION as f64
+    }
+
+    #[test]
+    fn test_wrap_forward() {
+        let mut multiturn = MultiTurn::default();
+        let position = feed(&mut multiturn, (0..20).map(|i| 15000 + i * 3000), 0);
+        assert_eq!(position, rotations(15000 + 19 * 3000));
+        assert_eq!(
+            multiturn.velocity(),
+            rotations(3000) * 1_000_000.0 / PERIOD as f64
+        );
+        assert_eq!(multiturn.missed_frames(), 0);
+    }
+
+    #[test]
+    fn test_wrap_backward() {
+        let mut multiturn = MultiTurn::default();
+        let position = feed(&mut multiturn, (0..20).map(|i| 1000 - i * 3000), 0);
+        assert_eq!(position, rotations(1000 - 19 * 3000));
+        assert!(multiturn.velocity() < 0.0);
+    }
+
+    #[test]
+    fn test_dropped_frames() {
+        let mut multiturn = MultiTurn::default();
+        feed(&mut multiturn, [0, 5000, 10000], 0);
+        // three frames dropped: 20000 counts on, more than half a turn, but where it was heading
+        let counts = 10000 + 4 * 5000;
+        let position = multiturn.update((counts % COUNTS_PER_ROTATION) as u16, 6 * PERIOD);
+        assert_eq!(position, Some(rotations(counts)));
+        assert_eq!(multiturn.missed_frames(), 3);
+        assert_eq!(multiturn.uncertain_unwraps(), 0);
+    }
+
+    #[test]
+    fn test_long_gap() {
+        let mut multiturn = MultiTurn::default();
+        feed(&mut multiturn, [0, 5000, 10000], 0);
+        // too long to trust the velocity, so it lands closest to where the shaft last was
+        let t = 2 * PERIOD + (MAX_PREDICT_PERIODS + 1) * PERIOD;
+        assert_eq!(multiturn.update(12000, t), Some(rotations(12000)));
+        assert_eq!(multiturn.uncertain_unwraps(), 1);
+        assert_eq!(
+            multiturn.update(2000, t),
+            Some(rotations(2000 + COUNTS_PER_ROTATION))
+        );
+        assert_eq!(multiturn.missed_frames(), MAX_PREDICT_PERIODS);
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let mut multiturn = MultiTurn::default();
+        feed(&mut multiturn, [0, 1000, 2000], 10 * PERIOD);
+        let before = multiturn.clone();
+        assert_eq!(multiturn.update(16000, 11 * PERIOD), None);
+        assert_eq!(multiturn, before);
+        // a repeat of the last frame reads the same and keeps the velocity
+        assert_eq!(multiturn.update(2000, 12 * PERIOD), Some(rotations(2000)));
+        assert_eq!(multiturn, before);
+    }
+
+    #[test]
+    fn test_set_position() {
+        let mut multiturn = MultiTurn::default();
+        assert_eq!(multiturn.position(), 0.0);
+        feed(&mut multiturn, [4096, 8192], 0);
+        multiturn.set_position(10.0);
+        assert_eq!(multiturn.position(), 10.0);
+        // the unwrap carries on from there, across the wrap
+        let position = feed(&mut multiturn, [12288, 16384, 20480], 2 * PERIOD);
+        assert_eq!(position, 10.75);
+
+        multiturn.reset();
+        assert_eq!(multiturn.position(), 0.0);
+        assert_eq!(multiturn.update(4096, 0), Some(0.25));
+    }
+
+    #[test]
+    fn test_update_message() {
+        let position = |absolute: u16| {
+            let mut data = [0; 6];
+            data[4..].copy_from_slice(&(absolute << 2 | 0b01).to_le_bytes());
+            data
+        };
+        let frame = |index: canandmag::MessageIndex, data: &[u8]| {
+            ReduxFIFOMessage::builder()
+                .id(index.frc_can_id(3).0)
+                .data(data)
+                .timestamp(PERIOD)
+                .build()
+        };
+
+        let mut multiturn = MultiTurn::default();
+        let msg = frame(canandmag::MessageIndex::PositionOutput, &position(4096));
+        assert_eq!(multiturn.update_message(&msg), Some(0.25));
+        let msg = frame(canandmag::MessageIndex::RawPositionOutput, &position(0));
+        assert_eq!(multiturn.update_message(&msg), None);
+        let msg = frame(canandmag::MessageIndex::PositionOutput, &position(0)[..4]);
+        assert_eq!(multiturn.update_message(&msg), None);
+        assert_eq!(multiturn.position(), 0.25);
+    }
+}
@@ -1,8 +1,9 @@
-use std::{time::Duration, u64};
+use std::{sync::Arc, time::Duration};
 
-use tokio::{sync::watch, task::JoinHandle};
+use parking_lot::Mutex;
+use tokio::{sync::watch, task::JoinHandle, time::Instant};
 
-use fifocore::{FIFOCore, ReduxFIFOMessage};
+use fifocore::{FIFOCore, ReduxFIFOMessage, WriteBuffer};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RepeaterState {
@@ -14,8 +15,74 @@ pub struct RepeaterState {
     times: u64,
 }
 
+/// How a [`Repeater`] sends updates that come in between ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateMode {
+    /// Every update is sent as soon as the task sees it, and restarts the period
+    #[default]
+    Immediate,
+    /// Latest value wins: updates wait for the next tick, and of several with the same arbitration
+    /// id only the newest is sent. The tick keeps its phase across updates.
+    Coalesce,
+}
+
+/// Updates a [`Repeater`] was given, how many were never sent because a newer one with the
+/// same arbitration id replaced them before the tick, and how many messages it has written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RepeaterStats {
+    pub updates: u64,
+    /// Always 0 in [`UpdateMode::Immediate`]
+    pub coalesced: u64,
+    pub sent: u64,
+}
+
+/// Replaces the message with the same arbitration id as `message`, or adds it to the end if there
+/// is none. Returns whether one was replaced.
+fn replace_same_id(messages: &mut Vec<ReduxFIFOMessage>, message: ReduxFIFOMessage) -> bool {
+    let same_id = messages
+        .iter_mut()
+        .find(|m| m.bus_id == message.bus_id && m.message_id == message.message_id);
+    match same_id {
+        Some(stale) => {
+            *stale = message;
+            true
+        }
+        None => {
+            messages.push(message);
+            false
+        }
+    }
+}
+
+/// Updates waiting for the next tick of a coalescing repeater.
+#[derive(Debug, Default)]
+struct Pending {
+    /// The newest message for each arbitration id, in the order the ids first came in
+    messages: Vec<ReduxFIFOMessage>,
+    stats: RepeaterStats,
+}
+
+impl Pending {
+    fn push(&mut self, message: ReduxFIFOMessage) {
+        if replace_same_id(&mut self.messages, message) {
+            self.stats.coalesced += 1;
+        }
+    }
+
+    /// Moves the updates since the last tick into `latest`, the newest message for every id the
+    /// repeater has been given, and counts `latest` as sent.
+    fn tick(&mut self, latest: &mut Vec<ReduxFIFOMessage>) {
+        for message in self.messages.drain(..) {
+            replace_same_id(latest, message);
+        }
+        self.stats.sent += latest.len() as u64;
+    }
+}
+
 pub struct Repeater {
     control: watch::Sender<RepeaterState>,
+    mode: UpdateMode,
+    pending: Arc<Mutex<Pending>>,
     handle: JoinHandle<()>,
 }
 
@@ -40,28 +107,72 @@ impl Repeater {
         period: Duration,
         times: u64,
         fifocore: FIFOCore,
+    ) -> Repeater {
+        Self::with_mode(message, period, times, UpdateMode::Immediate, fifocore)
+    }
+
+    pub fn with_mode(
+        message: ReduxFIFOMessage,
+        period: Duration,
+        times: u64,
+        mode: UpdateMode,
+        fifocore: FIFOCore,
     ) -> Repeater {
         let (control, watcher) = watch::channel(RepeaterState {
             message,
             period,
             times,
         });
-        let handle = fifocore
-            .runtime()
-            .spawn(run_repeater(fifocore.clone(), watcher));
-        Repeater { control, handle }
+        let pending = Arc::<Mutex<Pending>>::default();
+        let handle = match mode {
+            UpdateMode::Immediate => fifocore.runtime().spawn(run_repeater(
+                fifocore.clone(),
+                watcher,
+                Arc::clone(&pending),
+            )),
+            UpdateMode::Coalesce => fifocore.runtime().spawn(run_coalescing_repeater(
+                fifocore.clone(),
+                watcher,
+                Arc::clone(&pending),
+            )),
+        };
+        Repeater {
+            control,
+            mode,
+            pending,
+            handle,
+        }
     }
 
     pub fn update(&self, message: ReduxFIFOMessage, period: Duration, times: u64) {
+        {
+            let mut pending = self.pending.lock();
+            pending.stats.updates += 1;
+            if self.mode == UpdateMode::Coalesce && times > 0 {
+                pending.push(message);
+            }
+        }
         self.control.send_replace(RepeaterState {
             message,
             period,
             times,
         });
     }
+
+    pub fn mode(&self) -> UpdateMode {
+        self.mode
+    }
+
+    pub fn stats(&self) -> RepeaterStats {
+        self.pending.lock().stats
+    }
 }
 
-pub async fn run_repeater(fifocore: FIFOCore, mut watcher: watch::Receiver<RepeaterState>) {
+async fn run_repeater(
+    fifocore: FIFOCore,
+    mut watcher: watch::Receiver<RepeaterState>,
+    pending: Arc<Mutex<Pending>>,
+) {
     let mut state = *watcher.borrow_and_update();
     loop {
         tokio::select! {
@@ -77,8 +188,392 @@ pub async fn run_repeater(fifocore: FIFOCore, mut watcher: watch::Receiver<Repea
         }
         if state.times > 0 {
             let _ = fifocore.write_single(&state.message);
+            pending.lock().stats.sent += 1;
         } else {
             state.period = Duration::from_secs(u32::MAX as u64);
         }
     }
 }
+
+/// [`run_repeater`] for [`UpdateMode::Coalesce`]: updates only change what the next tick sends.
+/// Every tick sends the newest message for each arbitration id given since the repeater started.
+async fn run_coalescing_repeater(
+    fifocore: FIFOCore,
+    mut watcher: watch::Receiver<RepeaterState>,
+    pending: Arc<Mutex<Pending>>,
+) {
+    let mut state = *watcher.borrow_and_update();
+    let mut latest = Vec::new();
+    if state.times > 0 {
+        latest.push(state.message);
+    }
+    let mut tick = Instant::now();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep_until(tick + state.period) => {
+                tick += state.period;
+                // a whole period behind starts the phase over rather than catching up in a burst
+                if Instant::now().saturating_duration_since(tick) >= state.period {
+                    tick = Instant::now();
+                }
+            }
+            maybe_state = watcher.changed() => {
+                if maybe_state.is_err() {
+                    return;
+                }
+                state = *watcher.borrow_and_update();
+                // stopping forgets the ids, so a restart only repeats what it's given from then on
+                if state.times == 0 {
+                    latest.clear();
+                }
+                continue;
+            }
+        }
+        if state.times == 0 {
+            pending.lock().messages.clear();
+            continue;
+        }
+        state.times -= 1;
+        pending.lock().tick(&mut latest);
+        for message in latest.iter() {
+            let _ = fifocore.write_single(message);
+        }
+    }
+}
+
+/// Shortest period a [`RepeaterGroup`] runs at.
+pub const MIN_GROUP_PERIOD: Duration = Duration::from_millis(1);
+
+/// A message in a [`RepeaterGroup`], sent `offset` into every period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupEntry {
+    pub message: ReduxFIFOMessage,
+    /// How far into the period to send it. Offsets past the end of the period are sent at its end.
+    pub offset: Duration,
+}
+
+impl GroupEntry {
+    /// Sent right on the tick.
+    pub fn new(message: ReduxFIFOMessage) -> Self {
+        Self {
+            message,
+            offset: Duration::ZERO,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Duration) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GroupState {
+    entries: Vec<GroupEntry>,
+    period: Duration,
+}
+
+/// Sends a set of messages every period, on a common tick, e.g. control frames for several devices
+/// every 10 ms.
+///
+/// Messages with the same offset go out together in one write barrier, so nothing else on the
+/// host gets written in between them. Updates take effect at the start of the next period: a
+/// period is always sent entirely from one set of messages, and the tick keeps its phase, so an
+/// update neither drops nor doubles a cycle. If the runtime falls behind by whole periods, those
+/// are skipped rather than sent in a burst.
+pub struct RepeaterGroup {
+    control: watch::Sender<GroupState>,
+    handle: JoinHandle<()>,
+}
+
+impl Drop for RepeaterGroup {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl RepeaterGroup {
+    /// Starts sending `entries` every `period` (at least [`MIN_GROUP_PERIOD`]), with the first
+    /// tick now.
+    pub fn new(entries: Vec<GroupEntry>, period: Duration, fifocore: FIFOCore) -> RepeaterGroup {
+        let (control, watcher) = watch::channel(GroupState {
+            entries,
+            period: period.max(MIN_GROUP_PERIOD),
+        });
+        let handle = fifocore
+            .runtime()
+            .spawn(run_repeater_group(fifocore.clone(), watcher));
+        RepeaterGroup { control, handle }
+    }
+
+    /// Replaces the messages from the next period on.
+    pub fn update(&self, entries: Vec<GroupEntry>) {
+        self.control.send_modify(|state| state.entries = entries);
+    }
+
+    /// Changes the period from the next tick on. The tick stays where it was; only the one after
+    /// it moves.
+    pub fn set_period(&self, period: Duration) {
+        self.control
+            .send_modify(|state| state.period = period.max(MIN_GROUP_PERIOD));
+    }
+
+    /// Stops sending anything from the next period on, keeping the tick for a later update.
+    pub fn clear(&self) {
+        self.update(Vec::new());
+    }
+
+    pub fn period(&self) -> Duration {
+        self.control.borrow().period
+    }
+}
+
+/// Messages grouped by bus, before they're put in write buffers.
+type MessagesByBus = Vec<(u16, Vec<ReduxFIFOMessage>)>;
+
+/// Messages sent at one offset into the period, a write buffer per bus.
+struct Slot {
+    offset: Duration,
+    buffers: Vec<WriteBuffer>,
+}
+
+fn group_slots(state: &GroupState) -> Vec<Slot> {
+    let mut entries = state.entries.clone();
+    // stable, so messages sharing an offset go out in the order they were given
+    entries.sort_by_key(|entry| entry.offset.min(state.period));
+    let mut slots: Vec<(Duration, MessagesByBus)> = Vec::new();
+    for entry in entries {
+        let offset = entry.offset.min(state.period);
+        if slots.last().is_none_or(|(last, _)| *last != offset) {
+            slots.push((offset, Vec::new()));
+        }
+        let (_, slot) = slots.last_mut().unwrap();
+        let bus_id = entry.message.bus_id;
+        match slot.iter_mut().find(|(id, _)| *id == bus_id) {
+            Some((_, messages)) => messages.push(entry.message),
+            None => slot.push((bus_id, vec![entry.message])),
+        }
+    }
+    slots
+        .into_iter()
+        .map(|(offset, buses)| Slot {
+            offset,
+            buffers: buses
+                .into_iter()
+                .map(|(bus_id, messages)| WriteBuffer::new(bus_id, messages))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Where a [`RepeaterGroup`] is in its periods, and what it sends in them.
+struct GroupSchedule {
+    state: GroupState,
+    slots: Vec<Slot>,
+    /// Start of the current period
+    tick: Instant,
+}
+
+impl GroupSchedule {
+    fn new(state: GroupState, tick: Instant) -> Self {
+        Self {
+            slots: group_slots(&state),
+            state,
+            tick,
+        }
+    }
+
+    /// Moves on to the next period, skipping any the runtime fell whole periods behind on by
+    /// `now`, and sends `update` from it on.
+    fn next_period(&mut self, now: Instant, update: Option<GroupState>) {
+        let period = self.state.period;
+        self.tick += period;
+        let behind = now.saturating_duration_since(self.tick);
+        if behind >= period {
+            self.tick += period * (behind.as_nanos() / period.as_nanos()) as u32;
+        }
+        if let Some(state) = update {
+            self.slots = group_slots(&state);
+            self.state = state;
+        }
+    }
+}
+
+async fn run_repeater_group(fifocore: FIFOCore, mut watcher: watch::Receiver<GroupState>) {
+    let mut schedule = GroupSchedule::new(watcher.borrow_and_update().clone(), Instant::now());
+    loop {
+        let tick = schedule.tick;
+        for slot in schedule.slots.iter_mut() {
+            tokio::time::sleep_until(tick + slot.offset).await;
+            fifocore.write_barrier(&mut slot.buffers);
+        }
+        tokio::time::sleep_until(tick + schedule.state.period).await;
+
+        // only between periods, so none is sent half from the old messages and half from the new
+        let update = match watcher.has_changed() {
+            Ok(true) => Some(watcher.borrow_and_update().clone()),
+            Ok(false) => None,
+            Err(_) => return,
+        };
+        schedule.next_period(Instant::now(), update);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(bus_id: u16, message_id: u32, data: u8) -> ReduxFIFOMessage {
+        ReduxFIFOMessage::builder()
+            .bus(bus_id)
+            .id(message_id)
+            .data(&[data])
+            .build()
+    }
+
+    #[test]
+    fn test_latest_wins() {
+        let mut pending = Pending::default();
+        pending.push(message(0, 1, 1));
+        pending.push(message(0, 2, 1));
+        pending.push(message(0, 1, 2));
+        // same id on another bus is a different message
+        pending.push(message(1, 1, 1));
+        assert_eq!(
+            pending.messages,
+            [message(0, 1, 2), message(0, 2, 1), message(1, 1, 1)]
+        );
+        assert_eq!(pending.stats.coalesced, 1);
+
+        let mut latest = vec![message(0, 3, 1)];
+        pending.tick(&mut latest);
+        assert!(pending.messages.is_empty());
+        let sent = [
+            message(0, 3, 1),
+            message(0, 1, 2),
+            message(0, 2, 1),
+            message(1, 1, 1),
+        ];
+        assert_eq!(latest, sent);
+
+        // a tick without updates sends every id again, not just the last one given
+        pending.tick(&mut latest);
+        assert_eq!(latest, sent);
+
+        pending.push(message(0, 2, 3));
+        pending.tick(&mut latest);
+        assert_eq!(latest[2], message(0, 2, 3));
+        assert_eq!(latest.len(), 4);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut pending = Pending::default();
+        let mut latest = Vec::new();
+        pending.tick(&mut latest);
+        assert_eq!(pending.stats, RepeaterStats::default());
+
+        for data in 0..3 {
+            pending.push(message(0, 1, data));
+        }
+        pending.push(message(0, 2, 0));
+        pending.tick(&mut latest);
+        assert_eq!(pending.stats.coalesced, 2);
+        assert_eq!(pending.stats.sent, 2);
+
+        pending.tick(&mut latest);
+        pending.push(message(0, 2, 1));
+        pending.tick(&mut latest);
+        assert_eq!(pending.stats.coalesced, 2);
+        assert_eq!(pending.stats.sent, 6);
+    }
+
+    /// Each slot's offset, and the (bus, id) of every message in it, per write buffer.
+    fn layout(slots: &mut [Slot]) -> Vec<(Duration, Vec<Vec<(u16, u32)>>)> {
+        slots
+            .iter_mut()
+            .map(|slot| {
+                let buffers = slot
+                    .buffers
+                    .iter_mut()
+                    .map(|buf| {
+                        let messages = buf.messages().iter();
+                        messages.map(|m| (m.bus_id, m.message_id)).collect()
+                    })
+                    .collect();
+                (slot.offset, buffers)
+            })
+            .collect()
+    }
+
+    fn group(entries: &[(u16, u32, u64)], period_ms: u64) -> GroupState {
+        GroupState {
+            entries: entries
+                .iter()
+                .map(|&(bus_id, message_id, offset_ms)| {
+                    GroupEntry::new(message(bus_id, message_id, 0))
+                        .with_offset(Duration::from_millis(offset_ms))
+                })
+                .collect(),
+            period: Duration::from_millis(period_ms),
+        }
+    }
+
+    #[test]
+    fn test_group_slots() {
+        let ms = Duration::from_millis;
+        // sorted by offset, keeping the given order within one
+        let state = group(&[(0, 1, 5), (0, 2, 0), (0, 3, 5), (0, 4, 0)], 10);
+        assert_eq!(
+            layout(&mut group_slots(&state)),
+            [
+                (ms(0), vec![vec![(0, 2), (0, 4)]]),
+                (ms(5), vec![vec![(0, 1), (0, 3)]]),
+            ]
+        );
+
+        // offsets past the period are sent at its end, alongside any right on it
+        let state = group(&[(0, 1, 25), (0, 2, 10), (0, 3, 3)], 10);
+        assert_eq!(
+            layout(&mut group_slots(&state)),
+            [
+                (ms(3), vec![vec![(0, 3)]]),
+                (ms(10), vec![vec![(0, 1), (0, 2)]]),
+            ]
+        );
+
+        // a write buffer per bus in each slot, in the order the buses first come up
+        let state = group(&[(1, 1, 0), (0, 2, 0), (1, 3, 0), (0, 4, 2)], 10);
+        assert_eq!(
+            layout(&mut group_slots(&state)),
+            [
+                (ms(0), vec![vec![(1, 1), (1, 3)], vec![(0, 2)]]),
+                (ms(2), vec![vec![(0, 4)]]),
+            ]
+        );
+
+        assert!(group_slots(&group(&[], 10)).is_empty());
+    }
+
+    #[test]
+    fn test_group_period_boundary() {
+        let ms = Duration::from_millis;
+        let t0 = Instant::now();
+        let mut schedule = GroupSchedule::new(group(&[(0, 1, 0)], 10), t0);
+
+        schedule.next_period(t0 + ms(10), None);
+        assert_eq!(schedule.tick, t0 + ms(10));
+        assert_eq!(layout(&mut schedule.slots), [(ms(0), vec![vec![(0, 1)]])]);
+
+        // whole periods behind are skipped, keeping the phase
+        schedule.next_period(t0 + ms(55), None);
+        assert_eq!(schedule.tick, t0 + ms(50));
+
+        // an update is only picked up at the boundary, which is still a whole old period on
+        schedule.next_period(t0 + ms(60), Some(group(&[(0, 2, 5)], 20)));
+        assert_eq!(schedule.tick, t0 + ms(60));
+        assert_eq!(layout(&mut schedule.slots), [(ms(5), vec![vec![(0, 2)]])]);
+        schedule.next_period(t0 + ms(80), None);
+        assert_eq!(schedule.tick, t0 + ms(80));
+    }
+}